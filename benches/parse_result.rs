@@ -0,0 +1,58 @@
+//! How long reading the pipeline's result takes for wide schemas, the accumulation done once
+//! the server has grouped the sample's paths and types. Run with `cargo bench --features bench`
+
+use bson::{doc, Bson, Document};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+const DOCUMENTS: i64 = 10_000;
+
+/// A result as `schema_pipeline` returns it, for a sample whose documents have `width`
+/// fields, each seen with two types, and most of them in every document
+fn fixture(width: usize) -> Document {
+    let paths = (0..width)
+        .map(|i| format!("group{}.field{}", i / 100, i))
+        .collect::<Vec<_>>();
+    let presence = paths
+        .iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let count = if i % 10 == 0 {
+                DOCUMENTS / 2
+            } else {
+                DOCUMENTS
+            };
+            Bson::Document(doc! { "_id": path, "count": count })
+        })
+        .collect::<Vec<_>>();
+    let types = paths
+        .iter()
+        .map(|path| {
+            Bson::Document(doc! {
+                "_id": path,
+                "types": [
+                    { "type": "string", "count": DOCUMENTS - 10 },
+                    { "type": "null", "count": 10_i64 },
+                ]
+            })
+        })
+        .collect::<Vec<_>>();
+    doc! {
+        "documents": [{ "count": DOCUMENTS }],
+        "presence": presence,
+        "types": types,
+    }
+}
+
+fn parse_result(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse_result");
+    for width in [100, 1_000, 5_000] {
+        let result = fixture(width);
+        group.bench_with_input(BenchmarkId::from_parameter(width), &result, |b, result| {
+            b.iter(|| schema_analyzer::parsed_fields(result).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse_result);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+fn main() {
+    // the gRPC service's code is generated from its protobuf definition
+    #[cfg(feature = "grpc")]
+    {
+        // builds don't need protoc installed
+        std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path().unwrap());
+        println!("cargo:rerun-if-changed=proto/schema_analyzer.proto");
+        tonic_build::compile_protos("proto/schema_analyzer.proto").unwrap();
+    }
+}
@@ -0,0 +1,44 @@
+#ifndef SCHEMA_ANALYZER_H
+#define SCHEMA_ANALYZER_H
+
+/* Generated by cbindgen from src/ffi.rs. Don't edit by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Infer the schema of `len` bytes of BSON documents back to back, as in a mongodump `.bson`
+// file, and return it as a JSON string naming it `ns`, e.g. `shop.orders`. `options_json` is
+// a JSON object of the library's `AnalysisOptions` keys, or null for the defaults.
+//
+// # Safety
+//
+// `data` must point to `len` readable bytes, or be null if `len` is 0. `ns` must be a
+// nul-terminated string, and `options_json` one or null
+char *schema_analyzer_analyze_bson(const uint8_t *data,
+                                   size_t len,
+                                   const char *ns,
+                                   const char *options_json);
+
+// Why the last call on this thread that returned null failed, or null if none has. The
+// string stays valid until the next failing call on the thread; don't free it
+const char *schema_analyzer_last_error(void);
+
+// Free a string returned by this API. Null is ignored.
+//
+// # Safety
+//
+// `s` must have been returned by this API and not freed already
+void schema_analyzer_free_string(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SCHEMA_ANALYZER_H */
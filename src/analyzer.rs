@@ -0,0 +1,1167 @@
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use bson::{doc, Bson, Document};
+use futures::stream::TryStreamExt;
+use mongodb::{
+    error::ErrorKind,
+    options::{
+        AggregateOptions, ClientOptions, Collation, CollectionOptions, CountOptions, FindOptions,
+        Hint, ReadPreference, SelectionCriteria,
+    },
+    results::{CollectionSpecification, CollectionType},
+    Client, Collection, Cursor, Namespace,
+};
+use sha2::{Digest, Sha256};
+use tracing::{debug, info, warn};
+
+use crate::{
+    infer::SchemaBuilder,
+    options::{AnalysisOptions, IndexHint, SampleStrategy, Sampling, Strategy, TypeMode},
+    pipeline::{
+        parse_result, parse_shapes, profile_pipeline, sample_stages, schema_pipeline, Descent,
+        PROFILE_SAMPLE_SIZE,
+    },
+    progress::{Progress, ProgressFn},
+    schema::{
+        condition_presence, CollectionSchema, FieldSchema, FormatVersion, RunMetadata,
+        SampleMethod, Shapes, Timings,
+    },
+    AnalyzerError, DocumentSizes, DocumentWidths, Evolution, FieldNameIssue, FieldSizes, Result,
+    ResultCache, ViewDefinition,
+};
+
+/// The appName connections are told apart by in server logs and `currentOp`, unless the
+/// connection string or the caller names one
+pub const APP_NAME: &str = "schema-analyzer";
+
+/// Name the connections of `options` after `app_name` if given, or else the connection
+/// string's appName, or else [`APP_NAME`]
+pub fn identify_client(options: &mut ClientOptions, app_name: Option<&str>) {
+    match app_name {
+        Some(app_name) => options.app_name = Some(app_name.to_string()),
+        None => {
+            options.app_name.get_or_insert_with(|| APP_NAME.to_string());
+        }
+    }
+}
+
+/// Infers the schema of one collection by running a sampling aggregation against it
+///
+/// ```no_run
+/// # async fn run(client: mongodb::Client) -> schema_analyzer::Result<()> {
+/// use schema_analyzer::SchemaAnalyzer;
+///
+/// let ns = mongodb::Namespace::new("shop", "orders");
+/// let schema = SchemaAnalyzer::new(client, ns).analyze().await?;
+/// println!("{:?}", schema.fields);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct SchemaAnalyzer {
+    pub(crate) client: Client,
+    pub(crate) ns: Namespace,
+    pub(crate) options: AnalysisOptions,
+    /// The definition of the view being analyzed, once it's been found to be one
+    pub(crate) view: Option<ViewDefinition>,
+}
+
+impl SchemaAnalyzer {
+    pub fn new(client: Client, ns: Namespace) -> Self {
+        Self::with_options(client, ns, AnalysisOptions::default())
+    }
+
+    pub fn with_options(client: Client, ns: Namespace, options: AnalysisOptions) -> Self {
+        Self {
+            client,
+            ns,
+            options,
+            view: None,
+        }
+    }
+
+    /// See [`AnalysisOptions::sampling`]
+    pub fn sampling(mut self, sampling: Sampling) -> Self {
+        self.options.sampling = sampling;
+        self
+    }
+
+    /// See [`AnalysisOptions::max_depth`]
+    pub fn max_depth(mut self, max_depth: u32) -> Self {
+        self.options.max_depth = max_depth;
+        self
+    }
+
+    /// See [`AnalysisOptions::comment`]
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = comment.into();
+        self
+    }
+
+    /// See [`AnalysisOptions::filter`]
+    pub fn filter(mut self, filter: Option<Document>) -> Self {
+        self.options.filter = filter;
+        self
+    }
+
+    /// See [`AnalysisOptions::ids`]
+    pub fn ids(mut self, ids: Vec<Bson>) -> Self {
+        self.options.ids = ids;
+        self
+    }
+
+    /// See [`AnalysisOptions::random_key_field`]
+    pub fn random_key_field(mut self, field: Option<String>) -> Self {
+        self.options.random_key_field = field;
+        self
+    }
+
+    /// See [`AnalysisOptions::allow_disk_use`]
+    pub fn allow_disk_use(mut self, allow_disk_use: bool) -> Self {
+        self.options.allow_disk_use = allow_disk_use;
+        self
+    }
+
+    /// See [`AnalysisOptions::max_time`]
+    pub fn max_time(mut self, max_time: Option<Duration>) -> Self {
+        self.options.max_time = max_time;
+        self
+    }
+
+    /// See [`AnalysisOptions::batch_size`]
+    pub fn batch_size(mut self, batch_size: Option<u32>) -> Self {
+        self.options.batch_size = batch_size;
+        self
+    }
+
+    /// See [`AnalysisOptions::progress`]
+    pub fn on_progress(mut self, f: impl Fn(Progress<'_>) + Send + Sync + 'static) -> Self {
+        self.options.progress = Some(ProgressFn::new(f));
+        self
+    }
+
+    /// See [`AnalysisOptions::strategy`]
+    pub fn strategy(mut self, strategy: Strategy) -> Self {
+        self.options.strategy = strategy;
+        self
+    }
+
+    /// See [`AnalysisOptions::read_preference`]
+    pub fn read_preference(mut self, read_preference: Option<ReadPreference>) -> Self {
+        self.options.read_preference = read_preference;
+        self
+    }
+
+    /// See [`AnalysisOptions::cache`]
+    pub fn cache(mut self, cache: Option<ResultCache>) -> Self {
+        self.options.cache = cache;
+        self
+    }
+
+    pub async fn analyze(&self) -> Result<CollectionSchema> {
+        let Some(cache) = &self.options.cache else {
+            return self.sample().await;
+        };
+        if let Some(schema) = cache.get(&self.ns, &self.options) {
+            debug!(namespace = %self.ns, "using a cached result");
+            return Ok(schema);
+        }
+        let schema = self.sample().await?;
+        if let Err(e) = cache.put(&self.ns, &self.options, &schema) {
+            warn!(namespace = %self.ns, "could not cache the result: {}", e);
+        }
+        Ok(schema)
+    }
+
+    /// Sample the collection, or the view as its definition says once it's been looked up
+    async fn sample(&self) -> Result<CollectionSchema> {
+        if let Some(targeted) = self.targeted() {
+            let schema = Box::pin(targeted.sample()).await?;
+            let expected = self.options.distinct_ids().len() as u64;
+            if schema.sample_size < expected {
+                warn!(
+                    namespace = %self.ns,
+                    "{} of the {} ids given weren't found{}",
+                    expected - schema.sample_size,
+                    expected,
+                    if self.options.filter.is_some() { " or don't match the filter" } else { "" }
+                );
+            }
+            return Ok(schema);
+        }
+        let start = Instant::now();
+        let (specification, server_version) =
+            futures::join!(self.specification(), self.server_version());
+        match specification.as_ref().and_then(ViewDefinition::of) {
+            Some(view) => {
+                debug!(namespace = %self.ns, view_on = %view.view_on, "analyzing a view");
+                let view = SchemaAnalyzer {
+                    view: Some(view),
+                    ..self.clone()
+                };
+                view.sample_from(start, specification, server_version).await
+            }
+            None => self.sample_from(start, specification, server_version).await,
+        }
+    }
+
+    async fn sample_from(
+        &self,
+        start: Instant,
+        specification: Option<CollectionSpecification>,
+        server_version: Option<String>,
+    ) -> Result<CollectionSchema> {
+        let collection = self.collection();
+        if let Some(collection) = self.ns.coll.strip_prefix("system.buckets.") {
+            warn!(
+                namespace = %self.ns,
+                "this is the bucket storage of a time-series collection: analyze {}.{} for its \
+                 measurements",
+                self.ns.db, collection
+            );
+        }
+        let exact = counted_exactly(specification.as_ref());
+        let document_count = self.retrying(|| self.count(&collection, exact)).await?;
+        if let Some(ranged) = self.ranged(document_count) {
+            // the range is counted and read whole
+            return Box::pin(ranged.sample_from(start, specification, server_version)).await;
+        }
+        let capped = specification
+            .as_ref()
+            .is_some_and(|specification| specification.options.capped == Some(true));
+        let time_series = specification.and_then(|specification| specification.options.timeseries);
+        let sample_size = self.options.sampling.size(document_count);
+        debug!(namespace = %self.ns, document_count, ?sample_size, "counted documents");
+        if capped && sample_size.is_some() && self.options.sample_strategy == SampleStrategy::Random
+        {
+            info!(
+                namespace = %self.ns,
+                "capped collection: a random sample is mostly documents about to be overwritten, \
+                 sampling the most recent ones may say more about what's written now"
+            );
+        }
+        let pre_query = start.elapsed();
+
+        let sampling = async {
+            // a seeded sample is picked from every document as it's read
+            let seeded = self.seeded_share(sample_size, document_count);
+            let scan_size = if seeded.is_some() { None } else { sample_size };
+            let (strategy, mut cursor) = self
+                .retrying(|| self.open_cursor(&collection, sample_size, scan_size))
+                .await?;
+
+            let query = start.elapsed() - pre_query;
+
+            let mut read_rate = None;
+            let sampled = match strategy {
+                // the pipeline reduces the sample to a single document, so one failing part
+                // way is run again from the start
+                Strategy::Pipeline | Strategy::Auto => match self
+                    .retry(cursor.try_next().await, || async {
+                        self.aggregate_schema(&collection, sample_size)
+                            .await?
+                            .try_next()
+                            .await
+                    })
+                    .await?
+                {
+                    Some(doc) => {
+                        let parsed = parse_result(&doc)?;
+                        Sampled {
+                            documents: parsed.documents,
+                            fields: parsed.fields,
+                            truncated_documents: parsed.truncated_documents,
+                            shapes: parse_shapes(&doc)?,
+                            ..Sampled::default()
+                        }
+                    }
+                    None => Sampled::default(),
+                },
+                Strategy::Client => {
+                    let mut builder =
+                        SchemaBuilder::new(self.options.max_depth).limited_by(&self.options);
+                    if let Some(top) = self.options.shapes {
+                        builder = builder.with_shapes(top);
+                    }
+                    if self.options.profiles_values() {
+                        builder = builder
+                            .with_examples(self.options.examples, self.options.redaction)
+                            .with_top_values(self.options.top_values);
+                    }
+                    if self.options.stats {
+                        builder = builder.with_stats();
+                        if let Some(scale) = self.options.histograms {
+                            builder =
+                                builder.with_histograms(scale, self.options.histogram_buckets);
+                        }
+                    }
+                    if self.options.field_sizes {
+                        builder = builder.with_field_sizes();
+                    }
+                    if self.options.document_sizes {
+                        builder = builder.with_document_sizes();
+                    }
+                    if self.options.document_widths {
+                        builder = builder.with_document_widths();
+                    }
+                    if let Some(field) = &self.options.time_field {
+                        builder = builder.with_evolution(field, self.options.time_bucket);
+                    }
+                    if self.options.check_field_names {
+                        builder = builder.with_name_check();
+                    }
+                    let throttle = Throttle::new(self.options.throttle);
+                    let mut last_id = None;
+                    let mut attempt = 0;
+                    loop {
+                        let doc = match cursor.try_next().await {
+                            Ok(Some(doc)) => doc,
+                            Ok(None) => break,
+                            // a stratified sample can't be picked up again, as it's random
+                            Err(e)
+                                if attempt < self.options.retries
+                                    && is_transient(&e)
+                                    && self.options.stratify_by.is_none() =>
+                            {
+                                attempt += 1;
+                                self.back_off(attempt, &e).await;
+                                let read = builder.documents();
+                                if scan_size.is_some_and(|size| read >= size) {
+                                    break;
+                                }
+                                cursor = self
+                                    .retrying(|| {
+                                        self.client_find(
+                                            &collection,
+                                            scan_size,
+                                            read,
+                                            last_id.as_ref(),
+                                        )
+                                    })
+                                    .await?;
+                                continue;
+                            }
+                            Err(e) => return Err(e.into()),
+                        };
+                        attempt = 0;
+                        last_id = doc.get("_id").cloned();
+                        if let (Some(share), Some(seed)) = (seeded, self.options.seed) {
+                            if seeded_rank(seed, last_id.as_ref()) >= share {
+                                continue;
+                            }
+                        }
+                        builder.add(&doc);
+                        self.report(Progress::Documents {
+                            read: builder.documents(),
+                            expected: sample_size.or(Some(document_count)),
+                        });
+                        throttle.wait(builder.documents()).await;
+                    }
+                    read_rate = Some(throttle.rate(builder.documents()));
+                    let schema = builder.build(self.ns.to_string());
+                    Sampled {
+                        documents: schema.sample_size,
+                        fields: schema.fields,
+                        truncated_documents: schema.truncated_documents,
+                        shapes: schema.shapes,
+                        sizes: schema.sizes.map(|sizes| sizes.extrapolate(document_count)),
+                        document_sizes: schema.document_sizes,
+                        document_widths: schema.document_widths,
+                        evolution: schema.evolution,
+                        field_names: schema.field_names,
+                    }
+                }
+            };
+            Ok::<_, AnalyzerError>((strategy, query, read_rate, sampled))
+        };
+        // with the pipeline documents are measured by an aggregation of their own, run
+        // alongside
+        let measuring = async {
+            if (self.options.document_sizes || self.options.document_widths)
+                && self.options.strategy != Strategy::Client
+            {
+                Some(self.measure_documents(sample_size).await)
+            } else {
+                None
+            }
+        };
+        let (sampled, measured) = futures::join!(sampling, measuring);
+        let (strategy, query, read_rate, sampled) = sampled?;
+        let Sampled {
+            documents,
+            mut fields,
+            truncated_documents,
+            mut shapes,
+            sizes,
+            document_sizes,
+            document_widths,
+            evolution,
+            mut field_names,
+        } = sampled;
+        let measurements = (document_sizes, document_widths);
+        let (document_sizes, document_widths) = match measured {
+            Some(Ok(measured)) => measured,
+            // the server rejected the pipeline, and the client-side fallback measured them
+            Some(Err(AnalyzerError::Server(_))) if strategy == Strategy::Client => measurements,
+            Some(Err(e)) => return Err(e),
+            None => measurements,
+        };
+
+        let sample_method = match (strategy, sample_size) {
+            _ if self.options.stratify_by.is_some() => SampleMethod::Stratified,
+            (Strategy::Client, Some(_)) if self.options.seed.is_some() => SampleMethod::Seeded,
+            (Strategy::Client, Some(_))
+                if self.options.sample_strategy == SampleStrategy::Recent =>
+            {
+                SampleMethod::Recent
+            }
+            (_, None) => SampleMethod::All,
+            (Strategy::Client, Some(_)) => SampleMethod::First,
+            (_, Some(_)) => SampleMethod::Random,
+        };
+
+        if (self.options.profiles_values() || self.options.stats || self.options.check_field_names)
+            && strategy != Strategy::Client
+        {
+            field_names = self
+                .profile_values(&collection, sample_size, &mut fields)
+                .await?;
+        }
+        condition_presence(&mut fields);
+        let reported = self.options.reported_paths(fields.keys());
+        fields.retain(|path, _| reported.contains(path));
+        if let Some(shapes) = &mut shapes {
+            shapes.retain_paths(|path| reported.contains(path));
+        }
+        let mut evolution = match (&self.options.time_field, strategy) {
+            (None, _) => None,
+            (Some(_), Strategy::Client) => evolution,
+            (Some(field), _) => Some(self.analyze_evolution(field, sample_size).await?),
+        };
+        if let Some(evolution) = &mut evolution {
+            evolution.fields.retain(|path, _| reported.contains(path));
+        }
+
+        let time_series = match time_series {
+            Some(options) => Some(self.analyze_time_series(options).await?),
+            None => None,
+        };
+
+        let sizes = match (self.options.field_sizes, strategy) {
+            (false, _) => None,
+            (true, Strategy::Client) => sizes,
+            (true, _) => {
+                let top_level = fields
+                    .keys()
+                    .filter(|path| !path.contains('.') && !path.ends_with("[]"))
+                    .map(String::as_str)
+                    .collect::<Vec<_>>();
+                Some(
+                    self.field_sizes(sample_size, document_count, &top_level)
+                        .await?,
+                )
+            }
+        };
+
+        let post_query = start.elapsed() - pre_query - query;
+        debug!(
+            namespace = %self.ns,
+            ?strategy,
+            ?pre_query,
+            ?query,
+            ?post_query,
+            "sampled {} documents",
+            documents
+        );
+
+        let mut schema = CollectionSchema {
+            format_version: FormatVersion::default(),
+            namespace: self.ns.to_string(),
+            document_count,
+            sample_size: documents,
+            sample_method,
+            fields,
+            meta: Some(RunMetadata {
+                server_version,
+                strategy: Some(strategy),
+                timings: Some(Timings {
+                    pre_query,
+                    query,
+                    post_query,
+                    read_rate,
+                }),
+                ..RunMetadata::new(document_count, documents)
+            }),
+            shapes,
+            anomalies: Vec::new(),
+            privacy: None,
+            rare_fields: Vec::new(),
+            findings: Vec::new(),
+            confidence: None,
+            field_names,
+            sizes,
+            document_sizes,
+            document_widths,
+            truncated_documents,
+            variants: None,
+            versions: None,
+            time_series,
+            view: self.view.clone(),
+            shards: None,
+            indexes: None,
+            evolution,
+            type_mode: TypeMode::Strict,
+        };
+        schema.normalize_types(self.options.type_mode);
+        schema.anomalies = schema.find_anomalies(&self.options.profile_thresholds);
+        schema.rare_fields = schema.find_rare_fields(self.options.rare_fields);
+        schema.estimate_confidence();
+        if self.options.type_shares {
+            schema.share_types();
+        }
+        if self.options.detect_pii {
+            schema.privacy = Some(schema.find_pii());
+        }
+        if let Some(field) = &self.options.group_by {
+            schema.variants = Some(self.analyze_variants(field, sample_size).await?);
+        }
+        if let Some(field) = &self.options.version_field {
+            schema.versions = Some(self.analyze_versions(field, sample_size).await?);
+        }
+        Ok(schema)
+    }
+
+    /// The server's version, for the run's metadata. Servers that won't say, like some
+    /// API-compatible services, are no reason to fail the analysis
+    async fn server_version(&self) -> Option<String> {
+        let info = self
+            .client
+            .database(&self.ns.db)
+            .run_command(doc! { "buildInfo": 1 }, None)
+            .await
+            .ok()?;
+        info.get_str("version").ok().map(str::to_string)
+    }
+
+    /// The command the sampling runs, without running it, e.g. to review it or to reproduce a
+    /// problem in the shell. Only the document count the sample size is derived from is
+    /// queried. The aggregations of the options that take samples of their own, like
+    /// [`AnalysisOptions::field_sizes`], aren't included
+    pub async fn dry_run(&self) -> Result<Document> {
+        if let Some(targeted) = self.targeted() {
+            return Box::pin(targeted.dry_run()).await;
+        }
+        if self.options.random_key_field.is_some() {
+            let exact = counted_exactly(self.specification().await.as_ref());
+            let document_count = self.count(&self.collection(), exact).await?;
+            if let Some(ranged) = self.ranged(document_count) {
+                return Box::pin(ranged.dry_run()).await;
+            }
+        }
+        let sample_size = match self.options.sampling {
+            Sampling::Size(size) => Some(size),
+            Sampling::Full => None,
+            sampling => {
+                let exact = counted_exactly(self.specification().await.as_ref());
+                sampling.size(self.count(&self.collection(), exact).await?)
+            }
+        };
+        let client = self.options.samples_client_side();
+        let mut command = match (client, self.options.stratify()) {
+            (true, None) => {
+                let scan_size = if self.options.seed.is_some() {
+                    None
+                } else {
+                    sample_size
+                };
+                let mut command = doc! {
+                    "find": &self.ns.coll,
+                    "filter": self.options.filter.clone().unwrap_or_default(),
+                };
+                match scan_size {
+                    Some(size) => {
+                        if self.options.sample_strategy == SampleStrategy::Recent {
+                            command.insert("sort", doc! { "$natural": -1 });
+                        }
+                        command.insert("limit", i64::try_from(size).unwrap_or(i64::MAX))
+                    }
+                    None => command.insert("sort", doc! { "_id": 1 }),
+                };
+                if let Some(batch_size) = self.options.batch_size {
+                    command.insert("batchSize", i64::from(batch_size));
+                }
+                command
+            }
+            (true, Some(stratify)) => {
+                let pipeline = sample_stages(self.options.filter.as_ref(), None, Some(stratify));
+                self.aggregate_command(pipeline)
+            }
+            (false, _) => self.aggregate_command(schema_pipeline(
+                self.options.filter.as_ref(),
+                sample_size,
+                self.options.stratify(),
+                Descent::of(&self.options),
+                self.options.shapes,
+            )),
+        };
+        command.insert("comment", &self.options.comment);
+        if let Some(collation) = &self.options.collation {
+            command.insert("collation", collation.clone());
+        }
+        if let Some(hint) = &self.options.hint {
+            command.insert("hint", bson::to_bson(hint).unwrap_or_default());
+        }
+        if self.options.allow_disk_use {
+            command.insert("allowDiskUse", true);
+        }
+        if let Some(max_time) = self.options.max_time {
+            command.insert("maxTimeMS", max_time.as_millis() as i64);
+        }
+        Ok(command)
+    }
+
+    /// This analyzer reading just the documents of [`AnalysisOptions::ids`], if they're set
+    fn targeted(&self) -> Option<SchemaAnalyzer> {
+        Some(SchemaAnalyzer {
+            options: self.options.targeted()?,
+            ..self.clone()
+        })
+    }
+
+    /// This analyzer reading the range of [`AnalysisOptions::random_key_field`] a sample of
+    /// `document_count` documents covers, if it's set
+    fn ranged(&self, document_count: u64) -> Option<SchemaAnalyzer> {
+        Some(SchemaAnalyzer {
+            options: self.options.ranged(document_count)?,
+            ..self.clone()
+        })
+    }
+
+    fn aggregate_command(&self, pipeline: Vec<Document>) -> Document {
+        let mut cursor = Document::new();
+        if let Some(batch_size) = self.options.batch_size {
+            cursor.insert("batchSize", i64::from(batch_size));
+        }
+        doc! { "aggregate": &self.ns.coll, "pipeline": pipeline, "cursor": cursor }
+    }
+
+    /// How many documents the sample size is derived from: those matching the filter, or an
+    /// estimate of the collection's unless it should be counted `exact`ly, see
+    /// [`counted_exactly`]
+    pub(crate) async fn count(
+        &self,
+        collection: &Collection<Document>,
+        exact: bool,
+    ) -> mongodb::error::Result<u64> {
+        let filter = match &self.options.filter {
+            Some(filter) => filter.clone(),
+            None if exact => Document::new(),
+            None => return collection.estimated_document_count(None).await,
+        };
+        let options = CountOptions::builder()
+            .collation(self.collation()?)
+            .hint(self.hint())
+            .comment(Bson::String(self.options.comment.clone()))
+            .max_time(self.options.max_time)
+            .build();
+        collection.count_documents(filter, options).await
+    }
+
+    /// Profile the values of the fields the pipeline found, and collect their numeric
+    /// statistics, on a random sample of its own, as the pipeline only reports types. Returns
+    /// the sample's field name issues, if they're being checked
+    async fn profile_values(
+        &self,
+        collection: &Collection<Document>,
+        sample_size: Option<u64>,
+        fields: &mut BTreeMap<String, FieldSchema>,
+    ) -> mongodb::error::Result<Option<Vec<FieldNameIssue>>> {
+        let size = sample_size.map_or(PROFILE_SAMPLE_SIZE, |size| size.min(PROFILE_SAMPLE_SIZE));
+        let mut cursor = collection
+            .aggregate(
+                profile_pipeline(self.options.filter.as_ref(), size),
+                self.aggregate_options()?,
+            )
+            .await?;
+        let mut builder = SchemaBuilder::new(self.options.max_depth).limited_by(&self.options);
+        if self.options.profiles_values() {
+            builder = builder
+                .with_examples(self.options.examples, self.options.redaction)
+                .with_top_values(self.options.top_values);
+        }
+        if self.options.stats {
+            builder = builder.with_stats();
+            if let Some(scale) = self.options.histograms {
+                builder = builder.with_histograms(scale, self.options.histogram_buckets);
+            }
+        }
+        if self.options.check_field_names {
+            builder = builder.with_name_check();
+        }
+        while let Some(doc) = cursor.try_next().await? {
+            builder.add(&doc);
+        }
+        let profiled = builder.build(self.ns.to_string());
+        let mut profiled_fields = profiled.fields;
+        for (path, field) in fields {
+            let profiled = profiled_fields.remove(path);
+            if self.options.profiles_values() {
+                field.values = Some(
+                    profiled
+                        .as_ref()
+                        .and_then(|profiled| profiled.values.clone())
+                        .unwrap_or_default(),
+                );
+            }
+            if let Some(profiled) = profiled {
+                field.stats = profiled.stats;
+                field.length_stats = profiled.length_stats;
+                field.date_stats = profiled.date_stats;
+                field.binary_subtypes = profiled.binary_subtypes;
+            }
+        }
+        Ok(profiled.field_names)
+    }
+
+    pub(crate) fn aggregate_options(&self) -> mongodb::error::Result<AggregateOptions> {
+        Ok(AggregateOptions::builder()
+            .comment(self.options.comment.clone())
+            .collation(self.collation()?)
+            .hint(self.hint())
+            .allow_disk_use(self.options.allow_disk_use.then_some(true))
+            .max_time(self.options.max_time)
+            .batch_size(self.options.batch_size)
+            .build())
+    }
+
+    /// The options' collation, failing if the server couldn't take it
+    pub(crate) fn collation(&self) -> mongodb::error::Result<Option<Collation>> {
+        let Some(collation) = &self.options.collation else {
+            return Ok(None);
+        };
+        Ok(Some(bson::from_document(collation.clone())?))
+    }
+
+    pub(crate) fn hint(&self) -> Option<Hint> {
+        self.options.hint.clone().map(|hint| match hint {
+            IndexHint::Name(name) => Hint::Name(name),
+            IndexHint::Keys(keys) => Hint::Keys(keys),
+        })
+    }
+
+    pub(crate) fn report(&self, progress: Progress<'_>) {
+        if let Some(progress_fn) = &self.options.progress {
+            progress_fn.report(progress);
+        }
+    }
+
+    /// The collection's listing, or `None` if it doesn't exist or can't be listed
+    pub(crate) async fn specification(&self) -> Option<CollectionSpecification> {
+        let database = self.client.database(&self.ns.db);
+        let listed = async {
+            let mut cursor = database
+                .list_collections(doc! { "name": &self.ns.coll }, None)
+                .await?;
+            cursor.try_next().await
+        };
+        match listed.await {
+            Ok(specification) => specification,
+            Err(e) => {
+                debug!(namespace = %self.ns, error = %e, "could not list the collection");
+                None
+            }
+        }
+    }
+
+    /// The analyzed collection, read according to the options' read preference
+    pub(crate) fn collection(&self) -> Collection<Document> {
+        self.collection_named(&self.ns.coll)
+    }
+
+    /// A collection of the analyzed one's database, read like it
+    fn collection_named(&self, name: &str) -> Collection<Document> {
+        let options = CollectionOptions::builder()
+            .selection_criteria(
+                self.options
+                    .read_preference
+                    .clone()
+                    .map(SelectionCriteria::ReadPreference),
+            )
+            .build();
+        self.client
+            .database(&self.ns.db)
+            .collection_with_options(name, options)
+    }
+
+    /// The share of the documents a seeded sample of `sample_size` keeps, if it's seeded
+    fn seeded_share(&self, sample_size: Option<u64>, document_count: u64) -> Option<f64> {
+        self.options.seed?;
+        if self.options.strategy == Strategy::Pipeline {
+            return None;
+        }
+        let size = sample_size?;
+        Some(size as f64 / document_count.max(1) as f64)
+    }
+
+    /// Start sampling with the configured strategy, returning the strategy that was used. A
+    /// client-side scan reads `scan_size` documents, where the pipeline samples `sample_size`
+    async fn open_cursor(
+        &self,
+        collection: &Collection<Document>,
+        sample_size: Option<u64>,
+        scan_size: Option<u64>,
+    ) -> mongodb::error::Result<(Strategy, Cursor<Document>)> {
+        // `$sample` can't be seeded, nor can an aggregation sort in natural order, so those
+        // samples are always taken client-side
+        if self.options.samples_client_side() {
+            let cursor = self.client_find(collection, scan_size, 0, None).await?;
+            return Ok((Strategy::Client, cursor));
+        }
+
+        self.report(Progress::Aggregating);
+        match self.aggregate_schema(collection, sample_size).await {
+            Ok(cursor) => Ok((Strategy::Pipeline, cursor)),
+            // the server doesn't know one of the stages or operators
+            Err(e)
+                if self.options.strategy == Strategy::Auto
+                    && matches!(*e.kind, ErrorKind::Command(_))
+                    && !is_transient(&e) =>
+            {
+                info!(namespace = %self.ns, error = %e, "falling back to a client-side scan");
+                let cursor = self.client_find(collection, scan_size, 0, None).await?;
+                Ok((Strategy::Client, cursor))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn aggregate_schema(
+        &self,
+        collection: &Collection<Document>,
+        sample_size: Option<u64>,
+    ) -> mongodb::error::Result<Cursor<Document>> {
+        let pipeline = schema_pipeline(
+            self.options.filter.as_ref(),
+            sample_size,
+            self.options.stratify(),
+            Descent::of(&self.options),
+            self.options.shapes,
+        );
+        collection
+            .aggregate(pipeline, self.aggregate_options()?)
+            .await
+    }
+
+    /// The documents a client-side scan reads, after the `read` it already has. A full scan
+    /// goes in `_id` order so it can pick up after `last_id`; a limited one takes the first
+    /// documents in natural order, skipping those read
+    async fn client_find(
+        &self,
+        collection: &Collection<Document>,
+        sample_size: Option<u64>,
+        read: u64,
+        last_id: Option<&Bson>,
+    ) -> mongodb::error::Result<Cursor<Document>> {
+        if let Some(stratify) = self.options.stratify() {
+            let pipeline = sample_stages(self.options.filter.as_ref(), None, Some(stratify));
+            return collection
+                .aggregate(pipeline, self.aggregate_options()?)
+                .await;
+        }
+        let filter = match (sample_size, last_id) {
+            (None, Some(last_id)) => {
+                let after = doc! { "_id": { "$gt": last_id.clone() } };
+                Some(match &self.options.filter {
+                    Some(filter) => doc! { "$and": [filter.clone(), after] },
+                    None => after,
+                })
+            }
+            _ => self.options.filter.clone(),
+        };
+        let to_i64 = |n: u64| i64::try_from(n).unwrap_or(i64::MAX);
+        // a view is read through its pipeline on what it's a view on, so a hint names the
+        // indexes there, which the view itself has none of
+        if let Some(view) = &self.view {
+            let mut pipeline = view.pipeline.clone();
+            if let Some(filter) = filter {
+                pipeline.push(doc! { "$match": filter });
+            }
+            match sample_size {
+                Some(size) => {
+                    if read > 0 {
+                        pipeline.push(doc! { "$skip": to_i64(read) });
+                    }
+                    pipeline.push(doc! { "$limit": to_i64(size - read).max(1) });
+                }
+                None => pipeline.push(doc! { "$sort": { "_id": 1 } }),
+            }
+            return self
+                .collection_named(&view.view_on)
+                .aggregate(pipeline, self.aggregate_options()?)
+                .await;
+        }
+        let options = FindOptions::builder()
+            .limit(sample_size.map(|size| to_i64(size - read)))
+            .skip((sample_size.is_some() && read > 0).then_some(read))
+            .sort(match sample_size {
+                None => Some(doc! { "_id": 1 }),
+                Some(_) if self.options.sample_strategy == SampleStrategy::Recent => {
+                    Some(doc! { "$natural": -1 })
+                }
+                Some(_) => None,
+            })
+            .comment(self.options.comment.clone())
+            .collation(self.collation()?)
+            .hint(self.hint())
+            .allow_disk_use(self.options.allow_disk_use.then_some(true))
+            .max_time(self.options.max_time)
+            .batch_size(self.options.batch_size)
+            .build();
+        collection.find(filter, options).await
+    }
+
+    /// Run `op`, retrying it as by [`SchemaAnalyzer::retry`]
+    async fn retrying<T, F: Future<Output = mongodb::error::Result<T>>>(
+        &self,
+        mut op: impl FnMut() -> F,
+    ) -> mongodb::error::Result<T> {
+        let result = op().await;
+        self.retry(result, op).await
+    }
+
+    /// `result`, or if it's a transient error, that of running `op` again after a backoff,
+    /// up to [`AnalysisOptions::retries`] times
+    async fn retry<T, F: Future<Output = mongodb::error::Result<T>>>(
+        &self,
+        mut result: mongodb::error::Result<T>,
+        mut op: impl FnMut() -> F,
+    ) -> mongodb::error::Result<T> {
+        let mut attempt = 0;
+        loop {
+            match result {
+                Err(e) if attempt < self.options.retries && is_transient(&e) => {
+                    attempt += 1;
+                    self.back_off(attempt, &e).await;
+                    result = op().await;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Wait before the `attempt`th retry: half a second, doubling each time up to 30 seconds
+    async fn back_off(&self, attempt: u32, error: &mongodb::error::Error) {
+        let delay = (Duration::from_millis(500) * 2u32.saturating_pow(attempt - 1))
+            .min(Duration::from_secs(30));
+        self.report(Progress::Retrying {
+            attempt,
+            delay,
+            error: &error.to_string(),
+        });
+        tokio::time::sleep(delay).await;
+    }
+}
+
+/// Paces a client-side scan to [`AnalysisOptions::throttle`] documents a second
+pub(crate) struct Throttle {
+    per_second: Option<u32>,
+    start: Instant,
+}
+
+impl Throttle {
+    pub(crate) fn new(per_second: Option<u32>) -> Self {
+        Throttle {
+            per_second,
+            start: Instant::now(),
+        }
+    }
+
+    /// Wait until reading `read` documents since the start is within the limit. Short waits
+    /// are put off until they add up, so batches aren't broken into a sleep per document
+    pub(crate) async fn wait(&self, read: u64) {
+        let Some(per_second) = self.per_second.filter(|&n| n > 0) else {
+            return;
+        };
+        let due = Duration::from_secs_f64(read as f64 / f64::from(per_second));
+        let ahead = due.saturating_sub(self.start.elapsed());
+        if ahead >= Duration::from_millis(10) {
+            tokio::time::sleep(ahead).await;
+        }
+    }
+
+    /// Documents a second over the scan so far, having read `read`
+    pub(crate) fn rate(&self, read: u64) -> f64 {
+        let elapsed = self.start.elapsed().as_secs_f64();
+        if elapsed > 0.0 {
+            read as f64 / elapsed
+        } else {
+            0.0
+        }
+    }
+}
+
+/// What reading the sample found, before the options' further aggregations: the pipeline
+/// only fills in the first four
+#[derive(Default)]
+struct Sampled {
+    documents: u64,
+    fields: BTreeMap<String, FieldSchema>,
+    truncated_documents: u64,
+    shapes: Option<Shapes>,
+    sizes: Option<FieldSizes>,
+    document_sizes: Option<DocumentSizes>,
+    document_widths: Option<DocumentWidths>,
+    evolution: Option<Evolution>,
+    field_names: Option<Vec<FieldNameIssue>>,
+}
+
+/// Whether the documents of `specification`'s collection are counted rather than estimated:
+/// estimates count a time-series collection's buckets, and a view has none of its own
+pub(crate) fn counted_exactly(specification: Option<&CollectionSpecification>) -> bool {
+    specification.is_some_and(|specification| {
+        matches!(
+            specification.collection_type,
+            CollectionType::View | CollectionType::Timeseries
+        )
+    })
+}
+
+/// Where the document with `id` falls in a seeded sample, from 0 to 1: the documents ranked
+/// below the share being sampled are kept. A hash of the `_id`'s BSON, so the rank doesn't
+/// change between runs, platforms or releases
+fn seeded_rank(seed: u64, id: Option<&Bson>) -> f64 {
+    let id = bson::to_vec(&doc! { "_id": id.cloned().unwrap_or(Bson::Null) }).unwrap_or_default();
+    let digest = Sha256::new()
+        .chain_update(seed.to_le_bytes())
+        .chain_update(id)
+        .finalize();
+    let rank = u64::from_le_bytes(digest[..8].try_into().unwrap());
+    rank as f64 / u64::MAX as f64
+}
+
+/// Server error codes of elections, shutdowns and network trouble, after which the same
+/// operation can succeed
+const TRANSIENT_CODES: [i32; 11] = [
+    6,     // HostUnreachable
+    7,     // HostNotFound
+    89,    // NetworkTimeout
+    91,    // ShutdownInProgress
+    189,   // PrimarySteppedDown
+    9001,  // SocketException
+    10107, // NotWritablePrimary
+    11600, // InterruptedAtShutdown
+    11602, // InterruptedDueToReplStateChange
+    13435, // NotPrimaryNoSecondaryOk
+    13436, // NotPrimaryOrSecondary
+];
+
+/// Whether `error` is worth retrying
+fn is_transient(error: &mongodb::error::Error) -> bool {
+    // server selection already waits out elections, so failing it means the cluster is
+    // down or unreachable
+    match &*error.kind {
+        ErrorKind::Io(_) | ErrorKind::ConnectionPoolCleared { .. } => true,
+        ErrorKind::Command(e) => TRANSIENT_CODES.contains(&e.code),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn forwards_app_name_and_comment() {
+        let mut options = ClientOptions::parse("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        identify_client(&mut options, None);
+        assert_eq!(options.app_name.as_deref(), Some(APP_NAME));
+
+        let mut named = ClientOptions::parse("mongodb://localhost:27017/?appName=etl")
+            .await
+            .unwrap();
+        identify_client(&mut named, None);
+        assert_eq!(named.app_name.as_deref(), Some("etl"));
+        identify_client(&mut named, Some("nightly"));
+        assert_eq!(named.app_name.as_deref(), Some("nightly"));
+
+        let client = Client::with_options(options).unwrap();
+        let analyzer =
+            SchemaAnalyzer::new(client, Namespace::new("shop", "orders")).comment("nightly audit");
+        let aggregate = analyzer.aggregate_options().unwrap();
+        assert_eq!(aggregate.comment.as_deref(), Some("nightly audit"));
+    }
+
+    #[tokio::test]
+    async fn reads_exactly_the_documents_with_the_ids_given() {
+        let options = ClientOptions::parse("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        let analyzer = SchemaAnalyzer::new(
+            Client::with_options(options).unwrap(),
+            Namespace::new("shop", "orders"),
+        )
+        .sampling(Sampling::Size(100))
+        .filter(Some(doc! { "tenant": "acme" }))
+        .ids(vec![Bson::Int32(7), Bson::Int32(3), Bson::Int32(7)]);
+        let command = analyzer.dry_run().await.unwrap();
+        let pipeline = command.get_array("pipeline").unwrap();
+        assert_eq!(
+            pipeline[0],
+            Bson::Document(doc! {
+                "$match": { "$and": [{ "tenant": "acme" }, { "_id": { "$in": [7, 3] } }] }
+            })
+        );
+        assert!(!pipeline.iter().any(|stage| stage
+            .as_document()
+            .is_some_and(|stage| stage.contains_key("$sample"))));
+    }
+
+    #[tokio::test]
+    async fn samples_a_range_of_the_random_key_field() {
+        let options = ClientOptions::parse("mongodb://localhost:27017")
+            .await
+            .unwrap();
+        let analyzer = SchemaAnalyzer::new(
+            Client::with_options(options).unwrap(),
+            Namespace::new("shop", "orders"),
+        )
+        .sampling(Sampling::Size(1_000))
+        .filter(Some(doc! { "tenant": "acme" }))
+        .random_key_field(Some("rand".to_string()));
+        // a tenth of the documents
+        let command = analyzer.ranged(10_000).unwrap().dry_run().await.unwrap();
+        let pipeline = command.get_array("pipeline").unwrap();
+        assert_eq!(
+            pipeline[0],
+            Bson::Document(doc! {
+                "$match": { "$and": [{ "tenant": "acme" }, { "rand": { "$gte": 0.0, "$lt": 0.1 } }] }
+            })
+        );
+        assert!(!pipeline.iter().any(|stage| stage
+            .as_document()
+            .is_some_and(|stage| stage.contains_key("$sample") || stage.contains_key("$sort"))));
+
+        // a seeded range starts elsewhere, the same each time
+        let seeded = analyzer.clone().options;
+        let seeded = AnalysisOptions {
+            seed: Some(7),
+            ..seeded
+        };
+        let ranged = seeded.ranged(10_000).unwrap();
+        assert_eq!(ranged.filter, seeded.ranged(10_000).unwrap().filter);
+        assert_ne!(
+            ranged.filter,
+            analyzer.options.ranged(10_000).unwrap().filter
+        );
+        assert_eq!((ranged.seed, ranged.sampling), (None, Sampling::Full));
+
+        // nothing to range over when every document is sampled
+        assert!(analyzer.ranged(500).is_none());
+    }
+}
@@ -0,0 +1,492 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    BinaryKind, BsonType, CollectionSchema, FieldSchema, ProfileThresholds, ScalarValue,
+    ValueProfile,
+};
+
+/// Fields present in at least this share of their parents' documents, but not all, are
+/// reported as [`AnomalyKind::RarelyMissing`]
+const RARELY_MISSING: f64 = 0.95;
+
+/// Enum candidates need at least this many values per distinct value, so a small sample of
+/// free-form strings doesn't pass for one
+const ENUM_REPEATS: u64 = 3;
+
+/// How much an anomaly is likely to matter to code reading the collection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Worth knowing, but readers rarely trip over it
+    Info,
+    /// Likely to break readers that expect the common case
+    Warn,
+    /// Readers have to handle incompatible types
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Severity::Info => "info",
+            Severity::Warn => "warn",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// Something suspicious about a field, found by [`CollectionSchema::find_anomalies`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Anomaly {
+    pub path: String,
+    pub severity: Severity,
+    #[serde(flatten)]
+    pub kind: AnomalyKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AnomalyKind {
+    /// The field holds types that can't stand in for each other, e.g. a string in some
+    /// documents and an int or an object in others
+    ConflictingTypes { types: Vec<BsonType> },
+    /// The field holds numbers of more than one type, e.g. ints and doubles
+    MixedNumeric { types: Vec<BsonType> },
+    /// The field is an array in some documents and a single value in others
+    ScalarOrArray,
+    /// The field is missing from a small fraction of the documents that could hold it,
+    /// which usually means it was meant to be required
+    RarelyMissing { fraction: f64 },
+    /// The field is present, but null in every document
+    AlwaysNull,
+    /// Nearly all of the field's strings are ISO-8601 dates, which sort and compare
+    /// correctly only as BSON dates. `fraction` is the share of strings that parse
+    DateAsString { fraction: f64 },
+    /// Most of the field's strings are numbers, which sort and sum correctly only when stored
+    /// as numbers. `fraction` is the share of strings that parse, and `integers` whether
+    /// they all parse as 64-bit integers
+    NumberAsString { fraction: f64, integers: bool },
+    /// The field's strings or integers take so few distinct values that they're likely an
+    /// enumeration. Code generators then emit an enum or union of `values`
+    EnumCandidate { values: Vec<ScalarValue> },
+    /// Some of the field's binary values are UUIDs in the legacy subtype 3, whose byte order
+    /// depends on the driver that wrote them. `fraction` is their share of the binary values
+    LegacyUuid { fraction: f64 },
+    /// In `documents` sampled documents the field held a subdocument or array with more
+    /// fields or elements than the limits, or nested deeper, so what's under it may be missing
+    Truncated { documents: u64 },
+    /// Fields of the same object whose names differ only by case, e.g. `userId` and `userID`,
+    /// which is almost always an application writing the wrong one. The anomaly's path is the
+    /// most common spelling, and `spellings` every spelling, most common first
+    CaseCollision { spellings: Vec<Spelling> },
+}
+
+/// One of the names of an [`AnomalyKind::CaseCollision`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spelling {
+    pub path: String,
+    /// How many sampled documents hold the field under this name
+    pub count: u64,
+}
+
+impl AnomalyKind {
+    pub fn severity(&self) -> Severity {
+        match self {
+            AnomalyKind::ConflictingTypes { .. } => Severity::Error,
+            AnomalyKind::ScalarOrArray
+            | AnomalyKind::RarelyMissing { .. }
+            | AnomalyKind::DateAsString { .. }
+            | AnomalyKind::NumberAsString { .. }
+            | AnomalyKind::LegacyUuid { .. }
+            | AnomalyKind::CaseCollision { .. } => Severity::Warn,
+            AnomalyKind::MixedNumeric { .. }
+            | AnomalyKind::AlwaysNull
+            | AnomalyKind::EnumCandidate { .. }
+            | AnomalyKind::Truncated { .. } => Severity::Info,
+        }
+    }
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.path)?;
+        match &self.kind {
+            AnomalyKind::ConflictingTypes { types } => {
+                write!(f, "conflicting types {}", join(types))
+            }
+            AnomalyKind::MixedNumeric { types } => write!(f, "mixed numeric types {}", join(types)),
+            AnomalyKind::ScalarOrArray => f.write_str("sometimes an array, sometimes not"),
+            AnomalyKind::RarelyMissing { fraction } if self.path.contains('.') => {
+                write!(
+                    f,
+                    "missing from {:.1}% of its parent objects",
+                    fraction * 100.0
+                )
+            }
+            AnomalyKind::RarelyMissing { fraction } => {
+                write!(f, "missing from {:.1}% of documents", fraction * 100.0)
+            }
+            AnomalyKind::AlwaysNull => f.write_str("always null"),
+            AnomalyKind::DateAsString { fraction } => write!(
+                f,
+                "date stored as string ({:.1}% of strings parse as dates), store it as a date",
+                fraction * 100.0
+            ),
+            AnomalyKind::NumberAsString { fraction, integers } => write!(
+                f,
+                "number stored as string ({:.1}% of strings parse as {}), convert it with {}",
+                fraction * 100.0,
+                if *integers { "integers" } else { "numbers" },
+                if *integers { "$toLong" } else { "$toDouble" }
+            ),
+            AnomalyKind::EnumCandidate { values } => {
+                let values = values.iter().map(|v| v.to_string()).collect::<Vec<_>>();
+                write!(f, "enum candidate, one of {}", values.join(", "))
+            }
+            AnomalyKind::LegacyUuid { fraction } => write!(
+                f,
+                "legacy UUID subtype 3 ({:.1}% of binary values), migrate them to subtype 4",
+                fraction * 100.0
+            ),
+            AnomalyKind::Truncated { documents } => write!(
+                f,
+                "cut short in {} documents by the depth or size limits, nested fields may be \
+                 incomplete",
+                documents
+            ),
+            AnomalyKind::CaseCollision { spellings } => {
+                let others = spellings
+                    .iter()
+                    .filter(|spelling| spelling.path != self.path)
+                    .map(|spelling| format!("{} in {}", spelling.path, spelling.count))
+                    .collect::<Vec<_>>();
+                let count = spellings
+                    .iter()
+                    .find(|spelling| spelling.path == self.path)
+                    .map_or(0, |spelling| spelling.count);
+                write!(
+                    f,
+                    "in {} documents, also spelled {}: names differing only by case",
+                    count,
+                    others.join(", ")
+                )
+            }
+        }
+    }
+}
+
+fn join(types: &[BsonType]) -> String {
+    types
+        .iter()
+        .map(BsonType::name)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl CollectionSchema {
+    /// The values of the field at `path`, if it was found to be an
+    /// [`AnomalyKind::EnumCandidate`]
+    pub fn enum_values(&self, path: &str) -> Option<&[ScalarValue]> {
+        self.anomalies
+            .iter()
+            .find_map(|anomaly| match &anomaly.kind {
+                AnomalyKind::EnumCandidate { values } if anomaly.path == path => Some(&values[..]),
+                _ => None,
+            })
+    }
+
+    /// Classify the fields whose types or presence readers are likely to trip over, most
+    /// severe first. `thresholds` apply to fields whose values were profiled
+    pub fn find_anomalies(&self, thresholds: &ProfileThresholds) -> Vec<Anomaly> {
+        let mut anomalies = Vec::new();
+        for (path, field) in &self.fields {
+            // GeoJSON fixes what a geometry holds, and its coordinates nest differently by
+            // geometry type, values and all
+            if self.in_geometry(path) {
+                continue;
+            }
+            for kind in self.field_anomalies(path, field, thresholds) {
+                anomalies.push(Anomaly {
+                    path: path.clone(),
+                    severity: kind.severity(),
+                    kind,
+                });
+            }
+        }
+        anomalies.extend(self.case_collisions());
+        anomalies.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        anomalies
+    }
+
+    fn field_anomalies(
+        &self,
+        path: &str,
+        field: &FieldSchema,
+        thresholds: &ProfileThresholds,
+    ) -> Vec<AnomalyKind> {
+        let mut kinds = Vec::new();
+        // nulls and absence are covered by optionality, not the type
+        let held = field
+            .types
+            .iter()
+            .filter(|t| !matches!(t, BsonType::Null | BsonType::Missing | BsonType::Undefined))
+            .cloned()
+            .collect::<Vec<_>>();
+        let singles = held
+            .iter()
+            .filter(|t| **t != BsonType::Array)
+            .cloned()
+            .collect::<Vec<_>>();
+        if singles.len() > 1 {
+            if singles.iter().all(is_numeric) {
+                kinds.push(AnomalyKind::MixedNumeric { types: singles });
+            } else {
+                kinds.push(AnomalyKind::ConflictingTypes { types: singles });
+            }
+        }
+        if held.contains(&BsonType::Array) && held.len() > 1 {
+            kinds.push(AnomalyKind::ScalarOrArray);
+        }
+
+        // an element path is missing for empty arrays, which isn't worth reporting
+        if !path.contains("[]") {
+            let possible = match path.rsplit_once('.') {
+                Some((parent, _)) => self
+                    .fields
+                    .get(parent)
+                    .and_then(|parent| parent.type_counts.get(&BsonType::Object))
+                    .copied()
+                    .unwrap_or_default(),
+                None => self.sample_size,
+            };
+            if possible > 0 && field.count < possible {
+                let fraction = (possible - field.count) as f64 / possible as f64;
+                if fraction <= 1.0 - RARELY_MISSING {
+                    kinds.push(AnomalyKind::RarelyMissing { fraction });
+                }
+            }
+        }
+
+        if field.count > 0 && field.nulls() == field.count {
+            kinds.push(AnomalyKind::AlwaysNull);
+        }
+        if field.truncated > 0 {
+            kinds.push(AnomalyKind::Truncated {
+                documents: field.truncated,
+            });
+        }
+        if let Some(legacy) = field.binary_subtypes.get(&BinaryKind::UuidLegacy) {
+            let binaries = field.binary_subtypes.values().sum::<u64>();
+            kinds.push(AnomalyKind::LegacyUuid {
+                fraction: *legacy as f64 / binaries as f64,
+            });
+        }
+        if let Some(values) = &field.values {
+            if let Some(fraction) = values.date_fraction() {
+                if fraction >= thresholds.date_strings {
+                    kinds.push(AnomalyKind::DateAsString { fraction });
+                }
+            }
+            if let Some(fraction) = values.number_fraction() {
+                if fraction >= thresholds.numeric_strings {
+                    kinds.push(AnomalyKind::NumberAsString {
+                        fraction,
+                        integers: values.integers == values.numbers,
+                    });
+                }
+            }
+            if let Some(values) = enum_values(path, &held, values, thresholds) {
+                kinds.push(AnomalyKind::EnumCandidate { values });
+            }
+        }
+        kinds
+    }
+}
+
+impl CollectionSchema {
+    /// Whether the field at `path` is nested in a GeoJSON geometry
+    fn in_geometry(&self, path: &str) -> bool {
+        let mut outer = path;
+        while let Some((parent, _)) = outer.rsplit_once('.') {
+            outer = parent;
+            if self
+                .fields
+                .get(outer)
+                .is_some_and(|field| field.geojson_types().is_some())
+            {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The sibling fields whose names are the same but for case, a group per name
+    fn case_collisions(&self) -> Vec<Anomaly> {
+        let mut groups = BTreeMap::<(&str, String), Vec<Spelling>>::new();
+        // element paths share their array's name, so they'd only repeat its collision
+        for (path, field) in self.fields.iter().filter(|(path, _)| !path.ends_with("[]")) {
+            let (parent, name) = path.rsplit_once('.').unwrap_or(("", path));
+            groups
+                .entry((parent, name.to_lowercase()))
+                .or_default()
+                .push(Spelling {
+                    path: path.clone(),
+                    count: field.count,
+                });
+        }
+        groups
+            .into_values()
+            .filter(|spellings| spellings.len() > 1)
+            .map(|mut spellings| {
+                spellings.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.path.cmp(&b.path)));
+                let path = spellings[0].path.clone();
+                let kind = AnomalyKind::CaseCollision { spellings };
+                Anomaly {
+                    path,
+                    severity: kind.severity(),
+                    kind,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The distinct values of a field holding only strings, or only integers, if there are few
+/// enough of them and they repeat. Ids are unique by design, so `_id` never qualifies
+fn enum_values(
+    path: &str,
+    types: &[BsonType],
+    values: &ValueProfile,
+    thresholds: &ProfileThresholds,
+) -> Option<Vec<ScalarValue>> {
+    let single_kind = matches!(types, [BsonType::String])
+        || (!types.is_empty()
+            && types
+                .iter()
+                .all(|t| matches!(t, BsonType::Int | BsonType::Long)));
+    let distinct = values.distinct.len() as u64;
+    let seen = values.distinct.iter().map(|v| v.count).sum::<u64>();
+    let qualifies = single_kind
+        && path != "_id"
+        && !values.too_many_values
+        && distinct >= 2
+        && distinct <= thresholds.enum_values as u64
+        && seen >= distinct * ENUM_REPEATS;
+    qualifies.then(|| values.distinct.iter().map(|v| v.value.clone()).collect())
+}
+
+fn is_numeric(ty: &BsonType) -> bool {
+    matches!(
+        ty,
+        BsonType::Int | BsonType::Long | BsonType::Double | BsonType::Decimal | BsonType::Number
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    fn anomalies(ndjson: &str) -> Vec<Anomaly> {
+        let options = AnalysisOptions {
+            profile_values: true,
+            ..AnalysisOptions::default()
+        };
+        CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options)
+            .unwrap()
+            .anomalies
+    }
+
+    fn kinds_at<'a>(anomalies: &'a [Anomaly], path: &str) -> Vec<&'a AnomalyKind> {
+        anomalies
+            .iter()
+            .filter(|anomaly| anomaly.path == path)
+            .map(|anomaly| &anomaly.kind)
+            .collect()
+    }
+
+    #[test]
+    fn classifies_what_readers_would_trip_over_most_severe_first() {
+        // 20 documents, the last without a `sku`
+        let mut ndjson = String::new();
+        for i in 0..20 {
+            let status = ["new", "paid", "shipped"][i % 3];
+            let qty = if i % 2 == 0 { "1" } else { "1.5" };
+            let code = if i == 3 { "\"A-3\"" } else { "3" };
+            let tags = if i == 5 { "\"one\"" } else { "[\"a\"]" };
+            let sku = if i == 19 {
+                String::new()
+            } else {
+                format!(", \"sku\": \"S{}\"", i)
+            };
+            ndjson.push_str(&format!(
+                "{{\"status\": \"{}\", \"qty\": {}, \"code\": {}, \"tags\": {}, \"gone\": null, \
+                 \"placed\": \"2024-01-{:02}\"{}}}\n",
+                status,
+                qty,
+                code,
+                tags,
+                i + 1,
+                sku
+            ));
+        }
+        ndjson.push_str("{\"userId\": 1, \"userID\": 2}\n{\"userId\": 3}\n");
+        let anomalies = anomalies(&ndjson);
+
+        assert_eq!(anomalies[0].path, "code");
+        assert_eq!(anomalies[0].severity, Severity::Error);
+        assert!(anomalies
+            .windows(2)
+            .all(|pair| pair[0].severity >= pair[1].severity));
+
+        assert!(matches!(
+            kinds_at(&anomalies, "qty")[..],
+            [AnomalyKind::MixedNumeric { .. }]
+        ));
+        assert!(kinds_at(&anomalies, "tags").contains(&&AnomalyKind::ScalarOrArray));
+        assert!(kinds_at(&anomalies, "gone").contains(&&AnomalyKind::AlwaysNull));
+        assert!(kinds_at(&anomalies, "placed").iter().any(
+            |kind| matches!(kind, AnomalyKind::DateAsString { fraction } if *fraction == 1.0)
+        ));
+        assert!(kinds_at(&anomalies, "status").iter().any(
+            |kind| matches!(kind, AnomalyKind::EnumCandidate { values } if values.len() == 3)
+        ));
+        // missing from 3 of the 22 documents, too many for it to be rarely missing
+        assert!(!kinds_at(&anomalies, "sku")
+            .iter()
+            .any(|kind| matches!(kind, AnomalyKind::RarelyMissing { .. })));
+
+        let collisions = kinds_at(&anomalies, "userId");
+        let [AnomalyKind::CaseCollision { spellings }] = &collisions[..] else {
+            panic!("{:?}", collisions);
+        };
+        assert_eq!(spellings[0].count, 2);
+        assert_eq!(spellings[1].path, "userID");
+    }
+
+    #[test]
+    fn reports_fields_missing_from_a_few_documents() {
+        let mut ndjson = "{\"a\": 1, \"b\": {\"c\": 1}}\n".repeat(19);
+        ndjson.push_str("{\"b\": {}}\n");
+        let anomalies = anomalies(&ndjson);
+        let kinds = kinds_at(&anomalies, "a");
+        let [AnomalyKind::RarelyMissing { fraction }] = kinds[..] else {
+            panic!("{:?}", anomalies);
+        };
+        assert!((fraction - 0.05).abs() < 1e-9);
+        let nested = anomalies
+            .iter()
+            .find(|anomaly| anomaly.path == "b.c")
+            .unwrap();
+        assert_eq!(
+            nested.to_string(),
+            "b.c: missing from 5.0% of its parent objects"
+        );
+    }
+}
@@ -0,0 +1,285 @@
+//! Schemas with their names hashed and their values dropped, for sharing reports outside the
+//! organization, e.g. in support tickets, without giving away the data model
+
+use std::collections::BTreeMap;
+
+use bson::oid::ObjectId;
+use sha2::{Digest, Sha256};
+
+use crate::{AnomalyKind, CollectionSchema, SchemaReport};
+
+/// Hashes names the same way for a whole report, so a field shared by two collections, or a
+/// relationship between them, still lines up. The salt is new for every report, so names
+/// can't be recovered by hashing likely ones
+struct Names {
+    salt: [u8; 12],
+}
+
+impl Names {
+    fn new() -> Self {
+        Names {
+            salt: ObjectId::new().bytes(),
+        }
+    }
+
+    fn hash(&self, prefix: &str, name: &str) -> String {
+        let digest = Sha256::new()
+            .chain_update(self.salt)
+            .chain_update(name.as_bytes())
+            .finalize();
+        let hex = digest[..5]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>();
+        format!("{}_{}", prefix, hex)
+    }
+
+    /// `path` with every field name hashed, keeping `_id` and the `[]` of array elements,
+    /// e.g. `f_1a2b3c4d5e[].f_6f7a8b9c0d` for `items[].sku`
+    fn path(&self, path: &str) -> String {
+        path.split('.')
+            .map(|segment| {
+                let name = segment.trim_end_matches("[]");
+                let elements = &segment[name.len()..];
+                match name {
+                    "_id" | "" => segment.to_string(),
+                    name => self.hash("f", name) + elements,
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    fn namespace(&self, namespace: &str) -> String {
+        match namespace.split_once('.') {
+            Some((db, coll)) => format!("{}.{}", self.hash("db", db), self.hash("c", coll)),
+            None => self.hash("c", namespace),
+        }
+    }
+
+    /// Index names default to their keys, so all but the `_id` index's are hashed
+    fn index(&self, name: &str) -> String {
+        match name {
+            "_id_" => name.to_string(),
+            name => self.hash("i", name),
+        }
+    }
+
+    fn schema(&self, schema: &CollectionSchema) -> CollectionSchema {
+        let mut schema = schema.clone();
+        schema.namespace = self.namespace(&schema.namespace);
+        schema.fields = std::mem::take(&mut schema.fields)
+            .into_iter()
+            .map(|(path, mut field)| {
+                // examples, top values and enum values
+                field.values = None;
+                (self.path(&path), field)
+            })
+            .collect();
+        if let Some(shapes) = &mut schema.shapes {
+            for shape in &mut shapes.top {
+                shape.fields = std::mem::take(&mut shape.fields)
+                    .into_iter()
+                    .map(|(path, types)| (self.path(&path), types))
+                    .collect();
+            }
+        }
+        schema
+            .anomalies
+            .retain(|anomaly| !matches!(anomaly.kind, AnomalyKind::EnumCandidate { .. }));
+        for anomaly in &mut schema.anomalies {
+            anomaly.path = self.path(&anomaly.path);
+            if let AnomalyKind::CaseCollision { spellings } = &mut anomaly.kind {
+                for spelling in spellings {
+                    spelling.path = self.path(&spelling.path);
+                }
+            }
+        }
+        for finding in schema.privacy.iter_mut().flatten() {
+            finding.path = self.path(&finding.path);
+        }
+        for field in &mut schema.rare_fields {
+            field.path = self.path(&field.path);
+        }
+        for finding in &mut schema.findings {
+            finding.path = self.path(&finding.path);
+        }
+        for issue in schema.field_names.iter_mut().flatten() {
+            issue.path = self.path(&issue.path);
+            // the _ids point back at the documents
+            issue.documents.clear();
+        }
+        for size in schema.sizes.iter_mut().flat_map(|sizes| &mut sizes.fields) {
+            size.path = self.path(&size.path);
+        }
+        if let Some(widths) = &mut schema.document_widths {
+            widths.key_patterns.clear();
+        }
+        if let Some(view) = &mut schema.view {
+            view.view_on = self.hash("c", &view.view_on);
+            // the stages name fields and hold the values they filter on
+            view.pipeline.clear();
+        }
+        if let Some(time_series) = &mut schema.time_series {
+            time_series.time_field = self.path(&time_series.time_field);
+            time_series.meta_field = time_series.meta_field.as_deref().map(|f| self.path(f));
+            time_series.meta_fields = std::mem::take(&mut time_series.meta_fields)
+                .into_iter()
+                .map(|(path, mut field)| {
+                    field.values = None;
+                    (self.path(&path), field)
+                })
+                .collect();
+        }
+        // grouped by the values of a field
+        schema.variants = None;
+        schema.versions = None;
+        if let Some(evolution) = &mut schema.evolution {
+            evolution.field = self.path(&evolution.field);
+            evolution.fields = std::mem::take(&mut evolution.fields)
+                .into_iter()
+                .map(|(path, history)| (self.path(&path), history))
+                .collect();
+        }
+        for difference in schema
+            .shards
+            .iter_mut()
+            .flat_map(|shards| &mut shards.differences)
+        {
+            difference.path = self.path(&difference.path);
+        }
+        if let Some(indexes) = &mut schema.indexes {
+            for index in &mut indexes.existing {
+                index.name = self.index(&index.name);
+                for key in index.keys.iter_mut().chain(&mut index.spherical) {
+                    *key = self.path(key);
+                }
+            }
+            for suggestion in &mut indexes.suggestions {
+                suggestion.path = self.path(&suggestion.path);
+            }
+            for rare in &mut indexes.rare {
+                rare.index = self.index(&rare.index);
+                rare.path = self.path(&rare.path);
+            }
+        }
+        schema
+    }
+}
+
+impl CollectionSchema {
+    /// A copy to share without exposing the data model: the namespace and every field name
+    /// but `_id` hashed, and example values, enum values and the breakdowns by a field's
+    /// values dropped. Types, counts and statistics are kept
+    pub fn anonymized(&self) -> Self {
+        Names::new().schema(self)
+    }
+}
+
+impl SchemaReport {
+    /// Every schema [anonymized](CollectionSchema::anonymized), names hashed alike across
+    /// the report. Why namespaces failed is dropped, as errors can quote names
+    pub fn anonymized(&self) -> Self {
+        let names = Names::new();
+        let mut report = self.clone();
+        report.namespaces = self
+            .namespaces
+            .iter()
+            .map(|(namespace, schema)| (names.namespace(namespace), names.schema(schema)))
+            .collect();
+        for relationship in &mut report.relationships {
+            relationship.from = names.namespace(&relationship.from);
+            relationship.path = names.path(&relationship.path);
+            relationship.to = names.namespace(&relationship.to);
+        }
+        report.failures = self
+            .failures
+            .keys()
+            .map(|namespace| (names.namespace(namespace), "failed".to_string()))
+            .collect::<BTreeMap<_, _>>();
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use bson::doc;
+
+    use crate::{AnalysisOptions, CollectionSchema, TimeSeries, ViewDefinition};
+
+    fn schema(lines: &str) -> CollectionSchema {
+        let options = AnalysisOptions {
+            profile_values: true,
+            ..AnalysisOptions::default()
+        };
+        CollectionSchema::from_ndjson(lines.as_bytes(), "metrics.readings", &options).unwrap()
+    }
+
+    #[test]
+    fn hashes_paths_and_drops_values() {
+        let schema = schema(
+            r#"{"_id": 1, "sensor": {"site": "berlin"}, "tags": ["a"]}
+{"_id": 2, "sensor": {"site": "paris"}, "tags": []}
+"#,
+        );
+        let anonymized = schema.anonymized();
+        assert_ne!(anonymized.namespace, schema.namespace);
+        assert!(anonymized.fields.contains_key("_id"));
+        assert!(anonymized.fields.keys().any(|path| path.ends_with("[]")));
+        for (path, field) in &anonymized.fields {
+            assert!(
+                !path.contains("sensor") && !path.contains("site"),
+                "{}",
+                path
+            );
+            assert!(field.values.is_none(), "{} kept its values", path);
+        }
+    }
+
+    #[test]
+    fn hashes_time_series_fields() {
+        let mut schema =
+            schema(r#"{"ts": {"$date": "2024-01-01T00:00:00Z"}, "meta": {"sensor": "berlin-1"}}"#);
+        let meta_fields = schema
+            .fields
+            .iter()
+            .filter(|(path, _)| path.starts_with("meta"))
+            .map(|(path, field)| (path.clone(), field.clone()))
+            .collect::<BTreeMap<_, _>>();
+        assert!(meta_fields.values().any(|field| field.values.is_some()));
+        schema.time_series = Some(TimeSeries {
+            time_field: "ts".to_string(),
+            meta_field: Some("meta".to_string()),
+            granularity: None,
+            series: 1,
+            meta_fields,
+        });
+
+        let anonymized = schema.anonymized();
+        let time_series = anonymized.time_series.unwrap();
+        assert!(anonymized.fields.contains_key(&time_series.time_field));
+        assert_ne!(time_series.time_field, "ts");
+        let meta_field = time_series.meta_field.unwrap();
+        assert!(anonymized.fields.contains_key(&meta_field));
+        assert_eq!(time_series.meta_fields.len(), 2);
+        for (path, field) in &time_series.meta_fields {
+            assert!(path.starts_with(&meta_field) && !path.contains("sensor"));
+            assert!(field.values.is_none());
+        }
+    }
+
+    #[test]
+    fn hides_view_definitions() {
+        let mut schema = schema(r#"{"tenant": "acme"}"#);
+        schema.view = Some(ViewDefinition {
+            view_on: "readings_raw".to_string(),
+            pipeline: vec![doc! { "$match": { "tenant": "acme" } }],
+        });
+        let view = schema.anonymized().view.unwrap();
+        assert!(view.view_on.starts_with("c_") && !view.view_on.contains("readings"));
+        assert!(view.pipeline.is_empty());
+        assert_eq!(view.describe(), format!("on `{}`", view.view_on));
+    }
+}
@@ -0,0 +1,152 @@
+use std::{fs, io, path::Path};
+
+use bson::{doc, Bson, DateTime, Document};
+#[cfg(feature = "driver")]
+use mongodb::{options::FindOneOptions, Collection};
+
+use crate::{AnalyzerError, CollectionSchema, Result, SchemaDiff};
+
+impl CollectionSchema {
+    /// Save the schema as pretty JSON so it can be committed and checked against later
+    pub fn write_baseline(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json + "\n")
+    }
+
+    /// Load a schema saved by [`CollectionSchema::write_baseline`], or a JSON Schema named
+    /// after the file, see [`CollectionSchema::from_json`]
+    pub fn read_baseline(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let namespace = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        Self::from_json(&fs::read_to_string(path)?, namespace)
+    }
+
+    /// Parse a schema in the format baselines and JSON output are written in, or a
+    /// `$jsonSchema` or validator as Extended JSON, converted by
+    /// [`CollectionSchema::from_json_schema`] and named `namespace`
+    pub fn from_json(json: &str, namespace: impl Into<String>) -> io::Result<Self> {
+        let invalid = |e: String| io::Error::new(io::ErrorKind::InvalidData, e);
+        let value =
+            serde_json::from_str::<serde_json::Value>(json).map_err(|e| invalid(e.to_string()))?;
+        // `fields` isn't a JSON Schema keyword
+        if value.get("fields").is_some() {
+            return serde_json::from_value(value).map_err(|e| invalid(e.to_string()));
+        }
+        match Bson::try_from(value).map_err(|e| invalid(e.to_string()))? {
+            Bson::Document(json_schema) => Ok(Self::from_json_schema(namespace, &json_schema)),
+            _ => Err(invalid("expected a JSON object".to_string())),
+        }
+    }
+
+    /// How this (live) schema has drifted from the baseline saved at `path`
+    pub fn check_baseline(&self, path: impl AsRef<Path>) -> io::Result<SchemaDiff> {
+        Ok(Self::read_baseline(path)?.diff(self))
+    }
+
+    /// Insert the schema into `collection`, stamped with the time of the run, so past results
+    /// can be queried and diffed. Each document holds the namespace, `analyzed_at`, the sample's
+    /// size and method, the `fields` as `{ path, types, count }` for querying, and the whole
+    /// schema as the JSON a baseline file holds, field paths not being valid keys everywhere
+    #[cfg(feature = "driver")]
+    pub async fn store(&self, collection: &Collection<Document>) -> Result<()> {
+        let encoding = |e: &dyn std::fmt::Display| {
+            AnalyzerError::MalformedResult(format!("could not encode the schema: {}", e))
+        };
+        let json = serde_json::to_string(self).map_err(|e| encoding(&e))?;
+        let sample_method = bson::to_bson(&self.sample_method).map_err(|e| encoding(&e))?;
+        let fields = self
+            .fields
+            .iter()
+            .map(|(path, field)| {
+                doc! {
+                    "path": path,
+                    "types": field.types.iter().map(|ty| ty.to_string()).collect::<Vec<_>>(),
+                    "count": Bson::Int64(field.count as i64),
+                }
+            })
+            .collect::<Vec<_>>();
+        collection
+            .insert_one(
+                doc! {
+                    "namespace": &self.namespace,
+                    "analyzed_at": DateTime::now(),
+                    "document_count": Bson::Int64(self.document_count as i64),
+                    "sample_size": Bson::Int64(self.sample_size as i64),
+                    "sample_method": sample_method,
+                    "fields": fields,
+                    "schema": json,
+                },
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// The schema of `namespace` most recently stored in `collection` by
+    /// [`CollectionSchema::store`], if there is one
+    #[cfg(feature = "driver")]
+    pub async fn latest_stored(
+        collection: &Collection<Document>,
+        namespace: &str,
+    ) -> Result<Option<Self>> {
+        let options = FindOneOptions::builder()
+            .sort(doc! { "analyzed_at": -1 })
+            .build();
+        let Some(stored) = collection
+            .find_one(doc! { "namespace": namespace }, options)
+            .await?
+        else {
+            return Ok(None);
+        };
+        let json = stored.get_str("schema").map_err(|_| {
+            AnalyzerError::MalformedResult(format!("stored result for {} has no schema", namespace))
+        })?;
+        serde_json::from_str(json)
+            .map(Some)
+            .map_err(|e| AnalyzerError::MalformedResult(format!("stored schema: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnalysisOptions, BsonType};
+
+    #[test]
+    fn checks_a_schema_against_the_baseline_written_before() {
+        let options = AnalysisOptions::default();
+        let old =
+            CollectionSchema::from_ndjson(b"{\"a\": 1}\n".as_slice(), "db.coll", &options).unwrap();
+        let path = std::env::temp_dir().join(format!("baseline-{}.json", std::process::id()));
+        old.write_baseline(&path).unwrap();
+        let read = CollectionSchema::read_baseline(&path).unwrap();
+        assert_eq!((read.namespace, read.fields), (old.namespace, old.fields));
+
+        let new = CollectionSchema::from_ndjson(
+            b"{\"a\": 1, \"b\": true}\n".as_slice(),
+            "db.coll",
+            &options,
+        )
+        .unwrap();
+        let drift = new.check_baseline(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            (drift.added, drift.removed),
+            (vec!["b".to_string()], vec![])
+        );
+    }
+
+    #[test]
+    fn reads_a_json_schema_as_a_baseline() {
+        let json = r#"{"$jsonSchema": {"bsonType": "object", "required": ["name"],
+            "properties": {"name": {"bsonType": "string"}, "age": {"bsonType": "int"}}}}"#;
+        let schema = CollectionSchema::from_json(json, "db.people").unwrap();
+        assert_eq!(schema.namespace, "db.people");
+        assert_eq!(schema.fields["name"].types, [BsonType::String]);
+        assert!(schema.fields["age"].types.contains(&BsonType::Missing));
+        assert!(CollectionSchema::from_json("[1]", "db.people").is_err());
+        assert!(CollectionSchema::from_json("{", "db.people").is_err());
+    }
+}
@@ -0,0 +1,332 @@
+//! Timing the inference strategies and sample sizes against one collection, and checking
+//! whether the schemas they infer agree, for picking the ones to use
+
+use std::{fmt, time::Duration};
+
+#[cfg(feature = "driver")]
+use std::time::Instant;
+
+#[cfg(feature = "driver")]
+use bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "driver")]
+use tracing::{info, warn};
+
+#[cfg(feature = "driver")]
+use crate::{explain::number, AnalyzerError, Result, SchemaAnalyzer};
+use crate::{sizes::format_bytes, Sampling, Strategy};
+
+/// How each strategy fared at each sample size, see [`SchemaAnalyzer::benchmark`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Benchmark {
+    pub namespace: String,
+    /// The documents in the collection, as the runs counted them
+    pub document_count: u64,
+    /// How many times each run was repeated
+    pub repeat: u32,
+    /// The pipeline's runs, then the client's, each in the order the samplings were given
+    pub runs: Vec<BenchmarkRun>,
+    /// The run the others' schemas are compared with: the one sampling the most documents,
+    /// the pipeline's if both sampled as many
+    pub reference: usize,
+}
+
+/// One strategy at one sample size
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkRun {
+    pub strategy: Strategy,
+    pub sampling: Sampling,
+    /// How many documents were sampled
+    pub sample_size: u64,
+    /// How many field paths the schema has
+    pub fields: usize,
+    /// The median of the repetitions' wall-clock times, from counting the documents to
+    /// building the schema
+    #[serde(rename = "time_ms", with = "crate::schema::millis")]
+    pub time: Duration,
+    /// What the server did, averaged over the repetitions, if it reported it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server: Option<ServerLoad>,
+    /// How many paths the schema has that the reference's hasn't or the other way round,
+    /// plus those whose types differ
+    pub differences: usize,
+    /// Why the run failed, if it did. The counts are zero then
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// The growth of the server's counters over a run, from `serverStatus`. They count every
+/// client's operations, so other load on the server inflates them, and a mongos reports
+/// only what it sent itself
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServerLoad {
+    /// `metrics.queryExecutor.scannedObjects`
+    pub documents_examined: u64,
+    /// `metrics.queryExecutor.scanned`
+    pub keys_examined: u64,
+    /// `network.bytesOut`
+    pub bytes_out: u64,
+}
+
+impl ServerLoad {
+    /// These counts plus how far each grew from `before` to `after`
+    fn plus_growth(self, before: ServerLoad, after: ServerLoad) -> ServerLoad {
+        let grown = |before: u64, after: u64| after.saturating_sub(before);
+        ServerLoad {
+            documents_examined: self.documents_examined
+                + grown(before.documents_examined, after.documents_examined),
+            keys_examined: self.keys_examined + grown(before.keys_examined, after.keys_examined),
+            bytes_out: self.bytes_out + grown(before.bytes_out, after.bytes_out),
+        }
+    }
+
+    fn divided_by(self, n: u64) -> ServerLoad {
+        ServerLoad {
+            documents_examined: self.documents_examined / n,
+            keys_examined: self.keys_examined / n,
+            bytes_out: self.bytes_out / n,
+        }
+    }
+}
+
+impl Benchmark {
+    /// The fastest run whose schema is the reference's
+    pub fn fastest_agreeing(&self) -> Option<&BenchmarkRun> {
+        self.runs
+            .iter()
+            .filter(|run| run.error.is_none() && run.differences == 0)
+            .min_by_key(|run| run.time)
+    }
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Analyze the collection with the pipeline and the client-side strategy at each of
+    /// `samplings`, `repeat` times each, timing the runs and reading the server's counters
+    /// around them. Results aren't cached, so every run reads the collection. A run the
+    /// server rejects, as servers without `$sample` reject the pipeline, is recorded as
+    /// failed; only if every run fails is the first error returned, or as soon as the server
+    /// can't be reached or the credentials are refused
+    pub async fn benchmark(&self, samplings: &[Sampling], repeat: u32) -> Result<Benchmark> {
+        let repeat = repeat.max(1);
+        let mut runs = Vec::new();
+        let mut schemas = Vec::new();
+        let mut first_error = None;
+        let mut document_count = 0;
+        for strategy in [Strategy::Pipeline, Strategy::Client] {
+            for &sampling in samplings {
+                let mut options = self.options.clone();
+                options.strategy = strategy;
+                options.sampling = sampling;
+                options.cache = None;
+                options.progress = None;
+                let analyzer = SchemaAnalyzer {
+                    options,
+                    ..self.clone()
+                };
+                let mut times = Vec::new();
+                let mut load = Some(ServerLoad::default());
+                let mut outcome = None;
+                for _ in 0..repeat {
+                    let before = self.server_counters().await;
+                    let start = Instant::now();
+                    let result = analyzer.analyze().await;
+                    times.push(start.elapsed());
+                    let after = self.server_counters().await;
+                    load = match (load, before, after) {
+                        (Some(load), Some(before), Some(after)) => {
+                            Some(load.plus_growth(before, after))
+                        }
+                        _ => None,
+                    };
+                    let failed = result.is_err();
+                    outcome = Some(result);
+                    if failed {
+                        break;
+                    }
+                }
+                times.sort();
+                let time = times[times.len() / 2];
+                let run = BenchmarkRun {
+                    strategy,
+                    sampling,
+                    sample_size: 0,
+                    fields: 0,
+                    time,
+                    server: None,
+                    differences: 0,
+                    error: None,
+                };
+                match outcome.expect("every run is repeated at least once") {
+                    Ok(schema) => {
+                        let sample_size = schema.meta.as_ref().map_or(schema.sample_size, |meta| {
+                            document_count = meta.document_count;
+                            meta.sample_size
+                        });
+                        info!(
+                            namespace = %self.ns,
+                            ?strategy,
+                            sample_size,
+                            "ran in {:.1?}",
+                            time
+                        );
+                        let n = u64::from(repeat);
+                        runs.push(BenchmarkRun {
+                            sample_size,
+                            fields: schema.fields.len(),
+                            server: load.map(|load| load.divided_by(n)),
+                            ..run
+                        });
+                        schemas.push(Some(schema));
+                    }
+                    // the other runs won't get any further
+                    Err(e @ (AnalyzerError::Connection(_) | AnalyzerError::Authentication(_))) => {
+                        return Err(e)
+                    }
+                    Err(e) => {
+                        warn!(namespace = %self.ns, ?strategy, "run failed: {}", e);
+                        runs.push(BenchmarkRun {
+                            error: Some(e.to_string()),
+                            ..run
+                        });
+                        schemas.push(None);
+                        first_error.get_or_insert(e);
+                    }
+                }
+            }
+        }
+
+        // the first of the largest samples, which is the pipeline's if it has one
+        let reference = runs
+            .iter()
+            .enumerate()
+            .filter(|(_, run)| run.error.is_none())
+            .max_by(|(i, a), (j, b)| a.sample_size.cmp(&b.sample_size).then(j.cmp(i)))
+            .map(|(i, _)| i);
+        let Some(reference) = reference else {
+            return Err(first_error.expect("a run failed if none succeeded"));
+        };
+        let expected = schemas[reference]
+            .as_ref()
+            .expect("the reference succeeded");
+        for (run, schema) in runs.iter_mut().zip(&schemas) {
+            if let Some(schema) = schema {
+                let diff = expected.diff(schema);
+                run.differences = diff.added.len() + diff.removed.len() + diff.changed.len();
+            }
+        }
+        Ok(Benchmark {
+            namespace: self.ns.to_string(),
+            document_count,
+            repeat,
+            runs,
+            reference,
+        })
+    }
+
+    /// The counters of [`ServerLoad`] so far, unless the server won't say, e.g. for lack of
+    /// the `serverStatus` privilege
+    async fn server_counters(&self) -> Option<ServerLoad> {
+        let status = self
+            .client
+            .database("admin")
+            .run_command(doc! { "serverStatus": 1, "repl": 0, "locks": 0 }, None)
+            .await
+            .ok()?;
+        let executor = status
+            .get_document("metrics")
+            .and_then(|metrics| metrics.get_document("queryExecutor"))
+            .ok();
+        let counter = |section: Option<&Document>, key| section.and_then(|s| number(s, key));
+        Some(ServerLoad {
+            documents_examined: counter(executor, "scannedObjects").unwrap_or(0),
+            keys_examined: counter(executor, "scanned").unwrap_or(0),
+            bytes_out: counter(status.get_document("network").ok(), "bytesOut").unwrap_or(0),
+        })
+    }
+}
+
+impl fmt::Display for Benchmark {
+    /// A line per run, the times the median of its repetitions, then the fastest run whose
+    /// schema agrees with the largest sample's
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{}: {} documents, each run repeated {} times",
+            self.namespace, self.document_count, self.repeat
+        )?;
+        writeln!(
+            f,
+            "{:<8}  {:>16}  {:>6}  {:>10}  {:>14}  {:>10}  {:>10}  schema",
+            "strategy", "sample", "fields", "time", "docs examined", "keys", "sent"
+        )?;
+        for (i, run) in self.runs.iter().enumerate() {
+            let strategy = strategy_name(run.strategy);
+            if let Some(error) = &run.error {
+                let sample = sampling_name(run.sampling);
+                writeln!(f, "{:<8}  {:>16}  failed: {}", strategy, sample, error)?;
+                continue;
+            }
+            // a size asked for by number is what was sampled, or every document if there are fewer
+            let sample = match run.sampling {
+                Sampling::Size(_) => run.sample_size.to_string(),
+                sampling => format!("{} ({})", run.sample_size, sampling_name(sampling)),
+            };
+            let (examined, keys, sent) = match run.server {
+                Some(load) => (
+                    load.documents_examined.to_string(),
+                    load.keys_examined.to_string(),
+                    format_bytes(load.bytes_out as f64),
+                ),
+                None => ("-".to_string(), "-".to_string(), "-".to_string()),
+            };
+            let schema = if self.reference == i {
+                "reference".to_string()
+            } else if run.differences == 0 {
+                "agrees".to_string()
+            } else {
+                format!("{} differences", run.differences)
+            };
+            writeln!(
+                f,
+                "{:<8}  {:>16}  {:>6}  {:>10}  {:>14}  {:>10}  {:>10}  {}",
+                strategy,
+                sample,
+                run.fields,
+                format!("{:.1?}", run.time),
+                examined,
+                keys,
+                sent,
+                schema
+            )?;
+        }
+        if let Some(run) = self.fastest_agreeing() {
+            writeln!(
+                f,
+                "\nFastest agreeing with the largest sample: {} strategy, {} documents ({:.1?})",
+                strategy_name(run.strategy),
+                run.sample_size,
+                run.time
+            )?;
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn strategy_name(strategy: Strategy) -> &'static str {
+    match strategy {
+        Strategy::Auto => "auto",
+        Strategy::Pipeline => "pipeline",
+        Strategy::Client => "client",
+    }
+}
+
+/// How the sample size was asked for, e.g. `default` or `5%`
+pub(crate) fn sampling_name(sampling: Sampling) -> String {
+    match sampling {
+        Sampling::Auto => "default".to_string(),
+        Sampling::Size(size) => size.to_string(),
+        Sampling::Percent(percent) => format!("{}%", percent),
+        Sampling::Full => "all".to_string(),
+    }
+}
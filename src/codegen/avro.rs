@@ -0,0 +1,249 @@
+use serde_json::{json, Value};
+
+use super::model::{records, Annotation, FieldType, Record, UnionStrategy};
+use crate::{CollectionSchema, ScalarValue};
+
+impl CollectionSchema {
+    /// Generate an Avro record schema for the collection's documents, with nested records for
+    /// subdocuments. Optional or null fields become unions with `null` defaulting to null,
+    /// mixed types become unions of each type, and enum candidates enums where their values
+    /// are valid symbols. Fields are optional when present in fewer than `required_presence`
+    /// (a fraction) of the objects holding them
+    pub fn to_avro(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> Value {
+        let records = records(self, unions, required_presence, annotation);
+        let namespace = self.namespace.split_once('.').map(|(db, _)| name(db));
+        record(&records, 0, namespace)
+    }
+}
+
+fn record(records: &[Record], index: usize, namespace: Option<String>) -> Value {
+    let fields = records[index]
+        .fields
+        .iter()
+        .map(|field| {
+            let ty = avro_type(&field.ty, records);
+            let mut schema = json!({ "name": name(&field.name) });
+            if (field.optional || field.nullable) && ty != "null" {
+                // unions can't nest, so null joins a mixed type's union
+                let mut members = vec![json!("null")];
+                match ty {
+                    Value::Array(types) => members.extend(types),
+                    ty => members.push(ty),
+                }
+                schema["type"] = Value::Array(members);
+                schema["default"] = Value::Null;
+            } else {
+                schema["type"] = ty;
+            }
+            let stored =
+                (name(&field.name) != field.name).then(|| format!("Stored as {:?}", field.name));
+            if let Some(doc) = field.notes(stored) {
+                schema["doc"] = Value::String(doc);
+            }
+            schema
+        })
+        .collect::<Vec<_>>();
+    let mut schema = json!({ "type": "record", "name": records[index].name });
+    if let Some(namespace) = namespace {
+        schema["namespace"] = Value::String(namespace);
+    }
+    schema["fields"] = Value::Array(fields);
+    schema
+}
+
+fn avro_type(ty: &FieldType, records: &[Record]) -> Value {
+    match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "number" => json!("double"),
+            "int" => json!("int"),
+            "long" | "timestamp" => json!("long"),
+            "bool" => json!("boolean"),
+            "binData" => json!("bytes"),
+            "date" => json!({ "type": "long", "logicalType": "timestamp-millis" }),
+            // object ids as hex, decimals as their string form, since their scale varies
+            _ => json!("string"),
+        },
+        FieldType::Record(index) => record(records, *index, None),
+        // a subdocument that wasn't inferred, as Extended JSON
+        FieldType::Document => json!("string"),
+        FieldType::Array(elements) => json!({
+            "type": "array",
+            "items": avro_type(elements, records),
+        }),
+        // as Extended JSON, like a subdocument that wasn't inferred
+        FieldType::Union(union) if union.degraded => json!("string"),
+        FieldType::Union(union) => {
+            // a union can't hold the same type twice, e.g. for a string and an object id
+            let mut members = Vec::new();
+            for ty in &union.members {
+                let member = avro_type(ty, records);
+                if !members.contains(&member) {
+                    members.push(member);
+                }
+            }
+            match members.len() {
+                1 => members.remove(0),
+                _ => Value::Array(members),
+            }
+        }
+        FieldType::Unknown => json!("null"),
+        // enum symbols have to be valid names, so other strings stay strings
+        FieldType::Enum(enum_type)
+            if enum_type.is_string()
+                && enum_type
+                    .values
+                    .iter()
+                    .all(|value| matches!(value, ScalarValue::String(s) if name(s) == *s)) =>
+        {
+            let symbols = enum_type
+                .values
+                .iter()
+                .map(|value| match value {
+                    ScalarValue::String(s) => json!(s),
+                    ScalarValue::Int(n) => json!(n),
+                })
+                .collect::<Vec<_>>();
+            json!({ "type": "enum", "name": enum_type.name, "symbols": symbols })
+        }
+        FieldType::Enum(enum_type) => {
+            avro_type(&FieldType::Scalar(enum_type.base.clone()), records)
+        }
+    }
+}
+
+/// Avro names are limited to letters, digits and underscores, not starting with a digit
+fn name(key: &str) -> String {
+    let name = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    match name.chars().next() {
+        None => "_".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("_{}", name),
+        Some(_) => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_a_record_schema() {
+        assert_eq!(
+            fixture().to_avro(UnionStrategy::Union, 1.0, Annotation::None),
+            json!({
+              "type": "record",
+              "name": "Users",
+              "namespace": "app",
+              "fields": [
+                {
+                  "name": "_id",
+                  "type": "string"
+                },
+                {
+                  "name": "address",
+                  "type": {
+                    "type": "record",
+                    "name": "UsersAddress",
+                    "fields": [
+                      {
+                        "name": "city",
+                        "type": "string"
+                      },
+                      {
+                        "name": "zip",
+                        "type": [
+                          "null",
+                          "string"
+                        ],
+                        "default": null
+                      }
+                    ]
+                  }
+                },
+                {
+                  "name": "age",
+                  "type": "long"
+                },
+                {
+                  "name": "createdAt",
+                  "type": {
+                    "type": "long",
+                    "logicalType": "timestamp-millis"
+                  }
+                },
+                {
+                  "name": "externalId",
+                  "type": [
+                    "string",
+                    "int"
+                  ]
+                },
+                {
+                  "name": "name",
+                  "type": "string"
+                },
+                {
+                  "name": "nickname",
+                  "type": [
+                    "null",
+                    "string"
+                  ],
+                  "default": null
+                },
+                {
+                  "name": "orders",
+                  "type": {
+                    "type": "array",
+                    "items": {
+                      "type": "record",
+                      "name": "UsersOrders",
+                      "fields": [
+                        {
+                          "name": "qty",
+                          "type": "int"
+                        },
+                        {
+                          "name": "sku",
+                          "type": "string"
+                        }
+                      ]
+                    }
+                  }
+                },
+                {
+                  "name": "score",
+                  "type": "double"
+                },
+                {
+                  "name": "status",
+                  "type": {
+                    "type": "enum",
+                    "name": "UsersStatus",
+                    "symbols": [
+                      "active",
+                      "banned"
+                    ]
+                  }
+                },
+                {
+                  "name": "tags",
+                  "type": {
+                    "type": "array",
+                    "items": "string"
+                  }
+                }
+              ]
+            })
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use super::model::{
+    enums, pascal_case, records, Annotation, EnumType, FieldType, Record, UnionStrategy,
+};
+use crate::{CollectionSchema, ScalarValue};
+
+/// Words that can only be used as C# identifiers with an `@` in front
+const KEYWORDS: &[&str] = &[
+    "abstract",
+    "as",
+    "base",
+    "bool",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "checked",
+    "class",
+    "const",
+    "continue",
+    "decimal",
+    "default",
+    "delegate",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "event",
+    "explicit",
+    "extern",
+    "false",
+    "finally",
+    "fixed",
+    "float",
+    "for",
+    "foreach",
+    "goto",
+    "if",
+    "implicit",
+    "in",
+    "int",
+    "interface",
+    "internal",
+    "is",
+    "lock",
+    "long",
+    "namespace",
+    "new",
+    "null",
+    "object",
+    "operator",
+    "out",
+    "override",
+    "params",
+    "private",
+    "protected",
+    "public",
+    "readonly",
+    "ref",
+    "return",
+    "sbyte",
+    "sealed",
+    "short",
+    "sizeof",
+    "stackalloc",
+    "static",
+    "string",
+    "struct",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typeof",
+    "uint",
+    "ulong",
+    "unchecked",
+    "unsafe",
+    "ushort",
+    "using",
+    "virtual",
+    "void",
+    "volatile",
+    "while",
+];
+
+/// The driver's namespace of mapping attributes, which every class uses
+const ATTRIBUTES: &str = "MongoDB.Bson.Serialization.Attributes";
+
+impl CollectionSchema {
+    /// Generate a C# class for the collection's documents, with a nested class per
+    /// subdocument shape, mapped by the .NET driver's attributes: `[BsonId]` on `_id`,
+    /// `[BsonElement]` naming each property's key, and extra elements ignored, as the sample
+    /// may not have seen every field. Fields that were null, or present in fewer than
+    /// `required_presence` (a fraction) of the objects holding them, are nullable, the
+    /// missing ones left out when null. String enum candidates whose values are all
+    /// identifiers become enums stored by name. C# has no unions, so fields of several types
+    /// are `BsonValue`s noting the types seen
+    pub fn to_csharp(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut usings = BTreeSet::from([ATTRIBUTES]);
+        let mut body = String::new();
+        write_class(&mut body, &records[0], &records, "", &mut usings);
+        for record in &records[1..] {
+            body.push('\n');
+            write_class(&mut body, record, &records, "    ", &mut usings);
+            writeln!(body, "    }}").unwrap();
+        }
+        for enum_type in enums(&records) {
+            if let Some(members) = members(enum_type) {
+                body.push('\n');
+                writeln!(body, "    public enum {}", enum_type.name).unwrap();
+                writeln!(body, "    {{").unwrap();
+                for member in members {
+                    writeln!(body, "        {},", member).unwrap();
+                }
+                writeln!(body, "    }}").unwrap();
+            }
+        }
+        writeln!(body, "}}").unwrap();
+
+        let mut out = String::from("#nullable enable\n\n");
+        // System's namespaces first
+        let mut usings = usings.into_iter().collect::<Vec<_>>();
+        usings.sort_by_key(|using| (!using.starts_with("System"), *using));
+        for using in usings {
+            writeln!(out, "using {};", using).unwrap();
+        }
+        out.push('\n');
+        out + &body
+    }
+}
+
+/// The class's attributes, declaration and properties, leaving it open for nested types
+fn write_class(
+    out: &mut String,
+    record: &Record,
+    records: &[Record],
+    indent: &str,
+    usings: &mut BTreeSet<&'static str>,
+) {
+    writeln!(out, "{}[BsonIgnoreExtraElements]", indent).unwrap();
+    writeln!(out, "{}public class {}", indent, record.name).unwrap();
+    writeln!(out, "{}{{", indent).unwrap();
+    // a member can't be named like the class holding it
+    let mut used = HashSet::from([record.name.clone()]);
+    for (i, field) in record.fields.iter().enumerate() {
+        let mut name = pascal_case(&field.name);
+        let mut suffix = 2;
+        while !used.insert(name.clone()) {
+            name = format!("{}{}", pascal_case(&field.name), suffix);
+            suffix += 1;
+        }
+        if i > 0 {
+            out.push('\n');
+        }
+        if let Some(note) = field.notes(note(&field.ty)) {
+            writeln!(out, "{}    // {}", indent, note).unwrap();
+        }
+        if field.name == "_id" {
+            writeln!(out, "{}    [BsonId]", indent).unwrap();
+        } else {
+            writeln!(out, "{}    [BsonElement({:?})]", indent, field.name).unwrap();
+        }
+        if field.optional {
+            writeln!(out, "{}    [BsonIgnoreIfNull]", indent).unwrap();
+        }
+        if matches!(&field.ty, FieldType::Enum(enum_type) if members(enum_type).is_some()) {
+            usings.insert("MongoDB.Bson");
+            writeln!(out, "{}    [BsonRepresentation(BsonType.String)]", indent).unwrap();
+        }
+        let (mut ty, value) = csharp_type(&field.ty, records, usings);
+        // a reference type that isn't nullable is set by the driver when it's deserialized
+        let initializer = if field.optional || field.nullable {
+            ty.push('?');
+            ""
+        } else if value {
+            ""
+        } else {
+            " = null!;"
+        };
+        writeln!(
+            out,
+            "{}    public {} {} {{ get; set; }}{}",
+            indent, ty, name, initializer
+        )
+        .unwrap();
+    }
+}
+
+/// What a field's type doesn't say: the types a `BsonValue` was seen as, or the values an
+/// enum candidate that can't be an enum takes
+fn note(ty: &FieldType) -> Option<String> {
+    match ty {
+        FieldType::Union(union) => Some(format!("Seen as {}", union.labels().join(", "))),
+        FieldType::Enum(enum_type) if enum_type.is_string() && members(enum_type).is_none() => {
+            let values = enum_type
+                .values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            Some(format!("One of {}", values.join(", ")))
+        }
+        FieldType::Array(elements) => note(elements),
+        _ => None,
+    }
+}
+
+/// The members of a string enum, which are its values, unless one isn't an identifier.
+/// Keywords are escaped with an `@`, which the members' names don't include
+fn members(enum_type: &EnumType) -> Option<Vec<String>> {
+    if !enum_type.is_string() {
+        return None;
+    }
+    enum_type
+        .values
+        .iter()
+        .map(|value| match value {
+            ScalarValue::String(s) if is_identifier(s) => Some(escape(s)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn escape(ident: &str) -> String {
+    if KEYWORDS.contains(&ident) {
+        format!("@{}", ident)
+    } else {
+        ident.to_string()
+    }
+}
+
+/// The C# type, and whether it's a value type
+fn csharp_type(
+    ty: &FieldType,
+    records: &[Record],
+    usings: &mut BTreeSet<&'static str>,
+) -> (String, bool) {
+    let (ty, using, value) = match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "number" => ("double", None, true),
+            "int" => ("int", None, true),
+            "long" => ("long", None, true),
+            "bool" => ("bool", None, true),
+            "string" | "symbol" | "javascript" => ("string", None, false),
+            "binData" => ("byte[]", None, false),
+            "date" => ("DateTime", Some("System"), true),
+            "objectId" => ("ObjectId", Some("MongoDB.Bson"), true),
+            "decimal" => ("Decimal128", Some("MongoDB.Bson"), true),
+            "timestamp" => ("BsonTimestamp", Some("MongoDB.Bson"), false),
+            "regex" => ("BsonRegularExpression", Some("MongoDB.Bson"), false),
+            _ => ("BsonValue", Some("MongoDB.Bson"), false),
+        },
+        FieldType::Record(index) => return (records[*index].name.clone(), false),
+        FieldType::Document => ("BsonDocument", Some("MongoDB.Bson"), false),
+        FieldType::Array(elements) => {
+            usings.insert("System.Collections.Generic");
+            let (elements, _) = csharp_type(elements, records, usings);
+            return (format!("List<{}>", elements), false);
+        }
+        FieldType::Union(_) | FieldType::Unknown => ("BsonValue", Some("MongoDB.Bson"), false),
+        FieldType::Enum(enum_type) if members(enum_type).is_some() => {
+            return (enum_type.name.clone(), true)
+        }
+        FieldType::Enum(enum_type) => {
+            return csharp_type(&FieldType::Scalar(enum_type.base.clone()), records, usings)
+        }
+    };
+    usings.extend(using);
+    (ty.to_string(), value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_classes() {
+        assert_eq!(
+            fixture().to_csharp(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"#nullable enable
+
+using System;
+using System.Collections.Generic;
+using MongoDB.Bson;
+using MongoDB.Bson.Serialization.Attributes;
+
+[BsonIgnoreExtraElements]
+public class Users
+{
+    [BsonId]
+    public ObjectId Id { get; set; }
+
+    [BsonElement("address")]
+    public UsersAddress Address { get; set; } = null!;
+
+    [BsonElement("age")]
+    public long Age { get; set; }
+
+    [BsonElement("createdAt")]
+    public DateTime CreatedAt { get; set; }
+
+    // Seen as string, int
+    [BsonElement("externalId")]
+    public BsonValue ExternalId { get; set; } = null!;
+
+    [BsonElement("name")]
+    public string Name { get; set; } = null!;
+
+    [BsonElement("nickname")]
+    [BsonIgnoreIfNull]
+    public string? Nickname { get; set; }
+
+    [BsonElement("orders")]
+    public List<UsersOrders> Orders { get; set; } = null!;
+
+    [BsonElement("score")]
+    public double Score { get; set; }
+
+    [BsonElement("status")]
+    [BsonRepresentation(BsonType.String)]
+    public UsersStatus Status { get; set; }
+
+    [BsonElement("tags")]
+    public List<string> Tags { get; set; } = null!;
+
+    [BsonIgnoreExtraElements]
+    public class UsersAddress
+    {
+        [BsonElement("city")]
+        public string City { get; set; } = null!;
+
+        [BsonElement("zip")]
+        [BsonIgnoreIfNull]
+        public string? Zip { get; set; }
+    }
+
+    [BsonIgnoreExtraElements]
+    public class UsersOrders
+    {
+        [BsonElement("qty")]
+        public int Qty { get; set; }
+
+        [BsonElement("sku")]
+        public string Sku { get; set; } = null!;
+    }
+
+    public enum UsersStatus
+    {
+        active,
+        banned,
+    }
+}
+"#
+        );
+    }
+}
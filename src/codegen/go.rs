@@ -0,0 +1,280 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use super::model::{enums, records, words, Annotation, EnumType, FieldType, Record, UnionStrategy};
+use crate::CollectionSchema;
+
+/// Words Go spells in capitals within names, as in `UserID`
+const INITIALISMS: &[&str] = &[
+    "api", "db", "dns", "html", "http", "https", "id", "ip", "json", "sql", "ssh", "tcp", "tls",
+    "ttl", "udp", "ui", "uid", "uri", "url", "utf8", "uuid", "xml",
+];
+
+/// The driver's package of BSON-specific types
+const PRIMITIVE: &str = "go.mongodb.org/mongo-driver/bson/primitive";
+
+impl CollectionSchema {
+    /// Generate Go structs for the collection's documents, one per subdocument shape, tagged
+    /// with the keys the Go driver's `bson` package maps them to. Fields present in fewer than
+    /// `required_presence` (a fraction) of the objects holding them are `omitempty`, and
+    /// they and fields that were null are pointers, unless their type is already nillable.
+    /// String enum candidates become string types with a constant per value. Go has no
+    /// unions, so fields of several types are `interface{}`s noting the types seen
+    pub fn to_go(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut imports = BTreeSet::new();
+        let mut body = String::new();
+        for record in &records {
+            body.push('\n');
+            write_struct(&mut body, record, &records, &mut imports);
+        }
+        for enum_type in enums(&records) {
+            if enum_type.is_string() {
+                body.push('\n');
+                write_enum(&mut body, enum_type);
+            }
+        }
+
+        let mut out = String::from("package models\n");
+        // the standard library's packages, then the driver's
+        let (standard, driver): (Vec<_>, Vec<_>) = imports
+            .into_iter()
+            .partition(|import| !import.contains('.'));
+        match (standard.as_slice(), driver.as_slice()) {
+            ([], []) => {}
+            ([only], []) | ([], [only]) => writeln!(out, "\nimport {:?}", only).unwrap(),
+            _ => {
+                writeln!(out, "\nimport (").unwrap();
+                for import in &standard {
+                    writeln!(out, "\t{:?}", import).unwrap();
+                }
+                if !standard.is_empty() && !driver.is_empty() {
+                    out.push('\n');
+                }
+                for import in &driver {
+                    writeln!(out, "\t{:?}", import).unwrap();
+                }
+                writeln!(out, ")").unwrap();
+            }
+        }
+        out.push_str(&body);
+        out
+    }
+}
+
+fn write_struct(
+    out: &mut String,
+    record: &Record,
+    records: &[Record],
+    imports: &mut BTreeSet<&'static str>,
+) {
+    if record.fields.is_empty() {
+        writeln!(out, "type {} struct{{}}", record.name).unwrap();
+        return;
+    }
+    // each field's comment, name, type and tag, aligned in columns as gofmt does
+    let mut used = HashSet::new();
+    let mut lines = Vec::new();
+    for field in &record.fields {
+        let mut name = exported(&field.name);
+        let mut suffix = 2;
+        while !used.insert(name.clone()) {
+            name = format!("{}{}", exported(&field.name), suffix);
+            suffix += 1;
+        }
+        let mut ty = go_type(&field.ty, records, imports);
+        let nillable = matches!(
+            field.ty,
+            FieldType::Document | FieldType::Array(_) | FieldType::Union(_) | FieldType::Unknown
+        );
+        if (field.optional || field.nullable) && !nillable {
+            ty = format!("*{}", ty);
+        }
+        // a zero `_id` is left out, so the driver generates one on insert
+        let tag = if field.optional || field.name == "_id" {
+            format!("`bson:\"{},omitempty\"`", field.name)
+        } else {
+            format!("`bson:{:?}`", field.name)
+        };
+        let comment = field.notes(match &field.ty {
+            FieldType::Union(union) => Some(format!("Seen as {}", union.labels().join(", "))),
+            _ => None,
+        });
+        lines.push((comment, name, ty, tag));
+    }
+
+    writeln!(out, "type {} struct {{", record.name).unwrap();
+    // a comment ends a run of aligned lines
+    let mut start = 0;
+    while start < lines.len() {
+        let end = lines[start + 1..]
+            .iter()
+            .position(|(comment, ..)| comment.is_some())
+            .map_or(lines.len(), |i| start + 1 + i);
+        let run = &lines[start..end];
+        let name_width = run
+            .iter()
+            .map(|(_, name, ..)| name.chars().count())
+            .max()
+            .unwrap();
+        let type_width = run
+            .iter()
+            .map(|(_, _, ty, _)| ty.chars().count())
+            .max()
+            .unwrap();
+        for (comment, name, ty, tag) in run {
+            if let Some(comment) = comment {
+                writeln!(out, "\t// {}", comment).unwrap();
+            }
+            writeln!(
+                out,
+                "\t{:name_width$} {:type_width$} {}",
+                name,
+                ty,
+                tag,
+                name_width = name_width,
+                type_width = type_width
+            )
+            .unwrap();
+        }
+        start = end;
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+/// A string type with a constant per value, named after the type
+fn write_enum(out: &mut String, enum_type: &EnumType) {
+    writeln!(out, "type {} string", enum_type.name).unwrap();
+    out.push('\n');
+    writeln!(out, "const (").unwrap();
+    let constants = enum_type.variants(|value| format!("{}{}", enum_type.name, exported(value)));
+    let width = constants
+        .iter()
+        .map(|(name, _)| name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (name, value) in constants {
+        writeln!(
+            out,
+            "\t{:width$} {} = {:?}",
+            name,
+            enum_type.name,
+            value,
+            width = width
+        )
+        .unwrap();
+    }
+    writeln!(out, ")").unwrap();
+}
+
+fn go_type(ty: &FieldType, records: &[Record], imports: &mut BTreeSet<&'static str>) -> String {
+    let (ty, import) = match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "number" => ("float64", None),
+            "int" => ("int32", None),
+            "long" => ("int64", None),
+            "bool" => ("bool", None),
+            "string" | "symbol" | "javascript" => ("string", None),
+            "date" => ("time.Time", Some("time")),
+            "binData" => ("primitive.Binary", Some(PRIMITIVE)),
+            "objectId" => ("primitive.ObjectID", Some(PRIMITIVE)),
+            "decimal" => ("primitive.Decimal128", Some(PRIMITIVE)),
+            "timestamp" => ("primitive.Timestamp", Some(PRIMITIVE)),
+            "regex" => ("primitive.Regex", Some(PRIMITIVE)),
+            _ => ("interface{}", None),
+        },
+        FieldType::Record(index) => return records[*index].name.clone(),
+        FieldType::Document => ("bson.M", Some("go.mongodb.org/mongo-driver/bson")),
+        FieldType::Array(elements) => return format!("[]{}", go_type(elements, records, imports)),
+        FieldType::Union(_) | FieldType::Unknown => ("interface{}", None),
+        FieldType::Enum(enum_type) if enum_type.is_string() => return enum_type.name.clone(),
+        FieldType::Enum(enum_type) => {
+            return go_type(&FieldType::Scalar(enum_type.base.clone()), records, imports)
+        }
+    };
+    imports.extend(import);
+    ty.to_string()
+}
+
+/// An exported Go name: PascalCase, with initialisms in capitals
+fn exported(name: &str) -> String {
+    let name = words(name)
+        .iter()
+        .map(|word| {
+            if INITIALISMS.contains(&word.as_str()) {
+                return word.to_uppercase();
+            }
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<String>();
+    match name.chars().next() {
+        None => "Field".to_string(),
+        // names starting with a digit or a lowercase letter aren't exported
+        Some(first) if !first.is_uppercase() => format!("F{}", name),
+        Some(_) => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_structs() {
+        assert_eq!(
+            fixture().to_go(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"package models
+
+import (
+	"time"
+
+	"go.mongodb.org/mongo-driver/bson/primitive"
+)
+
+type Users struct {
+	ID        primitive.ObjectID `bson:"_id,omitempty"`
+	Address   UsersAddress       `bson:"address"`
+	Age       int64              `bson:"age"`
+	CreatedAt time.Time          `bson:"createdAt"`
+	// Seen as string, int
+	ExternalID interface{}   `bson:"externalId"`
+	Name       string        `bson:"name"`
+	Nickname   *string       `bson:"nickname,omitempty"`
+	Orders     []UsersOrders `bson:"orders"`
+	Score      float64       `bson:"score"`
+	Status     UsersStatus   `bson:"status"`
+	Tags       []string      `bson:"tags"`
+}
+
+type UsersAddress struct {
+	City string  `bson:"city"`
+	Zip  *string `bson:"zip,omitempty"`
+}
+
+type UsersOrders struct {
+	Qty int32  `bson:"qty"`
+	Sku string `bson:"sku"`
+}
+
+type UsersStatus string
+
+const (
+	UsersStatusActive UsersStatus = "active"
+	UsersStatusBanned UsersStatus = "banned"
+)
+"#
+        );
+    }
+}
@@ -0,0 +1,184 @@
+use std::{collections::BTreeSet, fmt::Write};
+
+use super::model::{
+    enums, records, snake_case, Annotation, EnumType, FieldType, Record, UnionStrategy,
+};
+use crate::CollectionSchema;
+
+impl CollectionSchema {
+    /// Generate GraphQL object types for the collection's documents, one per subdocument
+    /// shape. Fields present in at least `required_presence` (a fraction) of the objects
+    /// holding them and never null are non-null; BSON types without a
+    /// GraphQL counterpart, and fields of mixed type, use custom scalars declared up front.
+    /// String enum candidates become enums of their values in CONSTANT_CASE
+    pub fn to_graphql(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut scalars = BTreeSet::new();
+        let mut body = String::new();
+        for record in &records {
+            body.push('\n');
+            write_type(&mut body, record, &records, &mut scalars);
+        }
+        for enum_type in enums(&records) {
+            if enum_type.is_string() {
+                body.push('\n');
+                write_enum(&mut body, enum_type);
+            }
+        }
+
+        let mut out = String::new();
+        for scalar in scalars {
+            writeln!(out, "scalar {}", scalar).unwrap();
+        }
+        out.push_str(&body);
+        out.trim_start().to_string()
+    }
+}
+
+fn write_type(
+    out: &mut String,
+    record: &Record,
+    records: &[Record],
+    scalars: &mut BTreeSet<&'static str>,
+) {
+    writeln!(out, "type {} {{", record.name).unwrap();
+    for field in &record.fields {
+        let mut descriptions = field.note.iter().cloned().collect::<Vec<_>>();
+        let name = field_name(&field.name);
+        if name != field.name {
+            descriptions.push(format!("Stored as {:?}", field.name));
+        }
+        if let FieldType::Union(union) = &field.ty {
+            descriptions.push(format!("Seen as {}", union.labels().join(", ")));
+        }
+        if !descriptions.is_empty() {
+            writeln!(out, "  {:?}", descriptions.join(". ")).unwrap();
+        }
+
+        let mut ty = graphql_type(&field.ty, records, scalars);
+        if !field.optional && !field.nullable {
+            ty.push('!');
+        }
+        writeln!(out, "  {}: {}", name, ty).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+fn write_enum(out: &mut String, enum_type: &EnumType) {
+    writeln!(out, "enum {} {{", enum_type.name).unwrap();
+    for (variant, value) in enum_type.variants(enum_value) {
+        writeln!(out, "  {:?}", format!("Stored as {:?}", value)).unwrap();
+        writeln!(out, "  {}", variant).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+/// A value in CONSTANT_CASE, which also keeps clear of `true`, `false` and `null`
+fn enum_value(value: &str) -> String {
+    field_name(&snake_case(value).to_uppercase())
+}
+
+fn graphql_type(
+    ty: &FieldType,
+    records: &[Record],
+    scalars: &mut BTreeSet<&'static str>,
+) -> String {
+    let (ty, scalar) = match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "number" => ("Float", None),
+            "int" => ("Int", None),
+            // Int is only 32 bits
+            "long" => ("Long", Some("Long")),
+            "string" | "symbol" | "javascript" => ("String", None),
+            "bool" => ("Boolean", None),
+            "objectId" => ("ID", None),
+            "date" => ("DateTime", Some("DateTime")),
+            "decimal" => ("Decimal", Some("Decimal")),
+            _ => ("JSON", Some("JSON")),
+        },
+        FieldType::Record(index) => return records[*index].name.clone(),
+        FieldType::Array(elements) => {
+            return format!("[{}]", graphql_type(elements, records, scalars))
+        }
+        // unions can only hold object types
+        FieldType::Document | FieldType::Union(_) | FieldType::Unknown => ("JSON", Some("JSON")),
+        FieldType::Enum(enum_type) if enum_type.is_string() => return enum_type.name.clone(),
+        FieldType::Enum(enum_type) => {
+            return graphql_type(&FieldType::Scalar(enum_type.base.clone()), records, scalars)
+        }
+    };
+    scalars.extend(scalar);
+    ty.to_string()
+}
+
+/// GraphQL names are limited to letters, digits and underscores, not starting with a digit,
+/// and names starting with `__` are reserved for introspection
+fn field_name(key: &str) -> String {
+    let name = key
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect::<String>();
+    let name = match name.chars().next() {
+        None => "_".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("_{}", name),
+        Some(_) => name,
+    };
+    match name.strip_prefix("__") {
+        Some(rest) => format!("_{}", rest),
+        None => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_types() {
+        assert_eq!(
+            fixture().to_graphql(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"scalar DateTime
+scalar JSON
+scalar Long
+
+type Users {
+  _id: ID!
+  address: UsersAddress!
+  age: Long!
+  createdAt: DateTime!
+  "Seen as string, int"
+  externalId: JSON!
+  name: String!
+  nickname: String
+  orders: [UsersOrders]!
+  score: Float!
+  status: UsersStatus!
+  tags: [String]!
+}
+
+type UsersAddress {
+  city: String!
+  zip: String
+}
+
+type UsersOrders {
+  qty: Int!
+  sku: String!
+}
+
+enum UsersStatus {
+  "Stored as \"active\""
+  ACTIVE
+  "Stored as \"banned\""
+  BANNED
+}
+"#
+        );
+    }
+}
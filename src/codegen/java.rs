@@ -0,0 +1,307 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use super::model::{
+    block_comment, camel_case, enums, records, Annotation, EnumType, FieldType, Record,
+    UnionStrategy,
+};
+use crate::{CollectionSchema, ScalarValue};
+
+/// Words that can't be used as Java identifiers
+const KEYWORDS: &[&str] = &[
+    "_",
+    "abstract",
+    "assert",
+    "boolean",
+    "break",
+    "byte",
+    "case",
+    "catch",
+    "char",
+    "class",
+    "const",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extends",
+    "false",
+    "final",
+    "finally",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "implements",
+    "import",
+    "instanceof",
+    "int",
+    "interface",
+    "long",
+    "native",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "return",
+    "short",
+    "static",
+    "strictfp",
+    "super",
+    "switch",
+    "synchronized",
+    "this",
+    "throw",
+    "throws",
+    "transient",
+    "true",
+    "try",
+    "void",
+    "volatile",
+    "while",
+];
+
+impl CollectionSchema {
+    /// Generate a Java class for the collection's documents, with a static nested class per
+    /// subdocument shape, as POJOs the driver's `PojoCodecProvider` maps: public fields,
+    /// `@BsonId` on `_id` and `@BsonProperty` on keys that aren't field names. Fields that
+    /// were null, or present in fewer than `required_presence` (a fraction) of the objects
+    /// holding them, are boxed so they can be null. String enum candidates whose values are
+    /// all identifiers become enums, as the codec stores an enum by its constant's name. Java
+    /// has no unions, so fields of several types are `BsonValue`s noting the types seen
+    pub fn to_java(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut imports = BTreeSet::new();
+        let mut body = String::new();
+        writeln!(body, "public class {} {{", records[0].name).unwrap();
+        write_fields(&mut body, &records[0], &records, "    ", &mut imports);
+        for record in &records[1..] {
+            body.push('\n');
+            writeln!(body, "    public static class {} {{", record.name).unwrap();
+            write_fields(&mut body, record, &records, "        ", &mut imports);
+            writeln!(body, "    }}").unwrap();
+        }
+        for enum_type in enums(&records) {
+            if let Some(constants) = constants(enum_type) {
+                body.push('\n');
+                writeln!(body, "    public enum {} {{", enum_type.name).unwrap();
+                writeln!(body, "        {}", constants.join(",\n        ")).unwrap();
+                writeln!(body, "    }}").unwrap();
+            }
+        }
+        writeln!(body, "}}").unwrap();
+
+        // java.* imports, then the others
+        let mut out = String::new();
+        let mut group = None;
+        for import in imports {
+            let package = import.split('.').next();
+            if group.is_some() && group != package {
+                out.push('\n');
+            }
+            writeln!(out, "import {};", import).unwrap();
+            group = package;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out + &body
+    }
+}
+
+fn write_fields(
+    out: &mut String,
+    record: &Record,
+    records: &[Record],
+    indent: &str,
+    imports: &mut BTreeSet<&'static str>,
+) {
+    let mut used = HashSet::new();
+    for field in &record.fields {
+        let mut ident = identifier(&field.name);
+        let mut suffix = 2;
+        while !used.insert(ident.clone()) {
+            ident = format!("{}{}", identifier(&field.name), suffix);
+            suffix += 1;
+        }
+        if let Some(note) = field.notes(note(&field.ty)) {
+            writeln!(out, "{}/** {} */", indent, block_comment(&note)).unwrap();
+        }
+        if field.name == "_id" {
+            imports.insert("org.bson.codecs.pojo.annotations.BsonId");
+            writeln!(out, "{}@BsonId", indent).unwrap();
+        } else if ident != field.name {
+            imports.insert("org.bson.codecs.pojo.annotations.BsonProperty");
+            writeln!(out, "{}@BsonProperty({:?})", indent, field.name).unwrap();
+        }
+        let boxed = field.optional || field.nullable;
+        let ty = java_type(&field.ty, records, boxed, imports);
+        writeln!(out, "{}public {} {};", indent, ty, ident).unwrap();
+    }
+}
+
+/// What a field's type doesn't say: the types a `BsonValue` was seen as, or the values an
+/// enum candidate that can't be an enum takes
+fn note(ty: &FieldType) -> Option<String> {
+    match ty {
+        FieldType::Union(union) => Some(format!("Seen as {}", union.labels().join(", "))),
+        FieldType::Enum(enum_type) if enum_type.is_string() && constants(enum_type).is_none() => {
+            let values = enum_type
+                .values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            Some(format!("One of {}", values.join(", ")))
+        }
+        FieldType::Array(elements) => note(elements),
+        _ => None,
+    }
+}
+
+/// The constants of a string enum, which are its values, unless one isn't an identifier
+fn constants(enum_type: &EnumType) -> Option<Vec<&str>> {
+    if !enum_type.is_string() {
+        return None;
+    }
+    enum_type
+        .values
+        .iter()
+        .map(|value| match value {
+            ScalarValue::String(s) if is_identifier(s) && !KEYWORDS.contains(&s.as_str()) => {
+                Some(s.as_str())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_' || first == '$')
+        && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// `boxed` types can be null, the element types of lists always are
+fn java_type(
+    ty: &FieldType,
+    records: &[Record],
+    boxed: bool,
+    imports: &mut BTreeSet<&'static str>,
+) -> String {
+    let (ty, import) = match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "number" if boxed => ("Double", None),
+            "double" | "number" => ("double", None),
+            "int" if boxed => ("Integer", None),
+            "int" => ("int", None),
+            "long" if boxed => ("Long", None),
+            "long" => ("long", None),
+            "bool" if boxed => ("Boolean", None),
+            "bool" => ("boolean", None),
+            "string" | "symbol" | "javascript" => ("String", None),
+            "binData" => ("Binary", Some("org.bson.types.Binary")),
+            "objectId" => ("ObjectId", Some("org.bson.types.ObjectId")),
+            "date" => ("Instant", Some("java.time.Instant")),
+            "decimal" => ("Decimal128", Some("org.bson.types.Decimal128")),
+            "timestamp" => ("BsonTimestamp", Some("org.bson.BsonTimestamp")),
+            "regex" => (
+                "BsonRegularExpression",
+                Some("org.bson.BsonRegularExpression"),
+            ),
+            _ => ("BsonValue", Some("org.bson.BsonValue")),
+        },
+        FieldType::Record(index) => return records[*index].name.clone(),
+        FieldType::Document => ("Document", Some("org.bson.Document")),
+        FieldType::Array(elements) => {
+            imports.insert("java.util.List");
+            return format!("List<{}>", java_type(elements, records, true, imports));
+        }
+        FieldType::Union(_) | FieldType::Unknown => ("BsonValue", Some("org.bson.BsonValue")),
+        FieldType::Enum(enum_type) if constants(enum_type).is_some() => {
+            return enum_type.name.clone()
+        }
+        FieldType::Enum(enum_type) => {
+            return java_type(
+                &FieldType::Scalar(enum_type.base.clone()),
+                records,
+                boxed,
+                imports,
+            )
+        }
+    };
+    imports.extend(import);
+    ty.to_string()
+}
+
+fn identifier(name: &str) -> String {
+    let ident = camel_case(name);
+    if KEYWORDS.contains(&ident.as_str()) {
+        format!("{}_", ident)
+    } else {
+        ident
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_classes() {
+        assert_eq!(
+            fixture().to_java(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"import java.time.Instant;
+import java.util.List;
+
+import org.bson.BsonValue;
+import org.bson.codecs.pojo.annotations.BsonId;
+import org.bson.types.ObjectId;
+
+public class Users {
+    @BsonId
+    public ObjectId id;
+    public UsersAddress address;
+    public long age;
+    public Instant createdAt;
+    /** Seen as string, int */
+    public BsonValue externalId;
+    public String name;
+    public String nickname;
+    public List<UsersOrders> orders;
+    public double score;
+    public UsersStatus status;
+    public List<String> tags;
+
+    public static class UsersAddress {
+        public String city;
+        public String zip;
+    }
+
+    public static class UsersOrders {
+        public int qty;
+        public String sku;
+    }
+
+    public enum UsersStatus {
+        active,
+        banned
+    }
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use super::model::{
+    block_comment, camel_case, enums, records, Annotation, EnumType, FieldType, Record,
+    UnionStrategy,
+};
+use crate::{CollectionSchema, ScalarValue};
+
+/// Words that can only be used as Kotlin identifiers between backticks
+const KEYWORDS: &[&str] = &[
+    "as",
+    "break",
+    "class",
+    "continue",
+    "do",
+    "else",
+    "false",
+    "for",
+    "fun",
+    "if",
+    "in",
+    "interface",
+    "is",
+    "null",
+    "object",
+    "package",
+    "return",
+    "super",
+    "this",
+    "throw",
+    "true",
+    "try",
+    "typealias",
+    "typeof",
+    "val",
+    "var",
+    "when",
+    "while",
+];
+
+impl CollectionSchema {
+    /// Generate Kotlin data classes for the collection's documents, one per subdocument shape,
+    /// as the Kotlin driver's data class codec maps them: `@BsonId` on `_id` and
+    /// `@BsonProperty` on keys that aren't property names. Fields that were null, or present
+    /// in fewer than `required_presence` (a fraction) of the objects holding them, are
+    /// nullable, the missing ones defaulting to `null`. String enum candidates whose values
+    /// are all identifiers become enum classes, as the codec stores an enum by its constant's
+    /// name. Kotlin has no unions, so fields of several types are `BsonValue`s noting the
+    /// types seen
+    pub fn to_kotlin(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut imports = BTreeSet::new();
+        let mut body = String::new();
+        for record in &records {
+            body.push('\n');
+            write_class(&mut body, record, &records, &mut imports);
+        }
+        for enum_type in enums(&records) {
+            if let Some(constants) = constants(enum_type) {
+                body.push('\n');
+                writeln!(body, "enum class {} {{", enum_type.name).unwrap();
+                for constant in constants {
+                    writeln!(body, "    {},", constant).unwrap();
+                }
+                writeln!(body, "}}").unwrap();
+            }
+        }
+
+        let mut out = String::new();
+        for import in imports {
+            writeln!(out, "import {}", import).unwrap();
+        }
+        out.push_str(&body);
+        out.trim_start().to_string()
+    }
+}
+
+fn write_class(
+    out: &mut String,
+    record: &Record,
+    records: &[Record],
+    imports: &mut BTreeSet<&'static str>,
+) {
+    // a data class needs a property
+    if record.fields.is_empty() {
+        writeln!(out, "class {}", record.name).unwrap();
+        return;
+    }
+    writeln!(out, "data class {}(", record.name).unwrap();
+    let mut used = HashSet::new();
+    for field in &record.fields {
+        let mut ident = escape(&camel_case(&field.name));
+        let mut suffix = 2;
+        while !used.insert(ident.clone()) {
+            ident = format!("{}{}", camel_case(&field.name), suffix);
+            suffix += 1;
+        }
+        if let Some(note) = field.notes(note(&field.ty)) {
+            writeln!(out, "    /** {} */", block_comment(&note)).unwrap();
+        }
+        let annotation = if field.name == "_id" {
+            imports.insert("org.bson.codecs.pojo.annotations.BsonId");
+            "@BsonId ".to_string()
+        } else if ident.trim_matches('`') != field.name {
+            imports.insert("org.bson.codecs.pojo.annotations.BsonProperty");
+            format!("@BsonProperty({:?}) ", field.name)
+        } else {
+            String::new()
+        };
+        let mut ty = kotlin_type(&field.ty, records, imports);
+        if field.optional || field.nullable {
+            ty.push('?');
+        }
+        let default = if field.optional { " = null" } else { "" };
+        writeln!(out, "    {}val {}: {}{},", annotation, ident, ty, default).unwrap();
+    }
+    writeln!(out, ")").unwrap();
+}
+
+/// What a field's type doesn't say: the types a `BsonValue` was seen as, or the values an
+/// enum candidate that can't be an enum takes
+fn note(ty: &FieldType) -> Option<String> {
+    match ty {
+        FieldType::Union(union) => Some(format!("Seen as {}", union.labels().join(", "))),
+        FieldType::Enum(enum_type) if enum_type.is_string() && constants(enum_type).is_none() => {
+            let values = enum_type
+                .values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            Some(format!("One of {}", values.join(", ")))
+        }
+        FieldType::Array(elements) => note(elements),
+        _ => None,
+    }
+}
+
+/// The constants of a string enum, which are its values, unless one isn't an identifier.
+/// Keywords are quoted in backticks, which the constants' names don't include
+fn constants(enum_type: &EnumType) -> Option<Vec<String>> {
+    if !enum_type.is_string() {
+        return None;
+    }
+    enum_type
+        .values
+        .iter()
+        .map(|value| match value {
+            ScalarValue::String(s) if is_identifier(s) => Some(escape(s)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_identifier(value: &str) -> bool {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .is_some_and(|first| first.is_alphabetic() || first == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+        && value != "_"
+}
+
+fn kotlin_type(ty: &FieldType, records: &[Record], imports: &mut BTreeSet<&'static str>) -> String {
+    let (ty, import) = match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "number" => ("Double", None),
+            "int" => ("Int", None),
+            "long" => ("Long", None),
+            "bool" => ("Boolean", None),
+            "string" | "symbol" | "javascript" => ("String", None),
+            "binData" => ("Binary", Some("org.bson.types.Binary")),
+            "objectId" => ("ObjectId", Some("org.bson.types.ObjectId")),
+            "date" => ("Instant", Some("java.time.Instant")),
+            "decimal" => ("Decimal128", Some("org.bson.types.Decimal128")),
+            "timestamp" => ("BsonTimestamp", Some("org.bson.BsonTimestamp")),
+            "regex" => (
+                "BsonRegularExpression",
+                Some("org.bson.BsonRegularExpression"),
+            ),
+            _ => ("BsonValue", Some("org.bson.BsonValue")),
+        },
+        FieldType::Record(index) => return records[*index].name.clone(),
+        FieldType::Document => ("Document", Some("org.bson.Document")),
+        FieldType::Array(elements) => {
+            return format!("List<{}>", kotlin_type(elements, records, imports))
+        }
+        FieldType::Union(_) | FieldType::Unknown => ("BsonValue", Some("org.bson.BsonValue")),
+        FieldType::Enum(enum_type) if constants(enum_type).is_some() => {
+            return enum_type.name.clone()
+        }
+        FieldType::Enum(enum_type) => {
+            return kotlin_type(&FieldType::Scalar(enum_type.base.clone()), records, imports)
+        }
+    };
+    imports.extend(import);
+    ty.to_string()
+}
+
+fn escape(ident: &str) -> String {
+    if KEYWORDS.contains(&ident) {
+        format!("`{}`", ident)
+    } else {
+        ident.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_data_classes() {
+        assert_eq!(
+            fixture().to_kotlin(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"import java.time.Instant
+import org.bson.BsonValue
+import org.bson.codecs.pojo.annotations.BsonId
+import org.bson.types.ObjectId
+
+data class Users(
+    @BsonId val id: ObjectId,
+    val address: UsersAddress,
+    val age: Long,
+    val createdAt: Instant,
+    /** Seen as string, int */
+    val externalId: BsonValue,
+    val name: String,
+    val nickname: String? = null,
+    val orders: List<UsersOrders>,
+    val score: Double,
+    val status: UsersStatus,
+    val tags: List<String>,
+)
+
+data class UsersAddress(
+    val city: String,
+    val zip: String? = null,
+)
+
+data class UsersOrders(
+    val qty: Int,
+    val sku: String,
+)
+
+enum class UsersStatus {
+    active,
+    banned,
+}
+"#
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Turn an inferred [`CollectionSchema`](crate::CollectionSchema) into artifacts other tools
+//! consume. Each backend adds a `to_*` method to `CollectionSchema`
+
+mod avro;
+mod csharp;
+mod go;
+mod graphql;
+mod java;
+mod kotlin;
+mod model;
+mod mongoose;
+mod openapi;
+mod proto;
+mod pydantic;
+mod rust;
+mod sql;
+mod typescript;
+mod validator;
+
+pub use model::{Annotation, UnionStrategy};
+pub use rust::RustOptions;
+pub use sql::SqlDialect;
+pub(crate) use validator::allowed_types;
+#[cfg(feature = "driver")]
+pub use validator::{apply_validator, fetch_validator};
+
+/// The collection the backends' tests generate from: a subdocument with an optional field,
+/// arrays of strings and of subdocuments, a nullable field, numbers that widen, a union and
+/// an enum candidate
+#[cfg(test)]
+pub(crate) fn fixture() -> crate::CollectionSchema {
+    let options = crate::AnalysisOptions {
+        profile_values: true,
+        ..crate::AnalysisOptions::default()
+    };
+    crate::CollectionSchema::from_ndjson(FIXTURE.as_bytes(), "app.users", &options).unwrap()
+}
+
+#[cfg(test)]
+pub(crate) const FIXTURE: &str = r#"{"_id": {"$oid": "64b000000000000000000001"}, "name": "Ada", "age": 36, "score": 9.5, "status": "active", "createdAt": {"$date": "2024-01-01T00:00:00Z"}, "address": {"city": "London", "zip": "N1"}, "tags": ["admin", "ops"], "orders": [{"sku": "A1", "qty": 2}], "nickname": null, "externalId": "A-1"}
+{"_id": {"$oid": "64b000000000000000000002"}, "name": "Grace", "age": {"$numberLong": "45"}, "score": 8, "status": "banned", "createdAt": {"$date": "2024-02-01T00:00:00Z"}, "address": {"city": "New York"}, "tags": [], "orders": [], "nickname": "amazing", "externalId": 17}
+{"_id": {"$oid": "64b000000000000000000003"}, "name": "Linus", "age": 28, "score": 7.25, "status": "active", "createdAt": {"$date": "2024-03-01T00:00:00Z"}, "address": {"city": "Helsinki", "zip": "00100"}, "tags": ["dev"], "orders": [{"sku": "B2", "qty": 1}], "externalId": "B-2"}
+{"_id": {"$oid": "64b000000000000000000004"}, "name": "Barbara", "age": 52, "score": 6.5, "status": "active", "createdAt": {"$date": "2024-04-01T00:00:00Z"}, "address": {"city": "Boston", "zip": "02134"}, "tags": [], "orders": [], "externalId": 42}
+{"_id": {"$oid": "64b000000000000000000005"}, "name": "Ken", "age": 61, "score": 9.75, "status": "banned", "createdAt": {"$date": "2024-05-01T00:00:00Z"}, "address": {"city": "Kyoto"}, "tags": ["ops"], "orders": [], "externalId": "C-3"}
+{"_id": {"$oid": "64b000000000000000000006"}, "name": "Margaret", "age": 33, "score": 8.5, "status": "active", "createdAt": {"$date": "2024-06-01T00:00:00Z"}, "address": {"city": "Paoli", "zip": "19301"}, "tags": [], "orders": [], "externalId": 7}
+"#;
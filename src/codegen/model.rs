@@ -0,0 +1,508 @@
+use std::collections::HashSet;
+
+use crate::{BsonType, CollectionSchema, ScalarValue};
+
+/// A named object type: the collection's documents or one of their subdocuments
+#[derive(Debug, Clone)]
+pub(crate) struct Record {
+    /// PascalCase type name, unique among the records of a schema
+    pub name: String,
+    pub fields: Vec<RecordField>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct RecordField {
+    /// The field's key as stored in the documents
+    pub name: String,
+    /// Where the field is in the schema, e.g. `orders[].sku`
+    pub path: String,
+    /// The field isn't [required](CollectionSchema::is_required)
+    pub optional: bool,
+    /// Some sampled documents hold an explicit null
+    pub nullable: bool,
+    pub ty: FieldType,
+    /// What was observed of the field, for a comment on it, per [`Annotation`]
+    pub note: Option<String>,
+}
+
+impl RecordField {
+    /// The field's note followed by `typed`, what a generator notes of its type, if either
+    pub fn notes(&self, typed: Option<String>) -> Option<String> {
+        match (&self.note, typed) {
+            (Some(note), Some(typed)) => Some(format!("{}; {}", note, typed)),
+            (note, typed) => note.clone().or(typed),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FieldType {
+    /// A BSON type name other than object or array
+    Scalar(String),
+    /// A subdocument, described by the record at this index
+    Record(usize),
+    /// A subdocument whose fields weren't inferred (the depth limit was reached)
+    Document,
+    Array(Box<FieldType>),
+    /// More than one type that can't be widened into one
+    Union(TypeUnion),
+    /// Only null or missing was ever seen
+    Unknown,
+    /// A string or integer field found to take only a few values
+    Enum(EnumType),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct EnumType {
+    /// PascalCase type name, unique among the records and enums of a schema
+    pub name: String,
+    /// The BSON type name the values are stored as: string, int or long
+    pub base: String,
+    pub values: Vec<ScalarValue>,
+}
+
+/// The types of a field seen as several that don't widen into one, e.g. a string or a
+/// subdocument
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TypeUnion {
+    /// PascalCase type name for backends that declare unions, unique among the records and
+    /// enums of a schema
+    pub name: String,
+    /// Never nulls or other unions
+    pub members: Vec<FieldType>,
+    /// The union is generated as the backend's type for any value, per
+    /// [`UnionStrategy::Any`]. Its subdocuments are then [`FieldType::Document`]s, so no
+    /// records go unused
+    pub degraded: bool,
+}
+
+impl TypeUnion {
+    /// The members' names as in `seen as string, object` notes, objects and arrays by kind
+    pub fn labels(&self) -> Vec<String> {
+        self.members
+            .iter()
+            .map(|ty| match ty {
+                FieldType::Scalar(name) => name.clone(),
+                FieldType::Enum(enum_type) => enum_type.base.clone(),
+                FieldType::Array(_) => "array".to_string(),
+                _ => "object".to_string(),
+            })
+            .collect()
+    }
+}
+
+/// How generated code types fields seen with several types that don't widen into one. Only
+/// backends whose languages have unions emit them; the others always fall back to their type
+/// for any value, noting the types seen
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnionStrategy {
+    /// A union of the types: a Rust enum, a TypeScript, Python, Avro or OpenAPI union
+    #[default]
+    Union,
+    /// The language's type for any value, e.g. `bson::Bson` or `unknown`
+    Any,
+    /// The type most documents hold, as though the others weren't seen
+    Dominant,
+}
+
+/// How much of what was observed generated code notes in a comment on each field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Annotation {
+    /// No comments
+    #[default]
+    None,
+    /// The field's presence and types, e.g. `present in 87% of documents; types: string, null`
+    Brief,
+    /// Its examples too, when they were collected: `...; 2 examples: "Ada", "Grace"`
+    Full,
+}
+
+impl EnumType {
+    pub fn is_string(&self) -> bool {
+        self.base == "string"
+    }
+
+    /// The string values, named by `name` and made unique with a numeric suffix
+    pub fn variants(&self, name: impl Fn(&str) -> String) -> Vec<(String, &str)> {
+        let mut used = HashSet::new();
+        self.values
+            .iter()
+            .filter_map(|value| match value {
+                ScalarValue::String(s) => Some(s.as_str()),
+                ScalarValue::Int(_) => None,
+            })
+            .map(|value| {
+                let mut variant = name(value);
+                let mut suffix = 2;
+                while !used.insert(variant.clone()) {
+                    variant = format!("{}{}", name(value), suffix);
+                    suffix += 1;
+                }
+                (variant, value)
+            })
+            .collect()
+    }
+}
+
+/// `note` made safe to put in a `/* */` comment, which it could otherwise end
+pub(crate) fn block_comment(note: &str) -> String {
+    note.replace("*/", "*\\/")
+}
+
+/// Every enum the records' fields use, in the order they were first referenced
+pub(crate) fn enums(records: &[Record]) -> Vec<&EnumType> {
+    fn collect<'a>(ty: &'a FieldType, enums: &mut Vec<&'a EnumType>) {
+        match ty {
+            FieldType::Enum(enum_type) => enums.push(enum_type),
+            FieldType::Array(elements) => collect(elements, enums),
+            FieldType::Union(union) => union.members.iter().for_each(|ty| collect(ty, enums)),
+            _ => {}
+        }
+    }
+    let mut enums = Vec::new();
+    for field in records.iter().flat_map(|record| &record.fields) {
+        collect(&field.ty, &mut enums);
+    }
+    enums
+}
+
+/// Every union the records' fields use that isn't degraded, in the order they were first
+/// referenced
+pub(crate) fn union_types(records: &[Record]) -> Vec<&TypeUnion> {
+    fn collect<'a>(ty: &'a FieldType, unions: &mut Vec<&'a TypeUnion>) {
+        match ty {
+            FieldType::Array(elements) => collect(elements, unions),
+            FieldType::Union(union) if !union.degraded => unions.push(union),
+            _ => {}
+        }
+    }
+    let mut unions = Vec::new();
+    for field in records.iter().flat_map(|record| &record.fields) {
+        collect(&field.ty, &mut unions);
+    }
+    unions
+}
+
+impl CollectionSchema {
+    /// Whether generated code and validators should treat the field at `path` as required:
+    /// it was never missing, or the share of the objects holding it that contain it is at
+    /// least `required_presence`. At 1.0 only fields that were never missing are. The fields
+    /// of subdocuments in arrays go by their
+    /// [`element_presence`](crate::FieldSchema::element_presence), when it was counted, and
+    /// the fields of other subdocuments by their
+    /// [`conditional_presence`](crate::FieldSchema::conditional_presence)
+    pub fn is_required(&self, path: &str, required_presence: f64) -> bool {
+        let Some(field) = self.fields.get(path) else {
+            return false;
+        };
+        if let Some(presence) = field.element_presence {
+            return presence.of > 0 && presence.share() >= required_presence;
+        }
+        if !field.types.contains(&BsonType::Missing) {
+            return true;
+        }
+        if let Some(presence) = field.conditional_presence {
+            return presence >= required_presence;
+        }
+        let parent = match path.rsplit_once('.') {
+            Some((parent, _)) => self.fields.get(parent).map_or(0, |parent| {
+                parent
+                    .type_counts
+                    .get(&BsonType::Object)
+                    .copied()
+                    .unwrap_or_default()
+            }),
+            None => self.sample_size,
+        };
+        parent > 0 && field.count as f64 / parent as f64 >= required_presence
+    }
+}
+
+/// Flatten the schema into records, the document root first and nested records after their
+/// parent, typing fields of several types per `unions` and noting them per `annotation`
+pub(crate) fn records(
+    schema: &CollectionSchema,
+    unions: UnionStrategy,
+    required_presence: f64,
+    annotation: Annotation,
+) -> Vec<Record> {
+    let root = schema
+        .namespace
+        .split_once('.')
+        .map_or(schema.namespace.as_str(), |(_, coll)| coll);
+    let mut builder = Builder {
+        schema,
+        unions,
+        required_presence,
+        annotation,
+        records: Vec::new(),
+        names: HashSet::new(),
+    };
+    builder.record(pascal_case(root), "");
+    builder.records
+}
+
+struct Builder<'a> {
+    schema: &'a CollectionSchema,
+    unions: UnionStrategy,
+    required_presence: f64,
+    annotation: Annotation,
+    records: Vec<Record>,
+    names: HashSet<String>,
+}
+
+impl Builder<'_> {
+    /// Add the record for the object at `path`, returning its index
+    fn record(&mut self, name: String, path: &str) -> usize {
+        let name = self.unique_name(name);
+        let index = self.records.len();
+        self.records.push(Record {
+            name: name.clone(),
+            fields: Vec::new(),
+        });
+
+        let prefix = if path.is_empty() {
+            String::new()
+        } else {
+            format!("{}.", path)
+        };
+        let children = self
+            .schema
+            .children(&prefix)
+            .map(|(field, path)| (field.to_string(), path.to_string()))
+            .collect::<Vec<_>>();
+        let fields = children
+            .into_iter()
+            .map(|(field, path)| {
+                let optional = !self.schema.is_required(&path, self.required_presence);
+                let nullable = self.schema.fields[&path].nulls() > 0;
+                let ty = self.field_type(&format!("{}{}", name, pascal_case(&field)), &path);
+                let note = self.note(&path);
+                RecordField {
+                    name: field,
+                    path,
+                    optional,
+                    nullable,
+                    ty,
+                    note,
+                }
+            })
+            .collect();
+        self.records[index].fields = fields;
+        index
+    }
+
+    fn field_type(&mut self, name: &str, path: &str) -> FieldType {
+        let field = &self.schema.fields[path];
+        let mut types = field
+            .types
+            .iter()
+            .filter(|t| !matches!(t, BsonType::Missing | BsonType::Null))
+            .map(|t| t.to_string())
+            .collect::<Vec<_>>();
+        let union = types.len() > 1 && widen_numeric(&types).is_none();
+        if union && self.unions == UnionStrategy::Dominant {
+            // the first of the most common, as types are in order
+            let count = |ty: &String| {
+                field
+                    .type_counts
+                    .iter()
+                    .find(|(t, _)| t.to_string() == *ty)
+                    .map_or(0, |(_, count)| *count)
+            };
+            let dominant = types.iter().fold(&types[0], |dominant, ty| {
+                if count(ty) > count(dominant) {
+                    ty
+                } else {
+                    dominant
+                }
+            });
+            types = vec![dominant.clone()];
+        }
+        let degraded = union && self.unions == UnionStrategy::Any;
+        // the union takes the field's name, so its subdocuments are named for their kind
+        let (union_name, member_name) = match union && self.unions != UnionStrategy::Dominant {
+            true => (
+                Some(self.unique_name(name.to_string())),
+                format!("{}Object", name),
+            ),
+            false => (None, name.to_string()),
+        };
+
+        let mut converted = types
+            .iter()
+            .map(|t| match t.as_str() {
+                "object" => {
+                    if !degraded && self.schema.children(&format!("{}.", path)).next().is_some() {
+                        FieldType::Record(self.record(member_name.clone(), path))
+                    } else {
+                        FieldType::Document
+                    }
+                }
+                "array" => {
+                    let elements = format!("{}[]", path);
+                    if !degraded && self.schema.fields.contains_key(&elements) {
+                        FieldType::Array(Box::new(self.field_type(&member_name, &elements)))
+                    } else {
+                        FieldType::Array(Box::new(FieldType::Unknown))
+                    }
+                }
+                scalar => FieldType::Scalar(scalar.to_string()),
+            })
+            .collect::<Vec<_>>();
+
+        let ty = match (converted.len(), union_name) {
+            (0, _) => FieldType::Unknown,
+            (_, Some(name)) => FieldType::Union(TypeUnion {
+                name,
+                members: converted,
+                degraded,
+            }),
+            (1, None) => converted.remove(0),
+            (_, None) => widen_numeric(&types).unwrap(),
+        };
+        match (ty, self.schema.enum_values(path)) {
+            (FieldType::Scalar(base), Some(values))
+                if matches!(base.as_str(), "string" | "int" | "long") =>
+            {
+                FieldType::Enum(EnumType {
+                    name: self.unique_name(name.to_string()),
+                    base,
+                    values: values.to_vec(),
+                })
+            }
+            (ty, _) => ty,
+        }
+    }
+
+    /// What was observed of the field at `path`, per the annotation asked for
+    fn note(&self, path: &str) -> Option<String> {
+        if self.annotation == Annotation::None {
+            return None;
+        }
+        let field = &self.schema.fields[path];
+        let presence = self.schema.presence_of(path).unwrap_or_default() * 100.0;
+        let of = match path.rsplit_once('.') {
+            None => "documents",
+            Some((parent, _)) if parent.ends_with("[]") => "elements",
+            Some(_) => "the objects holding it",
+        };
+        let types = field
+            .types
+            .iter()
+            .filter(|ty| **ty != BsonType::Missing)
+            .map(|ty| ty.to_string())
+            .collect::<Vec<_>>();
+        let mut note = format!(
+            "present in {:.0}% of {}; types: {}",
+            presence,
+            of,
+            types.join(", ")
+        );
+        let examples = field
+            .values
+            .as_ref()
+            .map_or(&[][..], |values| &values.examples[..]);
+        if self.annotation == Annotation::Full && !examples.is_empty() {
+            let shown = examples.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+            let noun = if examples.len() == 1 {
+                "example"
+            } else {
+                "examples"
+            };
+            note.push_str(&format!(
+                "; {} {}: {}",
+                examples.len(),
+                noun,
+                shown.join(", ")
+            ));
+        }
+        Some(note)
+    }
+
+    fn unique_name(&mut self, name: String) -> String {
+        let mut candidate = name.clone();
+        let mut suffix = 2;
+        while !self.names.insert(candidate.clone()) {
+            candidate = format!("{}{}", name, suffix);
+            suffix += 1;
+        }
+        candidate
+    }
+}
+
+/// A mix of integer types widens to `long`, and integers mixed with doubles to `double`
+fn widen_numeric(types: &[String]) -> Option<FieldType> {
+    if !types
+        .iter()
+        .all(|t| matches!(t.as_str(), "int" | "long" | "double"))
+    {
+        return None;
+    }
+    if types.iter().any(|t| t == "double") {
+        Some(FieldType::Scalar("double".to_string()))
+    } else {
+        Some(FieldType::Scalar("long".to_string()))
+    }
+}
+
+/// Split a field name into lowercase words at separators and lower-to-upper case changes
+pub(crate) fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut previous_lower = false;
+    for c in name.chars() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            previous_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && previous_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        previous_lower = c.is_lowercase() || c.is_ascii_digit();
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+pub(crate) fn pascal_case(name: &str) -> String {
+    let name = words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<String>();
+    match name.chars().next() {
+        None => "Field".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("T{}", name),
+        Some(_) => name,
+    }
+}
+
+pub(crate) fn snake_case(name: &str) -> String {
+    let name = words(name).join("_");
+    match name.chars().next() {
+        None => "field".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("_{}", name),
+        Some(_) => name,
+    }
+}
+
+pub(crate) fn camel_case(name: &str) -> String {
+    let name = pascal_case(name);
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => name,
+    }
+}
@@ -0,0 +1,180 @@
+use std::fmt::Write;
+
+use super::{
+    model::{records, Annotation, EnumType, FieldType, Record, UnionStrategy},
+    typescript::property_name,
+};
+use crate::CollectionSchema;
+
+impl CollectionSchema {
+    /// Generate a CommonJS module defining Mongoose schemas for the collection's documents,
+    /// one per subdocument shape, and exporting the collection's model. A field is `required`
+    /// when it was never null and at least `required_presence` (a fraction) of the objects
+    /// holding it contain it, see [`CollectionSchema::is_required`]. Enum
+    /// candidates are limited to their values with `enum`
+    pub fn to_mongoose(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let collection = self
+            .namespace
+            .split_once('.')
+            .map_or(self.namespace.as_str(), |(_, coll)| coll);
+
+        let mut out = String::from("const { Schema, model } = require(\"mongoose\");\n");
+        // subdocument schemas come after their parent, but have to be declared before it
+        for (index, record) in records.iter().enumerate().rev() {
+            out.push('\n');
+            writeln!(out, "const {} = new Schema(", variable(record)).unwrap();
+            writeln!(out, "  {{").unwrap();
+            for field in &record.fields {
+                if let Some(note) = &field.note {
+                    writeln!(out, "    // {}", note).unwrap();
+                }
+                let mut comment = String::new();
+                let mut definition = vec![format!(
+                    "type: {}",
+                    mongoose_type(&field.ty, &records, &mut comment)
+                )];
+                if let FieldType::Enum(enum_type) = &field.ty {
+                    definition.push(format!("enum: {}", enum_values(enum_type)));
+                }
+                // required also rejects null
+                if !field.optional && !field.nullable {
+                    definition.push("required: true".to_string());
+                }
+                writeln!(
+                    out,
+                    "    {}: {{ {} }},{}",
+                    property_name(&field.name),
+                    definition.join(", "),
+                    comment
+                )
+                .unwrap();
+            }
+            writeln!(out, "  }},").unwrap();
+            if index == 0 {
+                writeln!(out, "  {{ collection: {:?} }},", collection).unwrap();
+            } else {
+                // an _id is only generated for subdocuments that had one
+                writeln!(out, "  {{ _id: false }},").unwrap();
+            }
+            writeln!(out, ");").unwrap();
+        }
+
+        writeln!(
+            out,
+            "\nmodule.exports = model({:?}, {});",
+            records[0].name,
+            variable(&records[0])
+        )
+        .unwrap();
+        out
+    }
+}
+
+fn variable(record: &Record) -> String {
+    let mut chars = record.name.chars();
+    match chars.next() {
+        Some(first) => format!("{}{}Schema", first.to_lowercase(), chars.as_str()),
+        None => "schema".to_string(),
+    }
+}
+
+/// The `type` for the field, noting in `comment` when it had to fall back to `Mixed`
+fn mongoose_type(ty: &FieldType, records: &[Record], comment: &mut String) -> String {
+    match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "int" | "long" | "number" => "Number",
+            "string" | "symbol" | "javascript" => "String",
+            "bool" => "Boolean",
+            "date" => "Date",
+            "objectId" => "Schema.Types.ObjectId",
+            "decimal" => "Schema.Types.Decimal128",
+            "binData" => "Buffer",
+            _ => "Schema.Types.Mixed",
+        }
+        .to_string(),
+        FieldType::Record(index) => variable(&records[*index]),
+        FieldType::Document => "Schema.Types.Mixed".to_string(),
+        FieldType::Array(elements) => match elements.as_ref() {
+            FieldType::Enum(enum_type) => format!(
+                "[{{ type: {}, enum: {} }}]",
+                mongoose_type(elements, records, comment),
+                enum_values(enum_type)
+            ),
+            elements => format!("[{}]", mongoose_type(elements, records, comment)),
+        },
+        FieldType::Union(union) => {
+            let types = union.labels();
+            *comment = format!(" // seen as {}", types.join(", "));
+            "Schema.Types.Mixed".to_string()
+        }
+        FieldType::Unknown => "Schema.Types.Mixed".to_string(),
+        FieldType::Enum(enum_type) if enum_type.is_string() => "String".to_string(),
+        FieldType::Enum(_) => "Number".to_string(),
+    }
+}
+
+/// The values as a JavaScript array
+fn enum_values(enum_type: &EnumType) -> String {
+    let values = enum_type
+        .values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>();
+    format!("[{}]", values.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_schemas() {
+        assert_eq!(
+            fixture().to_mongoose(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"const { Schema, model } = require("mongoose");
+
+const usersOrdersSchema = new Schema(
+  {
+    qty: { type: Number, required: true },
+    sku: { type: String, required: true },
+  },
+  { _id: false },
+);
+
+const usersAddressSchema = new Schema(
+  {
+    city: { type: String, required: true },
+    zip: { type: String },
+  },
+  { _id: false },
+);
+
+const usersSchema = new Schema(
+  {
+    _id: { type: Schema.Types.ObjectId, required: true },
+    address: { type: usersAddressSchema, required: true },
+    age: { type: Number, required: true },
+    createdAt: { type: Date, required: true },
+    externalId: { type: Schema.Types.Mixed, required: true }, // seen as string, int
+    name: { type: String, required: true },
+    nickname: { type: String },
+    orders: { type: [usersOrdersSchema], required: true },
+    score: { type: Number, required: true },
+    status: { type: String, enum: ["active", "banned"], required: true },
+    tags: { type: [String], required: true },
+  },
+  { collection: "users" },
+);
+
+module.exports = model("Users", usersSchema);
+"#
+        );
+    }
+}
@@ -0,0 +1,244 @@
+use serde_json::{json, Map, Value};
+
+use super::model::{records, Annotation, FieldType, Record, UnionStrategy};
+use crate::{CollectionSchema, ScalarValue};
+
+impl CollectionSchema {
+    /// Generate an OpenAPI 3.1 fragment with a `components.schemas` entry for the collection's
+    /// documents and one per subdocument shape, referencing each other. Fields present in at
+    /// least `required_presence` (a fraction) of the objects holding them are `required`, null
+    /// joins a field's types, mixed types become `anyOf`, and enum candidates list their
+    /// values in `enum`
+    pub fn to_openapi(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> Value {
+        let records = records(self, unions, required_presence, annotation);
+        let schemas = records
+            .iter()
+            .map(|record| (record.name.clone(), object_schema(record, &records)))
+            .collect::<Map<_, _>>();
+        json!({ "components": { "schemas": schemas } })
+    }
+}
+
+fn object_schema(record: &Record, records: &[Record]) -> Value {
+    let mut properties = Map::new();
+    let mut required = Vec::new();
+    for field in &record.fields {
+        let mut schema = field_schema(&field.ty, records);
+        if field.nullable {
+            schema = nullable(schema);
+        }
+        if let (Some(note), Value::Object(schema)) = (&field.note, &mut schema) {
+            schema.insert("description".to_string(), Value::String(note.clone()));
+        }
+        properties.insert(field.name.clone(), schema);
+        if !field.optional {
+            required.push(Value::String(field.name.clone()));
+        }
+    }
+
+    let mut schema = json!({ "type": "object", "properties": properties });
+    if !required.is_empty() {
+        schema["required"] = Value::Array(required);
+    }
+    schema
+}
+
+fn field_schema(ty: &FieldType, records: &[Record]) -> Value {
+    match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" => json!({ "type": "number", "format": "double" }),
+            "number" => json!({ "type": "number" }),
+            "int" => json!({ "type": "integer", "format": "int32" }),
+            "long" => json!({ "type": "integer", "format": "int64" }),
+            "string" | "symbol" | "javascript" => json!({ "type": "string" }),
+            "bool" => json!({ "type": "boolean" }),
+            "date" => json!({ "type": "string", "format": "date-time" }),
+            "objectId" => json!({ "type": "string", "pattern": "^[0-9a-fA-F]{24}$" }),
+            "decimal" => json!({ "type": "string", "format": "decimal" }),
+            "binData" => json!({ "type": "string", "contentEncoding": "base64" }),
+            // no JSON counterpart, so anything goes
+            _ => json!({}),
+        },
+        FieldType::Record(index) => {
+            json!({ "$ref": format!("#/components/schemas/{}", records[*index].name) })
+        }
+        FieldType::Document => json!({ "type": "object" }),
+        FieldType::Array(elements) => {
+            json!({ "type": "array", "items": field_schema(elements, records) })
+        }
+        FieldType::Union(union) if union.degraded => json!({}),
+        FieldType::Union(union) => {
+            let mut members = Vec::new();
+            for ty in &union.members {
+                let member = field_schema(ty, records);
+                if !members.contains(&member) {
+                    members.push(member);
+                }
+            }
+            json!({ "anyOf": members })
+        }
+        FieldType::Unknown => json!({ "type": "null" }),
+        FieldType::Enum(enum_type) => {
+            let mut schema = field_schema(&FieldType::Scalar(enum_type.base.clone()), records);
+            schema["enum"] = enum_type
+                .values
+                .iter()
+                .map(|value| match value {
+                    ScalarValue::Int(n) => json!(n),
+                    ScalarValue::String(s) => json!(s),
+                })
+                .collect();
+            schema
+        }
+    }
+}
+
+/// Allow null as well: as a second `type` where there is one, and with `anyOf` otherwise
+fn nullable(schema: Value) -> Value {
+    match schema.get("type").and_then(Value::as_str) {
+        Some("null") => schema,
+        Some(ty) => {
+            let types = json!([ty, "null"]);
+            let mut schema = schema;
+            schema["type"] = types;
+            // an enum has to list null to allow it
+            if let Some(Value::Array(values)) = schema.get_mut("enum") {
+                values.push(Value::Null);
+            }
+            schema
+        }
+        None if schema.as_object().is_some_and(Map::is_empty) => schema,
+        None => json!({ "anyOf": [schema, { "type": "null" }] }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_component_schemas() {
+        assert_eq!(
+            fixture().to_openapi(UnionStrategy::Union, 1.0, Annotation::None),
+            json!({
+              "components": {
+                "schemas": {
+                  "Users": {
+                    "type": "object",
+                    "properties": {
+                      "_id": {
+                        "type": "string",
+                        "pattern": "^[0-9a-fA-F]{24}$"
+                      },
+                      "address": {
+                        "$ref": "#/components/schemas/UsersAddress"
+                      },
+                      "age": {
+                        "type": "integer",
+                        "format": "int64"
+                      },
+                      "createdAt": {
+                        "type": "string",
+                        "format": "date-time"
+                      },
+                      "externalId": {
+                        "anyOf": [
+                          {
+                            "type": "string"
+                          },
+                          {
+                            "type": "integer",
+                            "format": "int32"
+                          }
+                        ]
+                      },
+                      "name": {
+                        "type": "string"
+                      },
+                      "nickname": {
+                        "type": [
+                          "string",
+                          "null"
+                        ]
+                      },
+                      "orders": {
+                        "type": "array",
+                        "items": {
+                          "$ref": "#/components/schemas/UsersOrders"
+                        }
+                      },
+                      "score": {
+                        "type": "number",
+                        "format": "double"
+                      },
+                      "status": {
+                        "type": "string",
+                        "enum": [
+                          "active",
+                          "banned"
+                        ]
+                      },
+                      "tags": {
+                        "type": "array",
+                        "items": {
+                          "type": "string"
+                        }
+                      }
+                    },
+                    "required": [
+                      "_id",
+                      "address",
+                      "age",
+                      "createdAt",
+                      "externalId",
+                      "name",
+                      "orders",
+                      "score",
+                      "status",
+                      "tags"
+                    ]
+                  },
+                  "UsersAddress": {
+                    "type": "object",
+                    "properties": {
+                      "city": {
+                        "type": "string"
+                      },
+                      "zip": {
+                        "type": "string"
+                      }
+                    },
+                    "required": [
+                      "city"
+                    ]
+                  },
+                  "UsersOrders": {
+                    "type": "object",
+                    "properties": {
+                      "qty": {
+                        "type": "integer",
+                        "format": "int32"
+                      },
+                      "sku": {
+                        "type": "string"
+                      }
+                    },
+                    "required": [
+                      "qty",
+                      "sku"
+                    ]
+                  }
+                }
+              }
+            })
+        );
+    }
+}
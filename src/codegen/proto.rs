@@ -0,0 +1,266 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use super::model::{records, snake_case, Annotation, EnumType, FieldType, Record, UnionStrategy};
+use crate::CollectionSchema;
+
+impl CollectionSchema {
+    /// Generate proto3 message definitions for the collection's documents, with subdocuments
+    /// as messages nested in their parent and arrays as `repeated` fields. Null fields, and
+    /// fields present in fewer than `required_presence` (a fraction) of the objects holding
+    /// them, are `optional`, string enum candidates are enums nested like
+    /// subdocuments, and fields whose types conflict become `google.protobuf.Value` with a
+    /// comment listing the types to choose from
+    pub fn to_proto(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut imports = BTreeSet::new();
+        let mut body = String::new();
+        write_message(&mut body, &records, 0, 0, &mut imports);
+
+        let mut out = String::from("syntax = \"proto3\";\n");
+        if let Some((db, _)) = self.namespace.split_once('.') {
+            writeln!(out, "\npackage {};", snake_case(db)).unwrap();
+        }
+        if !imports.is_empty() {
+            out.push('\n');
+            for import in imports {
+                writeln!(out, "import \"{}\";", import).unwrap();
+            }
+        }
+        out.push('\n');
+        out.push_str(&body);
+        out
+    }
+}
+
+fn write_message(
+    out: &mut String,
+    records: &[Record],
+    index: usize,
+    depth: usize,
+    imports: &mut BTreeSet<&'static str>,
+) {
+    let indent = "  ".repeat(depth);
+    let record = &records[index];
+    writeln!(out, "{}message {} {{", indent, record.name).unwrap();
+    for field in &record.fields {
+        for nested in nested_records(&field.ty) {
+            write_message(out, records, nested, depth + 1, imports);
+            out.push('\n');
+        }
+        if let Some(enum_type) = string_enum(&field.ty) {
+            write_enum(out, enum_type, depth + 1);
+            out.push('\n');
+        }
+    }
+
+    let mut used = HashSet::new();
+    for (number, field) in record.fields.iter().enumerate() {
+        let (repeated, ty) = match &field.ty {
+            FieldType::Array(elements) => (true, elements.as_ref()),
+            ty => (false, ty),
+        };
+        let mut comments = Vec::new();
+        let ty = match ty {
+            FieldType::Array(_) => {
+                comments.push("nested arrays have no protobuf equivalent".to_string());
+                imports.insert("google/protobuf/struct.proto");
+                "google.protobuf.ListValue".to_string()
+            }
+            ty => message_type(ty, records, imports, &mut comments),
+        };
+        if let Some(note) = &field.note {
+            writeln!(out, "{}  // {}", indent, note).unwrap();
+        }
+        for comment in comments {
+            writeln!(out, "{}  // TODO: {}", indent, comment).unwrap();
+        }
+
+        let mut ident = snake_case(&field.name);
+        let mut suffix = 2;
+        while !used.insert(ident.clone()) {
+            ident = format!("{}_{}", snake_case(&field.name), suffix);
+            suffix += 1;
+        }
+        let label = if repeated {
+            "repeated "
+        } else if field.optional || field.nullable {
+            "optional "
+        } else {
+            ""
+        };
+        let json_name = if ident != field.name {
+            format!(" [json_name = {:?}]", field.name)
+        } else {
+            String::new()
+        };
+        writeln!(
+            out,
+            "{}  {}{} {} = {}{};",
+            indent,
+            label,
+            ty,
+            ident,
+            number + 1,
+            json_name
+        )
+        .unwrap();
+    }
+    writeln!(out, "{}}}", indent).unwrap();
+}
+
+/// Values are prefixed with the enum's name, as enum values share their parent's scope, and
+/// the first has to be zero, so it's left unspecified
+fn write_enum(out: &mut String, enum_type: &EnumType, depth: usize) {
+    let indent = "  ".repeat(depth);
+    let prefix = snake_case(&enum_type.name).to_uppercase();
+    writeln!(out, "{}enum {} {{", indent, enum_type.name).unwrap();
+    writeln!(out, "{}  {}_UNSPECIFIED = 0;", indent, prefix).unwrap();
+    for (number, (variant, value)) in enum_type
+        .variants(|value| format!("{}_{}", prefix, snake_case(value).to_uppercase()))
+        .into_iter()
+        .enumerate()
+    {
+        writeln!(
+            out,
+            "{}  {} = {}; // {:?}",
+            indent,
+            variant,
+            number + 1,
+            value
+        )
+        .unwrap();
+    }
+    writeln!(out, "{}}}", indent).unwrap();
+}
+
+/// The string enum a field's type is declared with, if any
+fn string_enum(ty: &FieldType) -> Option<&EnumType> {
+    match ty {
+        FieldType::Enum(enum_type) if enum_type.is_string() => Some(enum_type),
+        FieldType::Array(elements) => string_enum(elements),
+        _ => None,
+    }
+}
+
+/// The records a field's type is declared with, to be nested in the field's message
+fn nested_records(ty: &FieldType) -> Vec<usize> {
+    match ty {
+        FieldType::Record(index) => vec![*index],
+        FieldType::Array(elements) => nested_records(elements),
+        // mixed types are declared as Value, so their records go unused
+        _ => Vec::new(),
+    }
+}
+
+fn message_type(
+    ty: &FieldType,
+    records: &[Record],
+    imports: &mut BTreeSet<&'static str>,
+    comments: &mut Vec<String>,
+) -> String {
+    match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "number" => "double",
+            "int" => "int32",
+            "long" => "int64",
+            "bool" => "bool",
+            "binData" => "bytes",
+            "timestamp" => "uint64",
+            "date" => {
+                imports.insert("google/protobuf/timestamp.proto");
+                "google.protobuf.Timestamp"
+            }
+            // object ids as hex, decimals as their string form
+            "string" | "objectId" | "decimal" | "symbol" | "javascript" | "regex" => "string",
+            other => {
+                comments.push(format!("no protobuf type for {}", other));
+                imports.insert("google/protobuf/struct.proto");
+                "google.protobuf.Value"
+            }
+        }
+        .to_string(),
+        FieldType::Record(index) => records[*index].name.clone(),
+        FieldType::Document => {
+            imports.insert("google/protobuf/struct.proto");
+            "google.protobuf.Struct".to_string()
+        }
+        FieldType::Union(union) => {
+            let types = union.labels();
+            comments.push(format!("seen as {}, pick one type", types.join(", ")));
+            imports.insert("google/protobuf/struct.proto");
+            "google.protobuf.Value".to_string()
+        }
+        FieldType::Unknown => {
+            comments.push("only null or missing was seen".to_string());
+            imports.insert("google/protobuf/struct.proto");
+            "google.protobuf.Value".to_string()
+        }
+        FieldType::Enum(enum_type) if enum_type.is_string() => enum_type.name.clone(),
+        FieldType::Enum(enum_type) => message_type(
+            &FieldType::Scalar(enum_type.base.clone()),
+            records,
+            imports,
+            comments,
+        ),
+        FieldType::Array(_) => unreachable!("arrays are repeated fields"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_messages() {
+        assert_eq!(
+            fixture().to_proto(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"syntax = "proto3";
+
+package app;
+
+import "google/protobuf/struct.proto";
+import "google/protobuf/timestamp.proto";
+
+message Users {
+  message UsersAddress {
+    string city = 1;
+    optional string zip = 2;
+  }
+
+  message UsersOrders {
+    int32 qty = 1;
+    string sku = 2;
+  }
+
+  enum UsersStatus {
+    USERS_STATUS_UNSPECIFIED = 0;
+    USERS_STATUS_ACTIVE = 1; // "active"
+    USERS_STATUS_BANNED = 2; // "banned"
+  }
+
+  string id = 1 [json_name = "_id"];
+  UsersAddress address = 2;
+  int64 age = 3;
+  google.protobuf.Timestamp created_at = 4 [json_name = "createdAt"];
+  // TODO: seen as string, int, pick one type
+  google.protobuf.Value external_id = 5 [json_name = "externalId"];
+  string name = 6;
+  optional string nickname = 7;
+  repeated UsersOrders orders = 8;
+  double score = 9;
+  UsersStatus status = 10;
+  repeated string tags = 11;
+}
+"#
+        );
+    }
+}
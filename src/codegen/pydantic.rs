@@ -0,0 +1,252 @@
+use std::{
+    collections::{BTreeSet, HashSet},
+    fmt::Write,
+};
+
+use super::model::{records, snake_case, Annotation, FieldType, Record, UnionStrategy};
+use crate::CollectionSchema;
+
+/// Words that can't be used as Python identifiers
+const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+/// What a generated module imports, by module
+#[derive(Default)]
+struct Imports {
+    typing: BTreeSet<&'static str>,
+    bson: BTreeSet<&'static str>,
+    datetime: bool,
+    field: bool,
+}
+
+impl CollectionSchema {
+    /// Generate Pydantic v2 models for the collection's documents, one per subdocument shape,
+    /// using PyMongo's `bson` types for object ids and other BSON-specific values.
+    /// Fields present in fewer than `required_presence` (a fraction) of the objects holding
+    /// them default to `None`, null makes a field `Optional`, enum
+    /// candidates are `Literal`s of their values, and keys that aren't valid identifiers are
+    /// aliased
+    pub fn to_pydantic(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut imports = Imports::default();
+        let mut body = String::new();
+        // nested models are declared before the models using them
+        for record in records.iter().rev() {
+            body.push_str("\n\n");
+            write_model(&mut body, record, &records, &mut imports);
+        }
+
+        let mut out = String::new();
+        if imports.datetime {
+            writeln!(out, "from datetime import datetime").unwrap();
+        }
+        if !imports.typing.is_empty() {
+            let typing = imports.typing.into_iter().collect::<Vec<_>>();
+            writeln!(out, "from typing import {}", typing.join(", ")).unwrap();
+        }
+        out.push('\n');
+        if !imports.bson.is_empty() {
+            let bson = imports.bson.into_iter().collect::<Vec<_>>();
+            writeln!(out, "from bson import {}", bson.join(", ")).unwrap();
+        }
+        let pydantic = if imports.field {
+            "BaseModel, ConfigDict, Field"
+        } else {
+            "BaseModel, ConfigDict"
+        };
+        writeln!(out, "from pydantic import {}", pydantic).unwrap();
+        out.push_str(&body);
+        out.trim_start().to_string()
+    }
+}
+
+fn write_model(out: &mut String, record: &Record, records: &[Record], imports: &mut Imports) {
+    writeln!(out, "class {}(BaseModel):", record.name).unwrap();
+    // the bson types aren't pydantic models, and aliased fields can be set by either name
+    writeln!(
+        out,
+        "    model_config = ConfigDict(arbitrary_types_allowed=True, populate_by_name=True)"
+    )
+    .unwrap();
+    if record.fields.is_empty() {
+        return;
+    }
+    out.push('\n');
+
+    let mut used = HashSet::new();
+    for field in &record.fields {
+        let mut ident = identifier(&field.name);
+        let mut suffix = 2;
+        while !used.insert(ident.clone()) {
+            ident = format!("{}_{}", identifier(&field.name), suffix);
+            suffix += 1;
+        }
+
+        if let Some(note) = &field.note {
+            writeln!(out, "    # {}", note).unwrap();
+        }
+        let mut ty = python_type(&field.ty, records, imports);
+        if (field.optional || field.nullable) && ty != "Any" {
+            imports.typing.insert("Optional");
+            ty = format!("Optional[{}]", ty);
+        }
+        let mut arguments = Vec::new();
+        if field.optional {
+            arguments.push("default=None".to_string());
+        }
+        if ident != field.name {
+            arguments.push(format!("alias={:?}", field.name));
+        }
+        let default = match arguments.as_slice() {
+            [] => String::new(),
+            [only] if only == "default=None" => " = None".to_string(),
+            _ => {
+                imports.field = true;
+                format!(" = Field({})", arguments.join(", "))
+            }
+        };
+        writeln!(out, "    {}: {}{}", ident, ty, default).unwrap();
+    }
+}
+
+fn python_type(ty: &FieldType, records: &[Record], imports: &mut Imports) -> String {
+    match ty {
+        FieldType::Scalar(name) => {
+            let (ty, bson) = match name.as_str() {
+                "double" | "number" => ("float", None),
+                "int" | "long" => ("int", None),
+                "string" | "symbol" | "javascript" => ("str", None),
+                "bool" => ("bool", None),
+                "binData" => ("bytes", None),
+                "date" => {
+                    imports.datetime = true;
+                    ("datetime", None)
+                }
+                "objectId" => ("ObjectId", Some("ObjectId")),
+                "decimal" => ("Decimal128", Some("Decimal128")),
+                "timestamp" => ("Timestamp", Some("Timestamp")),
+                "regex" => ("Regex", Some("Regex")),
+                _ => {
+                    imports.typing.insert("Any");
+                    ("Any", None)
+                }
+            };
+            imports.bson.extend(bson);
+            ty.to_string()
+        }
+        FieldType::Record(index) => records[*index].name.clone(),
+        FieldType::Document => {
+            imports.typing.insert("Any");
+            "dict[str, Any]".to_string()
+        }
+        FieldType::Array(elements) => format!("list[{}]", python_type(elements, records, imports)),
+        FieldType::Union(union) if union.degraded => {
+            imports.typing.insert("Any");
+            "Any".to_string()
+        }
+        FieldType::Union(union) => {
+            let mut members = Vec::new();
+            for ty in &union.members {
+                let member = python_type(ty, records, imports);
+                if !members.contains(&member) {
+                    members.push(member);
+                }
+            }
+            match members.len() {
+                1 => members.remove(0),
+                _ => {
+                    imports.typing.insert("Union");
+                    format!("Union[{}]", members.join(", "))
+                }
+            }
+        }
+        FieldType::Unknown => {
+            imports.typing.insert("Any");
+            "Any".to_string()
+        }
+        FieldType::Enum(enum_type) => {
+            imports.typing.insert("Literal");
+            // JSON string and integer literals are valid Python literals too
+            let values = enum_type
+                .values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            format!("Literal[{}]", values.join(", "))
+        }
+    }
+}
+
+fn identifier(name: &str) -> String {
+    let ident = snake_case(name);
+    // a leading underscore would make pydantic treat the field as private
+    let ident = ident.trim_start_matches('_').to_string();
+    let ident = match ident.chars().next() {
+        None => "field".to_string(),
+        Some(first) if first.is_ascii_digit() => format!("f_{}", ident),
+        Some(_) => ident,
+    };
+    if KEYWORDS.contains(&ident.as_str()) {
+        format!("{}_", ident)
+    } else {
+        ident
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_models() {
+        assert_eq!(
+            fixture().to_pydantic(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"from datetime import datetime
+from typing import Literal, Optional, Union
+
+from bson import ObjectId
+from pydantic import BaseModel, ConfigDict, Field
+
+
+class UsersOrders(BaseModel):
+    model_config = ConfigDict(arbitrary_types_allowed=True, populate_by_name=True)
+
+    qty: int
+    sku: str
+
+
+class UsersAddress(BaseModel):
+    model_config = ConfigDict(arbitrary_types_allowed=True, populate_by_name=True)
+
+    city: str
+    zip: Optional[str] = None
+
+
+class Users(BaseModel):
+    model_config = ConfigDict(arbitrary_types_allowed=True, populate_by_name=True)
+
+    id: ObjectId = Field(alias="_id")
+    address: UsersAddress
+    age: int
+    created_at: datetime = Field(alias="createdAt")
+    external_id: Union[str, int] = Field(alias="externalId")
+    name: str
+    nickname: Optional[str] = None
+    orders: list[UsersOrders]
+    score: float
+    status: Literal["active", "banned"]
+    tags: list[str]
+"#
+        );
+    }
+}
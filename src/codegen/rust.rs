@@ -0,0 +1,511 @@
+use std::{collections::HashSet, fmt::Write};
+
+use super::model::{
+    enums, pascal_case, records, snake_case, union_types, Annotation, EnumType, FieldType, Record,
+    TypeUnion, UnionStrategy,
+};
+use crate::CollectionSchema;
+
+/// Words that can only be used as Rust identifiers in their raw form
+const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "dyn", "else", "enum", "extern", "false",
+    "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref",
+    "return", "static", "struct", "trait", "true", "type", "unsafe", "use", "where", "while",
+    "abstract", "become", "box", "do", "final", "macro", "override", "priv", "typeof", "unsized",
+    "virtual", "yield", "try", "gen",
+];
+
+/// Keywords that aren't allowed even as raw identifiers
+const RESERVED: &[&str] = &["crate", "self", "super"];
+
+/// Deserializes fields that can be both missing and null, keeping the two apart
+const NULLABLE_HELPER: &str = r#"/// Reads a field that may be missing or null into `None` or `Some(None)`
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+"#;
+
+/// How [`CollectionSchema::to_rust_with`] generates structs
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RustOptions {
+    /// Type fields that were null apart from fields that were missing: an always present,
+    /// sometimes null field is an `Option` serialized as null, a sometimes missing one an
+    /// `Option` left out when `None`, and one that is both an `Option<Option<_>>` whose
+    /// `Some(None)` is null. Otherwise either is an `Option` left out when `None`
+    pub separate_nulls: bool,
+    /// Also emit a test deserializing a document put together from the examples collected,
+    /// see [`AnalysisOptions::examples`](crate::AnalysisOptions::examples), into the
+    /// document struct. Left out if a required field has no example. The test parses the
+    /// document's Extended JSON with `serde_json`
+    pub tests: bool,
+}
+
+impl CollectionSchema {
+    /// Generate serde-compatible Rust structs for the collection's documents, one per
+    /// subdocument shape. Fields that were null, or present in fewer than `required_presence`
+    /// (a fraction) of the objects holding them, become `Option`s, string enum candidates
+    /// enums, and fields of several types untagged enums unless `unions` says otherwise
+    pub fn to_rust(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        self.to_rust_with(
+            unions,
+            required_presence,
+            annotation,
+            &RustOptions::default(),
+        )
+    }
+
+    /// [`CollectionSchema::to_rust`], generating as `options` say
+    pub fn to_rust_with(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+        options: &RustOptions,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut out = String::from("use serde::{Deserialize, Serialize};\n");
+        for record in &records {
+            out.push('\n');
+            write_struct(&mut out, record, &records, options);
+        }
+        for union in union_types(&records) {
+            out.push('\n');
+            write_union(&mut out, union, &records);
+        }
+        for enum_type in enums(&records) {
+            if enum_type.is_string() {
+                out.push('\n');
+                write_enum(&mut out, enum_type);
+            }
+        }
+        let nullable = |record: &Record| {
+            record
+                .fields
+                .iter()
+                .any(|field| field.optional && field.nullable)
+        };
+        if options.separate_nulls && records.iter().any(nullable) {
+            out.push('\n');
+            out.push_str(NULLABLE_HELPER);
+        }
+        if options.tests {
+            if let Some(example) = example(self, &records, 0) {
+                out.push('\n');
+                write_test(&mut out, &records[0].name, &example);
+            }
+        }
+        out
+    }
+}
+
+fn write_struct(out: &mut String, record: &Record, records: &[Record], options: &RustOptions) {
+    writeln!(
+        out,
+        "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]"
+    )
+    .unwrap();
+    writeln!(out, "pub struct {} {{", record.name).unwrap();
+    let mut used = HashSet::new();
+    for field in &record.fields {
+        // keys like `userId` and `user_id` both map to `user_id`
+        let mut ident = identifier(&field.name);
+        let mut suffix = 2;
+        while !used.insert(ident.clone()) {
+            ident = format!("{}_{}", identifier(&field.name), suffix);
+            suffix += 1;
+        }
+        if let Some(note) = &field.note {
+            writeln!(out, "    /// {}", note).unwrap();
+        }
+        if ident.trim_start_matches("r#") != field.name {
+            writeln!(out, "    #[serde(rename = {:?})]", field.name).unwrap();
+        }
+        let mut ty = rust_type(&field.ty, records);
+        match (field.optional, field.nullable) {
+            (true, true) if options.separate_nulls => {
+                writeln!(
+                    out,
+                    "    #[serde(default, deserialize_with = \"nullable\", skip_serializing_if = \
+                     \"Option::is_none\")]"
+                )
+                .unwrap();
+                ty = format!("Option<Option<{}>>", ty);
+            }
+            // serialized as null rather than left out
+            (false, true) if options.separate_nulls => ty = format!("Option<{}>", ty),
+            (false, false) => {}
+            _ => {
+                writeln!(
+                    out,
+                    "    #[serde(default, skip_serializing_if = \"Option::is_none\")]"
+                )
+                .unwrap();
+                ty = format!("Option<{}>", ty);
+            }
+        }
+        writeln!(out, "    pub {}: {},", ident, ty).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+fn write_enum(out: &mut String, enum_type: &EnumType) {
+    writeln!(
+        out,
+        "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]"
+    )
+    .unwrap();
+    writeln!(out, "pub enum {} {{", enum_type.name).unwrap();
+    for (variant, value) in enum_type.variants(|value| match pascal_case(value).as_str() {
+        "Self" => "Self_".to_string(),
+        variant => variant.to_string(),
+    }) {
+        if variant != value {
+            writeln!(out, "    #[serde(rename = {:?})]", value).unwrap();
+        }
+        writeln!(out, "    {},", variant).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+/// An untagged enum, so serde tries each member's type in turn
+fn write_union(out: &mut String, union: &TypeUnion, records: &[Record]) {
+    writeln!(
+        out,
+        "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]"
+    )
+    .unwrap();
+    writeln!(out, "#[serde(untagged)]").unwrap();
+    writeln!(out, "pub enum {} {{", union.name).unwrap();
+    let mut used = HashSet::new();
+    for (member, label) in union.members.iter().zip(union.labels()) {
+        let mut variant = pascal_case(&label);
+        let mut suffix = 2;
+        while !used.insert(variant.clone()) {
+            variant = format!("{}{}", pascal_case(&label), suffix);
+            suffix += 1;
+        }
+        writeln!(out, "    {}({}),", variant, rust_type(member, records)).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+/// A test deserializing `example` into the struct `name`
+fn write_test(out: &mut String, name: &str, example: &serde_json::Value) {
+    let example = serde_json::to_string_pretty(example).unwrap();
+    // enough hashes that the raw string can't end early
+    let mut hashes = "#".to_string();
+    while example.contains(&format!("\"{}", hashes)) {
+        hashes.push('#');
+    }
+    writeln!(out, "#[cfg(test)]").unwrap();
+    writeln!(out, "mod tests {{").unwrap();
+    writeln!(out, "    use super::*;").unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "    /// A document put together from the examples the analysis collected"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "    const EXAMPLE: &str = r{}\"{}\"{};",
+        hashes, example, hashes
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "    #[test]").unwrap();
+    writeln!(out, "    fn deserializes_an_example() {{").unwrap();
+    writeln!(
+        out,
+        "        let json = serde_json::from_str::<serde_json::Value>(EXAMPLE).unwrap();"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "        let document = bson::Bson::try_from(json).unwrap();"
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "        let parsed = bson::from_bson::<{}>(document).unwrap();",
+        name
+    )
+    .unwrap();
+    writeln!(out, "        bson::to_document(&parsed).unwrap();").unwrap();
+    writeln!(out, "    }}").unwrap();
+    writeln!(out, "}}").unwrap();
+}
+
+/// A document for the record at `index` from the fields' examples, or `None` if a required
+/// field has none
+fn example(
+    schema: &CollectionSchema,
+    records: &[Record],
+    index: usize,
+) -> Option<serde_json::Value> {
+    let mut document = serde_json::Map::new();
+    for field in &records[index].fields {
+        match example_value(schema, records, &field.ty, &field.path) {
+            Some(value) => {
+                document.insert(field.name.clone(), value);
+            }
+            None if field.optional => {}
+            None if field.nullable => {
+                document.insert(field.name.clone(), serde_json::Value::Null);
+            }
+            None => return None,
+        }
+    }
+    Some(serde_json::Value::Object(document))
+}
+
+fn example_value(
+    schema: &CollectionSchema,
+    records: &[Record],
+    ty: &FieldType,
+    path: &str,
+) -> Option<serde_json::Value> {
+    match ty {
+        FieldType::Record(index) => example(schema, records, *index),
+        FieldType::Document => Some(serde_json::Value::Object(serde_json::Map::new())),
+        // an empty array when the elements have no examples
+        FieldType::Array(elements) => Some(serde_json::Value::Array(
+            example_value(schema, records, elements, &format!("{}[]", path))
+                .into_iter()
+                .collect(),
+        )),
+        _ => schema.fields[path]
+            .values
+            .as_ref()?
+            .examples
+            .first()
+            .cloned(),
+    }
+}
+
+fn rust_type(ty: &FieldType, records: &[Record]) -> String {
+    match ty {
+        FieldType::Scalar(name) => match name.as_str() {
+            "double" | "number" => "f64",
+            "string" => "String",
+            "binData" => "bson::Binary",
+            "objectId" => "bson::oid::ObjectId",
+            "bool" => "bool",
+            "date" => "bson::DateTime",
+            "regex" => "bson::Regex",
+            "int" => "i32",
+            "timestamp" => "bson::Timestamp",
+            "long" => "i64",
+            "decimal" => "bson::Decimal128",
+            _ => "bson::Bson",
+        }
+        .to_string(),
+        FieldType::Record(index) => records[*index].name.clone(),
+        FieldType::Document => "bson::Document".to_string(),
+        FieldType::Array(elements) => format!("Vec<{}>", rust_type(elements, records)),
+        FieldType::Union(union) if !union.degraded => union.name.clone(),
+        FieldType::Union(_) | FieldType::Unknown => "bson::Bson".to_string(),
+        FieldType::Enum(enum_type) if enum_type.is_string() => enum_type.name.clone(),
+        // a plain integer, as serde can't map integers to variants without serde_repr
+        FieldType::Enum(enum_type) => {
+            rust_type(&FieldType::Scalar(enum_type.base.clone()), records)
+        }
+    }
+}
+
+fn identifier(name: &str) -> String {
+    let ident = snake_case(name);
+    if KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{}", ident)
+    } else if RESERVED.contains(&ident.as_str()) {
+        format!("{}_", ident)
+    } else {
+        ident
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_structs() {
+        assert_eq!(
+            fixture().to_rust(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Users {
+    #[serde(rename = "_id")]
+    pub id: bson::oid::ObjectId,
+    pub address: UsersAddress,
+    pub age: i64,
+    #[serde(rename = "createdAt")]
+    pub created_at: bson::DateTime,
+    #[serde(rename = "externalId")]
+    pub external_id: UsersExternalId,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nickname: Option<String>,
+    pub orders: Vec<UsersOrders>,
+    pub score: f64,
+    pub status: UsersStatus,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsersAddress {
+    pub city: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub zip: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsersOrders {
+    pub qty: i32,
+    pub sku: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum UsersExternalId {
+    String(String),
+    Int(i32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum UsersStatus {
+    #[serde(rename = "active")]
+    Active,
+    #[serde(rename = "banned")]
+    Banned,
+}
+"#
+        );
+    }
+
+    /// A field seen as a string everywhere, also as null, missing from some documents, and
+    /// both
+    const NULLS: &str =
+        "{\"always\": \"a\", \"nullable\": null, \"optional\": \"a\", \"both\": null}\n\
+                         {\"always\": \"b\", \"nullable\": \"b\", \"both\": \"b\"}\n\
+                         {\"always\": \"c\", \"nullable\": \"c\"}\n";
+
+    fn nulls() -> CollectionSchema {
+        CollectionSchema::from_ndjson(NULLS.as_bytes(), "app.nulls", &Default::default()).unwrap()
+    }
+
+    #[test]
+    fn types_nullable_and_optional_fields_apart_when_asked() {
+        let options = RustOptions {
+            separate_nulls: true,
+            ..RustOptions::default()
+        };
+        let rust = nulls().to_rust_with(UnionStrategy::Union, 1.0, Annotation::None, &options);
+        assert!(rust.contains("    pub always: String,\n"), "{}", rust);
+        assert!(
+            rust.contains("    pub nullable: Option<String>,\n")
+                && !rust.contains("is_none\")]\n    pub nullable"),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains(
+                "    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n    \
+                 pub optional: Option<String>,\n"
+            ),
+            "{}",
+            rust
+        );
+        assert!(
+            rust.contains(
+                "    #[serde(default, deserialize_with = \"nullable\", skip_serializing_if = \
+                 \"Option::is_none\")]\n    pub both: Option<Option<String>>,\n"
+            ),
+            "{}",
+            rust
+        );
+        assert!(rust.contains(NULLABLE_HELPER));
+    }
+
+    #[test]
+    fn types_nullable_and_optional_fields_alike_by_default() {
+        let rust = nulls().to_rust(UnionStrategy::Union, 1.0, Annotation::None);
+        for field in ["nullable", "optional", "both"] {
+            assert!(
+                rust.contains(&format!(
+                    "    #[serde(default, skip_serializing_if = \"Option::is_none\")]\n    \
+                     pub {}: Option<String>,\n",
+                    field
+                )),
+                "{}",
+                rust
+            );
+        }
+        assert!(rust.contains("    pub always: String,\n"));
+        assert!(!rust.contains("fn nullable"));
+    }
+
+    #[test]
+    fn emits_a_test_deserializing_an_example_into_the_document_struct() {
+        let options = crate::AnalysisOptions {
+            examples: 1,
+            ..crate::AnalysisOptions::default()
+        };
+        let schema = CollectionSchema::from_ndjson(
+            crate::codegen::FIXTURE.as_bytes(),
+            "app.users",
+            &options,
+        )
+        .unwrap();
+        let rust = schema.to_rust_with(
+            UnionStrategy::Union,
+            1.0,
+            Annotation::None,
+            &RustOptions {
+                tests: true,
+                ..RustOptions::default()
+            },
+        );
+        let test = &rust[rust.find("#[cfg(test)]").unwrap()..];
+        assert!(
+            test.contains("bson::from_bson::<Users>(document)"),
+            "{}",
+            test
+        );
+        let example = test
+            .split_once("r#\"")
+            .and_then(|(_, rest)| rest.split_once("\"#;"))
+            .unwrap()
+            .0;
+        let example = serde_json::from_str::<serde_json::Value>(example).unwrap();
+        assert_eq!(example["name"], "Ada");
+        assert_eq!(example["_id"]["$oid"], "64b000000000000000000001");
+        assert_eq!(example["address"]["city"], "London");
+        assert_eq!(example["orders"][0]["sku"], "A1");
+        // the first example, which for a nullable field can be null
+        assert_eq!(example["nickname"], serde_json::Value::Null);
+
+        // nothing to put a document together from
+        let rust = fixture().to_rust_with(
+            UnionStrategy::Union,
+            1.0,
+            Annotation::None,
+            &RustOptions {
+                tests: true,
+                ..RustOptions::default()
+            },
+        );
+        assert!(!rust.contains("#[cfg(test)]"));
+    }
+}
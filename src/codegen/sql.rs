@@ -0,0 +1,340 @@
+use std::{collections::HashSet, fmt::Write};
+
+use super::model::{
+    records, snake_case, Annotation, FieldType, Record, RecordField, UnionStrategy,
+};
+use crate::{CollectionSchema, ScalarValue};
+
+/// The database [`CollectionSchema::to_sql`] writes DDL for
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SqlDialect {
+    #[default]
+    Postgres,
+    Mysql,
+    Sqlite,
+}
+
+impl CollectionSchema {
+    /// Generate a `CREATE TABLE` statement approximating the collection. Subdocuments of
+    /// scalars are flattened into `parent_child` columns, and deeper subdocuments, arrays
+    /// without a native column type and fields of mixed type are stored as JSON, each with a
+    /// comment flagging it for review. Columns are `NOT NULL` when the field was never null
+    /// and present in at least `required_presence` (a fraction) of the objects holding it,
+    /// enum candidates are limited to their values (with MySQL's `ENUM` for strings, `CHECK`
+    /// otherwise), and `_id` is the primary key
+    pub fn to_sql(
+        &self,
+        dialect: SqlDialect,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut columns = Vec::new();
+        for field in &records[0].fields {
+            let primary_key = field.name == "_id";
+            match &field.ty {
+                FieldType::Record(index) if is_flat(&records[*index]) => {
+                    for child in &records[*index].fields {
+                        let mut column = column(child, dialect, false);
+                        column.name = format!("{}_{}", snake_case(&field.name), column.name);
+                        column.not_null &= !field.optional && !field.nullable;
+                        columns.push(column);
+                    }
+                }
+                FieldType::Record(_) => {
+                    let mut column = column(field, dialect, primary_key);
+                    column.ty = json_type(dialect).to_string();
+                    column.comments.push(
+                        "subdocument with nested or mixed fields, stored as JSON".to_string(),
+                    );
+                    columns.push(column);
+                }
+                _ => columns.push(column(field, dialect, primary_key)),
+            }
+        }
+
+        let table = self
+            .namespace
+            .split_once('.')
+            .map_or(self.namespace.as_str(), |(_, coll)| coll);
+        let mut out = String::new();
+        writeln!(out, "-- approximated from {}", self.namespace).unwrap();
+        writeln!(out, "CREATE TABLE {} (", quote(&snake_case(table), dialect)).unwrap();
+        let mut used = HashSet::new();
+        for (i, column) in columns.iter().enumerate() {
+            if let Some(note) = &column.note {
+                writeln!(out, "    -- {}", note).unwrap();
+            }
+            for comment in &column.comments {
+                writeln!(out, "    -- TODO: {}", comment).unwrap();
+            }
+            let mut name = column.name.clone();
+            let mut suffix = 2;
+            while !used.insert(name.clone()) {
+                name = format!("{}_{}", column.name, suffix);
+                suffix += 1;
+            }
+            write!(out, "    {} {}", quote(&name, dialect), column.ty).unwrap();
+            if column.primary_key {
+                write!(out, " PRIMARY KEY").unwrap();
+            } else if column.not_null {
+                write!(out, " NOT NULL").unwrap();
+            }
+            if !column.allowed.is_empty() {
+                write!(
+                    out,
+                    " CHECK ({} IN ({}))",
+                    quote(&name, dialect),
+                    column.allowed.join(", ")
+                )
+                .unwrap();
+            }
+            writeln!(out, "{}", if i + 1 < columns.len() { "," } else { "" }).unwrap();
+        }
+        writeln!(out, ");").unwrap();
+        out
+    }
+}
+
+struct Column {
+    name: String,
+    ty: String,
+    not_null: bool,
+    primary_key: bool,
+    /// The only values the column may hold, as SQL literals, or empty for any
+    allowed: Vec<String>,
+    /// Why the field doesn't map cleanly onto a column
+    comments: Vec<String>,
+    /// What was observed of the field
+    note: Option<String>,
+}
+
+/// A subdocument of scalars only, which can be spread over its parent's columns
+fn is_flat(record: &Record) -> bool {
+    record
+        .fields
+        .iter()
+        .all(|field| matches!(field.ty, FieldType::Scalar(_) | FieldType::Enum(_)))
+}
+
+fn column(field: &RecordField, dialect: SqlDialect, primary_key: bool) -> Column {
+    let mut comments = Vec::new();
+    let mut allowed = Vec::new();
+    let ty = match &field.ty {
+        FieldType::Scalar(name) => scalar_type(name, dialect, primary_key, &mut comments),
+        FieldType::Enum(enum_type) => {
+            let literals = enum_type
+                .values
+                .iter()
+                .map(|value| match value {
+                    ScalarValue::Int(n) => n.to_string(),
+                    ScalarValue::String(s) => format!("'{}'", s.replace('\'', "''")),
+                })
+                .collect::<Vec<_>>();
+            if dialect == SqlDialect::Mysql && enum_type.is_string() && !primary_key {
+                format!("ENUM({})", literals.join(", "))
+            } else {
+                allowed = literals;
+                scalar_type(&enum_type.base, dialect, primary_key, &mut comments)
+            }
+        }
+        FieldType::Array(elements) => match (elements.as_ref(), dialect) {
+            (FieldType::Scalar(name), SqlDialect::Postgres) => {
+                format!("{}[]", scalar_type(name, dialect, false, &mut comments))
+            }
+            (FieldType::Enum(enum_type), SqlDialect::Postgres) => format!(
+                "{}[]",
+                scalar_type(&enum_type.base, dialect, false, &mut comments)
+            ),
+            (FieldType::Scalar(_) | FieldType::Enum(_), _) => json_type(dialect).to_string(),
+            (FieldType::Record(_) | FieldType::Document, _) => {
+                comments.push("array of subdocuments, a child table may fit better".to_string());
+                json_type(dialect).to_string()
+            }
+            _ => {
+                comments.push("array of mixed or nested arrays, stored as JSON".to_string());
+                json_type(dialect).to_string()
+            }
+        },
+        FieldType::Record(_) | FieldType::Document => json_type(dialect).to_string(),
+        FieldType::Union(union) => {
+            let types = union.labels();
+            comments.push(format!("seen as {}, stored as JSON", types.join(", ")));
+            json_type(dialect).to_string()
+        }
+        FieldType::Unknown => {
+            comments.push("only null or missing was seen".to_string());
+            "TEXT".to_string()
+        }
+    };
+    Column {
+        name: snake_case(&field.name),
+        ty,
+        not_null: !field.optional && !field.nullable,
+        primary_key,
+        allowed,
+        comments,
+        note: field.note.clone(),
+    }
+}
+
+fn scalar_type(
+    name: &str,
+    dialect: SqlDialect,
+    primary_key: bool,
+    comments: &mut Vec<String>,
+) -> String {
+    use SqlDialect::*;
+    match (name, dialect) {
+        ("double" | "number", Postgres) => "DOUBLE PRECISION",
+        ("double" | "number", Mysql) => "DOUBLE",
+        ("double" | "number", Sqlite) => "REAL",
+        ("int", Mysql) => "INT",
+        ("int", _) => "INTEGER",
+        ("long", Sqlite) => "INTEGER",
+        ("long", _) => "BIGINT",
+        // MySQL can't index TEXT without a prefix length
+        ("string", Mysql) if primary_key => "VARCHAR(255)",
+        ("string", _) => "TEXT",
+        ("bool", Sqlite) => "INTEGER",
+        ("bool", _) => "BOOLEAN",
+        ("date", Postgres) => "TIMESTAMPTZ",
+        ("date", Mysql) => "DATETIME(3)",
+        ("date", Sqlite) => "TEXT",
+        ("objectId", Sqlite) => "TEXT",
+        ("objectId", _) => "CHAR(24)",
+        ("decimal", Postgres) => "NUMERIC",
+        ("decimal", Mysql) => {
+            comments.push("decimal128 has more precision than DECIMAL(65, 30)".to_string());
+            "DECIMAL(65, 30)"
+        }
+        ("decimal", Sqlite) => "TEXT",
+        ("binData", Postgres) => "BYTEA",
+        ("binData", _) => "BLOB",
+        ("timestamp", _) => {
+            comments.push("BSON timestamp, seconds in the high 32 bits".to_string());
+            if dialect == Sqlite {
+                "INTEGER"
+            } else {
+                "BIGINT"
+            }
+        }
+        (other, _) => {
+            comments.push(format!("no column type for {}, stored as text", other));
+            "TEXT"
+        }
+    }
+    .to_string()
+}
+
+fn json_type(dialect: SqlDialect) -> &'static str {
+    match dialect {
+        SqlDialect::Postgres => "JSONB",
+        SqlDialect::Mysql => "JSON",
+        SqlDialect::Sqlite => "TEXT",
+    }
+}
+
+fn quote(identifier: &str, dialect: SqlDialect) -> String {
+    match dialect {
+        SqlDialect::Mysql => format!("`{}`", identifier),
+        SqlDialect::Postgres | SqlDialect::Sqlite => format!("\"{}\"", identifier),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_postgres_tables() {
+        assert_eq!(
+            fixture().to_sql(
+                SqlDialect::Postgres,
+                UnionStrategy::Union,
+                1.0,
+                Annotation::None
+            ),
+            r#"-- approximated from app.users
+CREATE TABLE "users" (
+    "id" CHAR(24) PRIMARY KEY,
+    "address_city" TEXT NOT NULL,
+    "address_zip" TEXT,
+    "age" BIGINT NOT NULL,
+    "created_at" TIMESTAMPTZ NOT NULL,
+    -- TODO: seen as string, int, stored as JSON
+    "external_id" JSONB NOT NULL,
+    "name" TEXT NOT NULL,
+    "nickname" TEXT,
+    -- TODO: array of subdocuments, a child table may fit better
+    "orders" JSONB NOT NULL,
+    "score" DOUBLE PRECISION NOT NULL,
+    "status" TEXT NOT NULL CHECK ("status" IN ('active', 'banned')),
+    "tags" TEXT[] NOT NULL
+);
+"#
+        );
+    }
+
+    #[test]
+    fn generates_mysql_tables() {
+        assert_eq!(
+            fixture().to_sql(
+                SqlDialect::Mysql,
+                UnionStrategy::Union,
+                1.0,
+                Annotation::None
+            ),
+            r#"-- approximated from app.users
+CREATE TABLE `users` (
+    `id` CHAR(24) PRIMARY KEY,
+    `address_city` TEXT NOT NULL,
+    `address_zip` TEXT,
+    `age` BIGINT NOT NULL,
+    `created_at` DATETIME(3) NOT NULL,
+    -- TODO: seen as string, int, stored as JSON
+    `external_id` JSON NOT NULL,
+    `name` TEXT NOT NULL,
+    `nickname` TEXT,
+    -- TODO: array of subdocuments, a child table may fit better
+    `orders` JSON NOT NULL,
+    `score` DOUBLE NOT NULL,
+    `status` ENUM('active', 'banned') NOT NULL,
+    `tags` JSON NOT NULL
+);
+"#
+        );
+    }
+
+    #[test]
+    fn generates_sqlite_tables() {
+        assert_eq!(
+            fixture().to_sql(
+                SqlDialect::Sqlite,
+                UnionStrategy::Union,
+                1.0,
+                Annotation::None
+            ),
+            r#"-- approximated from app.users
+CREATE TABLE "users" (
+    "id" TEXT PRIMARY KEY,
+    "address_city" TEXT NOT NULL,
+    "address_zip" TEXT,
+    "age" INTEGER NOT NULL,
+    "created_at" TEXT NOT NULL,
+    -- TODO: seen as string, int, stored as JSON
+    "external_id" TEXT NOT NULL,
+    "name" TEXT NOT NULL,
+    "nickname" TEXT,
+    -- TODO: array of subdocuments, a child table may fit better
+    "orders" TEXT NOT NULL,
+    "score" REAL NOT NULL,
+    "status" TEXT NOT NULL CHECK ("status" IN ('active', 'banned')),
+    "tags" TEXT NOT NULL
+);
+"#
+        );
+    }
+}
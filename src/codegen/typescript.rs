@@ -0,0 +1,284 @@
+use std::{collections::BTreeSet, fmt::Write};
+
+use super::model::{block_comment, records, Annotation, FieldType, Record, UnionStrategy};
+use crate::CollectionSchema;
+
+impl CollectionSchema {
+    /// Generate TypeScript interfaces for the collection's documents using the Node driver's
+    /// BSON classes. Fields present in fewer than `required_presence` (a fraction) of the
+    /// objects holding them are optional, null is a union member, and mixed types and enum
+    /// candidates become unions
+    pub fn to_typescript(
+        &self,
+        unions: UnionStrategy,
+        required_presence: f64,
+        annotation: Annotation,
+    ) -> String {
+        let records = records(self, unions, required_presence, annotation);
+        let mut imports = BTreeSet::new();
+        let mut body = String::new();
+        for record in &records {
+            body.push('\n');
+            write_interface(&mut body, record, &records, &mut imports);
+        }
+
+        let mut out = String::new();
+        if !imports.is_empty() {
+            let imports = imports.into_iter().collect::<Vec<_>>();
+            writeln!(out, "import {{ {} }} from \"mongodb\";", imports.join(", ")).unwrap();
+        }
+        out.push_str(&body);
+        out.trim_start().to_string()
+    }
+}
+
+fn write_interface(
+    out: &mut String,
+    record: &Record,
+    records: &[Record],
+    imports: &mut BTreeSet<&'static str>,
+) {
+    writeln!(out, "export interface {} {{", record.name).unwrap();
+    for field in &record.fields {
+        let mut ty = ts_type(&field.ty, records, imports);
+        if field.nullable {
+            ty = format!("{} | null", ty);
+        }
+        if let Some(note) = &field.note {
+            writeln!(out, "  /** {} */", block_comment(note)).unwrap();
+        }
+        let optional = if field.optional { "?" } else { "" };
+        writeln!(out, "  {}{}: {};", property_name(&field.name), optional, ty).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+}
+
+fn ts_type(ty: &FieldType, records: &[Record], imports: &mut BTreeSet<&'static str>) -> String {
+    match ty {
+        FieldType::Scalar(name) => {
+            let (ty, import) = match name.as_str() {
+                "double" | "int" | "long" | "number" => ("number", None),
+                "string" | "symbol" | "javascript" => ("string", None),
+                "bool" => ("boolean", None),
+                "date" => ("Date", None),
+                "objectId" => ("ObjectId", Some("ObjectId")),
+                "decimal" => ("Decimal128", Some("Decimal128")),
+                "binData" => ("Binary", Some("Binary")),
+                "timestamp" => ("Timestamp", Some("Timestamp")),
+                "regex" => ("BSONRegExp", Some("BSONRegExp")),
+                _ => ("unknown", None),
+            };
+            imports.extend(import);
+            ty.to_string()
+        }
+        FieldType::Record(index) => records[*index].name.clone(),
+        FieldType::Document => "Record<string, unknown>".to_string(),
+        FieldType::Array(elements) => {
+            let elements = ts_type(elements, records, imports);
+            if elements.contains(" | ") {
+                format!("({})[]", elements)
+            } else {
+                format!("{}[]", elements)
+            }
+        }
+        FieldType::Union(union) if union.degraded => "unknown".to_string(),
+        FieldType::Union(union) => {
+            let mut members = Vec::new();
+            for ty in &union.members {
+                let member = ts_type(ty, records, imports);
+                if !members.contains(&member) {
+                    members.push(member);
+                }
+            }
+            members.join(" | ")
+        }
+        FieldType::Unknown => "unknown".to_string(),
+        FieldType::Enum(enum_type) => {
+            let values = enum_type
+                .values
+                .iter()
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>();
+            values.join(" | ")
+        }
+    }
+}
+
+/// Keys that aren't valid identifiers have to be quoted
+pub(crate) fn property_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$');
+    if valid {
+        name.to_string()
+    } else {
+        serde_json::to_string(name).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::{fixture, FIXTURE};
+
+    #[test]
+    fn generates_interfaces() {
+        assert_eq!(
+            fixture().to_typescript(UnionStrategy::Union, 1.0, Annotation::None),
+            r#"import { ObjectId } from "mongodb";
+
+export interface Users {
+  _id: ObjectId;
+  address: UsersAddress;
+  age: number;
+  createdAt: Date;
+  externalId: string | number;
+  name: string;
+  nickname?: string | null;
+  orders: UsersOrders[];
+  score: number;
+  status: "active" | "banned";
+  tags: string[];
+}
+
+export interface UsersAddress {
+  city: string;
+  zip?: string;
+}
+
+export interface UsersOrders {
+  qty: number;
+  sku: string;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn types_unions_as_unknown_with_any() {
+        assert_eq!(
+            fixture().to_typescript(UnionStrategy::Any, 1.0, Annotation::None),
+            r#"import { ObjectId } from "mongodb";
+
+export interface Users {
+  _id: ObjectId;
+  address: UsersAddress;
+  age: number;
+  createdAt: Date;
+  externalId: unknown;
+  name: string;
+  nickname?: string | null;
+  orders: UsersOrders[];
+  score: number;
+  status: "active" | "banned";
+  tags: string[];
+}
+
+export interface UsersAddress {
+  city: string;
+  zip?: string;
+}
+
+export interface UsersOrders {
+  qty: number;
+  sku: string;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn keeps_the_dominant_type_and_requires_by_presence() {
+        assert_eq!(
+            fixture().to_typescript(UnionStrategy::Dominant, 0.5, Annotation::None),
+            r#"import { ObjectId } from "mongodb";
+
+export interface Users {
+  _id: ObjectId;
+  address: UsersAddress;
+  age: number;
+  createdAt: Date;
+  externalId: string;
+  name: string;
+  nickname?: string | null;
+  orders: UsersOrders[];
+  score: number;
+  status: "active" | "banned";
+  tags: string[];
+}
+
+export interface UsersAddress {
+  city: string;
+  zip: string;
+}
+
+export interface UsersOrders {
+  qty: number;
+  sku: string;
+}
+"#
+        );
+    }
+
+    #[test]
+    fn quotes_keys_that_arent_identifiers() {
+        assert_eq!(property_name("createdAt"), "createdAt");
+        assert_eq!(property_name("$ref"), "$ref");
+        assert_eq!(property_name("first-name"), r#""first-name""#);
+        assert_eq!(property_name("2fa"), r#""2fa""#);
+    }
+
+    #[test]
+    fn types_nullable_and_optional_fields_apart() {
+        let ndjson =
+            "{\"always\": \"a\", \"nullable\": null, \"optional\": \"a\", \"both\": null}\n\
+                      {\"always\": \"b\", \"nullable\": \"b\", \"both\": \"b\"}\n\
+                      {\"always\": \"c\", \"nullable\": \"c\"}\n";
+        let schema =
+            CollectionSchema::from_ndjson(ndjson.as_bytes(), "app.nulls", &Default::default())
+                .unwrap();
+        assert_eq!(
+            schema.to_typescript(UnionStrategy::Union, 1.0, Annotation::None),
+            "export interface Nulls {\n  always: string;\n  both?: string | null;\n  \
+             nullable: string | null;\n  optional?: string;\n}\n"
+        );
+    }
+
+    #[test]
+    fn comments_fields_with_what_was_observed_of_them() {
+        let options = crate::AnalysisOptions {
+            examples: 2,
+            ..crate::AnalysisOptions::default()
+        };
+        let schema =
+            CollectionSchema::from_ndjson(FIXTURE.as_bytes(), "app.users", &options).unwrap();
+        let brief = schema.to_typescript(UnionStrategy::Union, 1.0, Annotation::Brief);
+        assert!(
+            brief.contains(
+                "  /** present in 33% of documents; types: string, null */
+  \
+                 nickname?: string | null;\n"
+            ),
+            "{}",
+            brief
+        );
+        assert!(
+            brief.contains("  /** present in 67% of the objects holding it; types: string */"),
+            "{}",
+            brief
+        );
+        let full = schema.to_typescript(UnionStrategy::Union, 1.0, Annotation::Full);
+        assert!(
+            full.contains(
+                "  /** present in 100% of documents; types: string; 2 examples: \"Ada\", \
+                 \"Grace\" */\n  name: string;\n"
+            ),
+            "{}",
+            full
+        );
+        let none = schema.to_typescript(UnionStrategy::Union, 1.0, Annotation::None);
+        assert!(!none.contains("/**"), "{}", none);
+    }
+}
@@ -0,0 +1,409 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use bson::{doc, Bson, Document};
+#[cfg(feature = "driver")]
+use futures::stream::TryStreamExt;
+#[cfg(feature = "driver")]
+use mongodb::Database;
+
+use crate::{
+    BsonType, CollectionSchema, FieldSchema, FormatVersion, Result, SampleMethod, ScalarValue,
+    SchemaDiff, TypeChange, TypeMode,
+};
+
+impl CollectionSchema {
+    /// Build a `$jsonSchema` validator accepting every document shape seen in the sample.
+    ///
+    /// Fields present in at least `required_presence` (a fraction) of the objects holding
+    /// them are `required`, see [`CollectionSchema::is_required`]. Each field allows exactly
+    /// the BSON types observed for it, and enum candidates only their values
+    pub fn to_validator(&self, required_presence: f64) -> Document {
+        let mut root = doc! { "bsonType": "object" };
+        root.extend(self.object_json_schema("", required_presence));
+        doc! { "$jsonSchema": root }
+    }
+
+    /// `properties` and `required` for the object whose fields start with `prefix`
+    fn object_json_schema(&self, prefix: &str, required_presence: f64) -> Document {
+        let mut properties = Document::new();
+        let mut required = Vec::new();
+        for (name, path) in self.children(prefix) {
+            if self.is_required(path, required_presence) {
+                required.push(Bson::String(name.to_string()));
+            }
+            properties.insert(name, self.field_json_schema(path, required_presence));
+        }
+
+        let mut schema = doc! { "properties": properties };
+        if !required.is_empty() {
+            schema.insert("required", required);
+        }
+        schema
+    }
+
+    fn field_json_schema(&self, path: &str, required_presence: f64) -> Document {
+        let types = self.fields[path]
+            .types
+            .iter()
+            .filter(|t| **t != BsonType::Missing)
+            .map(|t| Bson::String(t.to_string()))
+            .collect::<Vec<_>>();
+
+        let mut schema = match types.as_slice() {
+            // any type, from a JSON Schema not restricting it
+            [] => Document::new(),
+            [single] => doc! { "bsonType": single.clone() },
+            _ => doc! { "bsonType": types.clone() },
+        };
+        if types.iter().any(|t| t.as_str() == Some("object")) {
+            let object = self.object_json_schema(&format!("{}.", path), required_presence);
+            if !object.get_document("properties").unwrap().is_empty() {
+                schema.extend(object);
+            }
+        }
+        if let Some(values) = self.enum_values(path) {
+            let mut values = values
+                .iter()
+                .map(|value| match value {
+                    ScalarValue::Int(n) => match i32::try_from(*n) {
+                        Ok(n) => Bson::Int32(n),
+                        Err(_) => Bson::Int64(*n),
+                    },
+                    ScalarValue::String(s) => Bson::String(s.clone()),
+                })
+                .collect::<Vec<_>>();
+            if self.fields[path].nulls() > 0 {
+                values.push(Bson::Null);
+            }
+            schema.insert("enum", values);
+        }
+        let elements = format!("{}[]", path);
+        if types.iter().any(|t| t.as_str() == Some("array")) && self.fields.contains_key(&elements)
+        {
+            schema.insert(
+                "items",
+                self.field_json_schema(&elements, required_presence),
+            );
+        }
+        schema
+    }
+
+    /// The schema a `$jsonSchema` describes, or a validator wrapping one, for checking
+    /// collections and baselines against a hand-written schema. Fields not `required` may be
+    /// missing, and fields whose types aren't restricted have none. There was no sample, so
+    /// counts are all zero and [`SampleMethod::Declared`]; what a JSON Schema says beyond
+    /// `properties`, `required`, `items` and types, like `enum`, is dropped
+    pub fn from_json_schema(namespace: impl Into<String>, json_schema: &Document) -> Self {
+        let json_schema = json_schema
+            .get_document("$jsonSchema")
+            .unwrap_or(json_schema);
+        let mut rules = BTreeMap::new();
+        described_fields(json_schema, "", &mut rules);
+        let fields = rules
+            .into_iter()
+            .map(|(path, rule)| {
+                let mut types = rule.types.unwrap_or_default();
+                let mut seen = BTreeSet::new();
+                types.retain(|ty| seen.insert(ty.clone()));
+                if !rule.required {
+                    types.push(BsonType::Missing);
+                }
+                let type_counts = types.iter().map(|ty| (ty.clone(), 0)).collect();
+                let mut field = FieldSchema::new(0, type_counts);
+                // in the order declared, as there are no counts to order by
+                field.types = types;
+                (path, field)
+            })
+            .collect();
+        CollectionSchema {
+            format_version: FormatVersion::default(),
+            namespace: namespace.into(),
+            document_count: 0,
+            sample_size: 0,
+            sample_method: SampleMethod::Declared,
+            fields,
+            meta: None,
+            shapes: None,
+            anomalies: Vec::new(),
+            privacy: None,
+            rare_fields: Vec::new(),
+            findings: Vec::new(),
+            confidence: None,
+            field_names: None,
+            sizes: None,
+            document_sizes: None,
+            document_widths: None,
+            truncated_documents: 0,
+            variants: None,
+            versions: None,
+            evolution: None,
+            time_series: None,
+            view: None,
+            shards: None,
+            indexes: None,
+            type_mode: TypeMode::Strict,
+        }
+    }
+
+    /// Compare against a collection's `$jsonSchema` validator, as returned by
+    /// [`fetch_validator`]. `added` lists the fields in the data that the validator doesn't
+    /// describe, `removed` the ones it describes that the sample never held, and `changed`
+    /// the types the validator would reject: "missing" for required fields some documents
+    /// lack. Types the validator allows but the sample lacks aren't reported, as validators
+    /// commonly allow more than is used
+    pub fn validator_diff(&self, json_schema: &Document) -> SchemaDiff {
+        let mut allowed = BTreeMap::new();
+        described_fields(json_schema, "", &mut allowed);
+
+        let mut diff = SchemaDiff::default();
+        for (path, field) in &self.fields {
+            let Some(rule) = allowed.get(path) else {
+                diff.added.push(path.clone());
+                continue;
+            };
+            let added_types = field
+                .types
+                .iter()
+                .filter(|t| match t {
+                    // an element path is missing for empty arrays, which validators can't rule out
+                    BsonType::Missing => rule.required && !path.ends_with("[]"),
+                    t => rule.types.as_ref().is_some_and(|types| !types.contains(t)),
+                })
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>();
+            if !added_types.is_empty() {
+                diff.changed.push(TypeChange {
+                    path: path.clone(),
+                    added_types,
+                    removed_types: Vec::new(),
+                });
+            }
+        }
+        diff.removed = allowed
+            .keys()
+            .filter(|path| !self.fields.contains_key(*path))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+/// What a validator allows for one field
+struct Rule {
+    /// The allowed types, or `None` if any type is
+    types: Option<Vec<BsonType>>,
+    required: bool,
+}
+
+/// Collect the rules for the properties, and array items, of the object schema `schema`
+fn described_fields(schema: &Document, prefix: &str, rules: &mut BTreeMap<String, Rule>) {
+    let Ok(properties) = schema.get_document("properties") else {
+        return;
+    };
+    let required = schema
+        .get_array("required")
+        .map(|required| required.iter().filter_map(Bson::as_str).collect::<Vec<_>>())
+        .unwrap_or_default();
+    for (name, property) in properties {
+        let Bson::Document(property) = property else {
+            continue;
+        };
+        let path = format!("{}{}", prefix, name);
+        described_field(property, &path, required.contains(&name.as_str()), rules);
+    }
+}
+
+fn described_field(
+    schema: &Document,
+    path: &str,
+    required: bool,
+    rules: &mut BTreeMap<String, Rule>,
+) {
+    let types = allowed_types(schema);
+    let object = types
+        .as_ref()
+        .is_none_or(|types| types.contains(&BsonType::Object));
+    let array = types
+        .as_ref()
+        .is_none_or(|types| types.contains(&BsonType::Array));
+    rules.insert(path.to_string(), Rule { types, required });
+    if object {
+        described_fields(schema, &format!("{}.", path), rules);
+    }
+    if array {
+        if let Ok(items) = schema.get_document("items") {
+            described_field(items, &format!("{}[]", path), false, rules);
+        }
+    }
+}
+
+/// The types `bsonType`, or failing that the JSON Schema `type`, allows
+pub(crate) fn allowed_types(schema: &Document) -> Option<Vec<BsonType>> {
+    let (names, json) = match (schema.get("bsonType"), schema.get("type")) {
+        (Some(names), _) => (names, false),
+        (None, Some(names)) => (names, true),
+        (None, None) => return None,
+    };
+    let names = match names {
+        Bson::String(name) => vec![name.as_str()],
+        Bson::Array(names) => names.iter().filter_map(Bson::as_str).collect(),
+        _ => return None,
+    };
+    let mut types = Vec::new();
+    for name in names {
+        match (name, json) {
+            ("number", _) => types.extend([
+                BsonType::Int,
+                BsonType::Long,
+                BsonType::Double,
+                BsonType::Decimal,
+            ]),
+            ("integer", true) => types.extend([BsonType::Int, BsonType::Long]),
+            ("boolean", true) => types.push(BsonType::Bool),
+            (name, _) => types.push(BsonType::from_name(name)),
+        }
+    }
+    Some(types)
+}
+
+/// The `$jsonSchema` of the collection's validator, if it has one. Validators made of query
+/// operators instead aren't returned
+#[cfg(feature = "driver")]
+pub async fn fetch_validator(database: &Database, collection: &str) -> Result<Option<Document>> {
+    let mut specs = database
+        .list_collections(doc! { "name": collection }, None)
+        .await?;
+    let Some(spec) = specs.try_next().await? else {
+        return Ok(None);
+    };
+    Ok(spec
+        .options
+        .validator
+        .and_then(|validator| validator.get_document("$jsonSchema").ok().cloned()))
+}
+
+/// Install `validator` (as built by [`CollectionSchema::to_validator`]) on the collection with
+/// `collMod`, leaving the validation level and action at the server's defaults
+#[cfg(feature = "driver")]
+pub async fn apply_validator(
+    database: &Database,
+    collection: &str,
+    validator: Document,
+) -> Result<()> {
+    database
+        .run_command(doc! { "collMod": collection, "validator": validator }, None)
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::Bson;
+    use serde_json::json;
+
+    use crate::codegen::fixture;
+
+    #[test]
+    fn generates_a_json_schema() {
+        assert_eq!(
+            Bson::Document(fixture().to_validator(1.0)).into_relaxed_extjson(),
+            json!({
+              "$jsonSchema": {
+                "bsonType": "object",
+                "properties": {
+                  "_id": {
+                    "bsonType": "objectId"
+                  },
+                  "address": {
+                    "bsonType": "object",
+                    "properties": {
+                      "city": {
+                        "bsonType": "string"
+                      },
+                      "zip": {
+                        "bsonType": "string"
+                      }
+                    },
+                    "required": [
+                      "city"
+                    ]
+                  },
+                  "age": {
+                    "bsonType": [
+                      "int",
+                      "long"
+                    ]
+                  },
+                  "createdAt": {
+                    "bsonType": "date"
+                  },
+                  "externalId": {
+                    "bsonType": [
+                      "string",
+                      "int"
+                    ]
+                  },
+                  "name": {
+                    "bsonType": "string"
+                  },
+                  "nickname": {
+                    "bsonType": [
+                      "string",
+                      "null"
+                    ]
+                  },
+                  "orders": {
+                    "bsonType": "array",
+                    "items": {
+                      "bsonType": "object",
+                      "properties": {
+                        "qty": {
+                          "bsonType": "int"
+                        },
+                        "sku": {
+                          "bsonType": "string"
+                        }
+                      },
+                      "required": [
+                        "qty",
+                        "sku"
+                      ]
+                    }
+                  },
+                  "score": {
+                    "bsonType": [
+                      "double",
+                      "int"
+                    ]
+                  },
+                  "status": {
+                    "bsonType": "string",
+                    "enum": [
+                      "active",
+                      "banned"
+                    ]
+                  },
+                  "tags": {
+                    "bsonType": "array",
+                    "items": {
+                      "bsonType": "string"
+                    }
+                  }
+                },
+                "required": [
+                  "_id",
+                  "address",
+                  "age",
+                  "createdAt",
+                  "externalId",
+                  "name",
+                  "orders",
+                  "score",
+                  "status",
+                  "tags"
+                ]
+              }
+            })
+        );
+    }
+}
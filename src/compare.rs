@@ -0,0 +1,221 @@
+//! Comparing the namespaces of two clusters, e.g. production and staging, to catch the
+//! environments drifting apart before a deploy does
+
+use std::{collections::BTreeMap, fmt};
+
+#[cfg(feature = "driver")]
+use mongodb::{Client, Namespace};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::{cluster_namespaces, collections, AnalysisOptions, NamespaceFilter, Result};
+use crate::{FormatVersion, SchemaDiff, SchemaReport};
+
+/// How the namespaces of two clusters differ: the ones only one of them has, and the schema
+/// differences of those both have, the left taken as the old side
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ClusterComparison {
+    #[serde(rename = "formatVersion", default = "FormatVersion::unversioned")]
+    pub format_version: FormatVersion,
+    /// What the left cluster is called in the report, e.g. `prod`
+    pub left: String,
+    pub right: String,
+    pub only_left: Vec<String>,
+    pub only_right: Vec<String>,
+    /// The namespaces both clusters have whose schemas differ, keyed by namespace
+    pub diffs: BTreeMap<String, SchemaDiff>,
+    /// The namespaces both clusters have with the same schema
+    pub identical: Vec<String>,
+    /// Why each namespace that couldn't be analyzed on one side failed, keyed by namespace
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub failures: BTreeMap<String, String>,
+}
+
+impl ClusterComparison {
+    /// Compare the schemas of `left` and `right`, reporting the namespaces only one of them
+    /// analyzed as only on that side
+    pub fn new(
+        left: impl Into<String>,
+        left_report: &SchemaReport,
+        right: impl Into<String>,
+        right_report: &SchemaReport,
+    ) -> Self {
+        let mut comparison = ClusterComparison {
+            format_version: FormatVersion::default(),
+            left: left.into(),
+            right: right.into(),
+            ..Default::default()
+        };
+        for (side, report) in [
+            (&comparison.left, left_report),
+            (&comparison.right, right_report),
+        ] {
+            for (namespace, error) in &report.failures {
+                let failure = format!("{}: {}", side, error);
+                // both sides may have failed, each for its own reason
+                comparison
+                    .failures
+                    .entry(namespace.clone())
+                    .and_modify(|failures| *failures = format!("{}; {}", failures, failure))
+                    .or_insert(failure);
+            }
+        }
+        for (namespace, old) in &left_report.namespaces {
+            let Some(new) = right_report.namespaces.get(namespace) else {
+                if !comparison.failures.contains_key(namespace) {
+                    comparison.only_left.push(namespace.clone());
+                }
+                continue;
+            };
+            let diff = old.diff(new);
+            if diff.is_empty() {
+                comparison.identical.push(namespace.clone());
+            } else {
+                comparison.diffs.insert(namespace.clone(), diff);
+            }
+        }
+        comparison.only_right = right_report
+            .namespaces
+            .keys()
+            .filter(|namespace| {
+                !left_report.namespaces.contains_key(*namespace)
+                    && !comparison.failures.contains_key(*namespace)
+            })
+            .cloned()
+            .collect();
+        comparison
+    }
+
+    /// Whether the clusters have the same namespaces with the same schemas
+    pub fn is_empty(&self) -> bool {
+        self.only_left.is_empty() && self.only_right.is_empty() && self.diffs.is_empty()
+    }
+
+    /// List the namespaces matching `filter` on both clusters, or those of `db` if given, and
+    /// analyze the ones both have, at most `concurrency` at a time on each. Namespaces only
+    /// one cluster has are reported without being analyzed
+    #[cfg(feature = "driver")]
+    pub async fn analyze(
+        (left, left_client): (&str, &Client),
+        (right, right_client): (&str, &Client),
+        db: Option<&str>,
+        filter: &NamespaceFilter,
+        concurrency: usize,
+        options_for: impl Fn(&Namespace) -> AnalysisOptions + Copy,
+    ) -> Result<Self> {
+        let (left_namespaces, right_namespaces) = futures::try_join!(
+            listed(left_client, db, filter),
+            listed(right_client, db, filter)
+        )?;
+        let names = |namespaces: &[Namespace]| {
+            namespaces
+                .iter()
+                .map(Namespace::to_string)
+                .collect::<std::collections::BTreeSet<_>>()
+        };
+        let (left_names, right_names) = (names(&left_namespaces), names(&right_namespaces));
+        let common = left_namespaces
+            .iter()
+            .filter(|ns| right_names.contains(&ns.to_string()))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let (left_report, right_report) = futures::try_join!(
+            SchemaReport::analyze_namespaces_concurrently(
+                left_client,
+                common.clone(),
+                concurrency,
+                options_for
+            ),
+            SchemaReport::analyze_namespaces_concurrently(
+                right_client,
+                common,
+                concurrency,
+                options_for
+            ),
+        )?;
+        let mut comparison = Self::new(left, &left_report, right, &right_report);
+        comparison.only_left = left_names.difference(&right_names).cloned().collect();
+        comparison.only_right = right_names.difference(&left_names).cloned().collect();
+        Ok(comparison)
+    }
+}
+
+/// The namespaces of `db` matching `filter`, or of the whole cluster if no `db` is given
+#[cfg(feature = "driver")]
+async fn listed(
+    client: &Client,
+    db: Option<&str>,
+    filter: &NamespaceFilter,
+) -> Result<Vec<Namespace>> {
+    match db {
+        Some(db) => {
+            let mut namespaces = collections(client, db).await?;
+            namespaces.retain(|ns| filter.matches(ns));
+            Ok(namespaces)
+        }
+        None => cluster_namespaces(client, filter).await,
+    }
+}
+
+impl fmt::Display for ClusterComparison {
+    /// A summary line, the namespaces only one side has, then each differing namespace's
+    /// diff as `schema-analyzer diff` prints it
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} and {}: {} namespaces on both, {} identical, {} differing",
+            self.left,
+            self.right,
+            self.identical.len() + self.diffs.len(),
+            self.identical.len(),
+            self.diffs.len()
+        )?;
+        for (side, namespaces) in [
+            (&self.left, &self.only_left),
+            (&self.right, &self.only_right),
+        ] {
+            if !namespaces.is_empty() {
+                writeln!(f, "\nOnly on {}:", side)?;
+                for namespace in namespaces {
+                    writeln!(f, "  {}", namespace)?;
+                }
+            }
+        }
+        for (namespace, diff) in &self.diffs {
+            writeln!(f, "\n{}:", namespace)?;
+            write!(f, "{}", diff)?;
+        }
+        if !self.failures.is_empty() {
+            writeln!(f, "\nNot compared:")?;
+            for (namespace, error) in &self.failures {
+                writeln!(f, "  {}: {}", namespace, error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_both_sides_failures() {
+        let failed = |error: &str| SchemaReport {
+            failures: BTreeMap::from([("app.users".to_string(), error.to_string())]),
+            ..Default::default()
+        };
+        let comparison = ClusterComparison::new(
+            "prod",
+            &failed("unauthorized"),
+            "staging",
+            &failed("timed out"),
+        );
+        assert_eq!(
+            comparison.failures["app.users"],
+            "prod: unauthorized; staging: timed out"
+        );
+        assert!(comparison.only_left.is_empty() && comparison.only_right.is_empty());
+    }
+}
@@ -0,0 +1,195 @@
+//! How far the sample's presence percentages can be trusted for the whole collection:
+//! Wilson score intervals around each field's presence, and how rare a field can be and
+//! still likely have been missed
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CollectionSchema, SampleMethod};
+
+/// The confidence level of the intervals
+const LEVEL: f64 = 0.95;
+
+/// The standard normal quantile for [`LEVEL`], two-sided
+const Z: f64 = 1.959964;
+
+/// A range the true value lies in with the schema's [`Confidence::level`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Interval {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl fmt::Display for Interval {
+    /// As percentages, e.g. `96.1-98.4%`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.1}-{:.1}%", self.low * 100.0, self.high * 100.0)
+    }
+}
+
+/// What a sample of part of the collection can and can't tell, see
+/// [`CollectionSchema::estimate_confidence`]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Confidence {
+    /// The confidence level of the fields'
+    /// [`presence_interval`](crate::FieldSchema::presence_interval)s
+    pub level: f64,
+    /// The share of documents a field must be held by to be in the sample with `level`
+    /// confidence. Rarer fields may exist without having been sampled
+    pub detectable: f64,
+}
+
+impl Confidence {
+    /// The note on the presence ranges and the fields that may have been missed, e.g.
+    /// `presence ranges are 95% confidence intervals; fields in under 0.30% of documents
+    /// (about 3000) may have been missed, each having over a 5% chance of not being sampled`
+    pub(crate) fn note(&self, document_count: u64) -> String {
+        format!(
+            "presence ranges are {:.0}% confidence intervals; fields in under {:.2}% of \
+             documents (about {}) may have been missed, each having over a {:.0}% chance of not \
+             being sampled",
+            self.level * 100.0,
+            self.detectable * 100.0,
+            (self.detectable * document_count as f64).round() as u64,
+            (1.0 - self.level) * 100.0
+        )
+    }
+}
+
+impl CollectionSchema {
+    /// Attach a Wilson score interval to each field's presence and work out how rare a field
+    /// can be and still likely be in the sample, narrowing both by how much of the collection
+    /// the sample covers. Does nothing when every document was read, or for declared and
+    /// stratified samples, whose shares don't estimate the collection's. The intervals assume
+    /// the sampled documents were chosen at random, which the first documents in natural order
+    /// may not be
+    pub fn estimate_confidence(&mut self) {
+        if self.share_interval(0).is_none() {
+            return;
+        }
+        let intervals = self
+            .fields
+            .values()
+            .map(|field| self.share_interval(field.count))
+            .collect::<Vec<_>>();
+        for (field, interval) in self.fields.values_mut().zip(intervals) {
+            field.presence_interval = interval;
+        }
+        self.confidence = Some(Confidence {
+            level: LEVEL,
+            // a field held by a share p of documents is missed by n draws with probability
+            // (1 - p)^n, at most as often without replacement
+            detectable: 1.0 - (1.0 - LEVEL).powf(1.0 / self.sample_size as f64),
+        });
+    }
+
+    /// The interval around the [`share`](Self::share) of `count` sampled documents, or `None`
+    /// where [`estimate_confidence`](Self::estimate_confidence) sets none
+    pub(crate) fn share_interval(&self, count: u64) -> Option<Interval> {
+        let n = self.sample_size;
+        let population = self.document_count;
+        if n == 0
+            || n >= population
+            || matches!(
+                self.sample_method,
+                SampleMethod::All | SampleMethod::Declared | SampleMethod::Stratified
+            )
+        {
+            return None;
+        }
+        // the finite population correction: a sample of most of the collection leaves little
+        // room for the rest to differ
+        let correction = ((population - n) as f64 / (population - 1) as f64).sqrt();
+        Some(wilson(count, n, Z * correction))
+    }
+}
+
+/// The Wilson score interval for `successes` of `n`, at the normal quantile `z`
+fn wilson(successes: u64, n: u64, z: f64) -> Interval {
+    let n = n as f64;
+    let p = (successes as f64 / n).min(1.0);
+    let z2 = z * z;
+    let denominator = 1.0 + z2 / n;
+    let center = (p + z2 / (2.0 * n)) / denominator;
+    let half = z / denominator * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+    Interval {
+        low: (center - half).max(0.0),
+        high: (center + half).min(1.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "{} is not {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn computes_wilson_intervals() {
+        let half = wilson(50, 100, Z);
+        assert_close(half.low, 0.4038);
+        assert_close(half.high, 0.5962);
+        let none = wilson(0, 10, Z);
+        assert_close(none.low, 0.0);
+        assert_close(none.high, 0.2775);
+        let all = wilson(10, 10, Z);
+        assert_close(all.low, 0.7225);
+        assert_close(all.high, 1.0);
+        assert_eq!(half.to_string(), "40.4-59.6%");
+    }
+
+    fn sampled(lines: &str, document_count: u64) -> CollectionSchema {
+        let mut schema =
+            CollectionSchema::from_ndjson(lines.as_bytes(), "app.users", &Default::default())
+                .unwrap();
+        schema.document_count = document_count;
+        schema.sample_method = SampleMethod::Random;
+        schema
+    }
+
+    #[test]
+    fn estimates_presence_of_a_partial_sample() {
+        let lines = "{\"a\": 1, \"b\": 1}\n{\"a\": 2}\n".repeat(50);
+        let mut schema = sampled(&lines, 1_000_000);
+        schema.estimate_confidence();
+        let confidence = schema.confidence.unwrap();
+        assert_eq!(confidence.level, LEVEL);
+        assert_close(confidence.detectable, 1.0 - 0.05_f64.powf(1.0 / 100.0));
+        // the population is large enough for the correction not to matter
+        let b = schema.fields["b"].presence_interval.unwrap();
+        assert_close(b.low, 0.4038);
+        assert_close(b.high, 0.5962);
+        assert_eq!(schema.fields["a"].presence_interval.unwrap().high, 1.0);
+    }
+
+    #[test]
+    fn narrows_intervals_as_the_sample_nears_the_collection() {
+        let lines = "{\"a\": 1, \"b\": 1}\n{\"a\": 2}\n".repeat(50);
+        let mut schema = sampled(&lines, 110);
+        schema.estimate_confidence();
+        let b = schema.fields["b"].presence_interval.unwrap();
+        assert!(b.low > 0.47 && b.high < 0.53, "{:?}", b);
+    }
+
+    #[test]
+    fn leaves_full_samples_alone() {
+        let lines = "{\"a\": 1}\n".repeat(10);
+        let mut schema = sampled(&lines, 10);
+        schema.estimate_confidence();
+        assert_eq!(schema.confidence, None);
+        assert_eq!(schema.fields["a"].presence_interval, None);
+
+        let mut schema = sampled(&lines, 1000);
+        schema.sample_method = SampleMethod::Stratified;
+        schema.estimate_confidence();
+        assert_eq!(schema.confidence, None);
+    }
+}
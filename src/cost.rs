@@ -0,0 +1,194 @@
+//! What a sampling will read, estimated before running it, e.g. to ask before scanning a
+//! large collection
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::{analyzer::counted_exactly, Result, SchemaAnalyzer};
+use crate::{AnalysisOptions, Strategy};
+
+/// How small a share of the documents `$sample` may take to pick them with a random cursor
+/// rather than scanning and sorting the collection
+const RANDOM_CURSOR_SHARE: f64 = 0.05;
+
+/// How many documents a collection needs for `$sample` to use a random cursor at all
+const RANDOM_CURSOR_MIN_DOCUMENTS: u64 = 100;
+
+/// What the sampling would read, see [`SchemaAnalyzer::estimate_cost`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    /// Where the types would be inferred. [`Strategy::Auto`] is the pipeline, unless the
+    /// server turns out to reject it
+    pub strategy: Strategy,
+    /// The documents matching the filter, or the estimate of the collection's
+    pub document_count: u64,
+    /// How many documents would be inferred from, `None` for all of them
+    pub sample_size: Option<u64>,
+    /// About how many documents the server would read to pick them
+    pub documents_scanned: u64,
+    /// Why every document, or every one matching the filter, would be read, if so
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_scan: Option<String>,
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Estimate what [`SchemaAnalyzer::analyze`] would read, without running it. Only the
+    /// document count the sample size is derived from is queried, as for
+    /// [`SchemaAnalyzer::dry_run`]
+    pub async fn estimate_cost(&self) -> Result<CostEstimate> {
+        let exact = counted_exactly(self.specification().await.as_ref());
+        let document_count = self.count(&self.collection(), exact).await?;
+        Ok(CostEstimate::new(&self.options, document_count))
+    }
+}
+
+impl CostEstimate {
+    /// The cost of sampling `document_count` documents with `options`, choosing the strategy
+    /// and sample size as the analysis does
+    pub(crate) fn new(options: &AnalysisOptions, document_count: u64) -> Self {
+        let client = options.samples_client_side();
+        let strategy = if client {
+            Strategy::Client
+        } else {
+            Strategy::Pipeline
+        };
+        if !options.ids.is_empty() {
+            // found through the `_id` index
+            let ids = options.distinct_ids().len() as u64;
+            return CostEstimate {
+                strategy,
+                document_count,
+                sample_size: None,
+                documents_scanned: ids.min(document_count),
+                full_scan: None,
+            };
+        }
+        if let Some(ranged) = options.ranged(document_count) {
+            // found through the index on the random key
+            let sample_size = options.sampling.size(document_count);
+            return CostEstimate {
+                sample_size,
+                documents_scanned: sample_size.unwrap_or(document_count),
+                full_scan: None,
+                ..CostEstimate::new(&ranged, document_count)
+            };
+        }
+        let sample_size = options
+            .sampling
+            .size(document_count)
+            .filter(|size| *size < document_count);
+        let full_scan = match sample_size {
+            None => Some("every document is inferred from"),
+            Some(_) if options.stratify_by.is_some() => {
+                Some("a stratified sample groups every document by its stratum first")
+            }
+            Some(_) if client && options.seed.is_some() => {
+                Some("a seeded sample hashes the _id of every document")
+            }
+            Some(_) if client => None,
+            Some(_) if options.filter.is_some() => {
+                Some("$sample picks from every document matching the filter")
+            }
+            Some(size) => (document_count < RANDOM_CURSOR_MIN_DOCUMENTS
+                || size as f64 >= document_count as f64 * RANDOM_CURSOR_SHARE)
+                .then_some("$sample scans and sorts the collection for 5% or more of it"),
+        };
+        CostEstimate {
+            strategy,
+            document_count,
+            sample_size,
+            documents_scanned: match full_scan {
+                Some(_) => document_count,
+                None => sample_size.unwrap_or(document_count),
+            },
+            full_scan: full_scan.map(str::to_string),
+        }
+    }
+}
+
+impl fmt::Display for CostEstimate {
+    /// e.g. `about 2000000 documents scanned by the pipeline, for a sample of 10000 (full
+    /// scan: a seeded sample hashes the _id of every document)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "about {} documents scanned {}",
+            self.documents_scanned,
+            match self.strategy {
+                Strategy::Client => "client-side",
+                _ => "by the pipeline",
+            }
+        )?;
+        match self.sample_size {
+            Some(size) => write!(f, ", for a sample of {}", size)?,
+            None => write!(f, ", for all {} documents", self.document_count)?,
+        }
+        if let Some(reason) = &self.full_scan {
+            write!(f, " (full scan: {})", reason)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SampleStrategy, Sampling};
+
+    #[test]
+    fn estimates_what_each_strategy_reads() {
+        let sized = AnalysisOptions {
+            sampling: Sampling::Size(1_000),
+            ..AnalysisOptions::default()
+        };
+        // a random cursor picks 0.1% of the collection
+        let sampled = CostEstimate::new(&sized, 1_000_000);
+        assert_eq!(sampled.strategy, Strategy::Pipeline);
+        assert_eq!(sampled.sample_size, Some(1_000));
+        assert_eq!(sampled.documents_scanned, 1_000);
+        assert_eq!(sampled.full_scan, None);
+
+        // but 10% of it takes a scan and a sort
+        let large = CostEstimate::new(&sized, 10_000);
+        assert_eq!(large.documents_scanned, 10_000);
+        assert!(large.full_scan.is_some());
+
+        let recent = AnalysisOptions {
+            sample_strategy: SampleStrategy::Recent,
+            ..sized.clone()
+        };
+        let recent = CostEstimate::new(&recent, 10_000);
+        assert_eq!(recent.strategy, Strategy::Client);
+        assert_eq!((recent.documents_scanned, recent.full_scan), (1_000, None));
+
+        let seeded = AnalysisOptions {
+            seed: Some(7),
+            ..sized.clone()
+        };
+        let seeded = CostEstimate::new(&seeded, 1_000_000);
+        assert_eq!(seeded.strategy, Strategy::Client);
+        assert_eq!(seeded.documents_scanned, 1_000_000);
+        assert!(seeded.full_scan.is_some());
+
+        let ranged = AnalysisOptions {
+            random_key_field: Some("rand".to_string()),
+            seed: Some(7),
+            ..sized.clone()
+        };
+        let ranged = CostEstimate::new(&ranged, 1_000_000);
+        assert_eq!(ranged.strategy, Strategy::Pipeline);
+        assert_eq!((ranged.documents_scanned, ranged.full_scan), (1_000, None));
+
+        let full = AnalysisOptions {
+            sampling: Sampling::Full,
+            ..AnalysisOptions::default()
+        };
+        let full = CostEstimate::new(&full, 2_000_000);
+        assert_eq!(full.sample_size, None);
+        assert_eq!(full.documents_scanned, 2_000_000);
+        assert!(full.to_string().contains("full scan"), "{}", full);
+    }
+}
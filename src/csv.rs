@@ -0,0 +1,325 @@
+use std::fmt::Write;
+
+use crate::{
+    bench::{sampling_name, strategy_name},
+    Benchmark, ClusterComparison, CollectionSchema, SchemaDiff, SchemaReport, ServerLoad,
+    ValidationReport,
+};
+
+const HEADER: [&str; 7] = [
+    "namespace",
+    "field",
+    "type",
+    "presence_percent",
+    "count",
+    "presence_low_percent",
+    "presence_high_percent",
+];
+
+impl CollectionSchema {
+    /// Flatten the schema into comma-separated rows of namespace, field path, type, the
+    /// percentage of sampled documents holding that type and their count, under a header row.
+    /// A sample of part of the collection adds the bounds of the percentage's confidence
+    /// interval, see [`CollectionSchema::estimate_confidence`]
+    pub fn to_csv(&self) -> String {
+        self.to_delimited(',')
+    }
+
+    /// Like [`CollectionSchema::to_csv`], separated by tabs
+    pub fn to_tsv(&self) -> String {
+        self.to_delimited('\t')
+    }
+
+    fn to_delimited(&self, delimiter: char) -> String {
+        let mut out = String::new();
+        write_row(&mut out, delimiter, HEADER);
+        self.write_rows(&mut out, delimiter);
+        out
+    }
+
+    fn write_rows(&self, out: &mut String, delimiter: char) {
+        // every row of each variant, named after its value, in place of the union's
+        if let Some(variants) = &self.variants {
+            for variant in &variants.variants {
+                variant.schema.write_rows(out, delimiter);
+            }
+            return;
+        }
+        for (path, field) in &self.fields {
+            for ty in &field.types {
+                let count = field.type_counts[ty];
+                let (low, high) = match self.share_interval(count) {
+                    Some(interval) => (
+                        format!("{:.1}", interval.low * 100.0),
+                        format!("{:.1}", interval.high * 100.0),
+                    ),
+                    None => (String::new(), String::new()),
+                };
+                write_row(
+                    out,
+                    delimiter,
+                    [
+                        &self.namespace,
+                        path,
+                        ty.name(),
+                        &format!("{:.1}", self.share(count) * 100.0),
+                        &count.to_string(),
+                        &low,
+                        &high,
+                    ],
+                );
+            }
+        }
+    }
+}
+
+impl SchemaReport {
+    /// The rows of every schema as by [`CollectionSchema::to_csv`], under a single header
+    pub fn to_csv(&self) -> String {
+        self.to_delimited(',')
+    }
+
+    /// Like [`SchemaReport::to_csv`], separated by tabs
+    pub fn to_tsv(&self) -> String {
+        self.to_delimited('\t')
+    }
+
+    fn to_delimited(&self, delimiter: char) -> String {
+        let mut out = String::new();
+        write_row(&mut out, delimiter, HEADER);
+        for schema in self.namespaces.values() {
+            schema.write_rows(&mut out, delimiter);
+        }
+        out
+    }
+}
+
+impl SchemaDiff {
+    /// One row per difference: `added` and `removed` fields, and `type_added` and
+    /// `type_removed` with the type that changed
+    pub fn to_csv(&self) -> String {
+        self.to_delimited(',')
+    }
+
+    /// Like [`SchemaDiff::to_csv`], separated by tabs
+    pub fn to_tsv(&self) -> String {
+        self.to_delimited('\t')
+    }
+
+    fn to_delimited(&self, delimiter: char) -> String {
+        let mut out = String::new();
+        write_row(&mut out, delimiter, ["change", "field", "type"]);
+        for path in &self.added {
+            write_row(&mut out, delimiter, ["added", path, ""]);
+        }
+        for path in &self.removed {
+            write_row(&mut out, delimiter, ["removed", path, ""]);
+        }
+        for change in &self.changed {
+            for ty in &change.added_types {
+                write_row(&mut out, delimiter, ["type_added", &change.path, ty]);
+            }
+            for ty in &change.removed_types {
+                write_row(&mut out, delimiter, ["type_removed", &change.path, ty]);
+            }
+        }
+        out
+    }
+}
+
+impl ClusterComparison {
+    /// One row per difference, as in [`SchemaDiff::to_csv`] with the namespace first, and
+    /// `only_left` and `only_right` rows for the namespaces only one cluster has
+    pub fn to_csv(&self) -> String {
+        self.to_delimited(',')
+    }
+
+    /// Like [`ClusterComparison::to_csv`], separated by tabs
+    pub fn to_tsv(&self) -> String {
+        self.to_delimited('\t')
+    }
+
+    fn to_delimited(&self, delimiter: char) -> String {
+        let mut out = String::new();
+        write_row(
+            &mut out,
+            delimiter,
+            ["namespace", "change", "field", "type"],
+        );
+        for namespace in &self.only_left {
+            write_row(&mut out, delimiter, [namespace, "only_left", "", ""]);
+        }
+        for namespace in &self.only_right {
+            write_row(&mut out, delimiter, [namespace, "only_right", "", ""]);
+        }
+        for (namespace, diff) in &self.diffs {
+            for path in &diff.added {
+                write_row(&mut out, delimiter, [namespace, "added", path, ""]);
+            }
+            for path in &diff.removed {
+                write_row(&mut out, delimiter, [namespace, "removed", path, ""]);
+            }
+            for change in &diff.changed {
+                for ty in &change.added_types {
+                    write_row(
+                        &mut out,
+                        delimiter,
+                        [namespace, "type_added", &change.path, ty],
+                    );
+                }
+                for ty in &change.removed_types {
+                    write_row(
+                        &mut out,
+                        delimiter,
+                        [namespace, "type_removed", &change.path, ty],
+                    );
+                }
+            }
+        }
+        out
+    }
+}
+
+impl ValidationReport {
+    /// One row per failed constraint, with the number of documents failing it
+    pub fn to_csv(&self) -> String {
+        self.to_delimited(',')
+    }
+
+    /// Like [`ValidationReport::to_csv`], separated by tabs
+    pub fn to_tsv(&self) -> String {
+        self.to_delimited('\t')
+    }
+
+    fn to_delimited(&self, delimiter: char) -> String {
+        let mut out = String::new();
+        write_row(
+            &mut out,
+            delimiter,
+            ["namespace", "field", "constraint", "documents"],
+        );
+        for failure in &self.failures {
+            write_row(
+                &mut out,
+                delimiter,
+                [
+                    self.namespace.as_str(),
+                    &failure.path,
+                    &failure.constraint.to_string(),
+                    &failure.documents.to_string(),
+                ],
+            );
+        }
+        out
+    }
+}
+
+impl Benchmark {
+    /// One row per run, its server counters empty if the server didn't report them
+    pub fn to_csv(&self) -> String {
+        self.to_delimited(',')
+    }
+
+    /// Like [`Benchmark::to_csv`], separated by tabs
+    pub fn to_tsv(&self) -> String {
+        self.to_delimited('\t')
+    }
+
+    fn to_delimited(&self, delimiter: char) -> String {
+        let mut out = String::new();
+        write_row(
+            &mut out,
+            delimiter,
+            [
+                "namespace",
+                "strategy",
+                "sampling",
+                "sample_size",
+                "fields",
+                "time_ms",
+                "documents_examined",
+                "keys_examined",
+                "bytes_out",
+                "differences",
+                "reference",
+                "error",
+            ],
+        );
+        for (i, run) in self.runs.iter().enumerate() {
+            let server = |count: fn(&ServerLoad) -> u64| {
+                run.server
+                    .as_ref()
+                    .map(|load| count(load).to_string())
+                    .unwrap_or_default()
+            };
+            write_row(
+                &mut out,
+                delimiter,
+                [
+                    self.namespace.as_str(),
+                    strategy_name(run.strategy),
+                    &sampling_name(run.sampling),
+                    &run.sample_size.to_string(),
+                    &run.fields.to_string(),
+                    &format!("{:.3}", run.time.as_secs_f64() * 1000.0),
+                    &server(|load| load.documents_examined),
+                    &server(|load| load.keys_examined),
+                    &server(|load| load.bytes_out),
+                    &run.differences.to_string(),
+                    &(self.reference == i).to_string(),
+                    run.error.as_deref().unwrap_or_default(),
+                ],
+            );
+        }
+        out
+    }
+}
+
+/// Write the cells as a line, quoting those that hold the delimiter, a quote or a line break
+fn write_row<'a>(out: &mut String, delimiter: char, cells: impl IntoIterator<Item = &'a str>) {
+    for (i, cell) in cells.into_iter().enumerate() {
+        if i > 0 {
+            out.push(delimiter);
+        }
+        if cell.contains([delimiter, '"', '\n', '\r']) {
+            write!(out, "\"{}\"", cell.replace('"', "\"\"")).unwrap();
+        } else {
+            out.push_str(cell);
+        }
+    }
+    out.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    #[test]
+    fn writes_a_row_per_field_and_type() {
+        let documents = b"{\"a\": 1}\n{\"a\": 2}\n{\"a\": \"x\"}\n{\"a\": 3}\n";
+        let schema = CollectionSchema::from_ndjson(
+            documents.as_slice(),
+            "db.coll",
+            &AnalysisOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            schema.to_csv(),
+            "namespace,field,type,presence_percent,count,presence_low_percent,presence_high_percent\n\
+             db.coll,a,int,75.0,3,,\n\
+             db.coll,a,string,25.0,1,,\n"
+        );
+        assert!(schema.to_tsv().contains("db.coll\ta\tint\t75.0\t3\t\t\n"));
+    }
+
+    #[test]
+    fn quotes_cells_holding_the_delimiter_or_quotes() {
+        let mut out = String::new();
+        write_row(&mut out, ',', ["a,b", "say \"hi\"", "line\nbreak", "a\tb"]);
+        assert_eq!(out, "\"a,b\",\"say \"\"hi\"\"\",\"line\nbreak\",a\tb\n");
+        out.clear();
+        write_row(&mut out, '\t', ["a,b", "a\tb"]);
+        assert_eq!(out, "a,b\t\"a\tb\"\n");
+    }
+}
@@ -0,0 +1,130 @@
+//! DBRefs, the `{$ref: "users", $id: ..., $db: "app"}` convention for a reference to a
+//! document of another collection, told apart from other objects so they can be reported as
+//! references and followed when inferring relationships
+
+use bson::Document;
+
+use crate::{BsonType, CollectionSchema};
+
+/// The keys of a DBRef, which are fine to start with `$`
+pub(crate) const DBREF_KEYS: [&str; 3] = ["$ref", "$id", "$db"];
+
+/// The database `doc` names, if any, and the collection, if it's a DBRef: a string `$ref`
+/// with an `$id`
+fn parts(doc: &Document) -> Option<(Option<&str>, &str)> {
+    let coll = doc.get_str("$ref").ok()?;
+    doc.get("$id")?;
+    Some((doc.get_str("$db").ok(), coll))
+}
+
+/// The collection `doc` refers to, as `db.collection` if it names the database, if it's a
+/// DBRef
+pub(crate) fn target(doc: &Document) -> Option<String> {
+    Some(match parts(doc)? {
+        (Some(db), coll) => format!("{}.{}", db, coll),
+        (None, coll) => coll.to_string(),
+    })
+}
+
+/// The `db.collection` the DBRef `doc` refers to, in `db` unless it names another
+pub(crate) fn namespace(doc: &Document, db: &str) -> Option<String> {
+    let (named, coll) = parts(doc)?;
+    Some(format!("{}.{}", named.unwrap_or(db), coll))
+}
+
+impl CollectionSchema {
+    /// Whether the field at `path` holds DBRefs: every one of its subdocuments has a string
+    /// `$ref` and an `$id`. Told by the field paths, so values needn't be profiled
+    pub fn is_dbref(&self, path: &str) -> bool {
+        let Some(objects) = self
+            .fields
+            .get(path)
+            .and_then(|field| field.type_counts.get(&BsonType::Object))
+        else {
+            return false;
+        };
+        let holds = |key: &str, ty: Option<BsonType>| {
+            self.fields
+                .get(&format!("{}.{}", path, key))
+                .is_some_and(|field| {
+                    let count = match &ty {
+                        Some(ty) => field.type_counts.get(ty).copied().unwrap_or(0),
+                        None => field.count,
+                    };
+                    count == *objects
+                })
+        };
+        *objects > 0 && holds("$ref", Some(BsonType::String)) && holds("$id", None)
+    }
+
+    /// The collections the DBRefs at `path` refer to, most referenced first, if its values
+    /// were profiled
+    pub fn dbref_targets(&self, path: &str) -> Option<Vec<&str>> {
+        if !self.is_dbref(path) {
+            return None;
+        }
+        let values = self.fields[path].values.as_ref()?;
+        let mut targets = values.dbrefs.iter().collect::<Vec<_>>();
+        targets.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        Some(
+            targets
+                .into_iter()
+                .map(|(target, _)| target.as_str())
+                .collect(),
+        )
+    }
+
+    /// Whether the field at `path` is one of a DBRef's own keys
+    pub(crate) fn in_dbref(&self, path: &str) -> bool {
+        path.rsplit_once('.')
+            .is_some_and(|(parent, key)| DBREF_KEYS.contains(&key) && self.is_dbref(parent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::*;
+    use crate::AnalysisOptions;
+
+    #[test]
+    fn reads_where_a_dbref_points() {
+        let local = doc! { "$ref": "users", "$id": 1 };
+        let remote = doc! { "$ref": "users", "$id": 1, "$db": "auth" };
+        assert_eq!(target(&local).as_deref(), Some("users"));
+        assert_eq!(target(&remote).as_deref(), Some("auth.users"));
+        assert_eq!(namespace(&local, "app").as_deref(), Some("app.users"));
+        assert_eq!(namespace(&remote, "app").as_deref(), Some("auth.users"));
+        assert_eq!(target(&doc! { "$ref": "users" }), None);
+        assert_eq!(target(&doc! { "$ref": 1, "$id": 1 }), None);
+    }
+
+    #[test]
+    fn recognizes_fields_holding_dbrefs() {
+        let options = AnalysisOptions {
+            profile_values: true,
+            check_field_names: true,
+            ..AnalysisOptions::default()
+        };
+        let documents = vec![
+            doc! { "owner": { "$ref": "users", "$id": 1 }, "other": { "$ref": "users" } },
+            doc! { "owner": { "$ref": "admins", "$id": 2, "$db": "auth" } },
+            doc! { "owner": { "$ref": "users", "$id": 3 } },
+        ];
+        let schema = CollectionSchema::from_documents(documents, "app.things", &options);
+        assert!(schema.is_dbref("owner"));
+        assert!(schema.in_dbref("owner.$id"));
+        assert!(!schema.is_dbref("other"));
+        assert!(!schema.in_dbref("other.$ref"));
+        assert_eq!(
+            schema.dbref_targets("owner"),
+            Some(vec!["users", "auth.admins"])
+        );
+        // nothing is wrong with a DBRef's keys
+        assert!(!schema
+            .anomalies
+            .iter()
+            .any(|anomaly| anomaly.path.contains('$')));
+    }
+}
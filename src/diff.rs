@@ -0,0 +1,151 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{CollectionSchema, FormatVersion, TypeMode};
+
+/// How one schema differs from another, field by field
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaDiff {
+    #[serde(rename = "formatVersion", default = "FormatVersion::unversioned")]
+    pub format_version: FormatVersion,
+    /// Paths only present in the new schema
+    pub added: Vec<String>,
+    /// Paths only present in the old schema
+    pub removed: Vec<String>,
+    /// Paths present in both whose set of types differs
+    pub changed: Vec<TypeChange>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TypeChange {
+    pub path: String,
+    /// Types only seen in the new schema. "missing" here means the field became optional
+    pub added_types: Vec<String>,
+    /// Types only seen in the old schema
+    pub removed_types: Vec<String>,
+}
+
+impl SchemaDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl CollectionSchema {
+    /// Compare against `new`, treating `self` as the old schema
+    pub fn diff(&self, new: &CollectionSchema) -> SchemaDiff {
+        // a canonical schema can't be made strict again, so the strict one is made canonical
+        if self.type_mode != new.type_mode {
+            let (mut old, mut new) = (self.clone(), new.clone());
+            old.normalize_types(TypeMode::Canonical);
+            new.normalize_types(TypeMode::Canonical);
+            return old.diff(&new);
+        }
+        let mut diff = SchemaDiff::default();
+        for (path, old_field) in &self.fields {
+            let Some(new_field) = new.fields.get(path) else {
+                diff.removed.push(path.clone());
+                continue;
+            };
+            let added_types = new_field
+                .types
+                .iter()
+                .filter(|t| !old_field.types.contains(t))
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>();
+            let removed_types = old_field
+                .types
+                .iter()
+                .filter(|t| !new_field.types.contains(t))
+                .map(|t| t.to_string())
+                .collect::<Vec<_>>();
+            if !added_types.is_empty() || !removed_types.is_empty() {
+                diff.changed.push(TypeChange {
+                    path: path.clone(),
+                    added_types,
+                    removed_types,
+                });
+            }
+        }
+        diff.added = new
+            .fields
+            .keys()
+            .filter(|path| !self.fields.contains_key(*path))
+            .cloned()
+            .collect();
+        diff
+    }
+}
+
+impl fmt::Display for SchemaDiff {
+    /// `+` for added fields, `-` for removed ones and `~` for type changes
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no differences");
+        }
+        for path in &self.added {
+            writeln!(f, "+ {}", path)?;
+        }
+        for path in &self.removed {
+            writeln!(f, "- {}", path)?;
+        }
+        for change in &self.changed {
+            let types = change
+                .added_types
+                .iter()
+                .map(|t| format!("+{}", t))
+                .chain(change.removed_types.iter().map(|t| format!("-{}", t)))
+                .collect::<Vec<_>>();
+            writeln!(f, "~ {}: {}", change.path, types.join(" "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    fn schema(ndjson: &str, type_mode: TypeMode) -> CollectionSchema {
+        let options = AnalysisOptions {
+            type_mode,
+            ..AnalysisOptions::default()
+        };
+        CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options).unwrap()
+    }
+
+    #[test]
+    fn lists_added_removed_and_retyped_fields() {
+        let old = schema("{\"a\": 1, \"b\": \"x\", \"c\": true}\n", TypeMode::Strict);
+        let new = schema(
+            "{\"a\": \"one\", \"c\": true, \"d\": 1}\n{\"c\": false}\n",
+            TypeMode::Strict,
+        );
+        let diff = old.diff(&new);
+        assert_eq!(diff.added, ["d"]);
+        assert_eq!(diff.removed, ["b"]);
+        assert_eq!(
+            diff.changed,
+            [TypeChange {
+                path: "a".to_string(),
+                added_types: vec!["string".to_string(), "missing".to_string()],
+                removed_types: vec!["int".to_string()],
+            }]
+        );
+        assert_eq!(diff.to_string(), "+ d\n- b\n~ a: +string +missing -int\n");
+        assert!(old.diff(&old).is_empty());
+        assert_eq!(old.diff(&old).to_string(), "no differences\n");
+    }
+
+    #[test]
+    fn compares_in_canonical_types_when_either_schema_is() {
+        let old = schema("{\"n\": 1}\n", TypeMode::Strict);
+        let new = schema("{\"n\": {\"$numberLong\": \"1\"}}\n", TypeMode::Canonical);
+        assert!(old.diff(&new).is_empty(), "{}", old.diff(&new));
+        assert!(!old
+            .diff(&schema("{\"n\": 1.5}\n", TypeMode::Strict))
+            .is_empty());
+    }
+}
@@ -0,0 +1,397 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs::File,
+    io::{self, BufRead, BufReader, Read},
+    path::Path,
+};
+
+use bson::{Bson, Document};
+
+use crate::{
+    variants::{discriminator, Variants, Versions},
+    AnalysisOptions, CollectionSchema, FormatVersion, Progress, SampleMethod, Sampling,
+    SchemaBuilder, SchemaReport,
+};
+
+/// First four bytes of a `mongodump --archive` file
+const ARCHIVE_MAGIC: u32 = 0x8199_e26d;
+/// Ends each run of documents in an archive, where a document length would otherwise be
+const TERMINATOR: u32 = 0xffff_ffff;
+
+/// Most distinct discriminator values a file's documents are grouped by. Documents holding
+/// values first seen after that are only counted
+const MAX_GROUPED_VALUES: usize = 1000;
+
+impl CollectionSchema {
+    /// Infer the schema of a raw `.bson` file, as written per collection by mongodump or
+    /// bsondump. The namespace is taken from mongodump's `<db>/<collection>.bson` layout.
+    /// Every document is read, but with [`Sampling::Size`] only the first are inferred from, and
+    /// with [`Sampling::Percent`] evenly spaced ones. Compressed (`--gzip`) dumps need
+    /// decompressing first
+    pub fn from_bson_file(path: impl AsRef<Path>, options: &AnalysisOptions) -> io::Result<Self> {
+        let path = path.as_ref();
+        let collection = path.file_stem().unwrap_or_default().to_string_lossy();
+        let namespace = match path.parent().and_then(Path::file_name) {
+            Some(db) => format!("{}.{}", db.to_string_lossy(), collection),
+            None => collection.into_owned(),
+        };
+        Self::from_bson_reader(BufReader::new(File::open(path)?), namespace, options)
+    }
+
+    /// Infer the schema of BSON documents back to back, as in a `.bson` file, sampling them
+    /// as [`CollectionSchema::from_bson_file`] does
+    pub fn from_bson_reader(
+        mut reader: impl BufRead,
+        namespace: impl Into<String>,
+        options: &AnalysisOptions,
+    ) -> io::Result<Self> {
+        let mut sample = Sample::new(options);
+        while let Some(item) = read_item(&mut reader)? {
+            match item {
+                Item::Document(bytes) => sample.add(&bytes)?,
+                Item::Terminator => return Err(invalid("unexpected archive terminator")),
+            }
+        }
+        Ok(sample.build(namespace.into()))
+    }
+
+    /// Infer the schema of documents read from anywhere, sampling them as
+    /// [`CollectionSchema::from_bson_file`] does. Needs no deployment, so it is there without
+    /// the `driver` feature
+    pub fn from_documents(
+        documents: impl IntoIterator<Item = Document>,
+        namespace: impl Into<String>,
+        options: &AnalysisOptions,
+    ) -> Self {
+        let mut sample = Sample::new(options);
+        for doc in documents {
+            // infallible: the document is already parsed
+            sample.add_with(|| Ok(doc)).unwrap();
+        }
+        sample.build(namespace.into())
+    }
+}
+
+impl SchemaReport {
+    /// Infer the schema of every collection in an uncompressed `mongodump --archive` file,
+    /// applying `options` to each as [`CollectionSchema::from_bson_file`] does
+    pub fn from_archive(path: impl AsRef<Path>, options: &AnalysisOptions) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if u32::from_le_bytes(magic) != ARCHIVE_MAGIC {
+            return Err(invalid("not a mongodump archive"));
+        }
+
+        // the prelude: a header, then the metadata of every dumped collection
+        loop {
+            match read_item(&mut reader)? {
+                Some(Item::Document(_)) => {}
+                Some(Item::Terminator) => break,
+                None => return Err(io::ErrorKind::UnexpectedEof.into()),
+            }
+        }
+
+        // then blocks of documents, each introduced by a header naming its namespace. Blocks of
+        // different collections are interleaved when mongodump dumped them in parallel
+        let mut samples = BTreeMap::<String, Sample>::new();
+        while let Some(item) = read_item(&mut reader)? {
+            let Item::Document(bytes) = item else {
+                return Err(invalid("expected a namespace header"));
+            };
+            let header = Document::from_reader(&bytes[..]).map_err(invalid)?;
+            let (Ok(db), Ok(collection)) = (header.get_str("db"), header.get_str("collection"))
+            else {
+                return Err(invalid("namespace header without db or collection"));
+            };
+            let sample = samples
+                .entry(format!("{}.{}", db, collection))
+                .or_insert_with(|| Sample::new(options));
+            loop {
+                match read_item(&mut reader)? {
+                    Some(Item::Document(bytes)) => sample.add(&bytes)?,
+                    Some(Item::Terminator) => break,
+                    None => return Err(io::ErrorKind::UnexpectedEof.into()),
+                }
+            }
+        }
+
+        Ok(Self {
+            format_version: FormatVersion::default(),
+            namespaces: samples
+                .into_iter()
+                .map(|(ns, sample)| (ns.clone(), sample.build(ns)))
+                .collect(),
+            relationships: Vec::new(),
+            failures: BTreeMap::new(),
+        })
+    }
+}
+
+/// The documents of one collection, inferred from those the sampling selects
+pub(crate) struct Sample {
+    builder: SchemaBuilder,
+    options: AnalysisOptions,
+    documents: u64,
+    /// The selected documents split by the value of [`AnalysisOptions::group_by`]
+    variants: Partition,
+    /// And by that of [`AnalysisOptions::version_field`]
+    versions: Partition,
+}
+
+/// Selected documents split by the value of a field
+#[derive(Default)]
+struct Partition {
+    /// The documents of each value, keyed by its Extended JSON
+    values: BTreeMap<String, (Bson, Sample)>,
+    /// The values that came too late to be grouped by, and their documents
+    ungrouped: (BTreeSet<String>, u64),
+}
+
+impl Partition {
+    /// Fold a selected document into the schema of its `value`
+    fn add(&mut self, value: Bson, doc: Document, options: &AnalysisOptions) {
+        let key = value.clone().into_relaxed_extjson().to_string();
+        if !self.values.contains_key(&key) && self.values.len() == MAX_GROUPED_VALUES {
+            self.ungrouped.0.insert(key);
+            self.ungrouped.1 += 1;
+            return;
+        }
+        let (_, sample) = self.values.entry(key).or_insert_with(|| {
+            // the documents of each value are already selected
+            let options = AnalysisOptions {
+                sampling: Sampling::Full,
+                group_by: None,
+                version_field: None,
+                progress: None,
+                ..options.clone()
+            };
+            (value, Sample::new(&options))
+        });
+        // infallible: the document is already parsed
+        sample.add_with(|| Ok(doc)).unwrap();
+    }
+
+    /// The schema of each value, as (value, documents, schema)
+    fn build(
+        self,
+        namespace: &str,
+        sample_method: SampleMethod,
+    ) -> Vec<(Bson, u64, CollectionSchema)> {
+        self.values
+            .into_values()
+            .map(|(value, sample)| {
+                let documents = sample.documents;
+                let mut schema = sample.build(namespace.to_string());
+                schema.sample_method = sample_method;
+                (value, documents, schema)
+            })
+            .collect()
+    }
+}
+
+impl Sample {
+    pub(crate) fn new(options: &AnalysisOptions) -> Self {
+        let mut builder = SchemaBuilder::new(options.max_depth).limited_by(options);
+        if let Some(top) = options.shapes {
+            builder = builder.with_shapes(top);
+        }
+        if options.profiles_values() {
+            builder = builder
+                .with_examples(options.examples, options.redaction)
+                .with_top_values(options.top_values);
+        }
+        if options.stats {
+            builder = builder.with_stats();
+            if let Some(scale) = options.histograms {
+                builder = builder.with_histograms(scale, options.histogram_buckets);
+            }
+        }
+        if options.field_sizes {
+            builder = builder.with_field_sizes();
+        }
+        if options.document_sizes {
+            builder = builder.with_document_sizes();
+        }
+        if options.document_widths {
+            builder = builder.with_document_widths();
+        }
+        if let Some(field) = &options.time_field {
+            builder = builder.with_evolution(field, options.time_bucket);
+        }
+        if options.check_field_names {
+            builder = builder.with_name_check();
+        }
+        Self {
+            builder,
+            options: options.clone(),
+            documents: 0,
+            variants: Partition::default(),
+            versions: Partition::default(),
+        }
+    }
+
+    /// Count a document, only decoding it with `parse` if the sampling selects it
+    pub(crate) fn add_with(
+        &mut self,
+        parse: impl FnOnce() -> io::Result<Document>,
+    ) -> io::Result<()> {
+        self.documents += 1;
+        let selected = match self.options.sampling {
+            Sampling::Auto | Sampling::Full => true,
+            Sampling::Size(size) => self.builder.documents() < size,
+            // keep the sample at the percentage of the documents read so far
+            Sampling::Percent(percent) => {
+                (self.builder.documents() as f64) < self.documents as f64 * percent / 100.0
+            }
+        };
+        if selected {
+            let doc = parse()?;
+            self.builder.add(&doc);
+            if let Some(field) = &self.options.version_field {
+                self.versions
+                    .add(discriminator(&doc, field), doc.clone(), &self.options);
+            }
+            if let Some(field) = &self.options.group_by {
+                self.variants
+                    .add(discriminator(&doc, field), doc, &self.options);
+            }
+            if let Some(progress) = &self.options.progress {
+                progress.report(Progress::Documents {
+                    read: self.builder.documents(),
+                    expected: None,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.add_with(|| Document::from_reader(bytes).map_err(invalid))
+    }
+
+    pub(crate) fn build(self, namespace: String) -> CollectionSchema {
+        let mut schema = self.builder.build(namespace.clone());
+        schema.document_count = self.documents;
+        schema.sizes = schema.sizes.map(|sizes| sizes.extrapolate(self.documents));
+        schema.sample_method = match self.options.sampling {
+            Sampling::Auto | Sampling::Full => SampleMethod::All,
+            Sampling::Size(_) => SampleMethod::First,
+            Sampling::Percent(_) => SampleMethod::Spread,
+        };
+        schema.condition_presence();
+        let reported = self.options.reported_paths(schema.fields.keys());
+        schema.fields.retain(|path, _| reported.contains(path));
+        if let Some(shapes) = &mut schema.shapes {
+            shapes.retain_paths(|path| reported.contains(path));
+        }
+        if let Some(evolution) = &mut schema.evolution {
+            evolution.fields.retain(|path, _| reported.contains(path));
+        }
+        schema.normalize_types(self.options.type_mode);
+        schema.anomalies = schema.find_anomalies(&self.options.profile_thresholds);
+        schema.rare_fields = schema.find_rare_fields(self.options.rare_fields);
+        schema.estimate_confidence();
+        if self.options.type_shares {
+            schema.share_types();
+        }
+        if self.options.detect_pii {
+            schema.privacy = Some(schema.find_pii());
+        }
+        if let Some(field) = &self.options.group_by {
+            let (other_values, other_documents) = &self.variants.ungrouped;
+            let (other_values, other_documents) = (other_values.len() as u64, *other_documents);
+            let partitions = self.variants.build(&namespace, schema.sample_method);
+            let mut variants = Variants::new(&namespace, field, partitions);
+            variants.other_values += other_values;
+            variants.other_documents += other_documents;
+            schema.variants = Some(variants);
+        }
+        if let Some(field) = &self.options.version_field {
+            let (other_values, other_documents) = &self.versions.ungrouped;
+            let (other_values, other_documents) = (other_values.len() as u64, *other_documents);
+            let partitions = self.versions.build(&namespace, schema.sample_method);
+            let mut versions = Versions::new(&namespace, field, partitions);
+            versions.other_values += other_values;
+            versions.other_documents += other_documents;
+            schema.versions = Some(versions);
+        }
+        schema
+    }
+}
+
+enum Item {
+    Document(Vec<u8>),
+    Terminator,
+}
+
+/// Read the next length-prefixed document, or `None` at the end of the file
+fn read_item(reader: &mut impl BufRead) -> io::Result<Option<Item>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut len = [0; 4];
+    reader.read_exact(&mut len)?;
+    match u32::from_le_bytes(len) {
+        TERMINATOR => Ok(Some(Item::Terminator)),
+        // the smallest document is the length, no elements and the trailing nul
+        n if n < 5 => Err(invalid("invalid document length")),
+        n => {
+            let mut bytes = vec![0; n as usize];
+            bytes[..4].copy_from_slice(&len);
+            reader.read_exact(&mut bytes[4..])?;
+            Ok(Some(Item::Document(bytes)))
+        }
+    }
+}
+
+pub(crate) fn invalid(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::*;
+    use crate::{
+        codegen::{Annotation, UnionStrategy},
+        BsonType,
+    };
+
+    // runs with `cargo test --lib --no-default-features` too, for the driver-free build
+    #[test]
+    fn infers_and_generates_from_documents_without_a_deployment() {
+        let documents = vec![
+            doc! { "sku": "A-1", "qty": 2 },
+            doc! { "sku": "B-2", "qty": 5, "note": "gift" },
+        ];
+        let schema =
+            CollectionSchema::from_documents(documents, "shop.items", &AnalysisOptions::default());
+        assert_eq!(schema.sample_size, 2);
+        assert_eq!(schema.fields["qty"].types, [BsonType::Int]);
+        assert_eq!(schema.presence("note"), Some(0.5));
+        let typescript = schema.to_typescript(UnionStrategy::Union, 1.0, Annotation::None);
+        assert!(typescript.contains("note?: string;"), "{}", typescript);
+    }
+
+    #[test]
+    fn reports_only_the_fields_matching_the_pattern() {
+        let documents = vec![
+            doc! { "name": "a", "created_at": 1, "audit": { "updated_at": 2, "by": "x" } },
+            doc! { "name": "b", "created_at": 3, "events": [{ "seen_at": 4 }] },
+        ];
+        let options = AnalysisOptions {
+            field_pattern: Some("*_at".to_string()),
+            ..AnalysisOptions::default()
+        };
+        let schema = CollectionSchema::from_documents(documents, "app.users", &options);
+        assert_eq!(
+            schema.fields.keys().collect::<Vec<_>>(),
+            ["audit.updated_at", "created_at", "events[].seen_at"]
+        );
+        // counted as without the pattern
+        assert_eq!(schema.sample_size, 2);
+        assert_eq!(schema.presence("audit.updated_at"), Some(0.5));
+    }
+}
@@ -0,0 +1,73 @@
+use std::{io, net::SocketAddr, path::PathBuf};
+
+#[cfg(feature = "driver")]
+use mongodb::error::ErrorKind;
+use thiserror::Error;
+
+pub type Result<T, E = AnalyzerError> = std::result::Result<T, E>;
+
+/// Why an analysis failed. Driver errors are sorted by cause so callers can tell a cluster
+/// that can't be reached from one that refused the credentials or the query
+#[derive(Debug, Error)]
+pub enum AnalyzerError {
+    /// The connection string couldn't be parsed or resolved
+    #[cfg(feature = "driver")]
+    #[error("invalid connection string: {}", .0.kind)]
+    InvalidUri(#[source] mongodb::error::Error),
+    /// No suitable server could be reached, or the connection dropped
+    #[cfg(feature = "driver")]
+    #[error("could not connect: {}", .0.kind)]
+    Connection(#[source] mongodb::error::Error),
+    /// The server rejected the credentials
+    #[cfg(feature = "driver")]
+    #[error("authentication failed: {}", .0.kind)]
+    Authentication(#[source] mongodb::error::Error),
+    /// The server refused or failed an operation, e.g. for lack of privileges
+    #[cfg(feature = "driver")]
+    #[error("{}", .0.kind)]
+    Server(#[source] mongodb::error::Error),
+    /// The options given contradict each other
+    #[error("{0}")]
+    InvalidOptions(String),
+    /// The aggregation returned something other than what the pipeline produces
+    #[error("unexpected aggregation result: {0}")]
+    MalformedResult(String),
+    /// A file couldn't be read or written, or didn't hold what was expected
+    #[error("{}: {source}", path.display())]
+    File {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A server, like the metrics endpoint, couldn't listen on its address
+    #[error("could not listen on {addr}: {source}")]
+    Listen {
+        addr: SocketAddr,
+        #[source]
+        source: io::Error,
+    },
+    /// Schema changes couldn't be published to Kafka
+    #[cfg(feature = "kafka")]
+    #[error("kafka: {0}")]
+    Kafka(#[source] kafka::Error),
+    /// A webhook couldn't be reached or refused a notification
+    #[cfg(feature = "driver")]
+    #[error("webhook: {0}")]
+    Webhook(#[source] reqwest::Error),
+    /// The interactive browser couldn't draw on or read from the terminal
+    #[error("terminal: {0}")]
+    Terminal(#[source] io::Error),
+}
+
+#[cfg(feature = "driver")]
+impl From<mongodb::error::Error> for AnalyzerError {
+    fn from(error: mongodb::error::Error) -> Self {
+        match *error.kind {
+            ErrorKind::Authentication { .. } => Self::Authentication(error),
+            ErrorKind::ServerSelection { .. } | ErrorKind::DnsResolve { .. } | ErrorKind::Io(_) => {
+                Self::Connection(error)
+            }
+            _ => Self::Server(error),
+        }
+    }
+}
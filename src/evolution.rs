@@ -0,0 +1,386 @@
+//! A sample followed over time: broken down by the period of a date field, to tell when each
+//! field started and stopped showing up, and when its types changed
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use bson::{Bson, DateTime, Document};
+#[cfg(feature = "driver")]
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::SchemaAnalyzer;
+use crate::{
+    pipeline::{evolution_pipeline, parse_evolution, Descent},
+    stats::{civil_date, DateBucket},
+    variants::discriminator,
+    BsonType, Result, TimeBucket, TypeMode,
+};
+
+/// How the sampled documents' fields changed from one period of a date field to the next, see
+/// [`AnalysisOptions::time_field`](crate::AnalysisOptions::time_field)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Evolution {
+    /// The date field's path
+    pub field: String,
+    pub bucket: TimeBucket,
+    /// How many sampled documents fall in each period holding any, oldest first
+    pub periods: Vec<DateBucket>,
+    /// How many sampled documents hold neither a date nor an ObjectId in the field
+    #[serde(default)]
+    pub undated: u64,
+    /// What each path held over time
+    pub fields: BTreeMap<String, FieldHistory>,
+}
+
+/// What one path held over time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldHistory {
+    /// The first period any document holds the field in
+    pub first_seen: String,
+    /// The last one
+    pub last_seen: String,
+    /// The types the field held in each period holding it, oldest first
+    pub periods: Vec<PeriodTypes>,
+}
+
+/// The types a field held in one period
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeriodTypes {
+    pub period: String,
+    /// How many of the period's documents hold each type for the field
+    pub types: BTreeMap<BsonType, u64>,
+}
+
+/// The types a field started and stopped holding in a period, relative to the previous period
+/// holding it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeShift {
+    pub period: String,
+    pub added: Vec<BsonType>,
+    pub removed: Vec<BsonType>,
+}
+
+/// The documents of one period, and how many of them hold each type at each path
+#[derive(Debug, Clone, Default)]
+pub(crate) struct PeriodCounts {
+    pub(crate) documents: u64,
+    pub(crate) types: BTreeMap<String, BTreeMap<BsonType, u64>>,
+}
+
+impl TimeBucket {
+    /// The `$dateToString` format of the periods
+    pub(crate) fn format(&self) -> &'static str {
+        match self {
+            TimeBucket::Day => "%Y-%m-%d",
+            TimeBucket::Month => "%Y-%m",
+            TimeBucket::Year => "%Y",
+        }
+    }
+
+    /// The period `date` falls in, formatted like [`TimeBucket::format`]
+    pub(crate) fn period(&self, date: DateTime) -> String {
+        let (year, month, day) = civil_date(date);
+        match self {
+            TimeBucket::Day => format!("{:04}-{:02}-{:02}", year, month, day),
+            TimeBucket::Month => format!("{:04}-{:02}", year, month),
+            TimeBucket::Year => format!("{:04}", year),
+        }
+    }
+}
+
+impl fmt::Display for TimeBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            TimeBucket::Day => "day",
+            TimeBucket::Month => "month",
+            TimeBucket::Year => "year",
+        })
+    }
+}
+
+impl Evolution {
+    pub(crate) fn new(
+        field: &str,
+        bucket: TimeBucket,
+        periods: BTreeMap<String, PeriodCounts>,
+        undated: u64,
+    ) -> Self {
+        let mut fields = BTreeMap::<String, Vec<PeriodTypes>>::new();
+        for (period, counts) in &periods {
+            for (path, types) in &counts.types {
+                fields.entry(path.clone()).or_default().push(PeriodTypes {
+                    period: period.clone(),
+                    types: types.clone(),
+                });
+            }
+        }
+        Evolution {
+            field: field.to_string(),
+            bucket,
+            periods: periods
+                .into_iter()
+                .filter(|(_, counts)| counts.documents > 0)
+                .map(|(period, counts)| DateBucket {
+                    period,
+                    count: counts.documents,
+                })
+                .collect(),
+            undated,
+            fields: fields
+                .into_iter()
+                .filter_map(|(path, periods)| {
+                    let history = FieldHistory {
+                        first_seen: periods.first()?.period.clone(),
+                        last_seen: periods.last()?.period.clone(),
+                        periods,
+                    };
+                    Some((path, history))
+                })
+                .collect(),
+        }
+    }
+
+    /// Report the types in `mode`'s names, see
+    /// [`CollectionSchema::normalize_types`](crate::CollectionSchema::normalize_types)
+    pub(crate) fn normalize_types(&mut self, mode: TypeMode) {
+        for history in self.fields.values_mut() {
+            for period in &mut history.periods {
+                let mut types = BTreeMap::<BsonType, u64>::new();
+                for (ty, count) in &period.types {
+                    *types.entry(ty.normalize(mode)).or_default() += count;
+                }
+                period.types = types;
+            }
+        }
+    }
+
+    /// The fields worth pointing out: those first seen after the first period, last seen
+    /// before the last, or whose types changed along the way
+    pub(crate) fn notable(&self) -> impl Iterator<Item = (&String, &FieldHistory)> {
+        let first = self.periods.first().map(|bucket| bucket.period.as_str());
+        let last = self.periods.last().map(|bucket| bucket.period.as_str());
+        self.fields.iter().filter(move |(_, history)| {
+            Some(history.first_seen.as_str()) != first
+                || Some(history.last_seen.as_str()) != last
+                || !history.type_changes().is_empty()
+        })
+    }
+
+    /// The summary line, e.g. `1200 documents by month of createdAt, from 2023-01 to 2024-06`
+    pub(crate) fn describe(&self) -> String {
+        let documents: u64 = self.periods.iter().map(|bucket| bucket.count).sum();
+        let mut summary = format!(
+            "{} documents by {} of {}",
+            documents, self.bucket, self.field
+        );
+        if let (Some(first), Some(last)) = (self.periods.first(), self.periods.last()) {
+            summary.push_str(&format!(", from {} to {}", first.period, last.period));
+        }
+        if self.undated > 0 {
+            summary.push_str(&format!(
+                "; {} more have no date in {}",
+                self.undated, self.field
+            ));
+        }
+        summary
+    }
+}
+
+impl FieldHistory {
+    /// The periods whose types differ from those of the previous period holding the field
+    pub fn type_changes(&self) -> Vec<TypeShift> {
+        let mut shifts = Vec::new();
+        let mut previous: Option<BTreeSet<&BsonType>> = None;
+        for period in &self.periods {
+            let types = period.types.keys().collect::<BTreeSet<_>>();
+            if let Some(previous) = &previous {
+                if *previous != types {
+                    shifts.push(TypeShift {
+                        period: period.period.clone(),
+                        added: types.difference(previous).map(|ty| (*ty).clone()).collect(),
+                        removed: previous
+                            .difference(&types)
+                            .map(|ty| (*ty).clone())
+                            .collect(),
+                    });
+                }
+            }
+            previous = Some(types);
+        }
+        shifts
+    }
+
+    /// The types it first held and how they changed, e.g. `string; 2024-01: +int -string`
+    pub(crate) fn describe_types(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(first) = self.periods.first() {
+            let types = first
+                .types
+                .keys()
+                .map(BsonType::to_string)
+                .collect::<Vec<_>>();
+            parts.push(types.join(", "));
+        }
+        for shift in self.type_changes() {
+            let changes = shift
+                .added
+                .iter()
+                .map(|ty| format!("+{}", ty))
+                .chain(shift.removed.iter().map(|ty| format!("-{}", ty)))
+                .collect::<Vec<_>>();
+            parts.push(format!("{}: {}", shift.period, changes.join(" ")));
+        }
+        parts.join("; ")
+    }
+}
+
+/// Collects the documents added to a [`SchemaBuilder`](crate::SchemaBuilder) into an
+/// [`Evolution`]
+#[derive(Debug, Clone)]
+pub(crate) struct EvolutionSummary {
+    field: String,
+    bucket: TimeBucket,
+    periods: BTreeMap<String, PeriodCounts>,
+    undated: u64,
+}
+
+impl EvolutionSummary {
+    pub(crate) fn new(field: String, bucket: TimeBucket) -> Self {
+        EvolutionSummary {
+            field,
+            bucket,
+            periods: BTreeMap::new(),
+            undated: 0,
+        }
+    }
+
+    /// Count `doc`, whose (path, type) pairs are `pairs`, in its period
+    pub(crate) fn observe<'a>(
+        &mut self,
+        doc: &Document,
+        pairs: impl IntoIterator<Item = &'a (String, BsonType)>,
+    ) {
+        let period = match discriminator(doc, &self.field) {
+            Bson::DateTime(date) => self.bucket.period(date),
+            Bson::ObjectId(id) => self.bucket.period(id.timestamp()),
+            _ => {
+                self.undated += 1;
+                return;
+            }
+        };
+        let counts = self.periods.entry(period).or_default();
+        counts.documents += 1;
+        for (path, ty) in pairs {
+            *counts
+                .types
+                .entry(path.clone())
+                .or_default()
+                .entry(ty.clone())
+                .or_default() += 1;
+        }
+    }
+
+    pub(crate) fn evolution(&self) -> Evolution {
+        Evolution::new(&self.field, self.bucket, self.periods.clone(), self.undated)
+    }
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Follow the fields of a sample of `sample_size` (or all documents) over the periods of
+    /// `field`, with an aggregation of its own
+    pub(crate) async fn analyze_evolution(
+        &self,
+        field: &str,
+        sample_size: Option<u64>,
+    ) -> Result<Evolution> {
+        let pipeline = evolution_pipeline(
+            self.options.filter.as_ref(),
+            sample_size,
+            Descent::of(&self.options),
+            field,
+            self.options.time_bucket,
+        );
+        let mut cursor = self
+            .collection()
+            .aggregate(pipeline, self.aggregate_options()?)
+            .await?;
+        let (periods, undated) = match cursor.try_next().await? {
+            Some(result) => parse_evolution(&result)?,
+            None => (BTreeMap::new(), 0),
+        };
+        Ok(Evolution::new(
+            field,
+            self.options.time_bucket,
+            periods,
+            undated,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnalysisOptions, CollectionSchema};
+
+    #[test]
+    fn follows_fields_and_their_types_by_period() {
+        let options = AnalysisOptions {
+            time_field: Some("at".to_string()),
+            time_bucket: TimeBucket::Month,
+            ..AnalysisOptions::default()
+        };
+        let ndjson = "{\"at\": {\"$date\": \"2024-01-05T00:00:00Z\"}, \"price\": \"9.99\", \"old\": 1}\n\
+                      {\"at\": {\"$date\": \"2024-01-20T00:00:00Z\"}, \"price\": \"5\", \"old\": 2}\n\
+                      {\"at\": {\"$date\": \"2024-02-01T00:00:00Z\"}, \"price\": 5.5}\n\
+                      {\"at\": {\"$date\": \"2024-03-01T00:00:00Z\"}, \"price\": 7.5, \"new\": true}\n\
+                      {\"at\": \"yesterday\", \"price\": 1.5}\n";
+        let schema = CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options).unwrap();
+        let evolution = schema.evolution.unwrap();
+        let periods = evolution
+            .periods
+            .iter()
+            .map(|bucket| (bucket.period.as_str(), bucket.count))
+            .collect::<Vec<_>>();
+        assert_eq!(periods, [("2024-01", 2), ("2024-02", 1), ("2024-03", 1)]);
+        assert_eq!(evolution.undated, 1);
+        assert_eq!(
+            evolution.describe(),
+            "4 documents by month of at, from 2024-01 to 2024-03; 1 more have no date in at"
+        );
+
+        let old = &evolution.fields["old"];
+        assert_eq!(
+            (old.first_seen.as_str(), old.last_seen.as_str()),
+            ("2024-01", "2024-01")
+        );
+        assert_eq!(evolution.fields["new"].first_seen, "2024-03");
+        let price = &evolution.fields["price"];
+        assert_eq!(
+            price.type_changes(),
+            [TypeShift {
+                period: "2024-02".to_string(),
+                added: vec![BsonType::Double],
+                removed: vec![BsonType::String],
+            }]
+        );
+        assert_eq!(price.describe_types(), "string; 2024-02: +double -string");
+        let notable = evolution
+            .notable()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(notable, ["new", "old", "price"]);
+    }
+
+    #[test]
+    fn formats_periods_like_date_to_string() {
+        let date = DateTime::parse_rfc3339_str("2024-07-04T12:00:00Z").unwrap();
+        assert_eq!(TimeBucket::Day.period(date), "2024-07-04");
+        assert_eq!(TimeBucket::Month.period(date), "2024-07");
+        assert_eq!(TimeBucket::Year.period(date), "2024");
+    }
+}
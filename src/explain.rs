@@ -0,0 +1,274 @@
+//! What the server did running the sampling, from `explain`, for tuning sample sizes and
+//! spotting collection scans and spills on large collections
+
+use std::fmt;
+
+use bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+#[cfg(feature = "driver")]
+use crate::SchemaAnalyzer;
+
+/// The execution statistics of the sampling, see [`SchemaAnalyzer::explain`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExplainStats {
+    /// Every stage in the order documents pass through them: the query plan's, then the
+    /// pipeline's. A sharded collection lists each shard's in turn
+    pub stages: Vec<StageStats>,
+    /// Summed over shards
+    pub documents_examined: u64,
+    pub keys_examined: u64,
+    /// The longest time any shard's query took, in milliseconds
+    pub execution_time_ms: u64,
+    /// Whether any stage wrote temporary files, see
+    /// [`AnalysisOptions::allow_disk_use`](crate::AnalysisOptions::allow_disk_use)
+    pub used_disk: bool,
+}
+
+/// One stage of the plan or pipeline
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StageStats {
+    /// The stage, e.g. `COLLSCAN` or `$group`, prefixed by the shard on sharded collections
+    pub name: String,
+    /// The server's estimate of the milliseconds spent up to and including the stage
+    pub execution_time_ms: Option<u64>,
+    /// How many documents the stage passed on
+    pub returned: Option<u64>,
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Run the sampling command of [`SchemaAnalyzer::dry_run`] under `explain` with
+    /// `executionStats` verbosity, which executes it in full, and collect what each stage did
+    pub async fn explain(&self) -> Result<ExplainStats> {
+        let command = self.dry_run().await?;
+        let explained = self
+            .client
+            .database(&self.ns.db)
+            .run_command(
+                doc! { "explain": command, "verbosity": "executionStats" },
+                None,
+            )
+            .await?;
+        let mut stats = ExplainStats::default();
+        stats.add(&explained, None);
+        stats.used_disk = used_disk(&Bson::Document(explained));
+        Ok(stats)
+    }
+}
+
+impl ExplainStats {
+    /// Collect the stages and counts of one server's `explained` output, or of each shard's
+    fn add(&mut self, explained: &Document, shard: Option<&str>) {
+        if let Ok(shards) = explained.get_document("shards") {
+            for (name, shard) in shards {
+                if let Some(shard) = shard.as_document() {
+                    self.add(shard, Some(name));
+                }
+            }
+            return;
+        }
+        if let Ok(execution) = explained.get_document("executionStats") {
+            self.add_execution(execution, shard);
+        }
+        for stage in explained.get_array("stages").into_iter().flatten() {
+            let Some(stage) = stage.as_document() else {
+                continue;
+            };
+            let Some((name, body)) = stage.iter().find(|(key, _)| key.starts_with('$')) else {
+                continue;
+            };
+            // the query the pipeline starts with, planned like a find
+            if let Some(execution) = body
+                .as_document()
+                .and_then(|body| body.get_document("executionStats").ok())
+            {
+                self.add_execution(execution, shard);
+            }
+            self.stages.push(StageStats {
+                name: qualified(shard, name),
+                execution_time_ms: number(stage, "executionTimeMillisEstimate"),
+                returned: number(stage, "nReturned"),
+            });
+        }
+    }
+
+    /// Count a query plan's `executionStats` and list its stages, innermost first
+    fn add_execution(&mut self, execution: &Document, shard: Option<&str>) {
+        self.documents_examined += number(execution, "totalDocsExamined").unwrap_or(0);
+        self.keys_examined += number(execution, "totalKeysExamined").unwrap_or(0);
+        self.execution_time_ms = self
+            .execution_time_ms
+            .max(number(execution, "executionTimeMillis").unwrap_or(0));
+        if let Ok(stage) = execution.get_document("executionStages") {
+            self.add_plan_stage(stage, shard);
+        }
+    }
+
+    fn add_plan_stage(&mut self, stage: &Document, shard: Option<&str>) {
+        if let Ok(input) = stage.get_document("inputStage") {
+            self.add_plan_stage(input, shard);
+        }
+        for input in stage.get_array("inputStages").into_iter().flatten() {
+            if let Some(input) = input.as_document() {
+                self.add_plan_stage(input, shard);
+            }
+        }
+        if let Ok(name) = stage.get_str("stage") {
+            self.stages.push(StageStats {
+                name: qualified(shard, name),
+                execution_time_ms: number(stage, "executionTimeMillisEstimate"),
+                returned: number(stage, "nReturned"),
+            });
+        }
+    }
+}
+
+impl fmt::Display for ExplainStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Explain: {}ms, {} documents and {} keys examined, {}",
+            self.execution_time_ms,
+            self.documents_examined,
+            self.keys_examined,
+            if self.used_disk {
+                "spilled to disk"
+            } else {
+                "in memory"
+            }
+        )?;
+        let width = self
+            .stages
+            .iter()
+            .map(|stage| stage.name.len())
+            .max()
+            .unwrap_or(0);
+        for stage in &self.stages {
+            let time = stage
+                .execution_time_ms
+                .map_or_else(|| "-".to_string(), |ms| format!("{}ms", ms));
+            let returned = stage
+                .returned
+                .map_or_else(String::new, |n| format!("  {} returned", n));
+            writeln!(f, "  {:<width$}  {:>8}{}", stage.name, time, returned)?;
+        }
+        Ok(())
+    }
+}
+
+fn qualified(shard: Option<&str>, name: &str) -> String {
+    match shard {
+        Some(shard) => format!("{}: {}", shard, name),
+        None => name.to_string(),
+    }
+}
+
+/// The count at `key`, whichever numeric type the server reported it as
+pub(crate) fn number(doc: &Document, key: &str) -> Option<u64> {
+    count(doc.get(key)?)
+}
+
+fn count(value: &Bson) -> Option<u64> {
+    match value {
+        Bson::Int32(n) => u64::try_from(*n).ok(),
+        Bson::Int64(n) => u64::try_from(*n).ok(),
+        Bson::Double(n) if *n >= 0.0 => Some(*n as u64),
+        _ => None,
+    }
+}
+
+/// Whether any stage anywhere in `value` reports writing to disk: `usedDisk` before 6.0,
+/// `spills` since
+fn used_disk(value: &Bson) -> bool {
+    match value {
+        Bson::Document(doc) => doc.iter().any(|(key, value)| match (key.as_str(), value) {
+            ("usedDisk", Bson::Boolean(true)) => true,
+            ("spills", spills) => count(spills).is_some_and(|spills| spills > 0),
+            _ => used_disk(value),
+        }),
+        Bson::Array(values) => values.iter().any(used_disk),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::bson;
+
+    use super::*;
+
+    fn collection_scan(returned: i32) -> Document {
+        doc! {
+            "executionStats": {
+                "totalDocsExamined": 1000,
+                "totalKeysExamined": 0_i64,
+                "executionTimeMillis": 12.0,
+                "executionStages": {
+                    "stage": "SAMPLE",
+                    "nReturned": returned,
+                    "executionTimeMillisEstimate": 10,
+                    "inputStage": { "stage": "COLLSCAN", "nReturned": 1000 },
+                },
+            },
+        }
+    }
+
+    #[test]
+    fn lists_the_plan_and_pipeline_stages_in_order() {
+        let explained = doc! {
+            "stages": [
+                { "$cursor": collection_scan(100) },
+                { "$group": { "_id": null }, "nReturned": 1, "executionTimeMillisEstimate": 11 },
+            ],
+        };
+        let mut stats = ExplainStats::default();
+        stats.add(&explained, None);
+        let names = stats
+            .stages
+            .iter()
+            .map(|stage| stage.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["COLLSCAN", "SAMPLE", "$cursor", "$group"]);
+        assert_eq!(stats.stages[1].execution_time_ms, Some(10));
+        assert_eq!(stats.stages[0].execution_time_ms, None);
+        assert_eq!(
+            (
+                stats.documents_examined,
+                stats.keys_examined,
+                stats.execution_time_ms
+            ),
+            (1000, 0, 12)
+        );
+        let text = stats.to_string();
+        assert!(text.starts_with("Explain: 12ms, 1000 documents and 0 keys examined, in memory\n"));
+        assert!(
+            text.contains("  $group        11ms  1 returned\n"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn sums_shards_and_finds_spills() {
+        let explained = doc! {
+            "shards": { "shard0": collection_scan(10), "shard1": collection_scan(20) },
+        };
+        let mut stats = ExplainStats::default();
+        stats.add(&explained, None);
+        assert_eq!(stats.documents_examined, 2000);
+        assert_eq!(stats.execution_time_ms, 12);
+        assert_eq!(stats.stages[0].name, "shard0: COLLSCAN");
+        assert_eq!(stats.stages[3].name, "shard1: SAMPLE");
+
+        assert!(!used_disk(&Bson::Document(explained)));
+        assert!(used_disk(
+            &bson!({ "stages": [{ "$sort": { "usedDisk": true } }] })
+        ));
+        assert!(used_disk(
+            &bson!({ "stages": [{ "$group": {}, "spills": 2_i64 }] })
+        ));
+        assert!(!used_disk(&bson!({ "spills": 0 })));
+    }
+}
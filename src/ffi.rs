@@ -0,0 +1,108 @@
+//! A C API for services embedding the inference instead of running the binary, declared in
+//! `include/schema_analyzer.h`. Build with `--features ffi`; the header is regenerated with
+//! `cbindgen --config cbindgen.toml --output include/schema_analyzer.h`.
+//!
+//! Strings returned are owned by the caller and freed with [`schema_analyzer_free_string`].
+//! Failures return null, leaving a message for [`schema_analyzer_last_error`]
+
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    panic::{self, AssertUnwindSafe},
+    ptr, slice,
+};
+
+use crate::{AnalysisOptions, CollectionSchema};
+
+thread_local! {
+    /// Why the last call on this thread failed
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Infer the schema of `len` bytes of BSON documents back to back, as in a mongodump `.bson`
+/// file, and return it as a JSON string naming it `ns`, e.g. `shop.orders`. `options_json` is
+/// a JSON object of the library's `AnalysisOptions` keys, or null for the defaults.
+///
+/// # Safety
+///
+/// `data` must point to `len` readable bytes, or be null if `len` is 0. `ns` must be a
+/// nul-terminated string, and `options_json` one or null
+#[no_mangle]
+pub unsafe extern "C" fn schema_analyzer_analyze_bson(
+    data: *const u8,
+    len: usize,
+    ns: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char {
+    let documents = match data.is_null() {
+        true => &[][..],
+        false => slice::from_raw_parts(data, len),
+    };
+    let namespace = match ns.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(ns)),
+    };
+    let options = match options_json.is_null() {
+        true => None,
+        false => Some(CStr::from_ptr(options_json)),
+    };
+    // a panic mustn't unwind into the caller's frames
+    let result = panic::catch_unwind(AssertUnwindSafe(|| analyze(documents, namespace, options)))
+        .unwrap_or_else(|_| Err("the analysis panicked".to_string()));
+    match result.and_then(|json| CString::new(json).map_err(|e| e.to_string())) {
+        Ok(json) => json.into_raw(),
+        Err(message) => {
+            set_last_error(message);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Why the last call on this thread that returned null failed, or null if none has. The
+/// string stays valid until the next failing call on the thread; don't free it
+#[no_mangle]
+pub extern "C" fn schema_analyzer_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| error.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Free a string returned by this API. Null is ignored.
+///
+/// # Safety
+///
+/// `s` must have been returned by this API and not freed already
+#[no_mangle]
+pub unsafe extern "C" fn schema_analyzer_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+fn analyze(
+    documents: &[u8],
+    namespace: Option<&CStr>,
+    options: Option<&CStr>,
+) -> Result<String, String> {
+    let namespace = match namespace {
+        Some(namespace) => namespace
+            .to_str()
+            .map_err(|e| format!("namespace: {}", e))?,
+        None => return Err("no namespace".to_string()),
+    };
+    let options = match options {
+        Some(options) => {
+            let options = options.to_str().map_err(|e| format!("options: {}", e))?;
+            serde_json::from_str::<AnalysisOptions>(options)
+                .map_err(|e| format!("invalid options: {}", e))?
+        }
+        None => AnalysisOptions::default(),
+    };
+    let schema = CollectionSchema::from_bson_reader(documents, namespace, &options)
+        .map_err(|e| e.to_string())?;
+    serde_json::to_string(&schema).map_err(|e| e.to_string())
+}
+
+fn set_last_error(message: String) {
+    // messages can't hold nuls, though a namespace might
+    let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
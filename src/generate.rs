@@ -0,0 +1,389 @@
+//! Fake documents shaped like a schema's, for seeding test environments that mirror
+//! production without copying its data
+
+use std::collections::BTreeMap;
+
+use bson::{
+    oid::ObjectId, spec::BinarySubtype, Binary, Bson, DateTime, Document, JavaScriptCodeWithScope,
+    Regex, Timestamp,
+};
+use rand::{distributions::Alphanumeric, rngs::StdRng, Rng, SeedableRng};
+
+use crate::{BinaryKind, BsonType, CollectionSchema, FieldSchema, ScalarValue};
+
+/// How long an array is at most, as schemas don't record array lengths
+const MAX_ARRAY_LENGTH: usize = 4;
+
+/// The range of dates generated for fields without [`DateStats`](crate::DateStats): the year
+/// up to now
+const DEFAULT_DATE_SPAN_MS: i64 = 365 * 86_400_000;
+
+/// Documents conforming to a schema, see [`CollectionSchema::generate`]. Endless; take as
+/// many as needed
+pub struct DocumentGenerator<'a> {
+    schema: &'a CollectionSchema,
+    /// The paths of the fields directly in each object path, `""` for the top level
+    children: BTreeMap<&'a str, Vec<(&'a str, &'a str)>>,
+    rng: StdRng,
+    /// How many documents have been generated, for `_id`s that count up
+    generated: i64,
+}
+
+impl CollectionSchema {
+    /// Generate documents with the schema's fields, each present and of each type about as
+    /// often as in the sample. Strings and numbers follow the field's statistics, dates fall
+    /// between its earliest and latest, and enum candidates take one of their values. Fields
+    /// with no types, as in a JSON Schema that doesn't restrict them, hold strings. The same
+    /// `seed` generates the same documents
+    pub fn generate(&self, seed: Option<u64>) -> DocumentGenerator<'_> {
+        let mut children = BTreeMap::<_, Vec<_>>::new();
+        for path in self.fields.keys() {
+            // elements are generated with their array
+            if path.ends_with("[]") {
+                continue;
+            }
+            let (parent, name) = path.rsplit_once('.').unwrap_or(("", path));
+            children
+                .entry(parent)
+                .or_default()
+                .push((name, path.as_str()));
+        }
+        DocumentGenerator {
+            schema: self,
+            children,
+            rng: seed.map_or_else(StdRng::from_entropy, StdRng::seed_from_u64),
+            generated: 0,
+        }
+    }
+}
+
+impl Iterator for DocumentGenerator<'_> {
+    type Item = Document;
+
+    fn next(&mut self) -> Option<Document> {
+        let document = self.object("");
+        self.generated += 1;
+        Some(document)
+    }
+}
+
+impl DocumentGenerator<'_> {
+    fn object(&mut self, path: &str) -> Document {
+        let mut document = Document::new();
+        let children = self.children.get(path).cloned().unwrap_or_default();
+        for (name, path) in children {
+            if let Some(value) = self.field(path) {
+                document.insert(name, value);
+            }
+        }
+        document
+    }
+
+    /// A value for the field at `path`, or `None` to leave it out
+    fn field(&mut self, path: &str) -> Option<Bson> {
+        let field = self.schema.fields.get(path)?;
+        let ty = self.pick_type(field)?;
+        if path == "_id" && matches!(ty, BsonType::Int | BsonType::Long) {
+            let first = field.stats.as_ref().map_or(0.0, |stats| stats.min) as i64;
+            return Some(integer(&ty, first + self.generated));
+        }
+        Some(self.value(path, field, &ty))
+    }
+
+    /// One of the field's types, as often as the sample held it, or `None` for missing
+    fn pick_type(&mut self, field: &FieldSchema) -> Option<BsonType> {
+        if field.types.is_empty() {
+            return Some(BsonType::String);
+        }
+        let total = field.type_counts.values().sum::<u64>();
+        if total == 0 {
+            // declared rather than sampled: every type is as likely
+            let ty = &field.types[self.rng.gen_range(0..field.types.len())];
+            return (*ty != BsonType::Missing).then(|| ty.clone());
+        }
+        let mut pick = self.rng.gen_range(0..total);
+        for (ty, count) in &field.type_counts {
+            if pick < *count {
+                return (*ty != BsonType::Missing).then(|| ty.clone());
+            }
+            pick -= count;
+        }
+        None
+    }
+
+    fn value(&mut self, path: &str, field: &FieldSchema, ty: &BsonType) -> Bson {
+        if let Some(value) = self.enum_value(path, ty) {
+            return value;
+        }
+        match ty {
+            BsonType::Object => Bson::Document(self.object(path)),
+            BsonType::Array => Bson::Array(self.array(path, field)),
+            BsonType::Double | BsonType::Number => Bson::Double(self.number(field)),
+            BsonType::Int | BsonType::Long => integer(ty, self.number(field).round() as i64),
+            BsonType::Decimal => {
+                let value = self.number(field);
+                Bson::Decimal128(bson::Decimal128::from_bytes(decimal_bytes(value)))
+            }
+            BsonType::String => Bson::String(self.string(field)),
+            BsonType::Bool => Bson::Boolean(self.rng.gen()),
+            BsonType::Date => Bson::DateTime(self.date(field)),
+            BsonType::ObjectId => Bson::ObjectId(ObjectId::from_bytes(self.rng.gen())),
+            BsonType::BinData => Bson::Binary(self.binary(field)),
+            BsonType::Timestamp => Bson::Timestamp(Timestamp {
+                time: (self.date(field).timestamp_millis() / 1000) as u32,
+                increment: 1,
+            }),
+            BsonType::Regex => Bson::RegularExpression(Regex {
+                pattern: self.string(field),
+                options: String::new(),
+            }),
+            BsonType::Javascript => Bson::JavaScriptCode(self.string(field)),
+            BsonType::JavascriptWithScope => {
+                Bson::JavaScriptCodeWithScope(JavaScriptCodeWithScope {
+                    code: self.string(field),
+                    scope: Document::new(),
+                })
+            }
+            BsonType::Symbol => Bson::Symbol(self.string(field)),
+            BsonType::Undefined => Bson::Undefined,
+            BsonType::MinKey => Bson::MinKey,
+            BsonType::MaxKey => Bson::MaxKey,
+            // DBPointers can't be built, and unknown types have nothing to go on
+            BsonType::Null | BsonType::DbPointer | BsonType::Other(_) | BsonType::Missing => {
+                Bson::Null
+            }
+        }
+    }
+
+    /// One of the values of an enum candidate, taken for the types it can hold
+    fn enum_value(&mut self, path: &str, ty: &BsonType) -> Option<Bson> {
+        let values = self
+            .schema
+            .enum_values(path)?
+            .iter()
+            .filter(|value| match value {
+                ScalarValue::Int(_) => matches!(ty, BsonType::Int | BsonType::Long),
+                ScalarValue::String(_) => *ty == BsonType::String,
+            })
+            .collect::<Vec<_>>();
+        if values.is_empty() {
+            return None;
+        }
+        Some(match values[self.rng.gen_range(0..values.len())] {
+            ScalarValue::Int(n) => integer(ty, *n),
+            ScalarValue::String(s) => Bson::String(s.clone()),
+        })
+    }
+
+    /// Elements of the types the array's `[]` path held, or none as often as its arrays were
+    /// empty
+    fn array(&mut self, path: &str, field: &FieldSchema) -> Vec<Bson> {
+        let elements = format!("{}[]", path);
+        let Some(element) = self.schema.fields.get(&elements) else {
+            return Vec::new();
+        };
+        let arrays = field
+            .type_counts
+            .get(&BsonType::Array)
+            .copied()
+            .unwrap_or(0);
+        // the element path is also missing wherever the field isn't an array
+        let elsewhere = field.type_counts.values().sum::<u64>() - arrays;
+        let empty = element.missing().saturating_sub(elsewhere);
+        if arrays > 0 && self.rng.gen_range(0..arrays) < empty {
+            return Vec::new();
+        }
+        let mut present = element.clone();
+        present.type_counts.remove(&BsonType::Missing);
+        present.types.retain(|ty| *ty != BsonType::Missing);
+        let length = self.rng.gen_range(1..=MAX_ARRAY_LENGTH);
+        (0..length)
+            .filter_map(|_| {
+                let ty = self.pick_type(&present)?;
+                Some(self.value(&elements, &present, &ty))
+            })
+            .collect()
+    }
+
+    /// A number distributed like the field's, interpolating between its percentiles, or
+    /// between 0 and 1000 without statistics
+    fn number(&mut self, field: &FieldSchema) -> f64 {
+        let u = self.rng.gen::<f64>();
+        match &field.stats {
+            Some(stats) => interpolate(
+                u,
+                &[
+                    (0.0, stats.min),
+                    (0.25, stats.p25),
+                    (0.5, stats.p50),
+                    (0.75, stats.p75),
+                    (0.95, stats.p95),
+                    (0.99, stats.p99),
+                    (1.0, stats.max),
+                ],
+            ),
+            None => u * 1000.0,
+        }
+    }
+
+    /// Letters and digits, as long as the field's strings were
+    fn string(&mut self, field: &FieldSchema) -> String {
+        let u = self.rng.gen::<f64>();
+        let length = match &field.length_stats {
+            Some(lengths) => interpolate(
+                u,
+                &[
+                    (0.0, lengths.min as f64),
+                    (0.5, lengths.p50 as f64),
+                    (0.95, lengths.p95 as f64),
+                    (0.99, lengths.p99 as f64),
+                    (1.0, lengths.max as f64),
+                ],
+            ),
+            None => 5.0 + u * 15.0,
+        };
+        (&mut self.rng)
+            .sample_iter(Alphanumeric)
+            .take(length.round() as usize)
+            .map(char::from)
+            .collect()
+    }
+
+    /// A date between the field's earliest and latest, or in the past year
+    fn date(&mut self, field: &FieldSchema) -> DateTime {
+        let range = field.date_stats.as_ref().and_then(|dates| {
+            let earliest = DateTime::parse_rfc3339_str(&dates.earliest).ok()?;
+            let latest = DateTime::parse_rfc3339_str(&dates.latest).ok()?;
+            Some((earliest.timestamp_millis(), latest.timestamp_millis()))
+        });
+        let (earliest, latest) = range.unwrap_or_else(|| {
+            let now = DateTime::now().timestamp_millis();
+            (now - DEFAULT_DATE_SPAN_MS, now)
+        });
+        DateTime::from_millis(self.rng.gen_range(earliest..=latest.max(earliest)))
+    }
+
+    /// Random bytes of the field's most common subtype, UUID-sized
+    fn binary(&mut self, field: &FieldSchema) -> Binary {
+        let kind = field
+            .binary_subtypes
+            .iter()
+            .max_by_key(|(_, count)| **count)
+            .map(|(kind, _)| kind);
+        let subtype = match kind {
+            Some(BinaryKind::Uuid) => BinarySubtype::Uuid,
+            Some(BinaryKind::UuidLegacy) => BinarySubtype::UuidOld,
+            Some(BinaryKind::Md5) => BinarySubtype::Md5,
+            _ => BinarySubtype::Generic,
+        };
+        Binary {
+            subtype,
+            bytes: self.rng.gen::<[u8; 16]>().to_vec(),
+        }
+    }
+}
+
+/// `n` as the integer type `ty`, clamped to 32 bits for ints
+fn integer(ty: &BsonType, n: i64) -> Bson {
+    match ty {
+        BsonType::Int => Bson::Int32(n.clamp(i32::MIN.into(), i32::MAX.into()) as i32),
+        _ => Bson::Int64(n),
+    }
+}
+
+/// The value at quantile `u` of the distribution through `points`, (quantile, value) pairs in
+/// order
+fn interpolate(u: f64, points: &[(f64, f64)]) -> f64 {
+    for pair in points.windows(2) {
+        let ((q0, v0), (q1, v1)) = (pair[0], pair[1]);
+        if u <= q1 {
+            return if q1 > q0 {
+                v0 + (u - q0) / (q1 - q0) * (v1 - v0)
+            } else {
+                v1
+            };
+        }
+    }
+    points.last().map_or(0.0, |(_, v)| *v)
+}
+
+/// The IEEE 754-2008 decimal128 encoding of `value` rounded to two decimal places
+fn decimal_bytes(value: f64) -> [u8; 16] {
+    let coefficient = (value.abs() * 100.0).round() as u128;
+    // exponent -2, biased by 6176, in the 14 bits after the sign
+    let exponent = (6176u128 - 2) << 113;
+    let sign = if value < 0.0 { 1u128 << 127 } else { 0 };
+    (sign | exponent | coefficient).to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{codegen::FIXTURE, AnalysisOptions, SchemaBuilder};
+
+    /// The codegen fixture with statistics, so that dates fall between the sample's rather
+    /// than in the year up to now
+    fn schema() -> CollectionSchema {
+        let options = AnalysisOptions {
+            profile_values: true,
+            stats: true,
+            ..AnalysisOptions::default()
+        };
+        CollectionSchema::from_ndjson(FIXTURE.as_bytes(), "app.users", &options).unwrap()
+    }
+
+    #[test]
+    fn generates_the_same_documents_from_the_same_seed() {
+        let schema = schema();
+        let first = schema.generate(Some(7)).take(20).collect::<Vec<_>>();
+        let again = schema.generate(Some(7)).take(20).collect::<Vec<_>>();
+        assert_eq!(first, again);
+        let other = schema.generate(Some(8)).take(20).collect::<Vec<_>>();
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn generates_documents_conforming_to_the_schema() {
+        let schema = schema();
+        let mut builder = SchemaBuilder::default().with_stats();
+        for document in schema.generate(Some(1)).take(200) {
+            builder.add(&document);
+        }
+        let generated = builder.build("app.users");
+        for (path, field) in &generated.fields {
+            let original = &schema.fields[path];
+            for ty in &field.types {
+                assert!(original.types.contains(ty), "{}: {}", path, ty);
+            }
+            if original.count == schema.sample_size {
+                assert_eq!(field.count, 200, "{}", path);
+            }
+        }
+        let dates = generated.fields["createdAt"].date_stats.as_ref().unwrap();
+        assert!(
+            dates.earliest.as_str() >= "2024-01-01",
+            "{}",
+            dates.earliest
+        );
+        assert!(dates.latest.as_str() <= "2024-06-02", "{}", dates.latest);
+        for required in [
+            "_id",
+            "name",
+            "age",
+            "status",
+            "address.city",
+            "orders[].sku",
+        ] {
+            assert!(generated.fields.contains_key(required), "{}", required);
+        }
+        let statuses = schema
+            .generate(Some(1))
+            .take(200)
+            .map(|document| document.get_str("status").unwrap().to_string())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(
+            statuses.into_iter().collect::<Vec<_>>(),
+            ["active", "banned"]
+        );
+    }
+}
@@ -0,0 +1,115 @@
+//! GeoJSON geometries stored as subdocuments, `{type: "Point", coordinates: [lng, lat]}`,
+//! told apart from other objects so they can be reported and indexed as locations
+
+use std::fmt;
+
+use bson::{Bson, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::FieldSchema;
+
+/// A GeoJSON geometry type, as in the `type` field
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GeoJsonType {
+    Point,
+    MultiPoint,
+    LineString,
+    MultiLineString,
+    Polygon,
+    MultiPolygon,
+    GeometryCollection,
+}
+
+impl GeoJsonType {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "Point" => GeoJsonType::Point,
+            "MultiPoint" => GeoJsonType::MultiPoint,
+            "LineString" => GeoJsonType::LineString,
+            "MultiLineString" => GeoJsonType::MultiLineString,
+            "Polygon" => GeoJsonType::Polygon,
+            "MultiPolygon" => GeoJsonType::MultiPolygon,
+            "GeometryCollection" => GeoJsonType::GeometryCollection,
+            _ => return None,
+        })
+    }
+
+    /// The geometry type of `doc`, if it's a GeoJSON geometry: a known `type` with an array of
+    /// `coordinates`, or of `geometries` for a collection
+    pub(crate) fn of(doc: &Document) -> Option<Self> {
+        let ty = Self::parse(doc.get_str("type").ok()?)?;
+        let contents = match ty {
+            GeoJsonType::GeometryCollection => "geometries",
+            _ => "coordinates",
+        };
+        matches!(doc.get(contents), Some(Bson::Array(_))).then_some(ty)
+    }
+}
+
+impl fmt::Display for GeoJsonType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl FieldSchema {
+    /// The geometry types of the field's subdocuments, if its values were profiled and every
+    /// subdocument was a GeoJSON geometry
+    pub fn geojson_types(&self) -> Option<Vec<GeoJsonType>> {
+        let values = self.values.as_ref()?;
+        let geometries = values.geojson.values().sum::<u64>();
+        (values.objects > 0 && geometries == values.objects)
+            .then(|| values.geojson.keys().copied().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::*;
+    use crate::{AnalysisOptions, CollectionSchema};
+
+    #[test]
+    fn tells_geometries_from_other_objects() {
+        let point = doc! { "type": "Point", "coordinates": [-0.12, 51.5] };
+        assert_eq!(GeoJsonType::of(&point), Some(GeoJsonType::Point));
+        let collection = doc! { "type": "GeometryCollection", "geometries": [point] };
+        assert_eq!(
+            GeoJsonType::of(&collection),
+            Some(GeoJsonType::GeometryCollection)
+        );
+        assert_eq!(GeoJsonType::of(&doc! { "type": "Point" }), None);
+        assert_eq!(
+            GeoJsonType::of(&doc! { "type": "Circle", "coordinates": [] }),
+            None
+        );
+        assert_eq!(
+            GeoJsonType::of(&doc! { "type": "Point", "coordinates": "0,0" }),
+            None
+        );
+    }
+
+    #[test]
+    fn reports_fields_whose_every_object_is_a_geometry() {
+        let options = AnalysisOptions {
+            profile_values: true,
+            ..AnalysisOptions::default()
+        };
+        let ndjson = "{\"at\": {\"type\": \"Point\", \"coordinates\": [0, 0]}, \
+                      \"area\": {\"type\": \"Polygon\", \"coordinates\": [[[0, 0], [1, 1], [0, 0]]]}}\n\
+                      {\"at\": {\"type\": \"Point\", \"coordinates\": [1.5, 2]}, \
+                      \"area\": {\"name\": \"somewhere\"}}\n";
+        let schema = CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options).unwrap();
+        assert_eq!(
+            schema.fields["at"].geojson_types(),
+            Some(vec![GeoJsonType::Point])
+        );
+        assert_eq!(schema.fields["area"].geojson_types(), None);
+        // ints and doubles in coordinates are what GeoJSON holds, not an anomaly
+        assert!(!schema
+            .anomalies
+            .iter()
+            .any(|anomaly| anomaly.path.starts_with("at.")));
+    }
+}
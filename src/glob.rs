@@ -0,0 +1,113 @@
+/// A regular expression matching what [`glob_match`] does, for the server to match with.
+/// `.` has to match line breaks too, with the `s` option
+pub(crate) fn glob_regex(pattern: &str) -> String {
+    let mut regex = String::from("^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            c if "\\^$.|+()[]{}".contains(c) => {
+                regex.push('\\');
+                regex.push(c);
+            }
+            c => regex.push(c),
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// The text every match of `pattern` starts with: what comes before its first wildcard
+pub(crate) fn glob_prefix(pattern: &str) -> &str {
+    pattern.split(['*', '?']).next().unwrap_or_default()
+}
+
+/// Match `text` against a shell-style pattern where `*` matches any run of characters
+/// (including none) and `?` matches exactly one
+pub(crate) fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // where the last `*` was seen, and how much of the text it had consumed
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                // let the last `*` swallow one more character and retry
+                Some((star, consumed)) => {
+                    p = star + 1;
+                    t = consumed + 1;
+                    backtrack = Some((star, consumed + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Match a field's `path` as [`glob_match`] does, both as written and with its `[]` segments
+/// dropped, so `items.*` matches `items[].sku` as `items[].*` does
+pub(crate) fn path_match(pattern: &str, path: &str) -> bool {
+    glob_match(pattern, path)
+        || (path.contains("[]") && glob_match(pattern, &path.replace("[]", "")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_literals_and_wildcards() {
+        assert!(glob_match("password", "password"));
+        assert!(!glob_match("password", "passwords"));
+        assert!(glob_match("payment.*", "payment.card.number"));
+        assert!(!glob_match("payment.*", "payment"));
+        assert!(glob_match("*Id", "userId"));
+        assert!(glob_match("*Id", "Id"));
+        assert!(glob_match("user?", "users"));
+        assert!(!glob_match("user?", "user"));
+        assert!(glob_match("a*b*c", "a-b-b-c"));
+        assert!(!glob_match("a*b*c", "a-c-b"));
+        assert!(glob_match("**", ""));
+        assert!(glob_match("tags[]", "tags[]"));
+        assert!(glob_match("caf?", "café"));
+    }
+
+    #[test]
+    fn matches_element_paths_with_or_without_their_brackets() {
+        assert!(path_match("items.*", "items[].sku"));
+        assert!(path_match("items[].*", "items[].sku"));
+        assert!(path_match("*.internal_*", "events[].internal_id"));
+        assert!(path_match("tags", "tags[]"));
+        assert!(!path_match("items.*", "items[]"));
+        assert!(!path_match("items.*", "items"));
+    }
+
+    #[test]
+    fn translates_patterns_to_regexes() {
+        assert_eq!(glob_regex("user.*Id"), r"^user\..*Id$");
+        assert_eq!(glob_regex("tags[]?"), r"^tags\[\].$");
+        assert_eq!(glob_regex("a+(b)|$c"), r"^a\+\(b\)\|\$c$");
+    }
+
+    #[test]
+    fn takes_the_text_before_the_first_wildcard() {
+        assert_eq!(glob_prefix("user.*Id"), "user.");
+        assert_eq!(glob_prefix("a?b*"), "a");
+        assert_eq!(glob_prefix("*Id"), "");
+        assert_eq!(glob_prefix("name"), "name");
+    }
+}
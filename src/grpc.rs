@@ -0,0 +1,253 @@
+//! A gRPC service running analyses on demand, defined in `proto/schema_analyzer.proto`, for
+//! tooling that would rather stream a long analysis' progress than poll for its report.
+//! Build with `--features grpc`
+
+// tonic's Status is large, but it's what every call returns
+#![allow(clippy::result_large_err)]
+
+use std::{pin::Pin, sync::Arc};
+
+use futures::{stream, Stream};
+use mongodb::Namespace;
+use tokio::{
+    net::TcpListener,
+    sync::{mpsc, Semaphore},
+};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{
+    server::Clients, AnalyzerError, CollectionSchema, Discovery, Progress, ProgressFn,
+    SchemaAnalyzer, ServerOptions,
+};
+
+mod proto {
+    tonic::include_proto!("schema_analyzer.v1");
+}
+
+use proto::{
+    analyze_update, discovery, progress,
+    schema_analyzer_server::{self, SchemaAnalyzerServer},
+    watch_update, AnalyzeRequest, AnalyzeUpdate, DiffRequest, DiffResponse, WatchUpdate,
+};
+
+/// A stream of updates sent to the caller as they're made
+type Updates<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+struct Service {
+    options: ServerOptions,
+    /// Shared with the analyses running after their call returned its stream
+    permits: Arc<Semaphore>,
+    clients: Clients,
+}
+
+/// Answer calls on `listener` until it fails. Analyses aren't kept as reports like the REST
+/// service's, and run at most [`ServerOptions::max_concurrent`] at once of their own
+pub async fn serve_grpc(listener: TcpListener, options: ServerOptions) -> std::io::Result<()> {
+    let service = Service {
+        permits: Arc::new(Semaphore::new(options.max_concurrent.max(1))),
+        clients: Clients::new(options.max_clients),
+        options,
+    };
+    let incoming = stream::unfold(listener, |listener| async move {
+        let connection = listener.accept().await.map(|(stream, _)| stream);
+        Some((connection, listener))
+    });
+    Server::builder()
+        .add_service(SchemaAnalyzerServer::new(service))
+        .serve_with_incoming(incoming)
+        .await
+        .map_err(std::io::Error::other)
+}
+
+#[tonic::async_trait]
+impl schema_analyzer_server::SchemaAnalyzer for Service {
+    type AnalyzeStream = Updates<AnalyzeUpdate>;
+    type WatchStream = Updates<WatchUpdate>;
+
+    async fn analyze(
+        &self,
+        request: Request<AnalyzeRequest>,
+    ) -> Result<Response<Self::AnalyzeStream>, Status> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let reporter = sender.clone();
+        let analyzer = self
+            .analyzer(request.into_inner(), move |progress| {
+                let update = analyze_update::Update::Progress(progress);
+                let _ = reporter.send(Ok(AnalyzeUpdate {
+                    update: Some(update),
+                }));
+            })
+            .await?;
+        let permits = self.permits.clone();
+        tokio::spawn(async move {
+            let analysis = async {
+                // infallible: the semaphore is never closed
+                let _permit = permits.acquire().await.unwrap();
+                let schema = analyzer.analyze().await.map_err(status)?;
+                let update = analyze_update::Update::SchemaJson(schema_json(&schema)?);
+                Ok(AnalyzeUpdate {
+                    update: Some(update),
+                })
+            };
+            // stop analyzing once the caller has gone
+            tokio::select! {
+                update = analysis => {
+                    let _ = sender.send(update);
+                }
+                _ = sender.closed() => {}
+            }
+        });
+        Ok(Response::new(updates(receiver)))
+    }
+
+    async fn diff(&self, request: Request<DiffRequest>) -> Result<Response<DiffResponse>, Status> {
+        let request = request.into_inner();
+        let old = parse_schema(&request.old_schema_json, "old_schema_json")?;
+        let new = parse_schema(&request.new_schema_json, "new_schema_json")?;
+        let diff = old.diff(&new);
+        Ok(Response::new(DiffResponse {
+            diff_json: serde_json::to_string(&diff).map_err(|e| Status::internal(e.to_string()))?,
+            summary: diff.to_string(),
+            empty: diff.is_empty(),
+        }))
+    }
+
+    async fn watch(
+        &self,
+        request: Request<AnalyzeRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let reporter = sender.clone();
+        let analyzer = self
+            .analyzer(request.into_inner(), move |progress| {
+                let update = watch_update::Update::Progress(progress);
+                let _ = reporter.send(Ok(WatchUpdate {
+                    update: Some(update),
+                }));
+            })
+            .await?;
+        let permits = self.permits.clone();
+        tokio::spawn(async move {
+            let watch = async {
+                let initial = {
+                    // only the initial analysis counts against the limit; watching can go on
+                    // indefinitely
+                    let _permit = permits.acquire().await.unwrap();
+                    analyzer.analyze().await.map_err(status)?
+                };
+                let update = watch_update::Update::SchemaJson(schema_json(&initial)?);
+                let _ = sender.send(Ok(WatchUpdate {
+                    update: Some(update),
+                }));
+                analyzer
+                    .watch_from(&initial, |found, _| {
+                        let update = watch_update::Update::Discovery(discovery_update(found));
+                        let _ = sender.send(Ok(WatchUpdate {
+                            update: Some(update),
+                        }));
+                    })
+                    .await
+                    .map_err(status)?;
+                Ok(())
+            };
+            tokio::select! {
+                result = watch => {
+                    if let Err(status) = result {
+                        let _ = sender.send(Err(status));
+                    }
+                }
+                _ = sender.closed() => {}
+            }
+        });
+        Ok(Response::new(updates(receiver)))
+    }
+}
+
+impl Service {
+    /// The analyzer for `request`, reporting its progress to `on_progress`
+    async fn analyzer(
+        &self,
+        request: AnalyzeRequest,
+        on_progress: impl Fn(proto::Progress) + Send + Sync + 'static,
+    ) -> Result<SchemaAnalyzer, Status> {
+        let uri = Some(request.uri)
+            .filter(|uri| !uri.is_empty())
+            .or_else(|| self.options.uri.clone())
+            .or_else(|| self.options.config.uri.clone())
+            .ok_or_else(|| Status::invalid_argument("no connection string: pass uri"))?;
+        let ns = Namespace::new(request.db, request.collection);
+        let mut options = match request.options_json.trim() {
+            "" => self.options.config.options_for(&ns),
+            options => serde_json::from_str(options)
+                .map_err(|e| Status::invalid_argument(format!("options_json: {}", e)))?,
+        };
+        options.progress = Some(ProgressFn::new(move |progress| {
+            if let Some(progress) = progress_update(progress) {
+                on_progress(progress);
+            }
+        }));
+        let client = self.clients.get(&uri).await.map_err(status)?;
+        Ok(SchemaAnalyzer::with_options(client, ns, options))
+    }
+}
+
+fn updates<T: Send + 'static>(receiver: mpsc::UnboundedReceiver<Result<T, Status>>) -> Updates<T> {
+    Box::pin(stream::unfold(receiver, |mut receiver| async move {
+        let update = receiver.recv().await?;
+        Some((update, receiver))
+    }))
+}
+
+/// `None` for the start of a collection, as a call only analyzes one
+fn progress_update(progress: Progress<'_>) -> Option<proto::Progress> {
+    let step = match progress {
+        Progress::Collection { .. } => return None,
+        Progress::Aggregating => progress::Step::Aggregating(proto::Aggregating {}),
+        Progress::Documents { read, expected } => {
+            progress::Step::Documents(proto::Documents { read, expected })
+        }
+        Progress::Retrying {
+            attempt,
+            delay,
+            error,
+        } => progress::Step::Retrying(proto::Retrying {
+            attempt,
+            delay_seconds: delay.as_secs_f64(),
+            error: error.to_string(),
+        }),
+    };
+    Some(proto::Progress { step: Some(step) })
+}
+
+fn discovery_update(found: &Discovery) -> proto::Discovery {
+    let (kind, path, ty) = match found {
+        Discovery::NewField { path, ty } => (discovery::Kind::NewField, path, ty),
+        Discovery::NewType { path, ty } => (discovery::Kind::NewType, path, ty),
+    };
+    proto::Discovery {
+        kind: kind.into(),
+        path: path.clone(),
+        r#type: ty.clone(),
+    }
+}
+
+fn schema_json(schema: &CollectionSchema) -> Result<String, Status> {
+    serde_json::to_string(schema).map_err(|e| Status::internal(e.to_string()))
+}
+
+fn parse_schema(json: &str, name: &str) -> Result<CollectionSchema, Status> {
+    serde_json::from_str(json).map_err(|e| Status::invalid_argument(format!("{}: {}", name, e)))
+}
+
+/// The status of a failed analysis, sorted like the REST service's responses
+fn status(error: AnalyzerError) -> Status {
+    let message = error.to_string();
+    match error {
+        AnalyzerError::InvalidUri(_) | AnalyzerError::InvalidOptions(_) => {
+            Status::invalid_argument(message)
+        }
+        AnalyzerError::Authentication(_) => Status::unauthenticated(message),
+        AnalyzerError::Connection(_) | AnalyzerError::Server(_) => Status::unavailable(message),
+        _ => Status::internal(message),
+    }
+}
@@ -0,0 +1,784 @@
+use std::fmt::Write;
+
+use crate::{
+    schema::KNOWN_TYPES,
+    sizes::format_bytes,
+    stats::{format_number, rfc3339, LENGTH_HEADER, STATS_HEADER},
+    Benchmark, BsonType, ClusterComparison, CollectionSchema, Histogram, SchemaDiff, SchemaReport,
+    ValidationReport, Variants,
+};
+
+const STYLE: &str = "\
+body { font-family: system-ui, sans-serif; margin: 2em; color: #222; }
+h2 { font-family: ui-monospace, monospace; }
+.legend span, .row.mixed > code, .row.optional > code { padding: 0 .3em; border-radius: 3px; }
+.mixed { background: #fff3bf; }
+.optional { background: #d0ebff; }
+details > summary { cursor: pointer; }
+.children { margin-left: 1.5em; border-left: 1px solid #ddd; padding-left: .5em; }
+.row { display: inline-grid; grid-template-columns: 16em 5em 12em auto; gap: 1em; align-items: center; padding: .1em 0; }
+/* line leaves up with the rows after a disclosure marker */
+.leaf { margin-left: 1.1em; }
+.presence { text-align: right; }
+.interval { display: block; color: #555; font-size: .75em; }
+.bar { display: flex; height: .8em; background: #f1f3f5; border-radius: 3px; overflow: hidden; }
+.types { color: #555; font-size: .9em; }
+li.error strong { color: #c92a2a; }
+li.warn strong { color: #e67700; }
+p.warn { color: #e67700; }
+table.stats { border-collapse: collapse; font-size: .9em; }
+table.stats th, table.stats td { padding: .1em .6em; text-align: right; }
+table.stats th:first-child, table.stats td:first-child { text-align: left; }
+.histogram { display: inline-flex; align-items: flex-end; gap: 1px; height: 1.6em; }
+.histogram span { width: .4em; background: #74c0fc; }
+";
+
+impl CollectionSchema {
+    /// Render the schema as a standalone HTML page, with nested fields collapsible under their
+    /// parents, a bar showing each field's type distribution, and fields of mixed type or
+    /// missing from some documents highlighted
+    pub fn to_html(&self) -> String {
+        page(&self.namespace, &self.html_section())
+    }
+
+    fn html_section(&self) -> String {
+        if let Some(sections) = self.variant_sections() {
+            let mut out = sections
+                .iter()
+                .map(CollectionSchema::html_section)
+                .collect::<String>();
+            if let Some(note) = self.variants.as_ref().and_then(Variants::note) {
+                writeln!(out, "<p>{}</p>", escape(&note)).unwrap();
+            }
+            return out;
+        }
+        let mut out = String::new();
+        writeln!(out, "<section>").unwrap();
+        writeln!(out, "<h2>{}</h2>", escape(&self.namespace)).unwrap();
+        writeln!(
+            out,
+            "<p>{} of {} documents sampled ({}).</p>",
+            self.sample_size, self.document_count, self.sample_method
+        )
+        .unwrap();
+        if let Some(confidence) = &self.confidence {
+            writeln!(
+                out,
+                "<p>{}</p>",
+                escape(&confidence.note(self.document_count))
+            )
+            .unwrap();
+        }
+        for (name, path) in self.children("") {
+            self.write_html_field(&mut out, name, path);
+        }
+        let stats = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.stats.as_ref()?)))
+            .collect::<Vec<_>>();
+        if !stats.is_empty() {
+            let histograms = stats.iter().any(|(_, stats)| stats.histogram.is_some());
+            writeln!(out, "<h3>Numeric fields</h3>").unwrap();
+            writeln!(out, r#"<table class="stats">"#).unwrap();
+            write!(out, "<tr><th>Field</th>").unwrap();
+            for heading in STATS_HEADER {
+                write!(out, "<th>{}</th>", heading.to_lowercase()).unwrap();
+            }
+            if histograms {
+                write!(out, "<th>histogram</th>").unwrap();
+            }
+            writeln!(out, "</tr>").unwrap();
+            for (path, stats) in stats {
+                write!(out, "<tr><td><code>{}</code></td>", escape(path)).unwrap();
+                for column in stats.columns() {
+                    write!(out, "<td>{}</td>", column).unwrap();
+                }
+                if histograms {
+                    let chart = stats
+                        .histogram
+                        .as_ref()
+                        .map(|histogram| histogram_html(histogram, format_number));
+                    write!(out, "<td>{}</td>", chart.unwrap_or_default()).unwrap();
+                }
+                writeln!(out, "</tr>").unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        }
+        let lengths = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.length_stats.as_ref()?)))
+            .collect::<Vec<_>>();
+        if !lengths.is_empty() {
+            writeln!(out, "<h3>String lengths</h3>").unwrap();
+            writeln!(out, r#"<table class="stats">"#).unwrap();
+            write!(out, "<tr><th>Field</th>").unwrap();
+            for heading in LENGTH_HEADER {
+                write!(out, "<th>{}</th>", heading.to_lowercase()).unwrap();
+            }
+            writeln!(out, "<th>long outliers</th></tr>").unwrap();
+            for (path, stats) in lengths {
+                write!(out, "<tr><td><code>{}</code></td>", escape(path)).unwrap();
+                for column in stats.columns() {
+                    write!(out, "<td>{}</td>", column).unwrap();
+                }
+                writeln!(
+                    out,
+                    "<td>{}</td></tr>",
+                    if stats.long_outliers { "yes" } else { "" }
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        }
+        let dates = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.date_stats.as_ref()?)))
+            .collect::<Vec<_>>();
+        if !dates.is_empty() {
+            let histograms = dates.iter().any(|(_, stats)| stats.histogram.is_some());
+            writeln!(out, "<h3>Date fields</h3>").unwrap();
+            writeln!(out, r#"<table class="stats">"#).unwrap();
+            write!(out, "<tr><th>Field</th><th>earliest</th><th>latest</th>").unwrap();
+            if histograms {
+                write!(out, "<th>histogram</th>").unwrap();
+            }
+            writeln!(out, "<th>distribution</th></tr>").unwrap();
+            for (path, stats) in dates {
+                write!(
+                    out,
+                    "<tr><td><code>{}</code></td><td>{}</td><td>{}</td>",
+                    escape(path),
+                    stats.earliest,
+                    stats.latest,
+                )
+                .unwrap();
+                if histograms {
+                    let chart = stats.histogram.as_ref().map(|histogram| {
+                        histogram_html(histogram, |millis| {
+                            rfc3339(bson::DateTime::from_millis(millis as i64))
+                        })
+                    });
+                    write!(out, "<td>{}</td>", chart.unwrap_or_default()).unwrap();
+                }
+                writeln!(out, "<td>{}</td></tr>", stats.describe_distribution()).unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        }
+        if let Some(sizes) = &self.sizes {
+            writeln!(
+                out,
+                "<h3>Storage by field, average document {}</h3>",
+                format_bytes(sizes.average_document)
+            )
+            .unwrap();
+            writeln!(out, r#"<table class="stats">"#).unwrap();
+            writeln!(
+                out,
+                "<tr><th>Field</th><th>average</th><th>share</th><th>total</th></tr>"
+            )
+            .unwrap();
+            for field in &sizes.fields {
+                writeln!(
+                    out,
+                    "<tr><td><code>{}</code></td><td>{}</td><td>{:.1}%</td><td>{}</td></tr>",
+                    escape(&field.path),
+                    format_bytes(field.average),
+                    sizes.share(field) * 100.0,
+                    format_bytes(field.total as f64)
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+        }
+        if let Some(sizes) = &self.document_sizes {
+            writeln!(out, "<h3>Document sizes</h3>").unwrap();
+            writeln!(out, "<p>{}</p>", sizes.describe()).unwrap();
+            writeln!(out, r#"<table class="stats">"#).unwrap();
+            writeln!(
+                out,
+                "<tr><th>Size</th><th>documents</th><th>share</th></tr>"
+            )
+            .unwrap();
+            for bucket in &sizes.histogram {
+                writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{:.1}%</td></tr>",
+                    bucket,
+                    bucket.count,
+                    sizes.share(bucket) * 100.0
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+            if let Some(warning) = sizes.warning() {
+                writeln!(out, r#"<p class="warn">{}</p>"#, warning).unwrap();
+            }
+        }
+        if let Some(widths) = &self.document_widths {
+            writeln!(out, "<h3>Document widths</h3>").unwrap();
+            writeln!(out, "<p>{}</p>", widths.describe()).unwrap();
+            if let Some(warning) = widths.warning() {
+                writeln!(out, r#"<p class="warn">{}</p>"#, escape(&warning)).unwrap();
+            }
+        }
+        if let Some(warning) = self.truncation_warning() {
+            writeln!(out, r#"<p class="warn">{}</p>"#, warning).unwrap();
+        }
+        if let Some(versions) = &self.versions {
+            writeln!(
+                out,
+                "<h3>Versions of <code>{}</code></h3>",
+                escape(&versions.field)
+            )
+            .unwrap();
+            writeln!(out, "<ul>").unwrap();
+            for version in &versions.versions {
+                writeln!(
+                    out,
+                    "<li><code>{}</code>: {} documents</li>",
+                    escape(&version.value.to_string()),
+                    version.documents
+                )
+                .unwrap();
+            }
+            writeln!(out, "</ul>").unwrap();
+            for change in &versions.changes {
+                writeln!(
+                    out,
+                    "<h4><code>{}</code> to <code>{}</code></h4>",
+                    escape(&change.from.to_string()),
+                    escape(&change.to.to_string())
+                )
+                .unwrap();
+                writeln!(out, "<pre>{}</pre>", escape(&change.diff.to_string())).unwrap();
+            }
+            if let Some(note) = versions.note() {
+                writeln!(out, "<p>{}</p>", escape(&note)).unwrap();
+            }
+        }
+        if let Some(evolution) = &self.evolution {
+            writeln!(out, "<h3>Evolution</h3>").unwrap();
+            writeln!(out, "<p>{}</p>", escape(&evolution.describe())).unwrap();
+            let rows = evolution.notable().collect::<Vec<_>>();
+            if rows.is_empty() {
+                writeln!(out, "<p>Every field held the same types throughout.</p>").unwrap();
+            } else {
+                writeln!(out, r#"<table class="stats">"#).unwrap();
+                writeln!(
+                    out,
+                    "<tr><th>Field</th><th>first seen</th><th>last seen</th><th>types</th></tr>"
+                )
+                .unwrap();
+                for (path, history) in rows {
+                    writeln!(
+                        out,
+                        "<tr><td><code>{}</code></td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                        escape(path),
+                        history.first_seen,
+                        history.last_seen,
+                        escape(&history.describe_types())
+                    )
+                    .unwrap();
+                }
+                writeln!(out, "</table>").unwrap();
+            }
+        }
+        if let Some(view) = &self.view {
+            writeln!(out, "<h3>View</h3>").unwrap();
+            writeln!(out, "<p>{}</p>", escape(&view.describe())).unwrap();
+        }
+        if let Some(time_series) = &self.time_series {
+            writeln!(out, "<h3>Time series</h3>").unwrap();
+            writeln!(out, "<p>{}</p>", escape(&time_series.describe())).unwrap();
+            if !time_series.meta_fields.is_empty() {
+                writeln!(out, r#"<table class="stats">"#).unwrap();
+                writeln!(
+                    out,
+                    "<tr><th>Metadata</th><th>per series</th><th>types</th></tr>"
+                )
+                .unwrap();
+                for (path, field) in &time_series.meta_fields {
+                    writeln!(
+                        out,
+                        "<tr><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                        escape(path),
+                        time_series.presence(field),
+                        escape(&time_series.types(field))
+                    )
+                    .unwrap();
+                }
+                writeln!(out, "</table>").unwrap();
+            }
+        }
+        if let Some(shards) = &self.shards {
+            writeln!(out, "<h3>Shards</h3>").unwrap();
+            writeln!(out, "<p>{}</p>", escape(&shards.describe())).unwrap();
+            writeln!(out, r#"<table class="stats">"#).unwrap();
+            writeln!(
+                out,
+                "<tr><th>Shard</th><th>documents</th><th>sampled</th></tr>"
+            )
+            .unwrap();
+            for shard in &shards.shards {
+                writeln!(
+                    out,
+                    "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    escape(&shard.shard),
+                    shard.document_count,
+                    shard.sample_size
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+            if !shards.differences.is_empty() {
+                writeln!(out, "<ul>").unwrap();
+                for difference in &shards.differences {
+                    writeln!(
+                        out,
+                        r#"<li class="warn"><code>{}</code>: {}</li>"#,
+                        escape(&difference.path),
+                        escape(&difference.describe())
+                    )
+                    .unwrap();
+                }
+                writeln!(out, "</ul>").unwrap();
+            }
+        }
+        if let Some(indexes) = &self.indexes {
+            writeln!(out, "<h3>Indexes</h3>").unwrap();
+            writeln!(out, "<p>{}</p>", escape(&indexes.describe())).unwrap();
+            writeln!(out, r#"<table class="stats">"#).unwrap();
+            writeln!(out, "<tr><th>Index</th><th>keys</th></tr>").unwrap();
+            for index in &indexes.existing {
+                writeln!(
+                    out,
+                    "<tr><td>{}{}</td><td><code>{}</code></td></tr>",
+                    escape(&index.name),
+                    if index.sparse { " (sparse)" } else { "" },
+                    escape(&index.keys.join(", "))
+                )
+                .unwrap();
+            }
+            writeln!(out, "</table>").unwrap();
+            if !indexes.suggestions.is_empty() || !indexes.rare.is_empty() {
+                writeln!(out, "<ul>").unwrap();
+                for suggestion in &indexes.suggestions {
+                    writeln!(out, "<li>suggest {}</li>", escape(&suggestion.to_string())).unwrap();
+                }
+                for rare in &indexes.rare {
+                    writeln!(
+                        out,
+                        r#"<li class="warn">{}</li>"#,
+                        escape(&rare.to_string())
+                    )
+                    .unwrap();
+                }
+                writeln!(out, "</ul>").unwrap();
+            }
+        }
+        if let Some(shapes) = &self.shapes {
+            writeln!(out, "<h3>{}</h3>", shapes.summary()).unwrap();
+            writeln!(out, "<ol>").unwrap();
+            for shape in &shapes.top {
+                writeln!(
+                    out,
+                    "<li>{:.1}% ({} documents): <code>{}</code></li>",
+                    self.share(shape.count) * 100.0,
+                    shape.count,
+                    escape(&shape.describe())
+                )
+                .unwrap();
+            }
+            writeln!(out, "</ol>").unwrap();
+        }
+        if !self.anomalies.is_empty() {
+            writeln!(out, "<h3>Anomalies</h3>").unwrap();
+            writeln!(out, "<ul>").unwrap();
+            for anomaly in &self.anomalies {
+                writeln!(
+                    out,
+                    r#"<li class="{}"><strong>{}</strong> {}</li>"#,
+                    anomaly.severity,
+                    anomaly.severity,
+                    escape(&anomaly.to_string())
+                )
+                .unwrap();
+            }
+            writeln!(out, "</ul>").unwrap();
+        }
+        if !self.rare_fields.is_empty() {
+            writeln!(out, "<h3>Rare fields</h3>").unwrap();
+            writeln!(out, "<ul>").unwrap();
+            for field in &self.rare_fields {
+                writeln!(out, "<li>{}</li>", escape(&field.to_string())).unwrap();
+            }
+            writeln!(out, "</ul>").unwrap();
+        }
+        if !self.findings.is_empty() {
+            writeln!(out, "<h3>Findings</h3>").unwrap();
+            writeln!(out, "<ul>").unwrap();
+            for finding in &self.findings {
+                writeln!(
+                    out,
+                    r#"<li class="{}"><strong>{}</strong> {}</li>"#,
+                    finding.severity,
+                    finding.severity,
+                    escape(&finding.to_string())
+                )
+                .unwrap();
+            }
+            writeln!(out, "</ul>").unwrap();
+        }
+        if let Some(issues) = &self.field_names {
+            writeln!(out, "<h3>Field names</h3>").unwrap();
+            if issues.is_empty() {
+                writeln!(out, "<p>No field names break drivers or updates.</p>").unwrap();
+            } else {
+                writeln!(out, "<ul>").unwrap();
+                for issue in issues {
+                    writeln!(
+                        out,
+                        r#"<li class="warn">{}</li>"#,
+                        escape(&issue.to_string())
+                    )
+                    .unwrap();
+                }
+                writeln!(out, "</ul>").unwrap();
+            }
+        }
+        if let Some(privacy) = &self.privacy {
+            writeln!(out, "<h3>Privacy</h3>").unwrap();
+            if privacy.is_empty() {
+                writeln!(out, "<p>No fields look like personal data.</p>").unwrap();
+            } else {
+                writeln!(out, "<ul>").unwrap();
+                for finding in privacy {
+                    writeln!(out, "<li>{}</li>", escape(&finding.to_string())).unwrap();
+                }
+                writeln!(out, "</ul>").unwrap();
+            }
+        }
+        if let Some(meta) = &self.meta {
+            writeln!(out, "<p>Run: {}.</p>", escape(&meta.describe())).unwrap();
+            if let Some(explain) = &meta.explain {
+                writeln!(out, "<pre>{}</pre>", escape(&explain.to_string())).unwrap();
+            }
+        }
+        writeln!(out, "</section>").unwrap();
+        out
+    }
+
+    /// A row for the field, wrapped with its subfields and array elements in a `<details>`
+    /// when it has any
+    fn write_html_field(&self, out: &mut String, name: &str, path: &str) {
+        let field = &self.fields[path];
+        let elements = format!("{}[]", path);
+        let prefix = format!("{}.", path);
+        let mut nested = self.children(&prefix).collect::<Vec<_>>();
+        if self.fields.contains_key(&elements) {
+            nested.insert(0, ("[]", &elements));
+        }
+
+        let class = if field.is_mixed() {
+            " mixed"
+        } else if field.missing() > 0 {
+            " optional"
+        } else {
+            ""
+        };
+        // array elements can hold several types per document, so size the bar by type count
+        let total = field.type_counts.values().sum::<u64>().max(1) as f64;
+        let mut bar = String::new();
+        for ty in &field.types {
+            let share = field.type_counts[ty] as f64 / total * 100.0;
+            write!(
+                bar,
+                r#"<span style="width: {:.1}%; background: {}" title="{} {:.1}%"></span>"#,
+                share,
+                color(ty),
+                escape(ty.name()),
+                self.share(field.type_counts[ty]) * 100.0
+            )
+            .unwrap();
+        }
+        let mut types = field
+            .types
+            .iter()
+            .map(|t| {
+                format!(
+                    "{} {:.1}%",
+                    self.type_label(path, t),
+                    self.share(field.type_counts[t]) * 100.0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if let Some(presence) = field.element_presence {
+            write!(types, "; {}", presence).unwrap();
+        }
+        if let Some(examples) = field.examples() {
+            write!(types, "; e.g. {}", examples).unwrap();
+        }
+        if let Some(top) = field.top_values() {
+            write!(types, "; top {}", top).unwrap();
+        }
+        if let Some(subtypes) = field.binary_subtypes() {
+            write!(types, "; {}", subtypes).unwrap();
+        }
+        let presence = match field.presence_interval {
+            Some(interval) => format!(
+                r#"{:.1}%<span class="interval">{}</span>"#,
+                self.share(field.count) * 100.0,
+                interval
+            ),
+            None => format!("{:.1}%", self.share(field.count) * 100.0),
+        };
+        let row = format!(
+            r#"<span class="row{}"><code>{}</code><span class="presence">{}</span><span class="bar">{}</span><span class="types">{}</span></span>"#,
+            class,
+            escape(name),
+            presence,
+            bar,
+            escape(&types)
+        );
+
+        if nested.is_empty() {
+            writeln!(out, r#"<div class="leaf">{}</div>"#, row).unwrap();
+            return;
+        }
+        writeln!(out, "<details open><summary>{}</summary>", row).unwrap();
+        writeln!(out, r#"<div class="children">"#).unwrap();
+        for (name, path) in nested {
+            self.write_html_field(out, name, path);
+        }
+        writeln!(out, "</div></details>").unwrap();
+    }
+}
+
+impl SchemaReport {
+    /// Every schema as by [`CollectionSchema::to_html`], as sections of a single page
+    pub fn to_html(&self) -> String {
+        let mut sections = self
+            .namespaces
+            .values()
+            .map(CollectionSchema::html_section)
+            .collect::<String>();
+        if !self.relationships.is_empty() {
+            writeln!(sections, "<section>\n<h2>Relationships</h2>\n<ul>").unwrap();
+            for relationship in &self.relationships {
+                writeln!(sections, "<li>{}</li>", escape(&relationship.to_string())).unwrap();
+            }
+            writeln!(sections, "</ul>\n</section>").unwrap();
+        }
+        if !self.failures.is_empty() {
+            writeln!(sections, "<section>\n<h2>Failed</h2>\n<ul>").unwrap();
+            for (ns, error) in &self.failures {
+                writeln!(
+                    sections,
+                    "<li><code>{}</code>: {}</li>",
+                    escape(ns),
+                    escape(error)
+                )
+                .unwrap();
+            }
+            writeln!(sections, "</ul>\n</section>").unwrap();
+        }
+        page("Schema report", &sections)
+    }
+}
+
+impl SchemaDiff {
+    /// The text rendering as a standalone HTML page
+    pub fn to_html(&self) -> String {
+        page(
+            "Schema differences",
+            &format!("<pre>{}</pre>\n", escape(&self.to_string())),
+        )
+    }
+}
+
+impl ClusterComparison {
+    /// The text rendering as a standalone HTML page
+    pub fn to_html(&self) -> String {
+        page(
+            &format!("{} and {}", self.left, self.right),
+            &format!("<pre>{}</pre>\n", escape(&self.to_string())),
+        )
+    }
+}
+
+impl ValidationReport {
+    /// The text rendering as a standalone HTML page
+    pub fn to_html(&self) -> String {
+        page(
+            "Schema violations",
+            &format!("<pre>{}</pre>\n", escape(&self.to_string())),
+        )
+    }
+}
+
+impl Benchmark {
+    /// The text rendering as a standalone HTML page
+    pub fn to_html(&self) -> String {
+        page(
+            &format!("Benchmark of {}", self.namespace),
+            &format!("<pre>{}</pre>\n", escape(&self.to_string())),
+        )
+    }
+}
+
+fn page(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html lang=\"en\">
+<head>
+<meta charset=\"utf-8\">
+<title>{title}</title>
+<style>
+{STYLE}</style>
+</head>
+<body>
+<p class=\"legend\"><span class=\"mixed\">mixed types</span> <span class=\"optional\">missing from some documents</span></p>
+{body}</body>
+</html>
+",
+        title = escape(title),
+    )
+}
+
+/// A distinct color per known type, and grey for missing and types we don't know
+fn color(ty: &BsonType) -> String {
+    // number stands in for double and the other numeric types
+    let ty = match ty {
+        BsonType::Number => &BsonType::Double,
+        ty => ty,
+    };
+    match KNOWN_TYPES.iter().position(|known| known == ty) {
+        Some(i) if *ty != BsonType::Missing => format!("hsl({}, 65%, 55%)", i * 137 % 360),
+        _ => "#adb5bd".to_string(),
+    }
+}
+
+/// A bar per bucket, titled with its bounds as `label` formats them and its count, and the
+/// buckets as JSON in `data-histogram` for scripts drawing charts of their own
+fn histogram_html(histogram: &Histogram, label: impl Fn(f64) -> String) -> String {
+    let most = histogram
+        .buckets
+        .iter()
+        .map(|bucket| bucket.count)
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    let mut out = format!(
+        r#"<div class="histogram" data-histogram="{}">"#,
+        escape(&serde_json::to_string(histogram).unwrap())
+    );
+    for bucket in &histogram.buckets {
+        // nonempty buckets stay visible however small
+        let height = match bucket.count {
+            0 => 0.0,
+            n => (n as f64 / most as f64 * 100.0).max(4.0),
+        };
+        let title = format!(
+            "{} – {}: {}",
+            label(bucket.lower),
+            label(bucket.upper),
+            bucket.count
+        );
+        write!(
+            out,
+            r#"<span style="height: {:.1}%" title="{}"></span>"#,
+            height,
+            escape(&title)
+        )
+        .unwrap();
+    }
+    out.push_str("</div>");
+    out
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnalysisOptions, HistogramBucket, HistogramScale};
+
+    #[test]
+    fn nests_subdocument_fields_under_their_parent() {
+        let documents = b"{\"p\": {\"q\": 1}, \"<b>\": 1}\n{\"p\": {\"q\": \"x\"}}\n";
+        let html = CollectionSchema::from_ndjson(
+            documents.as_slice(),
+            "db.coll",
+            &AnalysisOptions::default(),
+        )
+        .unwrap()
+        .to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>db.coll</title>"));
+        let parent = html.find("<details open><summary>").unwrap();
+        let child = html
+            .find(r#"<span class="row mixed"><code>q</code>"#)
+            .unwrap();
+        assert!(parent < child, "{}", html);
+        assert!(html[parent..child].contains("<code>p</code>"));
+        // names are escaped, and the optional field highlighted
+        assert!(html.contains(r#"<span class="row optional"><code>&lt;b&gt;</code>"#));
+        assert!(!html.contains("<code><b>"));
+    }
+
+    #[test]
+    fn escapes_text_and_draws_histograms() {
+        assert_eq!(
+            escape(r#"<a href="x">&</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;"
+        );
+        assert_ne!(color(&BsonType::String), color(&BsonType::Int));
+        assert_eq!(color(&BsonType::Number), color(&BsonType::Double));
+        assert_eq!(color(&BsonType::Missing), "#adb5bd");
+
+        let histogram = Histogram {
+            scale: HistogramScale::EqualWidth,
+            buckets: [(0.0, 1.0, 100), (1.0, 2.0, 0), (2.0, 3.0, 1)]
+                .map(|(lower, upper, count)| HistogramBucket {
+                    lower,
+                    upper,
+                    count,
+                })
+                .into(),
+        };
+        let chart = histogram_html(&histogram, format_number);
+        assert!(chart.starts_with(r#"<div class="histogram" data-histogram="{&quot;"#));
+        // the smallest nonempty bucket stays visible
+        assert!(
+            chart.contains(r#"<span style="height: 100.0%" title="0 – 1: 100">"#),
+            "{}",
+            chart
+        );
+        assert!(chart.contains(r#"<span style="height: 0.0%" title="1 – 2: 0">"#));
+        assert!(chart.contains(r#"<span style="height: 4.0%" title="2 – 3: 1">"#));
+
+        let report = SchemaReport {
+            failures: [("db.gone".to_string(), "<denied>".to_string())].into(),
+            ..SchemaReport::default()
+        };
+        assert!(report
+            .to_html()
+            .contains("<li><code>db.gone</code>: &lt;denied&gt;</li>"));
+    }
+}
@@ -0,0 +1,461 @@
+//! The collection's indexes against what its documents hold: identifier, date and
+//! low-cardinality fields nothing indexes, unique-looking fields no unique index covers, and
+//! indexes on fields that barely exist. The suggestions are heuristics from the sample, not
+//! from the queries the collection serves
+
+use std::fmt;
+
+#[cfg(feature = "driver")]
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::SchemaAnalyzer;
+use crate::{BsonType, CollectionSchema, FieldSchema, GeoJsonType, Result};
+
+/// How few of the documents may hold an indexed field before the index is reported
+const RARE_PRESENCE: f64 = 0.05;
+
+/// How many of the documents must hold a field for an index on it to be suggested
+const SUGGEST_PRESENCE: f64 = 0.5;
+
+/// How many of an identifier's sampled values must be distinct for it to count as one
+const DISTINCT_SHARE: f64 = 0.9;
+
+/// How many distinct values a field may hold at most to count as low-cardinality
+const LOW_CARDINALITY: u64 = 10;
+
+/// How many times a low-cardinality field's values must repeat on average
+const LOW_CARDINALITY_REPEATS: u64 = 3;
+
+/// How many distinct values a field must hold, all of them different, to look unique
+const UNIQUE_MIN_VALUES: u64 = 10;
+
+/// What the indexes cover, see [`SchemaAnalyzer::index_report`]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Indexes {
+    pub existing: Vec<ExistingIndex>,
+    /// Fields no index starts with that likely deserve one, fields that look unique without
+    /// a unique index, and GeoJSON fields without a `2dsphere` index. Heuristics: whether an
+    /// index pays off depends on the queries
+    pub suggestions: Vec<IndexSuggestion>,
+    /// Fields of indexes that are neither sparse nor partial, held by under 5% of the sample
+    pub rare: Vec<RareIndexedField>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExistingIndex {
+    pub name: String,
+    /// The indexed paths, in key order
+    pub keys: Vec<String>,
+    /// The index is sparse or partial, so it only holds the documents having its fields
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sparse: bool,
+    /// The index rejects documents repeating its keys' values
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub unique: bool,
+    /// The keys indexed as `2dsphere`, which geospatial queries on GeoJSON need
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub spherical: Vec<String>,
+}
+
+/// A field to consider indexing
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexSuggestion {
+    pub path: String,
+    pub reason: SuggestionReason,
+    /// The share of sampled documents holding the field
+    pub presence: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SuggestionReason {
+    /// Named like an identifier, or an ObjectId or UUID, and nearly every value distinct.
+    /// `distinct` is how many distinct values were sampled, if the values were profiled and
+    /// there were few enough to count
+    Identifier { distinct: Option<u64> },
+    /// Holds mostly dates, which queries commonly filter and sort on
+    Date,
+    /// Holds GeoJSON geometries of `types`, which `$near` and `$geoWithin` queries need a
+    /// `2dsphere` index for
+    Geo { types: Vec<GeoJsonType> },
+    /// A string or integer holding few distinct values, which repeat, e.g. a status. Only worth
+    /// an index if queries filter on it often, best alongside a more selective field
+    LowCardinality { distinct: u64 },
+    /// A string or integer whose sampled values are all distinct, e.g. an email, which no
+    /// unique index covers by itself. Only told when the values were profiled and there were
+    /// few enough to count
+    UniqueCandidate { distinct: u64 },
+}
+
+/// A field of an index that few documents hold, so the index mostly maps them to null
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RareIndexedField {
+    pub index: String,
+    pub path: String,
+    /// The share of sampled documents holding the field
+    pub presence: f64,
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// List the collection's indexes and compare them against `schema`, inferred from it.
+    /// Identifiers are told by their values when
+    /// [`AnalysisOptions::profile_values`](crate::AnalysisOptions::profile_values) was on, and
+    /// by their names and types otherwise. GeoJSON fields are only recognized by their values
+    pub async fn index_report(&self, schema: &CollectionSchema) -> Result<Indexes> {
+        let indexes = self
+            .client
+            .database(&self.ns.db)
+            .collection::<bson::Document>(&self.ns.coll)
+            .list_indexes(None)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        let existing = indexes
+            .into_iter()
+            .map(|index| {
+                let options = index.options.unwrap_or_default();
+                ExistingIndex {
+                    name: options.name.unwrap_or_default(),
+                    keys: index.keys.keys().cloned().collect(),
+                    sparse: options.sparse.unwrap_or(false)
+                        || options.partial_filter_expression.is_some(),
+                    unique: options.unique.unwrap_or(false),
+                    spherical: index
+                        .keys
+                        .iter()
+                        .filter(|(_, kind)| kind.as_str() == Some("2dsphere"))
+                        .map(|(key, _)| key.clone())
+                        .collect(),
+                }
+            })
+            .collect();
+        Ok(Indexes::new(schema, existing))
+    }
+}
+
+impl Indexes {
+    pub(crate) fn new(schema: &CollectionSchema, existing: Vec<ExistingIndex>) -> Self {
+        let leading = existing
+            .iter()
+            .filter_map(|index| index.keys.first())
+            .collect::<Vec<_>>();
+        let spherical = existing
+            .iter()
+            .flat_map(|index| &index.spherical)
+            .collect::<Vec<_>>();
+        let unique = existing
+            .iter()
+            .filter(|index| index.unique && index.keys.len() == 1)
+            .map(|index| &index.keys[0])
+            .collect::<Vec<_>>();
+        let suggestions = schema
+            .fields
+            .iter()
+            // indexing array elements makes an index multikey, a choice best left to the reader
+            .filter(|(path, _)| *path != "_id" && !path.contains("[]"))
+            .filter_map(|(path, field)| {
+                let presence = schema.share(field.count);
+                if presence < SUGGEST_PRESENCE {
+                    return None;
+                }
+                // a geometry wants a 2dsphere index, whatever else leads with it
+                if let Some(types) = field.geojson_types() {
+                    return (!spherical.contains(&path)).then(|| IndexSuggestion {
+                        path: path.clone(),
+                        reason: SuggestionReason::Geo { types },
+                        presence,
+                    });
+                }
+                let indexed = leading.contains(&path);
+                let ty = field.types.iter().find(|ty| **ty != BsonType::Missing)?;
+                let reason = match ty {
+                    BsonType::Date if !indexed => SuggestionReason::Date,
+                    BsonType::ObjectId | BsonType::BinData if !indexed => identifier(field)?,
+                    BsonType::String | BsonType::Int | BsonType::Long
+                        if !indexed && is_id_name(path) =>
+                    {
+                        identifier(field)?
+                    }
+                    BsonType::String | BsonType::Int | BsonType::Long if !indexed => {
+                        unique_values(field).or_else(|| low_cardinality(field))?
+                    }
+                    BsonType::String | BsonType::Int | BsonType::Long
+                        if !unique.contains(&path) =>
+                    {
+                        unique_values(field)?
+                    }
+                    _ => return None,
+                };
+                Some(IndexSuggestion {
+                    path: path.clone(),
+                    reason,
+                    presence,
+                })
+            })
+            .collect();
+        let rare = if schema.sample_size == 0 {
+            Vec::new()
+        } else {
+            existing
+                .iter()
+                .filter(|index| !index.sparse)
+                .flat_map(|index| index.keys.iter().map(move |key| (index, key)))
+                .filter(|(_, key)| is_field_key(key))
+                .filter_map(|(index, key)| {
+                    let presence = schema_path(schema, key)
+                        .and_then(|path| schema.presence(&path))
+                        .unwrap_or(0.0);
+                    (presence < RARE_PRESENCE).then(|| RareIndexedField {
+                        index: index.name.clone(),
+                        path: key.clone(),
+                        presence,
+                    })
+                })
+                .collect()
+        };
+        Indexes {
+            existing,
+            suggestions,
+            rare,
+        }
+    }
+
+    /// The summary line, e.g. `4 indexes, 2 suggested, 1 on rarely present fields`
+    pub(crate) fn describe(&self) -> String {
+        let mut parts = vec![match self.existing.len() {
+            1 => "1 index".to_string(),
+            n => format!("{} indexes", n),
+        }];
+        if !self.suggestions.is_empty() {
+            parts.push(format!("{} suggested", self.suggestions.len()));
+        }
+        if !self.rare.is_empty() {
+            parts.push(format!("{} on rarely present fields", self.rare.len()));
+        }
+        parts.join(", ")
+    }
+}
+
+impl fmt::Display for IndexSuggestion {
+    /// e.g. `userId: identifier in 100.0% of documents, 40 distinct values sampled, not indexed`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match &self.reason {
+            SuggestionReason::Identifier { .. } => "identifier".to_string(),
+            SuggestionReason::Date => "date".to_string(),
+            SuggestionReason::Geo { types } => {
+                let types = types.iter().map(|ty| ty.to_string()).collect::<Vec<_>>();
+                format!("GeoJSON {}", types.join(", "))
+            }
+            SuggestionReason::LowCardinality { .. } => "low cardinality".to_string(),
+            SuggestionReason::UniqueCandidate { .. } => "unique candidate".to_string(),
+        };
+        write!(
+            f,
+            "{}: {} in {:.1}% of documents",
+            self.path,
+            kind,
+            self.presence * 100.0
+        )?;
+        match self.reason {
+            SuggestionReason::Geo { .. } => return f.write_str(", no 2dsphere index"),
+            SuggestionReason::LowCardinality { distinct } => {
+                return write!(
+                    f,
+                    ", {} distinct values sampled, not indexed: consider one if it's filtered \
+                     on often (heuristic)",
+                    distinct
+                )
+            }
+            SuggestionReason::UniqueCandidate { distinct } => {
+                return write!(
+                    f,
+                    ", all {} sampled values distinct, not uniquely indexed: consider a unique \
+                     index if they must be (heuristic)",
+                    distinct
+                )
+            }
+            _ => {}
+        }
+        if let SuggestionReason::Identifier {
+            distinct: Some(distinct),
+        } = self.reason
+        {
+            write!(f, ", {} distinct values sampled", distinct)?;
+        }
+        f.write_str(", not indexed")
+    }
+}
+
+impl fmt::Display for RareIndexedField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} indexes {}, in {:.1}% of documents",
+            self.index,
+            self.path,
+            self.presence * 100.0
+        )
+    }
+}
+
+/// Why `field` looks like an identifier, or `None` if its profiled values repeat too much
+fn identifier(field: &FieldSchema) -> Option<SuggestionReason> {
+    let Some(values) = &field.values else {
+        return Some(SuggestionReason::Identifier { distinct: None });
+    };
+    if values.too_many_values {
+        return Some(SuggestionReason::Identifier { distinct: None });
+    }
+    let distinct = values.distinct.len() as u64;
+    let held = field.count.saturating_sub(field.nulls());
+    (distinct > 1 && distinct as f64 >= held as f64 * DISTINCT_SHARE).then_some(
+        SuggestionReason::Identifier {
+            distinct: Some(distinct),
+        },
+    )
+}
+
+/// The distinct values of `field`, if they were profiled and few enough to count, and how
+/// many values they are of
+fn counted_values(field: &FieldSchema) -> Option<(u64, u64)> {
+    let values = field
+        .values
+        .as_ref()
+        .filter(|values| !values.too_many_values)?;
+    Some((
+        values.distinct.len() as u64,
+        field.count.saturating_sub(field.nulls()),
+    ))
+}
+
+/// Whether `field` holds few values that repeat, see [`SuggestionReason::LowCardinality`]
+fn low_cardinality(field: &FieldSchema) -> Option<SuggestionReason> {
+    let (distinct, held) = counted_values(field)?;
+    (distinct > 1 && distinct <= LOW_CARDINALITY && held >= distinct * LOW_CARDINALITY_REPEATS)
+        .then_some(SuggestionReason::LowCardinality { distinct })
+}
+
+/// Whether every value of `field` was different, see [`SuggestionReason::UniqueCandidate`]
+fn unique_values(field: &FieldSchema) -> Option<SuggestionReason> {
+    let (distinct, held) = counted_values(field)?;
+    (distinct >= UNIQUE_MIN_VALUES && distinct == held)
+        .then_some(SuggestionReason::UniqueCandidate { distinct })
+}
+
+/// Whether the last segment of `path` is named like an identifier: `id`, `user_id`, `userId`,
+/// `orderID`, `uuid`
+fn is_id_name(path: &str) -> bool {
+    let name = path.rsplit('.').next().unwrap_or(path);
+    name.eq_ignore_ascii_case("id")
+        || name.ends_with("_id")
+        || name.ends_with("Id")
+        || name.ends_with("ID")
+        || name.to_ascii_lowercase().ends_with("uuid")
+}
+
+/// Whether an index key names a field, rather than being `_id`, a wildcard or a text index's
+fn is_field_key(key: &str) -> bool {
+    key != "_id" && key != "_fts" && key != "_ftsx" && !key.ends_with("$**")
+}
+
+/// The schema's path for the index key `key`, which doesn't mark where it steps into arrays:
+/// `items.sku` is `items[].sku` when `items` held arrays of subdocuments
+fn schema_path(schema: &CollectionSchema, key: &str) -> Option<String> {
+    let mut path = String::new();
+    let mut segments = key.split('.').peekable();
+    while let Some(segment) = segments.next() {
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(segment);
+        if let Some(next) = segments.peek() {
+            let elements = format!("{}[]", path);
+            if !schema.fields.contains_key(&format!("{}.{}", path, next))
+                && schema
+                    .fields
+                    .contains_key(&format!("{}.{}", elements, next))
+            {
+                path = elements;
+            }
+        }
+    }
+    schema.fields.contains_key(&path).then_some(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    fn orders() -> CollectionSchema {
+        let ndjson = (0..12)
+            .map(|i| {
+                format!(
+                    "{{\"status\": \"{}\", \"email\": \"user{}@example.com\"}}\n",
+                    ["pending", "shipped", "delivered"][i % 3],
+                    i
+                )
+            })
+            .collect::<String>();
+        let options = AnalysisOptions {
+            profile_values: true,
+            ..AnalysisOptions::default()
+        };
+        CollectionSchema::from_ndjson(ndjson.as_bytes(), "shop.orders", &options).unwrap()
+    }
+
+    fn index(name: &str, key: &str, unique: bool) -> ExistingIndex {
+        ExistingIndex {
+            name: name.to_string(),
+            keys: vec![key.to_string()],
+            sparse: false,
+            unique,
+            spherical: Vec::new(),
+        }
+    }
+
+    fn reason(indexes: &Indexes, path: &str) -> Option<SuggestionReason> {
+        indexes
+            .suggestions
+            .iter()
+            .find(|suggestion| suggestion.path == path)
+            .map(|suggestion| suggestion.reason.clone())
+    }
+
+    #[test]
+    fn suggests_indexing_unindexed_low_cardinality_fields() {
+        let indexes = Indexes::new(&orders(), vec![index("_id_", "_id", true)]);
+        assert_eq!(
+            reason(&indexes, "status"),
+            Some(SuggestionReason::LowCardinality { distinct: 3 })
+        );
+        let status = indexes
+            .suggestions
+            .iter()
+            .find(|suggestion| suggestion.path == "status")
+            .unwrap();
+        assert!(status.to_string().ends_with("(heuristic)"));
+
+        let indexed = Indexes::new(&orders(), vec![index("status_1", "status", false)]);
+        assert_eq!(reason(&indexed, "status"), None);
+    }
+
+    #[test]
+    fn suggests_unique_indexes_for_fields_whose_values_are_all_distinct() {
+        let unindexed = Indexes::new(&orders(), Vec::new());
+        assert_eq!(
+            reason(&unindexed, "email"),
+            Some(SuggestionReason::UniqueCandidate { distinct: 12 })
+        );
+        let indexed = Indexes::new(&orders(), vec![index("email_1", "email", false)]);
+        assert_eq!(
+            reason(&indexed, "email"),
+            Some(SuggestionReason::UniqueCandidate { distinct: 12 })
+        );
+        let unique = Indexes::new(&orders(), vec![index("email_1", "email", true)]);
+        assert_eq!(reason(&unique, "email"), None);
+    }
+}
@@ -0,0 +1,757 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+};
+
+use bson::{Bson, Document};
+
+use crate::{
+    evolution::EvolutionSummary,
+    glob::path_match,
+    names::NameCheck,
+    pipeline::DEFAULT_MAX_DEPTH,
+    rare::RARE_PRESENCE,
+    schema::{
+        BinaryKind, BsonType, DocumentShape, ElementPresence, FormatVersion, RunMetadata,
+        SampleMethod, Shapes,
+    },
+    sizes::{document_size, DocumentSizeSummary, SizeSummary},
+    stats::{DateSummary, HistogramScale, NumericSummary},
+    widths::DocumentWidthSummary,
+    AnalysisOptions, CollectionSchema, FieldSchema, ProfileThresholds, Redaction, TimeBucket,
+    TypeMode, ValueProfile,
+};
+
+/// Infers a schema in Rust, one document at a time, following the same rules as the
+/// aggregation pipeline: fields are named by dotted path, array elements by the array's path
+/// with a `[]` suffix, and `max_depth` levels are reported
+#[derive(Debug, Clone)]
+pub struct SchemaBuilder {
+    max_depth: u32,
+    /// How many elements of each array are descended into, if not all
+    max_array_elements: Option<usize>,
+    /// How many fields of each document and subdocument are, if not all
+    max_fields_per_level: Option<usize>,
+    /// Paths (`*` and `?` wildcards) left out along with everything nested in them
+    excluded: Vec<String>,
+    documents: u64,
+    /// Documents with more top-level fields than `max_fields_per_level`
+    truncated_documents: u64,
+    fields: BTreeMap<String, FieldCounts>,
+    /// How many of the most common shapes to report, and the documents having each shape
+    shapes: Option<(usize, ShapeCounts)>,
+    /// The values seen at each path, if they are being profiled
+    values: Option<BTreeMap<String, ValueProfile>>,
+    /// How many example values to keep per path, and how to redact them
+    examples: (usize, Redaction),
+    /// How many of the most frequent values to report per path
+    top_values: usize,
+    /// The numbers seen at each path, if statistics are being collected
+    stats: Option<BTreeMap<String, NumericSummary>>,
+    /// The bytes of the documents and their top-level fields, if asked for
+    sizes: Option<SizeSummary>,
+    /// The sizes of the documents, if asked for
+    document_sizes: Option<DocumentSizeSummary>,
+    /// The key counts of the documents, if asked for
+    document_widths: Option<DocumentWidthSummary>,
+    /// The string lengths seen at each path, likewise
+    lengths: Option<BTreeMap<String, NumericSummary>>,
+    /// The binary subtypes seen at each path, likewise
+    binaries: Option<BTreeMap<String, BTreeMap<BinaryKind, u64>>>,
+    /// The dates seen at each path, likewise
+    dates: Option<BTreeMap<String, DateSummary>>,
+    /// How to bucket numbers and dates into histograms, and into how many buckets, if asked
+    histograms: Option<(HistogramScale, usize)>,
+    /// The fields seen in each period of a date field, if asked for
+    evolution: Option<EvolutionSummary>,
+    /// The field names that break drivers and updates, if they are being checked
+    names: Option<NameCheck>,
+}
+
+/// The documents having each set of (path, type) pairs
+type ShapeCounts = HashMap<Vec<(String, BsonType)>, u64>;
+
+#[derive(Debug, Clone, Default)]
+struct FieldCounts {
+    /// Documents containing the path
+    count: u64,
+    /// Documents holding each type at the path
+    types: BTreeMap<BsonType, u64>,
+    /// Documents whose value at the path wasn't fully descended into
+    truncated: u64,
+    /// For paths in arrays, the values at the path, each element counting, and how many of
+    /// them were subdocuments
+    values: u64,
+    objects: u64,
+}
+
+/// Something a document showed that no earlier document had
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Discovery {
+    /// A path never seen before, with the type it first appeared as
+    NewField { path: String, ty: String },
+    /// A known path holding a type it never had before
+    NewType { path: String, ty: String },
+}
+
+impl Discovery {
+    /// The path of the field the discovery is about
+    pub fn path(&self) -> &str {
+        match self {
+            Discovery::NewField { path, .. } | Discovery::NewType { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Discovery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Discovery::NewField { path, ty } => write!(f, "new field {}: {}", path, ty),
+            Discovery::NewType { path, ty } => write!(f, "new type for {}: {}", path, ty),
+        }
+    }
+}
+
+impl Default for SchemaBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_DEPTH)
+    }
+}
+
+impl SchemaBuilder {
+    pub fn new(max_depth: u32) -> Self {
+        Self {
+            max_depth: max_depth.max(1),
+            max_array_elements: None,
+            max_fields_per_level: None,
+            excluded: Vec::new(),
+            documents: 0,
+            truncated_documents: 0,
+            fields: BTreeMap::new(),
+            shapes: None,
+            values: None,
+            examples: (0, Redaction::None),
+            top_values: 0,
+            stats: None,
+            sizes: None,
+            document_sizes: None,
+            document_widths: None,
+            lengths: None,
+            binaries: None,
+            dates: None,
+            histograms: None,
+            evolution: None,
+            names: None,
+        }
+    }
+
+    /// Only descend into the first `max_array_elements` elements of arrays and
+    /// `max_fields_per_level` fields of documents and subdocuments, when set. Values cut short
+    /// are counted in [`FieldSchema::truncated`]
+    pub fn with_breadth_limits(
+        mut self,
+        max_array_elements: Option<usize>,
+        max_fields_per_level: Option<usize>,
+    ) -> Self {
+        self.max_array_elements = max_array_elements;
+        self.max_fields_per_level = max_fields_per_level;
+        self
+    }
+
+    /// Skip the fields matching `patterns` (`*` and `?` wildcards) and everything nested in
+    /// them, without descending into them
+    pub fn with_excluded_fields(mut self, patterns: Vec<String>) -> Self {
+        self.excluded = patterns;
+        self
+    }
+
+    /// The limits and exclusions of `options`
+    pub(crate) fn limited_by(self, options: &AnalysisOptions) -> Self {
+        self.with_breadth_limits(options.max_array_elements, options.max_fields_per_level)
+            .with_excluded_fields(options.exclude_fields.clone())
+    }
+
+    /// Also group the documents by shape, reporting the `top` most common
+    pub fn with_shapes(mut self, top: usize) -> Self {
+        self.shapes = Some((top, HashMap::new()));
+        self
+    }
+
+    /// Also profile the values at every path, see [`ValueProfile`]
+    pub fn with_value_profiles(mut self) -> Self {
+        self.values = Some(BTreeMap::new());
+        self
+    }
+
+    /// Also profile values, keeping up to `count` distinct examples per path, redacted
+    pub fn with_examples(self, count: usize, redaction: Redaction) -> Self {
+        let mut builder = self.with_value_profiles();
+        builder.examples = (count, redaction);
+        builder
+    }
+
+    /// Also collect statistics over the numeric, string and date values at every path, see
+    /// [`NumericStats`](crate::NumericStats), [`StringLengthStats`](crate::StringLengthStats)
+    /// and [`DateStats`](crate::DateStats), and count the subtypes of binary values
+    pub fn with_stats(mut self) -> Self {
+        self.stats = Some(BTreeMap::new());
+        self.lengths = Some(BTreeMap::new());
+        self.binaries = Some(BTreeMap::new());
+        self.dates = Some(BTreeMap::new());
+        self
+    }
+
+    /// Also add up how many bytes the documents' top-level fields take up, see
+    /// [`FieldSizes`](crate::FieldSizes)
+    pub fn with_field_sizes(mut self) -> Self {
+        self.sizes = Some(SizeSummary::default());
+        self
+    }
+
+    /// Also collect the distribution of the documents' sizes, see
+    /// [`DocumentSizes`](crate::DocumentSizes)
+    pub fn with_document_sizes(mut self) -> Self {
+        self.document_sizes = Some(DocumentSizeSummary::default());
+        self
+    }
+
+    /// Also collect the distribution of the documents' top-level key counts, see
+    /// [`DocumentWidths`](crate::DocumentWidths)
+    pub fn with_document_widths(mut self) -> Self {
+        self.document_widths = Some(DocumentWidthSummary::default());
+        self
+    }
+
+    /// With [`SchemaBuilder::with_stats`], also bucket numbers and dates into `buckets`
+    /// buckets, see [`Histogram`](crate::Histogram)
+    pub fn with_histograms(mut self, scale: HistogramScale, buckets: usize) -> Self {
+        self.histograms = Some((scale, buckets));
+        self
+    }
+
+    /// Also check the field names for dots, a leading `$`, emptiness and the like, see
+    /// [`FieldNameIssue`](crate::FieldNameIssue)
+    pub fn with_name_check(mut self) -> Self {
+        self.names = Some(NameCheck::default());
+        self
+    }
+
+    /// Also follow the fields over the `bucket` periods of the date (or ObjectId) at `field`,
+    /// see [`Evolution`](crate::Evolution)
+    pub fn with_evolution(mut self, field: impl Into<String>, bucket: TimeBucket) -> Self {
+        self.evolution = Some(EvolutionSummary::new(field.into(), bucket));
+        self
+    }
+
+    /// Also profile values, reporting the `count` most frequent per path
+    pub fn with_top_values(self, count: usize) -> Self {
+        let mut builder = self.with_value_profiles();
+        builder.top_values = count;
+        builder
+    }
+
+    /// Continue from an existing schema, e.g. one inferred by the pipeline, so only changes
+    /// relative to it are reported as discoveries
+    pub fn from_schema(schema: &CollectionSchema, max_depth: u32) -> Self {
+        let mut builder = Self::new(max_depth);
+        builder.documents = schema.sample_size;
+        builder.truncated_documents = schema.truncated_documents;
+        for (path, field) in &schema.fields {
+            let mut types = field.type_counts.clone();
+            types.remove(&BsonType::Missing);
+            builder.fields.insert(
+                path.clone(),
+                FieldCounts {
+                    count: field.count,
+                    types,
+                    truncated: field.truncated,
+                    ..FieldCounts::default()
+                },
+            );
+        }
+        // what the element presences were counted out of carries on
+        for (path, field) in &schema.fields {
+            let Some(presence) = &field.element_presence else {
+                continue;
+            };
+            if let Some(counts) = builder.fields.get_mut(path) {
+                counts.values = presence.count;
+            }
+            if let Some((parent, _)) = path.rsplit_once('.') {
+                if let Some(counts) = builder.fields.get_mut(parent) {
+                    counts.objects = presence.of;
+                }
+            }
+        }
+        builder
+    }
+
+    /// How many documents have been added
+    pub fn documents(&self) -> u64 {
+        self.documents
+    }
+
+    /// Fold a document into the schema, returning the fields and types it introduced
+    pub fn add(&mut self, doc: &Document) -> Vec<Discovery> {
+        self.documents += 1;
+        if let Some(sizes) = &mut self.sizes {
+            sizes.observe(doc);
+        }
+        if let Some(sizes) = &mut self.document_sizes {
+            sizes.observe(document_size(doc));
+        }
+        if let Some(widths) = &mut self.document_widths {
+            widths.observe(doc);
+        }
+        if let Some(names) = &mut self.names {
+            names.next_document();
+        }
+
+        // like the pipeline, count each path and (path, type) pair once per document
+        let mut pairs = BTreeSet::new();
+        let mut truncated = BTreeSet::new();
+        // but count every element's values, for the element subdocuments' field presence
+        let mut occurrences = HashMap::<String, (u64, u64)>::new();
+        let max_fields = self.max_fields_per_level.unwrap_or(usize::MAX);
+        let max_elements = self.max_array_elements.unwrap_or(usize::MAX);
+        if doc.len() > max_fields {
+            self.truncated_documents += 1;
+        }
+        let mut frontier = doc
+            .iter()
+            .take(max_fields)
+            .map(|(k, v)| (k.clone(), v))
+            .collect::<Vec<_>>();
+        if let Some(names) = &mut self.names {
+            for (path, _) in &frontier {
+                names.observe(doc, path, path);
+            }
+        }
+        for level in 1..=self.max_depth {
+            let mut children = Vec::new();
+            for (path, value) in frontier {
+                // what's nested in an excluded field never joins the frontier
+                if self
+                    .excluded
+                    .iter()
+                    .any(|pattern| path_match(pattern, &path))
+                {
+                    continue;
+                }
+                // the last level descends into nothing
+                let last = level == self.max_depth;
+                let (fields, elements) = if last {
+                    (0, 0)
+                } else {
+                    (max_fields, max_elements)
+                };
+                let cut = match value {
+                    Bson::Document(doc) => doc.len() > fields,
+                    Bson::Array(array) => array.len() > elements,
+                    _ => false,
+                };
+                if cut {
+                    truncated.insert(path.clone());
+                }
+                pairs.insert((path.clone(), bson_type(value)));
+                if path.contains("[]") {
+                    let (values, objects) = occurrences.entry(path.clone()).or_default();
+                    *values += 1;
+                    if let Bson::Document(_) = value {
+                        *objects += 1;
+                    }
+                }
+                if let Some(values) = &mut self.values {
+                    let (examples, redaction) = self.examples;
+                    values.entry(path.clone()).or_default().observe(
+                        value,
+                        examples,
+                        redaction,
+                        self.top_values > 0,
+                    );
+                }
+                if let Some(stats) = &mut self.stats {
+                    if matches!(
+                        value,
+                        Bson::Int32(_) | Bson::Int64(_) | Bson::Double(_) | Bson::Decimal128(_)
+                    ) {
+                        stats.entry(path.clone()).or_default().observe(value);
+                    }
+                }
+                if let (Some(lengths), Bson::String(s)) = (&mut self.lengths, value) {
+                    lengths
+                        .entry(path.clone())
+                        .or_default()
+                        .observe_number(s.len() as f64);
+                }
+                if let (Some(binaries), Bson::Binary(binary)) = (&mut self.binaries, value) {
+                    *binaries
+                        .entry(path.clone())
+                        .or_default()
+                        .entry(BinaryKind::of(binary.subtype))
+                        .or_default() += 1;
+                }
+                if let (Some(dates), Bson::DateTime(date)) = (&mut self.dates, value) {
+                    dates.entry(path.clone()).or_default().observe(*date);
+                }
+                if last {
+                    continue;
+                }
+                match value {
+                    Bson::Document(nested) => {
+                        for (k, v) in nested.iter().take(fields) {
+                            let child = format!("{}.{}", path, k);
+                            if let Some(names) = &mut self.names {
+                                names.observe(doc, &child, k);
+                            }
+                            children.push((child, v));
+                        }
+                    }
+                    Bson::Array(array) => children.extend(
+                        array
+                            .iter()
+                            .take(elements)
+                            .map(|v| (format!("{}[]", path), v)),
+                    ),
+                    _ => {}
+                }
+            }
+            frontier = children;
+        }
+        if let Some((_, shapes)) = &mut self.shapes {
+            *shapes.entry(pairs.iter().cloned().collect()).or_default() += 1;
+        }
+        if let Some(evolution) = &mut self.evolution {
+            evolution.observe(doc, &pairs);
+        }
+
+        let mut discoveries = Vec::new();
+        let mut counted = None;
+        for (path, ty) in pairs {
+            let field = match self.fields.get_mut(&path) {
+                Some(field) => field,
+                None => {
+                    discoveries.push(Discovery::NewField {
+                        path: path.clone(),
+                        ty: ty.to_string(),
+                    });
+                    self.fields.entry(path.clone()).or_default()
+                }
+            };
+            if counted.as_ref() != Some(&path) {
+                field.count += 1;
+            }
+            // a new field's first type is reported as part of the field
+            let first_type = field.types.is_empty();
+            let type_count = field.types.entry(ty.clone()).or_insert(0);
+            if *type_count == 0 && !first_type {
+                discoveries.push(Discovery::NewType {
+                    path: path.clone(),
+                    ty: ty.to_string(),
+                });
+            }
+            *type_count += 1;
+            counted = Some(path);
+        }
+        for path in truncated {
+            if let Some(field) = self.fields.get_mut(&path) {
+                field.truncated += 1;
+            }
+        }
+        for (path, (values, objects)) in occurrences {
+            if let Some(field) = self.fields.get_mut(&path) {
+                field.values += values;
+                field.objects += objects;
+            }
+        }
+        discoveries
+    }
+
+    /// The schema of every document added so far
+    pub fn build(&self, namespace: impl Into<String>) -> CollectionSchema {
+        let fields = self
+            .fields
+            .iter()
+            .map(|(path, field)| {
+                let mut type_counts = field.types.clone();
+                if field.count < self.documents {
+                    type_counts.insert(BsonType::Missing, self.documents - field.count);
+                }
+                let mut schema = FieldSchema::new(field.count, type_counts);
+                schema.truncated = field.truncated;
+                schema.values = self.values.as_ref().map(|values| {
+                    let mut profile = values.get(path).cloned().unwrap_or_default();
+                    if self.top_values > 0 {
+                        profile.rank_top_values(self.top_values, self.examples.1);
+                    }
+                    profile
+                });
+                schema.stats = self.stats.as_ref().and_then(|stats| {
+                    let summary = stats.get(path)?;
+                    let mut stats = summary.stats()?;
+                    stats.histogram = self
+                        .histograms
+                        .and_then(|(scale, buckets)| summary.histogram(scale, buckets));
+                    Some(stats)
+                });
+                schema.length_stats = self
+                    .lengths
+                    .as_ref()
+                    .and_then(|lengths| lengths.get(path)?.length_stats());
+                schema.binary_subtypes = self
+                    .binaries
+                    .as_ref()
+                    .and_then(|binaries| binaries.get(path).cloned())
+                    .unwrap_or_default();
+                schema.date_stats = self.dates.as_ref().and_then(|dates| {
+                    let summary = dates.get(path)?;
+                    let mut stats = summary.stats()?;
+                    stats.histogram = self
+                        .histograms
+                        .and_then(|(_, buckets)| summary.histogram(buckets));
+                    Some(stats)
+                });
+                schema.element_presence = path
+                    .rsplit_once('.')
+                    .filter(|(parent, _)| parent.contains("[]"))
+                    .and_then(|(parent, _)| self.fields.get(parent))
+                    .map(|parent| ElementPresence {
+                        count: field.values,
+                        of: parent.objects,
+                    });
+                (path.clone(), schema)
+            })
+            .collect();
+        let mut schema = CollectionSchema {
+            format_version: FormatVersion::default(),
+            namespace: namespace.into(),
+            document_count: self.documents,
+            sample_size: self.documents,
+            sample_method: SampleMethod::All,
+            fields,
+            meta: Some(RunMetadata::new(self.documents, self.documents)),
+            shapes: self.shapes.as_ref().map(|(top, shapes)| {
+                let mut counted = shapes.iter().collect::<Vec<_>>();
+                counted.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+                Shapes {
+                    distinct: shapes.len() as u64,
+                    top: counted
+                        .into_iter()
+                        .take(*top)
+                        .map(|(pairs, count)| shape(pairs, *count))
+                        .collect(),
+                }
+            }),
+            anomalies: Vec::new(),
+            privacy: None,
+            rare_fields: Vec::new(),
+            findings: Vec::new(),
+            confidence: None,
+            field_names: self.names.as_ref().map(NameCheck::issues),
+            sizes: self.sizes.as_ref().map(|sizes| sizes.sizes(self.documents)),
+            document_sizes: self.document_sizes.as_ref().and_then(|sizes| sizes.sizes()),
+            document_widths: self
+                .document_widths
+                .as_ref()
+                .and_then(|widths| widths.widths()),
+            truncated_documents: self.truncated_documents,
+            variants: None,
+            versions: None,
+            time_series: None,
+            view: None,
+            shards: None,
+            indexes: None,
+            evolution: self.evolution.as_ref().map(EvolutionSummary::evolution),
+            type_mode: TypeMode::Strict,
+        };
+        schema.anomalies = schema.find_anomalies(&ProfileThresholds::default());
+        schema.rare_fields = schema.find_rare_fields(RARE_PRESENCE);
+        schema
+    }
+}
+
+fn shape<'a>(pairs: impl IntoIterator<Item = &'a (String, BsonType)>, count: u64) -> DocumentShape {
+    let mut fields = BTreeMap::<String, Vec<BsonType>>::new();
+    for (path, ty) in pairs {
+        fields.entry(path.clone()).or_default().push(ty.clone());
+    }
+    DocumentShape { fields, count }
+}
+
+/// The type `$type` reports for a value
+pub(crate) fn bson_type(value: &Bson) -> BsonType {
+    match value {
+        Bson::Double(_) => BsonType::Double,
+        Bson::String(_) => BsonType::String,
+        Bson::Array(_) => BsonType::Array,
+        Bson::Document(_) => BsonType::Object,
+        Bson::Boolean(_) => BsonType::Bool,
+        Bson::Null => BsonType::Null,
+        Bson::RegularExpression(_) => BsonType::Regex,
+        Bson::JavaScriptCode(_) => BsonType::Javascript,
+        Bson::JavaScriptCodeWithScope(_) => BsonType::JavascriptWithScope,
+        Bson::Int32(_) => BsonType::Int,
+        Bson::Int64(_) => BsonType::Long,
+        Bson::Timestamp(_) => BsonType::Timestamp,
+        Bson::Binary(_) => BsonType::BinData,
+        Bson::ObjectId(_) => BsonType::ObjectId,
+        Bson::DateTime(_) => BsonType::Date,
+        Bson::Symbol(_) => BsonType::Symbol,
+        Bson::Decimal128(_) => BsonType::Decimal,
+        Bson::Undefined => BsonType::Undefined,
+        Bson::MaxKey => BsonType::MaxKey,
+        Bson::MinKey => BsonType::MinKey,
+        Bson::DbPointer(_) => BsonType::DbPointer,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::*;
+
+    fn types(schema: &CollectionSchema, path: &str) -> Vec<(BsonType, u64)> {
+        schema.fields[path]
+            .type_counts
+            .iter()
+            .map(|(ty, count)| (ty.clone(), *count))
+            .collect()
+    }
+
+    #[test]
+    fn counts_fields_and_types() {
+        let mut builder = SchemaBuilder::default();
+        builder.add(&doc! { "a": 1, "b": { "c": "x" } });
+        builder.add(&doc! { "a": "one" });
+        builder.add(&doc! { "a": null, "b": { "c": "y", "d": true } });
+        let schema = builder.build("db.coll");
+        assert_eq!(
+            (schema.namespace.as_str(), schema.sample_size),
+            ("db.coll", 3)
+        );
+        assert_eq!(
+            schema.fields.keys().collect::<Vec<_>>(),
+            ["a", "b", "b.c", "b.d"]
+        );
+        assert_eq!(schema.fields["a"].count, 3);
+        assert_eq!(
+            types(&schema, "a"),
+            [
+                (BsonType::String, 1),
+                (BsonType::Null, 1),
+                (BsonType::Int, 1)
+            ]
+        );
+        assert_eq!(
+            types(&schema, "b"),
+            [(BsonType::Object, 2), (BsonType::Missing, 1)]
+        );
+        assert_eq!(
+            types(&schema, "b.d"),
+            [(BsonType::Bool, 1), (BsonType::Missing, 2)]
+        );
+    }
+
+    #[test]
+    fn reports_new_fields_and_types() {
+        let mut builder = SchemaBuilder::default();
+        assert_eq!(
+            builder.add(&doc! { "a": 1 }),
+            [Discovery::NewField {
+                path: "a".to_string(),
+                ty: "int".to_string()
+            }]
+        );
+        assert_eq!(builder.add(&doc! { "a": 2 }), []);
+        assert_eq!(
+            builder.add(&doc! { "a": "x", "b": [1] }),
+            [
+                Discovery::NewType {
+                    path: "a".to_string(),
+                    ty: "string".to_string()
+                },
+                Discovery::NewField {
+                    path: "b".to_string(),
+                    ty: "array".to_string()
+                },
+                Discovery::NewField {
+                    path: "b[]".to_string(),
+                    ty: "int".to_string()
+                },
+            ]
+        );
+        assert_eq!(builder.documents(), 3);
+    }
+
+    #[test]
+    fn counts_array_elements_once_per_document() {
+        let mut builder = SchemaBuilder::default();
+        builder.add(&doc! { "tags": ["a", "b", 1], "items": [{ "x": 1 }, { "x": 2 }, {}] });
+        builder.add(&doc! { "tags": [], "items": [{ "y": 1 }] });
+        let schema = builder.build("db.coll");
+        assert_eq!(schema.fields["tags[]"].count, 1);
+        assert_eq!(
+            types(&schema, "tags[]"),
+            [
+                (BsonType::String, 1),
+                (BsonType::Int, 1),
+                (BsonType::Missing, 1)
+            ]
+        );
+        assert_eq!(schema.fields["items[].x"].count, 1);
+        assert_eq!(
+            schema.fields["items[].x"].element_presence,
+            Some(ElementPresence { count: 2, of: 4 })
+        );
+    }
+
+    #[test]
+    fn stops_at_the_maximum_depth() {
+        let mut builder = SchemaBuilder::new(2);
+        builder.add(&doc! { "a": { "b": { "c": 1 } }, "list": [[1]] });
+        let schema = builder.build("db.coll");
+        assert_eq!(
+            schema.fields.keys().collect::<Vec<_>>(),
+            ["a", "a.b", "list", "list[]"]
+        );
+        assert_eq!(schema.fields["a.b"].truncated, 1);
+        assert_eq!(schema.fields["list[]"].truncated, 1);
+        assert_eq!(schema.fields["a"].truncated, 0);
+    }
+
+    #[test]
+    fn limits_the_breadth_of_documents_and_arrays() {
+        let mut builder = SchemaBuilder::default().with_breadth_limits(Some(2), Some(2));
+        builder.add(&doc! { "a": 1, "b": { "x": 1, "y": 2, "z": 3 }, "c": 3 });
+        builder.add(&doc! { "a": [1, 2, "three"] });
+        let schema = builder.build("db.coll");
+        assert_eq!(
+            schema.fields.keys().collect::<Vec<_>>(),
+            ["a", "a[]", "b", "b.x", "b.y"]
+        );
+        assert_eq!(schema.truncated_documents, 1);
+        assert_eq!(schema.fields["b"].truncated, 1);
+        assert_eq!(schema.fields["a"].truncated, 1);
+        assert_eq!(
+            types(&schema, "a[]"),
+            [(BsonType::Int, 1), (BsonType::Missing, 1)]
+        );
+    }
+
+    #[test]
+    fn leaves_out_excluded_fields_and_their_contents() {
+        let mut builder = SchemaBuilder::default()
+            .with_excluded_fields(vec!["secret".to_string(), "*.token".to_string()]);
+        builder.add(&doc! {
+            "secret": { "key": 1 },
+            "name": "x",
+            "auth": { "token": "t", "user": "u" },
+        });
+        let schema = builder.build("db.coll");
+        assert_eq!(
+            schema.fields.keys().collect::<Vec<_>>(),
+            ["auth", "auth.user", "name"]
+        );
+    }
+}
@@ -0,0 +1,158 @@
+//! Publishing schema changes to Kafka, so downstream data teams can react to drift as the
+//! watch and scheduled modes detect it. Build with `--features kafka`
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use kafka::producer::{Producer, Record, RequiredAcks};
+use serde::{Deserialize, Serialize};
+
+use crate::{AnalyzerError, CollectionSchema, Discovery, FieldSchema, Result, SchemaDiff};
+
+/// One change to a field, published as a JSON message keyed by its namespace so a
+/// namespace's changes stay in order
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaChange {
+    pub namespace: String,
+    pub kind: ChangeKind,
+    pub path: String,
+    /// The field before the change, absent for new fields
+    pub before: Option<FieldSnippet>,
+    /// The field after the change, absent for removed fields
+    pub after: Option<FieldSnippet>,
+    /// When the change was detected, in RFC 3339
+    pub detected_at: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    NewField,
+    RemovedField,
+    /// The field gained or lost types
+    TypeChange,
+}
+
+/// What a change's message says about the field on either side of it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldSnippet {
+    /// Most frequent first, as in [`FieldSchema::types`]
+    pub types: Vec<String>,
+    pub count: u64,
+}
+
+impl From<&FieldSchema> for FieldSnippet {
+    fn from(field: &FieldSchema) -> Self {
+        FieldSnippet {
+            types: field.types.iter().map(|ty| ty.to_string()).collect(),
+            count: field.count,
+        }
+    }
+}
+
+impl SchemaChange {
+    /// The changes `diff` lists between `before` and `after`
+    pub fn from_diff(
+        before: &CollectionSchema,
+        after: &CollectionSchema,
+        diff: &SchemaDiff,
+    ) -> Vec<SchemaChange> {
+        let detected_at = now();
+        let change = |kind, path: &str| SchemaChange {
+            namespace: after.namespace.clone(),
+            kind,
+            path: path.to_string(),
+            before: before.fields.get(path).map(FieldSnippet::from),
+            after: after.fields.get(path).map(FieldSnippet::from),
+            detected_at: detected_at.clone(),
+        };
+        let added = diff
+            .added
+            .iter()
+            .map(|path| change(ChangeKind::NewField, path));
+        let removed = diff
+            .removed
+            .iter()
+            .map(|path| change(ChangeKind::RemovedField, path));
+        let changed = diff
+            .changed
+            .iter()
+            .map(|type_change| change(ChangeKind::TypeChange, &type_change.path));
+        added.chain(removed).chain(changed).collect()
+    }
+
+    /// The change a watch discovered, given the schema it's been added to
+    pub fn from_discovery(discovery: &Discovery, schema: &CollectionSchema) -> SchemaChange {
+        let path = discovery.path();
+        let after = schema.fields.get(path).map(FieldSnippet::from);
+        let (kind, before) = match discovery {
+            Discovery::NewField { .. } => (ChangeKind::NewField, None),
+            // the field as it was, without the type just seen
+            Discovery::NewType { ty, .. } => {
+                let before = after.clone().map(|mut before| {
+                    before.types.retain(|t| t != ty);
+                    before
+                });
+                (ChangeKind::TypeChange, before)
+            }
+        };
+        SchemaChange {
+            namespace: schema.namespace.clone(),
+            kind,
+            path: path.to_string(),
+            before,
+            after,
+            detected_at: now(),
+        }
+    }
+}
+
+/// A producer of [`SchemaChange`] messages on one topic. Clones share the producer, so one
+/// can be moved onto a blocking thread to publish
+#[derive(Clone)]
+pub struct KafkaSink {
+    producer: Arc<Mutex<Producer>>,
+    topic: String,
+}
+
+impl KafkaSink {
+    /// Connect to the cluster through `brokers` (`host:port`), loading the topic's metadata.
+    /// Messages are acknowledged by the partition leader
+    pub fn connect(brokers: Vec<String>, topic: impl Into<String>) -> Result<Self> {
+        let producer = Producer::from_hosts(brokers)
+            .with_ack_timeout(Duration::from_secs(5))
+            .with_required_acks(RequiredAcks::One)
+            .with_client_id("schema-analyzer".to_string())
+            .create()
+            .map_err(AnalyzerError::Kafka)?;
+        Ok(KafkaSink {
+            producer: Arc::new(Mutex::new(producer)),
+            topic: topic.into(),
+        })
+    }
+
+    /// Publish `changes`, blocking until they're acknowledged, so async callers should do it
+    /// on a blocking thread
+    pub fn publish(&self, changes: &[SchemaChange]) -> Result<()> {
+        let records = changes
+            .iter()
+            .map(|change| {
+                let json = serde_json::to_string(change).unwrap();
+                Record::from_key_value(&self.topic, change.namespace.as_str(), json)
+            })
+            .collect::<Vec<_>>();
+        self.producer
+            .lock()
+            .unwrap()
+            .send_all(&records)
+            .map_err(AnalyzerError::Kafka)?;
+        Ok(())
+    }
+}
+
+fn now() -> String {
+    // infallible for the current time
+    bson::DateTime::now().try_to_rfc3339_string().unwrap()
+}
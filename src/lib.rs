@@ -0,0 +1,146 @@
+//! Infer the schema of a MongoDB collection from a sample of its documents.
+//!
+//! The heavy lifting happens server-side: a single aggregation samples the collection and
+//! reduces it to the set of types observed for every field. Servers that can't run the
+//! pipeline get the same inference done client-side, see [`Strategy`].
+//!
+//! Connecting to deployments takes the default `driver` feature. Without it what's left infers
+//! schemas from given documents and files with the same logic, and builds for wasm32
+
+// the pipeline builders and the helpers of driver-only methods go unused without the driver
+#![cfg_attr(not(feature = "driver"), allow(dead_code, unused_imports))]
+
+#[cfg(feature = "driver")]
+mod analyzer;
+mod anomaly;
+mod anonymize;
+mod baseline;
+mod bench;
+#[cfg(feature = "driver")]
+mod cache;
+pub mod codegen;
+mod compare;
+mod confidence;
+mod config;
+mod cost;
+mod csv;
+mod dbref;
+mod diff;
+mod dump;
+mod error;
+mod evolution;
+mod explain;
+#[cfg(feature = "ffi")]
+mod ffi;
+mod generate;
+mod geo;
+mod glob;
+#[cfg(feature = "grpc")]
+mod grpc;
+mod html;
+mod indexes;
+mod infer;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod markdown;
+mod metrics;
+mod names;
+mod ndjson;
+mod options;
+mod pii;
+mod pipeline;
+mod profile;
+mod progress;
+#[cfg(feature = "python")]
+mod python;
+mod rare;
+mod relations;
+mod report;
+mod rules;
+mod schedule;
+mod schema;
+#[cfg(feature = "driver")]
+mod server;
+mod shards;
+mod sizes;
+mod stats;
+mod table;
+mod timeseries;
+#[cfg(feature = "driver")]
+mod tui;
+mod validate;
+mod variants;
+mod views;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "driver")]
+mod watch;
+#[cfg(feature = "driver")]
+mod webhook;
+mod widths;
+
+#[cfg(feature = "driver")]
+pub use analyzer::{identify_client, SchemaAnalyzer, APP_NAME};
+pub use anomaly::{Anomaly, AnomalyKind, Severity, Spelling};
+pub use bench::{Benchmark, BenchmarkRun, ServerLoad};
+#[cfg(feature = "driver")]
+pub use cache::ResultCache;
+pub use compare::ClusterComparison;
+pub use confidence::{Confidence, Interval};
+pub use config::{Config, NamespaceSettings};
+pub use cost::CostEstimate;
+pub use diff::{SchemaDiff, TypeChange};
+pub use error::{AnalyzerError, Result};
+pub use evolution::{Evolution, FieldHistory, PeriodTypes, TypeShift};
+pub use explain::{ExplainStats, StageStats};
+pub use generate::DocumentGenerator;
+pub use geo::GeoJsonType;
+#[cfg(feature = "grpc")]
+pub use grpc::serve_grpc;
+pub use indexes::{ExistingIndex, IndexSuggestion, Indexes, RareIndexedField, SuggestionReason};
+pub use infer::{Discovery, SchemaBuilder};
+#[cfg(feature = "kafka")]
+pub use kafka::{ChangeKind, FieldSnippet, KafkaSink, SchemaChange};
+pub use metrics::{render_metrics, SchemaMetrics};
+pub use names::{FieldNameIssue, NameProblem};
+pub use options::{
+    AnalysisOptions, IndexHint, SampleStrategy, Sampling, Strategy, TimeBucket, TypeMode,
+};
+pub use pii::{PiiFinding, PiiKind};
+#[cfg(feature = "bench")]
+#[doc(hidden)]
+pub use pipeline::parsed_fields;
+pub use pipeline::DEFAULT_MAX_DEPTH;
+pub use profile::{
+    ProfileThresholds, Redaction, ScalarValue, TopValue, ValueCount, ValueProfile,
+    MAX_COUNTED_VALUES, MAX_TRACKED_VALUES,
+};
+pub use progress::{Progress, ProgressFn};
+pub use rare::RareField;
+pub use relations::Relationship;
+#[cfg(feature = "driver")]
+pub use report::{cluster_namespaces, collections};
+pub use report::{NamespaceFilter, SchemaReport};
+pub use rules::{BuiltInRule, FieldRule, Finding, Rule, RuleConfig};
+pub use schedule::Schedule;
+pub use schema::{
+    BinaryKind, BsonType, CollectionSchema, DocumentShape, ElementPresence, FieldSchema,
+    FormatVersion, RunMetadata, SampleMethod, Shapes, Timings, FORMAT_VERSION,
+};
+#[cfg(feature = "driver")]
+pub use server::{serve, ServerOptions};
+pub use shards::{ShardDifference, ShardSample, Shards};
+pub use sizes::{DocumentSizes, FieldSize, FieldSizes, SizeBucket, MAX_DOCUMENT_SIZE};
+pub use stats::{
+    DateBucket, DateGranularity, DateStats, Histogram, HistogramBucket, HistogramScale,
+    NumericStats, StringLengthStats,
+};
+pub use timeseries::{TimeSeries, SERIES_LIMIT};
+#[cfg(feature = "driver")]
+pub use tui::browse;
+pub use validate::{Constraint, ConstraintFailures, InvalidDocument, ValidationReport, Violation};
+pub use variants::{Variant, Variants, VersionChange, Versions};
+pub use views::ViewDefinition;
+#[cfg(feature = "driver")]
+pub use webhook::{DriftNotification, Webhook};
+pub use widths::{DocumentWidths, KeyPattern};
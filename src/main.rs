@@ -1,208 +1,3256 @@
-use std::collections::HashMap;
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, Mutex, OnceLock},
+    time::Duration,
+};
 
-use bson::{doc, Document};
-use futures::stream::TryStreamExt;
-use mongodb::{options::*, Client};
+use bson::{Array, Bson, Document};
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use mongodb::{
+    options::{
+        AuthMechanism, ClientOptions, Collation, Credential, InsertManyOptions, ReadPreference,
+        ReadPreferenceOptions, SelectionCriteria, TagSet, Tls, TlsOptions,
+    },
+    Client, Namespace,
+};
+use schema_analyzer::{
+    browse, cluster_namespaces,
+    codegen::{
+        apply_validator, fetch_validator, Annotation, RustOptions, SqlDialect, UnionStrategy,
+    },
+    collections, identify_client, render_metrics, AnalysisOptions, AnalyzerError, Benchmark,
+    ClusterComparison, CollectionSchema, Config, DriftNotification, HistogramScale, IndexHint,
+    NamespaceFilter, NamespaceSettings, Progress, ProgressFn, Redaction, Result, ResultCache,
+    SampleStrategy, Sampling, Schedule, SchemaAnalyzer, SchemaDiff, SchemaMetrics, SchemaReport,
+    ServerOptions, Severity, Strategy, TimeBucket, TypeMode, ValidationReport, Webhook,
+};
+#[cfg(feature = "kafka")]
+use schema_analyzer::{KafkaSink, SchemaChange};
+use tracing::{debug, error, info, warn, Level};
+
+/// Infer the schema of MongoDB collections from a sample of their documents
+#[derive(Parser, Debug)]
+#[command(version, about, after_help = EXIT_STATUSES)]
+struct Cli {
+    /// Read the connection string and per-namespace settings from this TOML file. Defaults
+    /// to schema-analyzer.toml in the working directory, if there is one
+    #[arg(
+        long,
+        global = true,
+        value_name = "FILE",
+        env = "SCHEMA_ANALYZER_CONFIG"
+    )]
+    config: Option<PathBuf>,
+
+    /// Log more on standard error: -v for debugging details, -vv for everything
+    #[arg(short, long, global = true, action = ArgAction::Count, conflicts_with = "quiet")]
+    verbose: u8,
+
+    /// Only log errors
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// How to write log lines on standard error
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum LogFormat {
+    /// One human-readable line per event
+    Text,
+    /// One JSON object per event, for log collectors
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Infer and print the schema of one or more collections
+    Analyze(Box<AnalyzeArgs>),
+    /// Infer the schema of a mongodump or mongoexport file, without a connection
+    AnalyzeFile(AnalyzeFileArgs),
+    /// Compare the schemas of two collections, possibly on different clusters
+    Diff(DiffArgs),
+    /// Compare the namespaces of two clusters, e.g. production and staging: the ones only
+    /// one of them has, and the schema differences of those both have
+    CompareClusters(CompareClustersArgs),
+    /// Follow a collection's change stream, logging fields and types never seen before
+    Watch(WatchArgs),
+    /// Check a collection's documents against a $jsonSchema, counting the ones that violate
+    /// it and showing what's wrong with the first few
+    Validate(ValidateArgs),
+    /// Time the pipeline and client-side strategies at several sample sizes against one
+    /// collection, with the server's load, and check whether the schemas they infer agree
+    Bench(BenchArgs),
+    /// Run an HTTP service analyzing collections on request: POST /analyze with
+    /// {uri, db, collection, options} and GET /reports/{id}
+    Serve(ServeArgs),
+    /// Convert between the schema format of baselines and JSON output, and JSON Schema
+    Convert(ConvertArgs),
+    /// Generate fake documents shaped like a schema's, for seeding test environments
+    Generate(GenerateArgs),
+}
+
+#[derive(Args, Debug)]
+struct AnalyzeArgs {
+    /// MongoDB connection string. Can also be set in the config file
+    #[arg(long, env = "MONGODB_URI", hide_env_values = true)]
+    uri: Option<String>,
+
+    /// Database containing the collection
+    #[arg(
+        long,
+        env = "SCHEMA_ANALYZER_DB",
+        required_unless_present = "all_databases"
+    )]
+    db: Option<String>,
+
+    /// Collection to analyze
+    #[arg(long, env = "SCHEMA_ANALYZER_COLLECTION", required_unless_present_any = ["all_collections", "all_databases"])]
+    collection: Option<String>,
+
+    /// Analyze every collection in the database and report them keyed by namespace
+    #[arg(long, conflicts_with = "collection")]
+    all_collections: bool,
+
+    /// Analyze every collection in every non-system database on the cluster
+    #[arg(long, conflicts_with_all = ["db", "collection", "all_collections"])]
+    all_databases: bool,
+
+    /// Only analyze namespaces matching this db.collection pattern (* and ? wildcards).
+    /// Can be repeated
+    #[arg(long, value_name = "PATTERN")]
+    include_ns: Vec<String>,
+
+    /// Skip namespaces matching this db.collection pattern. Can be repeated
+    #[arg(long, value_name = "PATTERN")]
+    exclude_ns: Vec<String>,
+
+    /// With several collections, how many to analyze at once
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    concurrency: u32,
+
+    #[command(flatten)]
+    sampling: SamplingArgs,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    #[command(flatten)]
+    read: ReadArgs,
+
+    #[command(flatten)]
+    cache: CacheArgs,
+
+    /// How to print the inferred schema. Guessed from the extension of --out when omitted, and text
+    /// otherwise
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Write the output to this file instead of standard output. The file is only replaced
+    /// once the output is written in full. `{db}` and `{coll}` in the path are replaced by
+    /// the namespace, and with several namespaces write each schema to its own file, e.g.
+    /// `reports/{db}.{coll}.json`
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+
+    /// Print an artifact generated from the schema instead of the schema itself
+    #[arg(long, value_enum, conflicts_with_all = ["all_collections", "all_databases"])]
+    emit: Option<Emit>,
+
+    #[command(flatten)]
+    codegen: CodegenArgs,
+
+    /// Which findings fail the run, comma-separated. Replaces the default of failing on
+    /// drift from a baseline or validator and on collections that couldn't be analyzed; see
+    /// the exit statuses in --help
+    #[arg(long, value_enum, value_name = "FINDING", value_delimiter = ',')]
+    fail_on: Vec<FailOnArg>,
+
+    /// With several collections, probe fields holding ObjectIds or named like customer_id
+    /// against the other collections of their database and report the relationships found
+    #[arg(long, conflicts_with = "collection")]
+    relationships: bool,
+
+    /// Install the generated validator on the collection with collMod
+    #[arg(long, requires = "emit")]
+    apply_validator: bool,
+
+    /// Save the inferred schema to this file as the baseline for future --check-baseline runs
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["all_collections", "all_databases"])]
+    write_baseline: Option<PathBuf>,
+
+    /// Compare the inferred schema against a saved baseline, printing the differences instead
+    /// of the schema and exiting with status 1 if there are any
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["all_collections", "all_databases", "emit"])]
+    check_baseline: Option<PathBuf>,
+
+    /// Compare the inferred schema against the collection's $jsonSchema validator, printing
+    /// the fields and types it doesn't account for and exiting with status 1 if there are any
+    #[arg(long, conflicts_with_all = ["all_collections", "all_databases", "emit", "check_baseline"])]
+    check_validator: bool,
+
+    /// Also insert each inferred schema, stamped with the time of the run, into this
+    /// db.collection on the same cluster, so past results can be queried and diffed
+    #[arg(long, value_name = "NAMESPACE", value_parser = parse_namespace)]
+    store_results: Option<Namespace>,
+
+    /// Browse the inferred schemas interactively instead of printing them
+    #[arg(long, conflicts_with_all = ["out", "emit", "check_baseline", "check_validator"])]
+    tui: bool,
+
+    /// Stay running and re-run the analysis on this cron schedule, in UTC (e.g. "0 3 * * *"),
+    /// printing how each run's schemas differ from the previous run's. With --store-results,
+    /// the first run is compared against the last stored one
+    #[arg(
+        long,
+        value_name = "CRON",
+        conflicts_with_all = ["tui", "emit", "write_baseline", "check_baseline", "check_validator", "fail_on", "cache_ttl", "no_cache"]
+    )]
+    schedule: Option<Schedule>,
+
+    /// Run this shell command whenever a scheduled run's schema differs from the previous
+    /// run's, with the differences as JSON on standard input and the namespace in
+    /// $SCHEMA_NAMESPACE
+    #[arg(long, value_name = "COMMAND", requires = "schedule")]
+    on_change: Option<String>,
+
+    /// POST a JSON description of the drift and new anomalies a scheduled run finds to this
+    /// URL, e.g. a Slack incoming webhook. Failed posts are retried
+    #[arg(long, value_name = "URL", requires = "schedule")]
+    notify_webhook: Option<String>,
+
+    /// Post this file instead, with {{text}}, {{namespace}}, {{detected_at}}, {{diff}},
+    /// {{anomalies}} and {{summary}} replaced by their JSON values
+    #[arg(long, value_name = "FILE", requires = "notify_webhook")]
+    webhook_template: Option<PathBuf>,
+
+    /// Publish a JSON message for every field a scheduled run finds added, removed or
+    /// changed in type to Kafka, through these brokers (host:port, comma separated)
+    #[cfg(feature = "kafka")]
+    #[arg(
+        long,
+        value_name = "HOSTS",
+        value_delimiter = ',',
+        requires_all = ["schedule", "kafka_topic"]
+    )]
+    kafka_brokers: Vec<String>,
+
+    /// The topic of --kafka-brokers
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "TOPIC", requires = "kafka_brokers")]
+    kafka_topic: Option<String>,
+
+    /// On a sharded cluster, also analyze the collection on each shard directly and report
+    /// the fields whose types differ between shards
+    #[arg(long, conflicts_with_all = ["all_collections", "all_databases", "schedule"])]
+    per_shard: bool,
+
+    /// Also list the collection's indexes and report what they leave out: identifier and
+    /// date fields no index starts with, GeoJSON fields without a 2dsphere index, and indexed
+    /// fields few documents hold. Identifiers are told apart better, and GeoJSON only, with
+    /// --profile-values
+    #[arg(long, conflicts_with = "schedule")]
+    indexes: bool,
+
+    /// Print the command the sampling would run, as Extended JSON, instead of running it.
+    /// Only the document count the sample size depends on is queried
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "all_collections", "all_databases", "schedule", "tui", "emit", "write_baseline",
+            "check_baseline", "check_validator", "store_results", "per_shard", "indexes",
+            "apply_validator",
+        ]
+    )]
+    dry_run: bool,
+
+    /// Print what the sampling would read, as JSON, instead of running it: the sample size,
+    /// about how many documents are scanned for it, and why it's a full scan if it is. Only
+    /// the document count is queried
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "all_collections", "all_databases", "schedule", "tui", "emit", "write_baseline",
+            "check_baseline", "check_validator", "store_results", "per_shard", "indexes",
+            "apply_validator", "dry_run",
+        ]
+    )]
+    estimate_cost: bool,
+
+    /// Also run the sampling under explain and report each stage's time, the documents and
+    /// keys examined, and whether the server spilled to disk, with the run's metadata. Runs
+    /// the sampling a second time
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "all_collections", "all_databases", "schedule", "dry_run", "estimate_cost",
+        ]
+    )]
+    explain: bool,
+
+    /// Hash the namespace and field names and drop example and enum values from the output,
+    /// keeping only the types and statistics, to share the schema e.g. in a support ticket.
+    /// Names hash alike within one run only
+    #[arg(
+        long,
+        conflicts_with_all = ["tui", "check_baseline", "check_validator", "schedule", "dry_run"]
+    )]
+    anonymize: bool,
+}
+
+#[derive(Args, Debug)]
+struct AnalyzeFileArgs {
+    /// The file to read, or - for standard input
+    path: PathBuf,
+
+    /// What the file holds. Guessed from the extension when omitted: .bson files are raw
+    /// BSON, .json, .jsonl and .ndjson files newline-delimited JSON, and anything else an
+    /// archive
+    #[arg(long, value_enum)]
+    input: Option<Input>,
+
+    /// Only infer types from the first this many documents of each collection. Defaults to
+    /// all of them
+    #[arg(long)]
+    sample_size: Option<u64>,
+
+    /// Only infer types from this percentage of each collection's documents, spread evenly
+    /// through the file
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent, conflicts_with = "sample_size")]
+    sample_percent: Option<f64>,
+
+    #[command(flatten)]
+    inference: InferenceArgs,
+
+    /// How to print the inferred schema. Guessed from the extension of --out when omitted, and text
+    /// otherwise
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Write the output to this file instead of standard output. The file is only replaced
+    /// once the output is written in full. `{db}` and `{coll}` in the path are replaced by
+    /// the namespace, and with several namespaces write each schema to its own file, e.g.
+    /// `reports/{db}.{coll}.json`
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+
+    /// Print an artifact generated from the schema instead of the schema itself. Not for
+    /// archives
+    #[arg(long, value_enum)]
+    emit: Option<Emit>,
+
+    #[command(flatten)]
+    codegen: CodegenArgs,
+
+    /// Which findings fail the run, comma-separated. Replaces the default of failing on
+    /// drift from a baseline or validator and on collections that couldn't be analyzed; see
+    /// the exit statuses in --help
+    #[arg(long, value_enum, value_name = "FINDING", value_delimiter = ',')]
+    fail_on: Vec<FailOnArg>,
+
+    /// Browse the inferred schemas interactively instead of printing them
+    #[arg(long, conflicts_with_all = ["out", "emit"])]
+    tui: bool,
+
+    /// Hash the namespace and field names and drop example and enum values from the output,
+    /// keeping only the types and statistics, to share the schema e.g. in a support ticket.
+    /// Names hash alike within one run only
+    #[arg(long, conflicts_with = "tui")]
+    anonymize: bool,
+}
+
+#[derive(Args, Debug)]
+struct DiffArgs {
+    /// The old side of the comparison, as db.collection
+    #[arg(value_parser = parse_namespace)]
+    old: Namespace,
+
+    /// The new side of the comparison, as db.collection
+    #[arg(value_parser = parse_namespace)]
+    new: Namespace,
+
+    /// MongoDB connection string for the old collection. Can also be set in the config file
+    #[arg(long, env = "MONGODB_URI", hide_env_values = true)]
+    uri: Option<String>,
+
+    /// Connection string for the new collection, if it lives on another cluster
+    #[arg(long)]
+    new_uri: Option<String>,
+
+    #[command(flatten)]
+    sampling: SamplingArgs,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    #[command(flatten)]
+    read: ReadArgs,
+
+    #[command(flatten)]
+    cache: CacheArgs,
+
+    /// How to print the differences. Guessed from the extension of --out when omitted, and text
+    /// otherwise
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Write the output to this file instead of standard output. The file is only replaced
+    /// once the output is written in full
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct CompareClustersArgs {
+    /// Connection string of the old side of the comparison. Can also be set in the config file
+    #[arg(long, env = "MONGODB_URI", hide_env_values = true)]
+    left_uri: Option<String>,
+
+    /// Connection string of the new side of the comparison
+    #[arg(long)]
+    right_uri: String,
+
+    /// What to call the left cluster in the report
+    #[arg(long, value_name = "NAME", default_value = "left")]
+    left_name: String,
+
+    /// What to call the right cluster in the report
+    #[arg(long, value_name = "NAME", default_value = "right")]
+    right_name: String,
+
+    /// Only compare the collections of this database, rather than every non-system one
+    #[arg(long)]
+    db: Option<String>,
+
+    /// Only compare namespaces matching this db.collection pattern (* and ? wildcards). Can
+    /// be repeated
+    #[arg(long, value_name = "PATTERN")]
+    include_ns: Vec<String>,
+
+    /// Skip namespaces matching this db.collection pattern. Can be repeated
+    #[arg(long, value_name = "PATTERN")]
+    exclude_ns: Vec<String>,
+
+    /// How many collections to analyze at once on each cluster
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    concurrency: u32,
+
+    #[command(flatten)]
+    sampling: SamplingArgs,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    #[command(flatten)]
+    read: ReadArgs,
+
+    /// How to print the comparison. Guessed from the extension of --out when omitted, and
+    /// text otherwise
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Write the output to this file instead of standard output. The file is only replaced
+    /// once the output is written in full
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct WatchArgs {
+    /// MongoDB connection string. Change streams need a replica set or sharded cluster. Can
+    /// also be set in the config file
+    #[arg(long, env = "MONGODB_URI", hide_env_values = true)]
+    uri: Option<String>,
+
+    /// Database containing the collection
+    #[arg(long, env = "SCHEMA_ANALYZER_DB")]
+    db: String,
+
+    /// Collection to watch
+    #[arg(long, env = "SCHEMA_ANALYZER_COLLECTION")]
+    collection: String,
+
+    #[command(flatten)]
+    sampling: SamplingArgs,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    #[command(flatten)]
+    read: ReadArgs,
+
+    /// Serve Prometheus gauges of the schema (fields, mixed-type fields, new fields since the
+    /// baseline, analysis duration) at /metrics on this address, e.g. 127.0.0.1:9464
+    #[arg(long, value_name = "ADDR")]
+    metrics_addr: Option<SocketAddr>,
+
+    /// Count new fields against a baseline saved with --write-baseline rather than the
+    /// initial sample
+    #[arg(long, value_name = "FILE", requires = "metrics_addr")]
+    baseline: Option<PathBuf>,
+
+    /// POST a JSON description of every new field and type to this URL, e.g. a Slack
+    /// incoming webhook. Failed posts are retried
+    #[arg(long, value_name = "URL")]
+    notify_webhook: Option<String>,
+
+    /// Post this file instead, with {{text}}, {{namespace}}, {{detected_at}}, {{diff}},
+    /// {{anomalies}} and {{summary}} replaced by their JSON values
+    #[arg(long, value_name = "FILE", requires = "notify_webhook")]
+    webhook_template: Option<PathBuf>,
+
+    /// Publish a JSON message for every new field and type to Kafka, through these brokers
+    /// (host:port, comma separated)
+    #[cfg(feature = "kafka")]
+    #[arg(
+        long,
+        value_name = "HOSTS",
+        value_delimiter = ',',
+        requires = "kafka_topic"
+    )]
+    kafka_brokers: Vec<String>,
+
+    /// The topic of --kafka-brokers
+    #[cfg(feature = "kafka")]
+    #[arg(long, value_name = "TOPIC", requires = "kafka_brokers")]
+    kafka_topic: Option<String>,
+}
+
+#[derive(Args, Debug)]
+struct ServeArgs {
+    /// MongoDB connection string of requests that don't give one. Can also be set in the
+    /// config file
+    #[arg(long, env = "MONGODB_URI", hide_env_values = true)]
+    uri: Option<String>,
+
+    /// The address to listen on
+    #[arg(long, value_name = "ADDR", default_value = "127.0.0.1:8080")]
+    listen: SocketAddr,
+
+    /// Run at most N analyses at once, queueing further requests
+    #[arg(long, value_name = "N", default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..))]
+    max_concurrent: u32,
+
+    /// Answer a request for the same namespace and options with the report made within
+    /// this many seconds instead of analyzing again. 0 disables caching
+    #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+    cache_ttl: u64,
+
+    /// Keep at most N reports for GET /reports/{id}, dropping the oldest
+    #[arg(long, value_name = "N", default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    max_reports: u32,
+
+    /// Keep the connection pools of at most N connection strings, closing the least
+    /// recently used
+    #[arg(long, value_name = "N", default_value_t = 16, value_parser = clap::value_parser!(u32).range(1..))]
+    max_clients: u32,
+
+    /// Also serve the gRPC API of proto/schema_analyzer.proto on this address
+    #[cfg(feature = "grpc")]
+    #[arg(long, value_name = "ADDR")]
+    grpc_listen: Option<SocketAddr>,
+}
+
+#[derive(Args, Debug)]
+struct ConvertArgs {
+    /// The file to convert: a schema as written by --write-baseline or --format json, or a
+    /// JSON Schema or validator as written by --emit validator. - for standard input
+    path: PathBuf,
+
+    /// What to convert to
+    #[arg(long, value_enum)]
+    to: ConvertTo,
+
+    /// The namespace of a schema converted from a JSON Schema. Defaults to the file's name
+    #[arg(long)]
+    namespace: Option<String>,
+
+    /// Require fields present in at least this percentage of their parent objects in a
+    /// converted JSON Schema. At 100 only fields that were never missing are
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent, default_value_t = 100.0)]
+    required_presence: f64,
+
+    /// Write the output to this file instead of standard output. The file is only replaced
+    /// once the output is written in full
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ConvertTo {
+    /// A schema in the current format version, also upgrading schemas written by older
+    /// versions
+    Schema,
+    /// A bare $jsonSchema
+    JsonSchema,
+}
+
+#[derive(Args, Debug)]
+struct GenerateArgs {
+    /// The schema to follow: as written by --write-baseline or --format json, or a JSON
+    /// Schema or validator as written by --emit validator. - for standard input
+    path: PathBuf,
+
+    /// How many documents to generate
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    count: u64,
+
+    /// Generate the same documents on every run with the same seed
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Bulk-insert the documents into --target instead of printing them
+    #[arg(long, requires = "target", conflicts_with = "out")]
+    insert: bool,
+
+    /// The db.collection to insert into
+    #[arg(long, value_name = "NAMESPACE", value_parser = parse_namespace, requires = "insert")]
+    target: Option<Namespace>,
+
+    /// Drop the target collection before inserting, so reloading fixtures leaves only the
+    /// new documents
+    #[arg(long, requires = "insert")]
+    drop: bool,
+
+    /// MongoDB connection string for --insert. Can also be set in the config file
+    #[arg(long, env = "MONGODB_URI", hide_env_values = true)]
+    uri: Option<String>,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Write the documents to this file instead of standard output, as newline-delimited
+    /// canonical Extended JSON that mongoimport loads with every type intact. The file is only
+    /// replaced once the output is written in full
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct ValidateArgs {
+    /// MongoDB connection string. Can also be set in the config file
+    #[arg(long, env = "MONGODB_URI", hide_env_values = true)]
+    uri: Option<String>,
+
+    /// Database containing the collection
+    #[arg(long, env = "SCHEMA_ANALYZER_DB")]
+    db: String,
+
+    /// Collection to check
+    #[arg(long, env = "SCHEMA_ANALYZER_COLLECTION")]
+    collection: String,
+
+    /// Check against the JSON Schema in this file, either a validator as written by --emit
+    /// validator or the bare schema. Defaults to the schema inferred from the collection
+    #[arg(long, value_name = "FILE")]
+    schema: Option<PathBuf>,
+
+    /// Check against the validator generated from a baseline saved with --write-baseline
+    #[arg(long, value_name = "FILE", conflicts_with = "schema")]
+    baseline: Option<PathBuf>,
+
+    /// Show what's wrong with up to N invalid documents
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    invalid_examples: usize,
+
+    #[command(flatten)]
+    sampling: SamplingArgs,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    #[command(flatten)]
+    read: ReadArgs,
+
+    /// How to print the results. Guessed from the extension of --out when omitted, and text
+    /// otherwise
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Write the output to this file instead of standard output. The file is only replaced
+    /// once the output is written in full
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+#[derive(Args, Debug)]
+struct BenchArgs {
+    /// MongoDB connection string. Can also be set in the config file
+    #[arg(long, env = "MONGODB_URI", hide_env_values = true)]
+    uri: Option<String>,
+
+    /// Database containing the collection
+    #[arg(long, env = "SCHEMA_ANALYZER_DB")]
+    db: String,
+
+    /// Collection to benchmark
+    #[arg(long, env = "SCHEMA_ANALYZER_COLLECTION")]
+    collection: String,
+
+    /// Sample sizes to run each strategy at, comma-separated, besides the default of the
+    /// larger of 10000 or a third of the estimated document count. They replace
+    /// --sample-size, --sample-percent and --full-scan, as the runs do --strategy
+    #[arg(long, value_name = "N,...", value_delimiter = ',', default_values_t = [1000, 10000])]
+    sample_sizes: Vec<u64>,
+
+    /// Run each strategy at each sample size this many times, reporting the median time
+    #[arg(long, value_name = "N", default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    repeat: u32,
+
+    #[command(flatten)]
+    sampling: SamplingArgs,
+
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    #[command(flatten)]
+    read: ReadArgs,
+
+    /// How to print the results. Guessed from the extension of --out when omitted, and text
+    /// otherwise
+    #[arg(long, value_enum)]
+    format: Option<Format>,
+
+    /// Write the output to this file instead of standard output. The file is only replaced
+    /// once the output is written in full
+    #[arg(long, value_name = "FILE")]
+    out: Option<PathBuf>,
+}
+
+/// How to connect beyond what the connection string says, for deployments whose TLS and
+/// authentication settings don't belong in a connection string checked into scripts
+#[derive(Args, Debug)]
+struct ConnectionArgs {
+    /// Verify the server's certificate against the certificate authorities in this PEM
+    /// file. Enables TLS
+    #[arg(long, value_name = "FILE")]
+    tls_ca_file: Option<PathBuf>,
+
+    /// Present the certificate and private key in this PEM file to the server, as
+    /// MONGODB-X509 authenticates with. Enables TLS
+    #[arg(long, value_name = "FILE")]
+    tls_cert_key_file: Option<PathBuf>,
+
+    /// Authenticate with this mechanism rather than the connection string's. MONGODB-AWS
+    /// takes credentials from the connection string or else the AWS environment variables,
+    /// ECS task role or EC2 instance profile
+    #[arg(long, value_enum, value_name = "MECHANISM")]
+    auth_mechanism: Option<AuthMechanismArg>,
+
+    /// Give up on finding a suitable server after this many seconds rather than 30
+    #[arg(long, value_name = "SECONDS", value_parser = clap::value_parser!(u64).range(1..))]
+    server_selection_timeout: Option<u64>,
+
+    /// Name the connections in server logs and currentOp, rather than the connection
+    /// string's appName or schema-analyzer
+    #[arg(long, value_name = "NAME")]
+    app_name: Option<String>,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum AuthMechanismArg {
+    #[value(name = "SCRAM-SHA-1")]
+    ScramSha1,
+    #[value(name = "SCRAM-SHA-256")]
+    ScramSha256,
+    #[value(name = "MONGODB-X509")]
+    X509,
+    #[value(name = "MONGODB-AWS")]
+    Aws,
+    /// LDAP, through the $external database
+    #[value(name = "PLAIN")]
+    Plain,
+}
+
+impl ConnectionArgs {
+    /// Apply the settings to `client_options`, keeping the connection string's TLS options
+    /// and credentials the flags don't replace
+    fn apply(&self, client_options: &mut ClientOptions) -> Result<()> {
+        if self.tls_ca_file.is_some() || self.tls_cert_key_file.is_some() {
+            let mut tls = match client_options.tls.take() {
+                Some(Tls::Enabled(tls)) => tls,
+                _ => TlsOptions::default(),
+            };
+            // checked here, as the driver only reads them once it connects
+            for (file, option) in [
+                (&self.tls_ca_file, &mut tls.ca_file_path),
+                (&self.tls_cert_key_file, &mut tls.cert_key_file_path),
+            ] {
+                if let Some(path) = file {
+                    std::fs::metadata(path).map_err(|e| file_error(path, e))?;
+                    *option = Some(path.clone());
+                }
+            }
+            client_options.tls = Some(Tls::Enabled(tls));
+        }
+        if let Some(mechanism) = self.auth_mechanism {
+            let mechanism = match mechanism {
+                AuthMechanismArg::ScramSha1 => AuthMechanism::ScramSha1,
+                AuthMechanismArg::ScramSha256 => AuthMechanism::ScramSha256,
+                AuthMechanismArg::X509 => AuthMechanism::MongoDbX509,
+                AuthMechanismArg::Aws => AuthMechanism::MongoDbAws,
+                AuthMechanismArg::Plain => AuthMechanism::Plain,
+            };
+            client_options
+                .credential
+                .get_or_insert_with(Credential::default)
+                .mechanism = Some(mechanism);
+        }
+        if let Some(seconds) = self.server_selection_timeout {
+            client_options.server_selection_timeout = Some(Duration::from_secs(seconds));
+        }
+        Ok(())
+    }
+}
+
+/// Which replica set members to read from
+#[derive(Args, Debug)]
+struct ReadArgs {
+    /// Read from these members instead of the connection string's read preference (primary
+    /// unless set)
+    #[arg(long, value_enum)]
+    read_preference: Option<ReadMode>,
+
+    /// Prefer members with all of these tags, as comma-separated name:value pairs. Can be
+    /// repeated to fall back to other tag sets in order; an empty set matches any member
+    #[arg(long, value_name = "TAGS", value_parser = parse_tag_set, requires = "read_preference")]
+    read_preference_tags: Vec<TagSet>,
+}
+
+/// Reusing the results of recent runs
+#[derive(Args, Debug)]
+struct CacheArgs {
+    /// Reuse the result of analyzing the same namespace of the same cluster with the same
+    /// options within this many seconds, kept in $XDG_CACHE_HOME/schema-analyzer
+    /// (~/.cache/schema-analyzer). 0 disables caching
+    #[arg(long, value_name = "SECONDS", default_value_t = 300)]
+    cache_ttl: u64,
+
+    /// Analyze again even if a recent result is cached, replacing it
+    #[arg(long)]
+    no_cache: bool,
+}
+
+impl CacheArgs {
+    /// The cache of the cluster the connection string from the command line or else the
+    /// config file connects to, unless caching is disabled
+    fn cache(&self, uri: Option<&str>, config: &Config) -> Option<ResultCache> {
+        let uri = uri.or(config.uri.as_deref())?;
+        if self.cache_ttl == 0 {
+            return None;
+        }
+        let Some(dir) = ResultCache::default_dir() else {
+            debug!("not caching results: neither XDG_CACHE_HOME nor HOME is set");
+            return None;
+        };
+        let ttl = Duration::from_secs(self.cache_ttl);
+        Some(ResultCache::new(dir, uri, ttl).refresh(self.no_cache))
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ReadMode {
+    #[value(name = "primary")]
+    Primary,
+    #[value(name = "primaryPreferred")]
+    PrimaryPreferred,
+    #[value(name = "secondary")]
+    Secondary,
+    #[value(name = "secondaryPreferred")]
+    SecondaryPreferred,
+    #[value(name = "nearest")]
+    Nearest,
+}
+
+impl ReadArgs {
+    /// The read preference given, if any. Tag sets only apply to modes that can read from a
+    /// secondary
+    fn read_preference(&self) -> Result<Option<ReadPreference>> {
+        let Some(mode) = self.read_preference else {
+            return Ok(None);
+        };
+        let options = ReadPreferenceOptions::builder()
+            .tag_sets(
+                (!self.read_preference_tags.is_empty()).then(|| self.read_preference_tags.clone()),
+            )
+            .build();
+        Ok(Some(match mode {
+            ReadMode::Primary => {
+                if !self.read_preference_tags.is_empty() {
+                    return Err(AnalyzerError::InvalidOptions(
+                        "--read-preference-tags can't be used with the primary read preference"
+                            .to_string(),
+                    ));
+                }
+                ReadPreference::Primary
+            }
+            ReadMode::PrimaryPreferred => ReadPreference::PrimaryPreferred { options },
+            ReadMode::Secondary => ReadPreference::Secondary { options },
+            ReadMode::SecondaryPreferred => ReadPreference::SecondaryPreferred { options },
+            ReadMode::Nearest => ReadPreference::Nearest { options },
+        }))
+    }
+}
+
+/// Options controlling what's inferred from the sampled documents, for collections and files
+/// alike
+#[derive(Args, Debug)]
+struct InferenceArgs {
+    /// How many levels of nested subdocuments to descend into. 1 reports top-level fields
+    /// only. Defaults to 10
+    #[arg(long, env = "SCHEMA_ANALYZER_MAX_DEPTH", value_parser = clap::value_parser!(u32).range(1..))]
+    max_depth: Option<u32>,
+
+    /// Only infer the types of the first N elements of each array. Arrays cut short are
+    /// reported
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_array_elements: Option<u32>,
+
+    /// Only infer the types of the first N fields of each document and subdocument. Objects
+    /// cut short are reported
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_fields_per_level: Option<u32>,
+
+    /// Leave fields matching this path pattern (* and ? wildcards) and everything nested in
+    /// them out of the schema. Client-side inference doesn't descend into them at all. Array
+    /// elements match with or without their [], so 'items.*' covers 'items[].sku'. Can be
+    /// repeated
+    #[arg(long, visible_alias = "exclude", value_name = "PATTERN")]
+    exclude_field: Vec<String>,
+
+    /// Only report fields matching this path pattern (* and ? wildcards), everything nested
+    /// in them and their parents, e.g. 'events.*'. Can be repeated
+    #[arg(long, value_name = "PATTERN")]
+    include: Vec<String>,
+
+    /// Only report the fields whose paths match this pattern (* and ? wildcards), e.g.
+    /// '*_at', without their parents. Unlike --include it leaves the query alone, filtering
+    /// what was inferred
+    #[arg(long, value_name = "PATTERN")]
+    field_pattern: Option<String>,
+
+    /// Also group the sampled documents by their exact set of field paths and types, and
+    /// report the N most common shapes
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "10")]
+    shapes: Option<usize>,
+
+    /// Also inspect the sampled values, not just their types, reporting e.g. dates stored as
+    /// strings among the anomalies
+    #[arg(long)]
+    profile_values: bool,
+
+    /// With --profile-values, report string fields as numbers stored as strings when at
+    /// least this percentage of their values parse as numbers
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent, default_value_t = 90.0)]
+    numeric_string_threshold: f64,
+
+    /// List the fields present in fewer than this percentage of the sampled documents as
+    /// rare, which are usually typos, remnants of removed code or one-off migrations
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent, default_value_t = 1.0)]
+    rare_field_threshold: f64,
+
+    /// With --profile-values, report string and integer fields taking at most this many
+    /// distinct values as enum candidates, which code generators emit as enums
+    #[arg(long, value_name = "N", default_value_t = 10, value_parser = clap::value_parser!(u16).range(1..=50))]
+    max_enum_values: u16,
+
+    /// Keep up to N distinct example values per field in the report
+    #[arg(long, value_name = "N")]
+    examples: Option<usize>,
+
+    /// How to make string and binary example and top values safe to share. Defaults to keeping
+    /// them
+    #[arg(long, value_enum)]
+    redact: Option<RedactArg>,
+
+    /// Also report the N most frequent string and integer values of each field, with their
+    /// counts
+    #[arg(long, value_name = "N")]
+    top_values: Option<usize>,
+
+    /// Skip the statistics of numeric, string, date and binary fields. With the pipeline
+    /// strategy this saves reading a second, smaller sample
+    #[arg(long)]
+    no_stats: bool,
+
+    /// Also bucket the values of numeric and date fields into histograms, printed as
+    /// sparklines. Log scale only applies to numbers, and only if they're all positive
+    #[arg(long, value_enum, value_name = "SCALE", conflicts_with = "no_stats")]
+    histograms: Option<HistogramArg>,
+
+    /// How many buckets --histograms have
+    #[arg(long, value_name = "N", default_value_t = 20, requires = "histograms", value_parser = clap::value_parser!(u32).range(1..))]
+    histogram_buckets: u32,
+
+    /// Also list the fields whose names or sampled values look like emails, phone numbers,
+    /// social security numbers, IP addresses or credit card numbers
+    #[arg(long)]
+    detect_pii: bool,
+
+    /// Also report each type's share of each field's occurrences, e.g. 0.98 string and 0.02
+    /// null
+    #[arg(long)]
+    type_shares: bool,
+
+    /// Also list the fields whose names hold a dot, start with $, are empty, longer than 128
+    /// bytes or hold control characters, which break drivers and updates, with the _ids of
+    /// sampled documents having them
+    #[arg(long)]
+    check_field_names: bool,
+
+    /// Also break down how many bytes each top-level field takes up, on average and
+    /// extrapolated to the whole collection. Server-side this needs MongoDB 4.4
+    #[arg(long)]
+    field_sizes: bool,
+
+    /// Also report the distribution of document sizes, warning about documents nearing the
+    /// 16 MiB limit. Server-side this needs MongoDB 4.4
+    #[arg(long)]
+    document_sizes: bool,
+
+    /// Also report the distribution of top-level key counts, warning about documents with
+    /// hundreds of keys and what their keys look like
+    #[arg(long)]
+    document_widths: bool,
+
+    /// Which type names to report. canonical collapses int, long, double and decimal into
+    /// number, so baselines and diffs don't flag fields written as int by one client and
+    /// long by another
+    #[arg(long, value_enum, default_value_t = TypeModeArg::Strict)]
+    type_mode: TypeModeArg,
+
+    /// Also infer a schema per value of this discriminator field, e.g. type, reporting the
+    /// fields every variant shares and then each variant's own, for the 20 most common values
+    #[arg(long, value_name = "FIELD")]
+    group_by: Option<String>,
+
+    /// Also infer a schema per value of this version field, e.g. schemaVersion, and diff
+    /// each version against the one before it, documenting how the documents migrated
+    #[arg(long, value_name = "FIELD")]
+    version_field: Option<String>,
+
+    /// Also break the sample down by the period of this date or ObjectId field, e.g.
+    /// createdAt, reporting when each field first and last appears and how its types changed
+    #[arg(long, value_name = "FIELD")]
+    time_field: Option<String>,
+
+    /// The periods --time-field is broken down by
+    #[arg(long, value_enum, default_value_t = TimeBucketArg::Month, requires = "time_field")]
+    time_bucket: TimeBucketArg,
+}
+
+/// Options controlling how each collection is sampled. They override the config file
+#[derive(Args, Debug)]
+struct SamplingArgs {
+    /// Number of documents to sample. Defaults to the larger of 10000 or a third of the
+    /// estimated document count
+    #[arg(long, env = "SCHEMA_ANALYZER_SAMPLE_SIZE")]
+    sample_size: Option<u64>,
+
+    /// Sample this percentage of the estimated document count instead
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent, conflicts_with = "sample_size")]
+    sample_percent: Option<f64>,
+
+    /// Analyze every document instead of a sample. Exact, but reads the whole collection
+    #[arg(long, conflicts_with_all = ["sample_size", "sample_percent"])]
+    full_scan: bool,
+
+    /// Only analyze documents matching this query filter, given as Extended JSON, e.g.
+    /// '{"tenant": "acme"}'
+    #[arg(long = "match", value_name = "FILTER", value_parser = parse_filter)]
+    filter: Option<Document>,
+
+    /// Analyze exactly the documents with these `_id`s instead of a sample, given as an
+    /// Extended JSON array, e.g. '[{"$oid": "65f0c0ffee00000000000001"}, 42]'. A warning is
+    /// logged for those not found
+    #[arg(
+        long,
+        value_name = "IDS",
+        value_parser = parse_ids,
+        conflicts_with_all = ["sample_size", "sample_percent", "full_scan"]
+    )]
+    ids: Option<Array>,
+
+    /// Compare strings in the filter by these language rules, given as JSON, e.g.
+    /// '{"locale": "fr", "strength": 2}'
+    #[arg(long, value_name = "COLLATION", value_parser = parse_collation)]
+    collation: Option<Document>,
+
+    /// Have the queries use this index, given by name or as a JSON key pattern, e.g.
+    /// tenant_1 or '{"tenant": 1}'. Mostly useful with --match
+    #[arg(long, value_name = "INDEX", value_parser = parse_hint)]
+    hint: Option<IndexHint>,
+
+    /// Attach this comment to the queries instead of "schema-analyzer vVERSION
+    /// run-id=ID", where ID is the same for every query a run makes
+    #[arg(long, value_name = "TEXT")]
+    comment: Option<String>,
+
+    /// Let the aggregation write temporary files instead of failing at the server's memory
+    /// limit on large samples
+    #[arg(long)]
+    allow_disk_use: bool,
+
+    /// Abort the query on the server after this many milliseconds
+    #[arg(long, value_name = "MS")]
+    max_time_ms: Option<u64>,
+
+    /// How many documents the server returns per batch
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    batch_size: Option<u32>,
+
+    /// How many times to retry the sampling after a network error or failover, backing off
+    /// exponentially
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    retries: u32,
+
+    /// Read at most this many documents a second when documents are read client-side, to
+    /// spare a production member's cache and disk
+    #[arg(long, value_name = "DOCS_PER_SEC", value_parser = clap::value_parser!(u32).range(1..))]
+    throttle: Option<u32>,
+
+    /// Pick the sample by a hash of each _id under this seed instead of at random, so runs
+    /// against the same data sample the same documents. Reads every document client-side
+    #[arg(long, value_name = "SEED", conflicts_with = "stratify_by")]
+    seed: Option<u64>,
+
+    /// Which documents a sample is. recent reads the newest by natural order with find, for
+    /// capped collections and oplog-like data, where a random sample mostly tells you about
+    /// documents about to be deleted
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = SampleStrategyArg::Random,
+        conflicts_with_all = ["seed", "stratify_by"]
+    )]
+    sample_strategy: SampleStrategyArg,
+
+    /// Sample up to --per-stratum random documents per value of this field instead of a
+    /// random sample, so rare kinds of document are captured too. Reads every document and
+    /// needs MongoDB 5.2
+    #[arg(long, value_name = "FIELD")]
+    stratify_by: Option<String>,
+
+    /// How many documents --stratify-by samples per value
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 500,
+        requires = "stratify_by"
+    )]
+    per_stratum: u64,
+
+    /// Sample the documents in a range of this field, which has to hold a random number from 0
+    /// to 1 in every document and be indexed, instead of with $sample. The same documents are
+    /// sampled each time, from where --seed puts the range, without a scan or a sort
+    #[arg(long, value_name = "FIELD", conflicts_with = "stratify_by")]
+    random_key_field: Option<String>,
+
+    #[command(flatten)]
+    inference: InferenceArgs,
+
+    /// Where to infer types. auto runs the aggregation pipeline and falls back to client-side
+    /// inference when the server rejects it
+    #[arg(long, value_enum, default_value_t = StrategyArg::Auto)]
+    strategy: StrategyArg,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum SampleStrategyArg {
+    /// Documents picked at random
+    Random,
+    /// The last documents in natural order
+    Recent,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum StrategyArg {
+    /// Server-side aggregation, or client-side if the server can't run it
+    Auto,
+    /// Server-side aggregation only
+    Pipeline,
+    /// Read the first documents with find and infer their types locally
+    Client,
+}
+
+impl InferenceArgs {
+    /// Override `options` with what was given
+    fn apply(&self, options: &mut AnalysisOptions) {
+        NamespaceSettings {
+            max_depth: self.max_depth,
+            exclude_fields: self.exclude_field.clone(),
+            include_fields: self.include.clone(),
+            ..NamespaceSettings::default()
+        }
+        .apply(options);
+        options.max_array_elements = self.max_array_elements.map(|n| n as usize);
+        options.max_fields_per_level = self.max_fields_per_level.map(|n| n as usize);
+        options.field_pattern = self.field_pattern.clone();
+        options.shapes = self.shapes;
+        options.profile_values = self.profile_values;
+        options.profile_thresholds.numeric_strings = self.numeric_string_threshold / 100.0;
+        options.rare_fields = self.rare_field_threshold / 100.0;
+        options.profile_thresholds.enum_values = self.max_enum_values.into();
+        options.examples = self.examples.unwrap_or_default();
+        options.redaction = redaction(self.redact);
+        options.top_values = self.top_values.unwrap_or_default();
+        options.stats = !self.no_stats;
+        options.histograms = self.histograms.map(histogram_scale);
+        options.histogram_buckets = self.histogram_buckets as usize;
+        options.detect_pii = self.detect_pii;
+        options.type_shares = self.type_shares;
+        options.check_field_names = self.check_field_names;
+        options.field_sizes = self.field_sizes;
+        options.document_sizes = self.document_sizes;
+        options.document_widths = self.document_widths;
+        options.type_mode = type_mode(self.type_mode);
+        options.group_by = self.group_by.clone();
+        options.version_field = self.version_field.clone();
+        options.time_field = self.time_field.clone();
+        options.time_bucket = time_bucket(self.time_bucket);
+    }
+}
+
+impl SamplingArgs {
+    /// The config file's options for `ns`, overridden by the command line
+    fn options_for(&self, config: &Config, ns: &Namespace) -> AnalysisOptions {
+        let mut options = config.options_for(ns);
+        NamespaceSettings {
+            sample_size: self.sample_size,
+            sample_percent: self.sample_percent,
+            full_scan: self.full_scan,
+            ..NamespaceSettings::default()
+        }
+        .apply(&mut options);
+        options.filter = self.filter.clone();
+        options.ids = self.ids.clone().unwrap_or_default();
+        options.collation = self.collation.clone();
+        options.hint = self.hint.clone();
+        options.comment = match &self.comment {
+            Some(comment) => comment.clone(),
+            None => format!("{} run-id={}", options.comment, run_id()),
+        };
+        self.inference.apply(&mut options);
+        options.seed = self.seed;
+        options.sample_strategy = match self.sample_strategy {
+            SampleStrategyArg::Random => SampleStrategy::Random,
+            SampleStrategyArg::Recent => SampleStrategy::Recent,
+        };
+        options.stratify_by = self.stratify_by.clone();
+        options.per_stratum = self.per_stratum;
+        options.random_key_field = self.random_key_field.clone();
+        options.allow_disk_use = self.allow_disk_use;
+        options.max_time = self.max_time_ms.map(Duration::from_millis);
+        options.batch_size = self.batch_size;
+        options.retries = self.retries;
+        options.throttle = self.throttle;
+        options.strategy = match self.strategy {
+            StrategyArg::Auto => Strategy::Auto,
+            StrategyArg::Pipeline => Strategy::Pipeline,
+            StrategyArg::Client => Strategy::Client,
+        };
+        options
+    }
+}
+
+/// Options for particular --emit backends
+#[derive(Args, Debug)]
+struct CodegenArgs {
+    /// The SQL dialect for --emit sql
+    #[arg(long, value_enum, default_value_t = DialectArg::Postgres)]
+    dialect: DialectArg,
+
+    /// Treat fields present in at least this percentage of their parent objects as required,
+    /// rather than optional or nullable, in what --emit generates and --apply-validator
+    /// installs. At 100 only fields that were never missing are
+    #[arg(long, value_name = "PERCENT", value_parser = parse_percent, default_value_t = 100.0)]
+    required_presence: f64,
+
+    /// How --emit types fields seen with several types that don't widen into one, in the
+    /// backends whose languages have unions
+    #[arg(long, value_enum, default_value_t = UnionsArg::Union)]
+    unions: UnionsArg,
+
+    /// In --emit rust, type fields that were null apart from fields that were missing: an
+    /// Option serialized as null, an Option left out when None, or an Option<Option<_>> for
+    /// fields that were both
+    #[arg(long)]
+    separate_nulls: bool,
+
+    /// In --emit rust, also emit a test deserializing a document put together from the
+    /// --examples collected into the document struct. It needs serde_json
+    #[arg(long, requires = "examples")]
+    emit_tests: bool,
+
+    /// Comment each field --emit generates with what was observed of it: its presence and
+    /// types, and with full its --examples too
+    #[arg(long, value_enum, default_value_t = AnnotateArg::None)]
+    annotate: AnnotateArg,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum AnnotateArg {
+    None,
+    Brief,
+    Full,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum UnionsArg {
+    /// A Rust enum, or a TypeScript, Python, Avro or OpenAPI union
+    Union,
+    /// The language's type for any value, e.g. bson::Bson or unknown
+    Any,
+    /// The type most documents hold
+    Dominant,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum DialectArg {
+    Postgres,
+    Mysql,
+    Sqlite,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum RedactArg {
+    /// Replace them with a prefix of their SHA-256
+    Hash,
+    /// Keep the first few characters of strings and the length of binary data
+    Truncate,
+    /// Leave them out
+    Omit,
+}
+
+fn redaction(redact: Option<RedactArg>) -> Redaction {
+    match redact {
+        None => Redaction::None,
+        Some(RedactArg::Hash) => Redaction::Hash,
+        Some(RedactArg::Truncate) => Redaction::Truncate,
+        Some(RedactArg::Omit) => Redaction::Omit,
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TypeModeArg {
+    /// BSON's own type names, as $type reports them
+    Strict,
+    /// JSON Schema-like names: number for every numeric type
+    Canonical,
+}
+
+fn type_mode(mode: TypeModeArg) -> TypeMode {
+    match mode {
+        TypeModeArg::Strict => TypeMode::Strict,
+        TypeModeArg::Canonical => TypeMode::Canonical,
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum HistogramArg {
+    EqualWidth,
+    Log,
+}
+
+fn histogram_scale(scale: HistogramArg) -> HistogramScale {
+    match scale {
+        HistogramArg::EqualWidth => HistogramScale::EqualWidth,
+        HistogramArg::Log => HistogramScale::Log,
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum TimeBucketArg {
+    Day,
+    Month,
+    Year,
+}
+
+fn time_bucket(bucket: TimeBucketArg) -> TimeBucket {
+    match bucket {
+        TimeBucketArg::Day => TimeBucket::Day,
+        TimeBucketArg::Month => TimeBucket::Month,
+        TimeBucketArg::Year => TimeBucket::Year,
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum FailOnArg {
+    /// Anomalies or rules' findings of warning or error severity, exiting with 7
+    Warn,
+    /// Anomalies or rules' findings of error severity, exiting with 7
+    Error,
+    /// Fields that look like personal data, with --detect-pii, exiting with 10
+    Pii,
+    /// Fields rarer than --rare-field-threshold, exiting with 11
+    RareFields,
+    /// Drift from --check-baseline or --check-validator, exiting with 1
+    Drift,
+    /// Some of several collections failing to be analyzed, exiting with 9
+    Partial,
+    /// Nothing: only errors fail the run
+    None,
+}
+
+/// Which findings fail the run, from --fail-on
+#[derive(Debug, Clone, Copy)]
+struct FailPolicy {
+    /// The least severe anomaly that fails the run
+    anomalies: Option<Severity>,
+    pii: bool,
+    rare_fields: bool,
+    drift: bool,
+    partial: bool,
+}
+
+impl FailPolicy {
+    fn new(fail_on: &[FailOnArg]) -> Self {
+        if fail_on.is_empty() {
+            return FailPolicy {
+                anomalies: None,
+                pii: false,
+                rare_fields: false,
+                drift: true,
+                partial: true,
+            };
+        }
+        FailPolicy {
+            anomalies: if fail_on.contains(&FailOnArg::Warn) {
+                Some(Severity::Warn)
+            } else if fail_on.contains(&FailOnArg::Error) {
+                Some(Severity::Error)
+            } else {
+                None
+            },
+            pii: fail_on.contains(&FailOnArg::Pii),
+            rare_fields: fail_on.contains(&FailOnArg::RareFields),
+            drift: fail_on.contains(&FailOnArg::Drift),
+            partial: fail_on.contains(&FailOnArg::Partial),
+        }
+    }
+
+    /// Failure if the baseline or validator check found `drift` and drift is fatal
+    fn drift_exit_code(&self, drift: &SchemaDiff) -> ExitCode {
+        if self.drift && !drift.is_empty() {
+            ExitCode::from(EXIT_DRIFT)
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+
+    /// Failure if a fatal finding turned up in `report`: a collection that failed, then the
+    /// findings of [`FailPolicy::schemas_exit_code`]
+    fn report_exit_code(&self, report: &SchemaReport) -> ExitCode {
+        if self.partial && !report.failures.is_empty() {
+            ExitCode::from(EXIT_PARTIAL)
+        } else {
+            self.schemas_exit_code(report.namespaces.values())
+        }
+    }
+
+    /// Failure if any of the schemas has an anomaly or a rule's finding at least as severe as
+    /// the policy's, or with `pii`, a field that looks like personal data, or with
+    /// `rare_fields`, a rare field
+    fn schemas_exit_code<'a>(
+        &self,
+        schemas: impl IntoIterator<Item = &'a CollectionSchema>,
+    ) -> ExitCode {
+        let schemas = schemas.into_iter().collect::<Vec<_>>();
+        let anomalies = self.anomalies.is_some_and(|threshold| {
+            schemas
+                .iter()
+                .flat_map(|schema| {
+                    let anomalies = schema.anomalies.iter().map(|anomaly| anomaly.severity);
+                    anomalies.chain(schema.findings.iter().map(|finding| finding.severity))
+                })
+                .any(|severity| severity >= threshold)
+        });
+        let pii = self.pii
+            && schemas.iter().any(|schema| {
+                schema
+                    .privacy
+                    .as_ref()
+                    .is_some_and(|found| !found.is_empty())
+            });
+        let rare_fields =
+            self.rare_fields && schemas.iter().any(|schema| !schema.rare_fields.is_empty());
+        if anomalies {
+            ExitCode::from(EXIT_ANOMALIES)
+        } else if pii {
+            ExitCode::from(EXIT_PII)
+        } else if rare_fields {
+            ExitCode::from(EXIT_RARE_FIELDS)
+        } else {
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Format {
+    /// Human-readable text
+    Text,
+    /// Pretty-printed JSON
+    Json,
+    /// The same fields as json, as YAML
+    Yaml,
+    /// Markdown tables, for wikis and pull requests
+    Markdown,
+    /// A standalone HTML page, with nested fields collapsible and type distributions drawn
+    /// as bars
+    Html,
+    /// One comma-separated row per field and type, for spreadsheets and BI tools
+    Csv,
+    /// Like csv, separated by tabs
+    Tsv,
+}
+
+impl Format {
+    /// `format` if given, or else the format the extension of `out` names, or else text
+    fn resolve(format: Option<Format>, out: Option<&Path>) -> Format {
+        format
+            .or_else(|| {
+                let extension = out?.extension()?.to_str()?.to_ascii_lowercase();
+                Some(match extension.as_str() {
+                    "json" => Format::Json,
+                    "yaml" | "yml" => Format::Yaml,
+                    "md" | "markdown" => Format::Markdown,
+                    "html" | "htm" => Format::Html,
+                    "csv" => Format::Csv,
+                    "tsv" => Format::Tsv,
+                    _ => return None,
+                })
+            })
+            .unwrap_or(Format::Text)
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Emit {
+    /// A $jsonSchema validator document
+    Validator,
+    /// Rust structs deriving serde's Serialize and Deserialize
+    Rust,
+    /// TypeScript interfaces using the Node driver's BSON types
+    Typescript,
+    /// An Avro record schema, e.g. for a Kafka schema registry
+    Avro,
+    /// Protocol Buffers (proto3) messages
+    Proto,
+    /// GraphQL object types in SDL
+    Graphql,
+    /// A CREATE TABLE statement in the --dialect's SQL
+    Sql,
+    /// A Node module defining Mongoose schemas and the collection's model
+    Mongoose,
+    /// Python Pydantic v2 models
+    Pydantic,
+    /// Kotlin data classes for the Kotlin driver's data class codec
+    Kotlin,
+    /// Java POJOs annotated for the Java driver's POJO codec
+    Java,
+    /// Go structs with bson tags for the Go driver
+    Go,
+    /// C# classes with the .NET driver's mapping attributes
+    Csharp,
+    /// OpenAPI 3.1 components.schemas, as YAML with --format yaml and JSON otherwise
+    Openapi,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum Input {
+    /// A raw .bson file of one collection, as written by mongodump or bsondump
+    Bson,
+    /// An uncompressed mongodump --archive file
+    Archive,
+    /// Newline-delimited Extended JSON, as written by mongoexport
+    Ndjson,
+}
+
+fn parse_percent(percent: &str) -> Result<f64, String> {
+    match percent.parse::<f64>() {
+        Ok(percent) if percent > 0.0 && percent <= 100.0 => Ok(percent),
+        _ => Err(format!(
+            "expected a percentage above 0 and up to 100, got {:?}",
+            percent
+        )),
+    }
+}
+
+fn parse_filter(filter: &str) -> Result<Document, String> {
+    let json = serde_json::from_str::<serde_json::Value>(filter).map_err(|e| e.to_string())?;
+    match Bson::try_from(json).map_err(|e| e.to_string())? {
+        Bson::Document(filter) => Ok(filter),
+        _ => Err("expected a JSON object".to_string()),
+    }
+}
+
+fn parse_ids(ids: &str) -> Result<Array, String> {
+    let json = serde_json::from_str::<serde_json::Value>(ids).map_err(|e| e.to_string())?;
+    match Bson::try_from(json).map_err(|e| e.to_string())? {
+        Bson::Array(ids) if ids.is_empty() => Err("expected at least one id".to_string()),
+        Bson::Array(ids) => Ok(ids),
+        _ => Err("expected a JSON array".to_string()),
+    }
+}
+
+fn parse_collation(collation: &str) -> Result<Document, String> {
+    let collation = parse_filter(collation)?;
+    bson::from_document::<Collation>(collation.clone()).map_err(|e| e.to_string())?;
+    Ok(collation)
+}
+
+fn parse_hint(hint: &str) -> Result<IndexHint, String> {
+    match hint.trim_start().starts_with('{') {
+        true => parse_filter(hint).map(IndexHint::Keys),
+        false => Ok(IndexHint::Name(hint.to_string())),
+    }
+}
+
+/// Identifies the queries of this run in currentOp and the profiler, through their comment
+fn run_id() -> &'static str {
+    static RUN_ID: OnceLock<String> = OnceLock::new();
+    RUN_ID.get_or_init(|| format!("{:08x}", rand::random::<u32>()))
+}
+
+fn parse_tag_set(tags: &str) -> Result<TagSet, String> {
+    tags.split(',')
+        .filter(|tag| !tag.is_empty())
+        .map(|tag| match tag.split_once(':') {
+            Some((name, value)) => Ok((name.to_string(), value.to_string())),
+            None => Err(format!("expected name:value, got {:?}", tag)),
+        })
+        .collect()
+}
+
+fn parse_namespace(ns: &str) -> Result<Namespace, String> {
+    match ns.split_once('.') {
+        Some((db, coll)) if !db.is_empty() && !coll.is_empty() => Ok(Namespace::new(db, coll)),
+        _ => Err(format!("expected db.collection, got {:?}", ns)),
+    }
+}
+
+/// Exit status when a baseline check finds the schema has drifted
+const EXIT_DRIFT: u8 = 1;
+/// Exit status for a connection string that can't be used. clap also exits with 2 for bad
+/// arguments
+const EXIT_USAGE: u8 = 2;
+/// Exit status when no server could be reached
+const EXIT_CONNECTION: u8 = 3;
+/// Exit status when the server rejected the credentials
+const EXIT_AUTHENTICATION: u8 = 4;
+/// Exit status when the server refused or failed an operation, or answered unexpectedly
+const EXIT_SERVER: u8 = 5;
+/// Exit status when an input or output file couldn't be used
+const EXIT_FILE: u8 = 6;
+/// Exit status when --fail-on warn or error found anomalies of that severity
+const EXIT_ANOMALIES: u8 = 7;
+/// Exit status when validate found documents violating the schema
+const EXIT_INVALID: u8 = 8;
+/// Exit status when some, but not all, of several collections couldn't be analyzed
+const EXIT_PARTIAL: u8 = 9;
+/// Exit status when --fail-on pii found fields that look like personal data
+const EXIT_PII: u8 = 10;
+/// Exit status when --fail-on rare-fields found fields rarer than the threshold
+const EXIT_RARE_FIELDS: u8 = 11;
+
+/// The exit statuses, for --help
+const EXIT_STATUSES: &str = "\
+Exit status:
+  0   success
+  1   the schema drifted from --check-baseline or --check-validator
+  2   bad arguments or connection string
+  3   no server could be reached
+  4   the server rejected the credentials
+  5   the server refused or failed an operation, or answered unexpectedly
+  6   an input or output file couldn't be used
+  7   --fail-on warn or error found anomalies
+  8   validate found documents violating the schema
+  9   some, but not all, of several collections couldn't be analyzed
+  10  --fail-on pii found fields that look like personal data
+  11  --fail-on rare-fields found fields rarer than --rare-field-threshold
+--fail-on chooses which of 1, 7, 9, 10 and 11 apply; by default 1 and 9 do";
 
 #[tokio::main]
-async fn main() -> Result<(), ()> {
-    println!("program entry");
-    let start = std::time::Instant::now();
-    let client_uri = "mongodb+srv://<username>:<password>@<cluster-url>";
-    let client_options = ClientOptions::parse(client_uri).await.unwrap();
-    let client = Client::with_options(client_options).unwrap();
-    let database = client.database("<database>");
-    let collection_name = "<collection>";
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logging(&cli);
+    match run(cli).await {
+        Ok(code) => code,
+        Err(e) => {
+            error!("{}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+/// Log to stderr, so standard output only ever carries results and can be piped
+fn init_logging(cli: &Cli) {
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::INFO,
+        (false, 1) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    let logger = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_target(cli.verbose > 0);
+    match cli.log_format {
+        LogFormat::Text => logger
+            .with_ansi(std::io::IsTerminal::is_terminal(&std::io::stderr()))
+            .init(),
+        LogFormat::Json => logger.json().init(),
+    }
+}
 
-    println!("Initial setup: {:?}", start.elapsed());
+fn exit_code(error: &AnalyzerError) -> u8 {
+    match error {
+        AnalyzerError::InvalidUri(_)
+        | AnalyzerError::InvalidOptions(_)
+        | AnalyzerError::Listen { .. }
+        | AnalyzerError::Terminal(_) => EXIT_USAGE,
+        AnalyzerError::Connection(_) | AnalyzerError::Webhook(_) => EXIT_CONNECTION,
+        #[cfg(feature = "kafka")]
+        AnalyzerError::Kafka(_) => EXIT_CONNECTION,
+        AnalyzerError::Authentication(_) => EXIT_AUTHENTICATION,
+        AnalyzerError::Server(_) | AnalyzerError::MalformedResult(_) => EXIT_SERVER,
+        AnalyzerError::File { .. } => EXIT_FILE,
+    }
+}
 
-    let document_count = database
-        .collection::<Document>(collection_name)
-        .estimated_document_count(None)
-        .await?;
+async fn run(cli: Cli) -> Result<ExitCode> {
+    let config = load_config(cli.config.as_deref())?;
+    Ok(match cli.command {
+        Command::Analyze(args) => analyze(*args, &config).await?,
+        Command::AnalyzeFile(args) => analyze_file(args, &config)?,
+        Command::Diff(args) => {
+            diff(args, &config).await?;
+            ExitCode::SUCCESS
+        }
+        Command::CompareClusters(args) => {
+            compare_clusters(args, &config).await?;
+            ExitCode::SUCCESS
+        }
+        Command::Watch(args) => {
+            watch(args, &config).await?;
+            ExitCode::SUCCESS
+        }
+        Command::Validate(args) => validate(args, &config).await?,
+        Command::Bench(args) => {
+            bench(args, &config).await?;
+            ExitCode::SUCCESS
+        }
+        Command::Serve(args) => {
+            serve(args, config).await?;
+            ExitCode::SUCCESS
+        }
+        Command::Convert(args) => {
+            convert(args)?;
+            ExitCode::SUCCESS
+        }
+        Command::Generate(args) => {
+            generate(args, &config).await?;
+            ExitCode::SUCCESS
+        }
+    })
+}
+
+/// Load the config file given on the command line, or the default one if it exists
+fn load_config(path: Option<&Path>) -> Result<Config> {
+    let path = match path {
+        Some(path) => path,
+        None if Path::new(Config::FILE_NAME).exists() => Path::new(Config::FILE_NAME),
+        None => return Ok(Config::default()),
+    };
+    Config::load(path).map_err(|e| file_error(path, e))
+}
+
+/// Connect with the connection string from the command line or environment, or else the
+/// config file, and the connection flags. A read preference given on the command line
+/// overrides the connection string's for every operation, listing collections included
+async fn connect(
+    uri: Option<&str>,
+    config: &Config,
+    connection: &ConnectionArgs,
+    read: &ReadArgs,
+) -> Result<Client> {
+    Client::with_options(client_options(uri, config, connection, read).await?)
+        .map_err(AnalyzerError::InvalidUri)
+}
+
+async fn client_options(
+    uri: Option<&str>,
+    config: &Config,
+    connection: &ConnectionArgs,
+    read: &ReadArgs,
+) -> Result<ClientOptions> {
+    let Some(uri) = uri.or(config.uri.as_deref()) else {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "no connection string: pass --uri, set MONGODB_URI or add uri to the config file",
+            )
+            .exit();
+    };
+    let mut client_options = ClientOptions::parse(uri)
+        .await
+        .map_err(AnalyzerError::InvalidUri)?;
+    identify_client(&mut client_options, connection.app_name.as_deref());
+    connection.apply(&mut client_options)?;
+    if let Some(read_preference) = read.read_preference()? {
+        client_options.selection_criteria =
+            Some(SelectionCriteria::ReadPreference(read_preference));
+    }
+    Ok(client_options)
+}
 
-    let default_sample_size = 10000;
+async fn analyze(args: AnalyzeArgs, config: &Config) -> Result<ExitCode> {
+    let format = Format::resolve(args.format, args.out.as_deref());
+    let client = connect(args.uri.as_deref(), config, &args.connection, &args.read).await?;
 
-    // sample size is the max of the default sample size or 1/3 the document count
-    // it seems scientific enough
-    let sample_size =
-        f64::max(default_sample_size as f64, document_count as f64 / 3.0).round() as i64;
+    if let Some(schedule) = args.schedule.clone() {
+        return analyze_on_schedule(args, &schedule, &client, config).await;
+    }
 
-    let pipeline = vec![
-        // start with an adequate sample of the collection
-        doc! {
-            "$sample": {
-                "size": bson::Bson::Int64(sample_size)
+    let bars = ProgressBars::new();
+    let cache = args.cache.cache(args.uri.as_deref(), config);
+    let options_for = |ns: &Namespace| {
+        let mut options = args.sampling.options_for(config, ns);
+        options.progress = Some(bars.progress_fn());
+        options.cache = cache.clone();
+        options
+    };
+    let filter = NamespaceFilter {
+        include: args.include_ns,
+        exclude: args.exclude_ns,
+    };
+    let (db, collection) = match (args.db, args.collection) {
+        (Some(db), Some(collection)) => (db, collection),
+        (db, _) => {
+            let namespaces = match db {
+                Some(db) => {
+                    let mut namespaces = collections(&client, &db).await?;
+                    namespaces.retain(|ns| filter.matches(ns));
+                    namespaces
+                }
+                None => cluster_namespaces(&client, &filter).await?,
+            };
+            let mut report = SchemaReport::analyze_namespaces_concurrently(
+                &client,
+                namespaces,
+                args.concurrency as usize,
+                options_for,
+            )
+            .await?;
+            bars.finish();
+            for (ns, error) in &report.failures {
+                error!(namespace = %ns, "{}", error);
             }
-        },
-        // convert the document to an array of key-value pairs. The key is the field name, the value is the type
-        // of the field
-        doc! {
-            "$project": {
-                "_id": 0,
-                "schema": {
-                    "$map": {
-                        "input": {
-                            "$objectToArray": "$$ROOT"
-                        },
-                        "as": "field",
-                        "in": {
-                            "k": "$$field.k",
-                            "v": { "$type": "$$field.v" }
-                        }
+            if args.relationships {
+                report.infer_relationships(&client).await?;
+            }
+            if args.indexes {
+                for (namespace, schema) in &mut report.namespaces {
+                    let Ok(ns) = parse_namespace(namespace) else {
+                        continue;
+                    };
+                    let options = options_for(&ns);
+                    let analyzer = SchemaAnalyzer::with_options(client.clone(), ns, options);
+                    // like a failed analysis, one namespace's indexes don't stop the others'
+                    match analyzer.index_report(schema).await {
+                        Ok(indexes) => schema.indexes = Some(indexes),
+                        Err(e) => error!(namespace = %namespace, "could not report indexes: {}", e),
                     }
                 }
             }
-        },
-        // group the documents, getting the keys and the schemas
-        doc! {
-            "$group": {
-                "_id": null,
-                "keys": {
-                    "$addToSet": "$schema.k"
-                },
-                "schema": {
-                    "$addToSet": "$schema"
+            check_rules(config, report.namespaces.values_mut());
+            if let Some(results_ns) = &args.store_results {
+                let results = client.database(&results_ns.db).collection(&results_ns.coll);
+                for schema in report.namespaces.values() {
+                    schema.store(&results).await?;
                 }
+                info!(
+                    "Stored {} schemas in {}",
+                    report.namespaces.len(),
+                    results_ns
+                );
             }
-        },
-        // reduce the keys into a single array
-        doc! {
-            "$project": {
-                "_id": 0,
-                "keys": {
-                    "$reduce": {
-                        "input": "$keys",
-                        "initialValue": [],
-                        "in": {
-                            "$setUnion": ["$$value", "$$this"]
-                        }
-                    }
-                },
-                "schema": 1
+            if args.tui {
+                browse(report.namespaces.values()).map_err(AnalyzerError::Terminal)?;
+            } else if args.anonymize {
+                write_report(
+                    args.out.as_deref(),
+                    &report.anonymized(),
+                    &args.codegen,
+                    format,
+                )?;
+            } else {
+                write_report(args.out.as_deref(), &report, &args.codegen, format)?;
             }
-        },
-        // unwind the schema array
-        doc! {
-            "$unwind": "$schema"
-        },
-        // figure out which keys are missing from the schema. Insert them with the value "missing"
-        doc! {
-            "$project": {
-                "schema": {
-                    "$reduce": {
-                        "input": {
-                            "$setDifference": ["$keys", "$schema.k"]
-                        },
-                        "initialValue": "$schema",
-                        "in": {
-                            "$concatArrays": ["$$value", [{
-                                "k": "$$this",
-                                "v": "missing"
-                            }]]
-                        }
-                    }
-                }
+            return Ok(FailPolicy::new(&args.fail_on).report_exit_code(&report));
+        }
+    };
+
+    let ns = Namespace::new(db, collection);
+    let analyzer = SchemaAnalyzer::with_options(client.clone(), ns.clone(), options_for(&ns));
+    let out = args
+        .out
+        .as_deref()
+        .map(|out| namespace_path(out, &ns.to_string()));
+    if args.dry_run {
+        let command = Bson::Document(analyzer.dry_run().await?).into_relaxed_extjson();
+        let json = serde_json::to_string_pretty(&command).unwrap() + "\n";
+        write_output(out.as_deref(), &json)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if args.estimate_cost {
+        let estimate = analyzer.estimate_cost().await?;
+        let json = serde_json::to_string_pretty(&estimate).unwrap() + "\n";
+        write_output(out.as_deref(), &json)?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    let mut schema = analyzer.analyze().await?;
+    if args.per_shard {
+        let options =
+            client_options(args.uri.as_deref(), config, &args.connection, &args.read).await?;
+        schema.shards = Some(analyzer.analyze_shards(&options).await?);
+    }
+    if args.indexes {
+        schema.indexes = Some(analyzer.index_report(&schema).await?);
+    }
+    if args.explain {
+        let explained = analyzer.explain().await?;
+        if let Some(meta) = &mut schema.meta {
+            meta.explain = Some(explained);
+        }
+    }
+    bars.finish();
+    check_rules(config, [&mut schema]);
+
+    if let Some(results) = &args.store_results {
+        schema
+            .store(&client.database(&results.db).collection(&results.coll))
+            .await?;
+        info!("Stored the schema in {}", results);
+    }
+    if let Some(path) = &args.write_baseline {
+        schema
+            .write_baseline(path)
+            .map_err(|e| file_error(path, e))?;
+        info!("Wrote baseline to {}", path.display());
+    }
+    if args.check_validator {
+        let validator = fetch_validator(&client.database(&ns.db), &ns.coll).await?;
+        if validator.is_none() {
+            warn!("{} has no $jsonSchema validator", ns);
+        }
+        let drift = schema.validator_diff(&validator.unwrap_or_default());
+        write_output(out.as_deref(), &render_diff(&drift, format))?;
+        return Ok(FailPolicy::new(&args.fail_on).drift_exit_code(&drift));
+    }
+    if let Some(path) = &args.check_baseline {
+        let drift = schema
+            .check_baseline(path)
+            .map_err(|e| file_error(path, e))?;
+        write_output(out.as_deref(), &render_diff(&drift, format))?;
+        return Ok(FailPolicy::new(&args.fail_on).drift_exit_code(&drift));
+    }
+
+    if args.tui {
+        browse([&schema]).map_err(AnalyzerError::Terminal)?;
+    } else {
+        let anonymized;
+        let shown = if args.anonymize {
+            anonymized = schema.anonymized();
+            &anonymized
+        } else {
+            &schema
+        };
+        let out = args
+            .out
+            .as_deref()
+            .map(|out| namespace_path(out, &shown.namespace));
+        write_output(
+            out.as_deref(),
+            &render_schema(shown, args.emit, &args.codegen, format),
+        )?;
+    }
+    if args.apply_validator {
+        let validator = schema.to_validator(args.codegen.required_presence / 100.0);
+        apply_validator(&client.database(&ns.db), &ns.coll, validator).await?;
+        info!("Applied validator to {}", ns);
+    }
+    Ok(FailPolicy::new(&args.fail_on).schemas_exit_code([&schema]))
+}
+
+/// Analyze the namespaces of `args` every time `schedule` comes round, reporting how each
+/// schema changed since the run before. Failed runs are reported and retried at the next
+async fn analyze_on_schedule(
+    args: AnalyzeArgs,
+    schedule: &Schedule,
+    client: &Client,
+    config: &Config,
+) -> Result<ExitCode> {
+    let filter = NamespaceFilter {
+        include: args.include_ns.clone(),
+        exclude: args.exclude_ns.clone(),
+    };
+    let results = args
+        .store_results
+        .as_ref()
+        .map(|ns| client.database(&ns.db).collection::<Document>(&ns.coll));
+    let sinks = ChangeSinks {
+        webhook: webhook(
+            args.notify_webhook.as_deref(),
+            args.webhook_template.as_deref(),
+        )?,
+        #[cfg(feature = "kafka")]
+        kafka: kafka_sink(&args.kafka_brokers, args.kafka_topic.as_deref())?,
+    };
+    let mut previous = std::collections::BTreeMap::<String, CollectionSchema>::new();
+    loop {
+        let now = bson::DateTime::now();
+        let Some(next) = schedule.next_after(now) else {
+            warn!("{} never comes round again", schedule);
+            return Ok(ExitCode::SUCCESS);
+        };
+        info!("Next run at {}", next);
+        let wait = next.timestamp_millis() - now.timestamp_millis();
+        tokio::time::sleep(Duration::from_millis(wait.max(0) as u64)).await;
+
+        let run = scheduled_run(
+            &args,
+            client,
+            config,
+            &filter,
+            results.as_ref(),
+            &previous,
+            &sinks,
+        );
+        match run.await {
+            Ok(report) => {
+                // a namespace that failed is compared against its last good run next time
+                previous.retain(|ns, _| report.failures.contains_key(ns));
+                previous.extend(report.namespaces);
             }
-        },
-        // group the documents again, converting the schemas back to objects and only keeping unique ones
-        // why convert them back to objects? In testing, it seems to be faster
-        {
-            doc! {
-                "$group": {
-                    "_id": null,
-                    "schema": {
-                        "$addToSet":
-                        {
-                            "$arrayToObject": "$schema"
-                        }
-                    }
-                }
+            Err(e) => error!("{}", e),
+        }
+    }
+}
+
+/// Where scheduled runs send the changes they find, besides the output
+struct ChangeSinks {
+    webhook: Option<Webhook>,
+    #[cfg(feature = "kafka")]
+    kafka: Option<KafkaSink>,
+}
+
+/// One run of [`analyze_on_schedule`]: analyze, store, and report the differences from
+/// `previous`
+async fn scheduled_run(
+    args: &AnalyzeArgs,
+    client: &Client,
+    config: &Config,
+    filter: &NamespaceFilter,
+    results: Option<&mongodb::Collection<Document>>,
+    previous: &std::collections::BTreeMap<String, CollectionSchema>,
+    sinks: &ChangeSinks,
+) -> Result<SchemaReport> {
+    let format = Format::resolve(args.format, args.out.as_deref());
+    // collections come and go between runs, so they're listed every time
+    let namespaces = match (&args.db, &args.collection) {
+        (Some(db), Some(collection)) => vec![Namespace::new(db.clone(), collection.clone())],
+        (Some(db), None) => {
+            let mut namespaces = collections(client, db).await?;
+            namespaces.retain(|ns| filter.matches(ns));
+            namespaces
+        }
+        (None, _) => cluster_namespaces(client, filter).await?,
+    };
+    info!("Analyzing {} collections", namespaces.len());
+    let report = SchemaReport::analyze_namespaces_concurrently(
+        client,
+        namespaces,
+        args.concurrency as usize,
+        |ns| args.sampling.options_for(config, ns),
+    )
+    .await?;
+    for (ns, error) in &report.failures {
+        error!(namespace = %ns, "{}", error);
+    }
+
+    for (namespace, schema) in &report.namespaces {
+        let before = match (previous.get(namespace), results) {
+            (Some(before), _) => Some(before.clone()),
+            (None, Some(results)) => CollectionSchema::latest_stored(results, namespace).await?,
+            (None, None) => None,
+        };
+        if let Some(results) = results {
+            schema.store(results).await?;
+        }
+        if let Some(webhook) = &sinks.webhook {
+            notify(webhook, before.as_ref(), schema).await;
+        }
+        let Some(before) = before else {
+            info!(namespace, "first run, {} fields", schema.fields.len());
+            continue;
+        };
+        let diff = before.diff(schema);
+        if diff.is_empty() {
+            info!(namespace, "unchanged");
+            continue;
+        }
+        info!(namespace, "changed since the previous run");
+        let out = args
+            .out
+            .as_deref()
+            .map(|out| namespace_path(out, namespace));
+        write_output(out.as_deref(), &render_diff(&diff, format))?;
+        if let Some(command) = &args.on_change {
+            on_change(command, namespace, &diff).await;
+        }
+        #[cfg(feature = "kafka")]
+        if let Some(kafka) = &sinks.kafka {
+            let kafka = kafka.clone();
+            let changes = SchemaChange::from_diff(&before, schema, &diff);
+            let published = tokio::task::spawn_blocking(move || kafka.publish(&changes)).await;
+            // like a broken hook, an unreachable broker doesn't stop the schedule
+            match published {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => warn!(namespace, "could not publish the changes: {}", e),
+                Err(e) => warn!(namespace, "could not publish the changes: {}", e),
             }
-        },
-        // unwind the schema array
-        doc! {
-            "$unwind": "$schema"
-        },
-        // project the schema object back to an array
-        doc! {
-            "$project": {
-                "_id": 0,
-                "schema": {
-                    "$objectToArray": "$schema"
-                }
+        }
+    }
+    Ok(report)
+}
+
+/// Post the drift from `before` to `after`, and the anomalies new since (all of them on a
+/// first run), if there are any. Failures are reported and otherwise ignored
+async fn notify(webhook: &Webhook, before: Option<&CollectionSchema>, after: &CollectionSchema) {
+    let (diff, known) = match before {
+        Some(before) => (before.diff(after), &before.anomalies[..]),
+        None => (SchemaDiff::default(), &[][..]),
+    };
+    // an anomaly whose figures moved, like a field missing more often, isn't new
+    let anomalies = after
+        .anomalies
+        .iter()
+        .filter(|anomaly| {
+            !known.iter().any(|old| {
+                old.path == anomaly.path
+                    && std::mem::discriminant(&old.kind) == std::mem::discriminant(&anomaly.kind)
+            })
+        })
+        .cloned()
+        .collect();
+    let notification = DriftNotification::new(&after.namespace, diff, anomalies);
+    if notification.is_empty() {
+        return;
+    }
+    if let Err(e) = webhook.notify(&notification).await {
+        warn!(namespace = after.namespace, "could not notify: {}", e);
+    }
+}
+
+/// The webhook of `--notify-webhook`, if one was given
+fn webhook(url: Option<&str>, template: Option<&Path>) -> Result<Option<Webhook>> {
+    let Some(url) = url else {
+        return Ok(None);
+    };
+    let mut webhook = Webhook::new(url);
+    if let Some(path) = template {
+        let template = std::fs::read_to_string(path).map_err(|e| file_error(path, e))?;
+        webhook = webhook.with_template(template);
+    }
+    Ok(Some(webhook))
+}
+
+/// The producer of `--kafka-brokers`, if they were given
+#[cfg(feature = "kafka")]
+fn kafka_sink(brokers: &[String], topic: Option<&str>) -> Result<Option<KafkaSink>> {
+    match (brokers, topic) {
+        ([], _) | (_, None) => Ok(None),
+        (brokers, Some(topic)) => {
+            info!(
+                "Publishing schema changes to {} on {}",
+                topic,
+                brokers.join(",")
+            );
+            KafkaSink::connect(brokers.to_vec(), topic).map(Some)
+        }
+    }
+}
+
+/// Run `command` in a shell with `diff` on its standard input, reporting but otherwise
+/// ignoring failures so a broken hook doesn't stop the schedule
+async fn on_change(command: &str, namespace: &str, diff: &SchemaDiff) {
+    use tokio::io::AsyncWriteExt;
+
+    let child = tokio::process::Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .env("SCHEMA_NAMESPACE", namespace)
+        .stdin(std::process::Stdio::piped())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(e) => {
+            warn!("could not run {:?}: {}", command, e);
+            return;
+        }
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        let json = serde_json::to_vec(diff).unwrap();
+        // a hook that doesn't read its input closes the pipe early, which is fine
+        let _ = stdin.write_all(&json).await;
+    }
+    match child.wait().await {
+        Ok(status) if !status.success() => warn!("{:?} exited with {}", command, status),
+        Ok(_) => {}
+        Err(e) => warn!("could not run {:?}: {}", command, e),
+    }
+}
+
+fn render_schema(
+    schema: &CollectionSchema,
+    emit: Option<Emit>,
+    codegen: &CodegenArgs,
+    format: Format,
+) -> String {
+    let required = codegen.required_presence / 100.0;
+    let unions = match codegen.unions {
+        UnionsArg::Union => UnionStrategy::Union,
+        UnionsArg::Any => UnionStrategy::Any,
+        UnionsArg::Dominant => UnionStrategy::Dominant,
+    };
+    let annotation = match codegen.annotate {
+        AnnotateArg::None => Annotation::None,
+        AnnotateArg::Brief => Annotation::Brief,
+        AnnotateArg::Full => Annotation::Full,
+    };
+    match (emit, format) {
+        (Some(Emit::Validator), _) => {
+            let json = Bson::Document(schema.to_validator(required)).into_relaxed_extjson();
+            serde_json::to_string_pretty(&json).unwrap() + "\n"
+        }
+        (Some(Emit::Rust), _) => schema.to_rust_with(
+            unions,
+            required,
+            annotation,
+            &RustOptions {
+                separate_nulls: codegen.separate_nulls,
+                tests: codegen.emit_tests,
+            },
+        ),
+        (Some(Emit::Typescript), _) => schema.to_typescript(unions, required, annotation),
+        (Some(Emit::Proto), _) => schema.to_proto(unions, required, annotation),
+        (Some(Emit::Graphql), _) => schema.to_graphql(unions, required, annotation),
+        (Some(Emit::Sql), _) => schema.to_sql(
+            match codegen.dialect {
+                DialectArg::Postgres => SqlDialect::Postgres,
+                DialectArg::Mysql => SqlDialect::Mysql,
+                DialectArg::Sqlite => SqlDialect::Sqlite,
+            },
+            unions,
+            required,
+            annotation,
+        ),
+        (Some(Emit::Mongoose), _) => schema.to_mongoose(unions, required, annotation),
+        (Some(Emit::Pydantic), _) => schema.to_pydantic(unions, required, annotation),
+        (Some(Emit::Kotlin), _) => schema.to_kotlin(unions, required, annotation),
+        (Some(Emit::Java), _) => schema.to_java(unions, required, annotation),
+        (Some(Emit::Go), _) => schema.to_go(unions, required, annotation),
+        (Some(Emit::Csharp), _) => schema.to_csharp(unions, required, annotation),
+        (Some(Emit::Openapi), Format::Yaml) => {
+            serde_yaml::to_string(&schema.to_openapi(unions, required, annotation)).unwrap()
+        }
+        (Some(Emit::Openapi), _) => {
+            serde_json::to_string_pretty(&schema.to_openapi(unions, required, annotation)).unwrap()
+                + "\n"
+        }
+        (Some(Emit::Avro), _) => {
+            serde_json::to_string_pretty(&schema.to_avro(unions, required, annotation)).unwrap()
+                + "\n"
+        }
+        (None, Format::Text) => schema.to_colored_table(),
+        (None, Format::Json) => serde_json::to_string_pretty(schema).unwrap() + "\n",
+        (None, Format::Yaml) => serde_yaml::to_string(schema).unwrap(),
+        (None, Format::Markdown) => schema.to_markdown(),
+        (None, Format::Html) => schema.to_html(),
+        (None, Format::Csv) => schema.to_csv(),
+        (None, Format::Tsv) => schema.to_tsv(),
+    }
+}
+
+fn render_report(report: &SchemaReport, format: Format) -> String {
+    match format {
+        Format::Text => report.to_colored_table(),
+        Format::Json => serde_json::to_string_pretty(report).unwrap() + "\n",
+        Format::Yaml => serde_yaml::to_string(report).unwrap(),
+        Format::Markdown => report.to_markdown(),
+        Format::Html => report.to_html(),
+        Format::Csv => report.to_csv(),
+        Format::Tsv => report.to_tsv(),
+    }
+}
+
+fn render_benchmark(benchmark: &Benchmark, format: Format) -> String {
+    match format {
+        Format::Text => benchmark.to_string(),
+        Format::Json => serde_json::to_string_pretty(benchmark).unwrap() + "\n",
+        Format::Yaml => serde_yaml::to_string(benchmark).unwrap(),
+        Format::Markdown => benchmark.to_markdown(),
+        Format::Html => benchmark.to_html(),
+        Format::Csv => benchmark.to_csv(),
+        Format::Tsv => benchmark.to_tsv(),
+    }
+}
+
+fn render_validation(report: &ValidationReport, format: Format) -> String {
+    match format {
+        Format::Text => report.to_string(),
+        Format::Json => serde_json::to_string_pretty(report).unwrap() + "\n",
+        Format::Yaml => serde_yaml::to_string(report).unwrap(),
+        Format::Markdown => report.to_markdown(),
+        Format::Html => report.to_html(),
+        Format::Csv => report.to_csv(),
+        Format::Tsv => report.to_tsv(),
+    }
+}
+
+fn render_diff(diff: &SchemaDiff, format: Format) -> String {
+    match format {
+        Format::Text => diff.to_string(),
+        Format::Json => serde_json::to_string_pretty(diff).unwrap() + "\n",
+        Format::Yaml => serde_yaml::to_string(diff).unwrap(),
+        Format::Markdown => diff.to_markdown(),
+        Format::Html => diff.to_html(),
+        Format::Csv => diff.to_csv(),
+        Format::Tsv => diff.to_tsv(),
+    }
+}
+
+/// Print `output` to stdout, dropping its colors unless stdout is a terminal, or write it
+/// uncolored to `path`
+fn write_output(path: Option<&Path>, output: &str) -> Result<()> {
+    match path {
+        Some(path) => {
+            let output = anstream::adapter::strip_str(output).to_string();
+            write_atomically(path, &output).map_err(|e| file_error(path, e))
+        }
+        None => {
+            anstream::print!("{}", output);
+            Ok(())
+        }
+    }
+}
+
+/// Write `report` to `out` in one piece, or if `out` is templated, each of its schemas to the
+/// file of its namespace
+fn write_report(
+    out: Option<&Path>,
+    report: &SchemaReport,
+    codegen: &CodegenArgs,
+    format: Format,
+) -> Result<()> {
+    let Some(template) = out.filter(|out| is_template(out)) else {
+        return write_output(out, &render_report(report, format));
+    };
+    for (namespace, schema) in &report.namespaces {
+        let path = namespace_path(template, namespace);
+        write_output(Some(&path), &render_schema(schema, None, codegen, format))?;
+    }
+    info!(
+        "Wrote {} schemas to {}",
+        report.namespaces.len(),
+        template.display()
+    );
+    Ok(())
+}
+
+/// Write `contents` to a temporary file beside `path` and rename it into place, so a failed
+/// write leaves the previous file whole. Directories on the way are created, and paths that
+/// aren't regular files, like /dev/stdout, are written directly
+fn write_atomically(path: &Path, contents: &str) -> std::io::Result<()> {
+    if std::fs::metadata(path).is_ok_and(|metadata| !metadata.is_file()) {
+        return std::fs::write(path, contents);
+    }
+    if let Some(parent) = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+    {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", std::process::id()));
+    let temp = PathBuf::from(temp);
+    std::fs::write(&temp, contents)
+        .and_then(|()| std::fs::rename(&temp, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temp);
+        })
+}
+
+/// Whether `path` has a `{db}` or `{coll}` placeholder
+fn is_template(path: &Path) -> bool {
+    let path = path.to_string_lossy();
+    path.contains("{db}") || path.contains("{coll}")
+}
+
+/// `template` with `{db}` and `{coll}` replaced by the parts of `namespace`
+fn namespace_path(template: &Path, namespace: &str) -> PathBuf {
+    if !is_template(template) {
+        return template.to_path_buf();
+    }
+    let (db, coll) = namespace.split_once('.').unwrap_or((namespace, ""));
+    template
+        .to_string_lossy()
+        .replace("{db}", db)
+        .replace("{coll}", coll)
+        .into()
+}
+
+fn file_error(path: &Path, source: std::io::Error) -> AnalyzerError {
+    AnalyzerError::File {
+        path: path.to_path_buf(),
+        source,
+    }
+}
+
+fn analyze_file(args: AnalyzeFileArgs, config: &Config) -> Result<ExitCode> {
+    let format = Format::resolve(args.format, args.out.as_deref());
+    // files may not say which namespace they hold, so only the config's defaults apply
+    let mut options = AnalysisOptions::default();
+    config.defaults.apply(&mut options);
+    NamespaceSettings {
+        sample_size: args.sample_size,
+        sample_percent: args.sample_percent,
+        ..NamespaceSettings::default()
+    }
+    .apply(&mut options);
+    args.inference.apply(&mut options);
+    let bars = ProgressBars::new();
+    let path = &args.path;
+    let stdin = path.as_os_str() == "-";
+    let input = args
+        .input
+        .unwrap_or_else(|| match path.extension().and_then(|ext| ext.to_str()) {
+            Some("bson") => Input::Bson,
+            Some("json" | "jsonl" | "ndjson") => Input::Ndjson,
+            _ if stdin => Input::Ndjson,
+            _ => Input::Archive,
+        });
+    if stdin && input != Input::Ndjson {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "only newline-delimited JSON can be read from standard input",
+            )
+            .exit();
+    }
+    if input == Input::Archive && args.emit.is_some() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--emit needs a single collection, not an archive",
+            )
+            .exit();
+    }
+
+    // an archive interleaves its collections, so there's no one count to show
+    if input != Input::Archive {
+        options.progress = Some(bars.progress_fn());
+    }
+
+    let mut schema = match input {
+        Input::Bson => CollectionSchema::from_bson_file(path, &options),
+        Input::Ndjson => {
+            // mongoexport output doesn't record the database, so name it after the file
+            let result = if stdin {
+                CollectionSchema::from_ndjson(std::io::stdin().lock(), "stdin", &options)
+            } else {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                std::fs::File::open(path).and_then(|file| {
+                    CollectionSchema::from_ndjson(std::io::BufReader::new(file), name, &options)
+                })
+            };
+            result
+        }
+        Input::Archive => {
+            let mut report =
+                SchemaReport::from_archive(path, &options).map_err(|e| file_error(path, e))?;
+            check_rules(config, report.namespaces.values_mut());
+            if args.tui {
+                browse(report.namespaces.values()).map_err(AnalyzerError::Terminal)?;
+            } else if args.anonymize {
+                write_report(
+                    args.out.as_deref(),
+                    &report.anonymized(),
+                    &args.codegen,
+                    format,
+                )?;
+            } else {
+                write_report(args.out.as_deref(), &report, &args.codegen, format)?;
             }
-        },
-        // unwind the schema array. We now have a document for each field and type
-        doc! {
-            "$unwind": "$schema"
-        },
-        // group by the key, adding unique values to the types array
-        doc! {
-            "$group": {
-                "_id": "$schema.k",
-                "types": {
-                    "$addToSet": "$schema.v"
+            return Ok(FailPolicy::new(&args.fail_on).report_exit_code(&report));
+        }
+    }
+    .map_err(|e| file_error(path, e))?;
+    bars.finish();
+    check_rules(config, [&mut schema]);
+    if args.tui {
+        browse([&schema]).map_err(AnalyzerError::Terminal)?;
+    } else {
+        let anonymized;
+        let shown = if args.anonymize {
+            anonymized = schema.anonymized();
+            &anonymized
+        } else {
+            &schema
+        };
+        let out = args
+            .out
+            .as_deref()
+            .map(|out| namespace_path(out, &shown.namespace));
+        write_output(
+            out.as_deref(),
+            &render_schema(shown, args.emit, &args.codegen, format),
+        )?;
+    }
+    Ok(FailPolicy::new(&args.fail_on).schemas_exit_code([&schema]))
+}
+
+/// Record what the config file's rules find in each schema, if it has any
+fn check_rules<'a>(config: &Config, schemas: impl IntoIterator<Item = &'a mut CollectionSchema>) {
+    let Some(rules) = &config.rules else {
+        return;
+    };
+    let rules = rules.rules();
+    for schema in schemas {
+        schema.findings = schema.check_rules(&rules);
+    }
+}
+
+/// Progress bars on stderr for collections done and documents read, hidden unless stderr is a
+/// terminal
+struct ProgressBars {
+    multi: MultiProgress,
+    collections: ProgressBar,
+    documents: ProgressBar,
+    /// What the documents bar is currently showing, so its style only changes when that does
+    showing: Mutex<Option<Showing>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Showing {
+    Aggregating,
+    Documents { expected: Option<u64> },
+}
+
+impl ProgressBars {
+    fn new() -> Arc<Self> {
+        // --quiet hides progress along with everything else short of errors
+        let multi = if tracing::enabled!(Level::INFO) {
+            MultiProgress::new()
+        } else {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        };
+        Arc::new(Self {
+            multi,
+            collections: ProgressBar::hidden(),
+            documents: ProgressBar::hidden(),
+            showing: Mutex::new(None),
+        })
+    }
+
+    fn progress_fn(self: &Arc<Self>) -> ProgressFn {
+        let bars = self.clone();
+        ProgressFn::new(move |progress| bars.update(progress))
+    }
+
+    fn update(&self, progress: Progress<'_>) {
+        match progress {
+            Progress::Collection {
+                namespace,
+                index,
+                total,
+            } => {
+                if self.collections.length().is_none() {
+                    self.collections.set_style(
+                        ProgressStyle::with_template(
+                            "{pos}/{len} collections [{bar:30}] ETA {eta} {msg}",
+                        )
+                        .unwrap(),
+                    );
+                    self.collections.set_length(total as u64);
+                    self.multi.insert(0, self.collections.clone());
                 }
+                self.collections.set_position(index as u64);
+                self.collections.set_message(namespace.to_string());
+                // restarts the elapsed time and rate for the next collection
+                self.documents.reset();
             }
-        },
-        // group the groups into a single document
-        doc! {
-            "$group": {
-                "_id": null,
-                "schema": {
-                    "$addToSet": {
-                        "field": "$_id",
-                        "types": "$types"
-                    }
-                }
+            Progress::Aggregating => {
+                self.show(Showing::Aggregating);
             }
-        },
-    ];
+            Progress::Documents { read, expected } => {
+                self.show(Showing::Documents { expected });
+                self.documents.set_position(read);
+            }
+            Progress::Retrying {
+                attempt,
+                delay,
+                error,
+            } => {
+                self.multi
+                    .suspend(|| warn!("{}; retrying in {:?} (attempt {})", error, delay, attempt));
+            }
+        }
+    }
+
+    fn show(&self, showing: Showing) {
+        let mut current = self.showing.lock().unwrap();
+        if *current == Some(showing) {
+            return;
+        }
+        let template = match showing {
+            Showing::Aggregating => "{spinner} running the sampling aggregation {elapsed}",
+            Showing::Documents { expected: Some(_) } => {
+                "{human_pos}/{human_len} documents [{bar:30}] ETA {eta}"
+            }
+            Showing::Documents { expected: None } => "{spinner} {human_pos} documents {elapsed}",
+        };
+        self.documents.reset();
+        self.documents
+            .set_style(ProgressStyle::with_template(template).unwrap());
+        if let Showing::Documents {
+            expected: Some(expected),
+        } = showing
+        {
+            self.documents.set_length(expected);
+        }
+        if current.is_none() {
+            self.multi.add(self.documents.clone());
+        }
+        self.documents
+            .enable_steady_tick(Duration::from_millis(100));
+        *current = Some(showing);
+    }
 
-    #[derive(Hash, Eq, PartialEq, Debug)]
-    struct Schema {
-        field: String,
-        types: Vec<String>,
+    fn finish(&self) {
+        self.collections.finish_and_clear();
+        self.documents.finish_and_clear();
     }
+}
+
+async fn diff(args: DiffArgs, config: &Config) -> Result<()> {
+    let format = Format::resolve(args.format, args.out.as_deref());
+    // both sides are sampled alike, or settings would show up as differences
+    let options = args.sampling.options_for(config, &args.old);
+    let old_client = connect(args.uri.as_deref(), config, &args.connection, &args.read).await?;
+    let new_client = match &args.new_uri {
+        Some(uri) => connect(Some(uri), config, &args.connection, &args.read).await?,
+        None => old_client.clone(),
+    };
+
+    let old_cache = args.cache.cache(args.uri.as_deref(), config);
+    let new_cache = match &args.new_uri {
+        Some(uri) => args.cache.cache(Some(uri), config),
+        None => old_cache.clone(),
+    };
 
-    let mut schema = HashMap::<String, Vec<String>>::new();
+    let old = SchemaAnalyzer::with_options(old_client, args.old, options.clone())
+        .cache(old_cache)
+        .analyze()
+        .await?;
+    let new = SchemaAnalyzer::with_options(new_client, args.new, options)
+        .cache(new_cache)
+        .analyze()
+        .await?;
+
+    let diff = old.diff(&new);
+    write_output(args.out.as_deref(), &render_diff(&diff, format))?;
+    Ok(())
+}
+
+async fn compare_clusters(args: CompareClustersArgs, config: &Config) -> Result<()> {
+    let format = Format::resolve(args.format, args.out.as_deref());
+    let left_client = connect(
+        args.left_uri.as_deref(),
+        config,
+        &args.connection,
+        &args.read,
+    )
+    .await?;
+    let right_client = connect(Some(&args.right_uri), config, &args.connection, &args.read).await?;
+    let filter = NamespaceFilter {
+        include: args.include_ns,
+        exclude: args.exclude_ns,
+    };
+    info!("Comparing {} with {}", args.left_name, args.right_name);
+    // both sides are sampled alike, or settings would show up as differences
+    let comparison = ClusterComparison::analyze(
+        (&args.left_name, &left_client),
+        (&args.right_name, &right_client),
+        args.db.as_deref(),
+        &filter,
+        args.concurrency as usize,
+        |ns| args.sampling.options_for(config, ns),
+    )
+    .await?;
+
+    let output = match format {
+        Format::Text => comparison.to_string(),
+        Format::Json => serde_json::to_string_pretty(&comparison).unwrap() + "\n",
+        Format::Yaml => serde_yaml::to_string(&comparison).unwrap(),
+        Format::Markdown => comparison.to_markdown(),
+        Format::Html => comparison.to_html(),
+        Format::Csv => comparison.to_csv(),
+        Format::Tsv => comparison.to_tsv(),
+    };
+    write_output(args.out.as_deref(), &output)
+}
 
-    let pre_query = start.elapsed();
+async fn watch(args: WatchArgs, config: &Config) -> Result<()> {
+    let client = connect(args.uri.as_deref(), config, &args.connection, &args.read).await?;
+    let ns = Namespace::new(args.db, args.collection);
+    let options = args.sampling.options_for(config, &ns);
+    let analyzer = SchemaAnalyzer::with_options(client, ns.clone(), options);
+    let webhook = webhook(
+        args.notify_webhook.as_deref(),
+        args.webhook_template.as_deref(),
+    )?;
+    #[cfg(feature = "kafka")]
+    let kafka = kafka_sink(&args.kafka_brokers, args.kafka_topic.as_deref())?;
 
-    println!("Pre-query: {:?}", pre_query);
+    info!("Sampling {} before watching for changes", ns);
+    let start = std::time::Instant::now();
+    let initial = analyzer.analyze().await?;
+    let duration = start.elapsed();
 
-    let mut result = database
-        .collection::<Document>(collection_name)
-        .aggregate(pipeline, None)
+    let metrics = match args.metrics_addr {
+        Some(addr) => {
+            let baseline = match &args.baseline {
+                Some(path) => {
+                    CollectionSchema::read_baseline(path).map_err(|e| file_error(path, e))?
+                }
+                None => initial.clone(),
+            };
+            let metrics = Arc::new(Mutex::new(SchemaMetrics {
+                last_analysis_duration: duration,
+                ..SchemaMetrics::new(&initial, &baseline)
+            }));
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .map_err(|source| AnalyzerError::Listen { addr, source })?;
+            info!("Serving metrics at http://{}/metrics", addr);
+            tokio::spawn(serve_metrics(listener, metrics.clone()));
+            Some((metrics, baseline))
+        }
+        None => None,
+    };
+    analyzer
+        .watch_from(&initial, |discovery, builder| {
+            println!(
+                "{} {} (after {} documents)",
+                bson::DateTime::now(),
+                discovery,
+                builder.documents()
+            );
+            if let Some((metrics, baseline)) = &metrics {
+                let schema = builder.build(ns.to_string());
+                *metrics.lock().unwrap() = SchemaMetrics {
+                    last_analysis_duration: duration,
+                    ..SchemaMetrics::new(&schema, baseline)
+                };
+            }
+            if let Some(webhook) = &webhook {
+                let webhook = webhook.clone();
+                let notification = DriftNotification::from_discovery(ns.to_string(), discovery);
+                tokio::spawn(async move {
+                    if let Err(e) = webhook.notify(&notification).await {
+                        warn!("could not notify: {}", e);
+                    }
+                });
+            }
+            #[cfg(feature = "kafka")]
+            if let Some(kafka) = &kafka {
+                let schema = builder.build(ns.to_string());
+                let change = SchemaChange::from_discovery(discovery, &schema);
+                let kafka = kafka.clone();
+                tokio::task::spawn_blocking(move || {
+                    if let Err(e) = kafka.publish(&[change]) {
+                        warn!("could not publish the change: {}", e);
+                    }
+                });
+            }
+        })
         .await?;
+    info!("Change stream on {} ended", ns);
+    Ok(())
+}
+
+/// Answer every request on `listener` with the current gauges, for Prometheus to scrape
+async fn serve_metrics(listener: tokio::net::TcpListener, metrics: Arc<Mutex<SchemaMetrics>>) {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
-    let query = start.elapsed() - pre_query;
-
-    println!("Query: {:?}", query);
-
-    while let Some(doc) = result.try_next().await.unwrap() {
-        // iterate over the schema entry, putting the fields and types into the map
-        let schema_entry = doc.get("schema").unwrap().as_array().unwrap();
-        schema_entry.into_iter().for_each(|entry| {
-            let entry = entry.as_document().unwrap();
-            let field = entry.get("field").unwrap().as_str().unwrap();
-            let types = entry
-                .get("types")
-                .unwrap()
-                .as_array()
-                .unwrap()
-                .into_iter()
-                .map(|t| t.as_str().unwrap().to_string())
-                .collect::<Vec<String>>();
-            schema.insert(field.to_string(), types);
+    loop {
+        let Ok((mut stream, _)) = listener.accept().await else {
+            continue;
+        };
+        let metrics = metrics.clone();
+        tokio::spawn(async move {
+            // only the request line matters
+            let mut request = [0; 1024];
+            let Ok(read) = stream.read(&mut request).await else {
+                return;
+            };
+            let request = String::from_utf8_lossy(&request[..read]);
+            let path = request.split_whitespace().nth(1).unwrap_or_default();
+            let response = if path == "/metrics" {
+                let body = render_metrics([&*metrics.lock().unwrap()]);
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                     Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            } else {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_string()
+            };
+            let _ = stream.write_all(response.as_bytes()).await;
         });
-        println!("{:?}", schema);
     }
-    let post_query = start.elapsed() - pre_query - query;
-    println!("Post-query: {:?}", post_query);
+}
+
+fn convert(args: ConvertArgs) -> Result<()> {
+    let schema = read_schema(&args.path, args.namespace.clone())?;
+    let output = match args.to {
+        ConvertTo::Schema => serde_json::to_string_pretty(&schema).unwrap(),
+        ConvertTo::JsonSchema => {
+            let json = Bson::Document(json_schema_of(&schema, args.required_presence / 100.0))
+                .into_relaxed_extjson();
+            serde_json::to_string_pretty(&json).unwrap()
+        }
+    };
+    write_output(args.out.as_deref(), &(output + "\n"))
+}
+
+/// Read a schema, or a JSON Schema named `namespace` or else after the file, from `path` or
+/// standard input for -
+fn read_schema(path: &Path, namespace: Option<String>) -> Result<CollectionSchema> {
+    let json = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())
+    } else {
+        std::fs::read_to_string(path)
+    }
+    .map_err(|e| file_error(path, e))?;
+    let namespace = namespace.unwrap_or_else(|| {
+        path.file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned())
+    });
+    CollectionSchema::from_json(&json, namespace).map_err(|e| file_error(path, e))
+}
 
-    println!("Total: {:?}", start.elapsed());
+/// How many generated documents are inserted at a time
+const INSERT_BATCH: usize = 1000;
 
+async fn generate(args: GenerateArgs, config: &Config) -> Result<()> {
+    let schema = read_schema(&args.path, None)?;
+    let mut documents = schema.generate(args.seed).take(args.count as usize);
+    let Some(ns) = args.target.as_ref().filter(|_| args.insert) else {
+        let output = documents
+            .map(|document| {
+                Bson::Document(document)
+                    .into_canonical_extjson()
+                    .to_string()
+                    + "\n"
+            })
+            .collect::<String>();
+        return write_output(args.out.as_deref(), &output);
+    };
+    let read = ReadArgs {
+        read_preference: None,
+        read_preference_tags: Vec::new(),
+    };
+    let client = connect(args.uri.as_deref(), config, &args.connection, &read).await?;
+    let collection = client.database(&ns.db).collection::<Document>(&ns.coll);
+    if args.drop {
+        collection.drop(None).await?;
+        info!("Dropped {}", ns);
+    }
+    // the documents are independent, so the server may insert them in any order
+    let options = InsertManyOptions::builder().ordered(false).build();
+    let mut inserted = 0;
+    loop {
+        let batch = documents.by_ref().take(INSERT_BATCH).collect::<Vec<_>>();
+        if batch.is_empty() {
+            break;
+        }
+        inserted += collection
+            .insert_many(batch, options.clone())
+            .await?
+            .inserted_ids
+            .len();
+        debug!("Inserted {} of {} documents", inserted, args.count);
+    }
+    info!("Inserted {} documents into {}", inserted, ns);
     Ok(())
 }
+
+async fn serve(args: ServeArgs, config: Config) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(args.listen)
+        .await
+        .map_err(|source| AnalyzerError::Listen {
+            addr: args.listen,
+            source,
+        })?;
+    info!("Listening on http://{}", args.listen);
+    let options = ServerOptions {
+        uri: args.uri,
+        config,
+        max_concurrent: args.max_concurrent as usize,
+        cache_ttl: Duration::from_secs(args.cache_ttl),
+        max_reports: args.max_reports as usize,
+        max_clients: args.max_clients as usize,
+    };
+    #[cfg(feature = "grpc")]
+    if let Some(addr) = args.grpc_listen {
+        let grpc_listener = tokio::net::TcpListener::bind(addr)
+            .await
+            .map_err(|source| AnalyzerError::Listen { addr, source })?;
+        info!("Serving gRPC on {}", addr);
+        let grpc = async {
+            schema_analyzer::serve_grpc(grpc_listener, options.clone())
+                .await
+                .map_err(|source| AnalyzerError::Listen { addr, source })
+        };
+        let rest = async {
+            schema_analyzer::serve(listener, options.clone())
+                .await
+                .map_err(|source| AnalyzerError::Listen {
+                    addr: args.listen,
+                    source,
+                })
+        };
+        tokio::try_join!(rest, grpc)?;
+        return Ok(());
+    }
+    schema_analyzer::serve(listener, options)
+        .await
+        .map_err(|source| AnalyzerError::Listen {
+            addr: args.listen,
+            source,
+        })
+}
+
+async fn validate(args: ValidateArgs, config: &Config) -> Result<ExitCode> {
+    let format = Format::resolve(args.format, args.out.as_deref());
+    let client = connect(args.uri.as_deref(), config, &args.connection, &args.read).await?;
+    let ns = Namespace::new(args.db, args.collection);
+    let bars = ProgressBars::new();
+    let mut options = args.sampling.options_for(config, &ns);
+    options.progress = Some(bars.progress_fn());
+    let analyzer = SchemaAnalyzer::with_options(client, ns.clone(), options);
+
+    let json_schema = match (&args.schema, &args.baseline) {
+        (Some(path), _) => read_json_schema(path).map_err(|e| file_error(path, e))?,
+        (None, Some(path)) => {
+            let baseline =
+                CollectionSchema::read_baseline(path).map_err(|e| file_error(path, e))?;
+            json_schema_of(&baseline, 1.0)
+        }
+        (None, None) => {
+            info!("Inferring the schema of {} to check against", ns);
+            json_schema_of(&analyzer.analyze().await?, 1.0)
+        }
+    };
+    let report = analyzer
+        .validate(&json_schema, args.invalid_examples)
+        .await?;
+    bars.finish();
+    write_output(args.out.as_deref(), &render_validation(&report, format))?;
+    Ok(if report.invalid == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_INVALID)
+    })
+}
+
+async fn bench(args: BenchArgs, config: &Config) -> Result<()> {
+    let format = Format::resolve(args.format, args.out.as_deref());
+    let client = connect(args.uri.as_deref(), config, &args.connection, &args.read).await?;
+    let ns = Namespace::new(args.db, args.collection);
+    let options = args.sampling.options_for(config, &ns);
+    let mut sample_sizes = args.sample_sizes;
+    sample_sizes.sort_unstable();
+    sample_sizes.dedup();
+    let mut samplings = sample_sizes
+        .into_iter()
+        .map(Sampling::Size)
+        .collect::<Vec<_>>();
+    samplings.push(Sampling::Auto);
+    info!(
+        "Running {} analyses of {}",
+        samplings.len() * 2 * args.repeat as usize,
+        ns
+    );
+    let benchmark = SchemaAnalyzer::with_options(client, ns, options)
+        .benchmark(&samplings, args.repeat)
+        .await?;
+    write_output(args.out.as_deref(), &render_benchmark(&benchmark, format))
+}
+
+/// The `$jsonSchema` of the schema's generated validator, requiring fields present in at
+/// least `required_presence` of their parent objects
+fn json_schema_of(schema: &CollectionSchema, required_presence: f64) -> Document {
+    schema
+        .to_validator(required_presence)
+        .get_document("$jsonSchema")
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// A JSON Schema from an Extended JSON file, unwrapping a validator's `$jsonSchema`
+fn read_json_schema(path: &Path) -> std::io::Result<Document> {
+    let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+    let json = serde_json::from_str::<serde_json::Value>(&std::fs::read_to_string(path)?)
+        .map_err(|e| invalid(e.to_string()))?;
+    match Bson::try_from(json).map_err(|e| invalid(e.to_string()))? {
+        Bson::Document(mut schema) => match schema.remove("$jsonSchema") {
+            Some(Bson::Document(json_schema)) => Ok(json_schema),
+            Some(_) => Err(invalid("$jsonSchema is not an object".to_string())),
+            None => Ok(schema),
+        },
+        _ => Err(invalid("expected a JSON object".to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command, clap::Error> {
+        Cli::try_parse_from(["schema-analyzer"].iter().chain(args)).map(|cli| cli.command)
+    }
+
+    #[test]
+    fn declares_consistent_arguments() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn parses_analyze() {
+        let Ok(Command::Analyze(args)) = parse(&[
+            "analyze",
+            "--db",
+            "shop",
+            "--collection",
+            "orders",
+            "--sample-size",
+            "500",
+            "--emit",
+            "typescript",
+        ]) else {
+            panic!("expected analyze");
+        };
+        assert_eq!(args.db.as_deref(), Some("shop"));
+        assert_eq!(args.collection.as_deref(), Some("orders"));
+        assert_eq!(args.sampling.sample_size, Some(500));
+        assert!(matches!(args.emit, Some(Emit::Typescript)));
+        // a size and a percentage can't both be asked for
+        assert!(parse(&[
+            "analyze",
+            "--db",
+            "shop",
+            "--collection",
+            "orders",
+            "--sample-size",
+            "500",
+            "--sample-percent",
+            "5",
+        ])
+        .is_err());
+        assert!(parse(&["analyze", "--all-databases", "--sample-percent", "0"]).is_err());
+    }
+
+    #[test]
+    fn parses_ids_to_analyze() {
+        let Ok(Command::Analyze(args)) = parse(&[
+            "analyze",
+            "--db",
+            "shop",
+            "--collection",
+            "orders",
+            "--ids",
+            r#"[1, {"$oid": "65f0c0ffee00000000000001"}]"#,
+        ]) else {
+            panic!("expected analyze");
+        };
+        let ids = args.sampling.ids.unwrap();
+        assert_eq!(ids[0], Bson::Int32(1));
+        assert!(matches!(ids[1], Bson::ObjectId(_)));
+        assert!(parse(&[
+            "analyze",
+            "--db",
+            "shop",
+            "--collection",
+            "orders",
+            "--ids",
+            "[]"
+        ])
+        .is_err());
+        assert!(parse(&[
+            "analyze",
+            "shop.orders",
+            "--ids",
+            "[1]",
+            "--sample-size",
+            "5"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn parses_diff() {
+        let Ok(Command::Diff(args)) = parse(&[
+            "diff",
+            "shop.orders",
+            "shop.orders_v2",
+            "--new-uri",
+            "mongodb://staging",
+        ]) else {
+            panic!("expected diff");
+        };
+        assert_eq!(args.old, Namespace::new("shop", "orders"));
+        assert_eq!(args.new, Namespace::new("shop", "orders_v2"));
+        assert_eq!(args.new_uri.as_deref(), Some("mongodb://staging"));
+        assert!(parse(&["diff", "shop.orders"]).is_err());
+        assert!(parse(&["diff", "shop", "shop.orders"]).is_err());
+    }
+
+    #[test]
+    fn parses_generate() {
+        let Ok(Command::Generate(args)) = parse(&[
+            "generate",
+            "baseline.json",
+            "--count",
+            "10",
+            "--seed",
+            "7",
+            "--insert",
+            "--target",
+            "test.orders",
+        ]) else {
+            panic!("expected generate");
+        };
+        assert_eq!(args.path, PathBuf::from("baseline.json"));
+        assert_eq!((args.count, args.seed), (10, Some(7)));
+        assert!(args.insert);
+        assert_eq!(args.target, Some(Namespace::new("test", "orders")));
+        // inserting needs somewhere to insert into
+        assert!(parse(&["generate", "baseline.json", "--insert"]).is_err());
+    }
+
+    #[test]
+    fn parses_validate() {
+        let Ok(Command::Validate(args)) = parse(&[
+            "validate",
+            "--db",
+            "shop",
+            "--collection",
+            "orders",
+            "--schema",
+            "orders.schema.json",
+            "--invalid-examples",
+            "2",
+        ]) else {
+            panic!("expected validate");
+        };
+        assert_eq!(
+            (args.db.as_str(), args.collection.as_str()),
+            ("shop", "orders")
+        );
+        assert_eq!(args.schema, Some(PathBuf::from("orders.schema.json")));
+        assert_eq!(args.invalid_examples, 2);
+        assert!(parse(&[
+            "validate",
+            "--db",
+            "shop",
+            "--collection",
+            "orders",
+            "--schema",
+            "a.json",
+            "--baseline",
+            "b.json",
+        ])
+        .is_err());
+    }
+
+    fn read_args(args: &[&str]) -> ReadArgs {
+        let base = [
+            "schema-analyzer",
+            "analyze",
+            "--db",
+            "shop",
+            "--collection",
+            "orders",
+        ];
+        let cli = Cli::try_parse_from(base.iter().chain(args)).unwrap();
+        match cli.command {
+            Command::Analyze(args) => args.read,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn builds_read_preferences_with_tag_sets() {
+        assert_eq!(read_args(&[]).read_preference().unwrap(), None);
+        let read = read_args(&[
+            "--read-preference",
+            "secondaryPreferred",
+            "--read-preference-tags",
+            "dc:east,rack:1",
+            "--read-preference-tags",
+            "",
+        ]);
+        let Some(ReadPreference::SecondaryPreferred { options }) = read.read_preference().unwrap()
+        else {
+            panic!("expected secondaryPreferred");
+        };
+        let tag_sets = options.tag_sets.unwrap();
+        assert_eq!(tag_sets.len(), 2);
+        assert_eq!(tag_sets[0]["dc"], "east");
+        assert_eq!(tag_sets[0]["rack"], "1");
+        assert!(tag_sets[1].is_empty());
+    }
+
+    #[test]
+    fn rejects_tag_sets_for_the_primary() {
+        let read = read_args(&[
+            "--read-preference",
+            "primary",
+            "--read-preference-tags",
+            "dc:east",
+        ]);
+        assert!(matches!(
+            read.read_preference(),
+            Err(AnalyzerError::InvalidOptions(_))
+        ));
+        let read = read_args(&["--read-preference", "primary"]);
+        assert_eq!(
+            read.read_preference().unwrap(),
+            Some(ReadPreference::Primary)
+        );
+    }
+}
@@ -0,0 +1,624 @@
+use std::fmt::Write;
+
+use crate::{
+    sizes::format_bytes,
+    stats::{LENGTH_HEADER, STATS_HEADER},
+    Benchmark, ClusterComparison, CollectionSchema, SchemaDiff, SchemaReport, ValidationReport,
+    Variants,
+};
+
+impl CollectionSchema {
+    /// Render the schema as a Markdown section, for pasting into wikis and pull requests: a
+    /// heading naming the namespace, the sampling stats, then a table of every field path
+    pub fn to_markdown(&self) -> String {
+        if let Some(sections) = self.variant_sections() {
+            let mut out = sections
+                .iter()
+                .map(CollectionSchema::to_markdown)
+                .collect::<Vec<_>>()
+                .join("\n");
+            if let Some(note) = self.variants.as_ref().and_then(Variants::note) {
+                writeln!(out).unwrap();
+                writeln!(out, "{}", note).unwrap();
+            }
+            return out;
+        }
+        let mut out = String::new();
+        writeln!(out, "## `{}`", self.namespace).unwrap();
+        writeln!(out).unwrap();
+        writeln!(
+            out,
+            "{} of {} documents sampled ({}).",
+            self.sample_size, self.document_count, self.sample_method
+        )
+        .unwrap();
+        if let Some(confidence) = &self.confidence {
+            writeln!(out).unwrap();
+            writeln!(out, "{}", confidence.note(self.document_count)).unwrap();
+        }
+        if self.fields.is_empty() {
+            return out;
+        }
+
+        let examples = self.fields.values().any(|field| field.examples().is_some());
+        writeln!(out).unwrap();
+        if examples {
+            writeln!(out, "| Field | Presence | Null | Types | Examples |").unwrap();
+            writeln!(out, "| --- | ---: | ---: | --- | --- |").unwrap();
+        } else {
+            writeln!(out, "| Field | Presence | Null | Types |").unwrap();
+            writeln!(out, "| --- | ---: | ---: | --- |").unwrap();
+        }
+        for (path, field) in &self.fields {
+            let types = field
+                .types
+                .iter()
+                .map(|t| {
+                    format!(
+                        "{} {:.1}%",
+                        self.type_label(path, t),
+                        self.share(field.type_counts[t]) * 100.0
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let mut presence = format!("{:.1}%", self.share(field.count) * 100.0);
+            if let Some(interval) = field.presence_interval {
+                write!(presence, " ({})", interval).unwrap();
+            }
+            write!(
+                out,
+                "| `{}` | {} | {:.1}% | {} |",
+                // a pipe would end the cell, even inside a code span
+                path.replace('|', "\\|"),
+                presence,
+                self.share(field.nulls()) * 100.0,
+                types
+            )
+            .unwrap();
+            if examples {
+                let field_examples = field.examples().unwrap_or_default();
+                write!(out, " {} |", field_examples.replace('|', "\\|")).unwrap();
+            }
+            writeln!(out).unwrap();
+        }
+
+        let elements = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.element_presence?)))
+            .collect::<Vec<_>>();
+        if !elements.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "Array element fields:").unwrap();
+            writeln!(out).unwrap();
+            for (path, presence) in elements {
+                writeln!(out, "- `{}`: {}", path, presence).unwrap();
+            }
+        }
+
+        // only where the parent is optional, so it tells more than the presence
+        let conditional = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| {
+                let presence = field.conditional_presence?;
+                (presence != self.share(field.count)).then_some((path, presence))
+            })
+            .collect::<Vec<_>>();
+        if !conditional.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "Subdocument fields:").unwrap();
+            writeln!(out).unwrap();
+            for (path, presence) in conditional {
+                writeln!(
+                    out,
+                    "- `{}`: in {:.1}% of the objects holding its parent",
+                    path,
+                    presence * 100.0
+                )
+                .unwrap();
+            }
+        }
+
+        let top_values = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.top_values()?)))
+            .collect::<Vec<_>>();
+        if !top_values.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "Top values:").unwrap();
+            writeln!(out).unwrap();
+            for (path, top) in top_values {
+                writeln!(out, "- `{}`: {}", path, top).unwrap();
+            }
+        }
+
+        let binaries = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.binary_subtypes()?)))
+            .collect::<Vec<_>>();
+        if !binaries.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "Binary subtypes:").unwrap();
+            writeln!(out).unwrap();
+            for (path, subtypes) in binaries {
+                writeln!(out, "- `{}`: {}", path, subtypes).unwrap();
+            }
+        }
+
+        let stats = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.stats.as_ref()?.columns())))
+            .collect::<Vec<_>>();
+        if !stats.is_empty() {
+            writeln!(out).unwrap();
+            let headings = STATS_HEADER.map(|heading| heading.to_lowercase());
+            writeln!(out, "| Numeric field | {} |", headings.join(" | ")).unwrap();
+            writeln!(out, "| --- |{}", " ---: |".repeat(STATS_HEADER.len())).unwrap();
+            for (path, columns) in stats {
+                writeln!(
+                    out,
+                    "| `{}` | {} |",
+                    path.replace('|', "\\|"),
+                    columns.join(" | ")
+                )
+                .unwrap();
+            }
+        }
+
+        let lengths = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.length_stats.as_ref()?)))
+            .collect::<Vec<_>>();
+        if !lengths.is_empty() {
+            let headings = LENGTH_HEADER.map(str::to_lowercase);
+            writeln!(out).unwrap();
+            writeln!(
+                out,
+                "| String field | {} | Long outliers |",
+                headings.join(" | ")
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "| --- |{} --- |",
+                " ---: |".repeat(LENGTH_HEADER.len())
+            )
+            .unwrap();
+            for (path, stats) in lengths {
+                writeln!(
+                    out,
+                    "| `{}` | {} | {} |",
+                    path.replace('|', "\\|"),
+                    stats.columns().join(" | "),
+                    if stats.long_outliers { "yes" } else { "" }
+                )
+                .unwrap();
+            }
+        }
+
+        let dates = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.date_stats.as_ref()?)))
+            .collect::<Vec<_>>();
+        if !dates.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "| Date field | Earliest | Latest | Distribution |").unwrap();
+            writeln!(out, "| --- | --- | --- | --- |").unwrap();
+            for (path, stats) in dates {
+                writeln!(
+                    out,
+                    "| `{}` | {} | {} | {} |",
+                    path.replace('|', "\\|"),
+                    stats.earliest,
+                    stats.latest,
+                    stats.describe_distribution()
+                )
+                .unwrap();
+            }
+        }
+
+        if let Some(sizes) = &self.sizes {
+            writeln!(out).unwrap();
+            writeln!(
+                out,
+                "Storage by field, average document {}:",
+                format_bytes(sizes.average_document)
+            )
+            .unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "| Field | Average | Share | Total |").unwrap();
+            writeln!(out, "| --- | ---: | ---: | ---: |").unwrap();
+            for field in &sizes.fields {
+                writeln!(
+                    out,
+                    "| `{}` | {} | {:.1}% | {} |",
+                    field.path.replace('|', "\\|"),
+                    format_bytes(field.average),
+                    sizes.share(field) * 100.0,
+                    format_bytes(field.total as f64)
+                )
+                .unwrap();
+            }
+        }
+
+        if let Some(sizes) = &self.document_sizes {
+            writeln!(out).unwrap();
+            writeln!(out, "Document sizes: {}", sizes.describe()).unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "| Size | Documents | Share |").unwrap();
+            writeln!(out, "| --- | ---: | ---: |").unwrap();
+            for bucket in &sizes.histogram {
+                writeln!(
+                    out,
+                    "| {} | {} | {:.1}% |",
+                    bucket,
+                    bucket.count,
+                    sizes.share(bucket) * 100.0
+                )
+                .unwrap();
+            }
+            if let Some(warning) = sizes.warning() {
+                writeln!(out).unwrap();
+                writeln!(out, "**warn** {}", warning).unwrap();
+            }
+        }
+
+        if let Some(widths) = &self.document_widths {
+            writeln!(out).unwrap();
+            writeln!(out, "Document widths: {}", widths.describe()).unwrap();
+            if let Some(warning) = widths.warning() {
+                writeln!(out).unwrap();
+                writeln!(out, "**warn** {}", warning).unwrap();
+            }
+        }
+
+        if let Some(warning) = self.truncation_warning() {
+            writeln!(out).unwrap();
+            writeln!(out, "**warn** {}", warning).unwrap();
+        }
+
+        if let Some(versions) = &self.versions {
+            writeln!(out).unwrap();
+            writeln!(out, "Versions of `{}`:", versions.field).unwrap();
+            writeln!(out).unwrap();
+            for version in &versions.versions {
+                writeln!(
+                    out,
+                    "- `{}`: {} documents",
+                    version.value, version.documents
+                )
+                .unwrap();
+            }
+            for change in &versions.changes {
+                writeln!(out).unwrap();
+                writeln!(out, "`{}` to `{}`:", change.from, change.to).unwrap();
+                writeln!(out).unwrap();
+                write!(out, "{}", change.diff.to_markdown()).unwrap();
+            }
+            if let Some(note) = versions.note() {
+                writeln!(out).unwrap();
+                writeln!(out, "{}", note).unwrap();
+            }
+        }
+
+        if let Some(evolution) = &self.evolution {
+            writeln!(out).unwrap();
+            writeln!(out, "Evolution: {}", evolution.describe()).unwrap();
+            let rows = evolution.notable().collect::<Vec<_>>();
+            writeln!(out).unwrap();
+            if rows.is_empty() {
+                writeln!(out, "Every field held the same types throughout.").unwrap();
+            } else {
+                writeln!(out, "| Field | First seen | Last seen | Types |").unwrap();
+                writeln!(out, "| --- | --- | --- | --- |").unwrap();
+                for (path, history) in rows {
+                    writeln!(
+                        out,
+                        "| `{}` | {} | {} | {} |",
+                        path.replace('|', "\\|"),
+                        history.first_seen,
+                        history.last_seen,
+                        history.describe_types()
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        if let Some(view) = &self.view {
+            writeln!(out).unwrap();
+            writeln!(out, "View: {}", view.describe()).unwrap();
+        }
+
+        if let Some(time_series) = &self.time_series {
+            writeln!(out).unwrap();
+            writeln!(out, "Time series: {}", time_series.describe()).unwrap();
+            if !time_series.meta_fields.is_empty() {
+                writeln!(out).unwrap();
+                writeln!(out, "| Metadata | Per series | Types |").unwrap();
+                writeln!(out, "| --- | ---: | --- |").unwrap();
+                for (path, field) in &time_series.meta_fields {
+                    writeln!(
+                        out,
+                        "| `{}` | {} | {} |",
+                        path.replace('|', "\\|"),
+                        time_series.presence(field),
+                        time_series.types(field)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+
+        if let Some(shards) = &self.shards {
+            writeln!(out).unwrap();
+            writeln!(out, "Shards: {}", shards.describe()).unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "| Shard | Documents | Sampled |").unwrap();
+            writeln!(out, "| --- | ---: | ---: |").unwrap();
+            for shard in &shards.shards {
+                writeln!(
+                    out,
+                    "| {} | {} | {} |",
+                    shard.shard, shard.document_count, shard.sample_size
+                )
+                .unwrap();
+            }
+            if !shards.differences.is_empty() {
+                writeln!(out).unwrap();
+                for difference in &shards.differences {
+                    writeln!(out, "- `{}`: {}", difference.path, difference.describe()).unwrap();
+                }
+            }
+        }
+
+        if let Some(indexes) = &self.indexes {
+            writeln!(out).unwrap();
+            writeln!(out, "Indexes: {}", indexes.describe()).unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "| Index | Keys |").unwrap();
+            writeln!(out, "| --- | --- |").unwrap();
+            for index in &indexes.existing {
+                writeln!(
+                    out,
+                    "| {}{} | `{}` |",
+                    index.name.replace('|', "\\|"),
+                    if index.sparse { " (sparse)" } else { "" },
+                    index.keys.join(", ").replace('|', "\\|")
+                )
+                .unwrap();
+            }
+            if !indexes.suggestions.is_empty() || !indexes.rare.is_empty() {
+                writeln!(out).unwrap();
+                for suggestion in &indexes.suggestions {
+                    writeln!(out, "- suggest {}", suggestion).unwrap();
+                }
+                for rare in &indexes.rare {
+                    writeln!(out, "- **warn** {}", rare).unwrap();
+                }
+            }
+        }
+
+        if let Some(shapes) = &self.shapes {
+            writeln!(out).unwrap();
+            writeln!(out, "{}:", shapes.summary()).unwrap();
+            writeln!(out).unwrap();
+            writeln!(out, "| Documents | Share | Fields |").unwrap();
+            writeln!(out, "| ---: | ---: | --- |").unwrap();
+            for shape in &shapes.top {
+                writeln!(
+                    out,
+                    "| {} | {:.1}% | {} |",
+                    shape.count,
+                    self.share(shape.count) * 100.0,
+                    shape.describe().replace('|', "\\|")
+                )
+                .unwrap();
+            }
+        }
+
+        if !self.anomalies.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "Anomalies:").unwrap();
+            writeln!(out).unwrap();
+            for anomaly in &self.anomalies {
+                writeln!(out, "- **{}** {}", anomaly.severity, anomaly).unwrap();
+            }
+        }
+
+        if !self.rare_fields.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "Rare fields:").unwrap();
+            writeln!(out).unwrap();
+            for field in &self.rare_fields {
+                writeln!(out, "- {}", field).unwrap();
+            }
+        }
+
+        if !self.findings.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "Findings:").unwrap();
+            writeln!(out).unwrap();
+            for finding in &self.findings {
+                writeln!(out, "- **{}** {}", finding.severity, finding).unwrap();
+            }
+        }
+
+        if let Some(issues) = &self.field_names {
+            writeln!(out).unwrap();
+            writeln!(out, "Field names:").unwrap();
+            writeln!(out).unwrap();
+            if issues.is_empty() {
+                writeln!(out, "No field names break drivers or updates.").unwrap();
+            }
+            for issue in issues {
+                writeln!(out, "- {}", issue).unwrap();
+            }
+        }
+
+        if let Some(privacy) = &self.privacy {
+            writeln!(out).unwrap();
+            writeln!(out, "Privacy:").unwrap();
+            writeln!(out).unwrap();
+            if privacy.is_empty() {
+                writeln!(out, "No fields look like personal data.").unwrap();
+            }
+            for finding in privacy {
+                writeln!(out, "- {}", finding).unwrap();
+            }
+        }
+
+        if let Some(meta) = &self.meta {
+            writeln!(out).unwrap();
+            writeln!(out, "Run: {}.", meta.describe()).unwrap();
+            if let Some(explain) = &meta.explain {
+                writeln!(out).unwrap();
+                writeln!(out, "```").unwrap();
+                write!(out, "{}", explain).unwrap();
+                writeln!(out, "```").unwrap();
+            }
+        }
+        out
+    }
+}
+
+impl SchemaReport {
+    /// Every schema as by [`CollectionSchema::to_markdown`], one section after another
+    pub fn to_markdown(&self) -> String {
+        let mut out = self
+            .namespaces
+            .values()
+            .map(CollectionSchema::to_markdown)
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !self.relationships.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "## Relationships").unwrap();
+            writeln!(out).unwrap();
+            for relationship in &self.relationships {
+                writeln!(out, "- {}", relationship).unwrap();
+            }
+        }
+        if !self.failures.is_empty() {
+            writeln!(out).unwrap();
+            writeln!(out, "## Failed").unwrap();
+            writeln!(out).unwrap();
+            for (ns, error) in &self.failures {
+                writeln!(out, "- `{}`: {}", ns, error).unwrap();
+            }
+        }
+        out
+    }
+}
+
+impl SchemaDiff {
+    /// The text rendering in a `diff` code block, so added and removed fields are colored
+    pub fn to_markdown(&self) -> String {
+        format!("```diff\n{}```\n", self)
+    }
+}
+
+impl ClusterComparison {
+    /// The summary, lists of the namespaces only one cluster has, and a `diff` code block per
+    /// differing namespace
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{} namespaces on both {} and {}: {} identical, {} differing.",
+            self.identical.len() + self.diffs.len(),
+            self.left,
+            self.right,
+            self.identical.len(),
+            self.diffs.len()
+        )
+        .unwrap();
+        for (side, namespaces) in [
+            (&self.left, &self.only_left),
+            (&self.right, &self.only_right),
+        ] {
+            if !namespaces.is_empty() {
+                writeln!(out, "\n## Only on {}\n", side).unwrap();
+                for namespace in namespaces {
+                    writeln!(out, "- `{}`", namespace).unwrap();
+                }
+            }
+        }
+        for (namespace, diff) in &self.diffs {
+            writeln!(out, "\n## `{}`\n", namespace).unwrap();
+            out.push_str(&diff.to_markdown());
+        }
+        if !self.failures.is_empty() {
+            writeln!(out, "\n## Not compared\n").unwrap();
+            for (namespace, error) in &self.failures {
+                writeln!(out, "- `{}`: {}", namespace, error).unwrap();
+            }
+        }
+        out
+    }
+}
+
+impl ValidationReport {
+    /// The text rendering in a code block
+    pub fn to_markdown(&self) -> String {
+        format!("```\n{}```\n", self)
+    }
+}
+
+impl Benchmark {
+    /// The text rendering in a code block
+    pub fn to_markdown(&self) -> String {
+        format!("```\n{}```\n", self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    fn schema(documents: &[u8]) -> CollectionSchema {
+        CollectionSchema::from_ndjson(documents, "db.coll", &AnalysisOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn renders_a_table_row_per_field() {
+        let markdown = schema(b"{\"a|b\": 1, \"c\": true}\n{\"a|b\": 2}\n").to_markdown();
+        assert!(markdown.starts_with("## `db.coll`\n"), "{}", markdown);
+        assert!(markdown.contains("| Field | Presence | Null | Types |"));
+        // the pipe in the path doesn't end the cell
+        assert!(
+            markdown.contains("| `a\\|b` | 100.0% | 0.0% | int 100.0% |"),
+            "{}",
+            markdown
+        );
+        assert!(markdown.contains("| `c` | 50.0% | 0.0% | bool 50.0%, missing 50.0% |"));
+    }
+
+    #[test]
+    fn lists_subdocument_fields_by_their_presence_in_the_parent() {
+        let markdown = schema(b"{\"p\": {\"q\": 1}}\n{\"x\": 1}\n").to_markdown();
+        assert!(
+            markdown.contains(
+                "Subdocument fields:\n\n- `p.q`: in 100.0% of the objects holding its parent"
+            ),
+            "{}",
+            markdown
+        );
+
+        let report = SchemaReport {
+            failures: [("db.gone".to_string(), "not authorized".to_string())].into(),
+            ..SchemaReport::default()
+        };
+        assert!(report
+            .to_markdown()
+            .contains("## Failed\n\n- `db.gone`: not authorized"));
+    }
+}
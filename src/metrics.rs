@@ -0,0 +1,140 @@
+//! Gauges describing inferred schemas in Prometheus' text exposition format, so drift can be
+//! alerted on from a monitoring stack
+
+use std::{fmt::Write, time::Duration};
+
+use crate::CollectionSchema;
+
+/// The gauges of one namespace
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SchemaMetrics {
+    pub namespace: String,
+    /// How many field paths the schema has
+    pub fields: u64,
+    /// How many of them hold more than one type, not counting missing
+    pub mixed_type_fields: u64,
+    /// How many paths the schema has that the baseline doesn't
+    pub new_fields_since_baseline: u64,
+    /// How long the last full analysis took
+    pub last_analysis_duration: Duration,
+}
+
+impl SchemaMetrics {
+    /// The gauges of `schema`, compared against `baseline`
+    pub fn new(schema: &CollectionSchema, baseline: &CollectionSchema) -> Self {
+        SchemaMetrics {
+            namespace: schema.namespace.clone(),
+            fields: schema.fields.len() as u64,
+            mixed_type_fields: schema.fields.values().filter(|f| f.is_mixed()).count() as u64,
+            new_fields_since_baseline: baseline.diff(schema).added.len() as u64,
+            last_analysis_duration: Duration::ZERO,
+        }
+    }
+}
+
+/// A gauge's name, help text and value
+type Gauge = (&'static str, &'static str, fn(&SchemaMetrics) -> f64);
+
+/// Every namespace's gauges in the text exposition format, each gauge labeled by namespace
+pub fn render_metrics<'a>(metrics: impl IntoIterator<Item = &'a SchemaMetrics>) -> String {
+    let metrics = metrics.into_iter().collect::<Vec<_>>();
+    let gauges: [Gauge; 4] = [
+        (
+            "schema_fields_total",
+            "Field paths in the inferred schema",
+            |m| m.fields as f64,
+        ),
+        (
+            "schema_mixed_type_fields_total",
+            "Field paths holding more than one type",
+            |m| m.mixed_type_fields as f64,
+        ),
+        (
+            "schema_new_fields_since_baseline",
+            "Field paths the baseline schema doesn't have",
+            |m| m.new_fields_since_baseline as f64,
+        ),
+        (
+            "schema_last_analysis_duration_seconds",
+            "How long the last full analysis took",
+            |m| m.last_analysis_duration.as_secs_f64(),
+        ),
+    ];
+    let mut out = String::new();
+    for (name, help, value) in gauges {
+        writeln!(out, "# HELP {} {}", name, help).unwrap();
+        writeln!(out, "# TYPE {} gauge", name).unwrap();
+        for m in &metrics {
+            writeln!(
+                out,
+                "{}{{namespace=\"{}\"}} {}",
+                name,
+                escape_label(&m.namespace),
+                value(m)
+            )
+            .unwrap();
+        }
+    }
+    out
+}
+
+/// `value` with backslashes, quotes and newlines escaped for a label
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    #[test]
+    fn counts_fields_against_the_baseline() {
+        let options = AnalysisOptions::default();
+        let baseline =
+            CollectionSchema::from_ndjson(b"{\"a\": 1}\n".as_slice(), "db.coll", &options).unwrap();
+        let schema = CollectionSchema::from_ndjson(
+            b"{\"a\": 1, \"b\": 1}\n{\"a\": \"x\", \"c\": 1}\n".as_slice(),
+            "db.coll",
+            &options,
+        )
+        .unwrap();
+        let metrics = SchemaMetrics::new(&schema, &baseline);
+        assert_eq!(
+            (
+                metrics.fields,
+                metrics.mixed_type_fields,
+                metrics.new_fields_since_baseline
+            ),
+            (3, 1, 2)
+        );
+    }
+
+    #[test]
+    fn renders_a_gauge_per_namespace_with_escaped_labels() {
+        let metrics = [
+            SchemaMetrics {
+                namespace: "db.a".to_string(),
+                fields: 3,
+                last_analysis_duration: Duration::from_millis(1500),
+                ..SchemaMetrics::default()
+            },
+            SchemaMetrics {
+                namespace: "db.\"b\"".to_string(),
+                fields: 5,
+                ..SchemaMetrics::default()
+            },
+        ];
+        let text = render_metrics(&metrics);
+        assert!(text.contains(
+            "# TYPE schema_fields_total gauge\n\
+             schema_fields_total{namespace=\"db.a\"} 3\n\
+             schema_fields_total{namespace=\"db.\\\"b\\\"\"} 5\n"
+        ));
+        assert!(text.contains("schema_last_analysis_duration_seconds{namespace=\"db.a\"} 1.5\n"));
+        assert_eq!(text.matches("# HELP").count(), 4);
+    }
+}
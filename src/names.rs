@@ -0,0 +1,206 @@
+//! Field names that drivers, query paths and update operators trip over: dots, a leading
+//! `$`, empty keys, very long keys and keys mangled by a lossy decoding
+
+use std::fmt;
+
+use bson::{Bson, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::dbref::DBREF_KEYS;
+
+/// Keys longer than this many bytes are reported as [`NameProblem::Long`]
+const LONG_KEY: usize = 128;
+
+/// How many `_id`s of the documents having each problem are kept
+const EXAMPLE_DOCUMENTS: usize = 5;
+
+/// What's wrong with a field name
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NameProblem {
+    /// Holds a `.`, so query and update paths read it as a nested field
+    Dotted,
+    /// Starts with a `$`, which the server takes for an operator in updates and pipelines
+    DollarPrefixed,
+    /// The empty string, which no path can address
+    Empty,
+    /// Longer than 128 bytes, `length` of them
+    Long { length: usize },
+    /// Holds control characters or U+FFFD, the replacement for bytes that weren't UTF-8
+    Unprintable,
+}
+
+impl fmt::Display for NameProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameProblem::Dotted => f.write_str("name contains a dot"),
+            NameProblem::DollarPrefixed => f.write_str("name starts with $"),
+            NameProblem::Empty => f.write_str("empty name"),
+            NameProblem::Long { length } => write!(f, "name is {} bytes long", length),
+            NameProblem::Unprintable => f.write_str("name holds control or replacement characters"),
+        }
+    }
+}
+
+/// A field whose name has a [`NameProblem`], found if
+/// [`AnalysisOptions::check_field_names`](crate::AnalysisOptions::check_field_names) asked
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldNameIssue {
+    /// The field's path, the offending name last
+    pub path: String,
+    pub problem: NameProblem,
+    /// How many sampled documents hold the field
+    pub count: u64,
+    /// The `_id`s of the first few of them, or null for documents without one
+    pub documents: Vec<Bson>,
+}
+
+impl fmt::Display for FieldNameIssue {
+    /// e.g. `"price.usd": name contains a dot, in 3 documents, e.g. _id 1, 2, 3`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?}: {}, in {} documents",
+            self.path, self.problem, self.count
+        )?;
+        if !self.documents.is_empty() {
+            let ids = self
+                .documents
+                .iter()
+                .map(Bson::to_string)
+                .collect::<Vec<_>>();
+            write!(f, ", e.g. _id {}", ids.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The problem with `name`, if it has one. Emptiness and a leading `$` come before the
+/// rest, as they're what break reads and writes outright. A DBRef's keys are fine
+fn problem(name: &str) -> Option<NameProblem> {
+    if DBREF_KEYS.contains(&name) {
+        None
+    } else if name.is_empty() {
+        Some(NameProblem::Empty)
+    } else if name.starts_with('$') {
+        Some(NameProblem::DollarPrefixed)
+    } else if name.contains('.') {
+        Some(NameProblem::Dotted)
+    } else if name.chars().any(|c| c.is_control() || c == '\u{FFFD}') {
+        Some(NameProblem::Unprintable)
+    } else if name.len() > LONG_KEY {
+        Some(NameProblem::Long { length: name.len() })
+    } else {
+        None
+    }
+}
+
+/// Field name problems collected one document at a time
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NameCheck {
+    issues: Vec<FieldNameIssue>,
+    /// The issues the current document was counted for
+    counted: Vec<usize>,
+}
+
+impl NameCheck {
+    /// Start on the next document
+    pub fn next_document(&mut self) {
+        self.counted.clear();
+    }
+
+    /// Check the key `name` of the field at `path` in `doc`, the document being checked
+    pub fn observe(&mut self, doc: &Document, path: &str, name: &str) {
+        let Some(problem) = problem(name) else {
+            return;
+        };
+        let index = match self
+            .issues
+            .iter()
+            .position(|issue| issue.path == path && issue.problem == problem)
+        {
+            Some(index) => index,
+            None => {
+                self.issues.push(FieldNameIssue {
+                    path: path.to_string(),
+                    problem,
+                    count: 0,
+                    documents: Vec::new(),
+                });
+                self.issues.len() - 1
+            }
+        };
+        // a key inside array elements can repeat within a document
+        if self.counted.contains(&index) {
+            return;
+        }
+        self.counted.push(index);
+        let issue = &mut self.issues[index];
+        issue.count += 1;
+        if issue.documents.len() < EXAMPLE_DOCUMENTS {
+            issue
+                .documents
+                .push(doc.get("_id").cloned().unwrap_or(Bson::Null));
+        }
+    }
+
+    /// The issues found, in path order
+    pub fn issues(&self) -> Vec<FieldNameIssue> {
+        let mut issues = self.issues.clone();
+        issues.sort_by(|a, b| a.path.cmp(&b.path));
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnalysisOptions, CollectionSchema};
+
+    #[test]
+    fn tells_what_is_wrong_with_a_name() {
+        assert_eq!(problem("price.usd"), Some(NameProblem::Dotted));
+        assert_eq!(problem("$set"), Some(NameProblem::DollarPrefixed));
+        assert_eq!(problem("$a.b"), Some(NameProblem::DollarPrefixed));
+        assert_eq!(problem(""), Some(NameProblem::Empty));
+        assert_eq!(problem("caf\u{FFFD}"), Some(NameProblem::Unprintable));
+        assert_eq!(
+            problem(&"k".repeat(LONG_KEY + 1)),
+            Some(NameProblem::Long {
+                length: LONG_KEY + 1
+            })
+        );
+        assert_eq!(problem("$ref"), None);
+        assert_eq!(problem("name"), None);
+    }
+
+    #[test]
+    fn counts_each_document_once_per_problem() {
+        let options = AnalysisOptions {
+            check_field_names: true,
+            ..AnalysisOptions::default()
+        };
+        let ndjson = "{\"_id\": 1, \"items\": [{\"a.b\": 1}, {\"a.b\": 2}]}\n\
+                      {\"_id\": 2, \"items\": [{\"a.b\": 3}], \"\": true}\n{\"_id\": 3}\n";
+        let schema = CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options).unwrap();
+        let issues = schema.field_names.unwrap();
+        assert_eq!(issues.len(), 2, "{:?}", issues);
+        let [empty, dotted] = &issues[..] else {
+            unreachable!()
+        };
+        assert_eq!((empty.problem, empty.count), (NameProblem::Empty, 1));
+        assert_eq!(
+            (dotted.path.as_str(), dotted.problem),
+            ("items[].a.b", NameProblem::Dotted)
+        );
+        assert_eq!(dotted.count, 2);
+        assert_eq!(dotted.documents, [Bson::Int32(1), Bson::Int32(2)]);
+        assert_eq!(
+            dotted.to_string(),
+            format!(
+                "{:?}: name contains a dot, in 2 documents, e.g. _id 1, 2",
+                dotted.path
+            )
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::io::{self, BufRead};
+
+use bson::{Bson, Document};
+
+use crate::{
+    dump::{invalid, Sample},
+    infer::bson_type,
+    AnalysisOptions, CollectionSchema,
+};
+
+impl CollectionSchema {
+    /// Infer the schema of newline-delimited Extended JSON, one document per line, as written by
+    /// mongoexport. Both canonical and relaxed Extended JSON are understood; blank lines are
+    /// skipped. Sampling works as in [`CollectionSchema::from_bson_file`]
+    pub fn from_ndjson(
+        reader: impl BufRead,
+        namespace: impl Into<String>,
+        options: &AnalysisOptions,
+    ) -> io::Result<Self> {
+        let mut sample = Sample::new(options);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            sample.add_with(|| {
+                parse_line(&line).map_err(|e| invalid(format!("line {}: {}", i + 1, e)))
+            })?;
+        }
+        Ok(sample.build(namespace.into()))
+    }
+}
+
+pub(crate) fn parse_line(line: &str) -> Result<Document, String> {
+    let json = serde_json::from_str::<serde_json::Value>(line).map_err(|e| e.to_string())?;
+    match Bson::try_from(json).map_err(|e| e.to_string())? {
+        Bson::Document(doc) => Ok(doc),
+        other => Err(format!("expected a document, got {}", bson_type(&other))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BsonType;
+
+    #[test]
+    fn reads_canonical_and_relaxed_extended_json_skipping_blank_lines() {
+        let ndjson =
+            "{\"n\": {\"$numberLong\": \"1\"}, \"at\": {\"$date\": \"2024-01-01T00:00:00Z\"}}\n\
+                      \n   \n\
+                      {\"n\": 2, \"at\": {\"$date\": {\"$numberLong\": \"0\"}}}\n";
+        let schema = CollectionSchema::from_ndjson(
+            ndjson.as_bytes(),
+            "db.coll",
+            &AnalysisOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(schema.sample_size, 2);
+        assert!(schema.fields["n"].types.contains(&BsonType::Long));
+        assert!(schema.fields["n"].types.contains(&BsonType::Int));
+        assert_eq!(schema.fields["at"].types, [BsonType::Date]);
+    }
+
+    #[test]
+    fn names_the_line_that_is_not_a_document() {
+        let ndjson = "{\"a\": 1}\n[1, 2]\n";
+        let e = CollectionSchema::from_ndjson(
+            ndjson.as_bytes(),
+            "db.coll",
+            &AnalysisOptions::default(),
+        )
+        .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            e.to_string()
+                .contains("line 2: expected a document, got array"),
+            "{}",
+            e
+        );
+        assert!(parse_line("{\"a\": ").is_err());
+    }
+}
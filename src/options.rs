@@ -0,0 +1,478 @@
+use std::{collections::BTreeSet, time::Duration};
+
+use bson::{doc, Bson, Document};
+#[cfg(feature = "driver")]
+use mongodb::options::ReadPreference;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "driver")]
+use crate::ResultCache;
+use crate::{
+    glob::path_match,
+    pipeline::{default_sample_size, DEFAULT_MAX_DEPTH},
+    progress::ProgressFn,
+    rare::RARE_PRESENCE,
+    HistogramScale, ProfileThresholds, Redaction,
+};
+
+/// Settings controlling how a collection is sampled and analyzed. Multi-collection runs share
+/// them, unless a [`Config`](crate::Config) sets them per namespace
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnalysisOptions {
+    /// Which documents to infer the schema from
+    pub sampling: Sampling,
+    /// Only analyze documents matching this query filter, e.g. one tenant's. Sampling sizes
+    /// derived from the document count then count the matching documents exactly, which can
+    /// be slow without an index. Ignored for files
+    pub filter: Option<Document>,
+    /// Analyze exactly the documents with these `_id`s, along with the filter, instead of a
+    /// sample, e.g. to inspect the records a data issue was reported for. A warning is
+    /// logged for those not found. Ignored for files
+    pub ids: Vec<Bson>,
+    /// How many levels of fields to report. 1 only looks at top-level fields; deeper
+    /// subdocuments are still reported, but only as "object"
+    pub max_depth: u32,
+    /// Only infer the types of the first this many elements of each array, so huge arrays
+    /// don't dominate the run. Arrays cut short are reported, see
+    /// [`FieldSchema::truncated`](crate::FieldSchema::truncated)
+    pub max_array_elements: Option<usize>,
+    /// Only infer the types of the first this many fields of each document and subdocument,
+    /// so documents with thousands of keys don't produce thousands of paths. Documents cut
+    /// short are reported, see
+    /// [`CollectionSchema::truncated_documents`](crate::CollectionSchema::truncated_documents)
+    pub max_fields_per_level: Option<usize>,
+    /// Comment attached to the aggregation so it can be picked out in currentOp and the profiler
+    pub comment: String,
+    /// Compare strings by these language rules in the filter and the count, as the server
+    /// takes a collation, e.g. `{"locale": "fr", "strength": 2}`
+    pub collation: Option<Document>,
+    /// The index the queries should use, e.g. one suiting [`AnalysisOptions::filter`]
+    pub hint: Option<IndexHint>,
+    /// Let the aggregation spill to disk instead of failing at the 100MB per-stage memory
+    /// limit. Only sent when set, so older servers without the option still work
+    pub allow_disk_use: bool,
+    /// Abort the query on the server after this long
+    pub max_time: Option<Duration>,
+    /// How many documents the server returns per batch. Mostly matters for
+    /// [`Strategy::Client`], which pulls every sampled document
+    pub batch_size: Option<u32>,
+    /// How many times to retry the sampling after a network error or a replica set election,
+    /// waiting twice as long each time. A client-side scan picks up after the last `_id` it
+    /// read rather than starting over
+    pub retries: u32,
+    /// Read at most this many documents a second in client-side scans, so a full scan doesn't
+    /// crowd the working set out of a production member's cache. The server-side pipeline
+    /// can't be paced
+    pub throttle: Option<u32>,
+    /// Where type inference runs
+    pub strategy: Strategy,
+    /// Select the sample by a hash of each document's `_id` mixed with this seed, rather than
+    /// at random, so runs against the same data infer from the same documents. Sampling is
+    /// then client-side, reading every document in `_id` order and keeping about the sample
+    /// size of them; [`Strategy::Pipeline`] ignores it
+    pub seed: Option<u64>,
+    /// Which documents a sample of fewer than all of them is
+    pub sample_strategy: SampleStrategy,
+    /// Which members to read from, e.g. secondaries only so sampling never loads the primary.
+    /// Defaults to the client's read preference. Not serialized
+    #[serde(skip)]
+    #[cfg(feature = "driver")]
+    pub read_preference: Option<ReadPreference>,
+    /// Told how the analysis is getting on. Not serialized
+    #[serde(skip)]
+    pub progress: Option<ProgressFn>,
+    /// Answer with a recent result of the same analysis kept here, and keep the result
+    /// otherwise. Not serialized
+    #[serde(skip)]
+    #[cfg(feature = "driver")]
+    pub cache: Option<ResultCache>,
+    /// Leave fields matching these paths (`*` and `?` wildcards) out of the schema, along with
+    /// everything nested in them, e.g. `password` or `payment.*`. The paths of array elements
+    /// match with or without their `[]`, so `items.*` covers `items[].sku`. The pipeline
+    /// strategy doesn't descend into them on the server
+    pub exclude_fields: Vec<String>,
+    /// Only report fields matching these paths (`*` and `?` wildcards), everything nested in
+    /// them and the objects and arrays leading to them, e.g. `events.*`. Every field is
+    /// included when empty. Exclusions still apply within included fields. To focus the
+    /// report without changing what is read, see [`AnalysisOptions::field_pattern`]
+    pub include_fields: Vec<String>,
+    /// Only report the fields whose paths match this pattern (`*` and `?` wildcards, `*`
+    /// spanning dots), e.g. `*_at` or `audit.*`, leaving out the objects leading to them.
+    /// Unlike [`AnalysisOptions::include_fields`], which narrows what inference reads, this
+    /// filters the finished inference: the query and every field's counts are the same as
+    /// without it. Element paths match with or without their `[]`
+    pub field_pattern: Option<String>,
+    /// Also group the sampled documents by shape, their exact set of field paths and types,
+    /// and report this many of the most common. Server-side this needs MongoDB 5.2
+    pub shapes: Option<usize>,
+    /// Also inspect the sampled values, not just their types, to catch e.g. dates stored as
+    /// strings. This runs client-side, so with the pipeline a second, smaller random sample
+    /// is read
+    pub profile_values: bool,
+    /// When profiled values are reported as anomalies
+    pub profile_thresholds: ProfileThresholds,
+    /// Keep up to this many distinct example values per field. Profiles values, as with
+    /// [`AnalysisOptions::profile_values`]
+    pub examples: usize,
+    /// How example values, and top values, are made safe to share
+    pub redaction: Redaction,
+    /// Report this many of the most frequent string and integer values per field, with their
+    /// counts. Profiles values, as with [`AnalysisOptions::profile_values`]
+    pub top_values: usize,
+    /// Collect the min, max, mean and percentiles of numeric fields and of the lengths of
+    /// string fields, the range and distribution of date fields, and the subtypes of binary
+    /// fields. With the pipeline they are computed client-side, over the smaller sample
+    /// values are profiled on
+    pub stats: bool,
+    /// With [`AnalysisOptions::stats`], also bucket the values of numeric and date fields
+    /// into histograms this way, see [`Histogram`](crate::Histogram)
+    pub histograms: Option<HistogramScale>,
+    /// How many buckets [`AnalysisOptions::histograms`] have
+    pub histogram_buckets: usize,
+    /// Also report the fields whose names or values look like personal data, see
+    /// [`CollectionSchema::find_pii`](crate::CollectionSchema::find_pii). Profiles values,
+    /// as with [`AnalysisOptions::profile_values`]
+    pub detect_pii: bool,
+    /// Also report the share of each field's occurrences each of its types has, see
+    /// [`FieldSchema::type_shares`](crate::FieldSchema::type_shares)
+    pub type_shares: bool,
+    /// List the fields held by fewer than this fraction of the sampled documents, see
+    /// [`CollectionSchema::find_rare_fields`](crate::CollectionSchema::find_rare_fields)
+    pub rare_fields: f64,
+    /// Also report the fields whose names hold a dot, start with `$`, are empty, very long or
+    /// hold control characters, with the `_id`s of documents having them, see
+    /// [`FieldNameIssue`](crate::FieldNameIssue). With the pipeline they are checked
+    /// client-side, over the smaller sample values are profiled on
+    pub check_field_names: bool,
+    /// Also measure how many bytes each top-level field adds to the sampled documents, see
+    /// [`FieldSizes`](crate::FieldSizes). With the pipeline this is an aggregation of its own
+    /// using `$bsonSize`, which needs MongoDB 4.4
+    pub field_sizes: bool,
+    /// Also report the distribution of the sampled documents' sizes, see
+    /// [`DocumentSizes`](crate::DocumentSizes). With the pipeline this is an aggregation of
+    /// its own using `$bsonSize`, which needs MongoDB 4.4, run alongside the schema's
+    pub document_sizes: bool,
+    /// Also report the distribution of the sampled documents' top-level key counts, see
+    /// [`DocumentWidths`](crate::DocumentWidths). With the pipeline this shares the
+    /// aggregation of [`AnalysisOptions::document_sizes`]
+    pub document_widths: bool,
+    /// Which names types are reported in. [`TypeMode::Canonical`] reports number for every
+    /// numeric type, so a field written as int by one client and long by another isn't mixed
+    pub type_mode: TypeMode,
+    /// Sample up to [`AnalysisOptions::per_stratum`] random documents holding each value of
+    /// this field instead of a random sample of the collection, so rare document variants are
+    /// captured as well as common ones. Reads every document to pick them, and needs
+    /// MongoDB 5.2 for `$topN`. Ignored for files
+    pub stratify_by: Option<String>,
+    /// How many documents [`AnalysisOptions::stratify_by`] samples per value
+    pub per_stratum: u64,
+    /// Sample the documents whose value of this field falls in a range instead of with
+    /// `$sample`, for collections that already store a random number from 0 to 1 in every
+    /// document, e.g. written with `$rand` for sharding. The range covers the sample size's
+    /// share of the documents, starting where [`AnalysisOptions::seed`] puts it or at 0, so
+    /// the same documents are sampled each time without a scan or a sort. It needs an index
+    /// on the field, which would otherwise be scanned for; ignored with
+    /// [`AnalysisOptions::stratify_by`] and for files
+    pub random_key_field: Option<String>,
+    /// Also infer a schema of its own for the documents holding each of the most common
+    /// values of this field, e.g. `type` in a collection of several kinds of event, see
+    /// [`Variants`](crate::Variants). With the pipeline each variant is sampled by an
+    /// aggregation of its own, filtered on the value; an index on the field helps
+    pub group_by: Option<String>,
+    /// Also infer a schema of its own for the documents holding each value of this version
+    /// field, e.g. `schemaVersion`, and diff each version against the one before it, see
+    /// [`Versions`](crate::Versions). Sampled like [`AnalysisOptions::group_by`]
+    pub version_field: Option<String>,
+    /// Also break the sample down by the period of this date (or ObjectId) field, reporting
+    /// when each field first and last appears and how its types changed, see
+    /// [`Evolution`](crate::Evolution)
+    pub time_field: Option<String>,
+    /// The periods [`AnalysisOptions::time_field`] is broken down by
+    pub time_bucket: TimeBucket,
+}
+
+impl AnalysisOptions {
+    /// The field and count of [`AnalysisOptions::stratify_by`], if it's set
+    pub(crate) fn stratify(&self) -> Option<(&str, u64)> {
+        self.stratify_by
+            .as_deref()
+            .map(|field| (field, self.per_stratum))
+    }
+
+    /// Whether the documents are read with a `find` and their types inferred here: always
+    /// with [`Strategy::Client`], and with [`Strategy::Auto`] for samples `$sample` can't take
+    pub(crate) fn samples_client_side(&self) -> bool {
+        match self.strategy {
+            Strategy::Client => true,
+            Strategy::Auto => self.seed.is_some() || self.sample_strategy == SampleStrategy::Recent,
+            Strategy::Pipeline => false,
+        }
+    }
+
+    /// With [`AnalysisOptions::ids`], the options reading every one of those documents: the
+    /// ids become part of the filter and nothing is sampled
+    pub(crate) fn targeted(&self) -> Option<Self> {
+        if self.ids.is_empty() {
+            return None;
+        }
+        let by_id = doc! { "_id": { "$in": self.distinct_ids() } };
+        Some(Self {
+            filter: Some(match &self.filter {
+                Some(filter) => doc! { "$and": [filter.clone(), by_id] },
+                None => by_id,
+            }),
+            ids: Vec::new(),
+            sampling: Sampling::Full,
+            ..self.clone()
+        })
+    }
+
+    /// With [`AnalysisOptions::random_key_field`], the options reading the documents in the
+    /// range of it a sample of `document_count` documents covers: the range becomes part of
+    /// the filter and nothing else is sampled
+    pub(crate) fn ranged(&self, document_count: u64) -> Option<Self> {
+        let field = self.random_key_field.as_deref()?;
+        if self.stratify_by.is_some() {
+            return None;
+        }
+        let size = self
+            .sampling
+            .size(document_count)
+            .filter(|size| *size < document_count)?;
+        let share = size as f64 / document_count as f64;
+        let start = self.seed.map_or(0.0, |seed| {
+            let digest = Sha256::digest(seed.to_le_bytes());
+            u64::from_le_bytes(digest[..8].try_into().unwrap()) as f64 / u64::MAX as f64
+        });
+        let end = start + share;
+        // a range running past 1 carries on from 0
+        let range = if end > 1.0 {
+            doc! { "$or": [{ field: { "$gte": start } }, { field: { "$lt": end - 1.0 } }] }
+        } else {
+            doc! { field: { "$gte": start, "$lt": end } }
+        };
+        Some(Self {
+            filter: Some(match &self.filter {
+                Some(filter) => doc! { "$and": [filter.clone(), range] },
+                None => range,
+            }),
+            random_key_field: None,
+            seed: None,
+            sampling: Sampling::Full,
+            ..self.clone()
+        })
+    }
+
+    /// [`AnalysisOptions::ids`] without repeats, in their order
+    pub(crate) fn distinct_ids(&self) -> Vec<Bson> {
+        let mut ids = Vec::new();
+        for id in &self.ids {
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
+        ids
+    }
+
+    /// Whether values are looked at, not just their types
+    pub(crate) fn profiles_values(&self) -> bool {
+        self.profile_values || self.examples > 0 || self.top_values > 0 || self.detect_pii
+    }
+
+    /// Whether the field at `path`, or an object or array it is nested in, is excluded
+    pub(crate) fn excludes(&self, path: &str) -> bool {
+        ancestors(path).any(|ancestor| self.exclude_fields.iter().any(|p| path_match(p, ancestor)))
+    }
+
+    /// Whether the field at `path` isn't excluded and, if only some fields are included,
+    /// it or an object or array it is nested in is
+    pub(crate) fn includes(&self, path: &str) -> bool {
+        !self.excludes(path)
+            && (self.include_fields.is_empty()
+                || ancestors(path)
+                    .any(|ancestor| self.include_fields.iter().any(|p| path_match(p, ancestor))))
+    }
+
+    /// Which of `paths` to report: the included ones and the objects and arrays leading to
+    /// them, narrowed down to those matching [`AnalysisOptions::field_pattern`]
+    pub(crate) fn reported_paths<'a>(
+        &self,
+        paths: impl IntoIterator<Item = &'a String>,
+    ) -> BTreeSet<String> {
+        let mut reported = BTreeSet::new();
+        for path in paths.into_iter().filter(|path| self.includes(path)) {
+            reported.extend(ancestors(path).map(str::to_string));
+        }
+        if let Some(pattern) = &self.field_pattern {
+            reported.retain(|path| path_match(pattern, path));
+        }
+        reported
+    }
+}
+
+/// `path` and every object and array it is nested in: `a.b[].c` is inside `a.b[]`, `a.b`
+/// and `a`
+pub(crate) fn ancestors(path: &str) -> impl Iterator<Item = &str> {
+    path.match_indices(['.', '['])
+        .map(|(i, _)| &path[..i])
+        .chain([path])
+}
+
+/// An index named or given by its key pattern, as a query's hint takes it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IndexHint {
+    /// e.g. `tenant_1_createdAt_-1`
+    Name(String),
+    /// e.g. `{"tenant": 1, "createdAt": -1}`
+    Keys(Document),
+}
+
+/// How many of a collection's documents to analyze
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Sampling {
+    /// The larger of 10000 documents or a third of the estimated document count
+    #[default]
+    Auto,
+    /// Exactly this many documents, or all of them if there are fewer
+    Size(u64),
+    /// This percentage (0 to 100) of the estimated document count
+    Percent(f64),
+    /// Every document, without `$sample`. Exact, but reads the whole collection
+    Full,
+}
+
+impl Sampling {
+    /// How many documents to sample from a collection of about `document_count`, or `None`
+    /// to read them all
+    pub(crate) fn size(self, document_count: u64) -> Option<u64> {
+        match self {
+            Sampling::Auto => Some(default_sample_size(document_count)),
+            Sampling::Size(size) => Some(size),
+            Sampling::Percent(percent) => {
+                Some((document_count as f64 * percent / 100.0).ceil() as u64)
+            }
+            Sampling::Full => None,
+        }
+    }
+}
+
+/// Where the schema is inferred
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Strategy {
+    /// Run the aggregation pipeline, falling back to [`Strategy::Client`] if the server
+    /// rejects it
+    #[default]
+    Auto,
+    /// Only use the aggregation pipeline, which needs `$sample` and `$objectToArray`
+    Pipeline,
+    /// Read documents with a plain `find` and infer their types here. Works against older
+    /// servers and API-compatible services, but the sample is the first documents in natural
+    /// order rather than a random one, and every document crosses the network
+    Client,
+}
+
+/// Which documents a sample is, when it isn't every one of them
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SampleStrategy {
+    /// Documents picked at random
+    #[default]
+    Random,
+    /// The newest documents, last in natural order, read client-side by a reverse `find`.
+    /// Suits capped collections and other rolling windows, where a random sample is mostly
+    /// documents about to be deleted. [`Strategy::Pipeline`] ignores it, as does a seeded or
+    /// stratified sample
+    Recent,
+}
+
+/// How finely types are told apart
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TypeMode {
+    /// The BSON type names `$type` reports
+    #[default]
+    Strict,
+    /// Closer to JSON Schema's types: int, long, double and decimal are all number, and the
+    /// deprecated symbol, undefined and javascriptWithScope are string, null and javascript
+    Canonical,
+}
+
+impl TypeMode {
+    pub(crate) fn is_strict(&self) -> bool {
+        *self == TypeMode::Strict
+    }
+}
+
+/// The periods a sample is broken down by to follow its fields over time, in UTC
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeBucket {
+    /// `2024-03-17`
+    Day,
+    /// `2024-03`
+    #[default]
+    Month,
+    /// `2024`
+    Year,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            sampling: Sampling::default(),
+            filter: None,
+            ids: Vec::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            max_array_elements: None,
+            max_fields_per_level: None,
+            comment: format!("schema-analyzer v{}", env!("CARGO_PKG_VERSION")),
+            collation: None,
+            hint: None,
+            allow_disk_use: false,
+            max_time: None,
+            batch_size: None,
+            retries: 3,
+            throttle: None,
+            strategy: Strategy::default(),
+            seed: None,
+            sample_strategy: SampleStrategy::default(),
+            #[cfg(feature = "driver")]
+            read_preference: None,
+            progress: None,
+            #[cfg(feature = "driver")]
+            cache: None,
+            exclude_fields: Vec::new(),
+            include_fields: Vec::new(),
+            field_pattern: None,
+            shapes: None,
+            profile_values: false,
+            profile_thresholds: ProfileThresholds::default(),
+            examples: 0,
+            redaction: Redaction::default(),
+            top_values: 0,
+            stats: true,
+            histograms: None,
+            histogram_buckets: 20,
+            detect_pii: false,
+            type_shares: false,
+            rare_fields: RARE_PRESENCE,
+            check_field_names: false,
+            field_sizes: false,
+            document_sizes: false,
+            document_widths: false,
+            type_mode: TypeMode::default(),
+            stratify_by: None,
+            per_stratum: 500,
+            random_key_field: None,
+            group_by: None,
+            version_field: None,
+            time_field: None,
+            time_bucket: TimeBucket::default(),
+        }
+    }
+}
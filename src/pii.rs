@@ -0,0 +1,309 @@
+//! Heuristics for fields that likely hold personal data, going by their names and by what
+//! their sampled values look like. They are guesses meant for an inventory to review, not a
+//! guarantee that other fields are clean
+
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::CollectionSchema;
+
+/// Fields are reported by value when at least this share of their strings look like one kind
+/// of personal data
+const PII_VALUES: f64 = 0.5;
+
+/// A kind of personal data
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PiiKind {
+    Email,
+    Phone,
+    /// A US social security number
+    Ssn,
+    IpAddress,
+    CreditCard,
+}
+
+impl fmt::Display for PiiKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            PiiKind::Email => "email address",
+            PiiKind::Phone => "phone number",
+            PiiKind::Ssn => "social security number",
+            PiiKind::IpAddress => "IP address",
+            PiiKind::CreditCard => "credit card number",
+        })
+    }
+}
+
+/// A field that likely holds personal data, found by [`CollectionSchema::find_pii`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PiiFinding {
+    pub path: String,
+    pub kind: PiiKind,
+    /// The field's name suggests `kind`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub by_name: bool,
+    /// The share of the field's strings that look like `kind`, if enough of them do
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fraction: Option<f64>,
+}
+
+impl fmt::Display for PiiFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} (", self.path, self.kind)?;
+        if self.by_name {
+            f.write_str("by name")?;
+        }
+        if let Some(fraction) = self.fraction {
+            if self.by_name {
+                f.write_str(", ")?;
+            }
+            write!(f, "{:.1}% of values match", fraction * 100.0)?;
+        }
+        f.write_str(")")
+    }
+}
+
+impl CollectionSchema {
+    /// Find the fields whose names or profiled values look like personal data. Values are
+    /// only looked at for fields profiled with
+    /// [`AnalysisOptions::profile_values`](crate::AnalysisOptions::profile_values)
+    pub fn find_pii(&self) -> Vec<PiiFinding> {
+        let mut findings = Vec::new();
+        for (path, field) in &self.fields {
+            let named = name_kind(path);
+            let matched = field
+                .values
+                .iter()
+                .flat_map(|values| {
+                    values.pii.iter().filter_map(|(kind, count)| {
+                        let fraction = *count as f64 / values.strings as f64;
+                        (fraction >= PII_VALUES).then_some((*kind, fraction))
+                    })
+                })
+                .collect::<Vec<_>>();
+            if let Some(kind) = named {
+                if !matched.iter().any(|(matched, _)| *matched == kind) {
+                    findings.push(PiiFinding {
+                        path: path.clone(),
+                        kind,
+                        by_name: true,
+                        fraction: None,
+                    });
+                }
+            }
+            for (kind, fraction) in matched {
+                findings.push(PiiFinding {
+                    path: path.clone(),
+                    kind,
+                    by_name: named == Some(kind),
+                    fraction: Some(fraction),
+                });
+            }
+        }
+        findings
+    }
+}
+
+/// Field names, lowercased and without separators, that suggest each kind. The longer ones
+/// also count at the end of a name, as in `contactEmail` or `billing_phone_number`
+const NAMES: &[(PiiKind, &[&str])] = &[
+    (PiiKind::Email, &["email", "emailaddress", "mail"]),
+    (
+        PiiKind::Phone,
+        &[
+            "phone",
+            "phonenumber",
+            "mobile",
+            "cellphone",
+            "telephone",
+            "tel",
+            "fax",
+        ],
+    ),
+    (
+        PiiKind::Ssn,
+        &["ssn", "socialsecurity", "socialsecuritynumber"],
+    ),
+    (
+        PiiKind::IpAddress,
+        &["ip", "ipaddress", "ipaddr", "remoteaddr", "clientip"],
+    ),
+    (
+        PiiKind::CreditCard,
+        &[
+            "creditcard",
+            "creditcardnumber",
+            "cardnumber",
+            "ccnumber",
+            "pan",
+        ],
+    ),
+];
+
+/// The kind of personal data the last segment of `path` is named after, if any
+fn name_kind(path: &str) -> Option<PiiKind> {
+    let name = path.rsplit('.').next().unwrap_or(path);
+    let name = name
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect::<String>()
+        .to_ascii_lowercase();
+    NAMES.iter().find_map(|(kind, names)| {
+        names
+            .iter()
+            .any(|n| name == *n || (n.len() >= 5 && name.ends_with(n)))
+            .then_some(*kind)
+    })
+}
+
+/// The kind of personal data `s` looks like, if any
+pub(crate) fn value_kind(s: &str) -> Option<PiiKind> {
+    let s = s.trim();
+    if is_ssn(s) {
+        Some(PiiKind::Ssn)
+    } else if is_card_number(s) {
+        Some(PiiKind::CreditCard)
+    } else if s.parse::<Ipv4Addr>().is_ok() || s.parse::<Ipv6Addr>().is_ok() {
+        Some(PiiKind::IpAddress)
+    } else if is_email(s) {
+        Some(PiiKind::Email)
+    } else if is_phone(s) {
+        Some(PiiKind::Phone)
+    } else {
+        None
+    }
+}
+
+/// `local@domain.tld`, without whitespace
+fn is_email(s: &str) -> bool {
+    let Some((local, domain)) = s.split_once('@') else {
+        return false;
+    };
+    !local.is_empty()
+        && !s.contains(char::is_whitespace)
+        && !domain.contains('@')
+        && domain.contains('.')
+        && domain.split('.').all(|label| !label.is_empty())
+}
+
+/// `ddd-dd-dddd`, leaving out the area and group numbers that are never issued
+fn is_ssn(s: &str) -> bool {
+    let parts = s.split('-').collect::<Vec<_>>();
+    let [area, group, serial] = parts[..] else {
+        return false;
+    };
+    let digits = |part: &str, len| part.len() == len && part.bytes().all(|c| c.is_ascii_digit());
+    digits(area, 3)
+        && digits(group, 2)
+        && digits(serial, 4)
+        && area != "000"
+        && area != "666"
+        && !area.starts_with('9')
+        && group != "00"
+        && serial != "0000"
+}
+
+/// 13 to 19 digits, optionally grouped by spaces or dashes, passing the Luhn check
+fn is_card_number(s: &str) -> bool {
+    if !s
+        .bytes()
+        .all(|c| c.is_ascii_digit() || c == b' ' || c == b'-')
+    {
+        return false;
+    }
+    let digits = s
+        .bytes()
+        .filter(u8::is_ascii_digit)
+        .map(|c| u32::from(c - b'0'))
+        .collect::<Vec<_>>();
+    if !(13..=19).contains(&digits.len()) {
+        return false;
+    }
+    let sum = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, &d)| match (i % 2, d * 2) {
+            (0, _) => d,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum::<u32>();
+    sum % 10 == 0
+}
+
+/// 10 to 15 digits with the usual punctuation, and either a leading `+` or some punctuation,
+/// so plain numeric ids don't count
+fn is_phone(s: &str) -> bool {
+    let rest = s.strip_prefix('+').unwrap_or(s);
+    if !rest
+        .bytes()
+        .all(|c| c.is_ascii_digit() || b" -.()".contains(&c))
+    {
+        return false;
+    }
+    let digits = rest.bytes().filter(u8::is_ascii_digit).count();
+    (10..=15).contains(&digits) && (rest.len() < s.len() || digits < rest.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    #[test]
+    fn recognizes_personal_data_by_its_shape() {
+        assert_eq!(value_kind("ada@example.com"), Some(PiiKind::Email));
+        assert_eq!(value_kind("+44 20 7946 0958"), Some(PiiKind::Phone));
+        assert_eq!(value_kind("123-45-6789"), Some(PiiKind::Ssn));
+        assert_eq!(value_kind("192.168.0.1"), Some(PiiKind::IpAddress));
+        assert_eq!(value_kind("::1"), Some(PiiKind::IpAddress));
+        assert_eq!(value_kind("4111 1111 1111 1111"), Some(PiiKind::CreditCard));
+        // never issued, failing the Luhn check, or a plain numeric id
+        assert_eq!(value_kind("666-45-6789"), None);
+        assert_eq!(value_kind("4111 1111 1111 1112"), None);
+        assert_eq!(value_kind("1234567890"), None);
+        assert_eq!(value_kind("not @ an email"), None);
+    }
+
+    #[test]
+    fn finds_fields_by_name_and_by_value() {
+        let options = AnalysisOptions {
+            detect_pii: true,
+            ..AnalysisOptions::default()
+        };
+        let ndjson = "{\"contactEmail\": \"ada@example.com\", \"note\": \"123-45-6789\", \
+                      \"billing_phone\": 1, \"tel\": \"x\", \"hotel\": \"y\"}\n\
+                      {\"contactEmail\": \"grace@example.com\", \"note\": \"219-09-9999\"}\n";
+        let schema = CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options).unwrap();
+        let findings = schema.find_pii();
+        let email = findings
+            .iter()
+            .find(|finding| finding.path == "contactEmail")
+            .unwrap();
+        assert_eq!(
+            (email.kind, email.by_name, email.fraction),
+            (PiiKind::Email, true, Some(1.0))
+        );
+        let note = findings
+            .iter()
+            .find(|finding| finding.path == "note")
+            .unwrap();
+        assert_eq!((note.kind, note.by_name), (PiiKind::Ssn, false));
+        assert_eq!(
+            note.to_string(),
+            "note: social security number (100.0% of values match)"
+        );
+        assert!(findings
+            .iter()
+            .any(|finding| finding.path == "billing_phone" && finding.by_name));
+        // short names only count whole, so a hotel isn't a phone
+        assert!(findings.iter().any(|finding| finding.path == "tel"));
+        assert!(!findings.iter().any(|finding| finding.path == "hotel"));
+    }
+}
@@ -0,0 +1,1028 @@
+use std::collections::BTreeMap;
+
+use bson::{doc, Bson, Document};
+
+use crate::{
+    evolution::PeriodCounts,
+    glob::{glob_prefix, glob_regex},
+    schema::{BsonType, DocumentShape, ElementPresence, FieldSchema, Shapes},
+    AnalysisOptions, AnalyzerError, Result, TimeBucket,
+};
+
+/// Sample size used when the collection is small or the caller doesn't specify one
+pub(crate) const DEFAULT_SAMPLE_SIZE: u64 = 10000;
+
+/// Sample size is the max of the default sample size or 1/3 the document count.
+/// It seems scientific enough, and [`SchemaAnalyzer::benchmark`](crate::SchemaAnalyzer::benchmark)
+/// measures how other sizes compare on a given collection
+pub(crate) fn default_sample_size(document_count: u64) -> u64 {
+    f64::max(DEFAULT_SAMPLE_SIZE as f64, document_count as f64 / 3.0).round() as u64
+}
+
+/// Most documents read for [`AnalysisOptions::profile_values`](crate::AnalysisOptions::profile_values)
+/// when the schema itself is inferred by the pipeline
+pub(crate) const PROFILE_SAMPLE_SIZE: u64 = 1000;
+
+/// How many levels of subdocuments and arrays are descended into unless the caller says otherwise
+pub const DEFAULT_MAX_DEPTH: u32 = 10;
+
+/// How deep [`flatten_stages`] descends into documents, how much of each level it takes, and
+/// which fields it leaves out, as the options say
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Descent<'a> {
+    pub max_depth: u32,
+    pub max_array_elements: Option<usize>,
+    pub max_fields_per_level: Option<usize>,
+    pub exclude_fields: &'a [String],
+    pub include_fields: &'a [String],
+}
+
+impl<'a> Descent<'a> {
+    pub(crate) fn of(options: &'a AnalysisOptions) -> Self {
+        Descent {
+            max_depth: options.max_depth.max(1),
+            max_array_elements: options.max_array_elements,
+            max_fields_per_level: options.max_fields_per_level,
+            exclude_fields: &options.exclude_fields,
+            include_fields: &options.include_fields,
+        }
+    }
+
+    /// The condition on a `$$pair` of the frontier for it to be descended into, if any
+    /// fields are left out. Excluded paths are dropped with everything under them. Only
+    /// paths that can lead to an included one, or lie under one, are kept, judged by the
+    /// patterns' text up to their first wildcard, so a pattern starting with one keeps
+    /// everything. What's kept is narrowed down to the included paths after the sampling.
+    /// Paths are matched as [`path_match`](crate::glob::path_match) does, with and without their `[]` segments
+    fn kept(&self) -> Option<Bson> {
+        let mut conditions = Vec::new();
+        if !self.exclude_fields.is_empty() {
+            let excluded = self
+                .exclude_fields
+                .iter()
+                .flat_map(|pattern| {
+                    let regex = glob_regex(pattern);
+                    keys().map(move |key| {
+                        Bson::Document(doc! {
+                            "$regexMatch": { "input": key, "regex": &regex, "options": "s" }
+                        })
+                    })
+                })
+                .collect::<Vec<_>>();
+            conditions.push(Bson::Document(doc! { "$not": [{ "$or": excluded }] }));
+        }
+        let prefixes = self
+            .include_fields
+            .iter()
+            .map(|pattern| glob_prefix(pattern))
+            .collect::<Vec<_>>();
+        if !prefixes.is_empty() && prefixes.iter().all(|prefix| !prefix.is_empty()) {
+            let leading = prefixes
+                .into_iter()
+                .flat_map(|prefix| {
+                    // the path starts the prefix, or the prefix starts the path
+                    keys().flat_map(move |key| {
+                        [
+                            doc! { "$eq": [{ "$indexOfCP": [prefix, key.clone()] }, 0] },
+                            doc! { "$eq": [{ "$indexOfCP": [key, prefix] }, 0] },
+                        ]
+                    })
+                })
+                .map(Bson::Document)
+                .collect::<Vec<_>>();
+            conditions.push(Bson::Document(doc! { "$or": leading }));
+        }
+        match conditions.len() {
+            0 => None,
+            1 => conditions.pop(),
+            _ => Some(Bson::Document(doc! { "$and": conditions })),
+        }
+    }
+}
+
+/// A frontier pair's path as written and with its `[]` segments dropped
+fn keys() -> impl Iterator<Item = Bson> {
+    [
+        Bson::String("$$pair.k".to_string()),
+        Bson::Document(doc! {
+            "$replaceAll": { "input": "$$pair.k", "find": "[]", "replacement": "" }
+        }),
+    ]
+    .into_iter()
+}
+
+/// The type recorded, besides its real one, for a value whose fields or elements were not all
+/// descended into. `$type` never reports it. The path `""` stands for the document itself
+pub(crate) const TRUNCATED: &str = "truncated";
+
+/// Build the aggregation that reduces a sample of the collection to one document holding the
+/// number of documents sampled, how many of them contain each field path, how many hold
+/// each type for it, and how many array elements hold each path inside arrays. See
+/// [`parse_result`].
+///
+/// Subdocuments and arrays are descended into as `descent` says: until `max_depth` levels have
+/// been reported, taking only the first `max_array_elements` elements of arrays and
+/// `max_fields_per_level` fields of documents and subdocuments, and leaving out the excluded
+/// fields. Values cut short are marked [`TRUNCATED`].
+/// Nested fields are named by their dotted path (`address.city`) and array elements by the
+/// array's path with a `[]` suffix (`tags[]`, `items[].sku`). Without a `sample_size` every
+/// document is analyzed, and with a `filter` only the documents matching it. With `stratify`
+/// the sample is that of [`sample_stages`] instead. With `shapes` the result also holds that
+/// many of the most common document shapes, see [`parse_shapes`]
+pub(crate) fn schema_pipeline(
+    filter: Option<&Document>,
+    sample_size: Option<u64>,
+    stratify: Option<(&str, u64)>,
+    descent: Descent<'_>,
+    shapes: Option<usize>,
+) -> Vec<Document> {
+    let mut pipeline = sample_stages(filter, sample_size, stratify);
+    pipeline.extend(flatten_stages(descent, None, true));
+    // count the sampled documents, how many contain each field, and how many hold each type per field
+    let mut facet = doc! {
+        "documents": [
+            { "$count": "count" }
+        ],
+        "presence": [
+            // array elements can put several types under one path, so only count each path once
+            { "$project": { "keys": { "$setUnion": ["$schema.k"] } } },
+            { "$unwind": "$keys" },
+            { "$group": { "_id": "$keys", "count": { "$sum": 1 } } }
+        ],
+        // each element counting, for the share of an array's subdocuments holding a field
+        "elements": [
+            { "$unwind": "$elements" },
+            {
+                "$group": {
+                    "_id": "$elements.k",
+                    "values": { "$sum": 1 },
+                    "objects": {
+                        "$sum": { "$cond": [{ "$eq": ["$elements.v", "object"] }, 1, 0] }
+                    }
+                }
+            }
+        ],
+        "types": [
+            // we now have a document for each field and type
+            { "$unwind": "$schema" },
+            { "$group": { "_id": "$schema", "count": { "$sum": 1 } } },
+            // group by the key, collecting the count of each type
+            {
+                "$group": {
+                    "_id": "$_id.k",
+                    "types": { "$push": { "type": "$_id.v", "count": "$count" } }
+                }
+            }
+        ]
+    };
+    if let Some(top) = shapes {
+        facet.insert(
+            "shapes",
+            vec![
+                // the pairs are unique but unordered, so sort them to compare shapes. Being cut
+                // short doesn't change a document's shape
+                doc! {
+                    "$group": {
+                        "_id": {
+                            "$sortArray": {
+                                "input": {
+                                    "$filter": {
+                                        "input": "$schema",
+                                        "cond": { "$ne": ["$$this.v", TRUNCATED] }
+                                    }
+                                },
+                                "sortBy": { "k": 1, "v": 1 }
+                            }
+                        },
+                        "count": { "$sum": 1 }
+                    }
+                },
+                doc! { "$sort": { "count": -1 } },
+                doc! {
+                    "$group": {
+                        "_id": null,
+                        "distinct": { "$sum": 1 },
+                        "top": {
+                            "$firstN": {
+                                "input": { "shape": "$_id", "count": "$count" },
+                                "n": Bson::Int64(top.max(1) as i64)
+                            }
+                        }
+                    }
+                },
+            ],
+        );
+    }
+    pipeline.push(doc! { "$facet": facet });
+    pipeline
+}
+
+/// The stages selecting the documents to analyze from those matching `filter`: a random
+/// sample of `sample_size`, or every document without one. With `stratify`, a field's path and
+/// a count, up to that many random documents holding each value of the field instead, so rare
+/// values are as well represented as common ones. That reads every matching document
+pub(crate) fn sample_stages(
+    filter: Option<&Document>,
+    sample_size: Option<u64>,
+    stratify: Option<(&str, u64)>,
+) -> Vec<Document> {
+    let mut pipeline = Vec::new();
+    if let Some(filter) = filter {
+        pipeline.push(doc! { "$match": filter.clone() });
+    }
+    match (stratify, sample_size) {
+        (Some((field, per_stratum)), _) => pipeline.extend([
+            doc! { "$set": { STRATUM_RANK: { "$rand": {} } } },
+            // a missing field is a stratum of its own along with null, as in `$group`
+            doc! {
+                "$group": {
+                    "_id": format!("${}", field),
+                    "documents": {
+                        "$topN": {
+                            "n": Bson::Int64(per_stratum.max(1) as i64),
+                            "sortBy": { STRATUM_RANK: 1 },
+                            "output": "$$ROOT"
+                        }
+                    }
+                }
+            },
+            doc! { "$unwind": "$documents" },
+            doc! { "$replaceWith": "$documents" },
+            doc! { "$unset": STRATUM_RANK },
+        ]),
+        (None, Some(sample_size)) => {
+            // start with an adequate sample of the collection
+            pipeline.push(doc! { "$sample": { "size": Bson::Int64(sample_size as i64) } });
+        }
+        (None, None) => {}
+    }
+    pipeline
+}
+
+/// The field [`sample_stages`] orders each stratum's documents randomly by, removed again
+/// before they're analyzed
+const STRATUM_RANK: &str = "__schema_analyzer_rank";
+
+/// The aggregation returning a random sample of at most `size` documents to profile the values
+/// of, from those matching `filter`
+pub(crate) fn profile_pipeline(filter: Option<&Document>, size: u64) -> Vec<Document> {
+    let mut pipeline = Vec::new();
+    if let Some(filter) = filter {
+        pipeline.push(doc! { "$match": filter.clone() });
+    }
+    pipeline.push(doc! { "$sample": { "size": Bson::Int64(size as i64) } });
+    pipeline
+}
+
+/// Stages converting each document to an array of `{ k: path, v: type }` pairs, one stage per
+/// level of nesting.
+///
+/// Each stage records the types of the `frontier` (the key-value pairs found at the previous
+/// level) and replaces it with their children: the fields of subdocuments and the elements of
+/// arrays. Working a level at a time keeps the pipeline linear in `max_depth`, where a single
+/// recursive expression would have to repeat itself for both objects and arrays at every level.
+/// Only the first `max_fields_per_level` fields and `max_array_elements` elements become
+/// children, and values that lose some are recorded as [`TRUNCATED`] too, as are the non-empty
+/// subdocuments and arrays of the last level. Pairs `descent` leaves out never join the
+/// frontier, so nothing under them is looked at either. A `period` expression is evaluated
+/// against the document and carried along beside the pairs. With `elements`, the pairs of
+/// paths inside arrays are also kept in `elements` before they are made unique, one for each
+/// element holding the path
+fn flatten_stages(descent: Descent<'_>, period: Option<Document>, elements: bool) -> Vec<Document> {
+    let Descent {
+        max_depth,
+        max_array_elements,
+        max_fields_per_level,
+        ..
+    } = descent;
+    let kept = descent.kept();
+    let keep = |input: Bson| match &kept {
+        Some(cond) => Bson::Document(doc! {
+            "$filter": { "input": input, "as": "pair", "cond": cond.clone() }
+        }),
+        None => input,
+    };
+    let limit = |input: Bson, limit: Option<usize>| match limit {
+        Some(n) => Bson::Document(doc! { "$slice": [input, Bson::Int64(n as i64)] }),
+        None => input,
+    };
+    let typed_frontier = doc! {
+        "$map": {
+            "input": "$frontier",
+            "as": "field",
+            "in": {
+                "k": "$$field.k",
+                "v": { "$type": "$$field.v" }
+            }
+        }
+    };
+    let children = doc! {
+        "$reduce": {
+            "input": "$frontier",
+            "initialValue": [],
+            "in": {
+                "$concatArrays": ["$$value", {
+                    "$switch": {
+                        "branches": [
+                            {
+                                "case": { "$eq": [{ "$type": "$$this.v" }, "object"] },
+                                "then": {
+                                    "$map": {
+                                        "input": limit(
+                                            doc! { "$objectToArray": "$$this.v" }.into(),
+                                            max_fields_per_level,
+                                        ),
+                                        "as": "child",
+                                        "in": {
+                                            "k": { "$concat": ["$$this.k", ".", "$$child.k"] },
+                                            "v": "$$child.v"
+                                        }
+                                    }
+                                }
+                            },
+                            {
+                                "case": { "$eq": [{ "$type": "$$this.v" }, "array"] },
+                                "then": {
+                                    "$map": {
+                                        "input": limit("$$this.v".into(), max_array_elements),
+                                        "as": "element",
+                                        "in": {
+                                            "k": { "$concat": ["$$this.k", "[]"] },
+                                            "v": "$$element"
+                                        }
+                                    }
+                                }
+                            }
+                        ],
+                        "default": []
+                    }
+                }]
+            }
+        }
+    };
+
+    // the frontier values that don't pass all their fields or elements on: those over the
+    // limits, or at the last level every non-empty one
+    let truncated_frontier = |last: bool| {
+        let cut = |limit: Option<usize>| if last { Some(0) } else { limit };
+        let mut branches = Vec::new();
+        if let Some(n) = cut(max_fields_per_level) {
+            branches.push(doc! {
+                "case": { "$eq": [{ "$type": "$$field.v" }, "object"] },
+                "then": { "$gt": [{ "$size": { "$objectToArray": "$$field.v" } }, Bson::Int64(n as i64)] }
+            });
+        }
+        if let Some(n) = cut(max_array_elements) {
+            branches.push(doc! {
+                "case": { "$eq": [{ "$type": "$$field.v" }, "array"] },
+                "then": { "$gt": [{ "$size": "$$field.v" }, Bson::Int64(n as i64)] }
+            });
+        }
+        if branches.is_empty() {
+            return Bson::Array(Vec::new());
+        }
+        Bson::Document(doc! {
+            "$map": {
+                "input": {
+                    "$filter": {
+                        "input": "$frontier",
+                        "as": "field",
+                        "cond": { "$switch": { "branches": branches, "default": false } }
+                    }
+                },
+                "as": "field",
+                "in": { "k": "$$field.k", "v": TRUNCATED }
+            }
+        })
+    };
+    let truncated_root = match max_fields_per_level {
+        Some(n) => doc! {
+            "$cond": [
+                { "$gt": [{ "$size": { "$objectToArray": "$$ROOT" } }, Bson::Int64(n as i64)] },
+                [{ "k": "", "v": TRUNCATED }],
+                []
+            ]
+        },
+        None => doc! { "$literal": [] },
+    };
+
+    // convert the document to an array of key-value pairs. The key is the field name, the value is the field
+    // itself until its type has been recorded
+    let mut first = doc! {
+        "_id": 0,
+        "schema": truncated_root,
+        "frontier": keep(limit(
+            doc! { "$objectToArray": "$$ROOT" }.into(),
+            max_fields_per_level
+        ))
+    };
+    let carried = period.is_some();
+    if let Some(period) = period {
+        first.insert("period", period);
+    }
+    let mut stages = vec![doc! { "$project": first }];
+    let project = |mut stage: Document| {
+        if carried {
+            stage.insert("period", 1);
+        }
+        doc! { "$project": stage }
+    };
+    for _ in 1..max_depth {
+        stages.push(project(doc! {
+            "schema": {
+                "$concatArrays": ["$schema", typed_frontier.clone(), truncated_frontier(false)]
+            },
+            "frontier": keep(children.clone().into())
+        }));
+    }
+    // record the last level. Many array elements share a path and type, so only keep unique pairs
+    let pairs = doc! { "$concatArrays": ["$schema", typed_frontier, truncated_frontier(true)] };
+    if !elements {
+        stages.push(project(doc! { "schema": { "$setUnion": [pairs] } }));
+        return stages;
+    }
+    stages.push(project(doc! { "schema": pairs }));
+    stages.push(project(doc! {
+        "schema": { "$setUnion": ["$schema"] },
+        "elements": {
+            "$filter": {
+                "input": "$schema",
+                "as": "pair",
+                "cond": {
+                    "$and": [
+                        { "$ne": [{ "$indexOfCP": ["$$pair.k", "[]"] }, -1] },
+                        { "$ne": ["$$pair.v", TRUNCATED] }
+                    ]
+                }
+            }
+        }
+    }));
+    stages
+}
+
+/// Build the aggregation that breaks a sample down by the `bucket` period of the date (or
+/// ObjectId) at `time_field`, counting the documents in each period and how many of them hold
+/// each type at each path. Sampling and descent follow [`schema_pipeline`]; documents
+/// without a date fall in a null period. See [`parse_evolution`]
+pub(crate) fn evolution_pipeline(
+    filter: Option<&Document>,
+    sample_size: Option<u64>,
+    descent: Descent<'_>,
+    time_field: &str,
+    bucket: TimeBucket,
+) -> Vec<Document> {
+    let mut pipeline = Vec::new();
+    if let Some(filter) = filter {
+        pipeline.push(doc! { "$match": filter.clone() });
+    }
+    if let Some(sample_size) = sample_size {
+        pipeline.push(doc! { "$sample": { "size": Bson::Int64(sample_size as i64) } });
+    }
+    let period = doc! {
+        "$let": {
+            "vars": { "time": format!("${}", time_field) },
+            "in": {
+                "$switch": {
+                    "branches": [
+                        {
+                            "case": { "$eq": [{ "$type": "$$time" }, "date"] },
+                            "then": { "$dateToString": { "format": bucket.format(), "date": "$$time" } }
+                        },
+                        {
+                            "case": { "$eq": [{ "$type": "$$time" }, "objectId"] },
+                            "then": {
+                                "$dateToString": {
+                                    "format": bucket.format(),
+                                    "date": { "$toDate": "$$time" }
+                                }
+                            }
+                        }
+                    ],
+                    "default": null
+                }
+            }
+        }
+    };
+    pipeline.extend(flatten_stages(descent, Some(period), false));
+    pipeline.push(doc! {
+        "$facet": {
+            "documents": [
+                { "$group": { "_id": "$period", "count": { "$sum": 1 } } }
+            ],
+            "types": [
+                { "$unwind": "$schema" },
+                { "$match": { "schema.v": { "$ne": TRUNCATED } } },
+                {
+                    "$group": {
+                        "_id": { "period": "$period", "k": "$schema.k", "v": "$schema.v" },
+                        "count": { "$sum": 1 }
+                    }
+                }
+            ]
+        }
+    });
+    pipeline
+}
+
+/// Read the output of [`evolution_pipeline`] into the counts of each period, and how many
+/// documents had no date
+pub(crate) fn parse_evolution(result: &Document) -> Result<(BTreeMap<String, PeriodCounts>, u64)> {
+    let mut periods = BTreeMap::<String, PeriodCounts>::new();
+    let mut undated = 0;
+    for entry in array(result, "documents")? {
+        let entry = document(entry)?;
+        match entry.get_str("_id") {
+            Ok(period) => {
+                periods.entry(period.to_string()).or_default().documents = count_of(entry)?
+            }
+            Err(_) => undated = count_of(entry)?,
+        }
+    }
+    for entry in array(result, "types")? {
+        let entry = document(entry)?;
+        let id = entry
+            .get_document("_id")
+            .map_err(|_| malformed("expected a period, path and type".to_string()))?;
+        // the types of undated documents aren't followed
+        let Ok(period) = id.get_str("period") else {
+            continue;
+        };
+        *periods
+            .entry(period.to_string())
+            .or_default()
+            .types
+            .entry(string(id, "k")?.to_string())
+            .or_default()
+            .entry(BsonType::from_name(string(id, "v")?))
+            .or_default() += count_of(entry)?;
+    }
+    Ok((periods, undated))
+}
+
+/// What the output of [`schema_pipeline`] says about the sample
+#[derive(Debug, Default)]
+pub(crate) struct Parsed {
+    /// How many documents were sampled
+    pub documents: u64,
+    pub fields: BTreeMap<String, FieldSchema>,
+    /// How many documents had top-level fields left out
+    pub truncated_documents: u64,
+}
+
+/// Read the output of [`schema_pipeline`]
+pub(crate) fn parse_result(result: &Document) -> Result<Parsed> {
+    let documents = match array(result, "documents")?.first() {
+        Some(count) => count_of(document(count)?)?,
+        None => 0,
+    };
+
+    let mut presence = BTreeMap::new();
+    for entry in array(result, "presence")? {
+        let entry = document(entry)?;
+        presence.insert(string(entry, "_id")?, count_of(entry)?);
+    }
+
+    let mut fields = BTreeMap::new();
+    let mut truncated_documents = 0;
+    for entry in array(result, "types")? {
+        let entry = document(entry)?;
+        let field = string(entry, "_id")?;
+        let mut type_counts = BTreeMap::new();
+        let mut truncated = 0;
+        for t in array(entry, "types")? {
+            let t = document(t)?;
+            match string(t, "type")? {
+                TRUNCATED => truncated = count_of(t)?,
+                name => {
+                    type_counts.insert(BsonType::from_name(name), count_of(t)?);
+                }
+            }
+        }
+        if field.is_empty() {
+            truncated_documents = std::mem::take(&mut truncated);
+            if type_counts.is_empty() {
+                continue;
+            }
+        }
+
+        let count = presence.get(field).copied().unwrap_or_default();
+        if count < documents {
+            type_counts.insert(BsonType::Missing, documents - count);
+        }
+        let mut schema = FieldSchema::new(count, type_counts);
+        schema.truncated = truncated;
+        fields.insert(field.to_string(), schema);
+    }
+
+    // the elements holding each path inside arrays, and of them the subdocuments
+    let mut elements = BTreeMap::new();
+    if result.contains_key("elements") {
+        for entry in array(result, "elements")? {
+            let entry = document(entry)?;
+            let counts = (count_at(entry, "values")?, count_at(entry, "objects")?);
+            elements.insert(string(entry, "_id")?, counts);
+        }
+    }
+    for (path, field) in &mut fields {
+        let Some((parent, _)) = path
+            .rsplit_once('.')
+            .filter(|(parent, _)| parent.contains("[]"))
+        else {
+            continue;
+        };
+        if let (Some((count, _)), Some((_, of))) =
+            (elements.get(path.as_str()), elements.get(parent))
+        {
+            field.element_presence = Some(ElementPresence {
+                count: *count,
+                of: *of,
+            });
+        }
+    }
+    Ok(Parsed {
+        documents,
+        fields,
+        truncated_documents,
+    })
+}
+
+/// How many fields a result of [`schema_pipeline`] has, for benches/parse_result.rs to time
+/// [`parse_result`] through
+#[cfg(feature = "bench")]
+pub fn parsed_fields(result: &Document) -> Result<usize> {
+    parse_result(result).map(|parsed| parsed.fields.len())
+}
+
+/// Read the shapes [`schema_pipeline`] was asked for, if it was
+pub(crate) fn parse_shapes(result: &Document) -> Result<Option<Shapes>> {
+    if !result.contains_key("shapes") {
+        return Ok(None);
+    }
+    let Some(summary) = array(result, "shapes")?.first() else {
+        return Ok(Some(Shapes::default()));
+    };
+    let summary = document(summary)?;
+    let mut top = Vec::new();
+    for entry in array(summary, "top")? {
+        let entry = document(entry)?;
+        let mut fields = BTreeMap::<String, Vec<BsonType>>::new();
+        for pair in array(entry, "shape")? {
+            let pair = document(pair)?;
+            fields
+                .entry(string(pair, "k")?.to_string())
+                .or_default()
+                .push(BsonType::from_name(string(pair, "v")?));
+        }
+        top.push(DocumentShape {
+            fields,
+            count: count_of(entry)?,
+        });
+    }
+    Ok(Some(Shapes {
+        distinct: count_at(summary, "distinct")?,
+        top,
+    }))
+}
+
+fn count_of(doc: &Document) -> Result<u64> {
+    count_at(doc, "count")
+}
+
+/// `$count` and `$sum` produce an int or a long depending on the size of the result
+pub(crate) fn count_at(doc: &Document, key: &str) -> Result<u64> {
+    match doc.get(key) {
+        Some(Bson::Int32(count)) if *count >= 0 => Ok(*count as u64),
+        Some(Bson::Int64(count)) if *count >= 0 => Ok(*count as u64),
+        other => Err(malformed(format!("expected a count, got {:?}", other))),
+    }
+}
+
+fn array<'a>(doc: &'a Document, key: &str) -> Result<&'a Vec<Bson>> {
+    doc.get_array(key)
+        .map_err(|_| malformed(format!("expected an array at {:?}", key)))
+}
+
+fn string<'a>(doc: &'a Document, key: &str) -> Result<&'a str> {
+    doc.get_str(key)
+        .map_err(|_| malformed(format!("expected a string at {:?}", key)))
+}
+
+fn document(value: &Bson) -> Result<&Document> {
+    value
+        .as_document()
+        .ok_or_else(|| malformed(format!("expected a document, got {:?}", value)))
+}
+
+fn malformed(message: String) -> AnalyzerError {
+    AnalyzerError::MalformedResult(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn descent<'a>(exclude: &'a [String], include: &'a [String]) -> Descent<'a> {
+        Descent {
+            max_depth: 3,
+            max_array_elements: None,
+            max_fields_per_level: None,
+            exclude_fields: exclude,
+            include_fields: include,
+        }
+    }
+
+    /// The `$filter` conditions the frontiers are narrowed down with
+    fn frontier_conditions(stages: &[Document]) -> Vec<Bson> {
+        let mut conditions = Vec::new();
+        for stage in stages {
+            for (_, set) in stage {
+                let Some(frontier) = set.as_document().and_then(|set| set.get("frontier")) else {
+                    continue;
+                };
+                if let Some(filter) = frontier
+                    .as_document()
+                    .and_then(|frontier| frontier.get_document("$filter").ok())
+                {
+                    conditions.push(filter.get("cond").cloned().unwrap());
+                }
+            }
+        }
+        conditions
+    }
+
+    #[test]
+    fn descends_into_everything_without_patterns() {
+        let stages = flatten_stages(descent(&[], &[]), None, true);
+        assert!(frontier_conditions(&stages).is_empty());
+    }
+
+    #[test]
+    fn prunes_excluded_and_unincluded_fields() {
+        let exclude = vec!["secret*".to_string()];
+        let include = vec!["user.*".to_string()];
+        let stages = flatten_stages(descent(&exclude, &include), None, true);
+        let conditions = frontier_conditions(&stages);
+        // the first frontier and every level descended into
+        assert_eq!(conditions.len(), 3);
+        let unindexed = doc! {
+            "$replaceAll": { "input": "$$pair.k", "find": "[]", "replacement": "" }
+        };
+        let expected = Bson::Document(doc! {
+            "$and": [
+                { "$not": [{ "$or": [
+                    { "$regexMatch": { "input": "$$pair.k", "regex": "^secret.*$", "options": "s" } },
+                    { "$regexMatch": { "input": &unindexed, "regex": "^secret.*$", "options": "s" } },
+                ] }] },
+                { "$or": [
+                    { "$eq": [{ "$indexOfCP": ["user.", "$$pair.k"] }, 0] },
+                    { "$eq": [{ "$indexOfCP": ["$$pair.k", "user."] }, 0] },
+                    { "$eq": [{ "$indexOfCP": ["user.", &unindexed] }, 0] },
+                    { "$eq": [{ "$indexOfCP": [&unindexed, "user."] }, 0] },
+                ] },
+            ]
+        });
+        assert!(conditions.iter().all(|condition| *condition == expected));
+    }
+
+    #[test]
+    fn agrees_with_the_client_on_excluded_array_fields() {
+        let options = AnalysisOptions {
+            exclude_fields: vec!["items.*".to_string()],
+            ..AnalysisOptions::default()
+        };
+        let sampled = doc! { "items": [{ "sku": "a", "qty": 1 }], "tags": ["x"] };
+
+        // the server matches the elements' fields without their `[]` as well
+        let kept = Descent::of(&options).kept().unwrap().to_string();
+        assert!(kept.contains("$replaceAll"), "{}", kept);
+
+        let mut builder = crate::SchemaBuilder::default().limited_by(&options);
+        builder.add(&sampled);
+        let client = builder.build("db.coll").fields;
+
+        // what the pipeline's result would hold had the server descended into the elements
+        let result = doc! {
+            "documents": [{ "count": 1 }],
+            "presence": [
+                { "_id": "items", "count": 1 },
+                { "_id": "items[]", "count": 1 },
+                { "_id": "items[].qty", "count": 1 },
+                { "_id": "items[].sku", "count": 1 },
+                { "_id": "tags", "count": 1 },
+                { "_id": "tags[]", "count": 1 },
+            ],
+            "types": [
+                { "_id": "items", "types": [{ "type": "array", "count": 1 }] },
+                { "_id": "items[]", "types": [{ "type": "object", "count": 1 }] },
+                { "_id": "items[].qty", "types": [{ "type": "int", "count": 1 }] },
+                { "_id": "items[].sku", "types": [{ "type": "string", "count": 1 }] },
+                { "_id": "tags", "types": [{ "type": "array", "count": 1 }] },
+                { "_id": "tags[]", "types": [{ "type": "string", "count": 1 }] },
+            ],
+        };
+        let pipeline = parse_result(&result).unwrap().fields;
+        let reported = |fields: &BTreeMap<String, FieldSchema>| {
+            let reported = options.reported_paths(fields.keys());
+            fields
+                .keys()
+                .filter(|path| reported.contains(*path))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(reported(&pipeline), ["items", "items[]", "tags", "tags[]"]);
+        assert_eq!(reported(&client), reported(&pipeline));
+    }
+
+    /// A result of [`schema_pipeline`] for three documents, one of them too deep to descend
+    /// into fully
+    fn result() -> Document {
+        doc! {
+            "documents": [{ "count": 3 }],
+            "presence": [
+                { "_id": "_id", "count": 3 },
+                { "_id": "address", "count": 2 },
+                { "_id": "address.city", "count": 2 },
+                { "_id": "tags", "count": 3 },
+                { "_id": "tags[]", "count": 2_i64 },
+            ],
+            "types": [
+                { "_id": "", "types": [{ "type": TRUNCATED, "count": 1 }] },
+                { "_id": "_id", "types": [{ "type": "objectId", "count": 3 }] },
+                {
+                    "_id": "address",
+                    "types": [
+                        { "type": "object", "count": 2 },
+                        { "type": TRUNCATED, "count": 1 },
+                    ]
+                },
+                { "_id": "address.city", "types": [{ "type": "string", "count": 2 }] },
+                { "_id": "tags", "types": [{ "type": "array", "count": 3 }] },
+                {
+                    "_id": "tags[]",
+                    "types": [
+                        { "type": "string", "count": 2 },
+                        { "type": "int", "count": 1 },
+                    ]
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn parses_presence_and_types() {
+        let Parsed {
+            documents,
+            fields,
+            truncated_documents,
+        } = parse_result(&result()).unwrap();
+        assert_eq!(documents, 3);
+        assert_eq!(truncated_documents, 1);
+        assert_eq!(
+            fields.keys().collect::<Vec<_>>(),
+            ["_id", "address", "address.city", "tags", "tags[]"]
+        );
+        assert_eq!(fields["_id"].types, [BsonType::ObjectId]);
+        let address = &fields["address"];
+        assert_eq!(address.count, 2);
+        assert_eq!(address.truncated, 1);
+        assert_eq!(
+            address.type_counts,
+            BTreeMap::from([(BsonType::Object, 2), (BsonType::Missing, 1)])
+        );
+        let elements = &fields["tags[]"];
+        assert_eq!(
+            elements.type_counts,
+            BTreeMap::from([
+                (BsonType::String, 2),
+                (BsonType::Int, 1),
+                (BsonType::Missing, 1)
+            ])
+        );
+    }
+
+    #[test]
+    fn parses_an_empty_sample() {
+        let result = doc! { "documents": [], "presence": [], "types": [] };
+        let parsed = parse_result(&result).unwrap();
+        assert_eq!(
+            (
+                parsed.documents,
+                parsed.fields.len(),
+                parsed.truncated_documents
+            ),
+            (0, 0, 0)
+        );
+        assert_eq!(parse_shapes(&result).unwrap(), None);
+    }
+
+    #[test]
+    fn parses_shapes() {
+        let mut result = result();
+        result.insert(
+            "shapes",
+            vec![Bson::Document(doc! {
+                "distinct": 2,
+                "top": [
+                    {
+                        "shape": [
+                            { "k": "_id", "v": "objectId" },
+                            { "k": "tags", "v": "array" },
+                            { "k": "tags[]", "v": "string" },
+                            { "k": "tags[]", "v": "int" },
+                        ],
+                        "count": 2,
+                    },
+                    { "shape": [{ "k": "_id", "v": "objectId" }], "count": 1 },
+                ],
+            })],
+        );
+        let shapes = parse_shapes(&result).unwrap().unwrap();
+        assert_eq!(shapes.distinct, 2);
+        assert_eq!(shapes.top.len(), 2);
+        assert_eq!(shapes.top[0].count, 2);
+        assert_eq!(
+            shapes.top[0].fields["tags[]"],
+            [BsonType::String, BsonType::Int]
+        );
+        assert_eq!(
+            shapes.top[1].fields,
+            BTreeMap::from([("_id".to_string(), vec![BsonType::ObjectId])])
+        );
+
+        result.insert("shapes", Vec::<Bson>::new());
+        assert_eq!(parse_shapes(&result).unwrap(), Some(Shapes::default()));
+    }
+
+    #[test]
+    fn parses_the_presence_of_fields_in_array_elements() {
+        // two documents: one with three line items, one of them discounted, and one with an
+        // item and a plain number
+        let result = doc! {
+            "documents": [{ "count": 2 }],
+            "presence": [
+                { "_id": "lineItems", "count": 2 },
+                { "_id": "lineItems[]", "count": 2 },
+                { "_id": "lineItems[].sku", "count": 2 },
+                { "_id": "lineItems[].discount", "count": 1 },
+            ],
+            "elements": [
+                { "_id": "lineItems[]", "values": 5, "objects": 4 },
+                { "_id": "lineItems[].sku", "values": 4, "objects": 0 },
+                { "_id": "lineItems[].discount", "values": 1, "objects": 0 },
+            ],
+            "types": [
+                { "_id": "lineItems", "types": [{ "type": "array", "count": 2 }] },
+                {
+                    "_id": "lineItems[]",
+                    "types": [{ "type": "object", "count": 2 }, { "type": "int", "count": 1 }]
+                },
+                { "_id": "lineItems[].sku", "types": [{ "type": "string", "count": 2 }] },
+                { "_id": "lineItems[].discount", "types": [{ "type": "double", "count": 1 }] },
+            ],
+        };
+        let fields = parse_result(&result).unwrap().fields;
+        let discount = fields["lineItems[].discount"].element_presence.unwrap();
+        assert_eq!(discount, ElementPresence { count: 1, of: 4 });
+        assert_eq!(discount.share(), 0.25);
+        assert_eq!(
+            fields["lineItems[].sku"].element_presence,
+            Some(ElementPresence { count: 4, of: 4 })
+        );
+        // only fields of subdocuments in arrays have one
+        assert_eq!(fields["lineItems[]"].element_presence, None);
+        assert_eq!(fields["lineItems"].element_presence, None);
+    }
+
+    #[test]
+    fn keeps_each_elements_pairs_for_counting() {
+        let stages = flatten_stages(descent(&[], &[]), None, true);
+        let last = stages.last().unwrap().get_document("$project").unwrap();
+        assert!(last.contains_key("elements"));
+        let facet = schema_pipeline(None, None, None, descent(&[], &[]), None);
+        let facet = facet.last().unwrap().get_document("$facet").unwrap();
+        assert!(facet.contains_key("elements"));
+
+        let stages = flatten_stages(descent(&[], &[]), None, false);
+        let last = stages.last().unwrap().get_document("$project").unwrap();
+        assert!(!last.contains_key("elements"));
+    }
+
+    #[test]
+    fn rejects_entries_without_a_path() {
+        let result = doc! {
+            "documents": [{ "count": 1 }],
+            "presence": [],
+            "types": [{ "types": [{ "type": "string", "count": 1 }] }],
+        };
+        assert!(matches!(
+            parse_result(&result),
+            Err(AnalyzerError::MalformedResult(_))
+        ));
+    }
+
+    #[test]
+    fn keeps_everything_for_includes_starting_with_a_wildcard() {
+        let include = vec!["user.*".to_string(), "*Id".to_string()];
+        assert_eq!(descent(&[], &include).kept(), None);
+    }
+}
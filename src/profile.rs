@@ -0,0 +1,394 @@
+//! Value profiling: looking at the sampled values themselves rather than just their `$type`,
+//! to catch data stored under the wrong type. It runs client-side, on documents the
+//! [`SchemaBuilder`](crate::SchemaBuilder) sees
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt,
+};
+
+use bson::Bson;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    dbref,
+    pii::{self, PiiKind},
+    GeoJsonType,
+};
+
+/// Most distinct values tracked per field. Fields with more are never enum candidates
+pub const MAX_TRACKED_VALUES: usize = 50;
+
+/// Most distinct values counted per field for [`ValueProfile::top_values`]. Values first seen
+/// after this many others aren't counted
+pub const MAX_COUNTED_VALUES: usize = 10_000;
+
+/// How much of a field's values have to look like something else before it's reported
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProfileThresholds {
+    /// Fraction of the strings that have to parse as dates for
+    /// [`AnomalyKind::DateAsString`](crate::AnomalyKind::DateAsString)
+    pub date_strings: f64,
+    /// Fraction of the strings that have to parse as numbers for
+    /// [`AnomalyKind::NumberAsString`](crate::AnomalyKind::NumberAsString)
+    pub numeric_strings: f64,
+    /// Most distinct values a string or integer field can hold to be reported as an
+    /// [`AnomalyKind::EnumCandidate`](crate::AnomalyKind::EnumCandidate). Capped at
+    /// [`MAX_TRACKED_VALUES`]
+    pub enum_values: usize,
+}
+
+impl Default for ProfileThresholds {
+    fn default() -> Self {
+        Self {
+            date_strings: 0.9,
+            numeric_strings: 0.9,
+            enum_values: 10,
+        }
+    }
+}
+
+/// What the sampled values of a field looked like
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueProfile {
+    /// How many string values were inspected
+    pub strings: u64,
+    /// How many of the strings parse as ISO-8601 / RFC 3339 dates or date-times
+    pub dates: u64,
+    /// How many of the strings parse as numbers, integers included
+    #[serde(default)]
+    pub numbers: u64,
+    /// How many of the strings parse as 64-bit integers
+    #[serde(default)]
+    pub integers: u64,
+    /// How many of the strings look like each kind of personal data
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub pii: BTreeMap<PiiKind, u64>,
+    /// How many subdocuments were inspected
+    #[serde(default)]
+    pub objects: u64,
+    /// How many of the subdocuments are GeoJSON geometries of each type
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub geojson: BTreeMap<GeoJsonType, u64>,
+    /// How many of the subdocuments are DBRefs to each collection, `db.collection` where they
+    /// name the database
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub dbrefs: BTreeMap<String, u64>,
+    /// The distinct string and integer values seen, in order, while there were at most
+    /// [`MAX_TRACKED_VALUES`]. Not serialized, so reports only carry the values of enum
+    /// candidates and redacted examples
+    #[serde(skip)]
+    pub distinct: Vec<ValueCount>,
+    /// There were more distinct values than are tracked, so `distinct` was dropped
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub too_many_values: bool,
+    /// The first distinct values seen, as relaxed Extended JSON, if
+    /// [`AnalysisOptions::examples`](crate::AnalysisOptions::examples) asked for them.
+    /// Objects and arrays are left out, as their contents have examples of their own
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub examples: Vec<serde_json::Value>,
+    /// The most frequent string and integer values, most frequent first, if
+    /// [`AnalysisOptions::top_values`](crate::AnalysisOptions::top_values) asked for them.
+    /// Redacted like the examples
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_values: Vec<TopValue>,
+    /// More than [`MAX_COUNTED_VALUES`] distinct values were seen, so `top_values` may miss
+    /// values that only became frequent later in the sample
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub top_values_capped: bool,
+    /// How many times each value was seen, while counting for `top_values`
+    #[serde(skip)]
+    counts: HashMap<ScalarValue, u64>,
+}
+
+/// A frequent value and how many times it was seen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TopValue {
+    pub value: serde_json::Value,
+    pub count: u64,
+}
+
+/// How example values are made safe to share. Only strings (symbols and JavaScript code
+/// included) and binary data are redacted; numbers, dates and the like are kept as they are
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Redaction {
+    /// Keep the values
+    #[default]
+    None,
+    /// Replace them with a prefix of their SHA-256, so equal values can still be recognized
+    Hash,
+    /// Keep the first few characters of strings, and only the length of binary data
+    Truncate,
+    /// Don't capture them
+    Omit,
+}
+
+/// Characters [`Redaction::Truncate`] keeps
+const TRUNCATED_LENGTH: usize = 4;
+
+impl Redaction {
+    /// `value` as an example, or `None` if it has to be left out
+    fn example(self, value: &Bson) -> Option<serde_json::Value> {
+        let (text, bytes) = match value {
+            Bson::Document(_) | Bson::Array(_) => return None,
+            Bson::String(s) | Bson::Symbol(s) | Bson::JavaScriptCode(s) => (Some(s), s.as_bytes()),
+            Bson::Binary(binary) => (None, &binary.bytes[..]),
+            other => return Some(other.clone().into_relaxed_extjson()),
+        };
+        match self {
+            Redaction::None => Some(value.clone().into_relaxed_extjson()),
+            Redaction::Hash => {
+                let digest = Sha256::digest(bytes);
+                let hex = digest[..8]
+                    .iter()
+                    .map(|byte| format!("{:02x}", byte))
+                    .collect::<String>();
+                Some(serde_json::Value::String(format!("sha256:{}", hex)))
+            }
+            Redaction::Truncate => Some(serde_json::Value::String(match text {
+                Some(s) if s.chars().count() > TRUNCATED_LENGTH => {
+                    format!("{}…", s.chars().take(TRUNCATED_LENGTH).collect::<String>())
+                }
+                Some(s) => s.clone(),
+                None => format!("<{} bytes>", bytes.len()),
+            })),
+            Redaction::Omit => None,
+        }
+    }
+}
+
+/// A string or integer value with how many times it was seen
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ValueCount {
+    pub value: ScalarValue,
+    pub count: u64,
+}
+
+/// A value an enum can be made of
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum ScalarValue {
+    Int(i64),
+    String(String),
+}
+
+impl fmt::Display for ScalarValue {
+    /// Integers as they are and strings quoted, as in JSON
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScalarValue::Int(n) => write!(f, "{}", n),
+            ScalarValue::String(s) => f.write_str(&serde_json::to_string(s).unwrap()),
+        }
+    }
+}
+
+impl ValueProfile {
+    /// Profile `value`, keeping it as one of up to `examples` examples if it hasn't been seen,
+    /// and counting it towards the top values if `count_values` is set
+    pub(crate) fn observe(
+        &mut self,
+        value: &Bson,
+        examples: usize,
+        redaction: Redaction,
+        count_values: bool,
+    ) {
+        if self.examples.len() < examples {
+            if let Some(example) = redaction.example(value) {
+                if !self.examples.contains(&example) {
+                    self.examples.push(example);
+                }
+            }
+        }
+        let scalar = match value {
+            Bson::String(s) => Some(ScalarValue::String(s.clone())),
+            Bson::Int32(n) => Some(ScalarValue::Int(i64::from(*n))),
+            Bson::Int64(n) => Some(ScalarValue::Int(*n)),
+            _ => None,
+        };
+        if let Some(scalar) = scalar {
+            if count_values {
+                let counted = self.counts.len();
+                match self.counts.get_mut(&scalar) {
+                    Some(count) => *count += 1,
+                    None if counted == MAX_COUNTED_VALUES => self.top_values_capped = true,
+                    None => {
+                        self.counts.insert(scalar.clone(), 1);
+                    }
+                }
+            }
+            self.count_value(scalar);
+        }
+        if let Bson::String(s) = value {
+            self.strings += 1;
+            if is_iso_date(s) {
+                self.dates += 1;
+            }
+            if is_number(s) {
+                self.numbers += 1;
+                if s.parse::<i64>().is_ok() {
+                    self.integers += 1;
+                }
+            }
+            if let Some(kind) = pii::value_kind(s) {
+                *self.pii.entry(kind).or_default() += 1;
+            }
+        }
+        if let Bson::Document(doc) = value {
+            self.objects += 1;
+            if let Some(ty) = GeoJsonType::of(doc) {
+                *self.geojson.entry(ty).or_default() += 1;
+            }
+            if let Some(target) = dbref::target(doc) {
+                *self.dbrefs.entry(target).or_default() += 1;
+            }
+        }
+    }
+
+    /// Fill in [`ValueProfile::top_values`] with the `top` most frequent values counted,
+    /// ties broken by value
+    pub(crate) fn rank_top_values(&mut self, top: usize, redaction: Redaction) {
+        let mut counted = self.counts.iter().collect::<Vec<_>>();
+        counted.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        self.top_values = counted
+            .into_iter()
+            .filter_map(|(value, count)| {
+                let value = match value {
+                    ScalarValue::Int(n) => Bson::Int64(*n),
+                    ScalarValue::String(s) => Bson::String(s.clone()),
+                };
+                Some(TopValue {
+                    value: redaction.example(&value)?,
+                    count: *count,
+                })
+            })
+            .take(top)
+            .collect();
+    }
+
+    /// The fraction of the strings that parse as dates, if there were any strings
+    pub fn date_fraction(&self) -> Option<f64> {
+        self.fraction(self.dates)
+    }
+
+    /// The fraction of the strings that parse as numbers, if there were any strings
+    pub fn number_fraction(&self) -> Option<f64> {
+        self.fraction(self.numbers)
+    }
+
+    fn count_value(&mut self, value: ScalarValue) {
+        if self.too_many_values {
+            return;
+        }
+        match self
+            .distinct
+            .binary_search_by(|seen| seen.value.cmp(&value))
+        {
+            Ok(i) => self.distinct[i].count += 1,
+            Err(_) if self.distinct.len() == MAX_TRACKED_VALUES => {
+                self.distinct = Vec::new();
+                self.too_many_values = true;
+            }
+            Err(i) => self.distinct.insert(i, ValueCount { value, count: 1 }),
+        }
+    }
+
+    fn fraction(&self, count: u64) -> Option<f64> {
+        (self.strings > 0).then(|| count as f64 / self.strings as f64)
+    }
+}
+
+/// Whether `s` is a finite decimal number, e.g. `42`, `-0.5` or `1e9`. Numbers with leading
+/// zeros don't count: postcodes, phone numbers and account ids would lose them if converted
+pub(crate) fn is_number(s: &str) -> bool {
+    let unsigned = s.strip_prefix(['-', '+']).unwrap_or(s);
+    let leading_zero =
+        unsigned.len() > 1 && unsigned.starts_with('0') && unsigned.as_bytes()[1].is_ascii_digit();
+    // f64 parsing also takes "inf", "NaN" and ".5", which aren't what we're after
+    unsigned.starts_with(|c: char| c.is_ascii_digit())
+        && !leading_zero
+        && s.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+/// Whether `s` is a calendar date (`2024-01-31`) or a date-time, `T` or space separated, with
+/// optional seconds, fraction and `Z` or `±hh:mm` offset (`2024-01-31T12:00:00.5+01:00`)
+pub(crate) fn is_iso_date(s: &str) -> bool {
+    let s = s.as_bytes();
+    let Some(rest) = date(s) else {
+        return false;
+    };
+    match rest {
+        [] => true,
+        [b'T' | b't' | b' ', time @ ..] => self::time(time).is_some_and(offset),
+        _ => false,
+    }
+}
+
+/// `YYYY-MM-DD`, returning what follows
+fn date(s: &[u8]) -> Option<&[u8]> {
+    let (year, s) = digits(s, 4)?;
+    let (month, s) = digits(s.strip_prefix(b"-")?, 2)?;
+    let (day, s) = digits(s.strip_prefix(b"-")?, 2)?;
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    (1..=days).contains(&day).then_some(s)
+}
+
+/// `hh:mm`, then optionally `:ss` and a fraction, returning what follows
+fn time(s: &[u8]) -> Option<&[u8]> {
+    let (hour, s) = digits(s, 2)?;
+    let (minute, mut s) = digits(s.strip_prefix(b":")?, 2)?;
+    if hour > 23 || minute > 59 {
+        return None;
+    }
+    if let Some(rest) = s.strip_prefix(b":") {
+        let (second, rest) = digits(rest, 2)?;
+        // 60 for leap seconds
+        if second > 60 {
+            return None;
+        }
+        s = rest;
+        if let Some(fraction) = s.strip_prefix(b".").or_else(|| s.strip_prefix(b",")) {
+            let len = fraction.iter().take_while(|c| c.is_ascii_digit()).count();
+            if len == 0 {
+                return None;
+            }
+            s = &fraction[len..];
+        }
+    }
+    Some(s)
+}
+
+/// Whether `s` is nothing (local time), `Z`, or a `±hh:mm` or `±hhmm` offset
+fn offset(s: &[u8]) -> bool {
+    match s {
+        [] | [b'Z' | b'z'] => true,
+        [b'+' | b'-', rest @ ..] => {
+            let Some((hours, rest)) = digits(rest, 2) else {
+                return false;
+            };
+            let rest = rest.strip_prefix(b":").unwrap_or(rest);
+            matches!(digits(rest, 2), Some((minutes, [])) if hours <= 23 && minutes <= 59)
+        }
+        _ => false,
+    }
+}
+
+/// The number made of the first `len` bytes, which all have to be digits
+fn digits(s: &[u8], len: usize) -> Option<(u32, &[u8])> {
+    if s.len() < len || !s[..len].iter().all(u8::is_ascii_digit) {
+        return None;
+    }
+    let n = s[..len]
+        .iter()
+        .fold(0, |n, digit| n * 10 + u32::from(digit - b'0'));
+    Some((n, &s[len..]))
+}
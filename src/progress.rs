@@ -0,0 +1,51 @@
+use std::{fmt, sync::Arc, time::Duration};
+
+/// A step in a running analysis, passed to [`ProgressFn`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress<'a> {
+    /// Analysis of a namespace is starting, the `index`th (counting from 0) of the `total` in
+    /// a multi-collection run
+    Collection {
+        namespace: &'a str,
+        index: usize,
+        total: usize,
+    },
+    /// The server is running the sampling aggregation, which reports nothing until it's done
+    Aggregating,
+    /// `read` documents have been inferred from client-side, out of `expected` if it's known
+    Documents { read: u64, expected: Option<u64> },
+    /// The sampling failed with a transient error, and is retried for the `attempt`th time
+    /// (counting from 1) after `delay`
+    Retrying {
+        attempt: u32,
+        delay: Duration,
+        error: &'a str,
+    },
+}
+
+/// Called with every [`Progress`] update, e.g. to drive a progress bar. Cheap to clone
+#[derive(Clone)]
+pub struct ProgressFn(Arc<dyn Fn(Progress<'_>) + Send + Sync>);
+
+impl ProgressFn {
+    pub fn new(f: impl Fn(Progress<'_>) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+
+    pub(crate) fn report(&self, progress: Progress<'_>) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressFn {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressFn")
+    }
+}
+
+impl PartialEq for ProgressFn {
+    /// The same callback, not one that behaves the same
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+}
@@ -0,0 +1,129 @@
+//! Python bindings, built by maturin into the `schema_analyzer` module so notebooks and
+//! scheduled jobs can run analyses without shelling out to the binary. Schemas are returned
+//! as they serialize to JSON, as dicts and lists
+
+use std::io;
+
+use bson::Document;
+use mongodb::{options::ClientOptions, Client, Namespace};
+use pyo3::{
+    exceptions::{PyRuntimeError, PyValueError},
+    prelude::*,
+    types::{PyBytes, PyDict},
+};
+
+use crate::{
+    dump::Sample, identify_client, ndjson::parse_line, AnalysisOptions, AnalyzerError,
+    SchemaAnalyzer,
+};
+
+#[pymodule]
+fn schema_analyzer(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(analyze, module)?)?;
+    module.add_function(wrap_pyfunction!(analyze_documents, module)?)?;
+    Ok(())
+}
+
+/// Sample the collection `db.collection` and infer its schema. `options` takes the keys of
+/// the library's AnalysisOptions, as the HTTP service's `options` does, e.g.
+/// `{"sampling": {"size": 1000}, "max_depth": 3}`
+#[pyfunction]
+#[pyo3(signature = (uri, db, collection, options=None))]
+fn analyze(
+    py: Python<'_>,
+    uri: &str,
+    db: &str,
+    collection: &str,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<PyObject> {
+    let options = analysis_options(py, options)?;
+    let ns = Namespace::new(db, collection);
+    let runtime = tokio::runtime::Runtime::new()?;
+    let schema = py
+        .allow_threads(|| {
+            runtime.block_on(async {
+                let mut client_options = ClientOptions::parse(uri)
+                    .await
+                    .map_err(AnalyzerError::InvalidUri)?;
+                identify_client(&mut client_options, None);
+                let client =
+                    Client::with_options(client_options).map_err(AnalyzerError::InvalidUri)?;
+                SchemaAnalyzer::with_options(client, ns, options)
+                    .analyze()
+                    .await
+            })
+        })
+        .map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    to_python(py, &schema)
+}
+
+/// Infer the schema of an iterable of documents: dicts, converted through
+/// `bson.json_util` when PyMongo is installed so its BSON types survive, or raw BSON bytes.
+/// Sampling options work as for files, only decoding the documents they select
+#[pyfunction]
+#[pyo3(signature = (documents, namespace="documents", options=None))]
+fn analyze_documents(
+    py: Python<'_>,
+    documents: &Bound<'_, PyAny>,
+    namespace: &str,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<PyObject> {
+    let options = analysis_options(py, options)?;
+    // plain json can't encode object ids or dates, which PyMongo hands out
+    let encoder = match py.import("bson.json_util") {
+        Ok(json_util) => json_util,
+        Err(_) => py.import("json")?,
+    };
+    let mut sample = Sample::new(&options);
+    for (i, document) in documents.try_iter()?.enumerate() {
+        let document = document?;
+        // the Python error is kept aside, as the sample only passes on io errors
+        let mut error = None;
+        let _ = sample.add_with(|| {
+            to_document(&encoder, &document).map_err(|e| {
+                error = Some(e);
+                io::ErrorKind::InvalidData.into()
+            })
+        });
+        if let Some(e) = error {
+            return Err(PyValueError::new_err(format!("document {}: {}", i, e)));
+        }
+    }
+    to_python(py, &sample.build(namespace.to_string()))
+}
+
+/// `options` as AnalysisOptions, the defaults for those not given
+fn analysis_options(
+    py: Python<'_>,
+    options: Option<&Bound<'_, PyDict>>,
+) -> PyResult<AnalysisOptions> {
+    let Some(options) = options else {
+        return Ok(AnalysisOptions::default());
+    };
+    let json = py
+        .import("json")?
+        .call_method1("dumps", (options,))?
+        .extract::<String>()?;
+    serde_json::from_str(&json)
+        .map_err(|e| PyValueError::new_err(format!("invalid options: {}", e)))
+}
+
+/// A document from raw BSON bytes, RawBSONDocuments included, or from what `encoder` makes
+/// of it as (Extended) JSON
+fn to_document(encoder: &Bound<'_, PyModule>, document: &Bound<'_, PyAny>) -> PyResult<Document> {
+    let raw = document.getattr("raw").unwrap_or_else(|_| document.clone());
+    if let Ok(bytes) = raw.downcast::<PyBytes>() {
+        return Document::from_reader(bytes.as_bytes())
+            .map_err(|e| PyValueError::new_err(e.to_string()));
+    }
+    let json = encoder
+        .call_method1("dumps", (document,))?
+        .extract::<String>()?;
+    parse_line(&json).map_err(PyValueError::new_err)
+}
+
+/// `value` as the Python objects its JSON decodes to
+fn to_python(py: Python<'_>, value: &impl serde::Serialize) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value).map_err(|e| PyRuntimeError::new_err(e.to_string()))?;
+    Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+}
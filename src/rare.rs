@@ -0,0 +1,102 @@
+//! Fields hardly any document holds, which are usually typos, remnants of removed code or
+//! one-off migrations
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::CollectionSchema;
+
+/// The default of [`AnalysisOptions::rare_fields`](crate::AnalysisOptions::rare_fields)
+pub(crate) const RARE_PRESENCE: f64 = 0.01;
+
+/// A field held by few of the sampled documents, see [`CollectionSchema::find_rare_fields`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RareField {
+    pub path: String,
+    /// How many sampled documents hold the field
+    pub count: u64,
+    /// Their share of the sample
+    pub presence: f64,
+}
+
+impl fmt::Display for RareField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: in {:.2}% of documents ({})",
+            self.path,
+            self.presence * 100.0,
+            self.count
+        )
+    }
+}
+
+impl CollectionSchema {
+    /// The fields held by fewer than `threshold` of the sampled documents. The fields of a
+    /// rare subdocument go with it rather than being listed too, and array elements aren't
+    /// listed, as they're absent wherever the array is empty
+    pub fn find_rare_fields(&self, threshold: f64) -> Vec<RareField> {
+        if self.sample_size == 0 {
+            return Vec::new();
+        }
+        let is_rare = |path: &str| {
+            self.fields
+                .get(path)
+                .is_some_and(|field| self.share(field.count) < threshold)
+        };
+        self.fields
+            .iter()
+            .filter(|(path, _)| !path.ends_with("[]") && is_rare(path))
+            .filter(|(path, _)| {
+                let mut parent = path.as_str();
+                while let Some((outer, _)) = parent.rsplit_once('.') {
+                    parent = outer.trim_end_matches("[]");
+                    if is_rare(parent) {
+                        return false;
+                    }
+                }
+                true
+            })
+            .map(|(path, field)| RareField {
+                path: path.clone(),
+                count: field.count,
+                presence: self.share(field.count),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    #[test]
+    fn lists_rare_fields_without_their_subfields() {
+        let documents = b"{\"a\": 1, \"tags\": []}\n{\"a\": 1, \"tags\": []}\n\
+            {\"a\": 1, \"tags\": []}\n{\"a\": 1, \"tags\": [1], \"typo\": 1, \"p\": {\"q\": 1}}\n";
+        let schema = CollectionSchema::from_ndjson(
+            documents.as_slice(),
+            "db.coll",
+            &AnalysisOptions::default(),
+        )
+        .unwrap();
+        let rare = schema.find_rare_fields(0.3);
+        let paths = rare
+            .iter()
+            .map(|rare| rare.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["p", "typo"]);
+        assert_eq!(rare[1].to_string(), "typo: in 25.00% of documents (1)");
+        assert!(schema.find_rare_fields(0.25).is_empty());
+    }
+
+    #[test]
+    fn finds_none_in_an_empty_sample() {
+        let schema =
+            CollectionSchema::from_ndjson(b"".as_slice(), "db.coll", &AnalysisOptions::default())
+                .unwrap();
+        assert!(schema.find_rare_fields(1.0).is_empty());
+    }
+}
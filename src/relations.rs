@@ -0,0 +1,345 @@
+//! Relationships between the collections of a [`SchemaReport`]: fields holding ObjectIds, or
+//! named like `customer_id`, are probed against the `_id`s of sibling collections in the same
+//! database to see how many of their values resolve, and DBRefs against the collections they
+//! name
+
+use std::{collections::BTreeMap, fmt};
+
+use bson::{doc, Bson, Document};
+#[cfg(feature = "driver")]
+use futures::stream::TryStreamExt;
+#[cfg(feature = "driver")]
+use mongodb::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{dbref, BsonType, CollectionSchema, Result, SchemaReport};
+
+/// Values sampled from each candidate field
+const RELATION_SAMPLE_SIZE: u64 = 100;
+
+/// A field whose values were found among the `_id`s of another collection
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Relationship {
+    /// The `db.collection` holding the field
+    pub from: String,
+    /// The referencing field's path
+    pub path: String,
+    /// The `db.collection` whose `_id`s the values resolve to
+    pub to: String,
+    /// How many distinct values were sampled from the field
+    pub sampled: u64,
+    /// How many of them are the `_id` of a document in `to`
+    pub resolved: u64,
+}
+
+impl Relationship {
+    /// The share of the sampled values that resolve
+    pub fn hit_rate(&self) -> f64 {
+        if self.sampled == 0 {
+            0.0
+        } else {
+            self.resolved as f64 / self.sampled as f64
+        }
+    }
+}
+
+impl fmt::Display for Relationship {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{} -> {}._id ({:.1}% of {} sampled values)",
+            self.from,
+            self.path,
+            self.to,
+            self.hit_rate() * 100.0,
+            self.sampled
+        )
+    }
+}
+
+impl SchemaReport {
+    /// Probe the fields that look like references against the other collections of their
+    /// database, keeping [`SchemaReport::relationships`] with at least one resolved value.
+    /// Fields named after a collection, like `customer_id` or `customerIds` for `customers`,
+    /// are only probed against it; other ObjectId fields against every sibling whose `_id`s
+    /// are ObjectIds. DBRefs are probed against the collections they name, analyzed or not,
+    /// and kept even if none resolve, as a dangling reference is worth knowing about
+    #[cfg(feature = "driver")]
+    pub async fn infer_relationships(&mut self, client: &Client) -> Result<()> {
+        let mut relationships = Vec::new();
+        for (ns, schema) in &self.namespaces {
+            let Some((db, _)) = ns.split_once('.') else {
+                continue;
+            };
+            let siblings = self
+                .namespaces
+                .iter()
+                .filter(|(other, _)| {
+                    *other != ns && other.split_once('.').map(|(d, _)| d) == Some(db)
+                })
+                .collect::<Vec<_>>();
+            for path in schema.fields.keys() {
+                if schema.is_dbref(path) {
+                    for (to, ids) in sample_dbrefs(client, ns, path).await? {
+                        relationships.push(Relationship {
+                            from: ns.clone(),
+                            path: path.clone(),
+                            resolved: resolve(client, &to, &ids).await?,
+                            to,
+                            sampled: ids.len() as u64,
+                        });
+                    }
+                    continue;
+                }
+                // a DBRef's `$id` is followed along with it
+                if schema.in_dbref(path) {
+                    continue;
+                }
+                let targets = targets(schema, path, &siblings);
+                if targets.is_empty() {
+                    continue;
+                }
+                let values = sample_values(client, ns, path).await?;
+                if values.is_empty() {
+                    continue;
+                }
+                for to in targets {
+                    let resolved = resolve(client, to, &values).await?;
+                    if resolved > 0 {
+                        relationships.push(Relationship {
+                            from: ns.clone(),
+                            path: path.clone(),
+                            to: to.to_string(),
+                            sampled: values.len() as u64,
+                            resolved,
+                        });
+                    }
+                }
+            }
+        }
+        self.relationships = relationships;
+        Ok(())
+    }
+}
+
+/// The sibling namespaces the field at `path` could refer to
+fn targets<'a>(
+    schema: &CollectionSchema,
+    path: &str,
+    siblings: &[(&'a String, &CollectionSchema)],
+) -> Vec<&'a str> {
+    // references are single values; an array of them is probed through its `[]` elements
+    let single = schema.fields[path].types.iter().any(|t| {
+        !matches!(
+            t,
+            BsonType::Array | BsonType::Object | BsonType::Null | BsonType::Missing
+        )
+    });
+    if !single || path == "_id" || path.ends_with("._id") {
+        return Vec::new();
+    }
+    if let Some(name) = referenced_name(path) {
+        let named = siblings
+            .iter()
+            .filter(|(ns, _)| {
+                let coll = ns.split_once('.').map_or("", |(_, coll)| coll);
+                names_collection(&name, &coll.to_ascii_lowercase())
+            })
+            .map(|(ns, _)| ns.as_str())
+            .collect::<Vec<_>>();
+        if !named.is_empty() {
+            return named;
+        }
+    }
+    if !schema.fields[path].types.contains(&BsonType::ObjectId) {
+        return Vec::new();
+    }
+    siblings
+        .iter()
+        .filter(|(_, sibling)| {
+            sibling
+                .fields
+                .get("_id")
+                .is_some_and(|id| id.types.contains(&BsonType::ObjectId))
+        })
+        .map(|(ns, _)| ns.as_str())
+        .collect()
+}
+
+/// The lowercased name a `<name>_id`, `<name>Id` or plural field is named after
+fn referenced_name(path: &str) -> Option<String> {
+    let name = path.trim_end_matches("[]");
+    let name = name.rsplit('.').next().unwrap_or(name);
+    let stem = ["_ids", "Ids", "_id", "Id"]
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix))?;
+    (!stem.is_empty()).then(|| stem.to_ascii_lowercase())
+}
+
+/// Whether `coll` is `name` or one of its plurals
+fn names_collection(name: &str, coll: &str) -> bool {
+    coll == name
+        || coll.strip_suffix('s') == Some(name)
+        || coll.strip_suffix("es") == Some(name)
+        || name
+            .strip_suffix('y')
+            .is_some_and(|stem| coll.strip_suffix("ies") == Some(stem))
+}
+
+/// How many of `values` are the `_id` of a document in the namespace `to`
+#[cfg(feature = "driver")]
+async fn resolve(client: &Client, to: &str, values: &[Bson]) -> Result<u64> {
+    let (db, coll) = to.split_once('.').unwrap_or_default();
+    Ok(client
+        .database(db)
+        .collection::<Document>(coll)
+        .count_documents(doc! { "_id": { "$in": values } }, None)
+        .await?)
+}
+
+/// Distinct values of the field at `path` in a random sample of `ns`, array elements included
+#[cfg(feature = "driver")]
+async fn sample_values(client: &Client, ns: &str, path: &str) -> Result<Vec<Bson>> {
+    let mut values = Vec::new();
+    for value in sample(client, ns, path).await? {
+        if !matches!(value, Bson::Null | Bson::Document(_) | Bson::Array(_))
+            && !values.contains(&value)
+        {
+            values.push(value);
+        }
+    }
+    Ok(values)
+}
+
+/// The distinct `$id`s of the DBRefs at `path` in a random sample of `ns`, by the namespace
+/// they refer to
+#[cfg(feature = "driver")]
+async fn sample_dbrefs(
+    client: &Client,
+    ns: &str,
+    path: &str,
+) -> Result<BTreeMap<String, Vec<Bson>>> {
+    let db = ns.split_once('.').map_or(ns, |(db, _)| db);
+    let mut ids = BTreeMap::<String, Vec<Bson>>::new();
+    for value in sample(client, ns, path).await? {
+        let Bson::Document(reference) = value else {
+            continue;
+        };
+        let (Some(to), Some(id)) = (dbref::namespace(&reference, db), reference.get("$id")) else {
+            continue;
+        };
+        let ids = ids.entry(to).or_default();
+        if !ids.contains(id) {
+            ids.push(id.clone());
+        }
+    }
+    Ok(ids)
+}
+
+/// The values at `path` in a random sample of `ns`, array elements included
+#[cfg(feature = "driver")]
+async fn sample(client: &Client, ns: &str, path: &str) -> Result<Vec<Bson>> {
+    let (db, coll) = ns.split_once('.').unwrap_or_default();
+    // `items[].product_id` is `items.product_id` to the server
+    let dotted = path.replace("[]", "");
+    let pipeline = [
+        doc! { "$sample": { "size": RELATION_SAMPLE_SIZE as i64 } },
+        doc! { "$match": { &dotted: { "$exists": true } } },
+        doc! { "$project": { "_id": 0, &dotted: 1 } },
+    ];
+    let mut cursor = client
+        .database(db)
+        .collection::<Document>(coll)
+        .aggregate(pipeline, None)
+        .await?;
+    let mut values = Vec::new();
+    while let Some(doc) = cursor.try_next().await? {
+        collect(&Bson::Document(doc), &dotted, &mut values);
+    }
+    Ok(values)
+}
+
+/// The values at the dotted `path` under `value`, descending into arrays along the way
+fn collect(value: &Bson, path: &str, found: &mut Vec<Bson>) {
+    match value {
+        Bson::Array(elements) => {
+            for element in elements {
+                collect(element, path, found);
+            }
+        }
+        Bson::Document(doc) if !path.is_empty() => {
+            let (key, rest) = path.split_once('.').unwrap_or((path, ""));
+            if let Some(value) = doc.get(key) {
+                collect(value, rest, found);
+            }
+        }
+        _ if path.is_empty() => found.push(value.clone()),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    fn schema(ns: &str, ndjson: &str) -> CollectionSchema {
+        CollectionSchema::from_ndjson(ndjson.as_bytes(), ns, &AnalysisOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn probes_fields_named_after_a_collection_against_it_alone() {
+        let orders = schema(
+            "shop.orders",
+            "{\"_id\": 1, \"customer_id\": 7, \"categoryIds\": [3], \"note\": \"x\", \
+             \"placedBy\": {\"$oid\": \"65f0c0ffee00000000000001\"}}\n",
+        );
+        let customers = schema(
+            "shop.customers",
+            "{\"_id\": {\"$oid\": \"65f0c0ffee00000000000002\"}}\n",
+        );
+        let categories = schema("shop.categories", "{\"_id\": 3}\n");
+        let (customers_ns, categories_ns) =
+            ("shop.customers".to_string(), "shop.categories".to_string());
+        let siblings = [(&customers_ns, &customers), (&categories_ns, &categories)];
+
+        assert_eq!(
+            targets(&orders, "customer_id", &siblings),
+            ["shop.customers"]
+        );
+        assert_eq!(
+            targets(&orders, "categoryIds[]", &siblings),
+            ["shop.categories"]
+        );
+        // the array itself isn't a reference, nor is a field named after nothing
+        assert!(targets(&orders, "categoryIds", &siblings).is_empty());
+        assert!(targets(&orders, "note", &siblings).is_empty());
+        assert!(targets(&orders, "_id", &siblings).is_empty());
+        // an ObjectId goes to every sibling whose `_id`s are ObjectIds
+        assert_eq!(targets(&orders, "placedBy", &siblings), ["shop.customers"]);
+    }
+
+    #[test]
+    fn matches_names_to_their_plurals() {
+        assert_eq!(
+            referenced_name("items[].product_id").as_deref(),
+            Some("product")
+        );
+        assert_eq!(referenced_name("tagIds[]").as_deref(), Some("tag"));
+        assert_eq!(referenced_name("_id"), None);
+        assert!(names_collection("product", "products"));
+        assert!(names_collection("box", "boxes"));
+        assert!(names_collection("category", "categories"));
+        assert!(!names_collection("product", "productlogs"));
+    }
+
+    #[test]
+    fn collects_values_through_arrays() {
+        let document = Bson::Document(doc! {
+            "items": [{ "product_id": 1 }, { "product_id": 2 }, { "other": 3 }],
+        });
+        let mut found = Vec::new();
+        collect(&document, "items.product_id", &mut found);
+        assert_eq!(found, [Bson::Int32(1), Bson::Int32(2)]);
+    }
+}
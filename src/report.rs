@@ -0,0 +1,188 @@
+use std::{collections::BTreeMap, fmt};
+
+use bson::doc;
+#[cfg(feature = "driver")]
+use futures::stream::{self, StreamExt};
+#[cfg(feature = "driver")]
+use mongodb::{Client, Namespace};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::SchemaAnalyzer;
+use crate::{
+    glob::glob_match, AnalysisOptions, CollectionSchema, FormatVersion, Progress, Relationship,
+    Result,
+};
+
+/// Databases holding server metadata rather than application data
+const SYSTEM_DATABASES: [&str; 3] = ["admin", "config", "local"];
+
+/// The schemas of several collections, keyed by namespace
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SchemaReport {
+    #[serde(rename = "formatVersion", default = "FormatVersion::unversioned")]
+    pub format_version: FormatVersion,
+    pub namespaces: BTreeMap<String, CollectionSchema>,
+    /// What [`SchemaReport::infer_relationships`] found, if it was run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub relationships: Vec<Relationship>,
+    /// Why each namespace that couldn't be analyzed failed, keyed by namespace
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub failures: BTreeMap<String, String>,
+}
+
+#[cfg(feature = "driver")]
+impl SchemaReport {
+    /// Analyze every collection in `db` with the same options, one after another. Views and
+    /// `system.*` collections are skipped
+    pub async fn analyze_database(
+        client: &Client,
+        db: &str,
+        options: &AnalysisOptions,
+    ) -> Result<Self> {
+        Self::analyze_namespaces(client, collections(client, db).await?, options).await
+    }
+
+    /// Analyze the collections of every non-system database on the cluster that pass `filter`
+    pub async fn analyze_cluster(
+        client: &Client,
+        filter: &NamespaceFilter,
+        options: &AnalysisOptions,
+    ) -> Result<Self> {
+        Self::analyze_namespaces(client, cluster_namespaces(client, filter).await?, options).await
+    }
+
+    /// Analyze each namespace in turn with the same options
+    pub async fn analyze_namespaces(
+        client: &Client,
+        namespaces: Vec<Namespace>,
+        options: &AnalysisOptions,
+    ) -> Result<Self> {
+        Self::analyze_namespaces_with(client, namespaces, |_| options.clone()).await
+    }
+
+    /// Analyze each namespace in turn with the options `options_for` picks for it, e.g. from a
+    /// [`Config`](crate::Config). Failures are kept as by
+    /// [`SchemaReport::analyze_namespaces_concurrently`]
+    pub async fn analyze_namespaces_with(
+        client: &Client,
+        namespaces: Vec<Namespace>,
+        options_for: impl Fn(&Namespace) -> AnalysisOptions,
+    ) -> Result<Self> {
+        Self::analyze_namespaces_concurrently(client, namespaces, 1, options_for).await
+    }
+
+    /// Like [`SchemaReport::analyze_namespaces_with`], running up to `concurrency` analyses at
+    /// once. A namespace that fails doesn't stop the others: its error is kept in
+    /// [`SchemaReport::failures`], and the run only fails when every namespace does
+    pub async fn analyze_namespaces_concurrently(
+        client: &Client,
+        namespaces: Vec<Namespace>,
+        concurrency: usize,
+        options_for: impl Fn(&Namespace) -> AnalysisOptions,
+    ) -> Result<Self> {
+        let total = namespaces.len();
+        let options_for = &options_for;
+        let mut results = stream::iter(namespaces.into_iter().enumerate())
+            .map(|(index, ns)| async move {
+                let options = options_for(&ns);
+                if let Some(progress) = &options.progress {
+                    progress.report(Progress::Collection {
+                        namespace: &ns.to_string(),
+                        index,
+                        total,
+                    });
+                }
+                let namespace = ns.to_string();
+                let schema = SchemaAnalyzer::with_options(client.clone(), ns, options)
+                    .analyze()
+                    .await;
+                (namespace, schema)
+            })
+            .buffer_unordered(concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+
+        let mut report = Self::default();
+        let mut first_error = None;
+        // sorted so the error returned when everything failed doesn't depend on timing
+        results.sort_by(|(a, _), (b, _)| a.cmp(b));
+        for (namespace, schema) in results {
+            match schema {
+                Ok(schema) => {
+                    report.namespaces.insert(schema.namespace.clone(), schema);
+                }
+                Err(e) => {
+                    report.failures.insert(namespace, e.to_string());
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if report.namespaces.is_empty() => Err(e),
+            _ => Ok(report),
+        }
+    }
+}
+
+/// Selects namespaces by shell-style patterns over `db.collection`, e.g. `shop.*` or
+/// `*.tmp_*`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NamespaceFilter {
+    /// When non-empty, only namespaces matching one of these are selected
+    pub include: Vec<String>,
+    /// Namespaces matching any of these are never selected, even if included
+    pub exclude: Vec<String>,
+}
+
+#[cfg(feature = "driver")]
+impl NamespaceFilter {
+    pub fn matches(&self, ns: &Namespace) -> bool {
+        let ns = ns.to_string();
+        (self.include.is_empty() || self.include.iter().any(|p| glob_match(p, &ns)))
+            && !self.exclude.iter().any(|p| glob_match(p, &ns))
+    }
+}
+
+impl fmt::Display for SchemaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_tables(f, false)
+    }
+}
+
+/// The namespaces of the regular and time-series, non-system collections in `db`, sorted by
+/// name. Views aside from time-series collections are left out
+#[cfg(feature = "driver")]
+pub async fn collections(client: &Client, db: &str) -> Result<Vec<Namespace>> {
+    let mut names = client
+        .database(db)
+        .list_collection_names(doc! { "type": { "$in": ["collection", "timeseries"] } })
+        .await?;
+    names.retain(|name| !name.starts_with("system."));
+    names.sort();
+    Ok(names
+        .into_iter()
+        .map(|name| Namespace::new(db, name))
+        .collect())
+}
+
+/// The namespaces of the regular collections in every non-system database that pass `filter`
+#[cfg(feature = "driver")]
+pub async fn cluster_namespaces(
+    client: &Client,
+    filter: &NamespaceFilter,
+) -> Result<Vec<Namespace>> {
+    let mut namespaces = Vec::new();
+    for db in client.list_database_names(None, None).await? {
+        if SYSTEM_DATABASES.contains(&db.as_str()) {
+            continue;
+        }
+        namespaces.extend(
+            collections(client, &db)
+                .await?
+                .into_iter()
+                .filter(|ns| filter.matches(ns)),
+        );
+    }
+    Ok(namespaces)
+}
@@ -0,0 +1,328 @@
+//! Checks run against an analyzed schema: the built-in ones over what the analysis found, and
+//! rules declared in the config file matching fields by path, type and presence
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{glob::glob_match, AnomalyKind, BsonType, CollectionSchema, Severity};
+
+/// Something a [`Rule`] found about a field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Finding {
+    /// The name of the rule that found it
+    pub rule: String,
+    pub path: String,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} ({})", self.path, self.message, self.rule)
+    }
+}
+
+/// A check of a schema, fed by its fields and whatever the analysis profiled about their
+/// values. Implement it to run checks of your own with [`CollectionSchema::check_rules`]
+pub trait Rule: Send + Sync {
+    /// Names the rule in its findings
+    fn name(&self) -> &str;
+
+    fn check(&self, schema: &CollectionSchema) -> Vec<Finding>;
+}
+
+/// The rules over what the analysis already looks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BuiltInRule {
+    /// Fields holding conflicting or mixed numeric types, at their anomalies' severity
+    MixedTypes,
+    /// Fields that look like personal data, as warnings. Their values are only looked at if
+    /// [`AnalysisOptions::profile_values`](crate::AnalysisOptions::profile_values) asked
+    Pii,
+    /// Fields rarer than
+    /// [`AnalysisOptions::rare_fields`](crate::AnalysisOptions::rare_fields), for information
+    RareFields,
+}
+
+impl BuiltInRule {
+    pub const ALL: [BuiltInRule; 3] = [
+        BuiltInRule::MixedTypes,
+        BuiltInRule::Pii,
+        BuiltInRule::RareFields,
+    ];
+}
+
+impl Rule for BuiltInRule {
+    fn name(&self) -> &str {
+        match self {
+            BuiltInRule::MixedTypes => "mixed_types",
+            BuiltInRule::Pii => "pii",
+            BuiltInRule::RareFields => "rare_fields",
+        }
+    }
+
+    fn check(&self, schema: &CollectionSchema) -> Vec<Finding> {
+        let finding = |path: &str, severity, described: String| Finding {
+            rule: self.name().to_string(),
+            path: path.to_string(),
+            severity,
+            // the descriptions start with the path, which the finding has already
+            message: described
+                .strip_prefix(&format!("{}: ", path))
+                .map(str::to_string)
+                .unwrap_or(described.clone()),
+        };
+        match self {
+            BuiltInRule::MixedTypes => schema
+                .anomalies
+                .iter()
+                .filter(|anomaly| {
+                    matches!(
+                        anomaly.kind,
+                        AnomalyKind::ConflictingTypes { .. } | AnomalyKind::MixedNumeric { .. }
+                    )
+                })
+                .map(|anomaly| finding(&anomaly.path, anomaly.severity, anomaly.to_string()))
+                .collect(),
+            BuiltInRule::Pii => match &schema.privacy {
+                Some(privacy) => privacy
+                    .iter()
+                    .map(|pii| finding(&pii.path, Severity::Warn, pii.to_string()))
+                    .collect(),
+                None => schema
+                    .find_pii()
+                    .iter()
+                    .map(|pii| finding(&pii.path, Severity::Warn, pii.to_string()))
+                    .collect(),
+            },
+            BuiltInRule::RareFields => schema
+                .rare_fields
+                .iter()
+                .map(|rare| finding(&rare.path, Severity::Info, rare.to_string()))
+                .collect(),
+        }
+    }
+}
+
+/// A rule from the config file, finding the fields that match all of what it gives:
+///
+/// ```toml
+/// [[rules.fields]]
+/// name = "no-legacy-ids"
+/// path = "*Id"
+/// types = ["string"]
+/// severity = "error"
+/// message = "ids should be ObjectIds"
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldRule {
+    pub name: String,
+    /// A pattern of paths, where `*` matches any run of characters and `?` any one
+    #[serde(default)]
+    pub path: Option<String>,
+    /// The field holds any of these types
+    #[serde(default)]
+    pub types: Vec<BsonType>,
+    /// The field is in less than this percentage of the sampled documents
+    #[serde(default)]
+    pub presence_below: Option<f64>,
+    /// The field is in at least this percentage of the sampled documents
+    #[serde(default)]
+    pub presence_at_least: Option<f64>,
+    #[serde(default = "default_severity")]
+    pub severity: Severity,
+    /// What the findings say, describing the match if not given
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+fn default_severity() -> Severity {
+    Severity::Warn
+}
+
+impl Rule for FieldRule {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn check(&self, schema: &CollectionSchema) -> Vec<Finding> {
+        schema
+            .fields
+            .iter()
+            .filter(|(path, _)| {
+                self.path
+                    .as_ref()
+                    .is_none_or(|pattern| glob_match(pattern, path))
+            })
+            .filter(|(_, field)| {
+                self.types.is_empty() || self.types.iter().any(|ty| field.types.contains(ty))
+            })
+            .filter_map(|(path, field)| {
+                let presence = schema.share(field.count) * 100.0;
+                if self.presence_below.is_some_and(|below| presence >= below)
+                    || self
+                        .presence_at_least
+                        .is_some_and(|at_least| presence < at_least)
+                {
+                    return None;
+                }
+                let message = self.message.clone().unwrap_or_else(|| {
+                    let types = field
+                        .types
+                        .iter()
+                        .map(|ty| ty.name())
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("{} in {:.1}% of documents", types, presence)
+                });
+                Some(Finding {
+                    rule: self.name.clone(),
+                    path: path.clone(),
+                    severity: self.severity,
+                    message,
+                })
+            })
+            .collect()
+    }
+}
+
+/// The `[rules]` of a config file: which of the built-in rules to run, all of them unless
+/// listed, and field rules of its own
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RuleConfig {
+    pub builtin: Vec<BuiltInRule>,
+    pub fields: Vec<FieldRule>,
+}
+
+impl Default for RuleConfig {
+    fn default() -> Self {
+        RuleConfig {
+            builtin: BuiltInRule::ALL.to_vec(),
+            fields: Vec::new(),
+        }
+    }
+}
+
+impl RuleConfig {
+    /// The built-in rules, then the field rules, as [`CollectionSchema::check_rules`] takes
+    /// them
+    pub fn rules(&self) -> Vec<Box<dyn Rule>> {
+        let builtin = self
+            .builtin
+            .iter()
+            .map(|rule| Box::new(*rule) as Box<dyn Rule>);
+        let fields = self
+            .fields
+            .iter()
+            .map(|rule| Box::new(rule.clone()) as Box<dyn Rule>);
+        builtin.chain(fields).collect()
+    }
+}
+
+impl CollectionSchema {
+    /// What the rules find, most severe first
+    pub fn check_rules(&self, rules: &[Box<dyn Rule>]) -> Vec<Finding> {
+        let mut findings = rules
+            .iter()
+            .flat_map(|rule| rule.check(self))
+            .collect::<Vec<_>>();
+        findings.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.path.cmp(&b.path))
+                .then_with(|| a.rule.cmp(&b.rule))
+        });
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    const NDJSON: &str = "{\"userId\": \"u1\", \"qty\": 1, \"email\": \"a@example.com\"}\n\
+                          {\"userId\": \"u2\", \"qty\": \"two\"}\n{\"qty\": 3}\n{\"qty\": 4}\n";
+
+    fn schema() -> CollectionSchema {
+        CollectionSchema::from_ndjson(NDJSON.as_bytes(), "db.coll", &AnalysisOptions::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn runs_the_rules_of_a_config_most_severe_first() {
+        let config = toml::from_str::<RuleConfig>(
+            r#"
+            builtin = ["mixed_types", "pii"]
+
+            [[fields]]
+            name = "no-legacy-ids"
+            path = "*Id"
+            types = ["string"]
+            severity = "error"
+            message = "ids should be ObjectIds"
+
+            [[fields]]
+            name = "rarely-there"
+            presence_below = 50
+            "#,
+        )
+        .unwrap();
+        let findings = schema().check_rules(&config.rules());
+        let found = findings
+            .iter()
+            .map(|finding| {
+                (
+                    finding.rule.as_str(),
+                    finding.path.as_str(),
+                    finding.severity,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                ("mixed_types", "qty", Severity::Error),
+                ("no-legacy-ids", "userId", Severity::Error),
+                ("pii", "email", Severity::Warn),
+                ("rarely-there", "email", Severity::Warn),
+            ]
+        );
+        // the path isn't repeated in the message
+        assert_eq!(findings[0].message, "conflicting types int, string");
+        assert_eq!(findings[3].message, "missing, string in 25.0% of documents");
+        assert!(toml::from_str::<RuleConfig>("[[fields]]\nname = \"x\"\npaht = \"*\"").is_err());
+    }
+
+    #[test]
+    fn runs_rules_of_its_own() {
+        struct Deep;
+        impl Rule for Deep {
+            fn name(&self) -> &str {
+                "deep"
+            }
+
+            fn check(&self, schema: &CollectionSchema) -> Vec<Finding> {
+                schema
+                    .fields
+                    .keys()
+                    .filter(|path| path.starts_with('q'))
+                    .map(|path| Finding {
+                        rule: self.name().to_string(),
+                        path: path.clone(),
+                        severity: Severity::Info,
+                        message: "starts with q".to_string(),
+                    })
+                    .collect()
+            }
+        }
+        let rules: Vec<Box<dyn Rule>> = vec![Box::new(Deep), Box::new(BuiltInRule::RareFields)];
+        let findings = schema().check_rules(&rules);
+        assert_eq!(findings.len(), 1, "{:?}", findings);
+        assert_eq!(findings[0].to_string(), "qty: starts with q (deep)");
+    }
+}
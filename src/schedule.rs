@@ -0,0 +1,267 @@
+//! Cron expressions, for re-running analyses on a timetable where change streams aren't
+//! available
+
+use std::{fmt, str::FromStr};
+
+use bson::DateTime;
+
+use crate::stats::civil_date;
+
+const MINUTE_MS: i64 = 60_000;
+const HOUR_MS: i64 = 60 * MINUTE_MS;
+const DAY_MS: i64 = 24 * HOUR_MS;
+
+/// When to run, as a five-field cron expression (`minute hour day-of-month month
+/// day-of-week`) read in UTC. Fields take `*`, numbers, `a-b` ranges, `/n` steps and
+/// comma-separated lists of those; `@hourly`, `@daily`, `@weekly`, `@monthly` and `@yearly`
+/// stand for their usual expressions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    expression: String,
+    /// A bit per allowed value of each field
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether day-of-month and day-of-week were `*`. When neither is, a day matching either
+    /// one is run on, as cron does
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            fields => fields,
+        };
+        let fields = fields.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "expected 5 fields (minute hour day month weekday), got {}",
+                fields.len()
+            ));
+        };
+        // 7 is Sunday as well as 0
+        let weekdays = parse_field(weekday, "day of week", 0, 7)?;
+        Ok(Schedule {
+            expression: expression.trim().to_string(),
+            minutes: parse_field(minute, "minute", 0, 59)?,
+            hours: parse_field(hour, "hour", 0, 23)?,
+            days: parse_field(day, "day of month", 1, 31)?,
+            months: parse_field(month, "month", 1, 12)?,
+            weekdays: (weekdays | weekdays >> 7) & 0x7f,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl Schedule {
+    /// The first minute strictly after `after` the schedule runs on, or `None` if it never
+    /// does, like `0 0 31 2 *`
+    pub fn next_after(&self, after: DateTime) -> Option<DateTime> {
+        let mut time = (after.timestamp_millis().div_euclid(MINUTE_MS) + 1) * MINUTE_MS;
+        // every day-of-month and month combination comes round within 4 years, except
+        // February 29th, within 8 across a skipped leap year
+        let end = time + 8 * 366 * DAY_MS;
+        while time < end {
+            let date = DateTime::from_millis(time);
+            if !self.runs_on(date) {
+                time = (time.div_euclid(DAY_MS) + 1) * DAY_MS;
+                continue;
+            }
+            let hour = time.rem_euclid(DAY_MS) / HOUR_MS;
+            if !has(self.hours, hour) {
+                time = (time.div_euclid(HOUR_MS) + 1) * HOUR_MS;
+                continue;
+            }
+            let minute = time.rem_euclid(HOUR_MS) / MINUTE_MS;
+            if has(self.minutes, minute) {
+                return Some(date);
+            }
+            time += MINUTE_MS;
+        }
+        None
+    }
+
+    /// Whether the schedule runs at some time on the day of `date`
+    fn runs_on(&self, date: DateTime) -> bool {
+        let (_, month, day) = civil_date(date);
+        // 1970-01-01 was a Thursday
+        let weekday = (date.timestamp_millis().div_euclid(DAY_MS) + 4).rem_euclid(7);
+        let day_matches = has(self.days, day.into());
+        let weekday_matches = has(self.weekdays, weekday);
+        has(self.months, month.into())
+            && match (self.any_day, self.any_weekday) {
+                (false, false) => day_matches || weekday_matches,
+                _ => day_matches && weekday_matches,
+            }
+    }
+}
+
+fn has(set: u64, value: i64) -> bool {
+    set & (1 << value) != 0
+}
+
+/// The values a cron field allows, a bit each
+fn parse_field(field: &str, name: &str, min: u64, max: u64) -> Result<u64, String> {
+    let invalid = || format!("invalid {} {:?}", name, field);
+    let number = |s: &str| {
+        s.parse::<u64>()
+            .ok()
+            .filter(|n| (min..=max).contains(n))
+            .ok_or_else(invalid)
+    };
+    let mut set = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (number(start)?, number(end)?),
+            // `5/15` runs from 5 to the end of the field
+            None if part.contains('/') => (number(range)?, max),
+            None => {
+                let n = number(range)?;
+                (n, n)
+            }
+        };
+        if start > end {
+            return Err(invalid());
+        }
+        for value in (start..=end).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime {
+        DateTime::parse_rfc3339_str(time).unwrap()
+    }
+
+    fn next(expression: &str, after: &str) -> Option<String> {
+        let schedule = expression.parse::<Schedule>().unwrap();
+        schedule
+            .next_after(at(after))
+            .map(|next| next.try_to_rfc3339_string().unwrap())
+    }
+
+    #[test]
+    fn parses_expressions() {
+        let schedule = "*/15 9-17 * * 1-5".parse::<Schedule>().unwrap();
+        assert_eq!(schedule.minutes, 1 | 1 << 15 | 1 << 30 | 1 << 45);
+        assert_eq!(
+            schedule.hours,
+            (9..=17).fold(0, |set, hour| set | 1 << hour)
+        );
+        assert_eq!(schedule.weekdays, 0b0111110);
+        assert!(schedule.any_day && !schedule.any_weekday);
+        assert_eq!(schedule.to_string(), "*/15 9-17 * * 1-5");
+
+        let sundays = "0 0 * * 7".parse::<Schedule>().unwrap();
+        assert_eq!(sundays.weekdays, 1);
+        assert_eq!(
+            "@weekly".parse::<Schedule>().unwrap().weekdays,
+            sundays.weekdays
+        );
+        assert_eq!(
+            "5/20,1 * * * *".parse::<Schedule>().unwrap().minutes,
+            1 << 1 | 1 << 5 | 1 << 25 | 1 << 45
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for (expression, error) in [
+            (
+                "* * * *",
+                "expected 5 fields (minute hour day month weekday), got 4",
+            ),
+            ("60 * * * *", "invalid minute \"60\""),
+            ("* 24 * * *", "invalid hour \"24\""),
+            ("* * 0 * *", "invalid day of month \"0\""),
+            ("* * * 13 *", "invalid month \"13\""),
+            ("* * * * 8", "invalid day of week \"8\""),
+            ("*/0 * * * *", "invalid minute \"*/0\""),
+            ("30-10 * * * *", "invalid minute \"30-10\""),
+            (
+                "@often",
+                "expected 5 fields (minute hour day month weekday), got 1",
+            ),
+        ] {
+            assert_eq!(expression.parse::<Schedule>().unwrap_err(), error);
+        }
+    }
+
+    #[test]
+    fn finds_the_next_run() {
+        assert_eq!(
+            next("*/15 * * * *", "2024-01-05T10:07:30Z").as_deref(),
+            Some("2024-01-05T10:15:00Z")
+        );
+        // strictly after
+        assert_eq!(
+            next("*/15 * * * *", "2024-01-05T10:15:00Z").as_deref(),
+            Some("2024-01-05T10:30:00Z")
+        );
+        assert_eq!(
+            next("@daily", "2024-12-31T23:59:30Z").as_deref(),
+            Some("2025-01-01T00:00:00Z")
+        );
+        // from Friday to Monday
+        assert_eq!(
+            next("0 9 * * 1-5", "2024-01-05T10:00:00Z").as_deref(),
+            Some("2024-01-08T09:00:00Z")
+        );
+        assert_eq!(
+            next("@monthly", "2024-01-31T12:00:00Z").as_deref(),
+            Some("2024-02-01T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn runs_on_either_day_when_both_are_given() {
+        // the 13th, or a Friday
+        assert_eq!(
+            next("0 0 13 * 5", "2024-01-06T00:00:00Z").as_deref(),
+            Some("2024-01-12T00:00:00Z")
+        );
+        assert_eq!(
+            next("0 0 13 * 5", "2024-01-12T00:00:00Z").as_deref(),
+            Some("2024-01-13T00:00:00Z")
+        );
+    }
+
+    #[test]
+    fn waits_for_leap_days_and_gives_up_on_impossible_dates() {
+        assert_eq!(
+            next("0 0 29 2 *", "2024-03-01T00:00:00Z").as_deref(),
+            Some("2028-02-29T00:00:00Z")
+        );
+        assert_eq!(next("0 0 31 2 *", "2024-01-01T00:00:00Z"), None);
+    }
+}
@@ -0,0 +1,1147 @@
+use std::{collections::BTreeMap, fmt, ops::Bound, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Anomaly, Confidence, DateStats, DocumentSizes, DocumentWidths, Evolution, ExplainStats,
+    FieldNameIssue, FieldSizes, Finding, Indexes, Interval, NumericStats, PiiFinding, RareField,
+    Shards, Strategy, StringLengthStats, TimeSeries, TypeMode, ValueProfile, Variants, Versions,
+    ViewDefinition,
+};
+
+/// The version of the JSON schemas, reports and diffs are written in, see [`FormatVersion`]
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The version of the format a schema, report or diff was written in, serialized as its
+/// `formatVersion`. Files and stored results from before the format was versioned read as
+/// version 1, and anything newer than [`FORMAT_VERSION`] fails to read rather than losing what
+/// this version can't represent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(transparent)]
+pub struct FormatVersion(pub u32);
+
+impl FormatVersion {
+    /// The version of documents without a `formatVersion`
+    pub(crate) fn unversioned() -> Self {
+        FormatVersion(1)
+    }
+}
+
+impl Default for FormatVersion {
+    fn default() -> Self {
+        FormatVersion(FORMAT_VERSION)
+    }
+}
+
+impl<'de> Deserialize<'de> for FormatVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let version = u32::deserialize(deserializer)?;
+        if version > FORMAT_VERSION {
+            return Err(serde::de::Error::custom(format!(
+                "format version {} is newer than the {} this version of schema-analyzer reads",
+                version, FORMAT_VERSION
+            )));
+        }
+        Ok(FormatVersion(version))
+    }
+}
+
+/// The inferred schema of a single collection
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CollectionSchema {
+    #[serde(rename = "formatVersion", default = "FormatVersion::unversioned")]
+    pub format_version: FormatVersion,
+    /// The `db.collection` the schema was inferred from
+    pub namespace: String,
+    /// The collection's estimated document count at the time of analysis, or the exact number
+    /// of documents matching [`AnalysisOptions::filter`](crate::AnalysisOptions::filter)
+    pub document_count: u64,
+    /// How many documents were sampled. Can be lower than requested for small collections
+    pub sample_size: u64,
+    /// How the sampled documents were chosen, and so how far the schema can be trusted to
+    /// cover the whole collection
+    #[serde(default)]
+    pub sample_method: SampleMethod,
+    /// Every field seen in the sample, keyed by path. Nested fields use dotted paths
+    /// (`address.city`) and array elements the array's path with a `[]` suffix (`tags[]`)
+    pub fields: BTreeMap<String, FieldSchema>,
+    /// How the analysis ran: the tool and server versions and how long each phase took
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta: Option<RunMetadata>,
+    /// The most common document shapes, if [`AnalysisOptions::shapes`](crate::AnalysisOptions::shapes)
+    /// asked for them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shapes: Option<Shapes>,
+    /// What [`CollectionSchema::find_anomalies`] found in the fields, most severe first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub anomalies: Vec<Anomaly>,
+    /// What [`CollectionSchema::find_pii`] found, if
+    /// [`AnalysisOptions::detect_pii`](crate::AnalysisOptions::detect_pii) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub privacy: Option<Vec<PiiFinding>>,
+    /// What [`CollectionSchema::find_rare_fields`] found, with the threshold of
+    /// [`AnalysisOptions::rare_fields`](crate::AnalysisOptions::rare_fields)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rare_fields: Vec<RareField>,
+    /// What [`CollectionSchema::check_rules`] found with the config file's rules, most severe
+    /// first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub findings: Vec<Finding>,
+    /// How far the presences can be trusted for the whole collection, if the sample was of
+    /// part of it, see [`CollectionSchema::estimate_confidence`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<Confidence>,
+    /// The fields whose names break drivers and updates, if
+    /// [`AnalysisOptions::check_field_names`](crate::AnalysisOptions::check_field_names) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field_names: Option<Vec<FieldNameIssue>>,
+    /// How the documents' bytes divide between their top-level fields, if
+    /// [`AnalysisOptions::field_sizes`](crate::AnalysisOptions::field_sizes) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sizes: Option<FieldSizes>,
+    /// How large the sampled documents are, if
+    /// [`AnalysisOptions::document_sizes`](crate::AnalysisOptions::document_sizes) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_sizes: Option<DocumentSizes>,
+    /// How many top-level keys the sampled documents have, if
+    /// [`AnalysisOptions::document_widths`](crate::AnalysisOptions::document_widths) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub document_widths: Option<DocumentWidths>,
+    /// How many sampled documents had more top-level fields than
+    /// [`AnalysisOptions::max_fields_per_level`](crate::AnalysisOptions::max_fields_per_level),
+    /// so only their first fields are in the schema
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub truncated_documents: u64,
+    /// The schemas of each value of the discriminator, if
+    /// [`AnalysisOptions::group_by`](crate::AnalysisOptions::group_by) asked. The fields
+    /// above are still those of every document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variants: Option<Variants>,
+    /// The schemas of each value of the version field and how they changed, if
+    /// [`AnalysisOptions::version_field`](crate::AnalysisOptions::version_field) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub versions: Option<Versions>,
+    /// How the fields changed over the periods of a date field, if
+    /// [`AnalysisOptions::time_field`](crate::AnalysisOptions::time_field) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub evolution: Option<Evolution>,
+    /// The collection's time-series configuration and the schema of its series' metadata,
+    /// if it's a time-series collection
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_series: Option<TimeSeries>,
+    /// What the namespace is a view of, if it's a view
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view: Option<ViewDefinition>,
+    /// How the fields differ from shard to shard, if
+    /// [`SchemaAnalyzer::analyze_shards`](crate::SchemaAnalyzer::analyze_shards) was run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub shards: Option<Shards>,
+    /// What the collection's indexes cover, if
+    /// [`SchemaAnalyzer::index_report`](crate::SchemaAnalyzer::index_report) was run
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub indexes: Option<Indexes>,
+    /// The names the types are reported in, see [`CollectionSchema::normalize_types`]
+    #[serde(default, skip_serializing_if = "TypeMode::is_strict")]
+    pub type_mode: TypeMode,
+}
+
+impl CollectionSchema {
+    /// The fraction of sampled documents containing the field at `path`
+    pub fn presence(&self, path: &str) -> Option<f64> {
+        let field = self.fields.get(path)?;
+        Some(self.share(field.count))
+    }
+
+    /// The fraction of sampled documents holding null for the field at `path`. Unlike
+    /// documents lacking the field, these count towards its [`presence`](Self::presence)
+    pub fn null_fraction(&self, path: &str) -> Option<f64> {
+        let field = self.fields.get(path)?;
+        Some(self.share(field.nulls()))
+    }
+
+    /// The types of the field at `path`, most frequent first, `missing` among them if some
+    /// documents lack it
+    pub fn types_of(&self, path: &str) -> Option<&[BsonType]> {
+        self.fields.get(path).map(|field| field.types.as_slice())
+    }
+
+    /// The fraction of the objects at the field's parent path containing the field at
+    /// `path`: of the sampled documents for top-level fields, of the subdocuments holding
+    /// its parent for nested ones, and of the elements for the fields of subdocuments in
+    /// arrays, when their [`element_presence`](FieldSchema::element_presence) was counted.
+    /// Unlike [`presence`](Self::presence), a field of an optional subdocument that is in
+    /// every one of them has a presence of 1
+    pub fn presence_of(&self, path: &str) -> Option<f64> {
+        let field = self.fields.get(path)?;
+        if let Some(presence) = field.element_presence {
+            return Some(presence.share());
+        }
+        if let Some(presence) = field.conditional_presence {
+            return Some(presence);
+        }
+        let parent = match path.rsplit_once('.') {
+            Some((parent, _)) => self
+                .fields
+                .get(parent)
+                .and_then(|parent| parent.type_counts.get(&BsonType::Object))
+                .copied()
+                .unwrap_or_default(),
+            None => self.sample_size,
+        };
+        Some(match parent {
+            0 => 0.0,
+            parent => (field.count as f64 / parent as f64).min(1.0),
+        })
+    }
+
+    /// Whether the field at `path` is missing from some of the objects that could hold it,
+    /// as generated code and validators go by, see
+    /// [`is_required`](Self::is_required)
+    pub fn is_optional(&self, path: &str) -> Option<bool> {
+        self.fields.get(path)?;
+        Some(!self.is_required(path, 1.0))
+    }
+
+    /// Whether the field at `path` holds more than one type, not counting missing
+    pub fn is_polymorphic(&self, path: &str) -> Option<bool> {
+        self.fields.get(path).map(FieldSchema::is_mixed)
+    }
+
+    /// How many field paths the schema reports, nested fields and array elements included
+    pub fn field_count(&self) -> usize {
+        self.fields.len()
+    }
+
+    /// `count` as a fraction of the sampled documents
+    pub(crate) fn share(&self, count: u64) -> f64 {
+        if self.sample_size == 0 {
+            0.0
+        } else {
+            count as f64 / self.sample_size as f64
+        }
+    }
+
+    /// How `ty` is reported for the field at `path`: objects that are all GeoJSON points are
+    /// `geojson:Point`, or `geojson` for several geometry types, and DBRefs are
+    /// `dbref:users`, or `dbref` when their targets are several or weren't profiled
+    pub(crate) fn type_label(&self, path: &str, ty: &BsonType) -> String {
+        if *ty != BsonType::Object {
+            return ty.to_string();
+        }
+        if let Some(types) = self.fields.get(path).and_then(FieldSchema::geojson_types) {
+            return match types[..] {
+                [only] => format!("geojson:{}", only),
+                _ => "geojson".to_string(),
+            };
+        }
+        match self.dbref_targets(path).as_deref() {
+            Some([only]) => format!("dbref:{}", only),
+            _ if self.is_dbref(path) => "dbref".to_string(),
+            _ => ty.to_string(),
+        }
+    }
+
+    /// Render the type of the field at `path` with its contents inlined, e.g.
+    /// `array<object{sku: string, qty: int}>`
+    pub fn type_expression(&self, path: &str) -> Option<String> {
+        let field = self.fields.get(path)?;
+        let types = field
+            .types
+            .iter()
+            .map(|t| match t {
+                BsonType::Array => match self.type_expression(&format!("{}[]", path)) {
+                    Some(elements) => format!("array<{}>", elements),
+                    None => "array".to_string(),
+                },
+                BsonType::Object => {
+                    let children = self
+                        .children(&format!("{}.", path))
+                        .map(|(name, child)| {
+                            format!("{}: {}", name, self.type_expression(child).unwrap())
+                        })
+                        .collect::<Vec<_>>();
+                    if children.is_empty() {
+                        "object".to_string()
+                    } else {
+                        format!("object{{{}}}", children.join(", "))
+                    }
+                }
+                other => other.to_string(),
+            })
+            .collect::<Vec<_>>();
+        Some(types.join(" | "))
+    }
+
+    /// Fill in every field's [`FieldSchema::type_shares`]
+    pub fn share_types(&mut self) {
+        for field in self.fields.values_mut() {
+            field.type_shares = Some(field.shares());
+        }
+    }
+
+    /// Fill in the [`FieldSchema::conditional_presence`] of every field of a subdocument
+    /// that isn't an array element, which [`FieldSchema::element_presence`] covers. Done
+    /// before fields are left out of the report, which may leave a field without its parent
+    pub fn condition_presence(&mut self) {
+        condition_presence(&mut self.fields);
+    }
+
+    /// Report the types in `mode`'s names, merging the counts of types it collapses. Going
+    /// back to [`TypeMode::Strict`] is a no-op, as the collapsed types can't be told apart.
+    /// Array elements count a document once per type, so one holding both ints and doubles
+    /// counted twice: merged counts are capped at the field's count, but can still overcount
+    pub fn normalize_types(&mut self, mode: TypeMode) {
+        if mode == TypeMode::Strict {
+            return;
+        }
+        for field in self.fields.values_mut() {
+            let had_shares = field.type_shares.is_some();
+            let mut type_counts = BTreeMap::<BsonType, u64>::new();
+            for (ty, count) in &field.type_counts {
+                *type_counts.entry(ty.normalize(mode)).or_default() += count;
+            }
+            for (ty, count) in &mut type_counts {
+                if *ty != BsonType::Missing {
+                    *count = (*count).min(field.count);
+                }
+            }
+            *field = FieldSchema {
+                values: field.values.take(),
+                stats: field.stats.take(),
+                length_stats: field.length_stats.take(),
+                date_stats: field.date_stats.take(),
+                binary_subtypes: std::mem::take(&mut field.binary_subtypes),
+                truncated: field.truncated,
+                presence_interval: field.presence_interval,
+                element_presence: field.element_presence,
+                conditional_presence: field.conditional_presence,
+                ..FieldSchema::new(field.count, type_counts)
+            };
+            if had_shares {
+                field.type_shares = Some(field.shares());
+            }
+        }
+        if let Some(shapes) = &mut self.shapes {
+            shapes.normalize_types(mode);
+        }
+        if let Some(evolution) = &mut self.evolution {
+            evolution.normalize_types(mode);
+        }
+        self.type_mode = mode;
+    }
+
+    /// The warning about documents whose top-level fields were cut short, if there are any
+    pub(crate) fn truncation_warning(&self) -> Option<String> {
+        (self.truncated_documents > 0).then(|| {
+            format!(
+                "{} documents have more top-level fields than the limit, only their first \
+                 fields were analyzed",
+                self.truncated_documents
+            )
+        })
+    }
+
+    /// The direct children of the object whose paths start with `prefix`, as (name, path) pairs
+    pub(crate) fn children<'a>(
+        &'a self,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = (&'a str, &'a str)> + 'a {
+        self.fields
+            .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+            .map(|(path, _)| path.as_str())
+            .take_while(move |path| path.starts_with(prefix))
+            .filter_map(move |path| {
+                let name = &path[prefix.len()..];
+                (!name.contains('.') && !name.ends_with("[]")).then_some((name, path))
+            })
+    }
+}
+
+impl fmt::Display for CollectionSchema {
+    /// A table of every field path with its presence and type distribution
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_table(f, false)
+    }
+}
+
+/// What was observed for a single field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSchema {
+    /// The types seen for the field, most frequent first. Documents lacking the field
+    /// contribute [`BsonType::Missing`], as do empty arrays for the `[]` element path, and
+    /// explicit nulls [`BsonType::Null`]
+    pub types: Vec<BsonType>,
+    /// How many sampled documents contain the field
+    pub count: u64,
+    /// How many sampled documents hold each type for the field. For array elements a document
+    /// counts once for every type among its elements
+    pub type_counts: BTreeMap<BsonType, u64>,
+    /// What the field's values looked like, if
+    /// [`AnalysisOptions::profile_values`](crate::AnalysisOptions::profile_values) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub values: Option<ValueProfile>,
+    /// Statistics over the field's numeric values, if it held any and
+    /// [`AnalysisOptions::stats`](crate::AnalysisOptions::stats) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stats: Option<NumericStats>,
+    /// The lengths of the field's strings, if it held any and
+    /// [`AnalysisOptions::stats`](crate::AnalysisOptions::stats) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length_stats: Option<StringLengthStats>,
+    /// The range and distribution of the field's dates, if it held any and
+    /// [`AnalysisOptions::stats`](crate::AnalysisOptions::stats) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub date_stats: Option<DateStats>,
+    /// How many of the field's binary values have each subtype, if
+    /// [`AnalysisOptions::stats`](crate::AnalysisOptions::stats) asked
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub binary_subtypes: BTreeMap<BinaryKind, u64>,
+    /// How many sampled documents held a subdocument or array here that wasn't fully
+    /// descended into, having more fields or elements than
+    /// [`AnalysisOptions::max_fields_per_level`](crate::AnalysisOptions::max_fields_per_level)
+    /// or [`AnalysisOptions::max_array_elements`](crate::AnalysisOptions::max_array_elements),
+    /// or sitting at [`AnalysisOptions::max_depth`](crate::AnalysisOptions::max_depth). What's
+    /// nested under it may be incomplete
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub truncated: u64,
+    /// The range the share of the collection's documents holding the field likely lies in,
+    /// if the sample was of part of it, see [`CollectionSchema::estimate_confidence`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub presence_interval: Option<Interval>,
+    /// For the fields of subdocuments in arrays, like `items[].discount`, how many of the
+    /// subdocuments held it, each element counting rather than each document
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub element_presence: Option<ElementPresence>,
+    /// For the fields of subdocuments, the share of the objects at the nearest ancestor path
+    /// that was present holding the field, e.g. 1 for an `address.city` in every address
+    /// when only some documents have one. See [`CollectionSchema::condition_presence`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub conditional_presence: Option<f64>,
+    /// Each type's share of the field's occurrences, if
+    /// [`AnalysisOptions::type_shares`](crate::AnalysisOptions::type_shares) asked, see
+    /// [`FieldSchema::shares`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub type_shares: Option<Vec<(BsonType, f64)>>,
+}
+
+/// See [`CollectionSchema::condition_presence`]
+pub(crate) fn condition_presence(fields: &mut BTreeMap<String, FieldSchema>) {
+    // the objects holding the nearest ancestor that was an object at all
+    let holding = |path: &str| {
+        for (end, _) in path.match_indices('.').rev() {
+            let ancestor = &path[..end];
+            if ancestor.ends_with("[]") {
+                return None;
+            }
+            let objects = fields
+                .get(ancestor)
+                .and_then(|ancestor| ancestor.type_counts.get(&BsonType::Object))
+                .copied()
+                .unwrap_or_default();
+            if objects > 0 {
+                return Some(objects);
+            }
+        }
+        None
+    };
+    let conditional = fields
+        .iter()
+        .filter_map(|(path, field)| {
+            let objects = holding(path)?;
+            Some((path.clone(), (field.count as f64 / objects as f64).min(1.0)))
+        })
+        .collect::<Vec<_>>();
+    for (path, presence) in conditional {
+        fields.get_mut(&path).unwrap().conditional_presence = Some(presence);
+    }
+}
+
+/// How many of the subdocuments in arrays hold a field, see
+/// [`FieldSchema::element_presence`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ElementPresence {
+    /// The subdocuments holding the field
+    pub count: u64,
+    /// The subdocuments at the field's parent path
+    pub of: u64,
+}
+
+impl ElementPresence {
+    /// The share of the subdocuments holding the field
+    pub fn share(&self) -> f64 {
+        if self.of == 0 {
+            0.0
+        } else {
+            self.count as f64 / self.of as f64
+        }
+    }
+}
+
+impl fmt::Display for ElementPresence {
+    /// e.g. `in 12.0% of elements (30 of 250)`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "in {:.1}% of elements ({} of {})",
+            self.share() * 100.0,
+            self.count,
+            self.of
+        )
+    }
+}
+
+impl FieldSchema {
+    pub(crate) fn new(count: u64, type_counts: BTreeMap<BsonType, u64>) -> Self {
+        let mut types = type_counts.keys().cloned().collect::<Vec<_>>();
+        types.sort_by(|a, b| type_counts[b].cmp(&type_counts[a]));
+        Self {
+            types,
+            count,
+            type_counts,
+            values: None,
+            stats: None,
+            length_stats: None,
+            date_stats: None,
+            binary_subtypes: BTreeMap::new(),
+            truncated: 0,
+            presence_interval: None,
+            element_presence: None,
+            conditional_presence: None,
+            type_shares: None,
+        }
+    }
+
+    /// How many sampled documents lack the field
+    pub fn missing(&self) -> u64 {
+        self.type_counts
+            .get(&BsonType::Missing)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// How many sampled documents hold null for the field
+    pub fn nulls(&self) -> u64 {
+        self.type_counts
+            .get(&BsonType::Null)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// The example values kept for the field, as compact JSON joined by commas
+    pub(crate) fn examples(&self) -> Option<String> {
+        let examples = &self.values.as_ref()?.examples;
+        if examples.is_empty() {
+            return None;
+        }
+        let examples = examples.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        Some(examples.join(", "))
+    }
+
+    /// The field's top values with their counts, e.g. `"shipped" 20, "pending" 12`
+    pub(crate) fn top_values(&self) -> Option<String> {
+        let top = &self.values.as_ref()?.top_values;
+        if top.is_empty() {
+            return None;
+        }
+        let top = top
+            .iter()
+            .map(|top| format!("{} {}", top.value, top.count))
+            .collect::<Vec<_>>();
+        Some(top.join(", "))
+    }
+
+    /// The field's binary subtypes with their counts, e.g. `uuid 90, uuid (legacy) 10`
+    pub(crate) fn binary_subtypes(&self) -> Option<String> {
+        if self.binary_subtypes.is_empty() {
+            return None;
+        }
+        let mut subtypes = self.binary_subtypes.iter().collect::<Vec<_>>();
+        subtypes.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        let subtypes = subtypes
+            .into_iter()
+            .map(|(kind, count)| format!("{} {}", kind, count))
+            .collect::<Vec<_>>();
+        Some(subtypes.join(", "))
+    }
+
+    /// Each type's share of the documents holding the field, missing aside, most frequent
+    /// first. They add up to 1, even for array elements, where a document counts once for
+    /// every type among its elements. Empty if the field was never seen
+    pub fn shares(&self) -> Vec<(BsonType, f64)> {
+        let held = self
+            .type_counts
+            .iter()
+            .filter(|(ty, _)| **ty != BsonType::Missing)
+            .map(|(_, count)| count)
+            .sum::<u64>();
+        if held == 0 {
+            return Vec::new();
+        }
+        self.types
+            .iter()
+            .filter(|ty| **ty != BsonType::Missing)
+            .map(|ty| (ty.clone(), self.type_counts[ty] as f64 / held as f64))
+            .collect()
+    }
+
+    /// Whether the field holds more than one type, not counting missing
+    pub fn is_mixed(&self) -> bool {
+        self.types
+            .iter()
+            .filter(|t| **t != BsonType::Missing)
+            .count()
+            > 1
+    }
+}
+
+/// The sampled documents grouped by their exact set of field paths and types
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Shapes {
+    /// How many different shapes the sample held
+    pub distinct: u64,
+    /// The most common shapes, most frequent first
+    pub top: Vec<DocumentShape>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DocumentShape {
+    /// The types at each path in documents of this shape. Array elements can put more than
+    /// one type under a path
+    pub fields: BTreeMap<String, Vec<BsonType>>,
+    /// How many sampled documents have exactly this shape
+    pub count: u64,
+}
+
+impl DocumentShape {
+    /// The shape's fields as `path: type` pairs, with a path's types joined by `|`
+    pub fn describe(&self) -> String {
+        self.fields
+            .iter()
+            .map(|(path, types)| {
+                let types = types.iter().map(BsonType::name).collect::<Vec<_>>();
+                format!("{}: {}", path, types.join("|"))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+impl Shapes {
+    /// How many shapes there are and how many of them are listed, e.g. "5 distinct document
+    /// shapes, the 3 most common"
+    pub(crate) fn summary(&self) -> String {
+        let shapes = match self.distinct {
+            1 => "shape",
+            _ => "shapes",
+        };
+        if self.top.len() as u64 >= self.distinct {
+            format!("{} distinct document {}", self.distinct, shapes)
+        } else {
+            format!(
+                "{} distinct document {}, the {} most common",
+                self.distinct,
+                shapes,
+                self.top.len()
+            )
+        }
+    }
+
+    /// Drop the paths `keep` rejects, merging shapes that only differed in them
+    pub(crate) fn retain_paths(&mut self, keep: impl Fn(&str) -> bool) {
+        for shape in &mut self.top {
+            shape.fields.retain(|path, _| keep(path));
+        }
+        self.merge();
+    }
+
+    /// Name the shapes' types as `mode` does, merging shapes that only differed in types it
+    /// collapses
+    pub(crate) fn normalize_types(&mut self, mode: TypeMode) {
+        for shape in &mut self.top {
+            for types in shape.fields.values_mut() {
+                let mut normalized = Vec::new();
+                for ty in types.iter().map(|ty| ty.normalize(mode)) {
+                    if !normalized.contains(&ty) {
+                        normalized.push(ty);
+                    }
+                }
+                *types = normalized;
+            }
+        }
+        self.merge();
+    }
+
+    /// Merge the top shapes a change to their fields made identical. Shapes that didn't make
+    /// the top can't be merged, so `distinct` may then overcount
+    fn merge(&mut self) {
+        let mut merged = Vec::<DocumentShape>::new();
+        for shape in self.top.drain(..) {
+            match merged.iter_mut().find(|other| other.fields == shape.fields) {
+                Some(other) => {
+                    other.count += shape.count;
+                    self.distinct -= 1;
+                }
+                None => merged.push(shape),
+            }
+        }
+        merged.sort_by_key(|shape| std::cmp::Reverse(shape.count));
+        self.top = merged;
+    }
+}
+
+/// How the documents a schema was inferred from were chosen
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleMethod {
+    /// A random `$sample`
+    Random,
+    /// The first documents in natural order, which may all share the shape of older data
+    First,
+    /// The last documents in natural order, the newest, see
+    /// [`SampleStrategy::Recent`](crate::SampleStrategy::Recent)
+    Recent,
+    /// Documents spread evenly through a file
+    Spread,
+    /// The documents whose `_id` hashes below a threshold under a seed, see
+    /// [`AnalysisOptions::seed`](crate::AnalysisOptions::seed)
+    Seeded,
+    /// Up to a fixed number of random documents per value of a field, see
+    /// [`AnalysisOptions::stratify_by`](crate::AnalysisOptions::stratify_by)
+    Stratified,
+    /// Every document
+    #[default]
+    All,
+    /// None: the schema was converted from a JSON Schema, see
+    /// [`CollectionSchema::from_json_schema`], so it says which fields and types are allowed
+    /// but not how common they are
+    Declared,
+}
+
+impl fmt::Display for SampleMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SampleMethod::Random => "random sample",
+            SampleMethod::First => "first in natural order",
+            SampleMethod::Recent => "last in natural order",
+            SampleMethod::Spread => "evenly spaced",
+            SampleMethod::Seeded => "seeded hash of _id",
+            SampleMethod::Stratified => "stratified random sample",
+            SampleMethod::All => "every document",
+            SampleMethod::Declared => "declared by a JSON Schema",
+        })
+    }
+}
+
+/// How and by what an analysis ran, for tracking its cost and provenance
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    /// The version of schema-analyzer that ran the analysis
+    pub tool_version: String,
+    /// The server's version, as `buildInfo` reports it, if it answered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_version: Option<String>,
+    /// Where the schema was inferred, after any fallback: `pipeline` or `client`. Absent for
+    /// files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<Strategy>,
+    pub document_count: u64,
+    pub sample_size: u64,
+    /// How long each phase took. Absent for files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
+    /// What the server did running the sampling, if
+    /// [`SchemaAnalyzer::explain`](crate::SchemaAnalyzer::explain) was run as well
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub explain: Option<ExplainStats>,
+}
+
+impl RunMetadata {
+    pub(crate) fn new(document_count: u64, sample_size: u64) -> Self {
+        RunMetadata {
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            server_version: None,
+            strategy: None,
+            document_count,
+            sample_size,
+            timings: None,
+            explain: None,
+        }
+    }
+
+    /// The run line, e.g. `schema-analyzer 0.1.0 against MongoDB 7.0.2, pipeline strategy;
+    /// pre-query 3.1ms, query 240.5ms, post-query 12.0ms`
+    pub(crate) fn describe(&self) -> String {
+        let mut out = format!("schema-analyzer {}", self.tool_version);
+        if let Some(version) = &self.server_version {
+            out += &format!(" against MongoDB {}", version);
+        }
+        if let Some(strategy) = self.strategy {
+            let strategy = serde_json::to_value(strategy).unwrap_or_default();
+            out += &format!(", {} strategy", strategy.as_str().unwrap_or_default());
+        }
+        if let Some(timings) = &self.timings {
+            out += &format!(
+                "; pre-query {:.1?}, query {:.1?}, post-query {:.1?}",
+                timings.pre_query, timings.query, timings.post_query
+            );
+            if let Some(rate) = timings.read_rate {
+                out += &format!(", {:.0} documents/s", rate);
+            }
+        }
+        out
+    }
+}
+
+/// Wall-clock durations of the analysis phases, serialized as milliseconds
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timings {
+    /// Counting documents and building the pipeline
+    #[serde(rename = "pre_query_ms", with = "millis")]
+    pub pre_query: Duration,
+    /// Running the aggregation until the cursor is returned
+    #[serde(rename = "query_ms", with = "millis")]
+    pub query: Duration,
+    /// Draining the cursor and building the schema
+    #[serde(rename = "post_query_ms", with = "millis")]
+    pub post_query: Duration,
+    /// How many documents a second a client-side scan read, over the whole scan
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub read_rate: Option<f64>,
+}
+
+/// Durations as fractional milliseconds
+pub(crate) mod millis {
+    use std::time::Duration;
+
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        let ms = f64::deserialize(deserializer)?;
+        Duration::try_from_secs_f64(ms / 1000.0).map_err(D::Error::custom)
+    }
+}
+
+/// A type observed for a field: one of the names `$type` returns, or [`BsonType::Missing`] for
+/// documents lacking the field. Serialized as the `$type` name, e.g. `"objectId"`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BsonType {
+    Double,
+    String,
+    Object,
+    Array,
+    BinData,
+    Undefined,
+    ObjectId,
+    Bool,
+    Date,
+    /// An explicit null value, as opposed to the field being absent
+    Null,
+    Regex,
+    DbPointer,
+    Javascript,
+    Symbol,
+    JavascriptWithScope,
+    Int,
+    Timestamp,
+    Long,
+    Decimal,
+    MinKey,
+    MaxKey,
+    /// Any of int, long, double and decimal, which [`TypeMode::Canonical`] collapses them to.
+    /// `$jsonSchema` accepts it as a `bsonType` alias
+    Number,
+    /// The field is absent from the document. For the `[]` element path, the array is empty
+    Missing,
+    /// A type added by a newer server than we know about
+    Other(String),
+}
+
+/// Every type but [`BsonType::Other`], in `$type` number order
+pub(crate) const KNOWN_TYPES: [BsonType; 22] = [
+    BsonType::Double,
+    BsonType::String,
+    BsonType::Object,
+    BsonType::Array,
+    BsonType::BinData,
+    BsonType::Undefined,
+    BsonType::ObjectId,
+    BsonType::Bool,
+    BsonType::Date,
+    BsonType::Null,
+    BsonType::Regex,
+    BsonType::DbPointer,
+    BsonType::Javascript,
+    BsonType::Symbol,
+    BsonType::JavascriptWithScope,
+    BsonType::Int,
+    BsonType::Timestamp,
+    BsonType::Long,
+    BsonType::Decimal,
+    BsonType::MinKey,
+    BsonType::MaxKey,
+    BsonType::Missing,
+];
+
+impl BsonType {
+    /// The name `$type` reports, or "missing"
+    pub fn name(&self) -> &str {
+        match self {
+            BsonType::Double => "double",
+            BsonType::String => "string",
+            BsonType::Object => "object",
+            BsonType::Array => "array",
+            BsonType::BinData => "binData",
+            BsonType::Undefined => "undefined",
+            BsonType::ObjectId => "objectId",
+            BsonType::Bool => "bool",
+            BsonType::Date => "date",
+            BsonType::Null => "null",
+            BsonType::Regex => "regex",
+            BsonType::DbPointer => "dbPointer",
+            BsonType::Javascript => "javascript",
+            BsonType::Symbol => "symbol",
+            BsonType::JavascriptWithScope => "javascriptWithScope",
+            BsonType::Int => "int",
+            BsonType::Timestamp => "timestamp",
+            BsonType::Long => "long",
+            BsonType::Decimal => "decimal",
+            BsonType::MinKey => "minKey",
+            BsonType::MaxKey => "maxKey",
+            BsonType::Number => "number",
+            BsonType::Missing => "missing",
+            BsonType::Other(name) => name,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Self {
+        if name == "number" {
+            return BsonType::Number;
+        }
+        KNOWN_TYPES
+            .iter()
+            .find(|known| known.name() == name)
+            .cloned()
+            .unwrap_or_else(|| BsonType::Other(name.to_string()))
+    }
+
+    /// The type in `mode`'s names: under [`TypeMode::Canonical`] the numeric types become
+    /// number, and the deprecated symbol, undefined and javascriptWithScope the string, null
+    /// and javascript they stand in for
+    pub fn normalize(&self, mode: TypeMode) -> BsonType {
+        match (mode, self) {
+            (TypeMode::Strict, ty) => ty.clone(),
+            (
+                TypeMode::Canonical,
+                BsonType::Int | BsonType::Long | BsonType::Double | BsonType::Decimal,
+            ) => BsonType::Number,
+            (TypeMode::Canonical, BsonType::Symbol) => BsonType::String,
+            (TypeMode::Canonical, BsonType::Undefined) => BsonType::Null,
+            (TypeMode::Canonical, BsonType::JavascriptWithScope) => BsonType::Javascript,
+            (TypeMode::Canonical, ty) => ty.clone(),
+        }
+    }
+}
+
+impl fmt::Display for BsonType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl Serialize for BsonType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for BsonType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from_name(&String::deserialize(deserializer)?))
+    }
+}
+
+/// The subtype of a `binData` value, which says how its bytes are meant to be read
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryKind {
+    /// Subtype 0, plain bytes
+    Generic,
+    /// Subtype 1
+    Function,
+    /// Subtype 2, superseded by subtype 0
+    BinaryOld,
+    /// Subtype 3, a UUID in a driver-specific byte order. Drivers read it back differently,
+    /// so it should be migrated to [`BinaryKind::Uuid`]
+    UuidLegacy,
+    /// Subtype 4, a UUID in the standard byte order
+    Uuid,
+    /// Subtype 5
+    Md5,
+    /// Subtype 6, encrypted by client-side field level encryption
+    Encrypted,
+    /// Subtype 7, a compressed time series column
+    Column,
+    /// Subtypes 0x80 to 0xff, defined by the application
+    UserDefined,
+    /// A subtype the server reserves for future use
+    Reserved,
+}
+
+impl BinaryKind {
+    pub(crate) fn of(subtype: bson::spec::BinarySubtype) -> Self {
+        use bson::spec::BinarySubtype;
+        match subtype {
+            BinarySubtype::Generic => BinaryKind::Generic,
+            BinarySubtype::Function => BinaryKind::Function,
+            BinarySubtype::BinaryOld => BinaryKind::BinaryOld,
+            BinarySubtype::UuidOld => BinaryKind::UuidLegacy,
+            BinarySubtype::Uuid => BinaryKind::Uuid,
+            BinarySubtype::Md5 => BinaryKind::Md5,
+            BinarySubtype::Encrypted => BinaryKind::Encrypted,
+            BinarySubtype::Column => BinaryKind::Column,
+            BinarySubtype::UserDefined(_) => BinaryKind::UserDefined,
+            _ => BinaryKind::Reserved,
+        }
+    }
+}
+
+impl fmt::Display for BinaryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            BinaryKind::Generic => "generic",
+            BinaryKind::Function => "function",
+            BinaryKind::BinaryOld => "binary (old)",
+            BinaryKind::UuidLegacy => "uuid (legacy)",
+            BinaryKind::Uuid => "uuid",
+            BinaryKind::Md5 => "md5",
+            BinaryKind::Encrypted => "encrypted",
+            BinaryKind::Column => "column",
+            BinaryKind::UserDefined => "user-defined",
+            BinaryKind::Reserved => "reserved",
+        })
+    }
+}
+
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        codegen::{Annotation, UnionStrategy},
+        AnalysisOptions,
+    };
+
+    fn analyzed(ndjson: &str, options: &AnalysisOptions) -> CollectionSchema {
+        CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", options).unwrap()
+    }
+
+    #[test]
+    fn reads_fields_through_accessors() {
+        let schema = analyzed(
+            "{\"a\": 1, \"b\": {\"c\": \"x\"}}\n{\"a\": \"two\"}\n\
+             {\"a\": 3, \"b\": {\"c\": \"y\"}}\n{\"a\": 4}\n",
+            &AnalysisOptions::default(),
+        );
+        assert_eq!(
+            schema.types_of("a"),
+            Some(&[BsonType::Int, BsonType::String][..])
+        );
+        let b = schema.types_of("b").unwrap();
+        assert!(b.len() == 2 && b.contains(&BsonType::Object) && b.contains(&BsonType::Missing));
+        assert_eq!(schema.types_of("nope"), None);
+
+        assert_eq!(schema.presence_of("a"), Some(1.0));
+        assert_eq!(schema.presence_of("b"), Some(0.5));
+        // in every subdocument that is there
+        assert_eq!(schema.presence_of("b.c"), Some(1.0));
+        assert_eq!(schema.presence_of("nope"), None);
+
+        assert_eq!(schema.is_optional("a"), Some(false));
+        assert_eq!(schema.is_optional("b"), Some(true));
+        assert_eq!(schema.is_optional("b.c"), Some(false));
+        assert_eq!(schema.is_optional("nope"), None);
+
+        assert_eq!(schema.is_polymorphic("a"), Some(true));
+        assert_eq!(schema.is_polymorphic("b"), Some(false));
+        assert_eq!(schema.is_polymorphic("nope"), None);
+
+        assert_eq!(schema.field_count(), 3);
+    }
+
+    #[test]
+    fn counts_the_presence_of_subdocument_fields_in_the_subdocuments() {
+        const ADDRESSES: &str = "{\"address\": {\"city\": \"Oslo\", \"zip\": \"0150\"}}\n\
+                                 {\"address\": {\"city\": \"Bergen\"}}\n{}\n{}\n";
+        let schema = analyzed(ADDRESSES, &AnalysisOptions::default());
+        assert_eq!(schema.presence("address.city"), Some(0.5));
+        assert_eq!(
+            schema.fields["address.city"].conditional_presence,
+            Some(1.0)
+        );
+        assert_eq!(schema.fields["address.zip"].conditional_presence, Some(0.5));
+        assert_eq!(schema.fields["address"].conditional_presence, None);
+        let rust = schema.to_rust(UnionStrategy::Union, 1.0, Annotation::None);
+        assert!(rust.contains("    pub city: String,\n"), "{}", rust);
+        assert!(rust.contains("    pub zip: Option<String>,\n"), "{}", rust);
+        assert!(
+            rust.contains("    pub address: Option<CollAddress>,\n"),
+            "{}",
+            rust
+        );
+
+        // still required without its parent in the report
+        let options = AnalysisOptions {
+            field_pattern: Some("address.city".to_string()),
+            ..AnalysisOptions::default()
+        };
+        let schema = analyzed(ADDRESSES, &options);
+        assert!(!schema.fields.contains_key("address"));
+        assert!(schema.is_required("address.city", 1.0));
+    }
+
+    #[test]
+    fn shares_types_by_their_counts() {
+        let options = AnalysisOptions {
+            type_shares: true,
+            ..AnalysisOptions::default()
+        };
+        let schema = analyzed(
+            "{\"a\": \"x\", \"b\": 1}\n{\"a\": \"y\"}\n{\"a\": null}\n{\"a\": 4, \"b\": 2}\n",
+            &options,
+        );
+        let a = &schema.fields["a"];
+        assert_eq!(
+            a.type_shares.as_deref(),
+            Some(
+                &[
+                    (BsonType::String, 0.5),
+                    (BsonType::Null, 0.25),
+                    (BsonType::Int, 0.25)
+                ][..]
+            )
+        );
+        // missing documents aside, a monomorphic field is all one type
+        let b = &schema.fields["b"];
+        assert_eq!(b.missing(), 2);
+        assert_eq!(b.type_shares.as_deref(), Some(&[(BsonType::Int, 1.0)][..]));
+        for field in schema.fields.values() {
+            let shares = field.type_shares.as_ref().unwrap();
+            let total = shares.iter().map(|(_, share)| share).sum::<f64>();
+            assert!((total - 1.0).abs() < 1e-12);
+            for (ty, share) in shares {
+                let held = field.count as f64;
+                assert_eq!(*share, field.type_counts[ty] as f64 / held);
+            }
+        }
+
+        let unshared = analyzed("{\"a\": 1}\n", &AnalysisOptions::default());
+        assert_eq!(unshared.fields["a"].type_shares, None);
+    }
+}
@@ -0,0 +1,302 @@
+//! An HTTP service running analyses on demand, for dashboards that would rather not shell out:
+//! `POST /analyze` with `{ uri, db, collection, options }` infers a schema and keeps it as a
+//! report, and `GET /reports/{id}` returns a kept report
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use mongodb::{options::ClientOptions, Client, Namespace};
+use serde::{Deserialize, Serialize};
+use tokio::{net::TcpListener, sync::Semaphore};
+
+use crate::{
+    identify_client, AnalysisOptions, AnalyzerError, CollectionSchema, Config, SchemaAnalyzer,
+};
+
+/// How the service runs
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// The connection string of requests that don't give one
+    pub uri: Option<String>,
+    /// The analysis settings of requests that don't give options, per namespace
+    pub config: Config,
+    /// How many analyses run at once. Further requests wait their turn
+    pub max_concurrent: usize,
+    /// How long a report answers a request for the same namespace and options, instead of
+    /// analyzing again
+    pub cache_ttl: Duration,
+    /// How many reports are kept, the oldest dropped first
+    pub max_reports: usize,
+    /// How many connection strings' clients are kept, the least recently used dropped
+    /// first
+    pub max_clients: usize,
+}
+
+impl Default for ServerOptions {
+    fn default() -> Self {
+        Self {
+            uri: None,
+            config: Config::default(),
+            max_concurrent: 4,
+            cache_ttl: Duration::from_secs(300),
+            max_reports: 100,
+            max_clients: 16,
+        }
+    }
+}
+
+/// What `POST /analyze` takes
+#[derive(Debug, Deserialize)]
+struct AnalyzeRequest {
+    uri: Option<String>,
+    db: String,
+    collection: String,
+    options: Option<AnalysisOptions>,
+}
+
+/// What `POST /analyze` and `GET /reports/{id}` return
+#[derive(Debug, Clone, Serialize)]
+struct Report {
+    id: u64,
+    /// Whether a kept report answered the request
+    cached: bool,
+    schema: Arc<CollectionSchema>,
+}
+
+struct ServerState {
+    options: ServerOptions,
+    permits: Semaphore,
+    clients: Clients,
+    /// The kept reports by id, and when each was made
+    reports: Mutex<BTreeMap<u64, (Instant, Arc<CollectionSchema>)>>,
+    /// The id of the report made for each request, keyed by its connection string,
+    /// namespace and options
+    requests: Mutex<HashMap<String, u64>>,
+    next_id: Mutex<u64>,
+}
+
+/// Answer requests on `listener` until it fails
+pub async fn serve(listener: TcpListener, options: ServerOptions) -> std::io::Result<()> {
+    let state = Arc::new(ServerState {
+        permits: Semaphore::new(options.max_concurrent.max(1)),
+        clients: Clients::new(options.max_clients),
+        options,
+        reports: Mutex::new(BTreeMap::new()),
+        requests: Mutex::new(HashMap::new()),
+        next_id: Mutex::new(1),
+    });
+    let app = Router::new()
+        .route("/analyze", post(analyze))
+        .route("/reports/:id", get(report))
+        .with_state(state);
+    axum::serve(listener, app).await
+}
+
+async fn analyze(
+    State(state): State<Arc<ServerState>>,
+    Json(request): Json<AnalyzeRequest>,
+) -> Result<Json<Report>, ApiError> {
+    let uri = request
+        .uri
+        .or_else(|| state.options.uri.clone())
+        .or_else(|| state.options.config.uri.clone())
+        .ok_or_else(|| ApiError::bad_request("no connection string: pass uri"))?;
+    let ns = Namespace::new(request.db, request.collection);
+    let options = request
+        .options
+        .unwrap_or_else(|| state.options.config.options_for(&ns));
+    let key = serde_json::to_string(&(&uri, ns.to_string(), &options))
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    if let Some(report) = state.cached(&key) {
+        return Ok(Json(report));
+    }
+    let client = state.clients.get(&uri).await?;
+    let schema = {
+        // infallible: the semaphore is never closed
+        let _permit = state.permits.acquire().await.unwrap();
+        // another request may have made the report while this one waited
+        if let Some(report) = state.cached(&key) {
+            return Ok(Json(report));
+        }
+        SchemaAnalyzer::with_options(client, ns, options)
+            .analyze()
+            .await?
+    };
+    Ok(Json(state.keep(key, schema)))
+}
+
+async fn report(
+    State(state): State<Arc<ServerState>>,
+    Path(id): Path<u64>,
+) -> Result<Json<Report>, ApiError> {
+    let reports = state.reports.lock().unwrap();
+    let (_, schema) = reports.get(&id).ok_or_else(|| ApiError {
+        status: StatusCode::NOT_FOUND,
+        message: format!("no report {}", id),
+    })?;
+    Ok(Json(Report {
+        id,
+        cached: true,
+        schema: schema.clone(),
+    }))
+}
+
+impl ServerState {
+    /// The report made for `key` within the cache's lifetime, if there is one
+    fn cached(&self, key: &str) -> Option<Report> {
+        let id = *self.requests.lock().unwrap().get(key)?;
+        let reports = self.reports.lock().unwrap();
+        let (made, schema) = reports.get(&id)?;
+        (made.elapsed() < self.options.cache_ttl).then(|| Report {
+            id,
+            cached: true,
+            schema: schema.clone(),
+        })
+    }
+
+    /// Keep `schema` as the report for `key`, dropping the oldest beyond the limit
+    fn keep(&self, key: String, schema: CollectionSchema) -> Report {
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id - 1
+        };
+        let schema = Arc::new(schema);
+        let mut reports = self.reports.lock().unwrap();
+        reports.insert(id, (Instant::now(), schema.clone()));
+        while reports.len() > self.options.max_reports.max(1) {
+            reports.pop_first();
+        }
+        let mut requests = self.requests.lock().unwrap();
+        requests.insert(key, id);
+        requests.retain(|_, id| reports.contains_key(id));
+        Report {
+            id,
+            cached: false,
+            schema,
+        }
+    }
+}
+
+/// Clients by connection string, so their connection pools are shared across requests, and
+/// when each was last asked for. At most `max` are kept, however many strings callers send
+pub(crate) struct Clients {
+    clients: Mutex<HashMap<String, (Instant, Client)>>,
+    max: usize,
+}
+
+impl Clients {
+    pub(crate) fn new(max: usize) -> Self {
+        Clients {
+            clients: Mutex::new(HashMap::new()),
+            max: max.max(1),
+        }
+    }
+
+    /// The client of `uri`, connecting the first time it's asked for. A client dropped for
+    /// the limit closes its pool once the analyses still using it finish
+    pub(crate) async fn get(&self, uri: &str) -> Result<Client, AnalyzerError> {
+        if let Some((used, client)) = self.clients.lock().unwrap().get_mut(uri) {
+            *used = Instant::now();
+            return Ok(client.clone());
+        }
+        let mut options = ClientOptions::parse(uri)
+            .await
+            .map_err(AnalyzerError::InvalidUri)?;
+        identify_client(&mut options, None);
+        let client = Client::with_options(options).map_err(AnalyzerError::InvalidUri)?;
+        self.insert(uri, client.clone());
+        Ok(client)
+    }
+
+    /// Keep `client` for `uri`, dropping the least recently used beyond the limit
+    fn insert(&self, uri: &str, client: Client) {
+        let mut clients = self.clients.lock().unwrap();
+        clients.insert(uri.to_string(), (Instant::now(), client));
+        while clients.len() > self.max {
+            let oldest = clients
+                .iter()
+                .min_by_key(|(_, (used, _))| *used)
+                .map(|(uri, _)| uri.clone());
+            match oldest {
+                Some(uri) => clients.remove(&uri),
+                None => break,
+            };
+        }
+    }
+}
+
+/// A failed request, answered with `{ "error": message }`
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+}
+
+impl From<AnalyzerError> for ApiError {
+    fn from(error: AnalyzerError) -> Self {
+        let status = match error {
+            AnalyzerError::InvalidUri(_) | AnalyzerError::InvalidOptions(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            AnalyzerError::Authentication(_) => StatusCode::UNAUTHORIZED,
+            AnalyzerError::Connection(_) | AnalyzerError::Server(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError {
+            status,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": self.message }));
+        (self.status, body).into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn drops_the_least_recently_used_client() {
+        let clients = Clients::new(2);
+        for uri in [
+            "mongodb://a:27017",
+            "mongodb://b:27017",
+            "mongodb://a:27017",
+            "mongodb://c:27017",
+        ] {
+            clients.get(uri).await.unwrap();
+            // apart, so the uses are ordered
+            std::thread::sleep(Duration::from_millis(2));
+        }
+        let kept = clients.clients.lock().unwrap();
+        assert_eq!(kept.len(), 2);
+        assert!(kept.contains_key("mongodb://a:27017"));
+        assert!(kept.contains_key("mongodb://c:27017"));
+    }
+}
@@ -0,0 +1,250 @@
+//! The schema shard by shard, for sharded collections whose bad data can sit on a single
+//! shard where sampling through mongos dilutes it
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use bson::doc;
+#[cfg(feature = "driver")]
+use mongodb::{
+    options::{ClientOptions, ServerAddress},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::SchemaAnalyzer;
+use crate::{AnalyzerError, BsonType, CollectionSchema, Result};
+
+/// How the fields differ between the shards of a collection, see
+/// [`SchemaAnalyzer::analyze_shards`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Shards {
+    /// What was sampled on each shard, by shard name
+    pub shards: Vec<ShardSample>,
+    /// The paths whose types aren't the same on every shard holding documents
+    pub differences: Vec<ShardDifference>,
+}
+
+/// The documents of one shard
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardSample {
+    pub shard: String,
+    /// How many documents of the collection the shard holds, orphans included
+    pub document_count: u64,
+    pub sample_size: u64,
+}
+
+/// A path holding different types on different shards
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ShardDifference {
+    pub path: String,
+    /// The types the path holds on each shard holding documents, not counting missing. Empty
+    /// where no document has the field
+    pub types: BTreeMap<String, Vec<BsonType>>,
+}
+
+impl Shards {
+    fn new(schemas: Vec<(String, CollectionSchema)>) -> Self {
+        let holding = schemas
+            .iter()
+            .filter(|(_, schema)| schema.sample_size > 0)
+            .collect::<Vec<_>>();
+        let paths = holding
+            .iter()
+            .flat_map(|(_, schema)| schema.fields.keys())
+            .collect::<BTreeSet<_>>();
+        let differences = paths
+            .into_iter()
+            .filter_map(|path| {
+                let types = holding
+                    .iter()
+                    .map(|(shard, schema)| {
+                        let types = schema.fields.get(path).map_or_else(Vec::new, |field| {
+                            field
+                                .types
+                                .iter()
+                                .filter(|ty| **ty != BsonType::Missing)
+                                .cloned()
+                                .collect()
+                        });
+                        (shard.clone(), types)
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                let mut distinct = types.values();
+                let first = distinct.next()?;
+                distinct
+                    .any(|types| types != first)
+                    .then(|| ShardDifference {
+                        path: path.clone(),
+                        types,
+                    })
+            })
+            .collect();
+        Shards {
+            shards: schemas
+                .iter()
+                .map(|(shard, schema)| ShardSample {
+                    shard: shard.clone(),
+                    document_count: schema.document_count,
+                    sample_size: schema.sample_size,
+                })
+                .collect(),
+            differences,
+        }
+    }
+
+    /// The summary line, e.g. `3 shards, 2 fields differ between them`
+    pub(crate) fn describe(&self) -> String {
+        match self.differences.len() {
+            0 => format!(
+                "{} shards, the same fields and types on each",
+                self.shards.len()
+            ),
+            1 => format!("{} shards, 1 field differs between them", self.shards.len()),
+            n => format!(
+                "{} shards, {} fields differ between them",
+                self.shards.len(),
+                n
+            ),
+        }
+    }
+}
+
+impl ShardDifference {
+    /// The types on each shard, e.g. `shard0: int; shard1: int, string`
+    pub(crate) fn describe(&self) -> String {
+        self.types
+            .iter()
+            .map(|(shard, types)| {
+                let types = if types.is_empty() {
+                    "absent".to_string()
+                } else {
+                    types
+                        .iter()
+                        .map(BsonType::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                };
+                format!("{}: {}", shard, types)
+            })
+            .collect::<Vec<_>>()
+            .join("; ")
+    }
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Analyze the collection on each shard of the cluster on its own, connecting to the
+    /// shards' replica sets directly with `options`, the options the client reaching mongos
+    /// was made with (for its credentials and TLS settings). Shards read directly also return
+    /// orphaned documents left behind by chunk migrations
+    pub async fn analyze_shards(&self, options: &ClientOptions) -> Result<Shards> {
+        let malformed = || AnalyzerError::MalformedResult("expected listShards' shards".into());
+        let listed = self
+            .client
+            .database("admin")
+            .run_command(doc! { "listShards": 1 }, None)
+            .await?;
+        let mut schemas = Vec::new();
+        for shard in listed.get_array("shards").map_err(|_| malformed())? {
+            let shard = shard.as_document().ok_or_else(malformed)?;
+            let (Ok(name), Ok(host)) = (shard.get_str("_id"), shard.get_str("host")) else {
+                return Err(malformed());
+            };
+            let client = Client::with_options(shard_options(options, host)?)
+                .map_err(AnalyzerError::InvalidUri)?;
+            let mut shard_options = self.options.clone();
+            shard_options.group_by = None;
+            shard_options.version_field = None;
+            shard_options.time_field = None;
+            shard_options.progress = None;
+            let analyzer = SchemaAnalyzer::with_options(client, self.ns.clone(), shard_options);
+            schemas.push((name.to_string(), analyzer.analyze().await?));
+        }
+        Ok(Shards::new(schemas))
+    }
+}
+
+/// `options` pointed at the shard listed with `host`: `rs0/a:27017,b:27017` for a replica
+/// set, or a bare address for a standalone
+#[cfg(feature = "driver")]
+fn shard_options(options: &ClientOptions, host: &str) -> Result<ClientOptions> {
+    let (repl_set_name, hosts) = match host.split_once('/') {
+        Some((name, hosts)) => (Some(name.to_string()), hosts),
+        None => (None, host),
+    };
+    let mut options = options.clone();
+    options.hosts = hosts
+        .split(',')
+        .map(ServerAddress::parse)
+        .collect::<mongodb::error::Result<_>>()
+        .map_err(AnalyzerError::InvalidUri)?;
+    options.direct_connection = Some(repl_set_name.is_none());
+    options.repl_set_name = repl_set_name;
+    options.load_balanced = None;
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    fn schema(documents: &[u8]) -> CollectionSchema {
+        CollectionSchema::from_ndjson(documents, "db.coll", &AnalysisOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn finds_the_fields_differing_between_shards() {
+        let shards = Shards::new(vec![
+            ("shard0".to_string(), schema(b"{\"a\": 1, \"b\": 1}\n")),
+            (
+                "shard1".to_string(),
+                schema(b"{\"a\": \"x\", \"b\": 1}\n{\"a\": 1}\n{\"a\": 2}\n"),
+            ),
+            // an empty shard has nothing to differ in
+            ("shard2".to_string(), schema(b"")),
+        ]);
+        assert_eq!(shards.shards.len(), 3);
+        assert_eq!(shards.shards[1].sample_size, 3);
+        let paths = shards
+            .differences
+            .iter()
+            .map(|difference| difference.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["a"]);
+        assert_eq!(shards.describe(), "3 shards, 1 field differs between them");
+        assert_eq!(
+            shards.differences[0].describe(),
+            "shard0: int; shard1: int, string"
+        );
+
+        let only_one = Shards::new(vec![
+            ("shard0".to_string(), schema(b"{\"a\": 1}\n")),
+            ("shard1".to_string(), schema(b"{\"b\": 1}\n")),
+        ]);
+        assert_eq!(
+            only_one.differences[0].describe(),
+            "shard0: int; shard1: absent"
+        );
+        assert_eq!(
+            only_one.describe(),
+            "2 shards, 2 fields differ between them"
+        );
+    }
+
+    #[cfg(feature = "driver")]
+    #[test]
+    fn connects_to_replica_set_shards_and_standalones() {
+        let options = ClientOptions::default();
+        let replica_set = shard_options(&options, "rs0/a:27017,b:27018").unwrap();
+        assert_eq!(replica_set.repl_set_name.as_deref(), Some("rs0"));
+        assert_eq!(replica_set.hosts.len(), 2);
+        assert_eq!(replica_set.direct_connection, Some(false));
+
+        let standalone = shard_options(&options, "c:27017").unwrap();
+        assert_eq!(standalone.repl_set_name, None);
+        assert_eq!(standalone.direct_connection, Some(true));
+        assert!(shard_options(&options, "rs0/a:port").is_err());
+    }
+}
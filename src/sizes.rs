@@ -0,0 +1,542 @@
+//! Where the bytes of a collection's documents go: how large the sampled documents are, and
+//! how much each top-level field adds to them, measured with `$bsonSize` on the server or by
+//! encoding the documents here
+
+use std::{collections::BTreeMap, fmt};
+
+use bson::{doc, spec::BinarySubtype, Bson, Document};
+#[cfg(feature = "driver")]
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::SchemaAnalyzer;
+use crate::{
+    pipeline::count_at,
+    stats::NumericSummary,
+    widths::{DocumentWidthSummary, PATTERN_KEYS, WIDE_DOCUMENT},
+    DocumentWidths, Result,
+};
+
+/// The largest BSON document the server stores
+pub const MAX_DOCUMENT_SIZE: u64 = 16 * 1024 * 1024;
+
+/// Documents larger than this are reported as nearing [`MAX_DOCUMENT_SIZE`]
+const NEAR_LIMIT: u64 = MAX_DOCUMENT_SIZE / 4 * 3;
+
+/// Upper bounds of the [`DocumentSizes::histogram`] buckets, each four times the last
+const BUCKETS: [u64; 8] = [
+    1 << 10,
+    1 << 12,
+    1 << 14,
+    1 << 16,
+    1 << 18,
+    1 << 20,
+    1 << 22,
+    MAX_DOCUMENT_SIZE,
+];
+
+/// How large the sampled documents are, in BSON bytes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentSizes {
+    /// How many documents were measured
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    /// How many documents fall in each size range, leaving out empty ranges
+    pub histogram: Vec<SizeBucket>,
+    /// How many documents are over three quarters of the 16 MiB limit, and so a few updates
+    /// away from failing to grow
+    pub near_limit: u64,
+}
+
+/// The documents of at least `min` and under `max` bytes
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SizeBucket {
+    pub min: u64,
+    pub max: u64,
+    pub count: u64,
+}
+
+impl fmt::Display for SizeBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let range = if self.min == 0 {
+            format!("under {}", format_bytes(self.max as f64))
+        } else {
+            format!(
+                "{} - {}",
+                format_bytes(self.min as f64),
+                format_bytes(self.max as f64)
+            )
+        };
+        f.pad(&range)
+    }
+}
+
+impl DocumentSizes {
+    /// The summary line, e.g. `min 120 B, median 610 B, p95 2.0 KiB, p99 3.1 KiB, max 5.3 KiB`
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "min {}, median {}, p95 {}, p99 {}, max {}, mean {}",
+            format_bytes(self.min as f64),
+            format_bytes(self.p50 as f64),
+            format_bytes(self.p95 as f64),
+            format_bytes(self.p99 as f64),
+            format_bytes(self.max as f64),
+            format_bytes(self.mean)
+        )
+    }
+
+    /// The warning about documents nearing the size limit, if any are
+    pub(crate) fn warning(&self) -> Option<String> {
+        (self.near_limit > 0).then(|| {
+            format!(
+                "{} documents are over {}, near the {} document size limit",
+                self.near_limit,
+                format_bytes(NEAR_LIMIT as f64),
+                format_bytes(MAX_DOCUMENT_SIZE as f64)
+            )
+        })
+    }
+
+    /// The share of the measured documents in `bucket`
+    pub fn share(&self, bucket: &SizeBucket) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            bucket.count as f64 / self.count as f64
+        }
+    }
+}
+
+/// Collects document sizes into [`DocumentSizes`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DocumentSizeSummary {
+    sizes: NumericSummary,
+    buckets: [u64; BUCKETS.len()],
+    near_limit: u64,
+}
+
+impl DocumentSizeSummary {
+    pub(crate) fn observe(&mut self, size: u64) {
+        self.sizes.observe_number(size as f64);
+        let bucket = BUCKETS
+            .iter()
+            .position(|max| size < *max)
+            .unwrap_or(BUCKETS.len() - 1);
+        self.buckets[bucket] += 1;
+        if size > NEAR_LIMIT {
+            self.near_limit += 1;
+        }
+    }
+
+    /// The distribution, if any documents were measured
+    pub(crate) fn sizes(&self) -> Option<DocumentSizes> {
+        let stats = self.sizes.stats()?;
+        let histogram = self
+            .buckets
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(i, count)| SizeBucket {
+                min: if i == 0 { 0 } else { BUCKETS[i - 1] },
+                max: BUCKETS[i],
+                count: *count,
+            })
+            .collect();
+        Some(DocumentSizes {
+            count: stats.count,
+            min: stats.min as u64,
+            max: stats.max as u64,
+            mean: stats.mean,
+            p50: stats.p50 as u64,
+            p95: stats.p95 as u64,
+            p99: stats.p99 as u64,
+            histogram,
+            near_limit: self.near_limit,
+        })
+    }
+}
+
+/// How the sampled documents' bytes divide between their top-level fields
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSizes {
+    /// The mean BSON size of a sampled document
+    pub average_document: f64,
+    /// Every top-level field, heaviest first
+    pub fields: Vec<FieldSize>,
+}
+
+/// How many bytes one top-level field takes up: its value, name and type tag
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldSize {
+    pub path: String,
+    /// The mean bytes the field adds to a sampled document, counting documents without it
+    pub average: f64,
+    /// The field's bytes across the whole collection, extrapolated from the sample
+    pub total: u64,
+}
+
+impl FieldSizes {
+    /// The sizes with their totals extrapolated to `document_count` documents
+    pub(crate) fn extrapolate(mut self, document_count: u64) -> Self {
+        for field in &mut self.fields {
+            field.total = (field.average * document_count as f64).round() as u64;
+        }
+        self
+    }
+
+    /// The share of the average document `field` takes up
+    pub fn share(&self, field: &FieldSize) -> f64 {
+        if self.average_document == 0.0 {
+            0.0
+        } else {
+            field.average / self.average_document
+        }
+    }
+}
+
+/// `bytes` in the largest binary unit that keeps it at least 1, e.g. `12.5 MiB`
+pub(crate) fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if bytes < 1024.0 {
+        return format!("{:.0} B", bytes);
+    }
+    let mut value = bytes / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if value.fract() == 0.0 {
+        format!("{:.0} {}", value, UNITS[unit])
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+/// Adds up the bytes of documents and of their top-level fields into [`FieldSizes`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SizeSummary {
+    documents: u64,
+    bytes: u64,
+    fields: BTreeMap<String, u64>,
+}
+
+impl SizeSummary {
+    pub(crate) fn observe(&mut self, doc: &Document) {
+        self.documents += 1;
+        self.bytes += document_size(doc);
+        for (name, value) in doc {
+            *self.fields.entry(name.clone()).or_default() += element_size(name, value);
+        }
+    }
+
+    /// The sizes, extrapolated to `document_count` documents
+    pub(crate) fn sizes(&self, document_count: u64) -> FieldSizes {
+        let average = |bytes: u64| {
+            if self.documents == 0 {
+                0.0
+            } else {
+                bytes as f64 / self.documents as f64
+            }
+        };
+        let mut fields = self
+            .fields
+            .iter()
+            .map(|(path, bytes)| FieldSize {
+                path: path.clone(),
+                average: average(*bytes),
+                total: 0,
+            })
+            .collect::<Vec<_>>();
+        fields.sort_by(|a, b| {
+            b.average
+                .total_cmp(&a.average)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        FieldSizes {
+            average_document: average(self.bytes),
+            fields,
+        }
+        .extrapolate(document_count)
+    }
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Measure every document the sampling selects, in an aggregation of its own returning
+    /// only the measurements: their size with `$bsonSize`, which needs MongoDB 4.4, if
+    /// [`AnalysisOptions::document_sizes`](crate::AnalysisOptions::document_sizes) asks, and
+    /// their keys if [`AnalysisOptions::document_widths`](crate::AnalysisOptions::document_widths)
+    /// does
+    pub(crate) async fn measure_documents(
+        &self,
+        sample_size: Option<u64>,
+    ) -> Result<(Option<DocumentSizes>, Option<DocumentWidths>)> {
+        let mut pipeline = Vec::new();
+        if let Some(filter) = &self.options.filter {
+            pipeline.push(doc! { "$match": filter.clone() });
+        }
+        if let Some(size) = sample_size {
+            pipeline.push(doc! { "$sample": { "size": Bson::Int64(size as i64) } });
+        }
+        let mut project = doc! { "_id": 0 };
+        if self.options.document_sizes {
+            project.insert("size", doc! { "$bsonSize": "$$ROOT" });
+        }
+        if self.options.document_widths {
+            // only wide documents send their first keys along, to find their patterns
+            let keys = doc! { "$objectToArray": "$$ROOT" };
+            project.insert("width", doc! { "$size": keys.clone() });
+            project.insert(
+                "keys",
+                doc! {
+                    "$cond": [
+                        { "$gt": [{ "$size": keys.clone() }, Bson::Int64(WIDE_DOCUMENT as i64)] },
+                        { "$slice": [{ "$map": { "input": keys, "in": "$$this.k" } }, PATTERN_KEYS as i64] },
+                        "$$REMOVE"
+                    ]
+                },
+            );
+        }
+        pipeline.push(doc! { "$project": project });
+
+        let mut cursor = self
+            .collection()
+            .aggregate(pipeline, self.aggregate_options()?)
+            .await?;
+        let mut sizes = DocumentSizeSummary::default();
+        let mut widths = DocumentWidthSummary::default();
+        while let Some(result) = cursor.try_next().await? {
+            if self.options.document_sizes {
+                sizes.observe(count_at(&result, "size")?);
+            }
+            if self.options.document_widths {
+                let keys = result
+                    .get_array("keys")
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+                widths.observe_keys(
+                    count_at(&result, "width")?,
+                    keys.iter().filter_map(Bson::as_str),
+                );
+            }
+        }
+        Ok((
+            sizes.sizes().filter(|_| self.options.document_sizes),
+            widths.widths().filter(|_| self.options.document_widths),
+        ))
+    }
+
+    /// Measure the top-level `fields` of the documents the sampling selects with `$bsonSize`,
+    /// which needs MongoDB 4.4, in one aggregation reducing them to their byte counts
+    pub(crate) async fn field_sizes(
+        &self,
+        sample_size: Option<u64>,
+        document_count: u64,
+        fields: &[&str],
+    ) -> Result<FieldSizes> {
+        let mut pipeline = Vec::new();
+        if let Some(filter) = &self.options.filter {
+            pipeline.push(doc! { "$match": filter.clone() });
+        }
+        if let Some(size) = sample_size {
+            pipeline.push(doc! { "$sample": { "size": Bson::Int64(size as i64) } });
+        }
+        let mut group = doc! {
+            "_id": null,
+            "documents": { "$sum": 1 },
+            "bytes": { "$sum": { "$bsonSize": "$$ROOT" } },
+        };
+        // accumulator names can't hold dots, so the fields are numbered
+        for (i, name) in fields.iter().enumerate() {
+            group.insert(format!("f{}", i), doc! { "$sum": size_expression(name) });
+        }
+        pipeline.push(doc! { "$group": group });
+
+        let mut cursor = self
+            .collection()
+            .aggregate(pipeline, self.aggregate_options()?)
+            .await?;
+        let mut summary = SizeSummary::default();
+        if let Some(result) = cursor.try_next().await? {
+            summary.documents = count_at(&result, "documents")?;
+            summary.bytes = count_at(&result, "bytes")?;
+            for (i, name) in fields.iter().enumerate() {
+                let bytes = count_at(&result, &format!("f{}", i))?;
+                if bytes > 0 {
+                    summary.fields.insert(name.to_string(), bytes);
+                }
+            }
+        }
+        Ok(summary.sizes(document_count))
+    }
+}
+
+/// The expression for the bytes the top-level field `name` adds to the document: those of a
+/// `{ v: <value> }` document, less its own overhead, plus the field's name
+fn size_expression(name: &str) -> Bson {
+    let value = if name.is_empty() || name.contains('.') || name.starts_with('$') {
+        Bson::Document(doc! {
+            "$getField": { "field": { "$literal": name }, "input": "$$ROOT" }
+        })
+    } else {
+        Bson::String(format!("${}", name))
+    };
+    // a document is 5 bytes, and the `v` element's tag and name 3 more
+    let overhead = 8 - (name.len() as i64 + 2);
+    Bson::Document(doc! {
+        "$cond": [
+            { "$eq": [{ "$type": value.clone() }, "missing"] },
+            0,
+            { "$subtract": [{ "$bsonSize": { "v": value } }, overhead] }
+        ]
+    })
+}
+
+/// The encoded size of `doc`
+pub(crate) fn document_size(doc: &Document) -> u64 {
+    // length, elements, terminator
+    4 + doc
+        .iter()
+        .map(|(name, value)| element_size(name, value))
+        .sum::<u64>()
+        + 1
+}
+
+/// The encoded size of one element: type tag, name and value
+fn element_size(name: &str, value: &Bson) -> u64 {
+    1 + name.len() as u64 + 1 + value_size(value)
+}
+
+fn value_size(value: &Bson) -> u64 {
+    // strings are a length, the bytes and a terminator
+    let string = |s: &str| 4 + s.len() as u64 + 1;
+    match value {
+        Bson::Double(_) | Bson::Int64(_) | Bson::DateTime(_) | Bson::Timestamp(_) => 8,
+        Bson::Int32(_) => 4,
+        Bson::Decimal128(_) => 16,
+        Bson::ObjectId(_) => 12,
+        Bson::Boolean(_) => 1,
+        Bson::Null | Bson::Undefined | Bson::MinKey | Bson::MaxKey => 0,
+        Bson::String(s) | Bson::JavaScriptCode(s) | Bson::Symbol(s) => string(s),
+        Bson::Document(doc) => document_size(doc),
+        Bson::Array(elements) => {
+            4 + elements
+                .iter()
+                .enumerate()
+                .map(|(i, element)| element_size(&i.to_string(), element))
+                .sum::<u64>()
+                + 1
+        }
+        Bson::Binary(binary) => {
+            // the old binary subtype repeats the length inside the data
+            let old = if binary.subtype == BinarySubtype::BinaryOld {
+                4
+            } else {
+                0
+            };
+            4 + 1 + old + binary.bytes.len() as u64
+        }
+        Bson::RegularExpression(regex) => {
+            regex.pattern.len() as u64 + 1 + regex.options.len() as u64 + 1
+        }
+        Bson::JavaScriptCodeWithScope(code) => 4 + string(&code.code) + document_size(&code.scope),
+        Bson::DbPointer(_) => {
+            // the namespace isn't exposed, so measure it encoded
+            let encoded = bson::to_vec(&doc! { "v": value.clone() }).map_or(0, |v| v.len());
+            encoded.saturating_sub(8) as u64
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::{oid::ObjectId, Binary, DateTime, Regex};
+
+    use super::*;
+
+    #[test]
+    fn measures_documents_as_they_encode() {
+        let doc = doc! {
+            "_id": ObjectId::new(),
+            "name": "Ada",
+            "n": 1,
+            "big": 1_i64,
+            "at": DateTime::from_millis(0),
+            "none": null,
+            "tags": ["a", { "b": [1.5, true] }],
+            "data": Binary { subtype: BinarySubtype::BinaryOld, bytes: vec![1, 2, 3] },
+            "re": Regex { pattern: "^a".to_string(), options: "i".to_string() },
+        };
+        assert_eq!(
+            document_size(&doc),
+            bson::to_vec(&doc).unwrap().len() as u64
+        );
+    }
+
+    #[test]
+    fn divides_the_bytes_between_the_top_level_fields() {
+        let mut summary = SizeSummary::default();
+        summary.observe(&doc! { "a": 1, "long": "x".repeat(100) });
+        summary.observe(&doc! { "a": 2 });
+        let sizes = summary.sizes(10);
+        assert_eq!(sizes.fields[0].path, "long");
+        // type tag, name and terminator, length, 100 bytes and terminator, in half the documents
+        assert_eq!(sizes.fields[0].average, (1.0 + 5.0 + 4.0 + 101.0) / 2.0);
+        assert_eq!(sizes.fields[0].total, 555);
+        assert_eq!(sizes.fields[1].average, 7.0);
+        let accounted = sizes
+            .fields
+            .iter()
+            .map(|field| sizes.share(field))
+            .sum::<f64>();
+        // the rest is each document's length and terminator
+        assert!(accounted < 1.0 && accounted > 0.9, "{}", accounted);
+    }
+
+    #[test]
+    fn buckets_document_sizes_and_flags_those_near_the_limit() {
+        let mut summary = DocumentSizeSummary::default();
+        assert_eq!(summary.sizes(), None);
+        for size in [100, 200, 5_000, NEAR_LIMIT + 1] {
+            summary.observe(size);
+        }
+        let sizes = summary.sizes().unwrap();
+        assert_eq!(
+            (sizes.min, sizes.max, sizes.near_limit),
+            (100, NEAR_LIMIT + 1, 1)
+        );
+        let counts = sizes
+            .histogram
+            .iter()
+            .map(|bucket| (bucket.to_string(), bucket.count))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts,
+            [
+                ("under 1 KiB".to_string(), 2),
+                ("4 KiB - 16 KiB".to_string(), 1),
+                ("4 MiB - 16 MiB".to_string(), 1),
+            ]
+        );
+        assert_eq!(sizes.share(&sizes.histogram[0]), 0.5);
+        assert!(sizes
+            .warning()
+            .unwrap()
+            .starts_with("1 documents are over 12 MiB"));
+    }
+
+    #[test]
+    fn formats_bytes_in_binary_units() {
+        assert_eq!(format_bytes(512.0), "512 B");
+        assert_eq!(format_bytes(1024.0), "1 KiB");
+        assert_eq!(format_bytes(1536.0), "1.5 KiB");
+        assert_eq!(format_bytes(16.0 * 1024.0 * 1024.0), "16 MiB");
+    }
+}
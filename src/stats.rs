@@ -0,0 +1,602 @@
+//! Statistics over the numeric, string and date values of a field. Exact for the count, min,
+//! max, mean and date ranges; percentiles and histograms come from a bounded, evenly spread
+//! subset of the values
+
+use std::{collections::BTreeMap, fmt};
+
+use bson::{Bson, DateTime};
+use serde::{Deserialize, Serialize};
+
+/// Most values kept per field to estimate percentiles from
+const PERCENTILE_SAMPLE: usize = 4096;
+
+/// What the int, long, double and decimal values of a field looked like
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NumericStats {
+    /// How many numeric values were seen
+    pub count: u64,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub p25: f64,
+    pub p50: f64,
+    pub p75: f64,
+    pub p95: f64,
+    pub p99: f64,
+    /// The values bucketed over their range, if
+    /// [`AnalysisOptions::histograms`](crate::AnalysisOptions::histograms) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<Histogram>,
+}
+
+/// Column headings for [`NumericStats::columns`]
+pub(crate) const STATS_HEADER: [&str; 8] =
+    ["MIN", "P25", "MEDIAN", "P75", "P95", "P99", "MAX", "MEAN"];
+
+impl NumericStats {
+    /// The statistics formatted for a table, in the order of [`STATS_HEADER`]
+    pub(crate) fn columns(&self) -> [String; 8] {
+        [
+            self.min, self.p25, self.p50, self.p75, self.p95, self.p99, self.max, self.mean,
+        ]
+        .map(format_number)
+    }
+}
+
+/// How a histogram's buckets divide the range of values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HistogramScale {
+    /// Buckets of equal width
+    #[default]
+    EqualWidth,
+    /// Buckets of equal width in the logarithm of the values, for values spanning orders of
+    /// magnitude. Only for numbers, and only if all of them are positive
+    Log,
+}
+
+/// Values bucketed over their range, as chart data. Exact for up to 4096 values, and scaled
+/// from an evenly spread subset of them beyond
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Histogram {
+    /// How the buckets were made, which is [`HistogramScale::EqualWidth`] if log scale was
+    /// asked for but a value wasn't positive
+    pub scale: HistogramScale,
+    /// From the smallest value to the largest. For dates the bounds are milliseconds since
+    /// the epoch
+    pub buckets: Vec<HistogramBucket>,
+}
+
+/// The values from `lower` up to `upper`, which the last bucket includes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistogramBucket {
+    pub lower: f64,
+    pub upper: f64,
+    pub count: u64,
+}
+
+/// Sparkline bars from the lowest to the highest
+const SPARKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+impl Histogram {
+    /// The bucket counts as a line of bars, blank for empty buckets
+    pub fn sparkline(&self) -> String {
+        let most = self
+            .buckets
+            .iter()
+            .map(|b| b.count)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        self.buckets
+            .iter()
+            .map(|bucket| match bucket.count {
+                0 => ' ',
+                n => SPARKS[((n * 8).div_ceil(most) as usize).clamp(1, 8) - 1],
+            })
+            .collect()
+    }
+}
+
+/// A string field is flagged for long outliers when its longest value is this many times its
+/// 95th percentile length
+const LONG_OUTLIER_FACTOR: f64 = 10.0;
+
+/// Strings shorter than this, in bytes, are never long outliers
+const LONG_OUTLIER_MIN: f64 = 256.0;
+
+/// What the lengths of the strings of a field looked like, in UTF-8 bytes as index keys and
+/// most column types count them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StringLengthStats {
+    /// How many strings were seen
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    /// The longest string is at least 256 bytes and ten times the 95th percentile length
+    pub long_outliers: bool,
+}
+
+/// Column headings for [`StringLengthStats::columns`]
+pub(crate) const LENGTH_HEADER: [&str; 6] = ["MIN", "MEDIAN", "P95", "P99", "MAX", "MEAN"];
+
+impl StringLengthStats {
+    fn new(lengths: NumericStats) -> Self {
+        StringLengthStats {
+            count: lengths.count,
+            min: lengths.min as u64,
+            max: lengths.max as u64,
+            mean: lengths.mean,
+            p50: lengths.p50 as u64,
+            p95: lengths.p95 as u64,
+            p99: lengths.p99 as u64,
+            long_outliers: lengths.max >= LONG_OUTLIER_MIN
+                && lengths.max >= lengths.p95 * LONG_OUTLIER_FACTOR,
+        }
+    }
+
+    /// The statistics formatted for a table, in the order of [`LENGTH_HEADER`]
+    pub(crate) fn columns(&self) -> [String; 6] {
+        [
+            self.min.to_string(),
+            self.p50.to_string(),
+            self.p95.to_string(),
+            self.p99.to_string(),
+            self.max.to_string(),
+            format_number(self.mean),
+        ]
+    }
+}
+
+/// Collects a field's numeric values, or the lengths of its strings, into [`NumericStats`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct NumericSummary {
+    count: u64,
+    min: f64,
+    max: f64,
+    sum: f64,
+    /// Every `stride`th value, halved whenever it fills up
+    kept: Vec<f64>,
+    stride: u64,
+}
+
+impl NumericSummary {
+    /// Count `value` if it is a finite number
+    pub(crate) fn observe(&mut self, value: &Bson) {
+        match value {
+            Bson::Int32(n) => self.observe_number(f64::from(*n)),
+            Bson::Int64(n) => self.observe_number(*n as f64),
+            Bson::Double(n) => self.observe_number(*n),
+            Bson::Decimal128(n) => {
+                if let Ok(n) = n.to_string().parse::<f64>() {
+                    self.observe_number(n)
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Count a value, if it is finite
+    pub(crate) fn observe_number(&mut self, n: f64) {
+        if !n.is_finite() {
+            return;
+        }
+        if self.count == 0 {
+            self.min = n;
+            self.max = n;
+            self.stride = 1;
+        }
+        self.min = self.min.min(n);
+        self.max = self.max.max(n);
+        self.sum += n;
+        if self.count.is_multiple_of(self.stride) {
+            if self.kept.len() == PERCENTILE_SAMPLE {
+                // keep every other value, and from now on every other one seen
+                let mut i = 0;
+                self.kept.retain(|_| {
+                    i += 1;
+                    i % 2 == 1
+                });
+                self.stride *= 2;
+            }
+            if self.count.is_multiple_of(self.stride) {
+                self.kept.push(n);
+            }
+        }
+        self.count += 1;
+    }
+
+    /// The statistics, if any numbers were seen
+    pub(crate) fn stats(&self) -> Option<NumericStats> {
+        if self.count == 0 {
+            return None;
+        }
+        let mut sorted = self.kept.clone();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        Some(NumericStats {
+            count: self.count,
+            min: self.min,
+            max: self.max,
+            mean: self.sum / self.count as f64,
+            p25: percentile(0.25),
+            p50: percentile(0.5),
+            p75: percentile(0.75),
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            histogram: None,
+        })
+    }
+
+    /// `buckets` buckets over the range of the values, if any were seen
+    pub(crate) fn histogram(&self, scale: HistogramScale, buckets: usize) -> Option<Histogram> {
+        if self.count == 0 {
+            return None;
+        }
+        let buckets = buckets.max(1);
+        let scale = match scale {
+            HistogramScale::Log if self.min > 0.0 => HistogramScale::Log,
+            _ => HistogramScale::EqualWidth,
+        };
+        // the bounds are spread evenly over the values after `to`, and mapped back with `from`
+        let log = scale == HistogramScale::Log;
+        let to = |n: f64| if log { n.ln() } else { n };
+        let from = |n: f64| if log { n.exp() } else { n };
+        let (low, high) = (to(self.min), to(self.max));
+        let width = (high - low) / buckets as f64;
+        let mut kept = vec![0u64; buckets];
+        for n in &self.kept {
+            let i = match width > 0.0 {
+                true => ((to(*n) - low) / width) as usize,
+                false => 0,
+            };
+            kept[i.min(buckets - 1)] += 1;
+        }
+        // each kept value stands for the ones skipped around it
+        let scale_up = self.count as f64 / self.kept.len() as f64;
+        let buckets = kept
+            .iter()
+            .enumerate()
+            .map(|(i, kept)| HistogramBucket {
+                lower: if i == 0 {
+                    self.min
+                } else {
+                    from(low + width * i as f64)
+                },
+                upper: if i == buckets - 1 {
+                    self.max
+                } else {
+                    from(low + width * (i + 1) as f64)
+                },
+                count: (*kept as f64 * scale_up).round() as u64,
+            })
+            .collect();
+        Some(Histogram { scale, buckets })
+    }
+
+    /// The statistics of the values as string lengths, if any were seen
+    pub(crate) fn length_stats(&self) -> Option<StringLengthStats> {
+        self.stats().map(StringLengthStats::new)
+    }
+}
+
+/// `n` compactly: integers without a fraction, others with at most four decimals
+pub(crate) fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else if n.abs() >= 1e15 || n.abs() < 1e-4 {
+        format!("{:e}", n)
+    } else {
+        let fixed = format!("{:.4}", n);
+        fixed
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string()
+    }
+}
+
+/// Most months a date distribution is broken down by before it's broken down by year
+const MAX_MONTHS: usize = 36;
+
+/// The range and spread of the dates of a field
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DateStats {
+    /// How many dates were seen
+    pub count: u64,
+    /// The earliest date, in RFC 3339
+    pub earliest: String,
+    /// The latest date, in RFC 3339
+    pub latest: String,
+    pub granularity: DateGranularity,
+    /// How many dates fall in each month or year from the earliest to the latest, empty
+    /// periods included
+    pub distribution: Vec<DateBucket>,
+    /// The dates bucketed over equal stretches of their range, if
+    /// [`AnalysisOptions::histograms`](crate::AnalysisOptions::histograms) asked
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub histogram: Option<Histogram>,
+}
+
+/// The periods a [`DateStats::distribution`] is broken down by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateGranularity {
+    Month,
+    Year,
+}
+
+/// The dates in one month (`2024-03`) or year (`2024`), in UTC
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DateBucket {
+    pub period: String,
+    pub count: u64,
+}
+
+impl fmt::Display for DateBucket {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.period, self.count)
+    }
+}
+
+impl DateStats {
+    /// The distribution as `2023: 120, 2024: 800`
+    pub(crate) fn describe_distribution(&self) -> String {
+        self.distribution
+            .iter()
+            .map(DateBucket::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Collects a field's dates into [`DateStats`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DateSummary {
+    earliest: Option<DateTime>,
+    latest: Option<DateTime>,
+    /// Dates seen in each (year, month)
+    months: BTreeMap<(i64, u32), u64>,
+    /// The dates as milliseconds since the epoch, for histograms
+    millis: NumericSummary,
+}
+
+impl DateSummary {
+    pub(crate) fn observe(&mut self, date: DateTime) {
+        self.earliest = Some(self.earliest.map_or(date, |earliest| earliest.min(date)));
+        self.latest = Some(self.latest.map_or(date, |latest| latest.max(date)));
+        *self.months.entry(year_month(date)).or_default() += 1;
+        self.millis.observe_number(date.timestamp_millis() as f64);
+    }
+
+    /// `buckets` buckets over the range of the dates, if any were seen
+    pub(crate) fn histogram(&self, buckets: usize) -> Option<Histogram> {
+        self.millis.histogram(HistogramScale::EqualWidth, buckets)
+    }
+
+    /// The statistics, if any dates were seen
+    pub(crate) fn stats(&self) -> Option<DateStats> {
+        let (earliest, latest) = (self.earliest?, self.latest?);
+        let (first, last) = (year_month(earliest), year_month(latest));
+        let months = (last.0 - first.0) * 12 + i64::from(last.1) - i64::from(first.1) + 1;
+        let (granularity, distribution) = if months <= MAX_MONTHS as i64 {
+            let mut distribution = Vec::new();
+            let (mut year, mut month) = first;
+            while (year, month) <= last {
+                distribution.push(DateBucket {
+                    period: format!("{:04}-{:02}", year, month),
+                    count: self.months.get(&(year, month)).copied().unwrap_or_default(),
+                });
+                (year, month) = if month == 12 {
+                    (year + 1, 1)
+                } else {
+                    (year, month + 1)
+                };
+            }
+            (DateGranularity::Month, distribution)
+        } else {
+            let distribution = (first.0..=last.0)
+                .map(|year| DateBucket {
+                    period: format!("{:04}", year),
+                    count: self
+                        .months
+                        .range((year, 1)..=(year, 12))
+                        .map(|(_, n)| n)
+                        .sum(),
+                })
+                .collect();
+            (DateGranularity::Year, distribution)
+        };
+        Some(DateStats {
+            count: self.months.values().sum(),
+            earliest: rfc3339(earliest),
+            latest: rfc3339(latest),
+            granularity,
+            distribution,
+            histogram: None,
+        })
+    }
+}
+
+/// `date` in RFC 3339, or its milliseconds since the epoch if it's out of that range
+pub(crate) fn rfc3339(date: DateTime) -> String {
+    date.try_to_rfc3339_string()
+        .unwrap_or_else(|_| date.timestamp_millis().to_string())
+}
+
+/// The UTC year and month of `date`
+fn year_month(date: DateTime) -> (i64, u32) {
+    let (year, month, _) = civil_date(date);
+    (year, month)
+}
+
+/// The UTC year, month and day of `date`
+pub(crate) fn civil_date(date: DateTime) -> (i64, u32, u32) {
+    // days since 1970-01-01 to a civil date, after Howard Hinnant's `civil_from_days`
+    let days = date.timestamp_millis().div_euclid(86_400_000) + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(values: impl IntoIterator<Item = f64>) -> NumericSummary {
+        let mut summary = NumericSummary::default();
+        for n in values {
+            summary.observe_number(n);
+        }
+        summary
+    }
+
+    #[test]
+    fn computes_percentiles() {
+        let stats = summary((1..=101).map(f64::from)).stats().unwrap();
+        assert_eq!(stats.count, 101);
+        assert_eq!((stats.min, stats.max, stats.mean), (1.0, 101.0, 51.0));
+        assert_eq!(
+            [stats.p25, stats.p50, stats.p75, stats.p95, stats.p99],
+            [26.0, 51.0, 76.0, 96.0, 100.0]
+        );
+        assert_eq!(NumericSummary::default().stats(), None);
+    }
+
+    #[test]
+    fn counts_only_finite_numbers() {
+        let mut summary = NumericSummary::default();
+        for value in [
+            Bson::Int32(3),
+            Bson::Int64(5),
+            Bson::Double(1.5),
+            Bson::Decimal128("2.5".parse().unwrap()),
+            Bson::Double(f64::NAN),
+            Bson::Double(f64::INFINITY),
+            Bson::String("7".to_string()),
+        ] {
+            summary.observe(&value);
+        }
+        let stats = summary.stats().unwrap();
+        assert_eq!((stats.count, stats.min, stats.max), (4, 1.5, 5.0));
+        assert_eq!(stats.mean, 3.0);
+    }
+
+    #[test]
+    fn keeps_a_bounded_spread_of_many_values() {
+        let summary = summary((0..100_000).map(f64::from));
+        assert!(summary.kept.len() <= PERCENTILE_SAMPLE);
+        let stats = summary.stats().unwrap();
+        assert_eq!(
+            (stats.count, stats.min, stats.max),
+            (100_000, 0.0, 99_999.0)
+        );
+        assert!((stats.p50 - 50_000.0).abs() < 100.0, "{}", stats.p50);
+        assert!((stats.p99 - 99_000.0).abs() < 100.0, "{}", stats.p99);
+        let histogram = summary.histogram(HistogramScale::EqualWidth, 4).unwrap();
+        for bucket in &histogram.buckets {
+            assert!((bucket.count as i64 - 25_000).abs() < 100, "{:?}", bucket);
+        }
+    }
+
+    #[test]
+    fn buckets_values_of_equal_width() {
+        let histogram = summary((0..10).map(f64::from))
+            .histogram(HistogramScale::EqualWidth, 5)
+            .unwrap();
+        assert_eq!(histogram.scale, HistogramScale::EqualWidth);
+        assert_eq!(
+            histogram
+                .buckets
+                .iter()
+                .map(|b| (b.lower, b.upper, b.count))
+                .collect::<Vec<_>>(),
+            [
+                (0.0, 1.8, 2),
+                (1.8, 3.6, 2),
+                (3.6, 5.4, 2),
+                (5.4, 7.2, 2),
+                (7.2, 9.0, 2)
+            ]
+        );
+        // a single value fills the first bucket
+        let histogram = summary([4.0, 4.0])
+            .histogram(HistogramScale::EqualWidth, 3)
+            .unwrap();
+        assert_eq!(
+            histogram
+                .buckets
+                .iter()
+                .map(|b| b.count)
+                .collect::<Vec<_>>(),
+            [2, 0, 0]
+        );
+    }
+
+    #[test]
+    fn buckets_positive_values_by_their_logarithm() {
+        let values = [1.0, 2.0, 5.0, 20.0, 50.0, 100.0];
+        let histogram = summary(values).histogram(HistogramScale::Log, 2).unwrap();
+        assert_eq!(histogram.scale, HistogramScale::Log);
+        assert!((histogram.buckets[0].upper - 10.0).abs() < 1e-9);
+        assert_eq!(
+            histogram
+                .buckets
+                .iter()
+                .map(|b| b.count)
+                .collect::<Vec<_>>(),
+            [3, 3]
+        );
+        // zero has no logarithm
+        let histogram = summary([0.0, 10.0])
+            .histogram(HistogramScale::Log, 2)
+            .unwrap();
+        assert_eq!(histogram.scale, HistogramScale::EqualWidth);
+    }
+
+    #[test]
+    fn draws_sparklines() {
+        let histogram = Histogram {
+            scale: HistogramScale::EqualWidth,
+            buckets: [0, 1, 4, 8]
+                .into_iter()
+                .map(|count| HistogramBucket {
+                    lower: 0.0,
+                    upper: 0.0,
+                    count,
+                })
+                .collect(),
+        };
+        assert_eq!(histogram.sparkline(), " ▁▄█");
+    }
+
+    #[test]
+    fn collects_statistics_by_default() {
+        let ndjson = "{\"n\": 1, \"s\": \"ab\"}\n{\"n\": 3, \"s\": \"abcd\"}\n";
+        let options = crate::AnalysisOptions::default();
+        let schema =
+            crate::CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options).unwrap();
+        let stats = schema.fields["n"].stats.as_ref().unwrap();
+        assert_eq!((stats.min, stats.max, stats.mean), (1.0, 3.0, 2.0));
+        let lengths = schema.fields["s"].length_stats.as_ref().unwrap();
+        assert_eq!((lengths.min, lengths.max), (2, 4));
+
+        let options = crate::AnalysisOptions {
+            stats: false,
+            ..options
+        };
+        let schema =
+            crate::CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options).unwrap();
+        assert_eq!(schema.fields["n"].stats, None);
+    }
+}
@@ -0,0 +1,953 @@
+use std::fmt::{self, Write};
+
+use anstyle::{AnsiColor, Color, Style};
+
+use crate::{
+    sizes::format_bytes,
+    stats::{LENGTH_HEADER, STATS_HEADER},
+    CollectionSchema, DateStats, Evolution, FieldSizes, Histogram, Indexes, SchemaReport, Severity,
+    Shapes, Shards, TimeSeries, Variants, Versions,
+};
+
+const HEADER: Style = Style::new().bold();
+/// Fields holding more than one type, not counting "missing"
+const MIXED: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Yellow)));
+/// Fields some documents lack
+const OPTIONAL: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Cyan)));
+const ERROR: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Red)));
+const WARN: Style = Style::new().fg_color(Some(Color::Ansi(AnsiColor::Yellow)));
+
+impl CollectionSchema {
+    /// The table `Display` prints, with fields of mixed type highlighted in yellow and
+    /// optional ones in cyan. Print it through `anstream` to drop the colors when the output
+    /// isn't a terminal
+    pub fn to_colored_table(&self) -> String {
+        let mut out = String::new();
+        self.write_table(&mut out, true).unwrap();
+        out
+    }
+
+    /// How many documents were sampled, then one row per field path with its presence and
+    /// type distribution
+    pub(crate) fn write_table(&self, out: &mut impl Write, color: bool) -> fmt::Result {
+        if let Some(sections) = self.variant_sections() {
+            for (i, section) in sections.iter().enumerate() {
+                if i > 0 {
+                    writeln!(out)?;
+                }
+                writeln!(out, "-- {} --", section.namespace)?;
+                section.write_table(out, color)?;
+            }
+            if let Some(note) = self.variants.as_ref().and_then(Variants::note) {
+                writeln!(out)?;
+                writeln!(out, "{}", note)?;
+            }
+            return Ok(());
+        }
+        writeln!(
+            out,
+            "{} documents sampled ({})",
+            self.sample_size, self.sample_method
+        )?;
+        if let Some(confidence) = &self.confidence {
+            writeln!(out, "{}", confidence.note(self.document_count))?;
+        }
+        if self.fields.is_empty() {
+            return Ok(());
+        }
+
+        let rows = self
+            .fields
+            .iter()
+            .map(|(path, field)| {
+                let mut presence = format!("{:.1}%", self.share(field.count) * 100.0);
+                if let Some(interval) = field.presence_interval {
+                    presence = format!("{} ({})", presence, interval);
+                }
+                let nulls = format!("{:.1}%", self.share(field.nulls()) * 100.0);
+                let types = field
+                    .types
+                    .iter()
+                    .map(|t| {
+                        format!(
+                            "{} {:.1}%",
+                            self.type_label(path, t),
+                            self.share(field.type_counts[t]) * 100.0
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                let style = if field.is_mixed() {
+                    MIXED
+                } else if field.missing() > 0 {
+                    OPTIONAL
+                } else {
+                    Style::new()
+                };
+                let examples = field.examples().unwrap_or_default();
+                (path.as_str(), presence, nulls, types, examples, style)
+            })
+            .collect::<Vec<_>>();
+        let path_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0).max(5);
+        let presence_width = rows
+            .iter()
+            .map(|row| row.1.len())
+            .max()
+            .unwrap_or(0)
+            .max("PRESENCE".len());
+        let null_width = "100.0%".len();
+        // examples line up after the types, when there are any
+        let examples = rows.iter().any(|row| !row.4.is_empty());
+        let types_width = if examples {
+            rows.iter().map(|row| row.3.len()).max().unwrap_or(0).max(5)
+        } else {
+            0
+        };
+
+        let style = |style: Style| if color { style } else { Style::new() };
+        writeln!(out)?;
+        writeln!(
+            out,
+            "{}{:<path_width$}  {:>presence_width$}  {:>null_width$}  {}{}",
+            style(HEADER).render(),
+            "FIELD",
+            "PRESENCE",
+            "NULL",
+            if examples {
+                format!("{:<types_width$}  EXAMPLES", "TYPES")
+            } else {
+                "TYPES".to_string()
+            },
+            style(HEADER).render_reset(),
+        )?;
+        for (path, presence, nulls, types, field_examples, row_style) in rows {
+            let types = if examples {
+                format!("{:<types_width$}  {}", types, field_examples)
+            } else {
+                types
+            };
+            writeln!(
+                out,
+                "{}{:<path_width$}  {:>presence_width$}  {:>null_width$}  {}{}",
+                style(row_style).render(),
+                path,
+                presence,
+                nulls,
+                types.trim_end(),
+                style(row_style).render_reset(),
+            )?;
+        }
+        let elements = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.element_presence?)))
+            .collect::<Vec<_>>();
+        if !elements.is_empty() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Array element fields:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            for (path, presence) in elements {
+                writeln!(out, "{}: {}", path, presence)?;
+            }
+        }
+        // only where the parent is optional, so it tells more than the presence
+        let conditional = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| {
+                let presence = field.conditional_presence?;
+                (presence != self.share(field.count)).then_some((path, presence))
+            })
+            .collect::<Vec<_>>();
+        if !conditional.is_empty() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Subdocument fields:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            for (path, presence) in conditional {
+                writeln!(
+                    out,
+                    "{}: in {:.1}% of the objects holding its parent",
+                    path,
+                    presence * 100.0
+                )?;
+            }
+        }
+        let top_values = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.top_values()?)))
+            .collect::<Vec<_>>();
+        if !top_values.is_empty() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Top values:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            for (path, top) in top_values {
+                writeln!(out, "{:<path_width$}  {}", path, top)?;
+            }
+        }
+        let binaries = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.binary_subtypes()?)))
+            .collect::<Vec<_>>();
+        if !binaries.is_empty() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Binary subtypes:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            for (path, subtypes) in binaries {
+                writeln!(out, "{:<path_width$}  {}", path, subtypes)?;
+            }
+        }
+        self.write_stats(out, color)?;
+        self.write_length_stats(out, color)?;
+        self.write_date_stats(out, color)?;
+        if let Some(sizes) = &self.sizes {
+            self.write_sizes(out, sizes, color)?;
+        }
+        if let Some(sizes) = &self.document_sizes {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Document sizes:{} {}",
+                style(HEADER).render(),
+                style(HEADER).render_reset(),
+                sizes.describe()
+            )?;
+            for bucket in &sizes.histogram {
+                writeln!(
+                    out,
+                    "  {:<20}  {:>8}  {:>5.1}%",
+                    bucket,
+                    bucket.count,
+                    sizes.share(bucket) * 100.0
+                )?;
+            }
+            if let Some(warning) = sizes.warning() {
+                writeln!(
+                    out,
+                    "{}warn{}   {}",
+                    style(WARN).render(),
+                    style(WARN).render_reset(),
+                    warning
+                )?;
+            }
+        }
+        if let Some(widths) = &self.document_widths {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Document widths:{} {}",
+                style(HEADER).render(),
+                style(HEADER).render_reset(),
+                widths.describe()
+            )?;
+            if let Some(warning) = widths.warning() {
+                writeln!(
+                    out,
+                    "{}warn{}   {}",
+                    style(WARN).render(),
+                    style(WARN).render_reset(),
+                    warning
+                )?;
+            }
+        }
+        if let Some(warning) = self.truncation_warning() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}warn{}   {}",
+                style(WARN).render(),
+                style(WARN).render_reset(),
+                warning
+            )?;
+        }
+        if let Some(versions) = &self.versions {
+            write_versions(out, versions, color)?;
+        }
+        if let Some(evolution) = &self.evolution {
+            write_evolution(out, evolution, color)?;
+        }
+        if let Some(time_series) = &self.time_series {
+            write_time_series(out, time_series, color)?;
+        }
+        if let Some(view) = &self.view {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}View:{} {}",
+                style(HEADER).render(),
+                style(HEADER).render_reset(),
+                view.describe()
+            )?;
+        }
+        if let Some(shards) = &self.shards {
+            write_shards(out, shards, color)?;
+        }
+        if let Some(indexes) = &self.indexes {
+            write_indexes(out, indexes, color)?;
+        }
+        if let Some(shapes) = &self.shapes {
+            self.write_shapes(out, shapes)?;
+        }
+        if !self.anomalies.is_empty() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Anomalies:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            for anomaly in &self.anomalies {
+                let severity = match anomaly.severity {
+                    Severity::Error => ERROR,
+                    Severity::Warn => WARN,
+                    Severity::Info => Style::new(),
+                };
+                writeln!(
+                    out,
+                    "{}{:<5}{}  {}",
+                    style(severity).render(),
+                    anomaly.severity,
+                    style(severity).render_reset(),
+                    anomaly
+                )?;
+            }
+        }
+        if !self.rare_fields.is_empty() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Rare fields:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            for field in &self.rare_fields {
+                writeln!(out, "{}", field)?;
+            }
+        }
+        if !self.findings.is_empty() {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Findings:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            for finding in &self.findings {
+                let severity = match finding.severity {
+                    Severity::Error => ERROR,
+                    Severity::Warn => WARN,
+                    Severity::Info => Style::new(),
+                };
+                writeln!(
+                    out,
+                    "{}{:<5}{}  {}",
+                    style(severity).render(),
+                    finding.severity,
+                    style(severity).render_reset(),
+                    finding
+                )?;
+            }
+        }
+        if let Some(issues) = &self.field_names {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Field names:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            if issues.is_empty() {
+                writeln!(out, "no field names break drivers or updates")?;
+            }
+            for issue in issues {
+                writeln!(
+                    out,
+                    "{}{}{}",
+                    style(WARN).render(),
+                    issue,
+                    style(WARN).render_reset()
+                )?;
+            }
+        }
+        if let Some(privacy) = &self.privacy {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Privacy:{}",
+                style(HEADER).render(),
+                style(HEADER).render_reset()
+            )?;
+            if privacy.is_empty() {
+                writeln!(out, "no fields look like personal data")?;
+            }
+            for finding in privacy {
+                writeln!(out, "{}", finding)?;
+            }
+        }
+        if let Some(meta) = &self.meta {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "{}Run:{} {}",
+                style(HEADER).render(),
+                style(HEADER).render_reset(),
+                meta.describe()
+            )?;
+            if let Some(explain) = &meta.explain {
+                write!(out, "{}", explain)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// A row of statistics for every numeric field, if there are any, ending in a sparkline of
+    /// its histogram if it has one
+    fn write_stats(&self, out: &mut impl Write, color: bool) -> fmt::Result {
+        let style = |style: Style| if color { style } else { Style::new() };
+        let rows = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| {
+                let stats = field.stats.as_ref()?;
+                let sparkline = stats.histogram.as_ref().map(Histogram::sparkline);
+                Some((path, stats.columns(), sparkline))
+            })
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let heading = "NUMERIC FIELD";
+        let path_width = rows
+            .iter()
+            .map(|(path, _, _)| path.len())
+            .max()
+            .unwrap_or(0)
+            .max(heading.len());
+        let mut widths = STATS_HEADER.map(str::len);
+        for (_, columns, _) in &rows {
+            for (width, column) in widths.iter_mut().zip(columns) {
+                *width = (*width).max(column.len());
+            }
+        }
+        let histograms = rows.iter().any(|(_, _, sparkline)| sparkline.is_some());
+        writeln!(out)?;
+        write!(out, "{}{:<path_width$}", style(HEADER).render(), heading)?;
+        for (heading, width) in STATS_HEADER.iter().zip(widths) {
+            write!(out, "  {:>width$}", heading)?;
+        }
+        if histograms {
+            write!(out, "  HISTOGRAM")?;
+        }
+        writeln!(out, "{}", style(HEADER).render_reset())?;
+        for (path, columns, sparkline) in rows {
+            write!(out, "{:<path_width$}", path)?;
+            for (column, width) in columns.iter().zip(widths) {
+                write!(out, "  {:>width$}", column)?;
+            }
+            if let Some(sparkline) = sparkline {
+                write!(out, "  {}", sparkline)?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// The string length statistics of every string field, if there are any, noting the
+    /// fields with long outliers in yellow
+    fn write_length_stats(&self, out: &mut impl Write, color: bool) -> fmt::Result {
+        let style = |style: Style| if color { style } else { Style::new() };
+        let rows = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.length_stats.as_ref()?)))
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let heading = "STRING FIELD";
+        let path_width = rows
+            .iter()
+            .map(|(path, _)| path.len())
+            .max()
+            .unwrap_or(0)
+            .max(heading.len());
+        let mut widths = LENGTH_HEADER.map(str::len);
+        for (_, stats) in &rows {
+            for (width, column) in widths.iter_mut().zip(stats.columns()) {
+                *width = (*width).max(column.len());
+            }
+        }
+        writeln!(out)?;
+        write!(out, "{}{:<path_width$}", style(HEADER).render(), heading)?;
+        for (heading, width) in LENGTH_HEADER.iter().zip(widths) {
+            write!(out, "  {:>width$}", heading)?;
+        }
+        writeln!(out, "{}", style(HEADER).render_reset())?;
+        for (path, stats) in rows {
+            write!(out, "{:<path_width$}", path)?;
+            for (column, width) in stats.columns().iter().zip(widths) {
+                write!(out, "  {:>width$}", column)?;
+            }
+            if stats.long_outliers {
+                write!(
+                    out,
+                    "  {}long outliers{}",
+                    style(WARN).render(),
+                    style(WARN).render_reset()
+                )?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    }
+
+    /// The bytes each top-level field takes up, heaviest first
+    fn write_sizes(&self, out: &mut impl Write, sizes: &FieldSizes, color: bool) -> fmt::Result {
+        let style = |style: Style| if color { style } else { Style::new() };
+        let heading = "FIELD";
+        let path_width = sizes
+            .fields
+            .iter()
+            .map(|field| field.path.len())
+            .max()
+            .unwrap_or(0)
+            .max(heading.len());
+        writeln!(out)?;
+        writeln!(
+            out,
+            "Storage by field, average document {}:",
+            format_bytes(sizes.average_document)
+        )?;
+        writeln!(
+            out,
+            "{}{:<path_width$}  {:>10}  {:>6}  {:>10}{}",
+            style(HEADER).render(),
+            heading,
+            "AVERAGE",
+            "SHARE",
+            "TOTAL",
+            style(HEADER).render_reset()
+        )?;
+        for field in &sizes.fields {
+            writeln!(
+                out,
+                "{:<path_width$}  {:>10}  {:>5.1}%  {:>10}",
+                field.path,
+                format_bytes(field.average),
+                sizes.share(field) * 100.0,
+                format_bytes(field.total as f64)
+            )?;
+        }
+        Ok(())
+    }
+
+    /// The range and distribution of every date field, if there are any
+    fn write_date_stats(&self, out: &mut impl Write, color: bool) -> fmt::Result {
+        let style = |style: Style| if color { style } else { Style::new() };
+        let rows = self
+            .fields
+            .iter()
+            .filter_map(|(path, field)| Some((path, field.date_stats.as_ref()?)))
+            .collect::<Vec<_>>();
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let heading = "DATE FIELD";
+        let path_width = rows
+            .iter()
+            .map(|(path, _)| path.len())
+            .max()
+            .unwrap_or(0)
+            .max(heading.len());
+        let width = |column: fn(&DateStats) -> &str| {
+            rows.iter()
+                .map(|(_, stats)| column(stats).len())
+                .max()
+                .unwrap_or(0)
+                .max("EARLIEST".len())
+        };
+        let earliest_width = width(|stats| &stats.earliest);
+        let latest_width = width(|stats| &stats.latest);
+        // sparklines of the histograms go before the distributions, which run on
+        let sparklines = rows
+            .iter()
+            .map(|(_, stats)| stats.histogram.as_ref().map(Histogram::sparkline))
+            .collect::<Vec<_>>();
+        let histogram_width = sparklines
+            .iter()
+            .flatten()
+            .map(|sparkline| sparkline.chars().count().max("HISTOGRAM".len()))
+            .max();
+        writeln!(out)?;
+        write!(
+            out,
+            "{}{:<path_width$}  {:<earliest_width$}  {:<latest_width$}  ",
+            style(HEADER).render(),
+            heading,
+            "EARLIEST",
+            "LATEST",
+        )?;
+        if let Some(width) = histogram_width {
+            write!(out, "{:<width$}  ", "HISTOGRAM")?;
+        }
+        writeln!(out, "DISTRIBUTION{}", style(HEADER).render_reset())?;
+        for ((path, stats), sparkline) in rows.into_iter().zip(sparklines) {
+            write!(
+                out,
+                "{:<path_width$}  {:<earliest_width$}  {:<latest_width$}  ",
+                path, stats.earliest, stats.latest,
+            )?;
+            if let Some(width) = histogram_width {
+                write!(out, "{:<width$}  ", sparkline.unwrap_or_default())?;
+            }
+            writeln!(out, "{}", stats.describe_distribution())?;
+        }
+        Ok(())
+    }
+
+    /// The share and number of documents of each shape, then the fields making it up
+    fn write_shapes(&self, out: &mut impl Write, shapes: &Shapes) -> fmt::Result {
+        writeln!(out)?;
+        writeln!(out, "{}:", shapes.summary())?;
+        for shape in &shapes.top {
+            writeln!(
+                out,
+                "{:>6.1}% {:>8}  {}",
+                self.share(shape.count) * 100.0,
+                shape.count,
+                shape.describe()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The fields that appeared, disappeared or changed type over time
+fn write_evolution(out: &mut impl Write, evolution: &Evolution, color: bool) -> fmt::Result {
+    let style = |style: Style| if color { style } else { Style::new() };
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{}Evolution:{} {}",
+        style(HEADER).render(),
+        style(HEADER).render_reset(),
+        evolution.describe()
+    )?;
+    let rows = evolution.notable().collect::<Vec<_>>();
+    if rows.is_empty() {
+        writeln!(out, "every field held the same types throughout")?;
+        return Ok(());
+    }
+    let heading = "FIELD";
+    let path_width = rows
+        .iter()
+        .map(|(path, _)| path.len())
+        .max()
+        .unwrap_or(0)
+        .max(heading.len());
+    let first_width = rows
+        .iter()
+        .map(|(_, history)| history.first_seen.len())
+        .max()
+        .unwrap_or(0)
+        .max("FIRST SEEN".len());
+    let last_width = rows
+        .iter()
+        .map(|(_, history)| history.last_seen.len())
+        .max()
+        .unwrap_or(0)
+        .max("LAST SEEN".len());
+    writeln!(
+        out,
+        "{}{:<path_width$}  {:<first_width$}  {:<last_width$}  TYPES{}",
+        style(HEADER).render(),
+        heading,
+        "FIRST SEEN",
+        "LAST SEEN",
+        style(HEADER).render_reset()
+    )?;
+    for (path, history) in rows {
+        writeln!(
+            out,
+            "{:<path_width$}  {:<first_width$}  {:<last_width$}  {}",
+            path,
+            history.first_seen,
+            history.last_seen,
+            history.describe_types()
+        )?;
+    }
+    Ok(())
+}
+
+/// The configuration, then the fields of the series' metadata
+fn write_time_series(out: &mut impl Write, time_series: &TimeSeries, color: bool) -> fmt::Result {
+    let style = |style: Style| if color { style } else { Style::new() };
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{}Time series:{} {}",
+        style(HEADER).render(),
+        style(HEADER).render_reset(),
+        time_series.describe()
+    )?;
+    if time_series.meta_fields.is_empty() {
+        return Ok(());
+    }
+    let rows = time_series
+        .meta_fields
+        .iter()
+        .map(|(path, field)| {
+            (
+                path.as_str(),
+                time_series.presence(field),
+                time_series.types(field),
+            )
+        })
+        .collect::<Vec<_>>();
+    let heading = "METADATA";
+    let path_width = rows
+        .iter()
+        .map(|row| row.0.len())
+        .max()
+        .unwrap_or(0)
+        .max(heading.len());
+    let presence_width = "PER SERIES".len();
+    writeln!(
+        out,
+        "{}{:<path_width$}  {:<presence_width$}  TYPES{}",
+        style(HEADER).render(),
+        heading,
+        "PER SERIES",
+        style(HEADER).render_reset()
+    )?;
+    for (path, presence, types) in rows {
+        writeln!(
+            out,
+            "{:<path_width$}  {:<presence_width$}  {}",
+            path, presence, types
+        )?;
+    }
+    Ok(())
+}
+
+/// The documents of each version, then what changed from one to the next
+fn write_versions(out: &mut impl Write, versions: &Versions, color: bool) -> fmt::Result {
+    let style = |style: Style| if color { style } else { Style::new() };
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{}Versions of {}:{}",
+        style(HEADER).render(),
+        versions.field,
+        style(HEADER).render_reset()
+    )?;
+    let value_width = versions
+        .versions
+        .iter()
+        .map(|version| version.value.to_string().len())
+        .max()
+        .unwrap_or(0);
+    for version in &versions.versions {
+        writeln!(
+            out,
+            "  {:<value_width$}  {} documents",
+            version.value.to_string(),
+            version.documents
+        )?;
+    }
+    for change in &versions.changes {
+        writeln!(out)?;
+        writeln!(out, "{} -> {}:", change.from, change.to)?;
+        for line in change.diff.to_string().lines() {
+            writeln!(out, "  {}", line)?;
+        }
+    }
+    if let Some(note) = versions.note() {
+        writeln!(out)?;
+        writeln!(out, "{}", note)?;
+    }
+    Ok(())
+}
+
+fn write_shards(out: &mut impl Write, shards: &Shards, color: bool) -> fmt::Result {
+    let style = |style: Style| if color { style } else { Style::new() };
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{}Shards:{} {}",
+        style(HEADER).render(),
+        style(HEADER).render_reset(),
+        shards.describe()
+    )?;
+    let name_width = shards
+        .shards
+        .iter()
+        .map(|shard| shard.shard.len())
+        .max()
+        .unwrap_or(0);
+    for shard in &shards.shards {
+        writeln!(
+            out,
+            "  {:<name_width$}  {} documents, {} sampled",
+            shard.shard, shard.document_count, shard.sample_size
+        )?;
+    }
+    for difference in &shards.differences {
+        writeln!(
+            out,
+            "{}differs{} {}: {}",
+            style(WARN).render(),
+            style(WARN).render_reset(),
+            difference.path,
+            difference.describe()
+        )?;
+    }
+    Ok(())
+}
+
+fn write_indexes(out: &mut impl Write, indexes: &Indexes, color: bool) -> fmt::Result {
+    let style = |style: Style| if color { style } else { Style::new() };
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{}Indexes:{} {}",
+        style(HEADER).render(),
+        style(HEADER).render_reset(),
+        indexes.describe()
+    )?;
+    let name_width = indexes
+        .existing
+        .iter()
+        .map(|index| index.name.len())
+        .max()
+        .unwrap_or(0);
+    for index in &indexes.existing {
+        writeln!(
+            out,
+            "  {:<name_width$}  {}{}",
+            index.name,
+            index.keys.join(", "),
+            if index.sparse { " (sparse)" } else { "" }
+        )?;
+    }
+    for suggestion in &indexes.suggestions {
+        writeln!(out, "suggest {}", suggestion)?;
+    }
+    for rare in &indexes.rare {
+        writeln!(
+            out,
+            "{}warn{}    {}",
+            style(WARN).render(),
+            style(WARN).render_reset(),
+            rare
+        )?;
+    }
+    Ok(())
+}
+
+impl SchemaReport {
+    /// Every schema as by [`CollectionSchema::to_colored_table`], under a header naming its
+    /// namespace
+    pub fn to_colored_table(&self) -> String {
+        let mut out = String::new();
+        self.write_tables(&mut out, true).unwrap();
+        out
+    }
+
+    pub(crate) fn write_tables(&self, out: &mut impl Write, color: bool) -> fmt::Result {
+        for (i, (ns, schema)) in self.namespaces.iter().enumerate() {
+            if i > 0 {
+                writeln!(out)?;
+            }
+            writeln!(out, "== {} ==", ns)?;
+            schema.write_table(out, color)?;
+        }
+        if !self.relationships.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "== Relationships ==")?;
+            for relationship in &self.relationships {
+                writeln!(out, "{}", relationship)?;
+            }
+        }
+        if !self.failures.is_empty() {
+            writeln!(out)?;
+            writeln!(out, "== Failed ==")?;
+            for (ns, error) in &self.failures {
+                writeln!(out, "{}: {}", ns, error)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AnalysisOptions;
+
+    fn schema() -> CollectionSchema {
+        let documents = b"{\"a\": 1, \"b\": 1, \"c\": true}\n{\"a\": 2, \"b\": \"x\"}\n";
+        CollectionSchema::from_ndjson(documents.as_slice(), "db.coll", &AnalysisOptions::default())
+            .unwrap()
+    }
+
+    #[test]
+    fn prints_a_row_per_field_with_its_presence_and_types() {
+        let table = schema().to_string();
+        assert!(table.starts_with("2 documents sampled"), "{}", table);
+        assert!(!table.contains('\x1b'), "{}", table);
+        let row = |field: &str| {
+            table
+                .lines()
+                .find(|line| line.starts_with(field))
+                .unwrap_or_else(|| panic!("no row for {} in {}", field, table))
+                .to_string()
+        };
+        assert!(row("FIELD").contains("PRESENCE"));
+        assert!(row("a ").contains("100.0%"));
+        assert!(row("c ").contains("50.0%"));
+        let mixed = row("b ");
+        assert!(mixed.contains("int 50.0%") && mixed.contains("string 50.0%"));
+    }
+
+    #[test]
+    fn colors_mixed_and_optional_fields() {
+        let table = schema().to_colored_table();
+        let row = |field: &str| {
+            table
+                .lines()
+                .find(|line| line.contains(&format!("{}  ", field)))
+                .unwrap()
+                .to_string()
+        };
+        assert!(row("b").starts_with(&MIXED.render().to_string()));
+        assert!(row("c").starts_with(&OPTIONAL.render().to_string()));
+        assert!(!row("a").starts_with(&OPTIONAL.render().to_string()));
+
+        let report = SchemaReport {
+            namespaces: [("db.coll".to_string(), schema())].into(),
+            failures: [("db.gone".to_string(), "not authorized".to_string())].into(),
+            ..SchemaReport::default()
+        };
+        let tables = report.to_colored_table();
+        assert!(tables.starts_with("== db.coll =="), "{}", tables);
+        assert!(tables.contains("== Failed ==\ndb.gone: not authorized"));
+    }
+}
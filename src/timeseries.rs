@@ -0,0 +1,188 @@
+//! Time-series collections: their measurements are sampled through the collection itself,
+//! never its `system.buckets` storage, and the series' metadata gets a schema of its own
+
+use std::collections::BTreeMap;
+
+#[cfg(feature = "driver")]
+use bson::{doc, Bson, Document};
+#[cfg(feature = "driver")]
+use futures::stream::TryStreamExt;
+#[cfg(feature = "driver")]
+use mongodb::options::TimeseriesOptions;
+use serde::{Deserialize, Serialize};
+
+use crate::FieldSchema;
+#[cfg(feature = "driver")]
+use crate::{infer::SchemaBuilder, Result, SchemaAnalyzer};
+
+/// At most this many series are read for the schema of their metadata
+pub const SERIES_LIMIT: u64 = 10_000;
+
+/// How a time-series collection is configured, and what its series' metadata holds
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimeSeries {
+    /// The top-level date field of every measurement
+    pub time_field: String,
+    /// The top-level field identifying the series a measurement belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub meta_field: Option<String>,
+    /// `seconds`, `minutes` or `hours`, if the server reported it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granularity: Option<String>,
+    /// How many distinct series the metadata was read from, up to [`SERIES_LIMIT`]
+    #[serde(default)]
+    pub series: u64,
+    /// The fields of the metadata, keyed by path from the measurement (`meta.sensor`), and
+    /// counted once per series rather than once per measurement
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub meta_fields: BTreeMap<String, FieldSchema>,
+}
+
+impl TimeSeries {
+    /// e.g. "timed by `ts`, series by `meta` (minutes granularity): 42 series"
+    pub fn describe(&self) -> String {
+        let mut out = format!("timed by `{}`", self.time_field);
+        if let Some(meta_field) = &self.meta_field {
+            out += &format!(", series by `{}`", meta_field);
+        }
+        if let Some(granularity) = &self.granularity {
+            out += &format!(" ({} granularity)", granularity);
+        }
+        if self.meta_field.is_some() {
+            let limited = if self.series >= SERIES_LIMIT {
+                "at least "
+            } else {
+                ""
+            };
+            out += &format!(": {}{} series", limited, self.series);
+        }
+        out
+    }
+
+    /// The share of series holding a metadata field, as a percentage
+    pub(crate) fn presence(&self, field: &FieldSchema) -> String {
+        format!("{:.1}%", share(field.count, self.series) * 100.0)
+    }
+
+    /// A metadata field's types with the share of series holding each
+    pub(crate) fn types(&self, field: &FieldSchema) -> String {
+        field
+            .types
+            .iter()
+            .map(|ty| {
+                let count = field.type_counts.get(ty).copied().unwrap_or_default();
+                format!("{} {:.1}%", ty.name(), share(count, self.series) * 100.0)
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+fn share(count: u64, of: u64) -> f64 {
+    if of == 0 {
+        0.0
+    } else {
+        count as f64 / of as f64
+    }
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Read the metadata of up to [`SERIES_LIMIT`] series matching the filter, each once.
+    /// Grouping by the meta field runs on the buckets, so it's cheap however many
+    /// measurements there are
+    pub(crate) async fn analyze_time_series(
+        &self,
+        options: TimeseriesOptions,
+    ) -> Result<TimeSeries> {
+        let granularity = options
+            .granularity
+            .and_then(|granularity| serde_json::to_value(granularity).ok())
+            .and_then(|granularity| granularity.as_str().map(str::to_string));
+        let mut time_series = TimeSeries {
+            time_field: options.time_field,
+            meta_field: options.meta_field,
+            granularity,
+            series: 0,
+            meta_fields: BTreeMap::new(),
+        };
+        let Some(meta_field) = &time_series.meta_field else {
+            return Ok(time_series);
+        };
+        let mut pipeline = Vec::new();
+        if let Some(filter) = &self.options.filter {
+            pipeline.push(doc! { "$match": filter.clone() });
+        }
+        pipeline.push(doc! { "$group": { "_id": format!("${}", meta_field) } });
+        pipeline.push(doc! { "$limit": SERIES_LIMIT as i64 });
+        let mut cursor = self
+            .collection()
+            .aggregate(pipeline, self.aggregate_options()?)
+            .await?;
+        let mut builder = SchemaBuilder::new(self.options.max_depth).limited_by(&self.options);
+        while let Some(series) = cursor.try_next().await? {
+            let meta = series.get("_id").cloned().unwrap_or(Bson::Null);
+            let mut document = Document::new();
+            document.insert(meta_field.as_str(), meta);
+            builder.add(&document);
+        }
+        time_series.series = builder.documents();
+        time_series.meta_fields = builder.build(self.ns.to_string()).fields;
+        Ok(time_series)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnalysisOptions, CollectionSchema};
+
+    fn time_series(meta_field: Option<&str>, series: u64) -> TimeSeries {
+        TimeSeries {
+            time_field: "ts".to_string(),
+            meta_field: meta_field.map(str::to_string),
+            granularity: Some("minutes".to_string()),
+            series,
+            meta_fields: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn describes_the_configuration_and_series() {
+        assert_eq!(
+            time_series(Some("meta"), 42).describe(),
+            "timed by `ts`, series by `meta` (minutes granularity): 42 series"
+        );
+        assert_eq!(
+            time_series(Some("meta"), SERIES_LIMIT).describe(),
+            "timed by `ts`, series by `meta` (minutes granularity): at least 10000 series"
+        );
+        assert_eq!(
+            time_series(None, 0).describe(),
+            "timed by `ts` (minutes granularity)"
+        );
+    }
+
+    #[test]
+    fn shares_metadata_fields_out_of_the_series() {
+        let series = b"{\"meta\": {\"sensor\": 1}}\n{\"meta\": {\"sensor\": \"a\"}}\n\
+            {\"meta\": {\"sensor\": 2}}\n{\"meta\": {}}\n";
+        let schema = CollectionSchema::from_ndjson(
+            series.as_slice(),
+            "db.coll",
+            &AnalysisOptions::default(),
+        )
+        .unwrap();
+        let metadata = TimeSeries {
+            meta_fields: schema.fields,
+            ..time_series(Some("meta"), 4)
+        };
+        let sensor = &metadata.meta_fields["meta.sensor"];
+        assert_eq!(metadata.presence(sensor), "75.0%");
+        assert_eq!(
+            metadata.types(sensor),
+            "int 50.0%, string 25.0%, missing 25.0%"
+        );
+        assert_eq!(time_series(Some("meta"), 0).presence(sensor), "0.0%");
+    }
+}
@@ -0,0 +1,411 @@
+//! An interactive browser for inferred schemas: collections on the left, their fields as a tree
+//! on the right, for collections too wide to read as printed output
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Borders, Cell, List, ListState, Paragraph, Row, Table, TableState},
+    DefaultTerminal, Frame,
+};
+
+use crate::{sizes::format_bytes, CollectionSchema, FieldSchema};
+
+/// How sibling fields are ordered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Sort {
+    Name,
+    /// Most present first
+    Presence,
+    /// Largest share of the documents' bytes first, for top-level fields with sizes
+    Size,
+}
+
+/// What the columns after the path show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum View {
+    Types,
+    Stats,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Collections,
+    Fields,
+}
+
+/// One visible line of the field tree
+struct FieldRow<'a> {
+    path: &'a str,
+    depth: usize,
+    has_children: bool,
+}
+
+struct Browser<'a> {
+    schemas: Vec<&'a CollectionSchema>,
+    collections: ListState,
+    fields: TableState,
+    /// The expanded paths of each collection
+    expanded: Vec<BTreeSet<String>>,
+    focus: Focus,
+    sort: Sort,
+    view: View,
+}
+
+/// Browse `schemas` in the terminal until `q` or Esc is pressed
+pub fn browse<'a>(schemas: impl IntoIterator<Item = &'a CollectionSchema>) -> io::Result<()> {
+    let schemas = schemas.into_iter().collect::<Vec<_>>();
+    let mut browser = Browser {
+        collections: ListState::default().with_selected(Some(0)),
+        fields: TableState::default().with_selected(Some(0)),
+        expanded: vec![BTreeSet::new(); schemas.len()],
+        focus: if schemas.len() > 1 {
+            Focus::Collections
+        } else {
+            Focus::Fields
+        },
+        sort: Sort::Name,
+        view: View::Types,
+        schemas,
+    };
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl<'a> Browser<'a> {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                KeyCode::Tab => {
+                    self.focus = match self.focus {
+                        Focus::Collections => Focus::Fields,
+                        Focus::Fields => Focus::Collections,
+                    }
+                }
+                KeyCode::Char('s') => {
+                    self.sort = match self.sort {
+                        Sort::Name => Sort::Presence,
+                        Sort::Presence => Sort::Size,
+                        Sort::Size => Sort::Name,
+                    }
+                }
+                KeyCode::Char('v') => {
+                    self.view = match self.view {
+                        View::Types => View::Stats,
+                        View::Stats => View::Types,
+                    }
+                }
+                KeyCode::Up | KeyCode::Char('k') => self.step(-1),
+                KeyCode::Down | KeyCode::Char('j') => self.step(1),
+                KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') => self.expand(true),
+                KeyCode::Left | KeyCode::Char('h') => self.expand(false),
+                _ => {}
+            }
+        }
+    }
+
+    fn schema(&self) -> Option<&'a CollectionSchema> {
+        self.schemas.get(self.collections.selected()?).copied()
+    }
+
+    /// Move the selection of the focused pane by `delta` rows
+    fn step(&mut self, delta: isize) {
+        let (len, selected) = match self.focus {
+            Focus::Collections => (self.schemas.len(), self.collections.selected()),
+            Focus::Fields => (self.rows().len(), self.fields.selected()),
+        };
+        if len == 0 {
+            return;
+        }
+        let next = (selected.unwrap_or(0) as isize + delta).clamp(0, len as isize - 1) as usize;
+        match self.focus {
+            Focus::Collections => {
+                self.collections.select(Some(next));
+                self.fields.select(Some(0));
+            }
+            Focus::Fields => self.fields.select(Some(next)),
+        }
+    }
+
+    /// Expand or collapse the selected field, or with the collections focused, move to the
+    /// fields
+    fn expand(&mut self, expand: bool) {
+        if self.focus == Focus::Collections {
+            if expand {
+                self.focus = Focus::Fields;
+            }
+            return;
+        }
+        let Some(collection) = self.collections.selected() else {
+            return;
+        };
+        let rows = self.rows();
+        let Some(row) = self.fields.selected().and_then(|i| rows.get(i)) else {
+            return;
+        };
+        let path = row.path.to_string();
+        // collapsing a field that isn't expanded selects its parent instead
+        let parent = if expand || self.expanded[collection].contains(&path) {
+            None
+        } else {
+            parent(&path).and_then(|parent| rows.iter().position(|row| row.path == parent))
+        };
+        if expand && row.has_children {
+            self.expanded[collection].insert(path);
+        } else if !expand {
+            self.expanded[collection].remove(&path);
+        }
+        if let Some(parent) = parent {
+            self.fields.select(Some(parent));
+        }
+    }
+
+    /// The visible fields of the selected collection: the top-level ones and the children of
+    /// expanded ones, siblings in the chosen order
+    fn rows(&self) -> Vec<FieldRow<'a>> {
+        let (Some(schema), Some(collection)) = (self.schema(), self.collections.selected()) else {
+            return Vec::new();
+        };
+        let mut children = BTreeMap::<Option<&str>, Vec<&str>>::new();
+        for path in schema.fields.keys() {
+            children.entry(parent(path)).or_default().push(path);
+        }
+        for siblings in children.values_mut() {
+            match self.sort {
+                Sort::Name => {}
+                Sort::Presence => siblings.sort_by_key(|path| {
+                    std::cmp::Reverse(schema.fields.get(*path).map_or(0, |field| field.count))
+                }),
+                Sort::Size => siblings.sort_by(|a, b| size(schema, b).total_cmp(&size(schema, a))),
+            }
+        }
+        let mut rows = Vec::new();
+        let mut stack = children
+            .get(&None)
+            .into_iter()
+            .flatten()
+            .rev()
+            .map(|path| (*path, 0))
+            .collect::<Vec<_>>();
+        while let Some((path, depth)) = stack.pop() {
+            let nested = children.get(&Some(path));
+            rows.push(FieldRow {
+                path,
+                depth,
+                has_children: nested.is_some(),
+            });
+            if self.expanded[collection].contains(path) {
+                stack.extend(
+                    nested
+                        .into_iter()
+                        .flatten()
+                        .rev()
+                        .map(|child| (*child, depth + 1)),
+                );
+            }
+        }
+        rows
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, help] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [left, right] =
+            Layout::horizontal([Constraint::Percentage(25), Constraint::Min(0)]).areas(main);
+        let focused = |focus: Focus| {
+            let style = Style::default();
+            if self.focus == focus {
+                style.fg(Color::Cyan)
+            } else {
+                style
+            }
+        };
+        let highlight = Style::default().add_modifier(Modifier::REVERSED);
+
+        let collections = List::new(self.schemas.iter().map(|schema| schema.namespace.as_str()))
+            .block(
+                Block::default()
+                    .title("Collections")
+                    .borders(Borders::ALL)
+                    .border_style(focused(Focus::Collections)),
+            )
+            .highlight_style(highlight);
+        frame.render_stateful_widget(collections, left, &mut self.collections);
+
+        let header = match self.view {
+            View::Types => ["FIELD", "PRESENCE", "SIZE", "TYPES"],
+            View::Stats => ["FIELD", "PRESENCE", "SIZE", "STATISTICS"],
+        };
+        let title = match self.schema() {
+            Some(schema) => format!(
+                "{} ({} documents sampled, by {})",
+                schema.namespace,
+                schema.sample_size,
+                match self.sort {
+                    Sort::Name => "name",
+                    Sort::Presence => "presence",
+                    Sort::Size => "size",
+                }
+            ),
+            None => "Fields".to_string(),
+        };
+        let rows = match self.schema() {
+            Some(schema) => self
+                .rows()
+                .iter()
+                .map(|row| field_row(schema, row, self.view, self.expanded_at(row.path)))
+                .collect(),
+            None => Vec::new(),
+        };
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Percentage(35),
+                Constraint::Length(9),
+                Constraint::Length(10),
+                Constraint::Min(0),
+            ],
+        )
+        .header(Row::new(header).style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(focused(Focus::Fields)),
+        )
+        .row_highlight_style(highlight);
+        frame.render_stateful_widget(table, right, &mut self.fields);
+
+        frame.render_widget(
+            Paragraph::new(Line::from(
+                "tab switch pane  ↑↓ move  → expand  ← collapse  s sort  v types/stats  q quit",
+            )),
+            help,
+        );
+    }
+
+    fn expanded_at(&self, path: &str) -> bool {
+        self.collections
+            .selected()
+            .is_some_and(|collection| self.expanded[collection].contains(path))
+    }
+}
+
+/// The table row of the field at `row`: its name indented by depth, presence, size and types
+/// or statistics
+fn field_row<'a>(
+    schema: &'a CollectionSchema,
+    row: &FieldRow<'a>,
+    view: View,
+    expanded: bool,
+) -> Row<'a> {
+    let field = &schema.fields[row.path];
+    let marker = match (row.has_children, expanded) {
+        (false, _) => " ",
+        (true, false) => "▸",
+        (true, true) => "▾",
+    };
+    let name = format!("{}{} {}", "  ".repeat(row.depth), marker, name(row.path));
+    let size = schema
+        .sizes
+        .as_ref()
+        .and_then(|sizes| sizes.fields.iter().find(|size| size.path == row.path))
+        .map(|size| format_bytes(size.average))
+        .unwrap_or_default();
+    let detail = match view {
+        View::Types => field
+            .types
+            .iter()
+            .map(|t| {
+                format!(
+                    "{} {:.1}%",
+                    schema.type_label(row.path, t),
+                    schema.share(field.type_counts[t]) * 100.0
+                )
+            })
+            .collect::<Vec<_>>()
+            .join(", "),
+        View::Stats => stats(field),
+    };
+    let style = if field.is_mixed() {
+        Style::default().fg(Color::Yellow)
+    } else if field.missing() > 0 {
+        Style::default().fg(Color::Cyan)
+    } else {
+        Style::default()
+    };
+    Row::new([
+        Cell::from(name),
+        Cell::from(format!("{:.1}%", schema.share(field.count) * 100.0)),
+        Cell::from(size),
+        Cell::from(detail),
+    ])
+    .style(style)
+}
+
+/// The field's numeric, length or date statistics, whichever it has
+fn stats(field: &FieldSchema) -> String {
+    let mut parts = Vec::new();
+    if let Some(stats) = &field.stats {
+        let [min, _, median, _, _, _, max, mean] = stats.columns();
+        parts.push(format!(
+            "min {min}, median {median}, max {max}, mean {mean}"
+        ));
+    }
+    if let Some(lengths) = &field.length_stats {
+        parts.push(format!(
+            "length min {}, median {}, max {}",
+            lengths.min, lengths.p50, lengths.max
+        ));
+    }
+    if let Some(dates) = &field.date_stats {
+        parts.push(format!("{} to {}", dates.earliest, dates.latest));
+    }
+    if parts.is_empty() {
+        "no statistics, run with --stats".to_string()
+    } else {
+        parts.join("; ")
+    }
+}
+
+/// The average bytes of a top-level field, or zero without sizes
+fn size(schema: &CollectionSchema, path: &str) -> f64 {
+    schema
+        .sizes
+        .as_ref()
+        .and_then(|sizes| sizes.fields.iter().find(|size| size.path == path))
+        .map_or(0.0, |size| size.average)
+}
+
+/// The path of the field `path` is nested in: `items[]` for `items[].sku`, `items` for
+/// `items[]`
+fn parent(path: &str) -> Option<&str> {
+    if let Some(array) = path.strip_suffix("[]") {
+        return Some(array);
+    }
+    path.rfind('.').map(|i| &path[..i])
+}
+
+/// The last segment of `path`, as shown in the tree
+fn name(path: &str) -> &str {
+    if path.ends_with("[]") {
+        return "[]";
+    }
+    path.rfind('.').map_or(path, |i| &path[i + 1..])
+}
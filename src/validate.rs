@@ -0,0 +1,393 @@
+use std::fmt;
+
+use bson::{Bson, Document};
+#[cfg(feature = "driver")]
+use futures::stream::TryStreamExt;
+#[cfg(feature = "driver")]
+use mongodb::options::FindOptions;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::{
+    analyzer::{counted_exactly, Throttle},
+    SchemaAnalyzer,
+};
+use crate::{
+    codegen::allowed_types, infer::bson_type, pipeline::profile_pipeline, Progress, Result,
+};
+
+/// How documents checked by [`SchemaAnalyzer::validate`] fared against a `$jsonSchema`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// The `db.collection` checked
+    pub namespace: String,
+    /// How many documents were checked
+    pub checked: u64,
+    /// How many of them violate the schema
+    pub invalid: u64,
+    /// How many documents fail each constraint, most common first
+    pub failures: Vec<ConstraintFailures>,
+    /// The first invalid documents, with everything wrong with them
+    pub examples: Vec<InvalidDocument>,
+}
+
+/// The documents failing one constraint on one path
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConstraintFailures {
+    pub path: String,
+    pub constraint: Constraint,
+    pub documents: u64,
+}
+
+/// An invalid document, by `_id`, and what's wrong with it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct InvalidDocument {
+    /// The document's `_id` as relaxed Extended JSON
+    pub id: serde_json::Value,
+    pub violations: Vec<Violation>,
+}
+
+/// One constraint a value fails
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Violation {
+    pub path: String,
+    pub constraint: Constraint,
+    pub message: String,
+}
+
+/// The `$jsonSchema` keywords that are checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Constraint {
+    Required,
+    BsonType,
+    Enum,
+    AdditionalProperties,
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Constraint::Required => "required",
+            Constraint::BsonType => "bsonType",
+            Constraint::Enum => "enum",
+            Constraint::AdditionalProperties => "additionalProperties",
+        })
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} of {} documents checked in {} are invalid",
+            self.invalid, self.checked, self.namespace
+        )?;
+        if !self.failures.is_empty() {
+            writeln!(f)?;
+            writeln!(f, "Failed constraints:")?;
+            for failure in &self.failures {
+                writeln!(
+                    f,
+                    "{:>8}  {:<20}  {}",
+                    failure.documents, failure.constraint, failure.path
+                )?;
+            }
+        }
+        for example in &self.examples {
+            writeln!(f)?;
+            writeln!(f, "_id {}:", example.id)?;
+            for violation in &example.violations {
+                writeln!(f, "  {}", violation)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Check the documents the sampling selects against `json_schema` (the value of a
+    /// validator's `$jsonSchema`), keeping up to `examples` invalid ones. Only `bsonType`
+    /// (or `type`), `required`, `properties`, `additionalProperties`, `items` and `enum`
+    /// are checked, which covers the validators [`to_validator`](crate::CollectionSchema::to_validator)
+    /// builds
+    pub async fn validate(
+        &self,
+        json_schema: &Document,
+        examples: usize,
+    ) -> Result<ValidationReport> {
+        let collection = self.collection();
+        let exact = counted_exactly(self.specification().await.as_ref());
+        let document_count = self.count(&collection, exact).await?;
+        let sample_size = self.options.sampling.size(document_count);
+        let mut cursor = match sample_size {
+            Some(size) => {
+                self.report(Progress::Aggregating);
+                collection
+                    .aggregate(
+                        profile_pipeline(self.options.filter.as_ref(), size),
+                        self.aggregate_options()?,
+                    )
+                    .await?
+            }
+            None => {
+                let options = FindOptions::builder()
+                    .comment(self.options.comment.clone())
+                    .collation(self.collation()?)
+                    .hint(self.hint())
+                    .max_time(self.options.max_time)
+                    .batch_size(self.options.batch_size)
+                    .build();
+                collection
+                    .find(self.options.filter.clone(), options)
+                    .await?
+            }
+        };
+
+        let mut report = ValidationReport {
+            namespace: self.ns.to_string(),
+            ..ValidationReport::default()
+        };
+        let throttle = Throttle::new(self.options.throttle);
+        while let Some(doc) = cursor.try_next().await? {
+            report.checked += 1;
+            self.report(Progress::Documents {
+                read: report.checked,
+                expected: sample_size.or(Some(document_count)),
+            });
+            throttle.wait(report.checked).await;
+            let violations = violations(json_schema, &doc);
+            if violations.is_empty() {
+                continue;
+            }
+            report.invalid += 1;
+            report.count(&violations);
+            if report.examples.len() < examples {
+                report.examples.push(InvalidDocument {
+                    id: doc
+                        .get("_id")
+                        .cloned()
+                        .unwrap_or(Bson::Null)
+                        .into_relaxed_extjson(),
+                    violations,
+                });
+            }
+        }
+        report.failures.sort_by(|a, b| {
+            b.documents
+                .cmp(&a.documents)
+                .then_with(|| a.path.cmp(&b.path))
+                .then_with(|| a.constraint.cmp(&b.constraint))
+        });
+        Ok(report)
+    }
+}
+
+impl ValidationReport {
+    /// Count one document's violations, each failed constraint once
+    fn count(&mut self, violations: &[Violation]) {
+        let mut counted: Vec<(&str, Constraint)> = Vec::new();
+        for violation in violations {
+            let key = (violation.path.as_str(), violation.constraint);
+            if counted.contains(&key) {
+                continue;
+            }
+            counted.push(key);
+            match self
+                .failures
+                .iter_mut()
+                .find(|f| f.path == violation.path && f.constraint == violation.constraint)
+            {
+                Some(failure) => failure.documents += 1,
+                None => self.failures.push(ConstraintFailures {
+                    path: violation.path.clone(),
+                    constraint: violation.constraint,
+                    documents: 1,
+                }),
+            }
+        }
+    }
+}
+
+/// Everything about `doc` that `json_schema` rejects
+fn violations(json_schema: &Document, doc: &Document) -> Vec<Violation> {
+    let mut violations = Vec::new();
+    check_object(json_schema, doc, "", &mut violations);
+    violations
+}
+
+fn check(schema: &Document, value: &Bson, path: &str, violations: &mut Vec<Violation>) {
+    let ty = bson_type(value);
+    if let Some(types) = allowed_types(schema) {
+        if !types.contains(&ty) {
+            let allowed = types.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+            violations.push(Violation {
+                path: path.to_string(),
+                constraint: Constraint::BsonType,
+                message: format!("is {}, expected {}", ty, allowed.join(" or ")),
+            });
+            return;
+        }
+    }
+    if let Ok(values) = schema.get_array("enum") {
+        if !values.iter().any(|allowed| same_value(allowed, value)) {
+            violations.push(Violation {
+                path: path.to_string(),
+                constraint: Constraint::Enum,
+                message: format!("{} is not one of the allowed values", value),
+            });
+        }
+    }
+    match value {
+        Bson::Document(doc) => check_object(schema, doc, &format!("{}.", path), violations),
+        Bson::Array(elements) => {
+            if let Ok(items) = schema.get_document("items") {
+                let path = format!("{}[]", path);
+                for element in elements {
+                    check(items, element, &path, violations);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The `required`, `properties` and `additionalProperties` constraints of an object, whose
+/// fields' paths start with `prefix`
+fn check_object(schema: &Document, doc: &Document, prefix: &str, violations: &mut Vec<Violation>) {
+    if let Ok(required) = schema.get_array("required") {
+        for name in required.iter().filter_map(Bson::as_str) {
+            if !doc.contains_key(name) {
+                violations.push(Violation {
+                    path: format!("{}{}", prefix, name),
+                    constraint: Constraint::Required,
+                    message: "is missing".to_string(),
+                });
+            }
+        }
+    }
+    let properties = schema.get_document("properties").ok();
+    let closed = schema.get_bool("additionalProperties") == Ok(false);
+    for (name, value) in doc {
+        let path = format!("{}{}", prefix, name);
+        match properties.and_then(|properties| properties.get_document(name).ok()) {
+            Some(property) => check(property, value, &path, violations),
+            None if closed => violations.push(Violation {
+                path,
+                constraint: Constraint::AdditionalProperties,
+                message: "is not allowed".to_string(),
+            }),
+            None => {}
+        }
+    }
+}
+
+/// Whether `value` matches the enum value `allowed`, comparing numbers by value as the server
+/// does
+fn same_value(allowed: &Bson, value: &Bson) -> bool {
+    fn number(value: &Bson) -> Option<f64> {
+        match value {
+            Bson::Int32(n) => Some(f64::from(*n)),
+            Bson::Int64(n) => Some(*n as f64),
+            Bson::Double(n) => Some(*n),
+            _ => None,
+        }
+    }
+    match (number(allowed), number(value)) {
+        (Some(a), Some(b)) => a == b,
+        _ => allowed == value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::*;
+
+    fn schema() -> Document {
+        doc! {
+            "bsonType": "object",
+            "required": ["name", "status"],
+            "additionalProperties": false,
+            "properties": {
+                "_id": {},
+                "name": { "bsonType": "string" },
+                "status": { "enum": ["active", "banned"] },
+                "score": { "bsonType": ["int", "double"] },
+                "tags": { "bsonType": "array", "items": { "bsonType": "string" } },
+                "address": {
+                    "bsonType": "object",
+                    "required": ["city"],
+                    "properties": { "city": { "bsonType": "string" } }
+                }
+            }
+        }
+    }
+
+    fn constraints(doc: &Document) -> Vec<(String, Constraint)> {
+        violations(&schema(), doc)
+            .into_iter()
+            .map(|violation| (violation.path, violation.constraint))
+            .collect()
+    }
+
+    #[test]
+    fn finds_everything_a_document_breaks() {
+        assert!(constraints(&doc! { "name": "Ada", "status": "active", "score": 9 }).is_empty());
+        // numbers compare by value, as on the server
+        assert!(same_value(&Bson::Int32(1), &Bson::Double(1.0)));
+        assert!(!same_value(&Bson::Int32(1), &Bson::String("1".to_string())));
+
+        let broken = doc! {
+            "name": 7,
+            "status": "gone",
+            "tags": ["a", 2],
+            "address": { "zip": "N1" },
+            "extra": true,
+        };
+        assert_eq!(
+            constraints(&broken),
+            [
+                ("name".to_string(), Constraint::BsonType),
+                ("status".to_string(), Constraint::Enum),
+                ("tags[]".to_string(), Constraint::BsonType),
+                ("address.city".to_string(), Constraint::Required),
+                ("extra".to_string(), Constraint::AdditionalProperties),
+            ]
+        );
+        let message = &violations(&schema(), &broken)[0];
+        assert_eq!(message.to_string(), "name: is int, expected string");
+        assert_eq!(
+            constraints(&doc! { "name": "Ada" }),
+            [("status".to_string(), Constraint::Required)]
+        );
+    }
+
+    #[test]
+    fn counts_each_failed_constraint_once_per_document() {
+        let mut report = ValidationReport::default();
+        let bad_tags = violations(
+            &schema(),
+            &doc! { "name": "Ada", "status": "active", "tags": [1, 2] },
+        );
+        assert_eq!(bad_tags.len(), 2);
+        report.count(&bad_tags);
+        report.count(&bad_tags);
+        assert_eq!(
+            report.failures,
+            [ConstraintFailures {
+                path: "tags[]".to_string(),
+                constraint: Constraint::BsonType,
+                documents: 2,
+            }]
+        );
+    }
+}
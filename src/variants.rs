@@ -0,0 +1,510 @@
+//! Polymorphic collections analyzed one variant at a time, keyed by a discriminator field like
+//! `type` or `kind`, so each variant's fields aren't lost in the union of all of them. A
+//! version field like `schemaVersion` is partitioned the same way, its versions then diffed in
+//! order
+
+use std::{cmp::Ordering, collections::BTreeSet};
+
+use bson::{doc, Bson, Document};
+#[cfg(feature = "driver")]
+use futures::stream::TryStreamExt;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "driver")]
+use crate::SchemaAnalyzer;
+use crate::{
+    options::ancestors,
+    pipeline::{count_at, profile_pipeline},
+    AnalyzerError, CollectionSchema, Result, SchemaDiff,
+};
+
+/// Most discriminator values given a schema of their own. The documents holding any other
+/// value are only counted
+pub(crate) const MAX_VARIANTS: usize = 20;
+
+/// The schemas of the documents holding each value of a discriminator field, see
+/// [`AnalysisOptions::group_by`](crate::AnalysisOptions::group_by)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Variants {
+    /// The discriminator's path
+    pub field: String,
+    /// One schema per value, the most common first
+    pub variants: Vec<Variant>,
+    /// The paths every variant has
+    pub common: Vec<String>,
+    /// How many sampled documents hold a value beyond the 20 most common, and how many such
+    /// values there are
+    #[serde(default)]
+    pub other_documents: u64,
+    #[serde(default)]
+    pub other_values: u64,
+}
+
+/// The documents holding one value of the discriminator
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Variant {
+    /// The value, as relaxed Extended JSON. Null also stands for documents lacking the field
+    pub value: serde_json::Value,
+    /// How many sampled documents hold it
+    pub documents: u64,
+    pub schema: CollectionSchema,
+}
+
+/// The schemas of the documents holding each value of a version field, in version order, see
+/// [`AnalysisOptions::version_field`](crate::AnalysisOptions::version_field)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Versions {
+    /// The version field's path
+    pub field: String,
+    /// One schema per version, the oldest first: documents lacking the field, then numbers in
+    /// order, then strings with their numeric parts compared as numbers (`v2` before `v10`)
+    pub versions: Vec<Variant>,
+    /// How each version's schema differs from the one before it
+    pub changes: Vec<VersionChange>,
+    /// How many sampled documents hold a version beyond the 20 most common, and how many such
+    /// versions there are
+    #[serde(default)]
+    pub other_documents: u64,
+    #[serde(default)]
+    pub other_values: u64,
+}
+
+/// How the schema changed from one version to the next
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VersionChange {
+    pub from: serde_json::Value,
+    pub to: serde_json::Value,
+    pub diff: SchemaDiff,
+}
+
+impl Variants {
+    /// Collect `variants`, given as (value, documents, schema), into the most common
+    /// [`MAX_VARIANTS`] and the paths they have in common
+    pub(crate) fn new(
+        namespace: &str,
+        field: &str,
+        mut variants: Vec<(Bson, u64, CollectionSchema)>,
+    ) -> Self {
+        variants.sort_by_key(|variant| std::cmp::Reverse(variant.1));
+        let others = variants.split_off(variants.len().min(MAX_VARIANTS));
+        let variants = variants
+            .into_iter()
+            .map(|(value, documents, mut schema)| {
+                let value = value.into_relaxed_extjson();
+                schema.namespace = format!("{} ({} = {})", namespace, field, value);
+                Variant {
+                    value,
+                    documents,
+                    schema,
+                }
+            })
+            .collect::<Vec<_>>();
+        let common = match variants.split_first() {
+            Some((first, rest)) => first
+                .schema
+                .fields
+                .iter()
+                .filter(|(path, field)| {
+                    field.count > 0
+                        && rest.iter().all(|variant| {
+                            variant
+                                .schema
+                                .fields
+                                .get(*path)
+                                .is_some_and(|field| field.count > 0)
+                        })
+                })
+                .map(|(path, _)| path.clone())
+                .collect(),
+            None => Vec::new(),
+        };
+        Variants {
+            field: field.to_string(),
+            variants,
+            common,
+            other_documents: others.iter().map(|(_, documents, _)| documents).sum(),
+            other_values: others.len() as u64,
+        }
+    }
+
+    /// The note about the documents left out, if any were
+    pub(crate) fn note(&self) -> Option<String> {
+        (self.other_values > 0).then(|| {
+            format!(
+                "{} documents hold {} other values of {}, which aren't broken down",
+                self.other_documents, self.other_values, self.field
+            )
+        })
+    }
+}
+
+impl Versions {
+    /// Order `versions`, given as (value, documents, schema), oldest first and diff each
+    /// against the one before it
+    pub(crate) fn new(
+        namespace: &str,
+        field: &str,
+        mut versions: Vec<(Bson, u64, CollectionSchema)>,
+    ) -> Self {
+        versions.sort_by_key(|version| std::cmp::Reverse(version.1));
+        let others = versions.split_off(versions.len().min(MAX_VARIANTS));
+        versions.sort_by(|a, b| version_order(&a.0, &b.0));
+        let versions = versions
+            .into_iter()
+            .map(|(value, documents, mut schema)| {
+                let value = value.into_relaxed_extjson();
+                schema.namespace = format!("{} ({} = {})", namespace, field, value);
+                Variant {
+                    value,
+                    documents,
+                    schema,
+                }
+            })
+            .collect::<Vec<_>>();
+        let changes = versions
+            .windows(2)
+            .map(|pair| VersionChange {
+                from: pair[0].value.clone(),
+                to: pair[1].value.clone(),
+                diff: pair[0].schema.diff(&pair[1].schema),
+            })
+            .collect();
+        Versions {
+            field: field.to_string(),
+            versions,
+            changes,
+            other_documents: others.iter().map(|(_, documents, _)| documents).sum(),
+            other_values: others.len() as u64,
+        }
+    }
+
+    /// The note about the documents left out, if any were
+    pub(crate) fn note(&self) -> Option<String> {
+        (self.other_values > 0).then(|| {
+            format!(
+                "{} documents hold {} other versions in {}, which aren't compared",
+                self.other_documents, self.other_values, self.field
+            )
+        })
+    }
+}
+
+/// Versions oldest first: null (the field missing), numbers, then strings compared a run of
+/// digits or non-digits at a time
+fn version_order(a: &Bson, b: &Bson) -> Ordering {
+    let number = |value: &Bson| match value {
+        Bson::Int32(n) => Some(f64::from(*n)),
+        Bson::Int64(n) => Some(*n as f64),
+        Bson::Double(n) => Some(*n),
+        _ => None,
+    };
+    let rank = |value: &Bson| match value {
+        Bson::Null => 0,
+        _ if number(value).is_some() => 1,
+        Bson::String(_) => 2,
+        _ => 3,
+    };
+    match (a, b) {
+        (Bson::String(a), Bson::String(b)) => natural_order(a, b),
+        _ => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => rank(a).cmp(&rank(b)).then_with(|| {
+                let json = |value: &Bson| value.clone().into_relaxed_extjson().to_string();
+                json(a).cmp(&json(b))
+            }),
+        },
+    }
+}
+
+/// `a` against `b` with their runs of digits compared as numbers
+fn natural_order(a: &str, b: &str) -> Ordering {
+    let runs = |s: &str| {
+        let mut runs = Vec::<(bool, String)>::new();
+        for c in s.chars() {
+            let digit = c.is_ascii_digit();
+            match runs.last_mut() {
+                Some((run_digits, run)) if *run_digits == digit => run.push(c),
+                _ => runs.push((digit, c.to_string())),
+            }
+        }
+        runs
+    };
+    let (a, b) = (runs(a), runs(b));
+    for ((a_digits, a), (b_digits, b)) in a.iter().zip(&b) {
+        let order = if *a_digits && *b_digits {
+            let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+            a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+        } else {
+            a.cmp(b)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+impl CollectionSchema {
+    /// With variants, what to render in place of the union: the schema cut down to the fields
+    /// every variant has, then each variant cut down to its own fields and their parents
+    pub(crate) fn variant_sections(&self) -> Option<Vec<CollectionSchema>> {
+        let variants = self.variants.as_ref()?;
+        let common = variants.common.iter().cloned().collect::<BTreeSet<_>>();
+        let mut sections = vec![self.restricted(
+            format!("{} (common to every {})", self.namespace, variants.field),
+            &common,
+        )];
+        for variant in &variants.variants {
+            let mut own = BTreeSet::new();
+            for path in variant.schema.fields.keys() {
+                if !common.contains(path) {
+                    own.extend(ancestors(path).map(str::to_string));
+                }
+            }
+            sections.push(
+                variant
+                    .schema
+                    .restricted(variant.schema.namespace.clone(), &own),
+            );
+        }
+        Some(sections)
+    }
+
+    /// A copy named `namespace` holding only the fields in `paths`
+    fn restricted(&self, namespace: String, paths: &BTreeSet<String>) -> CollectionSchema {
+        let mut schema = self.clone();
+        schema.namespace = namespace;
+        schema.variants = None;
+        schema.versions = None;
+        schema.shards = None;
+        schema.indexes = None;
+        schema.meta = None;
+        // the intervals are for the whole sample, not the variant's share of it
+        schema.confidence = None;
+        schema.fields.retain(|path, _| paths.contains(path));
+        for field in schema.fields.values_mut() {
+            field.presence_interval = None;
+        }
+        schema
+            .anomalies
+            .retain(|anomaly| paths.contains(&anomaly.path));
+        if let Some(privacy) = &mut schema.privacy {
+            privacy.retain(|finding| paths.contains(&finding.path));
+        }
+        schema
+            .rare_fields
+            .retain(|field| paths.contains(&field.path));
+        if let Some(issues) = &mut schema.field_names {
+            issues.retain(|issue| paths.contains(&issue.path));
+        }
+        if let Some(sizes) = &mut schema.sizes {
+            sizes.fields.retain(|size| paths.contains(&size.path));
+        }
+        if let Some(evolution) = &mut schema.evolution {
+            evolution.fields.retain(|path, _| paths.contains(path));
+        }
+        schema
+    }
+}
+
+#[cfg(feature = "driver")]
+impl SchemaAnalyzer {
+    /// Analyze the documents holding each of the most common values of `field` on their own,
+    /// among those a sample of `sample_size` (or all of them) holds
+    pub(crate) async fn analyze_variants(
+        &self,
+        field: &str,
+        sample_size: Option<u64>,
+    ) -> Result<Variants> {
+        let (partitions, other_documents, other_values) =
+            self.partition(field, sample_size).await?;
+        let mut variants = Variants::new(&self.ns.to_string(), field, partitions);
+        variants.other_documents = other_documents;
+        variants.other_values = other_values;
+        Ok(variants)
+    }
+
+    /// Analyze the documents of each of the most common versions in `field` on their own, and
+    /// diff each version against the one before it
+    pub(crate) async fn analyze_versions(
+        &self,
+        field: &str,
+        sample_size: Option<u64>,
+    ) -> Result<Versions> {
+        let (partitions, other_documents, other_values) =
+            self.partition(field, sample_size).await?;
+        let mut versions = Versions::new(&self.ns.to_string(), field, partitions);
+        versions.other_documents = other_documents;
+        versions.other_values = other_values;
+        Ok(versions)
+    }
+
+    /// The schema of the documents holding each of the [`MAX_VARIANTS`] most common values of
+    /// `field`, as (value, documents, schema), then how many documents hold other values and
+    /// how many other values there are
+    async fn partition(
+        &self,
+        field: &str,
+        sample_size: Option<u64>,
+    ) -> Result<(Vec<(Bson, u64, CollectionSchema)>, u64, u64)> {
+        let mut pipeline = match sample_size {
+            Some(size) => profile_pipeline(self.options.filter.as_ref(), size),
+            None => self
+                .options
+                .filter
+                .iter()
+                .map(|filter| doc! { "$match": filter.clone() })
+                .collect(),
+        };
+        pipeline.extend([
+            // a missing field groups with null, as `{ field: null }` matches both
+            doc! { "$group": { "_id": format!("${}", field), "count": { "$sum": 1 } } },
+            doc! {
+                "$facet": {
+                    "top": [
+                        { "$sort": { "count": -1, "_id": 1 } },
+                        { "$limit": MAX_VARIANTS as i64 }
+                    ],
+                    "others": [
+                        { "$sort": { "count": -1, "_id": 1 } },
+                        { "$skip": MAX_VARIANTS as i64 },
+                        { "$group": { "_id": null, "count": { "$sum": "$count" }, "values": { "$sum": 1 } } }
+                    ]
+                }
+            },
+        ]);
+        let mut cursor = self
+            .collection()
+            .aggregate(pipeline, self.aggregate_options()?)
+            .await?;
+        let result = cursor.try_next().await?.unwrap_or_default();
+        let malformed = || AnalyzerError::MalformedResult("expected variant counts".to_string());
+
+        let mut partitions = Vec::new();
+        for top in result.get_array("top").map_err(|_| malformed())? {
+            let top = top.as_document().ok_or_else(malformed)?;
+            let value = top.get("_id").cloned().unwrap_or(Bson::Null);
+            let mut options = self.options.clone();
+            options.group_by = None;
+            options.version_field = None;
+            options.filter = Some(match &self.options.filter {
+                Some(filter) => doc! { "$and": [filter.clone(), { field: value.clone() }] },
+                None => doc! { field: value.clone() },
+            });
+            let analyzer =
+                SchemaAnalyzer::with_options(self.client.clone(), self.ns.clone(), options);
+            let schema = Box::pin(analyzer.analyze()).await?;
+            partitions.push((value, count_at(top, "count")?, schema));
+        }
+        let (mut other_documents, mut other_values) = (0, 0);
+        if let Some(others) = result
+            .get_array("others")
+            .ok()
+            .and_then(|others| others.first())
+            .and_then(Bson::as_document)
+        {
+            other_documents = count_at(others, "count")?;
+            other_values = count_at(others, "values")?;
+        }
+        Ok((partitions, other_documents, other_values))
+    }
+}
+
+/// The value of the discriminator at the dotted `path` of `doc`, null if it's missing
+pub(crate) fn discriminator(doc: &Document, path: &str) -> Bson {
+    let mut value = None;
+    let mut current = Some(doc);
+    for key in path.split('.') {
+        value = current.and_then(|doc| doc.get(key));
+        current = value.and_then(Bson::as_document);
+    }
+    value.cloned().unwrap_or(Bson::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::AnalysisOptions;
+
+    const EVENTS: &str = "{\"type\": \"click\", \"at\": 1, \"x\": 10}\n\
+                          {\"type\": \"click\", \"at\": 2, \"x\": 20}\n\
+                          {\"type\": \"view\", \"at\": 3, \"page\": \"/\"}\n";
+
+    #[test]
+    fn infers_a_schema_per_discriminator_value() {
+        let options = AnalysisOptions {
+            group_by: Some("type".to_string()),
+            ..AnalysisOptions::default()
+        };
+        let schema =
+            CollectionSchema::from_ndjson(EVENTS.as_bytes(), "app.events", &options).unwrap();
+        let variants = schema.variants.as_ref().unwrap();
+        assert_eq!(variants.variants[0].value, json!("click"));
+        assert_eq!(variants.variants[0].documents, 2);
+        assert_eq!(
+            variants.variants[0].schema.namespace,
+            "app.events (type = \"click\")"
+        );
+        assert!(!variants.variants[0].schema.fields.contains_key("page"));
+        assert_eq!(variants.common, ["at", "type"]);
+        assert_eq!(variants.note(), None);
+
+        let sections = schema.variant_sections().unwrap();
+        let paths = |section: &CollectionSchema| section.fields.keys().cloned().collect::<Vec<_>>();
+        assert_eq!(paths(&sections[0]), ["at", "type"]);
+        assert_eq!(paths(&sections[1]), ["x"]);
+        assert_eq!(paths(&sections[2]), ["page"]);
+    }
+
+    #[test]
+    fn diffs_versions_in_version_order() {
+        let options = AnalysisOptions {
+            version_field: Some("v".to_string()),
+            ..AnalysisOptions::default()
+        };
+        let ndjson = "{\"v\": \"v10\", \"c\": 1}\n{\"v\": \"v2\", \"b\": 1}\n{\"a\": 1}\n";
+        let schema = CollectionSchema::from_ndjson(ndjson.as_bytes(), "db.coll", &options).unwrap();
+        let versions = schema.versions.unwrap();
+        let order = versions
+            .versions
+            .iter()
+            .map(|version| version.value.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(order, [json!(null), json!("v2"), json!("v10")]);
+        let last = &versions.changes[1].diff;
+        assert_eq!(
+            (&last.added, &last.removed),
+            (&vec!["c".to_string()], &vec!["b".to_string()])
+        );
+    }
+
+    #[test]
+    fn orders_versions_numbers_before_strings() {
+        let mut values = vec![
+            Bson::String("1.10".to_string()),
+            Bson::Int32(3),
+            Bson::String("1.9".to_string()),
+            Bson::Null,
+            Bson::Double(2.5),
+        ];
+        values.sort_by(version_order);
+        assert_eq!(
+            values,
+            [
+                Bson::Null,
+                Bson::Double(2.5),
+                Bson::Int32(3),
+                Bson::String("1.9".to_string()),
+                Bson::String("1.10".to_string()),
+            ]
+        );
+        assert_eq!(natural_order("a007", "a7"), Ordering::Equal);
+        assert_eq!(
+            discriminator(&doc! { "meta": { "kind": "x" } }, "meta.kind"),
+            Bson::String("x".to_string())
+        );
+        assert_eq!(discriminator(&doc! { "meta": 1 }, "meta.kind"), Bson::Null);
+    }
+}
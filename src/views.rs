@@ -0,0 +1,92 @@
+//! Views: sampled through the pipeline defining them, run on the collection they're a view on
+//! when the documents are read client-side
+
+use bson::Document;
+#[cfg(feature = "driver")]
+use mongodb::results::{CollectionSpecification, CollectionType};
+use serde::{Deserialize, Serialize};
+
+/// What a view shows: the documents of `view_on` passed through `pipeline`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ViewDefinition {
+    /// The collection, or view, the view is defined on, in the same database
+    pub view_on: String,
+    #[serde(default)]
+    pub pipeline: Vec<Document>,
+}
+
+impl ViewDefinition {
+    /// e.g. "on `orders`, through 3 stages", or just "on `orders`" without any, as an
+    /// anonymized view has
+    pub fn describe(&self) -> String {
+        let stages = match self.pipeline.len() {
+            0 => return format!("on `{}`", self.view_on),
+            1 => "stage",
+            _ => "stages",
+        };
+        format!(
+            "on `{}`, through {} {}",
+            self.view_on,
+            self.pipeline.len(),
+            stages
+        )
+    }
+
+    /// The definition of the view `specification` lists, if it's a view
+    #[cfg(feature = "driver")]
+    pub(crate) fn of(specification: &CollectionSpecification) -> Option<Self> {
+        if !matches!(specification.collection_type, CollectionType::View) {
+            return None;
+        }
+        Some(ViewDefinition {
+            view_on: specification.options.view_on.clone()?,
+            pipeline: specification.options.pipeline.clone().unwrap_or_default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bson::doc;
+
+    use super::*;
+
+    #[test]
+    fn describes_the_view_by_its_source_and_stages() {
+        let view = |pipeline: Vec<Document>| ViewDefinition {
+            view_on: "orders".to_string(),
+            pipeline,
+        };
+        assert_eq!(view(vec![]).describe(), "on `orders`");
+        assert_eq!(
+            view(vec![doc! { "$match": {} }]).describe(),
+            "on `orders`, through 1 stage"
+        );
+        assert_eq!(
+            view(vec![doc! { "$match": {} }, doc! { "$project": { "a": 1 } }]).describe(),
+            "on `orders`, through 2 stages"
+        );
+    }
+
+    #[cfg(feature = "driver")]
+    #[test]
+    fn reads_the_definition_of_views_only() {
+        let specification = |kind: &str| -> CollectionSpecification {
+            bson::from_document(doc! {
+                "name": "recent",
+                "type": kind,
+                "options": { "viewOn": "orders", "pipeline": [{ "$limit": 10 }] },
+                "info": { "readOnly": kind == "view" },
+            })
+            .unwrap()
+        };
+        assert_eq!(
+            ViewDefinition::of(&specification("view")),
+            Some(ViewDefinition {
+                view_on: "orders".to_string(),
+                pipeline: vec![doc! { "$limit": 10 }],
+            })
+        );
+        assert_eq!(ViewDefinition::of(&specification("collection")), None);
+    }
+}
@@ -0,0 +1,45 @@
+//! wasm-bindgen exports for in-browser tools, inferring schemas from pasted documents with the
+//! same logic as the command line tool. Build with `--no-default-features --features wasm`
+//! for `wasm32-unknown-unknown`
+
+use wasm_bindgen::prelude::*;
+
+use crate::{AnalysisOptions, CollectionSchema};
+
+/// Infer the schema of Extended JSON documents, given one per line or as a JSON array.
+/// `options` is a JSON object of [`AnalysisOptions`] keys, or empty for the defaults. Returns
+/// the schema as JSON
+#[wasm_bindgen(js_name = analyzeDocuments)]
+pub fn analyze_documents(
+    documents: &str,
+    namespace: &str,
+    options: &str,
+) -> Result<String, JsError> {
+    let options = match options.trim() {
+        "" => AnalysisOptions::default(),
+        options => serde_json::from_str(options)
+            .map_err(|e| JsError::new(&format!("invalid options: {}", e)))?,
+    };
+    let lines = match documents.trim_start().starts_with('[') {
+        true => serde_json::from_str::<Vec<serde_json::Value>>(documents)?
+            .iter()
+            .map(|document| document.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        false => documents.to_string(),
+    };
+    let schema = CollectionSchema::from_ndjson(lines.as_bytes(), namespace, &options)?;
+    Ok(serde_json::to_string(&schema)?)
+}
+
+/// Render a schema returned by [`analyze_documents`] as `markdown`, `html` or `csv`
+#[wasm_bindgen(js_name = renderSchema)]
+pub fn render_schema(schema: &str, format: &str) -> Result<String, JsError> {
+    let schema = serde_json::from_str::<CollectionSchema>(schema)?;
+    match format {
+        "markdown" => Ok(schema.to_markdown()),
+        "html" => Ok(schema.to_html()),
+        "csv" => Ok(schema.to_csv()),
+        other => Err(JsError::new(&format!("unknown format {:?}", other))),
+    }
+}
@@ -0,0 +1,57 @@
+use bson::doc;
+use futures::stream::TryStreamExt;
+use mongodb::options::{ChangeStreamOptions, FullDocumentType};
+
+use crate::{infer::SchemaBuilder, CollectionSchema, Discovery, Result, SchemaAnalyzer};
+
+impl SchemaAnalyzer {
+    /// Infer the collection's schema, then follow its change stream, folding every inserted,
+    /// replaced or updated document into the schema. `on_discovery` is called with the builder
+    /// whenever a document brings a field or type the schema hasn't seen.
+    ///
+    /// Runs until the change stream ends (e.g. the collection is dropped) or fails
+    pub async fn watch<F>(&self, on_discovery: F) -> Result<SchemaBuilder>
+    where
+        F: FnMut(&Discovery, &SchemaBuilder),
+    {
+        let initial = self.analyze().await?;
+        self.watch_from(&initial, on_discovery).await
+    }
+
+    /// Like [`SchemaAnalyzer::watch`], continuing from a schema already inferred
+    pub async fn watch_from<F>(
+        &self,
+        initial: &CollectionSchema,
+        mut on_discovery: F,
+    ) -> Result<SchemaBuilder>
+    where
+        F: FnMut(&Discovery, &SchemaBuilder),
+    {
+        let mut builder =
+            SchemaBuilder::from_schema(initial, self.options.max_depth).limited_by(&self.options);
+
+        let collection = self.collection();
+        // updates only describe the change, so ask for the whole document after it
+        let options = ChangeStreamOptions::builder()
+            .full_document(Some(FullDocumentType::UpdateLookup))
+            .build();
+        let pipeline = [doc! {
+            "$match": { "operationType": { "$in": ["insert", "replace", "update"] } }
+        }];
+        let mut stream = collection.watch(pipeline, options).await?;
+
+        while let Some(event) = stream.try_next().await? {
+            // the document may have been deleted before the update lookup ran
+            let Some(doc) = event.full_document else {
+                continue;
+            };
+            for discovery in builder.add(&doc) {
+                if !self.options.includes(discovery.path()) {
+                    continue;
+                }
+                on_discovery(&discovery, &builder);
+            }
+        }
+        Ok(builder)
+    }
+}
@@ -0,0 +1,237 @@
+//! Posting detected drift to a webhook, for Slack-compatible and generic endpoints
+
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::{AnalyzerError, Anomaly, Discovery, Result, SchemaDiff, TypeChange};
+
+/// What's posted when a namespace drifts: the changes since the previous run or the initial
+/// sample, and anomalies not seen before. `text` summarizes them in a line, which is what
+/// Slack-compatible endpoints show
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DriftNotification {
+    pub text: String,
+    pub namespace: String,
+    /// When the drift was detected, in RFC 3339
+    pub detected_at: String,
+    pub diff: SchemaDiff,
+    pub anomalies: Vec<Anomaly>,
+}
+
+impl DriftNotification {
+    pub fn new(namespace: impl Into<String>, diff: SchemaDiff, anomalies: Vec<Anomaly>) -> Self {
+        let namespace = namespace.into();
+        let mut parts = Vec::new();
+        for (count, what) in [
+            (diff.added.len(), "added"),
+            (diff.removed.len(), "removed"),
+            (diff.changed.len(), "changed in type"),
+        ] {
+            if count > 0 {
+                parts.push(format!("{} {} {}", count, plural(count, "field"), what));
+            }
+        }
+        if !anomalies.is_empty() {
+            let count = anomalies.len();
+            parts.push(format!("{} new {}", count, plural(count, "anomaly")));
+        }
+        DriftNotification {
+            text: format!("Schema drift in {}: {}", namespace, parts.join(", ")),
+            namespace,
+            // infallible for the current time
+            detected_at: bson::DateTime::now().try_to_rfc3339_string().unwrap(),
+            diff,
+            anomalies,
+        }
+    }
+
+    /// The drift a watch discovered
+    pub fn from_discovery(namespace: impl Into<String>, discovery: &Discovery) -> Self {
+        let mut diff = SchemaDiff::default();
+        match discovery {
+            Discovery::NewField { path, .. } => diff.added.push(path.clone()),
+            Discovery::NewType { path, ty } => diff.changed.push(TypeChange {
+                path: path.clone(),
+                added_types: vec![ty.clone()],
+                removed_types: Vec::new(),
+            }),
+        }
+        Self::new(namespace, diff, Vec::new())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.diff.is_empty() && self.anomalies.is_empty()
+    }
+}
+
+/// An endpoint notified of drift with a POST, retried with a backoff when it can't be reached
+/// or answers with a server error
+#[derive(Debug, Clone)]
+pub struct Webhook {
+    url: String,
+    template: Option<String>,
+    retries: u32,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    /// A webhook giving up on a post after 10 seconds, so an endpoint that never answers
+    /// counts as a failure to retry
+    pub fn new(url: impl Into<String>) -> Self {
+        // only fails if the TLS backend can't be loaded, as `Client::new` would panic
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(10))
+            .build()
+            .unwrap();
+        Webhook {
+            url: url.into(),
+            template: None,
+            retries: 3,
+            client,
+        }
+    }
+
+    /// Post `template` instead of the notification's JSON, with `{{text}}`, `{{namespace}}`,
+    /// `{{detected_at}}`, `{{diff}}`, `{{anomalies}}` replaced by the notification's values
+    /// as JSON, and `{{summary}}` by the diff as `schema-analyzer diff` prints it, e.g.
+    /// `{"text": {{text}}, "channel": "#data"}`
+    pub fn with_template(mut self, template: impl Into<String>) -> Self {
+        self.template = Some(template.into());
+        self
+    }
+
+    /// Retry a failed post this many times. Defaults to 3
+    pub fn with_retries(mut self, retries: u32) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    /// What's posted for `notification`
+    pub fn body(&self, notification: &DriftNotification) -> String {
+        let Some(template) = &self.template else {
+            return serde_json::to_string(notification).unwrap();
+        };
+        let n = notification;
+        let values = [
+            ("{{text}}", serde_json::to_string(&n.text)),
+            ("{{namespace}}", serde_json::to_string(&n.namespace)),
+            ("{{detected_at}}", serde_json::to_string(&n.detected_at)),
+            ("{{diff}}", serde_json::to_string(&n.diff)),
+            ("{{anomalies}}", serde_json::to_string(&n.anomalies)),
+            ("{{summary}}", serde_json::to_string(&n.diff.to_string())),
+        ];
+        // infallible: the values are all plain data
+        values
+            .into_iter()
+            .fold(template.clone(), |body, (placeholder, value)| {
+                body.replace(placeholder, &value.unwrap())
+            })
+    }
+
+    /// Post `notification`, retrying transient failures: half a second later, doubling each
+    /// time up to 30 seconds
+    pub async fn notify(&self, notification: &DriftNotification) -> Result<()> {
+        let body = self.body(notification);
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .client
+                .post(&self.url)
+                .header("content-type", "application/json")
+                .body(body.clone())
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match response {
+                Ok(_) => return Ok(()),
+                Err(e) if attempt < self.retries && is_transient(&e) => {
+                    attempt += 1;
+                    let delay = (Duration::from_millis(500) * 2u32.saturating_pow(attempt - 1))
+                        .min(Duration::from_secs(30));
+                    tokio::time::sleep(delay).await;
+                }
+                Err(e) => return Err(AnalyzerError::Webhook(e)),
+            }
+        }
+    }
+}
+
+/// Whether `error` is worth retrying: the endpoint couldn't be reached, or answered that it
+/// failed or is rate limiting
+fn is_transient(error: &reqwest::Error) -> bool {
+    match error.status() {
+        Some(status) => status.is_server_error() || status.as_u16() == 429,
+        None => error.is_connect() || error.is_timeout() || error.is_request(),
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    match (count, noun.strip_suffix('y')) {
+        (1, _) => noun.to_string(),
+        (_, Some(stem)) => format!("{}ies", stem),
+        (_, None) => format!("{}s", noun),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnomalyKind, BsonType, Severity};
+
+    #[test]
+    fn summarizes_the_drift_in_a_line() {
+        let diff = SchemaDiff {
+            added: vec!["a".to_string(), "b".to_string()],
+            removed: vec!["c".to_string()],
+            ..SchemaDiff::default()
+        };
+        let anomaly = Anomaly {
+            path: "a".to_string(),
+            severity: Severity::Error,
+            kind: AnomalyKind::ConflictingTypes {
+                types: vec![BsonType::String, BsonType::Int],
+            },
+        };
+        let notification = DriftNotification::new("db.coll", diff, vec![anomaly]);
+        assert_eq!(
+            notification.text,
+            "Schema drift in db.coll: 2 fields added, 1 field removed, 1 new anomaly"
+        );
+        assert!(!notification.is_empty());
+
+        let discovery = Discovery::NewType {
+            path: "a".to_string(),
+            ty: "string".to_string(),
+        };
+        let notification = DriftNotification::from_discovery("db.coll", &discovery);
+        assert_eq!(notification.diff.changed[0].added_types, ["string"]);
+        assert_eq!(
+            notification.text,
+            "Schema drift in db.coll: 1 field changed in type"
+        );
+    }
+
+    #[test]
+    fn fills_the_template_with_the_notification_as_json() {
+        let discovery = Discovery::NewField {
+            path: "a".to_string(),
+            ty: "int".to_string(),
+        };
+        let notification = DriftNotification::from_discovery("db.\"coll\"", &discovery);
+        let webhook = Webhook::new("http://localhost");
+        let body: serde_json::Value = serde_json::from_str(&webhook.body(&notification)).unwrap();
+        assert_eq!(body["namespace"], "db.\"coll\"");
+        assert_eq!(body["diff"]["added"][0], "a");
+
+        let webhook = webhook.with_template(r#"{"text": {{text}}, "ns": {{namespace}}}"#);
+        let body: serde_json::Value = serde_json::from_str(&webhook.body(&notification)).unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({
+                "text": "Schema drift in db.\"coll\": 1 field added",
+                "ns": "db.\"coll\"",
+            })
+        );
+    }
+}
@@ -0,0 +1,218 @@
+//! How many top-level keys the sampled documents have. Documents with hundreds or thousands
+//! are usually storing data, like user ids or dates, in their field names
+
+use std::collections::HashMap;
+
+use bson::Document;
+use serde::{Deserialize, Serialize};
+
+use crate::stats::NumericSummary;
+
+/// Documents with more top-level keys than this are reported as wide
+pub(crate) const WIDE_DOCUMENT: u64 = 200;
+
+/// Most keys of each wide document generalized into [`KeyPattern`]s
+pub(crate) const PATTERN_KEYS: usize = 100;
+
+/// How many of the most common key patterns are reported
+const TOP_PATTERNS: usize = 5;
+
+/// How many top-level keys the sampled documents have
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DocumentWidths {
+    /// How many documents were counted
+    pub count: u64,
+    pub min: u64,
+    pub max: u64,
+    pub mean: f64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    /// How many documents have more than 200 top-level keys
+    pub wide: u64,
+    /// The shapes the keys of the wide documents most often take, most common first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_patterns: Vec<KeyPattern>,
+}
+
+/// Keys that differ only in their digits, ids or dates, e.g. `user_{n}` for `user_17` and
+/// `user_42`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyPattern {
+    pub pattern: String,
+    /// How many of the wide documents' keys take this shape
+    pub keys: u64,
+    /// One of them
+    pub example: String,
+}
+
+impl DocumentWidths {
+    /// The summary line, e.g. `min 4, median 12, p95 30, p99 250, max 4000, mean 21.5 keys`
+    pub(crate) fn describe(&self) -> String {
+        format!(
+            "min {}, median {}, p95 {}, p99 {}, max {}, mean {:.1} keys",
+            self.min, self.p50, self.p95, self.p99, self.max, self.mean
+        )
+    }
+
+    /// The warning about wide documents, if there are any
+    pub(crate) fn warning(&self) -> Option<String> {
+        (self.wide > 0).then(|| {
+            let patterns = self
+                .key_patterns
+                .iter()
+                .map(|p| format!("{} (e.g. {})", p.pattern, p.example))
+                .collect::<Vec<_>>();
+            let mut warning = format!(
+                "{} documents have over {} top-level keys, which usually means data is stored \
+                 in field names",
+                self.wide, WIDE_DOCUMENT
+            );
+            if !patterns.is_empty() {
+                warning.push_str(&format!("; their keys look like {}", patterns.join(", ")));
+            }
+            warning
+        })
+    }
+}
+
+/// Collects key counts into [`DocumentWidths`]
+#[derive(Debug, Clone, Default)]
+pub(crate) struct DocumentWidthSummary {
+    widths: NumericSummary,
+    wide: u64,
+    /// How many keys take each pattern, and the first of them
+    patterns: HashMap<String, (u64, String)>,
+}
+
+impl DocumentWidthSummary {
+    pub(crate) fn observe(&mut self, doc: &Document) {
+        self.observe_keys(doc.len() as u64, doc.keys().map(String::as_str));
+    }
+
+    /// Count a document with `width` top-level keys, generalizing `keys` if it is wide
+    pub(crate) fn observe_keys<'a>(&mut self, width: u64, keys: impl IntoIterator<Item = &'a str>) {
+        self.widths.observe_number(width as f64);
+        if width <= WIDE_DOCUMENT {
+            return;
+        }
+        self.wide += 1;
+        for key in keys.into_iter().take(PATTERN_KEYS) {
+            let pattern = key_pattern(key);
+            // keys without ids or numbers in them are ordinary fields
+            if pattern == key {
+                continue;
+            }
+            let (count, _) = self
+                .patterns
+                .entry(pattern)
+                .or_insert_with(|| (0, key.to_string()));
+            *count += 1;
+        }
+    }
+
+    /// The distribution, if any documents were counted
+    pub(crate) fn widths(&self) -> Option<DocumentWidths> {
+        let stats = self.widths.stats()?;
+        let mut patterns = self
+            .patterns
+            .iter()
+            .map(|(pattern, (keys, example))| KeyPattern {
+                pattern: pattern.clone(),
+                keys: *keys,
+                example: example.clone(),
+            })
+            .collect::<Vec<_>>();
+        patterns.sort_by(|a, b| b.keys.cmp(&a.keys).then_with(|| a.pattern.cmp(&b.pattern)));
+        patterns.truncate(TOP_PATTERNS);
+        Some(DocumentWidths {
+            count: stats.count,
+            min: stats.min as u64,
+            max: stats.max as u64,
+            mean: stats.mean,
+            p50: stats.p50 as u64,
+            p95: stats.p95 as u64,
+            p99: stats.p99 as u64,
+            wide: self.wide,
+            key_patterns: patterns,
+        })
+    }
+}
+
+/// `key` with its ObjectIds, UUIDs and runs of digits replaced by `{objectId}`, `{uuid}` and
+/// `{n}`
+fn key_pattern(key: &str) -> String {
+    let is_hex = |s: &str| s.bytes().all(|c| c.is_ascii_hexdigit());
+    if key.len() == 24 && is_hex(key) {
+        return "{objectId}".to_string();
+    }
+    let groups = key.split('-').map(str::len).collect::<Vec<_>>();
+    if groups == [8, 4, 4, 4, 12] && is_hex(&key.replace('-', "")) {
+        return "{uuid}".to_string();
+    }
+    let mut pattern = String::new();
+    let mut digits = false;
+    for c in key.chars() {
+        if c.is_ascii_digit() {
+            if !digits {
+                pattern.push_str("{n}");
+            }
+            digits = true;
+        } else {
+            pattern.push(c);
+            digits = false;
+        }
+    }
+    pattern
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generalizes_ids_and_numbers_in_keys() {
+        assert_eq!(key_pattern("user_17"), "user_{n}");
+        assert_eq!(key_pattern("2024-01-31"), "{n}-{n}-{n}");
+        assert_eq!(key_pattern("65f0c0ffee00000000000001"), "{objectId}");
+        assert_eq!(
+            key_pattern("67e55044-10b1-426f-9247-bb680e5fe0c8"),
+            "{uuid}"
+        );
+        assert_eq!(key_pattern("name"), "name");
+    }
+
+    #[test]
+    fn reports_the_key_patterns_of_wide_documents() {
+        let mut summary = DocumentWidthSummary::default();
+        summary.observe(&bson::doc! { "a": 1, "b": 2 });
+        let mut wide = Document::new();
+        wide.insert("name", "x");
+        for i in 0..WIDE_DOCUMENT {
+            wide.insert(format!("user_{}", i), true);
+        }
+        summary.observe(&wide);
+
+        let widths = summary.widths().unwrap();
+        assert_eq!(
+            (widths.count, widths.min, widths.max),
+            (2, 2, WIDE_DOCUMENT + 1)
+        );
+        assert_eq!(widths.wide, 1);
+        // only the first keys are looked at, and `name` is an ordinary field
+        assert_eq!(
+            widths.key_patterns,
+            [KeyPattern {
+                pattern: "user_{n}".to_string(),
+                keys: PATTERN_KEYS as u64 - 1,
+                example: "user_0".to_string(),
+            }]
+        );
+        let warning = widths.warning().unwrap();
+        assert!(
+            warning.ends_with("their keys look like user_{n} (e.g. user_0)"),
+            "{}",
+            warning
+        );
+    }
+}